@@ -0,0 +1,95 @@
+#[macro_use]
+extern crate criterion;
+extern crate rml_amf0;
+
+use criterion::{black_box, Criterion};
+use rml_amf0::{deserialize, serialize, Amf0Value, ObjectProperties};
+use std::io::Cursor;
+
+/// The values of a typical `connect` command sent by a publishing client
+fn connect_command() -> Vec<Amf0Value> {
+    let mut properties = ObjectProperties::new();
+    properties.insert("app".to_string(), Amf0Value::from("live"));
+    properties.insert("type".to_string(), Amf0Value::from("nonprivate"));
+    properties.insert(
+        "flashVer".to_string(),
+        Amf0Value::from("FMLE/3.0 (compatible; FMSc/1.0)"),
+    );
+    properties.insert(
+        "swfUrl".to_string(),
+        Amf0Value::from("rtmp://localhost/live"),
+    );
+    properties.insert(
+        "tcUrl".to_string(),
+        Amf0Value::from("rtmp://localhost/live"),
+    );
+    properties.insert("fpad".to_string(), Amf0Value::Boolean(false));
+    properties.insert("capabilities".to_string(), Amf0Value::Number(239.0));
+    properties.insert("audioCodecs".to_string(), Amf0Value::Number(3575.0));
+    properties.insert("videoCodecs".to_string(), Amf0Value::Number(252.0));
+    properties.insert("videoFunction".to_string(), Amf0Value::Number(1.0));
+    properties.insert("objectEncoding".to_string(), Amf0Value::Number(0.0));
+
+    vec![
+        Amf0Value::from("connect"),
+        Amf0Value::Number(1.0),
+        Amf0Value::Object(properties),
+    ]
+}
+
+/// The values of a typical `@setDataFrame` data message sent by an encoder
+fn on_metadata() -> Vec<Amf0Value> {
+    let mut properties = ObjectProperties::new();
+    properties.insert("duration".to_string(), Amf0Value::Number(0.0));
+    properties.insert("fileSize".to_string(), Amf0Value::Number(0.0));
+    properties.insert("width".to_string(), Amf0Value::Number(1920.0));
+    properties.insert("height".to_string(), Amf0Value::Number(1080.0));
+    properties.insert("videocodecid".to_string(), Amf0Value::from("avc1"));
+    properties.insert("videodatarate".to_string(), Amf0Value::Number(2500.0));
+    properties.insert("framerate".to_string(), Amf0Value::Number(30.0));
+    properties.insert("audiocodecid".to_string(), Amf0Value::from("mp4a"));
+    properties.insert("audiodatarate".to_string(), Amf0Value::Number(160.0));
+    properties.insert("audiosamplerate".to_string(), Amf0Value::Number(48000.0));
+    properties.insert("audiosamplesize".to_string(), Amf0Value::Number(16.0));
+    properties.insert("audiochannels".to_string(), Amf0Value::Number(2.0));
+    properties.insert("stereo".to_string(), Amf0Value::Boolean(true));
+    properties.insert("2.1".to_string(), Amf0Value::Boolean(false));
+    properties.insert("3.1".to_string(), Amf0Value::Boolean(false));
+    properties.insert("4.0".to_string(), Amf0Value::Boolean(false));
+    properties.insert("4.1".to_string(), Amf0Value::Boolean(false));
+    properties.insert("5.1".to_string(), Amf0Value::Boolean(false));
+    properties.insert("7.1".to_string(), Amf0Value::Boolean(false));
+    properties.insert(
+        "encoder".to_string(),
+        Amf0Value::from("obs-output module (libobs version 27.2.4)"),
+    );
+
+    vec![
+        Amf0Value::from("@setDataFrame"),
+        Amf0Value::from("onMetaData"),
+        Amf0Value::Object(properties),
+    ]
+}
+
+fn bench_payload(c: &mut Criterion, name: &str, values: Vec<Amf0Value>) {
+    let bytes = serialize(&values).unwrap();
+
+    c.bench_function(&format!("serialize {}", name), |b| {
+        b.iter(|| serialize(black_box(&values)).unwrap())
+    });
+
+    c.bench_function(&format!("deserialize {}", name), |b| {
+        b.iter(|| deserialize(&mut Cursor::new(black_box(&bytes[..]))).unwrap())
+    });
+}
+
+fn connect_benchmarks(c: &mut Criterion) {
+    bench_payload(c, "connect command", connect_command());
+}
+
+fn metadata_benchmarks(c: &mut Criterion) {
+    bench_payload(c, "onMetaData", on_metadata());
+}
+
+criterion_group!(benches, connect_benchmarks, metadata_benchmarks);
+criterion_main!(benches);
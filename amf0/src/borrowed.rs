@@ -0,0 +1,371 @@
+//! A borrowed representation of AMF0 values, for reading values without copying strings out of
+//! the input buffer.
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::str;
+use deserialization::{check_element_count, check_string_length, create_date};
+use deserialization::{DeserializationLimits, Input};
+use errors::Amf0DeserializationError;
+use markers;
+use {Amf0Value, ObjectProperties};
+
+/// An Amf0 value whose strings point into the buffer it was read from.
+///
+/// Reference markers are not resolved, since that would require copying the referenced value.
+/// They are instead returned as `Reference` values holding the index of the value they refer to.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Amf0ValueRef<'a> {
+    Number(f64),
+    Boolean(bool),
+    Utf8String(&'a str),
+    Object(Vec<(&'a str, Amf0ValueRef<'a>)>),
+    StrictArray(Vec<Amf0ValueRef<'a>>),
+    Null,
+    Undefined,
+    Date {
+        unix_time_millis: f64,
+        time_zone: i16,
+    },
+    XmlDocument(&'a str),
+    TypedObject {
+        class_name: &'a str,
+        properties: Vec<(&'a str, Amf0ValueRef<'a>)>,
+    },
+    Reference(u16),
+}
+
+impl<'a> Amf0ValueRef<'a> {
+    /// Returns the number if this is a number value
+    pub fn as_number(&self) -> Option<f64> {
+        match *self {
+            Amf0ValueRef::Number(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the string if this is a string value
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            Amf0ValueRef::Utf8String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Copies the value into an owned `Amf0Value`.  This fails for values that contain a
+    /// reference or a date that can't be represented as a `SystemTime`.
+    pub fn to_owned_value(&self) -> Result<Amf0Value, Amf0DeserializationError> {
+        let value = match *self {
+            Amf0ValueRef::Number(value) => Amf0Value::Number(value),
+            Amf0ValueRef::Boolean(value) => Amf0Value::Boolean(value),
+            Amf0ValueRef::Utf8String(value) => Amf0Value::Utf8String(value.to_string()),
+            Amf0ValueRef::Object(ref properties) => {
+                Amf0Value::Object(to_owned_properties(properties)?)
+            }
+            Amf0ValueRef::StrictArray(ref values) => Amf0Value::StrictArray(
+                values
+                    .iter()
+                    .map(|x| x.to_owned_value())
+                    .collect::<Result<_, _>>()?,
+            ),
+            Amf0ValueRef::Null => Amf0Value::Null,
+            Amf0ValueRef::Undefined => Amf0Value::Undefined,
+            Amf0ValueRef::Date {
+                unix_time_millis,
+                time_zone,
+            } => create_date(unix_time_millis, time_zone)?,
+            Amf0ValueRef::XmlDocument(value) => Amf0Value::XmlDocument(value.to_string()),
+            Amf0ValueRef::TypedObject {
+                class_name,
+                ref properties,
+            } => Amf0Value::TypedObject {
+                class_name: class_name.to_string(),
+                properties: to_owned_properties(properties)?,
+            },
+            Amf0ValueRef::Reference(index) => {
+                return Err(Amf0DeserializationError::InvalidReference {
+                    index: index as usize,
+                })
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+/// Reads Amf0 values one at a time out of a byte slice without copying any strings.  Values
+/// after the ones that are read are never parsed, so callers that only need the first few
+/// values (such as a command's name and transaction id) don't pay for the rest.
+///
+/// # Examples
+/// ```
+/// use rml_amf0::{serialize, Amf0Value, Amf0ValueRef, BorrowedDeserializer};
+///
+/// let input = vec![Amf0Value::Utf8String("connect".to_string()), Amf0Value::Number(1.0)];
+/// let bytes = serialize(&input).unwrap();
+///
+/// let mut deserializer = BorrowedDeserializer::new(&bytes);
+/// assert_eq!(deserializer.next_value().unwrap(), Some(Amf0ValueRef::Utf8String("connect")));
+/// assert_eq!(deserializer.next_value().unwrap(), Some(Amf0ValueRef::Number(1.0)));
+/// assert_eq!(deserializer.next_value().unwrap(), None);
+/// ```
+pub struct BorrowedDeserializer<'a> {
+    input: Input<'a>,
+    limits: DeserializationLimits,
+    depth: usize,
+}
+
+impl<'a> BorrowedDeserializer<'a> {
+    /// Creates a deserializer that reads values from the start of the slice
+    pub fn new(bytes: &'a [u8]) -> BorrowedDeserializer<'a> {
+        BorrowedDeserializer::with_limits(bytes, DeserializationLimits::default())
+    }
+
+    /// Creates a deserializer that fails if any value exceeds the provided limits
+    pub fn with_limits(bytes: &'a [u8], limits: DeserializationLimits) -> BorrowedDeserializer<'a> {
+        BorrowedDeserializer {
+            input: Input::new(bytes),
+            limits,
+            depth: 0,
+        }
+    }
+
+    /// The bytes that have not been read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.input.remaining()
+    }
+
+    /// Reads the next value, returning `None` once the end of the slice has been reached
+    pub fn next_value(&mut self) -> Result<Option<Amf0ValueRef<'a>>, Amf0DeserializationError> {
+        if self.input.is_empty() {
+            return Ok(None);
+        }
+
+        let marker = self.input.read_u8()?;
+        if marker == markers::OBJECT_END_MARKER {
+            return Ok(None);
+        }
+
+        self.read_value_with_marker(marker).map(Some)
+    }
+
+    fn read_value_with_marker(
+        &mut self,
+        marker: u8,
+    ) -> Result<Amf0ValueRef<'a>, Amf0DeserializationError> {
+        match marker {
+            markers::NUMBER_MARKER => Ok(Amf0ValueRef::Number(self.input.read_f64()?)),
+            markers::BOOLEAN_MARKER => Ok(Amf0ValueRef::Boolean(self.input.read_u8()? == 1)),
+            markers::STRING_MARKER => {
+                let length = self.input.read_u16()? as usize;
+                Ok(Amf0ValueRef::Utf8String(self.read_str(length)?))
+            }
+
+            markers::OBJECT_MARKER => Ok(Amf0ValueRef::Object(self.read_properties()?)),
+            markers::NULL_MARKER => Ok(Amf0ValueRef::Null),
+            markers::UNDEFINED_MARKER => Ok(Amf0ValueRef::Undefined),
+            markers::REFERENCE_MARKER => Ok(Amf0ValueRef::Reference(self.input.read_u16()?)),
+            markers::ECMA_ARRAY_MARKER => {
+                // Treated as an object, see `parse_ecma_array()` in the owned deserializer
+                self.input.read_bytes(4)?;
+                Ok(Amf0ValueRef::Object(self.read_properties()?))
+            }
+
+            markers::STRICT_ARRAY_MARKER => {
+                let count = self.input.read_u32()?;
+                check_element_count(&self.limits, count as usize)?;
+                self.enter_nested_value()?;
+                let mut values = Vec::new();
+                for _ in 0..count {
+                    match self.next_value()? {
+                        Some(value) => values.push(value),
+                        None => return Err(Amf0DeserializationError::UnexpectedEof),
+                    }
+                }
+
+                self.depth -= 1;
+                Ok(Amf0ValueRef::StrictArray(values))
+            }
+
+            markers::DATE_MARKER => {
+                let unix_time_millis = self.input.read_f64()?;
+                let time_zone = self.input.read_i16()?;
+                Ok(Amf0ValueRef::Date {
+                    unix_time_millis,
+                    time_zone,
+                })
+            }
+
+            markers::LONG_STRING_MARKER => {
+                let length = self.input.read_u32()? as usize;
+                Ok(Amf0ValueRef::Utf8String(self.read_str(length)?))
+            }
+
+            markers::XML_DOCUMENT_MARKER => {
+                let length = self.input.read_u32()? as usize;
+                Ok(Amf0ValueRef::XmlDocument(self.read_str(length)?))
+            }
+
+            markers::TYPED_OBJECT_MARKER => {
+                let length = self.input.read_u16()? as usize;
+                let class_name = self.read_str(length)?;
+                Ok(Amf0ValueRef::TypedObject {
+                    class_name,
+                    properties: self.read_properties()?,
+                })
+            }
+
+            _ => Err(Amf0DeserializationError::UnknownMarker { marker }),
+        }
+    }
+
+    fn read_properties(
+        &mut self,
+    ) -> Result<Vec<(&'a str, Amf0ValueRef<'a>)>, Amf0DeserializationError> {
+        self.enter_nested_value()?;
+        let mut properties = Vec::new();
+        loop {
+            let label_length = self.input.read_u16()? as usize;
+            if label_length == 0 {
+                if self.input.read_u8()? != markers::OBJECT_END_MARKER {
+                    return Err(Amf0DeserializationError::UnexpectedEmptyObjectPropertyName);
+                }
+
+                self.depth -= 1;
+                return Ok(properties);
+            }
+
+            let label = self.read_str(label_length)?;
+            match self.next_value()? {
+                Some(value) => properties.push((label, value)),
+                None => return Err(Amf0DeserializationError::UnexpectedEof),
+            }
+
+            check_element_count(&self.limits, properties.len())?;
+        }
+    }
+
+    fn enter_nested_value(&mut self) -> Result<(), Amf0DeserializationError> {
+        if self.depth >= self.limits.max_depth {
+            return Err(Amf0DeserializationError::NestingTooDeep {
+                max_depth: self.limits.max_depth,
+            });
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn read_str(&mut self, length: usize) -> Result<&'a str, Amf0DeserializationError> {
+        check_string_length(&self.limits, length)?;
+        Ok(str::from_utf8(self.input.read_bytes(length)?)?)
+    }
+}
+
+fn to_owned_properties(
+    properties: &[(&str, Amf0ValueRef)],
+) -> Result<ObjectProperties, Amf0DeserializationError> {
+    let mut owned = ObjectProperties::new();
+    for (name, value) in properties {
+        owned.insert(name.to_string(), value.to_owned_value()?);
+    }
+
+    Ok(owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serialize;
+
+    #[test]
+    fn borrowed_values_convert_to_the_same_owned_values() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".to_string(), Amf0Value::Utf8String("test".to_string()));
+        properties.insert(
+            "b".to_string(),
+            Amf0Value::StrictArray(vec![Amf0Value::Number(1.0), Amf0Value::Null]),
+        );
+
+        let input = vec![
+            Amf0Value::TypedObject {
+                class_name: "Test".to_string(),
+                properties,
+            },
+            Amf0Value::XmlDocument("<a />".to_string()),
+            Amf0Value::Boolean(true),
+        ];
+
+        let bytes = serialize(&input).unwrap();
+        let mut deserializer = BorrowedDeserializer::new(&bytes);
+        let mut results = Vec::new();
+        while let Some(value) = deserializer.next_value().unwrap() {
+            results.push(value.to_owned_value().unwrap());
+        }
+
+        assert_eq!(results, input);
+    }
+
+    #[test]
+    fn strings_point_into_the_input_buffer() {
+        let bytes = serialize(&vec![Amf0Value::Utf8String("test".to_string())]).unwrap();
+        let mut deserializer = BorrowedDeserializer::new(&bytes);
+
+        let value = deserializer
+            .next_value()
+            .unwrap()
+            .unwrap()
+            .as_str()
+            .unwrap();
+
+        assert_eq!(value, "test");
+        assert_eq!(value.as_ptr(), bytes[3..].as_ptr());
+    }
+
+    #[test]
+    fn unread_values_are_left_in_remaining_bytes() {
+        let bytes = serialize(&vec![Amf0Value::Number(1.0), Amf0Value::Boolean(false)]).unwrap();
+        let mut deserializer = BorrowedDeserializer::new(&bytes);
+
+        deserializer.next_value().unwrap();
+
+        assert_eq!(deserializer.remaining(), &[markers::BOOLEAN_MARKER, 0]);
+    }
+
+    #[test]
+    fn error_when_string_is_truncated() {
+        let bytes = [markers::STRING_MARKER, 0, 10, b'a'];
+        let mut deserializer = BorrowedDeserializer::new(&bytes);
+
+        match deserializer.next_value() {
+            Err(Amf0DeserializationError::UnexpectedEof) => (),
+            x => panic!("Expected unexpected eof error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_values_are_nested_too_deeply() {
+        let bytes = [
+            markers::STRICT_ARRAY_MARKER,
+            0,
+            0,
+            0,
+            1,
+            markers::STRICT_ARRAY_MARKER,
+            0,
+            0,
+            0,
+            1,
+            markers::NULL_MARKER,
+        ];
+
+        let mut limits = DeserializationLimits::new();
+        limits.max_depth = 1;
+        let mut deserializer = BorrowedDeserializer::with_limits(&bytes, limits);
+
+        match deserializer.next_value() {
+            Err(Amf0DeserializationError::NestingTooDeep { max_depth: 1 }) => (),
+            x => panic!("Expected nesting too deep error, instead got {:?}", x),
+        }
+    }
+}
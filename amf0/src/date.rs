@@ -0,0 +1,52 @@
+//! Conversion between `Amf0DateTime` values and the number of milliseconds since the unix epoch
+//! that dates are encoded as
+
+#[cfg(feature = "std")]
+use std::time::{Duration, UNIX_EPOCH};
+use Amf0DateTime;
+
+/// The number of milliseconds between the unix epoch and the time, which is negative for times
+/// before the epoch
+#[cfg(feature = "std")]
+pub fn unix_time_millis(unix_time: &Amf0DateTime) -> f64 {
+    match unix_time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as f64,
+        Err(error) => -(error.duration().as_millis() as f64),
+    }
+}
+
+/// The number of milliseconds between the unix epoch and the time, which is negative for times
+/// before the epoch
+#[cfg(not(feature = "std"))]
+pub fn unix_time_millis(unix_time: &Amf0DateTime) -> f64 {
+    *unix_time
+}
+
+/// Converts a number of milliseconds since the unix epoch to a time, rounded to the nearest
+/// millisecond.  `None` is returned if the time can't be represented.
+#[cfg(feature = "std")]
+pub fn from_unix_time_millis(unix_time_millis: f64) -> Option<Amf0DateTime> {
+    // Make sure the value can be represented as a duration before converting it
+    let millis = unix_time_millis.abs().round();
+    if !millis.is_finite() || millis >= u64::MAX as f64 {
+        return None;
+    }
+
+    let offset = Duration::from_millis(millis as u64);
+    if unix_time_millis >= 0.0 {
+        UNIX_EPOCH.checked_add(offset)
+    } else {
+        UNIX_EPOCH.checked_sub(offset)
+    }
+}
+
+/// Converts a number of milliseconds since the unix epoch to a time.  `None` is returned if the
+/// number is not finite.
+#[cfg(not(feature = "std"))]
+pub fn from_unix_time_millis(unix_time_millis: f64) -> Option<Amf0DateTime> {
+    if unix_time_millis.is_finite() {
+        Some(unix_time_millis)
+    } else {
+        None
+    }
+}
@@ -2,106 +2,590 @@
 //! that were encoded via the AMF0 specification
 //! (http://wwwimages.adobe.com/content/dam/Adobe/en/devnet/amf/pdf/amf0-file-format-specification.pdf)
 
-use byteorder::{BigEndian, ReadBytesExt};
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::{BigEndian, ByteOrder};
+use date::from_unix_time_millis;
 use errors::Amf0DeserializationError;
 use markers;
-use std::collections::HashMap;
-use std::io::Read;
+#[cfg(feature = "std")]
+use std::io::{Cursor, Read};
 use Amf0Value;
+use ObjectProperties;
+
+// Resolving a reference copies the referenced value, so a small payload that references the
+// same values over and over can expand into an enormous amount of memory.  Limit how many values
+// can be created through references to keep that in check.
+const MAX_VALUES_FROM_REFERENCES: usize = 100_000;
+
+/// Limits on the size and shape of deserialized values, which protect against malicious
+/// payloads that would otherwise use an enormous amount of memory or overflow the stack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeserializationLimits {
+    /// How deeply objects and arrays can be nested inside each other
+    pub max_depth: usize,
+
+    /// The maximum length in bytes of any string, including property names, typed object class
+    /// names, and XML documents
+    pub max_string_length: usize,
+
+    /// The maximum number of properties in a single object or values in a single strict array
+    pub max_element_count: usize,
+}
+
+impl DeserializationLimits {
+    /// Creates limits that are generous enough for any legitimate payload
+    pub fn new() -> DeserializationLimits {
+        DeserializationLimits {
+            max_depth: 100,
+            max_string_length: 16 * 1024 * 1024,
+            max_element_count: 100_000,
+        }
+    }
+}
+
+impl Default for DeserializationLimits {
+    fn default() -> Self {
+        DeserializationLimits::new()
+    }
+}
+
+/// The bytes being deserialized and how far into them we have read
+pub(crate) struct Input<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Input<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Input<'a> {
+        Input { bytes, position: 0 }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+
+    pub(crate) fn read_bytes(
+        &mut self,
+        count: usize,
+    ) -> Result<&'a [u8], Amf0DeserializationError> {
+        if self.bytes.len() - self.position < count {
+            // Consume the rest of the input so callers can tell it ran out mid value
+            self.position = self.bytes.len();
+            return Err(Amf0DeserializationError::UnexpectedEof);
+        }
+
+        let bytes = &self.bytes[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, Amf0DeserializationError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, Amf0DeserializationError> {
+        Ok(BigEndian::read_u16(self.read_bytes(2)?))
+    }
+
+    pub(crate) fn read_i16(&mut self) -> Result<i16, Amf0DeserializationError> {
+        Ok(BigEndian::read_i16(self.read_bytes(2)?))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, Amf0DeserializationError> {
+        Ok(BigEndian::read_u32(self.read_bytes(4)?))
+    }
+
+    pub(crate) fn read_f64(&mut self) -> Result<f64, Amf0DeserializationError> {
+        Ok(BigEndian::read_f64(self.read_bytes(8)?))
+    }
+}
+
+enum ReferenceEntry {
+    /// The value is still being deserialized, so a reference to it is cyclic
+    InProgress,
+
+    /// The value starts at this position of the input, and is deserialized again when it is
+    /// referenced.  This saves copying every object just in case it is referenced later.
+    Position(usize),
+
+    /// The value itself, along with how many values it contains
+    Value(Amf0Value, usize),
+}
+
+/// Objects, typed objects, ECMA arrays and strict arrays are added to this table in the order
+/// they start, so later values can refer back to them via reference markers.
+struct ReferenceTable {
+    entries: Vec<ReferenceEntry>,
+    values_from_references: usize,
+}
+
+impl ReferenceTable {
+    fn new() -> ReferenceTable {
+        ReferenceTable {
+            entries: Vec::new(),
+            values_from_references: 0,
+        }
+    }
+
+    fn add_value_from_reference(&mut self, count: usize) -> Result<(), Amf0DeserializationError> {
+        self.values_from_references += count;
+        if self.values_from_references > MAX_VALUES_FROM_REFERENCES {
+            return Err(Amf0DeserializationError::TooManyReferencedValues);
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the Amf3 encoded value that follows an avmplus object marker and converts it to an
+/// `Amf0Value`.  This crate has no knowledge of Amf3, so the reader is supplied by the caller
+/// (e.g. `rml_amf3::read_avmplus_value`).  The reader must keep the value within the limits
+/// it's given.
+#[cfg(feature = "std")]
+pub type AvmPlusReader =
+    fn(&mut dyn Read, &mut AvmPlusLimits) -> Result<Amf0Value, Amf0DeserializationError>;
+
+/// The part of the Amf0 payload's limits that are left for the Amf3 value following an avmplus
+/// object marker
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AvmPlusLimits {
+    /// The limits the payload is deserialized with, except that `max_depth` has been reduced
+    /// by how deeply the avmplus object marker is nested
+    pub limits: DeserializationLimits,
+
+    /// How many more values can be created by resolving references before the payload is
+    /// rejected.  Readers subtract the values their own references create.
+    pub remaining_values_from_references: usize,
+}
+
+/// Everything that is tracked across values while deserializing a payload
+pub(crate) struct DeserializerState {
+    refs: ReferenceTable,
+    limits: DeserializationLimits,
+    depth: usize,
+    #[cfg(feature = "std")]
+    avmplus_reader: Option<AvmPlusReader>,
+
+    // Set while deserializing a referenced value a second time, during which no new entries are
+    // added to the reference table
+    replaying_reference: bool,
+}
+
+impl DeserializerState {
+    pub(crate) fn new(limits: DeserializationLimits) -> DeserializerState {
+        DeserializerState {
+            refs: ReferenceTable::new(),
+            limits,
+            depth: 0,
+            #[cfg(feature = "std")]
+            avmplus_reader: None,
+            replaying_reference: false,
+        }
+    }
+
+    /// Records the state's current position so it can be rolled back with `restore()`
+    pub(crate) fn checkpoint(&self) -> (usize, usize) {
+        (self.refs.entries.len(), self.refs.values_from_references)
+    }
+
+    /// Rolls back to a checkpoint taken between top level values
+    pub(crate) fn restore(&mut self, checkpoint: (usize, usize)) {
+        self.refs.entries.truncate(checkpoint.0);
+        self.refs.values_from_references = checkpoint.1;
+        self.depth = 0;
+    }
+
+    /// Copies every referenceable value out of the input, so the input can be discarded while
+    /// keeping the values available to later references.
+    pub(crate) fn detach_references(
+        &mut self,
+        input: &Input,
+    ) -> Result<(), Amf0DeserializationError> {
+        // Each value was already checked against the limit when it was first read, so copying
+        // it shouldn't count towards the limit again
+        let values_from_references = self.refs.values_from_references;
+        for index in 0..self.refs.entries.len() {
+            if let ReferenceEntry::Position(start) = self.refs.entries[index] {
+                self.refs.values_from_references = 0;
+                let value = replay_value(input, start, self)?;
+                let count = count_values(&value);
+                self.refs.entries[index] = ReferenceEntry::Value(value, count);
+            }
+        }
+
+        self.refs.values_from_references = values_from_references;
+        Ok(())
+    }
+
+    fn reserve_reference(&mut self) -> Option<usize> {
+        if self.replaying_reference {
+            return None;
+        }
+
+        self.refs.entries.push(ReferenceEntry::InProgress);
+        Some(self.refs.entries.len() - 1)
+    }
+
+    fn complete_reference(&mut self, index: Option<usize>, start: usize) {
+        if let Some(index) = index {
+            self.refs.entries[index] = ReferenceEntry::Position(start);
+        }
+    }
+
+    fn enter_nested_value(&mut self) -> Result<(), Amf0DeserializationError> {
+        if self.depth >= self.limits.max_depth {
+            return Err(Amf0DeserializationError::NestingTooDeep {
+                max_depth: self.limits.max_depth,
+            });
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn exit_nested_value(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn check_string_length(&self, length: usize) -> Result<(), Amf0DeserializationError> {
+        check_string_length(&self.limits, length)
+    }
+
+    fn check_element_count(&self, count: usize) -> Result<(), Amf0DeserializationError> {
+        check_element_count(&self.limits, count)
+    }
+}
+
+pub(crate) fn check_string_length(
+    limits: &DeserializationLimits,
+    length: usize,
+) -> Result<(), Amf0DeserializationError> {
+    if length > limits.max_string_length {
+        return Err(Amf0DeserializationError::StringTooLong {
+            length,
+            max_length: limits.max_string_length,
+        });
+    }
+
+    Ok(())
+}
 
-struct ObjectProperty {
-    label: String,
-    value: Amf0Value,
+pub(crate) fn check_element_count(
+    limits: &DeserializationLimits,
+    count: usize,
+) -> Result<(), Amf0DeserializationError> {
+    if count > limits.max_element_count {
+        return Err(Amf0DeserializationError::TooManyElements {
+            max_count: limits.max_element_count,
+        });
+    }
+
+    Ok(())
 }
 
-/// Turns any readable byte stream and converts it into an array of AMF0 values
+/// Turns any readable byte stream and converts it into an array of AMF0 values, using the
+/// default `DeserializationLimits`
+#[cfg(feature = "std")]
 pub fn deserialize<R: Read>(bytes: &mut R) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
-    let mut results = vec![];
+    deserialize_with_limits(bytes, DeserializationLimits::default())
+}
 
-    loop {
-        match read_next_value(bytes)? {
-            Some(x) => results.push(x),
-            None => break,
-        };
+/// Turns any readable byte stream and converts it into an array of AMF0 values, failing if any
+/// value exceeds the provided limits
+#[cfg(feature = "std")]
+pub fn deserialize_with_limits<R: Read>(
+    bytes: &mut R,
+    limits: DeserializationLimits,
+) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+    deserialize_with_state(bytes, DeserializerState::new(limits))
+}
+
+/// Turns any readable byte stream and converts it into an array of AMF0 values, where any
+/// value may be an avmplus object marker followed by an Amf3 encoded value.  This is how Amf3
+/// command and data messages embed Amf3 values in otherwise Amf0 encoded data.
+#[cfg(feature = "std")]
+pub fn deserialize_with_avmplus<R: Read>(
+    bytes: &mut R,
+    limits: DeserializationLimits,
+    avmplus_reader: AvmPlusReader,
+) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+    let mut state = DeserializerState::new(limits);
+    state.avmplus_reader = Some(avmplus_reader);
+    deserialize_with_state(bytes, state)
+}
+
+#[cfg(feature = "std")]
+fn deserialize_with_state<R: Read>(
+    bytes: &mut R,
+    state: DeserializerState,
+) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+    // Values are read out of an in memory buffer, which is much faster than going through the
+    // `Read` trait for every field and lets referenced values be read again from their bytes.
+    let mut buffer = Vec::new();
+    bytes.read_to_end(&mut buffer)?;
+    deserialize_all(&buffer, state)
+}
+
+/// Converts a slice of bytes into an array of AMF0 values, using the default
+/// `DeserializationLimits`.  Unlike `deserialize()` this is available without the `std` feature.
+pub fn deserialize_slice(bytes: &[u8]) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+    deserialize_slice_with_limits(bytes, DeserializationLimits::default())
+}
+
+/// Converts a slice of bytes into an array of AMF0 values, failing if any value exceeds the
+/// provided limits
+pub fn deserialize_slice_with_limits(
+    bytes: &[u8],
+    limits: DeserializationLimits,
+) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+    deserialize_all(bytes, DeserializerState::new(limits))
+}
+
+fn deserialize_all(
+    bytes: &[u8],
+    mut state: DeserializerState,
+) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+    let mut input = Input::new(bytes);
+    let mut results = vec![];
+    while let Some(x) = read_next_value(&mut input, &mut state)? {
+        results.push(x);
     }
 
     Ok(results)
 }
 
-fn read_next_value<R: Read>(bytes: &mut R) -> Result<Option<Amf0Value>, Amf0DeserializationError> {
-    let mut buffer: [u8; 1] = [0];
-    let bytes_read = bytes.read(&mut buffer)?;
-
-    if bytes_read == 0 {
+pub(crate) fn read_next_value(
+    input: &mut Input,
+    state: &mut DeserializerState,
+) -> Result<Option<Amf0Value>, Amf0DeserializationError> {
+    if input.is_empty() {
         return Ok(None);
     }
 
-    if buffer[0] == markers::OBJECT_END_MARKER {
+    let start = input.position();
+    let marker = input.read_u8()?;
+    if marker == markers::OBJECT_END_MARKER {
         return Ok(None);
     }
 
-    match buffer[0] {
-        markers::BOOLEAN_MARKER => parse_bool(bytes).map(Some),
-        markers::NULL_MARKER => parse_null().map(Some),
-        markers::UNDEFINED_MARKER => parse_undefined().map(Some),
-        markers::NUMBER_MARKER => parse_number(bytes).map(Some),
-        markers::OBJECT_MARKER => parse_object(bytes).map(Some),
-        markers::ECMA_ARRAY_MARKER => parse_ecma_array(bytes).map(Some),
-        markers::STRING_MARKER => parse_string(bytes).map(Some),
-        markers::STRICT_ARRAY_MARKER => parse_strict_array(bytes).map(Some),
-        _ => Err(Amf0DeserializationError::UnknownMarker { marker: buffer[0] }),
+    if state.replaying_reference {
+        state.refs.add_value_from_reference(1)?;
+    }
+
+    match marker {
+        markers::BOOLEAN_MARKER => Ok(Some(Amf0Value::Boolean(input.read_u8()? == 1))),
+        markers::NULL_MARKER => Ok(Some(Amf0Value::Null)),
+        markers::UNDEFINED_MARKER => Ok(Some(Amf0Value::Undefined)),
+        markers::NUMBER_MARKER => Ok(Some(Amf0Value::Number(input.read_f64()?))),
+        markers::OBJECT_MARKER => parse_object(input, state, start).map(Some),
+        markers::ECMA_ARRAY_MARKER => parse_ecma_array(input, state, start).map(Some),
+        markers::STRING_MARKER => parse_string(input, state).map(Some),
+        markers::STRICT_ARRAY_MARKER => parse_strict_array(input, state, start).map(Some),
+        markers::DATE_MARKER => parse_date(input).map(Some),
+        markers::LONG_STRING_MARKER => parse_long_string(input, state).map(Some),
+        markers::XML_DOCUMENT_MARKER => parse_xml_document(input, state).map(Some),
+        markers::TYPED_OBJECT_MARKER => parse_typed_object(input, state, start).map(Some),
+        markers::REFERENCE_MARKER => parse_reference(input, state).map(Some),
+        #[cfg(feature = "std")]
+        markers::AVMPLUS_OBJECT_MARKER => match state.avmplus_reader {
+            Some(avmplus_reader) => parse_avmplus_object(input, state, avmplus_reader).map(Some),
+            None => Err(Amf0DeserializationError::UnknownMarker { marker }),
+        },
+        _ => Err(Amf0DeserializationError::UnknownMarker { marker }),
     }
 }
 
-fn parse_number<R: Read>(bytes: &mut R) -> Result<Amf0Value, Amf0DeserializationError> {
-    let number = bytes.read_f64::<BigEndian>()?;
-    let value = Amf0Value::Number(number);
+fn parse_string(
+    input: &mut Input,
+    state: &mut DeserializerState,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    let length = input.read_u16()?;
+    let value = read_utf8_string(input, length as usize, state)?;
+    Ok(Amf0Value::Utf8String(value))
+}
 
-    Ok(value)
+fn parse_long_string(
+    input: &mut Input,
+    state: &mut DeserializerState,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    let length = input.read_u32()?;
+    let value = read_utf8_string(input, length as usize, state)?;
+    Ok(Amf0Value::Utf8String(value))
 }
 
-fn parse_null() -> Result<Amf0Value, Amf0DeserializationError> {
-    Ok(Amf0Value::Null)
+fn read_utf8_string(
+    input: &mut Input,
+    length: usize,
+    state: &mut DeserializerState,
+) -> Result<String, Amf0DeserializationError> {
+    state.check_string_length(length)?;
+    let bytes = input.read_bytes(length)?;
+
+    Ok(String::from_utf8(bytes.to_vec())?)
 }
 
-fn parse_undefined() -> Result<Amf0Value, Amf0DeserializationError> {
-    Ok(Amf0Value::Undefined)
+fn parse_object(
+    input: &mut Input,
+    state: &mut DeserializerState,
+    start: usize,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    let index = state.reserve_reference();
+    let properties = parse_object_properties(input, state)?;
+    state.complete_reference(index, start);
+    Ok(Amf0Value::Object(properties))
 }
 
-fn parse_bool<R: Read>(bytes: &mut R) -> Result<Amf0Value, Amf0DeserializationError> {
-    let value = bytes.read_u8()?;
+fn parse_xml_document(
+    input: &mut Input,
+    state: &mut DeserializerState,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    let length = input.read_u32()?;
+    let value = read_utf8_string(input, length as usize, state)?;
+    Ok(Amf0Value::XmlDocument(value))
+}
+
+fn parse_typed_object(
+    input: &mut Input,
+    state: &mut DeserializerState,
+    start: usize,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    let index = state.reserve_reference();
+    let length = input.read_u16()?;
+    let class_name = read_utf8_string(input, length as usize, state)?;
+    let properties = parse_object_properties(input, state)?;
+    state.complete_reference(index, start);
+
+    Ok(Amf0Value::TypedObject {
+        class_name,
+        properties,
+    })
+}
+
+fn parse_reference(
+    input: &mut Input,
+    state: &mut DeserializerState,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    let index = input.read_u16()? as usize;
+    match state.refs.entries.get(index) {
+        Some(&ReferenceEntry::Position(start)) => replay_value(input, start, state),
+        Some(&ReferenceEntry::Value(ref value, count)) => {
+            let value = value.clone();
+            state.refs.add_value_from_reference(count)?;
+            Ok(value)
+        }
 
-    if value == 1 {
-        Ok(Amf0Value::Boolean(true))
-    } else {
-        Ok(Amf0Value::Boolean(false))
+        Some(&ReferenceEntry::InProgress) => {
+            Err(Amf0DeserializationError::CyclicReference { index })
+        }
+
+        None => Err(Amf0DeserializationError::InvalidReference { index }),
+    }
+}
+
+/// Deserializes the value starting at the specified position again, without adding anything
+/// to the reference table.  Every value created this way counts against the limit of values
+/// created through references.
+fn replay_value(
+    input: &Input,
+    start: usize,
+    state: &mut DeserializerState,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    let mut replay = Input {
+        bytes: input.bytes,
+        position: start,
+    };
+
+    let was_replaying = state.replaying_reference;
+    state.replaying_reference = true;
+    let result = read_next_value(&mut replay, state);
+    state.replaying_reference = was_replaying;
+
+    match result? {
+        Some(value) => Ok(value),
+        None => Err(Amf0DeserializationError::UnexpectedEof),
     }
 }
 
-fn parse_string<R: Read>(bytes: &mut R) -> Result<Amf0Value, Amf0DeserializationError> {
-    let length = bytes.read_u16::<BigEndian>()?;
-    let mut buffer: Vec<u8> = vec![0_u8; length as usize];
-    bytes.read(&mut buffer)?;
+#[cfg(feature = "std")]
+fn parse_avmplus_object(
+    input: &mut Input,
+    state: &mut DeserializerState,
+    avmplus_reader: AvmPlusReader,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    let mut limits = AvmPlusLimits {
+        limits: DeserializationLimits {
+            max_depth: state.limits.max_depth - state.depth,
+            ..state.limits.clone()
+        },
+
+        remaining_values_from_references: MAX_VALUES_FROM_REFERENCES
+            .saturating_sub(state.refs.values_from_references),
+    };
+
+    let mut cursor = Cursor::new(input.remaining());
+    let value = avmplus_reader(&mut cursor, &mut limits)?;
+    input.read_bytes(cursor.position() as usize)?;
+
+    state.refs.values_from_references =
+        MAX_VALUES_FROM_REFERENCES - limits.remaining_values_from_references;
+
+    if state.replaying_reference {
+        // The marker itself was already counted when it was read
+        state
+            .refs
+            .add_value_from_reference(count_values(&value) - 1)?;
+    }
 
-    let value = String::from_utf8(buffer)?;
-    Ok(Amf0Value::Utf8String(value))
+    Ok(value)
 }
 
-fn parse_object<R: Read>(bytes: &mut R) -> Result<Amf0Value, Amf0DeserializationError> {
-    let mut properties = HashMap::new();
+fn parse_object_properties(
+    input: &mut Input,
+    state: &mut DeserializerState,
+) -> Result<ObjectProperties, Amf0DeserializationError> {
+    state.enter_nested_value()?;
+    let mut properties = ObjectProperties::new();
 
     loop {
-        match parse_object_property(bytes)? {
-            Some(property) => properties.insert(property.label, property.value),
-            None => break,
+        let label_length = input.read_u16()?;
+        if label_length == 0 {
+            // Next byte should be the end of object marker.  We need to read this
+            // to make sure we progress the current position.
+            if input.read_u8()? != markers::OBJECT_END_MARKER {
+                return Err(Amf0DeserializationError::UnexpectedEmptyObjectPropertyName);
+            }
+
+            break;
+        }
+
+        let label = read_utf8_string(input, label_length as usize, state)?;
+        match read_next_value(input, state)? {
+            Some(value) => properties.insert(label, value),
+            None => return Err(Amf0DeserializationError::UnexpectedEof),
         };
+
+        state.check_element_count(properties.len())?;
     }
 
-    let deserialized_value = Amf0Value::Object(properties);
-    Ok(deserialized_value)
+    state.exit_nested_value();
+    Ok(properties)
 }
 
-fn parse_ecma_array<R: Read>(bytes: &mut R) -> Result<Amf0Value, Amf0DeserializationError> {
+fn parse_ecma_array(
+    input: &mut Input,
+    state: &mut DeserializerState,
+    start: usize,
+) -> Result<Amf0Value, Amf0DeserializationError> {
     // An ECMA array is an array of values indexed via strings instead of numeric indexes (so
     // essentially a hash map).  It seems functionally equivalent to an object so for simplicity
     // treat it as such.
@@ -112,76 +596,109 @@ fn parse_ecma_array<R: Read>(bytes: &mut R) -> Result<Amf0Value, Amf0Deserializa
     // then the buffer will start at that ending and funky things will happen.  So for now it seems
     // like we can ignore the associative count and just read exactly as we would an object.
 
-    let _associative_count = bytes.read_u32::<BigEndian>()?;
-    parse_object(bytes)
+    let _associative_count = input.read_u32()?;
+    parse_object(input, state, start)
 }
 
-fn parse_strict_array<R: Read>(bytes: &mut R) -> Result<Amf0Value, Amf0DeserializationError> {
-    let _array_count = bytes.read_u32::<BigEndian>()?;
-    let mut values: Vec<Amf0Value> = Vec::new();
-
-    for _ in 0.._array_count {
-        match read_next_value(bytes)? {
+fn parse_strict_array(
+    input: &mut Input,
+    state: &mut DeserializerState,
+    start: usize,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    let index = state.reserve_reference();
+    let array_count = input.read_u32()? as usize;
+    state.check_element_count(array_count)?;
+    state.enter_nested_value()?;
+
+    // Every value is at least one byte, so don't trust counts larger than the remaining input
+    let mut values: Vec<Amf0Value> = Vec::with_capacity(array_count.min(input.remaining().len()));
+    for _ in 0..array_count {
+        match read_next_value(input, state)? {
             Some(value) => {
                 values.push(value);
             }
-            None => break,
+            None => return Err(Amf0DeserializationError::UnexpectedEof),
         };
     }
 
+    state.exit_nested_value();
+    state.complete_reference(index, start);
     Ok(Amf0Value::StrictArray(values))
 }
 
-fn parse_object_property<R: Read>(
-    bytes: &mut R,
-) -> Result<Option<ObjectProperty>, Amf0DeserializationError> {
-    let label_length = bytes.read_u16::<BigEndian>()?;
-    if label_length == 0 {
-        // Next byte should be the end of object marker.  We need to read this
-        // to make sure we progress the current position.
-        let byte = bytes.read_u8()?;
-        if byte != markers::OBJECT_END_MARKER {
-            return Err(Amf0DeserializationError::UnexpectedEmptyObjectPropertyName);
-        }
+fn parse_date(input: &mut Input) -> Result<Amf0Value, Amf0DeserializationError> {
+    let unix_time_millis = input.read_f64()?;
+    let time_zone = input.read_i16()?;
+    create_date(unix_time_millis, time_zone)
+}
 
-        return Ok(None);
+pub(crate) fn create_date(
+    unix_time_millis: f64,
+    time_zone: i16,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    match from_unix_time_millis(unix_time_millis) {
+        Some(unix_time) => Ok(Amf0Value::Date {
+            unix_time,
+            time_zone,
+        }),
+        None => Err(Amf0DeserializationError::InvalidDate { unix_time_millis }),
     }
+}
 
-    let mut label_buffer = vec![0; label_length as usize];
-    bytes.read(&mut label_buffer)?;
-
-    let label = String::from_utf8(label_buffer)?;
-
-    match read_next_value(bytes)? {
-        None => Err(Amf0DeserializationError::UnexpectedEof),
-        Some(property_value) => Ok(Some(ObjectProperty {
-            label,
-            value: property_value,
-        })),
+fn count_values(value: &Amf0Value) -> usize {
+    match *value {
+        Amf0Value::Object(ref properties) => {
+            1 + properties.values().map(count_values).sum::<usize>()
+        }
+        Amf0Value::TypedObject { ref properties, .. } => {
+            1 + properties.values().map(count_values).sum::<usize>()
+        }
+        Amf0Value::StrictArray(ref values) => 1 + values.iter().map(count_values).sum::<usize>(),
+        _ => 1,
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::vec_init_then_push)]
 mod tests {
+    use super::super::errors::Amf0DeserializationError;
     use super::super::Amf0Value;
-    use super::deserialize;
-    use byteorder::{BigEndian, WriteBytesExt};
+    use super::deserialize_slice;
+    #[cfg(feature = "std")]
+    use super::{
+        deserialize, deserialize_with_avmplus, deserialize_with_limits, AvmPlusLimits,
+        DeserializationLimits,
+    };
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use date::from_unix_time_millis;
     use markers;
-    use std::collections::HashMap;
-    use std::io::Cursor;
+    #[cfg(feature = "std")]
+    use std::io::{Cursor, Read};
+    use ObjectProperties;
+
+    /// Deserializes through `std::io::Read` when it's available, and from a slice otherwise
+    #[cfg(feature = "std")]
+    fn deserialize_bytes(bytes: Vec<u8>) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+        deserialize(&mut Cursor::new(bytes))
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn deserialize_bytes(bytes: Vec<u8>) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+        deserialize_slice(&bytes)
+    }
 
     #[test]
     fn can_deserialize_strict_array() {
         let mut vector = vec![];
         vector.push(markers::STRICT_ARRAY_MARKER);
-        vector.write_u32::<BigEndian>(2).unwrap();
+        vector.extend_from_slice(&u32::to_be_bytes(2));
         vector.push(markers::NUMBER_MARKER);
-        vector.write_f64::<BigEndian>(1.0).unwrap();
+        vector.extend_from_slice(&f64::to_be_bytes(1.0));
         vector.push(markers::NUMBER_MARKER);
-        vector.write_f64::<BigEndian>(2.0).unwrap();
+        vector.extend_from_slice(&f64::to_be_bytes(2.0));
 
-        let mut input = Cursor::new(vector);
-        let result = deserialize(&mut input).unwrap();
+        let result = deserialize_bytes(vector).unwrap();
 
         let mut array = Vec::new();
 
@@ -192,16 +709,64 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn slices_deserialize_the_same_as_readers() {
+        let mut vector = vec![];
+        vector.push(markers::NUMBER_MARKER);
+        vector.extend_from_slice(&f64::to_be_bytes(1.0));
+        vector.push(markers::NULL_MARKER);
+
+        let result = deserialize_slice(&vector).unwrap();
+
+        assert_eq!(result, deserialize(&mut Cursor::new(vector)).unwrap());
+        assert_eq!(result, vec![Amf0Value::Number(1.0), Amf0Value::Null]);
+    }
+
+    #[test]
+    fn can_deserialize_nested_strict_arrays() {
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(2));
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(1));
+        vector.push(markers::NULL_MARKER);
+        vector.push(markers::BOOLEAN_MARKER);
+        vector.push(1);
+
+        let result = deserialize_bytes(vector).unwrap();
+
+        let inner = Amf0Value::StrictArray(vec![Amf0Value::Null]);
+        let expected = vec![Amf0Value::StrictArray(vec![
+            inner,
+            Amf0Value::Boolean(true),
+        ])];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn error_when_strict_array_has_fewer_values_than_its_count() {
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(2));
+        vector.push(markers::NUMBER_MARKER);
+        vector.extend_from_slice(&f64::to_be_bytes(1.0));
+
+        match deserialize_bytes(vector) {
+            Err(Amf0DeserializationError::UnexpectedEof) => (),
+            x => panic!("Expected unexpected eof error, instead got {:?}", x),
+        }
+    }
+
     #[test]
     fn can_deserialize_number() {
         let number: f64 = 332.0;
 
         let mut vector = vec![];
-        vector.write_u8(markers::NUMBER_MARKER).unwrap();
-        vector.write_f64::<BigEndian>(number).unwrap();
+        vector.push(markers::NUMBER_MARKER);
+        vector.extend_from_slice(&f64::to_be_bytes(number));
 
-        let mut input = Cursor::new(vector);
-        let result = deserialize(&mut input).unwrap();
+        let result = deserialize_bytes(vector).unwrap();
 
         let expected = vec![Amf0Value::Number(number)];
         assert_eq!(result, expected);
@@ -210,11 +775,10 @@ mod tests {
     #[test]
     fn can_deserialize_true_boolean() {
         let mut vector = vec![];
-        vector.write_u8(markers::BOOLEAN_MARKER).unwrap();
-        vector.write_u8(1).unwrap();
+        vector.push(markers::BOOLEAN_MARKER);
+        vector.push(1);
 
-        let mut input = Cursor::new(vector);
-        let result = deserialize(&mut input).unwrap();
+        let result = deserialize_bytes(vector).unwrap();
 
         let expected = vec![Amf0Value::Boolean(true)];
         assert_eq!(result, expected);
@@ -223,11 +787,10 @@ mod tests {
     #[test]
     fn can_deserialize_false_boolean() {
         let mut vector = vec![];
-        vector.write_u8(markers::BOOLEAN_MARKER).unwrap();
-        vector.write_u8(0).unwrap();
+        vector.push(markers::BOOLEAN_MARKER);
+        vector.push(0);
 
-        let mut input = Cursor::new(vector);
-        let result = deserialize(&mut input).unwrap();
+        let result = deserialize_bytes(vector).unwrap();
 
         let expected = vec![Amf0Value::Boolean(false)];
         assert_eq!(result, expected);
@@ -238,12 +801,11 @@ mod tests {
         let value = "test";
 
         let mut vector = vec![];
-        vector.write_u8(markers::STRING_MARKER).unwrap();
-        vector.write_u16::<BigEndian>(value.len() as u16).unwrap();
+        vector.push(markers::STRING_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(value.len() as u16));
         vector.extend(value.as_bytes());
 
-        let mut input = Cursor::new(vector);
-        let result = deserialize(&mut input).unwrap();
+        let result = deserialize_bytes(vector).unwrap();
 
         let expected = vec![Amf0Value::Utf8String(value.to_string())];
         assert_eq!(result, expected);
@@ -252,10 +814,9 @@ mod tests {
     #[test]
     fn can_deserialize_null() {
         let mut vector = vec![];
-        vector.write_u8(markers::NULL_MARKER).unwrap();
+        vector.push(markers::NULL_MARKER);
 
-        let mut input = Cursor::new(vector);
-        let result = deserialize(&mut input).unwrap();
+        let result = deserialize_bytes(vector).unwrap();
 
         let expected = vec![Amf0Value::Null];
         assert_eq!(result, expected);
@@ -267,19 +828,16 @@ mod tests {
 
         let mut vector = vec![];
         vector.push(markers::OBJECT_MARKER);
-        vector.write_u16::<BigEndian>(4).unwrap();
+        vector.extend_from_slice(&u16::to_be_bytes(4));
         vector.extend("test".as_bytes());
         vector.push(markers::NUMBER_MARKER);
-        vector.write_f64::<BigEndian>(NUMBER).unwrap();
-        vector
-            .write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER)
-            .unwrap();
+        vector.extend_from_slice(&f64::to_be_bytes(NUMBER));
+        vector.extend_from_slice(&u16::to_be_bytes(markers::UTF_8_EMPTY_MARKER));
         vector.push(markers::OBJECT_END_MARKER);
 
-        let mut input = Cursor::new(vector);
-        let result = deserialize(&mut input).unwrap();
+        let result = deserialize_bytes(vector).unwrap();
 
-        let mut properties = HashMap::new();
+        let mut properties = ObjectProperties::new();
         properties.insert("test".to_string(), Amf0Value::Number(NUMBER));
 
         let expected = vec![Amf0Value::Object(properties)];
@@ -290,25 +848,22 @@ mod tests {
     fn can_deserialize_emca_array() {
         let mut vector = vec![];
         vector.push(markers::ECMA_ARRAY_MARKER);
-        vector.write_u32::<BigEndian>(2).unwrap();
-        vector.write_u16::<BigEndian>(5).unwrap();
+        vector.extend_from_slice(&u32::to_be_bytes(2));
+        vector.extend_from_slice(&u16::to_be_bytes(5));
         vector.extend("test1".as_bytes());
         vector.push(markers::NUMBER_MARKER);
-        vector.write_f64::<BigEndian>(1.0).unwrap();
-        vector.write_u16::<BigEndian>(5).unwrap();
+        vector.extend_from_slice(&f64::to_be_bytes(1.0));
+        vector.extend_from_slice(&u16::to_be_bytes(5));
         vector.extend("test2".as_bytes());
-        vector.write_u8(markers::STRING_MARKER).unwrap();
-        vector.write_u16::<BigEndian>(6).unwrap();
+        vector.push(markers::STRING_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(6));
         vector.extend("second".as_bytes());
-        vector
-            .write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER)
-            .unwrap();
+        vector.extend_from_slice(&u16::to_be_bytes(markers::UTF_8_EMPTY_MARKER));
         vector.push(markers::OBJECT_END_MARKER);
 
-        let mut input = Cursor::new(vector);
-        let result = deserialize(&mut input).unwrap();
+        let result = deserialize_bytes(vector).unwrap();
 
-        let mut properties = HashMap::new();
+        let mut properties = ObjectProperties::new();
         properties.insert("test1".to_string(), Amf0Value::Number(1.0));
         properties.insert(
             "test2".to_string(),
@@ -322,12 +877,394 @@ mod tests {
     #[test]
     fn can_deserialize_undefined() {
         let mut vector = vec![];
-        vector.write_u8(markers::UNDEFINED_MARKER).unwrap();
+        vector.push(markers::UNDEFINED_MARKER);
 
-        let mut input = Cursor::new(vector);
-        let result = deserialize(&mut input).unwrap();
+        let result = deserialize_bytes(vector).unwrap();
 
         let expected = vec![Amf0Value::Undefined];
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn can_deserialize_date() {
+        let mut vector = vec![];
+        vector.push(markers::DATE_MARKER);
+        vector.extend_from_slice(&f64::to_be_bytes(1_500_000_000_123.0));
+        vector.extend_from_slice(&i16::to_be_bytes(0));
+
+        let result = deserialize_bytes(vector).unwrap();
+
+        let expected = vec![Amf0Value::Date {
+            unix_time: from_unix_time_millis(1_500_000_000_123.0).unwrap(),
+            time_zone: 0,
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_date_before_unix_epoch() {
+        let mut vector = vec![];
+        vector.push(markers::DATE_MARKER);
+        vector.extend_from_slice(&f64::to_be_bytes(-1000.0));
+        vector.extend_from_slice(&i16::to_be_bytes(0));
+
+        let result = deserialize_bytes(vector).unwrap();
+
+        let expected = vec![Amf0Value::Date {
+            unix_time: from_unix_time_millis(-1000.0).unwrap(),
+            time_zone: 0,
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn error_when_date_is_not_a_number() {
+        let mut vector = vec![];
+        vector.push(markers::DATE_MARKER);
+        vector.extend_from_slice(&f64::to_be_bytes(f64::NAN));
+        vector.extend_from_slice(&i16::to_be_bytes(0));
+
+        match deserialize_bytes(vector) {
+            Err(Amf0DeserializationError::InvalidDate { .. }) => (),
+            x => panic!("Expected invalid date error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn can_deserialize_typed_object() {
+        let mut vector = vec![];
+        vector.push(markers::TYPED_OBJECT_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(6));
+        vector.extend("MyType".as_bytes());
+        vector.extend_from_slice(&u16::to_be_bytes(4));
+        vector.extend("test".as_bytes());
+        vector.push(markers::NUMBER_MARKER);
+        vector.extend_from_slice(&f64::to_be_bytes(1.0));
+        vector.extend_from_slice(&u16::to_be_bytes(markers::UTF_8_EMPTY_MARKER));
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let result = deserialize_bytes(vector).unwrap();
+
+        let mut properties = ObjectProperties::new();
+        properties.insert("test".to_string(), Amf0Value::Number(1.0));
+
+        let expected = vec![Amf0Value::TypedObject {
+            class_name: "MyType".to_string(),
+            properties,
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_xml_document() {
+        let value = "<a>test</a>";
+
+        let mut vector = vec![];
+        vector.push(markers::XML_DOCUMENT_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(value.len() as u32));
+        vector.extend(value.as_bytes());
+
+        let result = deserialize_bytes(vector).unwrap();
+
+        let expected = vec![Amf0Value::XmlDocument(value.to_string())];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_long_string() {
+        let value = "a".repeat(u16::MAX as usize + 1);
+
+        let mut vector = vec![];
+        vector.push(markers::LONG_STRING_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(value.len() as u32));
+        vector.extend(value.as_bytes());
+
+        let result = deserialize_bytes(vector).unwrap();
+
+        let expected = vec![Amf0Value::Utf8String(value)];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_reference_to_earlier_object() {
+        let mut vector = vec![];
+        vector.push(markers::OBJECT_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(4));
+        vector.extend("test".as_bytes());
+        vector.push(markers::NUMBER_MARKER);
+        vector.extend_from_slice(&f64::to_be_bytes(1.0));
+        vector.extend_from_slice(&u16::to_be_bytes(markers::UTF_8_EMPTY_MARKER));
+        vector.push(markers::OBJECT_END_MARKER);
+        vector.push(markers::REFERENCE_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(0));
+
+        let result = deserialize_bytes(vector).unwrap();
+
+        let mut properties = ObjectProperties::new();
+        properties.insert("test".to_string(), Amf0Value::Number(1.0));
+
+        let expected = vec![
+            Amf0Value::Object(properties.clone()),
+            Amf0Value::Object(properties),
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_reference_to_value_containing_references() {
+        // An array of `[[true], <reference to [true]>]` followed by a reference to that array,
+        // whose own reference has to be resolved again
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(2));
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(1));
+        vector.push(markers::BOOLEAN_MARKER);
+        vector.push(1);
+        vector.push(markers::REFERENCE_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(1));
+        vector.push(markers::REFERENCE_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(0));
+
+        let result = deserialize_bytes(vector).unwrap();
+
+        let inner = Amf0Value::StrictArray(vec![Amf0Value::Boolean(true)]);
+        let outer = Amf0Value::StrictArray(vec![inner.clone(), inner]);
+        assert_eq!(result, vec![outer.clone(), outer]);
+    }
+
+    #[test]
+    fn error_when_reference_is_cyclic() {
+        // Strict array that contains a reference to itself
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(1));
+        vector.push(markers::REFERENCE_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(0));
+
+        match deserialize_bytes(vector) {
+            Err(Amf0DeserializationError::CyclicReference { index: 0 }) => (),
+            x => panic!("Expected cyclic reference error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_reference_index_does_not_exist() {
+        let mut vector = vec![];
+        vector.push(markers::REFERENCE_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(3));
+
+        match deserialize_bytes(vector) {
+            Err(Amf0DeserializationError::InvalidReference { index: 3 }) => (),
+            x => panic!("Expected invalid reference error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_references_expand_into_too_many_values() {
+        // Each array holds two references to the previous array, doubling its size every time
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(0));
+        for index in 0..30 {
+            vector.push(markers::STRICT_ARRAY_MARKER);
+            vector.extend_from_slice(&u32::to_be_bytes(2));
+            vector.push(markers::REFERENCE_MARKER);
+            vector.extend_from_slice(&u16::to_be_bytes(index));
+            vector.push(markers::REFERENCE_MARKER);
+            vector.extend_from_slice(&u16::to_be_bytes(index));
+        }
+
+        match deserialize_bytes(vector) {
+            Err(Amf0DeserializationError::TooManyReferencedValues) => (),
+            x => panic!(
+                "Expected too many referenced values error, instead got {:?}",
+                x.map(|_| ())
+            ),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn avmplus_values_are_read_by_the_provided_reader() {
+        fn read_byte_as_number(
+            bytes: &mut dyn Read,
+            _limits: &mut AvmPlusLimits,
+        ) -> Result<Amf0Value, Amf0DeserializationError> {
+            let mut buffer = [0_u8; 1];
+            bytes.read_exact(&mut buffer)?;
+            Ok(Amf0Value::Number(buffer[0] as f64))
+        }
+
+        let mut vector = vec![];
+        vector.push(markers::AVMPLUS_OBJECT_MARKER);
+        vector.push(5);
+        vector.push(markers::OBJECT_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(1));
+        vector.extend_from_slice(b"a");
+        vector.push(markers::AVMPLUS_OBJECT_MARKER);
+        vector.push(6);
+        vector.extend_from_slice(&u16::to_be_bytes(markers::UTF_8_EMPTY_MARKER));
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_avmplus(
+            &mut input,
+            DeserializationLimits::default(),
+            read_byte_as_number,
+        )
+        .unwrap();
+
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".to_string(), Amf0Value::Number(6.0));
+        let expected = vec![Amf0Value::Number(5.0), Amf0Value::Object(properties)];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn avmplus_readers_are_given_the_limits_left_over() {
+        fn read_limits(
+            _bytes: &mut dyn Read,
+            limits: &mut AvmPlusLimits,
+        ) -> Result<Amf0Value, Amf0DeserializationError> {
+            // Use up all but 10 of the reference budget
+            let depth = limits.limits.max_depth;
+            limits.remaining_values_from_references = 10;
+            Ok(Amf0Value::Number(depth as f64))
+        }
+
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(1));
+        vector.push(markers::AVMPLUS_OBJECT_MARKER);
+
+        let mut input = Cursor::new(vector);
+        let result =
+            deserialize_with_avmplus(&mut input, DeserializationLimits::default(), read_limits)
+                .unwrap();
+
+        let expected = vec![Amf0Value::StrictArray(vec![Amf0Value::Number(99.0)])];
+        assert_eq!(result, expected);
+
+        // A strict array of 20 values is more than the 10 the reader left
+        let mut vector = vec![];
+        vector.push(markers::AVMPLUS_OBJECT_MARKER);
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(20));
+        vector.extend_from_slice(&[markers::NULL_MARKER; 20]);
+
+        vector.push(markers::REFERENCE_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(0));
+
+        let mut input = Cursor::new(vector);
+        match deserialize_with_avmplus(&mut input, DeserializationLimits::default(), read_limits) {
+            Err(Amf0DeserializationError::TooManyReferencedValues) => (),
+            x => panic!(
+                "Expected too many referenced values error, instead got {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn error_when_avmplus_marker_is_seen_without_a_reader() {
+        let vector = vec![markers::AVMPLUS_OBJECT_MARKER, 5];
+
+        match deserialize_bytes(vector) {
+            Err(Amf0DeserializationError::UnknownMarker {
+                marker: markers::AVMPLUS_OBJECT_MARKER,
+            }) => (),
+            x => panic!("Expected unknown marker error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn error_when_values_are_nested_too_deeply() {
+        let mut vector = vec![];
+        for _ in 0..3 {
+            vector.push(markers::STRICT_ARRAY_MARKER);
+            vector.extend_from_slice(&u32::to_be_bytes(1));
+        }
+        vector.push(markers::NULL_MARKER);
+
+        let mut limits = DeserializationLimits::new();
+        limits.max_depth = 2;
+
+        let mut input = Cursor::new(vector);
+        match deserialize_with_limits(&mut input, limits) {
+            Err(Amf0DeserializationError::NestingTooDeep { max_depth: 2 }) => (),
+            x => panic!("Expected nesting too deep error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_string_is_longer_than_limit_without_reading_it() {
+        // Only the length is sent, so this fails with an eof error if the string is read
+        let mut vector = vec![];
+        vector.push(markers::LONG_STRING_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(u32::MAX));
+
+        match deserialize_bytes(vector) {
+            Err(Amf0DeserializationError::StringTooLong { length, .. })
+                if length == u32::MAX as usize => {}
+            x => panic!("Expected string too long error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn error_when_object_has_too_many_properties() {
+        let mut vector = vec![];
+        vector.push(markers::OBJECT_MARKER);
+        for label in &["a", "b", "c"] {
+            vector.extend_from_slice(&u16::to_be_bytes(1));
+            vector.extend_from_slice(label.as_bytes());
+            vector.push(markers::NULL_MARKER);
+        }
+        vector.extend_from_slice(&u16::to_be_bytes(markers::UTF_8_EMPTY_MARKER));
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let mut limits = DeserializationLimits::new();
+        limits.max_element_count = 2;
+
+        let mut input = Cursor::new(vector);
+        match deserialize_with_limits(&mut input, limits) {
+            Err(Amf0DeserializationError::TooManyElements { max_count: 2 }) => (),
+            x => panic!("Expected too many elements error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn values_within_limits_are_deserialized() {
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.extend_from_slice(&u32::to_be_bytes(2));
+        vector.push(markers::STRING_MARKER);
+        vector.extend_from_slice(&u16::to_be_bytes(2));
+        vector.extend_from_slice(b"ab");
+        vector.push(markers::NULL_MARKER);
+
+        let limits = DeserializationLimits {
+            max_depth: 1,
+            max_string_length: 2,
+            max_element_count: 2,
+        };
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, limits).unwrap();
+
+        let expected = vec![Amf0Value::StrictArray(vec![
+            Amf0Value::Utf8String("ab".to_string()),
+            Amf0Value::Null,
+        ])];
+        assert_eq!(result, expected);
+    }
 }
@@ -1,8 +1,12 @@
-use std::{io, string};
+use alloc::string::{self, String};
+use core::str;
+#[cfg(feature = "std")]
+use std::{error, io};
 use thiserror::Error;
 
 /// Errors that can occur during the deserialization process
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Amf0DeserializationError {
     /// Every Amf0 value starts with a marker byte describing the type of value that was
     /// encoded.  For example a marker of `0x00` is a number, `0x01` is a string, etc..
@@ -21,7 +25,46 @@ pub enum Amf0DeserializationError {
     #[error("Hit end of the byte buffer but was expecting more data")]
     UnexpectedEof,
 
+    /// Dates are encoded as the number of milliseconds since the unix epoch.  This error is
+    /// raised if that number is not finite (e.g. `NaN`) and thus can't be represented as a
+    /// `SystemTime`.
+    #[error("Date value of {unix_time_millis} milliseconds is not a valid time")]
+    InvalidDate { unix_time_millis: f64 },
+
+    /// A reference marker pointed to a value that has not been deserialized
+    #[error("Reference to unknown value at index {index}")]
+    InvalidReference { index: usize },
+
+    /// A reference marker pointed to a value that contains the reference itself.  These can't
+    /// be represented by an `Amf0Value`.
+    #[error("Cyclic reference to value at index {index}")]
+    CyclicReference { index: usize },
+
+    /// Resolving references copies the referenced values, and too many values were created
+    /// this way.  This protects against small payloads expanding into enormous structures.
+    #[error("Too many values were created by resolving references")]
+    TooManyReferencedValues,
+
+    /// Objects and arrays were nested more deeply than the deserialization limits allow
+    #[error("Values were nested more than {max_depth} levels deep")]
+    NestingTooDeep { max_depth: usize },
+
+    /// A string's length was larger than the deserialization limits allow.  This is raised
+    /// before the string is read, so the length may not match the bytes that actually follow.
+    #[error("String length of {length} is greater than the maximum of {max_length}")]
+    StringTooLong { length: usize, max_length: usize },
+
+    /// An object or strict array contained more elements than the deserialization limits allow
+    #[error("Object or array contained more than {max_count} elements")]
+    TooManyElements { max_count: usize },
+
+    /// The Amf3 value following an avmplus object marker could not be read
+    #[cfg(feature = "std")]
+    #[error("Failed to read the Amf3 value after an avmplus object marker: {0}")]
+    InvalidAvmPlusValue(#[source] Box<dyn error::Error + Send + Sync>),
+
     /// An I/O Error occurred while reading the data buffer
+    #[cfg(feature = "std")]
     #[error("Failed to read byte buffer: {0}")]
     BufferReadError(#[from] io::Error),
 
@@ -29,17 +72,60 @@ pub enum Amf0DeserializationError {
     /// UTF-8 this error will be raised.
     #[error("Failed to read a utf8 string from the byte buffer: {0}")]
     StringParseError(#[from] string::FromUtf8Error),
+
+    /// A string borrowed from the input buffer was not valid UTF-8
+    #[error("Borrowed string was not valid utf8: {0}")]
+    BorrowedStringParseError(#[from] str::Utf8Error),
 }
 
 /// Errors raised during to the serialization process
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Amf0SerializationError {
-    /// Amf0 strings cannot be more than 65,535 characters, so if a string was provided
-    /// with a larger length than this than this error is raised.
+    /// Amf0 strings that are not sent as long strings (such as typed object class names)
+    /// cannot be more than 65,535 characters, so if a string was provided with a larger
+    /// length than this than this error is raised.
     #[error("String length greater than 65,535")]
     NormalStringTooLong,
 
+    /// Strings longer than 65,535 bytes are sent as long strings, which (like XML documents)
+    /// cannot be more than 4,294,967,295 bytes.  If a larger string was provided this error
+    /// is raised.
+    #[error("Long string length greater than 4,294,967,295")]
+    LongStringTooLong,
+
+    /// Dates are encoded as milliseconds since the unix epoch in a 64 bit float, so if the
+    /// date is too far from the epoch to be represented this error is raised.
+    #[error("Date is too far from the unix epoch to be encoded")]
+    DateOutOfRange,
+
+    /// An empty name marks the end of an object's properties, so a property with an empty
+    /// name can't be encoded without cutting the object short.
+    #[error("Object properties cannot have empty names")]
+    EmptyObjectPropertyName,
+
     /// An I/O error occurred while writing to the output buffer.
-    #[error("Failed to write to byte buffer")]
+    #[cfg(feature = "std")]
+    #[error("Failed to write to byte buffer: {0}")]
     BufferWriteError(#[from] io::Error),
 }
+
+/// Errors raised when converting an Amf0 object into a rust type
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Amf0ObjectError {
+    /// The value being converted was not an object
+    #[error("Expected an object")]
+    NotAnObject,
+
+    /// A required property was not present in the object
+    #[error("Required field '{field}' was missing")]
+    MissingField { field: String },
+
+    /// A property was present in the object but did not have the expected type
+    #[error("Field '{field}' was expected to be a {expected}")]
+    InvalidFieldType {
+        field: String,
+        expected: &'static str,
+    },
+}
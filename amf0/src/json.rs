@@ -0,0 +1,146 @@
+//! Conversion between Amf0 values and JSON, enabled by the `json` feature
+
+use date::unix_time_millis;
+use serde_json::{Map, Number, Value};
+use {Amf0Value, ObjectProperties};
+
+impl Amf0Value {
+    /// Converts the value to JSON.  JSON has no equivalent for some Amf0 values, so the
+    /// conversion is lossy:
+    ///
+    /// * Undefined values, and numbers that are not finite, become `null`
+    /// * Dates become the number of milliseconds since the unix epoch
+    /// * XML documents become strings
+    /// * Typed objects become objects, without their class name
+    pub fn to_json(&self) -> Value {
+        match *self {
+            Amf0Value::Number(number) => to_json_number(number),
+            Amf0Value::Boolean(boolean) => Value::Bool(boolean),
+            Amf0Value::Utf8String(ref string) => Value::String(string.clone()),
+            Amf0Value::Object(ref properties) => to_json_object(properties),
+            Amf0Value::StrictArray(ref values) => {
+                Value::Array(values.iter().map(|x| x.to_json()).collect())
+            }
+
+            Amf0Value::Null => Value::Null,
+            Amf0Value::Undefined => Value::Null,
+            Amf0Value::Date { ref unix_time, .. } => {
+                to_json_number(unix_time_millis(unix_time).round())
+            }
+
+            Amf0Value::XmlDocument(ref document) => Value::String(document.clone()),
+            Amf0Value::TypedObject { ref properties, .. } => to_json_object(properties),
+        }
+    }
+
+    /// Converts JSON to the equivalent Amf0 value.  Arrays become strict arrays and objects
+    /// become anonymous objects.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate rml_amf0;
+    /// #[macro_use]
+    /// extern crate serde_json;
+    ///
+    /// use rml_amf0::Amf0Value;
+    ///
+    /// # fn main() {
+    /// let json = json!({ "app": "live", "capabilities": 31.0 });
+    /// let value = Amf0Value::from_json(json.clone());
+    ///
+    /// assert_eq!(value.to_json(), json);
+    /// # }
+    /// ```
+    pub fn from_json(value: Value) -> Amf0Value {
+        match value {
+            Value::Null => Amf0Value::Null,
+            Value::Bool(boolean) => Amf0Value::Boolean(boolean),
+            Value::Number(number) => Amf0Value::Number(number.as_f64().unwrap_or(0.0)),
+            Value::String(string) => Amf0Value::Utf8String(string),
+            Value::Array(values) => {
+                Amf0Value::StrictArray(values.into_iter().map(Amf0Value::from_json).collect())
+            }
+
+            Value::Object(map) => Amf0Value::Object(
+                map.into_iter()
+                    .map(|(name, value)| (name, Amf0Value::from_json(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn to_json_number(number: f64) -> Value {
+    match Number::from_f64(number) {
+        Some(number) => Value::Number(number),
+        None => Value::Null,
+    }
+}
+
+fn to_json_object(properties: &ObjectProperties) -> Value {
+    let map = properties
+        .iter()
+        .map(|(name, value)| (name.clone(), value.to_json()))
+        .collect::<Map<String, Value>>();
+
+    Value::Object(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn lossy_values_are_converted_to_closest_json_value() {
+        let values = vec![
+            Amf0Value::Undefined,
+            Amf0Value::Number(f64::NAN),
+            Amf0Value::Date {
+                unix_time: UNIX_EPOCH + Duration::from_millis(1500),
+                time_zone: 0,
+            },
+            Amf0Value::XmlDocument("<a />".to_string()),
+            Amf0Value::TypedObject {
+                class_name: "Status".to_string(),
+                properties: ObjectProperties::new(),
+            },
+        ];
+
+        let expected = Value::Array(vec![
+            Value::Null,
+            Value::Null,
+            Value::Number(Number::from_f64(1500.0).unwrap()),
+            Value::String("<a />".to_string()),
+            Value::Object(Map::new()),
+        ]);
+
+        assert_eq!(Amf0Value::StrictArray(values).to_json(), expected);
+    }
+
+    #[test]
+    fn json_objects_convert_to_amf0_objects() {
+        let mut map = Map::new();
+        map.insert("a".to_string(), Value::Bool(true));
+        map.insert(
+            "b".to_string(),
+            Value::Array(vec![Value::Null, Value::String("c".to_string())]),
+        );
+
+        let mut expected = ObjectProperties::new();
+        expected.insert("a".to_string(), Amf0Value::Boolean(true));
+        expected.insert(
+            "b".to_string(),
+            Amf0Value::StrictArray(vec![
+                Amf0Value::Null,
+                Amf0Value::Utf8String("c".to_string()),
+            ]),
+        );
+
+        assert_eq!(
+            Amf0Value::from_json(Value::Object(map)),
+            Amf0Value::Object(expected)
+        );
+    }
+}
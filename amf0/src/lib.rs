@@ -4,12 +4,13 @@
 //!
 //! # Examples
 //! ```
+//! # #[cfg(feature = "std")]
+//! # fn main() {
 //! use std::io::Cursor;
-//! use std::collections::HashMap;
-//! use rml_amf0::{Amf0Value, serialize, deserialize};
+//! use rml_amf0::{Amf0Value, ObjectProperties, serialize, deserialize};
 //!
 //! // Put some data into the Amf0Value types
-//! let mut properties = HashMap::new();
+//! let mut properties = ObjectProperties::new();
 //! properties.insert("app".to_string(), Amf0Value::Number(99.0));
 //! properties.insert("second".to_string(), Amf0Value::Utf8String("test".to_string()));
 //!
@@ -27,32 +28,122 @@
 //! let results = deserialize(&mut serialized_cursor).unwrap();
 //!
 //! assert_eq!(input, results);
+//! # }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() {}
 //! ```
+//!
+//! Reference markers are resolved during deserialization by copying the value they refer to.
+//! Since `Amf0Value` owns all of its children it can never contain a cycle, so values are always
+//! written out in full when serializing.
+//!
+//! # Features
+//! By default object properties are stored in a `HashMap`, so the order properties are
+//! serialized in is arbitrary.  Enabling the `preserve_order` feature stores them in an
+//! `IndexMap` instead, which serializes properties in the order they were inserted (and
+//! deserialized properties in the order they were received).  Code should use the
+//! `ObjectProperties` alias so it works with either.
+//!
+//! The `json` feature adds `Amf0Value::to_json()` and `Amf0Value::from_json()` for converting
+//! to and from `serde_json` values.  For log output without any extra dependencies use
+//! `Amf0Value::to_pretty_string()`.
+//!
+//! The `std` feature is enabled by default.  Without it the crate only needs `alloc`, so it can
+//! be used on embedded devices and in other `no_std` environments.  In that case object
+//! properties are stored in a `BTreeMap`, dates are held as the number of milliseconds since
+//! the unix epoch instead of a `SystemTime` (see `Amf0DateTime`), and values are deserialized
+//! from byte slices with `deserialize_slice()` instead of from `std::io::Read` streams.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
 #[macro_use]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
+
 extern crate byteorder;
+#[cfg(feature = "preserve_order")]
+extern crate indexmap;
+#[cfg(feature = "json")]
+extern crate serde_json;
 extern crate thiserror;
 
+mod borrowed;
+mod date;
 mod deserialization;
 mod errors;
+#[cfg(feature = "json")]
+mod json;
+mod object;
+mod pretty;
 mod serialization;
+mod streaming;
+
+pub use borrowed::{Amf0ValueRef, BorrowedDeserializer};
+pub use date::{from_unix_time_millis, unix_time_millis};
+#[cfg(feature = "std")]
+pub use deserialization::{
+    deserialize, deserialize_with_avmplus, deserialize_with_limits, AvmPlusLimits, AvmPlusReader,
+};
+pub use deserialization::{
+    deserialize_slice, deserialize_slice_with_limits, DeserializationLimits,
+};
+pub use errors::{Amf0DeserializationError, Amf0ObjectError, Amf0SerializationError};
+pub use object::{take_field, take_optional_field, Amf0Field, Amf0Object};
+pub use serialization::{serialize, serialize_with_references, Amf0ObjectWriter};
+pub use streaming::StreamingDeserializer;
+
+use alloc::string::String;
+use alloc::vec::Vec;
 
-pub use deserialization::deserialize;
-pub use errors::{Amf0DeserializationError, Amf0SerializationError};
-pub use serialization::serialize;
+/// The map used to store the properties of Amf0 objects
+#[cfg(all(feature = "std", not(feature = "preserve_order")))]
+pub type ObjectProperties = std::collections::HashMap<String, Amf0Value>;
 
-use std::collections::HashMap;
+/// The map used to store the properties of Amf0 objects
+#[cfg(not(feature = "std"))]
+pub type ObjectProperties = alloc::collections::BTreeMap<String, Amf0Value>;
+
+/// The map used to store the properties of Amf0 objects
+#[cfg(feature = "preserve_order")]
+pub type ObjectProperties = indexmap::IndexMap<String, Amf0Value>;
+
+/// The point in time held by a date value
+#[cfg(feature = "std")]
+pub type Amf0DateTime = std::time::SystemTime;
+
+/// The point in time held by a date value, as the number of milliseconds since the unix epoch
+#[cfg(not(feature = "std"))]
+pub type Amf0DateTime = f64;
 
 /// An Enum representing the different supported types of Amf0 values
 #[derive(PartialEq, Debug, Clone)]
 pub enum Amf0Value {
     Number(f64),
     Boolean(bool),
+
+    /// A UTF-8 string.  Strings longer than 65,535 bytes are sent as long strings.
     Utf8String(String),
-    Object(HashMap<String, Amf0Value>),
+    Object(ObjectProperties),
     StrictArray(Vec<Amf0Value>),
     Null,
     Undefined,
+
+    /// A point in time, with millisecond precision.  The time zone is reserved by the
+    /// specification and should be zero, but it is preserved so values round trip unchanged.
+    Date {
+        unix_time: Amf0DateTime,
+        time_zone: i16,
+    },
+
+    /// An XML document, which is encoded the same way as a long string
+    XmlDocument(String),
+
+    /// An object that was serialized along with the name of the class it is an instance of
+    TypedObject {
+        class_name: String,
+        properties: ObjectProperties,
+    },
 }
 
 impl Amf0Value {
@@ -77,12 +168,96 @@ impl Amf0Value {
         }
     }
 
-    pub fn get_object_properties(self) -> Option<HashMap<String, Amf0Value>> {
+    pub fn get_object_properties(self) -> Option<ObjectProperties> {
         match self {
             Amf0Value::Object(properties) => Some(properties),
             _ => None,
         }
     }
+
+    pub fn get_date(self) -> Option<Amf0DateTime> {
+        match self {
+            Amf0Value::Date { unix_time, .. } => Some(unix_time),
+            _ => None,
+        }
+    }
+
+    pub fn get_strict_array(self) -> Option<Vec<Amf0Value>> {
+        match self {
+            Amf0Value::StrictArray(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Returns the number without consuming the value
+    pub fn as_number(&self) -> Option<f64> {
+        match *self {
+            Amf0Value::Number(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the boolean without consuming the value
+    pub fn as_boolean(&self) -> Option<bool> {
+        match *self {
+            Amf0Value::Boolean(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the string without consuming the value
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Amf0Value::Utf8String(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the properties of an object or typed object without consuming the value
+    pub fn as_object_properties(&self) -> Option<&ObjectProperties> {
+        match *self {
+            Amf0Value::Object(ref properties) => Some(properties),
+            Amf0Value::TypedObject { ref properties, .. } => Some(properties),
+            _ => None,
+        }
+    }
+
+    /// Returns the values of a strict array without consuming the value
+    pub fn as_strict_array(&self) -> Option<&[Amf0Value]> {
+        match *self {
+            Amf0Value::StrictArray(ref values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Looks up a nested value by a dot separated path.  Each segment of the path is either a
+    /// property name of an object, or an index into a strict array.  `None` is returned if any
+    /// part of the path does not exist.
+    ///
+    /// ```
+    /// use rml_amf0::ObjectProperties;
+    /// use rml_amf0::Amf0Value;
+    ///
+    /// let mut data = ObjectProperties::new();
+    /// data.insert("code".to_string(), Amf0Value::Utf8String("Success".to_string()));
+    /// let mut properties = ObjectProperties::new();
+    /// properties.insert("data".to_string(), Amf0Value::Object(data));
+    /// let value = Amf0Value::Object(properties);
+    ///
+    /// let code = value.get_path("data.code").and_then(|x| x.as_str());
+    /// assert_eq!(code, Some("Success"));
+    /// ```
+    pub fn get_path(&self, path: &str) -> Option<&Amf0Value> {
+        let mut current = self;
+        for segment in path.split('.') {
+            current = match *current {
+                Amf0Value::StrictArray(ref values) => values.get(segment.parse::<usize>().ok()?)?,
+                _ => current.as_object_properties()?.get(segment)?,
+            };
+        }
+
+        Some(current)
+    }
 }
 
 mod markers {
@@ -92,8 +267,64 @@ mod markers {
     pub const OBJECT_MARKER: u8 = 3;
     pub const NULL_MARKER: u8 = 5;
     pub const UNDEFINED_MARKER: u8 = 6;
+    pub const REFERENCE_MARKER: u8 = 7;
     pub const ECMA_ARRAY_MARKER: u8 = 8;
     pub const OBJECT_END_MARKER: u8 = 9;
     pub const STRICT_ARRAY_MARKER: u8 = 10;
+    pub const DATE_MARKER: u8 = 11;
+    pub const LONG_STRING_MARKER: u8 = 12;
+    pub const XML_DOCUMENT_MARKER: u8 = 15;
+    pub const TYPED_OBJECT_MARKER: u8 = 16;
+    #[cfg(feature = "std")]
+    pub const AVMPLUS_OBJECT_MARKER: u8 = 17;
     pub const UTF_8_EMPTY_MARKER: u16 = 0;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn get_path_returns_nested_object_values() {
+        let mut inner = ObjectProperties::new();
+        inner.insert(
+            "code".to_string(),
+            Amf0Value::Utf8String("test".to_string()),
+        );
+        let mut outer = ObjectProperties::new();
+        outer.insert("data".to_string(), Amf0Value::Object(inner));
+        let value = Amf0Value::Object(outer);
+
+        let result = value.get_path("data.code");
+
+        assert_eq!(result, Some(&Amf0Value::Utf8String("test".to_string())));
+    }
+
+    #[test]
+    fn get_path_can_index_into_strict_arrays() {
+        let mut properties = ObjectProperties::new();
+        properties.insert(
+            "list".to_string(),
+            Amf0Value::StrictArray(vec![Amf0Value::Null, Amf0Value::Number(5.0)]),
+        );
+        let value = Amf0Value::Object(properties);
+
+        assert_eq!(
+            value.get_path("list.1").and_then(|x| x.as_number()),
+            Some(5.0)
+        );
+        assert_eq!(value.get_path("list.2"), None);
+        assert_eq!(value.get_path("list.a"), None);
+    }
+
+    #[test]
+    fn get_path_returns_none_when_path_goes_through_non_object() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".to_string(), Amf0Value::Number(1.0));
+        let value = Amf0Value::Object(properties);
+
+        assert_eq!(value.get_path("a.b"), None);
+        assert_eq!(value.get_path("b"), None);
+    }
+}
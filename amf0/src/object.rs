@@ -0,0 +1,256 @@
+//! Helpers for mapping rust structs to and from Amf0 objects.
+//!
+//! Structs implement `Amf0Object` by hand, using `take_field()` and `take_optional_field()` to
+//! pull typed values out of an object's properties.  Any missing or mistyped property is reported
+//! with the name of the offending field.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use errors::Amf0ObjectError;
+use Amf0Value;
+use ObjectProperties;
+
+/// A type that can be converted to and from the properties of an Amf0 object
+///
+/// # Examples
+/// ```
+/// use rml_amf0::{take_field, take_optional_field, Amf0Object, Amf0ObjectError, Amf0Value};
+/// use rml_amf0::ObjectProperties;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Status {
+///     code: String,
+///     retries: Option<f64>,
+/// }
+///
+/// impl Amf0Object for Status {
+///     fn to_amf0_properties(&self) -> ObjectProperties {
+///         let mut properties = ObjectProperties::new();
+///         properties.insert("code".to_string(), Amf0Value::from(self.code.as_str()));
+///         if let Some(retries) = self.retries {
+///             properties.insert("retries".to_string(), Amf0Value::from(retries));
+///         }
+///
+///         properties
+///     }
+///
+///     fn from_amf0_properties(
+///         mut properties: ObjectProperties,
+///     ) -> Result<Self, Amf0ObjectError> {
+///         Ok(Status {
+///             code: take_field(&mut properties, "code")?,
+///             retries: take_optional_field(&mut properties, "retries")?,
+///         })
+///     }
+/// }
+///
+/// let status = Status { code: "Success".to_string(), retries: None };
+/// let value = status.to_amf0_value();
+/// assert_eq!(Status::from_amf0_value(value).unwrap(), status);
+/// ```
+pub trait Amf0Object: Sized {
+    /// Creates the properties of an Amf0 object that represents this value
+    fn to_amf0_properties(&self) -> ObjectProperties;
+
+    /// Creates the value from the properties of an Amf0 object
+    fn from_amf0_properties(properties: ObjectProperties) -> Result<Self, Amf0ObjectError>;
+
+    /// Creates an `Amf0Value::Object` that represents this value
+    fn to_amf0_value(&self) -> Amf0Value {
+        Amf0Value::Object(self.to_amf0_properties())
+    }
+
+    /// Creates the value from an `Amf0Value::Object` (or a typed object)
+    fn from_amf0_value(value: Amf0Value) -> Result<Self, Amf0ObjectError> {
+        match value {
+            Amf0Value::Object(properties) => Self::from_amf0_properties(properties),
+            Amf0Value::TypedObject { properties, .. } => Self::from_amf0_properties(properties),
+            _ => Err(Amf0ObjectError::NotAnObject),
+        }
+    }
+}
+
+/// A type that can be read out of a single Amf0 object property
+pub trait Amf0Field: Sized {
+    /// Name of the expected Amf0 type, used when reporting mistyped fields
+    const TYPE_NAME: &'static str;
+
+    /// Converts the property's value, returning `None` if it is not the expected type
+    fn from_amf0_field(value: Amf0Value) -> Option<Self>;
+}
+
+impl Amf0Field for f64 {
+    const TYPE_NAME: &'static str = "number";
+
+    fn from_amf0_field(value: Amf0Value) -> Option<Self> {
+        value.get_number()
+    }
+}
+
+impl Amf0Field for bool {
+    const TYPE_NAME: &'static str = "boolean";
+
+    fn from_amf0_field(value: Amf0Value) -> Option<Self> {
+        value.get_boolean()
+    }
+}
+
+impl Amf0Field for String {
+    const TYPE_NAME: &'static str = "string";
+
+    fn from_amf0_field(value: Amf0Value) -> Option<Self> {
+        value.get_string()
+    }
+}
+
+// Without the `std` feature dates are plain numbers, which already convert as numbers
+#[cfg(feature = "std")]
+impl Amf0Field for ::Amf0DateTime {
+    const TYPE_NAME: &'static str = "date";
+
+    fn from_amf0_field(value: Amf0Value) -> Option<Self> {
+        value.get_date()
+    }
+}
+
+impl Amf0Field for Vec<Amf0Value> {
+    const TYPE_NAME: &'static str = "strict array";
+
+    fn from_amf0_field(value: Amf0Value) -> Option<Self> {
+        value.get_strict_array()
+    }
+}
+
+impl Amf0Field for ObjectProperties {
+    const TYPE_NAME: &'static str = "object";
+
+    fn from_amf0_field(value: Amf0Value) -> Option<Self> {
+        value.get_object_properties()
+    }
+}
+
+impl Amf0Field for Amf0Value {
+    const TYPE_NAME: &'static str = "any value";
+
+    fn from_amf0_field(value: Amf0Value) -> Option<Self> {
+        Some(value)
+    }
+}
+
+/// Removes a required property from an object's properties and converts it to the requested
+/// type.
+pub fn take_field<T: Amf0Field>(
+    properties: &mut ObjectProperties,
+    name: &str,
+) -> Result<T, Amf0ObjectError> {
+    match take_optional_field(properties, name)? {
+        Some(value) => Ok(value),
+        None => Err(Amf0ObjectError::MissingField {
+            field: name.to_string(),
+        }),
+    }
+}
+
+/// Removes an optional property from an object's properties and converts it to the requested
+/// type.  Properties that are missing, null, or undefined are returned as `None`.
+pub fn take_optional_field<T: Amf0Field>(
+    properties: &mut ObjectProperties,
+    name: &str,
+) -> Result<Option<T>, Amf0ObjectError> {
+    match properties.remove(name) {
+        None | Some(Amf0Value::Null) | Some(Amf0Value::Undefined) => Ok(None),
+        Some(value) => match T::from_amf0_field(value) {
+            Some(value) => Ok(Some(value)),
+            None => Err(Amf0ObjectError::InvalidFieldType {
+                field: name.to_string(),
+                expected: T::TYPE_NAME,
+            }),
+        },
+    }
+}
+
+impl From<f64> for Amf0Value {
+    fn from(value: f64) -> Self {
+        Amf0Value::Number(value)
+    }
+}
+
+impl From<bool> for Amf0Value {
+    fn from(value: bool) -> Self {
+        Amf0Value::Boolean(value)
+    }
+}
+
+impl From<String> for Amf0Value {
+    fn from(value: String) -> Self {
+        Amf0Value::Utf8String(value)
+    }
+}
+
+impl<'a> From<&'a str> for Amf0Value {
+    fn from(value: &'a str) -> Self {
+        Amf0Value::Utf8String(value.to_string())
+    }
+}
+
+impl From<Vec<Amf0Value>> for Amf0Value {
+    fn from(value: Vec<Amf0Value>) -> Self {
+        Amf0Value::StrictArray(value)
+    }
+}
+
+impl From<ObjectProperties> for Amf0Value {
+    fn from(value: ObjectProperties) -> Self {
+        Amf0Value::Object(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_take_required_field() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".to_string(), Amf0Value::Number(5.0));
+
+        let value: f64 = take_field(&mut properties, "a").unwrap();
+
+        assert_eq!(value, 5.0);
+        assert!(properties.is_empty());
+    }
+
+    #[test]
+    fn error_when_required_field_is_missing() {
+        let mut properties = ObjectProperties::new();
+
+        match take_field::<String>(&mut properties, "a") {
+            Err(Amf0ObjectError::MissingField { ref field }) if field == "a" => (),
+            x => panic!("Expected missing field error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_field_has_wrong_type() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".to_string(), Amf0Value::Boolean(true));
+
+        match take_field::<String>(&mut properties, "a") {
+            Err(Amf0ObjectError::InvalidFieldType {
+                ref field,
+                expected: "string",
+            }) if field == "a" => (),
+            x => panic!("Expected invalid field type error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn null_optional_field_is_none() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".to_string(), Amf0Value::Null);
+
+        let value: Option<bool> = take_optional_field(&mut properties, "a").unwrap();
+
+        assert_eq!(value, None);
+    }
+}
@@ -0,0 +1,163 @@
+//! Human readable formatting of Amf0 values, for log lines and admin output
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use date::unix_time_millis;
+use {Amf0Value, ObjectProperties};
+
+const INDENT: &str = "  ";
+
+impl Amf0Value {
+    /// Formats the value across multiple indented lines, in a JSON like syntax that keeps the
+    /// Amf0 specific types (undefined, dates, XML documents, and typed object class names)
+    /// visible.
+    ///
+    /// Without the `preserve_order` feature object properties are listed in alphabetical order,
+    /// so the same value is always formatted the same way.
+    ///
+    /// # Examples
+    /// ```
+    /// use rml_amf0::{Amf0Value, ObjectProperties};
+    ///
+    /// let mut properties = ObjectProperties::new();
+    /// properties.insert("app".to_string(), Amf0Value::Utf8String("live".to_string()));
+    /// properties.insert("audioCodecs".to_string(), Amf0Value::Number(3191.0));
+    ///
+    /// let expected = "{\n  \"app\": \"live\",\n  \"audioCodecs\": 3191\n}";
+    /// assert_eq!(Amf0Value::Object(properties).to_pretty_string(), expected);
+    /// ```
+    pub fn to_pretty_string(&self) -> String {
+        let mut output = String::new();
+        write_value(self, 0, &mut output).expect("Writing to a string should not fail");
+        output
+    }
+}
+
+fn write_value(value: &Amf0Value, depth: usize, output: &mut String) -> fmt::Result {
+    match *value {
+        Amf0Value::Number(number) => write!(output, "{}", number),
+        Amf0Value::Boolean(boolean) => write!(output, "{}", boolean),
+        Amf0Value::Utf8String(ref string) => write!(output, "{:?}", string),
+        Amf0Value::Object(ref properties) => write_properties(properties, depth, output),
+        Amf0Value::StrictArray(ref values) => {
+            if values.is_empty() {
+                return output.write_str("[]");
+            }
+
+            output.write_str("[\n")?;
+            for (index, value) in values.iter().enumerate() {
+                write_indent(depth + 1, output);
+                write_value(value, depth + 1, output)?;
+                write_separator(index, values.len(), output);
+            }
+
+            write_indent(depth, output);
+            output.write_str("]")
+        }
+
+        Amf0Value::Null => output.write_str("null"),
+        Amf0Value::Undefined => output.write_str("undefined"),
+        Amf0Value::Date {
+            ref unix_time,
+            time_zone,
+        } => write!(
+            output,
+            "Date({} ms, time zone {})",
+            unix_time_millis(unix_time),
+            time_zone
+        ),
+
+        Amf0Value::XmlDocument(ref document) => write!(output, "Xml({:?})", document),
+        Amf0Value::TypedObject {
+            ref class_name,
+            ref properties,
+        } => {
+            write!(output, "{} ", class_name)?;
+            write_properties(properties, depth, output)
+        }
+    }
+}
+
+fn write_properties(
+    properties: &ObjectProperties,
+    depth: usize,
+    output: &mut String,
+) -> fmt::Result {
+    if properties.is_empty() {
+        return output.write_str("{}");
+    }
+
+    #[allow(unused_mut)]
+    let mut properties = properties.iter().collect::<Vec<_>>();
+
+    #[cfg(not(feature = "preserve_order"))]
+    properties.sort_by(|a, b| a.0.cmp(b.0));
+
+    output.write_str("{\n")?;
+    for (index, (name, value)) in properties.iter().enumerate() {
+        write_indent(depth + 1, output);
+        write!(output, "{:?}: ", name)?;
+        write_value(value, depth + 1, output)?;
+        write_separator(index, properties.len(), output);
+    }
+
+    write_indent(depth, output);
+    output.write_str("}")
+}
+
+fn write_indent(depth: usize, output: &mut String) {
+    for _ in 0..depth {
+        output.push_str(INDENT);
+    }
+}
+
+fn write_separator(index: usize, count: usize, output: &mut String) {
+    if index + 1 < count {
+        output.push(',');
+    }
+
+    output.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use date::from_unix_time_millis;
+
+    #[test]
+    fn nested_values_are_indented() {
+        let mut inner = ObjectProperties::new();
+        inner.insert("a".to_string(), Amf0Value::Boolean(true));
+
+        let mut properties = ObjectProperties::new();
+        properties.insert(
+            "list".to_string(),
+            Amf0Value::StrictArray(vec![Amf0Value::Null, Amf0Value::Object(inner)]),
+        );
+
+        let expected = "{\n  \"list\": [\n    null,\n    {\n      \"a\": true\n    }\n  ]\n}";
+        assert_eq!(Amf0Value::Object(properties).to_pretty_string(), expected);
+    }
+
+    #[test]
+    fn amf0_specific_types_are_labeled() {
+        let values = vec![
+            Amf0Value::Undefined,
+            Amf0Value::Date {
+                unix_time: from_unix_time_millis(1500.0).unwrap(),
+                time_zone: 0,
+            },
+            Amf0Value::XmlDocument("<a />".to_string()),
+            Amf0Value::TypedObject {
+                class_name: "Status".to_string(),
+                properties: ObjectProperties::new(),
+            },
+        ];
+
+        let expected =
+            "[\n  undefined,\n  Date(1500 ms, time zone 0),\n  Xml(\"<a />\"),\n  Status {}\n]";
+        assert_eq!(Amf0Value::StrictArray(values).to_pretty_string(), expected);
+    }
+}
@@ -2,53 +2,290 @@
 //! bytes based on the AMF0 specification
 //! (http://wwwimages.adobe.com/content/dam/Adobe/en/devnet/amf/pdf/amf0-file-format-specification.pdf)
 
-use byteorder::{BigEndian, WriteBytesExt};
+use alloc::vec::Vec;
+use date::unix_time_millis;
 use errors::Amf0SerializationError;
 use markers;
-use std::collections::HashMap;
+use Amf0DateTime;
 use Amf0Value;
+use ObjectProperties;
 
 /// Serializes values into an amf0 encoded vector of bytes
+#[allow(clippy::ptr_arg)] // Changing the public signature would break function pointers to it
 pub fn serialize(values: &Vec<Amf0Value>) -> Result<Vec<u8>, Amf0SerializationError> {
-    let mut bytes = vec![];
+    serialize_values(values, &mut References::disabled())
+}
+
+/// Serializes values into an amf0 encoded vector of bytes, writing any object or array that's
+/// equal to one written before it as a reference to the earlier one.  This keeps payloads that
+/// repeat the same structures small.
+///
+/// Strings are always written in full, as amf0 references can only point to objects and
+/// arrays.  References aren't written by `serialize()` because some RTMP implementations, such
+/// as librtmp, fail to read them.
+///
+/// # Examples
+/// ```
+/// use rml_amf0::{deserialize_slice, serialize, serialize_with_references, Amf0Value};
+///
+/// let array = Amf0Value::StrictArray(vec![Amf0Value::Number(1.0), Amf0Value::Number(2.0)]);
+/// let values = vec![array.clone(), array.clone(), array];
+///
+/// let bytes = serialize_with_references(&values).unwrap();
+/// assert!(bytes.len() < serialize(&values).unwrap().len());
+/// assert_eq!(deserialize_slice(&bytes).unwrap(), values);
+/// ```
+pub fn serialize_with_references(values: &[Amf0Value]) -> Result<Vec<u8>, Amf0SerializationError> {
+    serialize_values(values, &mut References::enabled())
+}
+
+fn serialize_values<'a>(
+    values: &'a [Amf0Value],
+    references: &mut References<'a>,
+) -> Result<Vec<u8>, Amf0SerializationError> {
+    let size = values.iter().map(serialized_size).sum();
+    let mut bytes = Vec::with_capacity(size);
     for value in values {
-        serialize_value(value, &mut bytes)?;
+        serialize_value(value, references, &mut bytes)?;
     }
 
     Ok(bytes)
 }
 
-fn serialize_value(value: &Amf0Value, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+/// The objects and arrays that have been written so far, in the same order deserializers number
+/// them, so ones that are repeated can be written as references to their first copy
+struct References<'a> {
+    written: Option<Vec<&'a Amf0Value>>,
+}
+
+impl<'a> References<'a> {
+    fn disabled() -> References<'a> {
+        References { written: None }
+    }
+
+    fn enabled() -> References<'a> {
+        References {
+            written: Some(Vec::new()),
+        }
+    }
+
+    /// Returns the index of an earlier value that's equal to this one.  If there isn't one the
+    /// value is given the next index, as long as it can still be referenced.
+    fn find_or_add(&mut self, value: &'a Amf0Value) -> Option<u16> {
+        let written = self.written.as_mut()?;
+        if let Some(index) = written.iter().position(|earlier| *earlier == value) {
+            return Some(index as u16);
+        }
+
+        // References hold a 16 bit index, so values after that can't be referred to
+        if written.len() <= u16::MAX as usize {
+            written.push(value);
+        }
+
+        None
+    }
+}
+
+/// Writes an Amf0 object one property at a time, so a struct can be serialized straight into
+/// bytes without building an `ObjectProperties` map for it first.
+///
+/// # Examples
+/// ```
+/// use rml_amf0::{deserialize_slice, Amf0ObjectWriter, Amf0Value};
+///
+/// let mut bytes = Vec::new();
+/// let mut writer = Amf0ObjectWriter::new(&mut bytes);
+/// writer.string_property("app", "live").unwrap();
+/// writer.number_property("objectEncoding", 0.0).unwrap();
+/// writer.finish();
+///
+/// let values = deserialize_slice(&bytes).unwrap();
+/// assert_eq!(values[0].get_path("app"), Some(&Amf0Value::from("live")));
+/// ```
+pub struct Amf0ObjectWriter<'a> {
+    bytes: &'a mut Vec<u8>,
+}
+
+impl<'a> Amf0ObjectWriter<'a> {
+    /// Starts an object at the end of the bytes
+    pub fn new(bytes: &'a mut Vec<u8>) -> Amf0ObjectWriter<'a> {
+        bytes.push(markers::OBJECT_MARKER);
+        Amf0ObjectWriter { bytes }
+    }
+
+    /// Writes a property with any value
+    pub fn property(
+        &mut self,
+        name: &str,
+        value: &Amf0Value,
+    ) -> Result<(), Amf0SerializationError> {
+        self.write_name(name)?;
+        serialize_value(value, &mut References::disabled(), self.bytes)
+    }
+
+    /// Writes a property with a string value
+    pub fn string_property(
+        &mut self,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Amf0SerializationError> {
+        self.write_name(name)?;
+        serialize_string(value, self.bytes)
+    }
+
+    /// Writes a property with a number value
+    pub fn number_property(
+        &mut self,
+        name: &str,
+        value: f64,
+    ) -> Result<(), Amf0SerializationError> {
+        self.write_name(name)?;
+        serialize_number(&value, self.bytes);
+        Ok(())
+    }
+
+    /// Ends the object.  An object that isn't finished is left incomplete in the bytes.
+    pub fn finish(self) {
+        self.bytes
+            .extend_from_slice(&markers::UTF_8_EMPTY_MARKER.to_be_bytes());
+        self.bytes.push(markers::OBJECT_END_MARKER);
+    }
+
+    fn write_name(&mut self, name: &str) -> Result<(), Amf0SerializationError> {
+        if name.is_empty() {
+            return Err(Amf0SerializationError::EmptyObjectPropertyName);
+        }
+
+        write_short_string(name, self.bytes)
+    }
+}
+
+/// The number of bytes the value will be serialized into, so the output buffer can be allocated
+/// once up front
+fn serialized_size(value: &Amf0Value) -> usize {
     match *value {
-        Amf0Value::Boolean(ref val) => Ok(serialize_bool(&val, bytes)),
-        Amf0Value::Null => Ok(serialize_null(bytes)),
-        Amf0Value::Undefined => Ok(serialize_undefined(bytes)),
-        Amf0Value::Number(ref val) => serialize_number(&val, bytes),
-        Amf0Value::Utf8String(ref val) => serialize_string(&val, bytes),
-        Amf0Value::Object(ref val) => serialize_object(&val, bytes),
-        Amf0Value::StrictArray(ref val) => serialize_strict_array(&val, bytes),
+        Amf0Value::Boolean(_) => 2,
+        Amf0Value::Null | Amf0Value::Undefined => 1,
+        Amf0Value::Number(_) => 9,
+        Amf0Value::Utf8String(ref val) if val.len() > (u16::MAX as usize) => 5 + val.len(),
+        Amf0Value::Utf8String(ref val) => 3 + val.len(),
+        Amf0Value::Object(ref val) => 1 + object_properties_size(val),
+        Amf0Value::StrictArray(ref val) => 5 + val.iter().map(serialized_size).sum::<usize>(),
+        Amf0Value::Date { .. } => 11,
+        Amf0Value::XmlDocument(ref val) => 5 + val.len(),
+        Amf0Value::TypedObject {
+            ref class_name,
+            ref properties,
+        } => 3 + class_name.len() + object_properties_size(properties),
     }
 }
 
-fn serialize_number(value: &f64, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+fn object_properties_size(properties: &ObjectProperties) -> usize {
+    let size = properties
+        .iter()
+        .map(|(name, value)| 2 + name.len() + serialized_size(value))
+        .sum::<usize>();
+
+    // Properties are followed by an empty name and the object end marker
+    size + 3
+}
+
+fn serialize_value<'a>(
+    value: &'a Amf0Value,
+    references: &mut References<'a>,
+    bytes: &mut Vec<u8>,
+) -> Result<(), Amf0SerializationError> {
+    match *value {
+        Amf0Value::Object(_) | Amf0Value::StrictArray(_) | Amf0Value::TypedObject { .. } => {
+            if let Some(index) = references.find_or_add(value) {
+                serialize_reference(index, bytes);
+                return Ok(());
+            }
+        }
+
+        _ => (),
+    }
+
+    match *value {
+        Amf0Value::Boolean(ref val) => {
+            serialize_bool(val, bytes);
+            Ok(())
+        }
+        Amf0Value::Null => {
+            serialize_null(bytes);
+            Ok(())
+        }
+        Amf0Value::Undefined => {
+            serialize_undefined(bytes);
+            Ok(())
+        }
+        Amf0Value::Number(ref val) => {
+            serialize_number(val, bytes);
+            Ok(())
+        }
+        Amf0Value::Utf8String(ref val) => serialize_string(val, bytes),
+        Amf0Value::Object(ref val) => serialize_object(val, references, bytes),
+        Amf0Value::StrictArray(ref val) => serialize_strict_array(val, references, bytes),
+        Amf0Value::Date {
+            ref unix_time,
+            time_zone,
+        } => serialize_date(unix_time, time_zone, bytes),
+        Amf0Value::XmlDocument(ref val) => serialize_xml_document(val, bytes),
+        Amf0Value::TypedObject {
+            ref class_name,
+            ref properties,
+        } => serialize_typed_object(class_name, properties, references, bytes),
+    }
+}
+
+fn serialize_reference(index: u16, bytes: &mut Vec<u8>) {
+    bytes.push(markers::REFERENCE_MARKER);
+    bytes.extend_from_slice(&index.to_be_bytes());
+}
+
+fn serialize_number(value: &f64, bytes: &mut Vec<u8>) {
     bytes.push(markers::NUMBER_MARKER);
-    bytes.write_f64::<BigEndian>(value.clone())?;
-    Ok(())
+    bytes.extend_from_slice(&value.to_bits().to_be_bytes());
 }
 
 fn serialize_bool(value: &bool, bytes: &mut Vec<u8>) {
     bytes.push(markers::BOOLEAN_MARKER);
-    bytes.push((value.clone()) as u8);
+    bytes.push(*value as u8);
 }
 
-fn serialize_string(value: &String, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
-    if value.len() > (u16::max_value() as usize) {
-        return Err(Amf0SerializationError::NormalStringTooLong);
+fn serialize_string(value: &str, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    if value.len() > (u16::MAX as usize) {
+        return serialize_long_string(value, bytes);
     }
 
     bytes.push(markers::STRING_MARKER);
-    bytes.write_u16::<BigEndian>(value.len() as u16)?;
-    bytes.extend(value.as_bytes());
+    write_short_string(value, bytes)
+}
+
+fn serialize_long_string(value: &str, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    bytes.push(markers::LONG_STRING_MARKER);
+    write_long_string(value, bytes)
+}
+
+/// Writes a string prefixed with its 16 bit length
+fn write_short_string(value: &str, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    if value.len() > (u16::MAX as usize) {
+        return Err(Amf0SerializationError::NormalStringTooLong);
+    }
+
+    bytes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+/// Writes a string prefixed with its 32 bit length
+fn write_long_string(value: &str, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    if value.len() > (u32::MAX as usize) {
+        return Err(Amf0SerializationError::LongStringTooLong);
+    }
+
+    bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(value.as_bytes());
     Ok(())
 }
 
@@ -60,47 +297,93 @@ fn serialize_undefined(bytes: &mut Vec<u8>) {
     bytes.push(markers::UNDEFINED_MARKER);
 }
 
-fn serialize_object(
-    properties: &HashMap<String, Amf0Value>,
+fn serialize_object<'a>(
+    properties: &'a ObjectProperties,
+    references: &mut References<'a>,
     bytes: &mut Vec<u8>,
 ) -> Result<(), Amf0SerializationError> {
     bytes.push(markers::OBJECT_MARKER);
+    serialize_object_properties(properties, references, bytes)
+}
+
+fn serialize_xml_document(value: &str, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    bytes.push(markers::XML_DOCUMENT_MARKER);
+    write_long_string(value, bytes)
+}
 
+fn serialize_typed_object<'a>(
+    class_name: &str,
+    properties: &'a ObjectProperties,
+    references: &mut References<'a>,
+    bytes: &mut Vec<u8>,
+) -> Result<(), Amf0SerializationError> {
+    bytes.push(markers::TYPED_OBJECT_MARKER);
+    write_short_string(class_name, bytes)?;
+    serialize_object_properties(properties, references, bytes)
+}
+
+fn serialize_object_properties<'a>(
+    properties: &'a ObjectProperties,
+    references: &mut References<'a>,
+    bytes: &mut Vec<u8>,
+) -> Result<(), Amf0SerializationError> {
     for (name, value) in properties {
-        // TODO: Add check that property name isn't greater than a u16
-        bytes.write_u16::<BigEndian>(name.len() as u16)?;
-        bytes.extend(name.as_bytes());
-        serialize_value(&value, bytes)?;
+        if name.is_empty() {
+            return Err(Amf0SerializationError::EmptyObjectPropertyName);
+        }
+
+        write_short_string(name, bytes)?;
+        serialize_value(value, references, bytes)?;
     }
 
-    bytes.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER)?;
+    bytes.extend_from_slice(&markers::UTF_8_EMPTY_MARKER.to_be_bytes());
     bytes.push(markers::OBJECT_END_MARKER);
     Ok(())
 }
 
-fn serialize_strict_array(
-    array: &Vec<Amf0Value>,
+fn serialize_strict_array<'a>(
+    array: &'a [Amf0Value],
+    references: &mut References<'a>,
     bytes: &mut Vec<u8>,
 ) -> Result<(), Amf0SerializationError> {
     bytes.push(markers::STRICT_ARRAY_MARKER);
-
-    bytes.write_u32::<BigEndian>(array.len() as u32)?;
+    bytes.extend_from_slice(&(array.len() as u32).to_be_bytes());
 
     for value in array {
-        serialize_value(&value, bytes)?;
+        serialize_value(value, references, bytes)?;
     }
 
     Ok(())
 }
 
+fn serialize_date(
+    unix_time: &Amf0DateTime,
+    time_zone: i16,
+    bytes: &mut Vec<u8>,
+) -> Result<(), Amf0SerializationError> {
+    let unix_time_millis = unix_time_millis(unix_time);
+    if !unix_time_millis.is_finite() {
+        return Err(Amf0SerializationError::DateOutOfRange);
+    }
+
+    bytes.push(markers::DATE_MARKER);
+    bytes.extend_from_slice(&unix_time_millis.to_bits().to_be_bytes());
+    bytes.extend_from_slice(&time_zone.to_be_bytes());
+    Ok(())
+}
+
 #[cfg(test)]
+#[allow(clippy::vec_init_then_push)]
 mod tests {
     use super::super::errors::Amf0SerializationError;
     use super::super::Amf0Value;
-    use super::serialize;
-    use byteorder::{BigEndian, WriteBytesExt};
+    use super::{serialize, serialize_with_references, Amf0ObjectWriter};
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use date::from_unix_time_millis;
+    use deserialization::deserialize_slice;
     use markers;
-    use std::collections::HashMap;
+    use ObjectProperties;
 
     #[test]
     fn can_serialize_strict_array() {
@@ -114,10 +397,10 @@ mod tests {
 
         let mut expected = vec![];
 
-        expected.write_u8(markers::STRICT_ARRAY_MARKER).unwrap();
-        expected.write_u32::<BigEndian>(1).unwrap();
-        expected.write_u8(markers::NUMBER_MARKER).unwrap();
-        expected.write_f64::<BigEndian>(number).unwrap();
+        expected.push(markers::STRICT_ARRAY_MARKER);
+        expected.extend_from_slice(&u32::to_be_bytes(1));
+        expected.push(markers::NUMBER_MARKER);
+        expected.extend_from_slice(&f64::to_be_bytes(number));
 
         assert_eq!(result, expected);
     }
@@ -130,8 +413,8 @@ mod tests {
         let result = serialize(&input).unwrap();
 
         let mut expected = vec![];
-        expected.write_u8(markers::NUMBER_MARKER).unwrap();
-        expected.write_f64::<BigEndian>(number).unwrap();
+        expected.push(markers::NUMBER_MARKER);
+        expected.extend_from_slice(&f64::to_be_bytes(number));
 
         assert_eq!(result, expected);
     }
@@ -142,8 +425,8 @@ mod tests {
         let result = serialize(&input).unwrap();
 
         let mut expected = vec![];
-        expected.write_u8(markers::BOOLEAN_MARKER).unwrap();
-        expected.write_u8(1).unwrap();
+        expected.push(markers::BOOLEAN_MARKER);
+        expected.push(1);
 
         assert_eq!(result, expected);
     }
@@ -154,8 +437,8 @@ mod tests {
         let result = serialize(&input).unwrap();
 
         let mut expected = vec![];
-        expected.write_u8(markers::BOOLEAN_MARKER).unwrap();
-        expected.write_u8(0).unwrap();
+        expected.push(markers::BOOLEAN_MARKER);
+        expected.push(0);
 
         assert_eq!(result, expected);
     }
@@ -168,8 +451,8 @@ mod tests {
         let result = serialize(&input).unwrap();
 
         let mut expected = vec![];
-        expected.write_u8(markers::STRING_MARKER).unwrap();
-        expected.write_u16::<BigEndian>(value.len() as u16).unwrap();
+        expected.push(markers::STRING_MARKER);
+        expected.extend_from_slice(&u16::to_be_bytes(value.len() as u16));
         expected.extend(value.as_bytes());
 
         assert_eq!(result, expected);
@@ -181,7 +464,7 @@ mod tests {
         let result = serialize(&input).unwrap();
 
         let mut expected = vec![];
-        expected.write_u8(markers::NULL_MARKER).unwrap();
+        expected.push(markers::NULL_MARKER);
 
         assert_eq!(result, expected);
     }
@@ -190,7 +473,7 @@ mod tests {
     fn can_serialize_object() {
         const NUMBER: f64 = 332.0;
 
-        let mut properties = HashMap::new();
+        let mut properties = ObjectProperties::new();
         properties.insert("test".to_string(), Amf0Value::Number(NUMBER));
 
         let input = vec![Amf0Value::Object(properties)];
@@ -198,33 +481,68 @@ mod tests {
 
         let mut expected = vec![];
         expected.push(markers::OBJECT_MARKER);
-        expected.write_u16::<BigEndian>(4).unwrap();
+        expected.extend_from_slice(&u16::to_be_bytes(4));
         expected.extend("test".as_bytes());
         expected.push(markers::NUMBER_MARKER);
-        expected.write_f64::<BigEndian>(NUMBER).unwrap();
-        expected
-            .write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER)
-            .unwrap();
+        expected.extend_from_slice(&f64::to_be_bytes(NUMBER));
+        expected.extend_from_slice(&u16::to_be_bytes(markers::UTF_8_EMPTY_MARKER));
         expected.push(markers::OBJECT_END_MARKER);
 
         assert_eq!(result, expected);
     }
 
     #[test]
-    fn error_when_string_length_greater_than_u16() {
-        let mut value = String::new();
-        let max = (u16::max_value() as u32) + 1;
-        for _ in 0..max {
-            value.push('a');
-        }
+    fn strings_longer_than_u16_are_serialized_as_long_strings() {
+        let value = "a".repeat(u16::MAX as usize + 1);
+
+        let input = vec![Amf0Value::Utf8String(value.clone())];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::LONG_STRING_MARKER);
+        expected.extend_from_slice(&u32::to_be_bytes(value.len() as u32));
+        expected.extend(value.as_bytes());
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn error_when_typed_object_class_name_length_greater_than_u16() {
+        let input = vec![Amf0Value::TypedObject {
+            class_name: "a".repeat(u16::MAX as usize + 1),
+            properties: ObjectProperties::new(),
+        }];
 
-        let input = vec![Amf0Value::Utf8String(value)];
         let result = serialize(&input);
 
-        assert!(match result {
-            Err(Amf0SerializationError::NormalStringTooLong) => true,
-            _ => false,
-        });
+        assert!(matches!(
+            result,
+            Err(Amf0SerializationError::NormalStringTooLong)
+        ));
+    }
+
+    #[test]
+    fn error_when_object_property_name_length_greater_than_u16() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".repeat(u16::MAX as usize + 1), Amf0Value::Null);
+
+        let input = vec![Amf0Value::Object(properties)];
+        match serialize(&input) {
+            Err(Amf0SerializationError::NormalStringTooLong) => (),
+            x => panic!("Expected normal string too long error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_object_property_name_is_empty() {
+        let mut properties = ObjectProperties::new();
+        properties.insert(String::new(), Amf0Value::Null);
+
+        let input = vec![Amf0Value::Object(properties)];
+        match serialize(&input) {
+            Err(Amf0SerializationError::EmptyObjectPropertyName) => (),
+            x => panic!("Expected empty property name error, instead got {:?}", x),
+        }
     }
 
     #[test]
@@ -233,8 +551,206 @@ mod tests {
         let result = serialize(&input).unwrap();
 
         let mut expected = vec![];
-        expected.write_u8(markers::UNDEFINED_MARKER).unwrap();
+        expected.push(markers::UNDEFINED_MARKER);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_date() {
+        let input = vec![Amf0Value::Date {
+            unix_time: from_unix_time_millis(1_500_000_000_123.0).unwrap(),
+            time_zone: 0,
+        }];
+
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::DATE_MARKER);
+        expected.extend_from_slice(&f64::to_be_bytes(1_500_000_000_123.0));
+        expected.extend_from_slice(&i16::to_be_bytes(0));
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn distant_dates_are_serialized_without_rounding_errors() {
+        let input = vec![Amf0Value::Date {
+            unix_time: from_unix_time_millis(4_404_715_777_523_488.0).unwrap(),
+            time_zone: 0,
+        }];
+
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::DATE_MARKER);
+        expected.extend_from_slice(&f64::to_be_bytes(4_404_715_777_523_488.0));
+        expected.extend_from_slice(&i16::to_be_bytes(0));
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_date_before_unix_epoch() {
+        let input = vec![Amf0Value::Date {
+            unix_time: from_unix_time_millis(-1000.0).unwrap(),
+            time_zone: 0,
+        }];
+
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::DATE_MARKER);
+        expected.extend_from_slice(&f64::to_be_bytes(-1000.0));
+        expected.extend_from_slice(&i16::to_be_bytes(0));
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_typed_object() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("test".to_string(), Amf0Value::Number(1.0));
+
+        let input = vec![Amf0Value::TypedObject {
+            class_name: "MyType".to_string(),
+            properties,
+        }];
+
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::TYPED_OBJECT_MARKER);
+        expected.extend_from_slice(&u16::to_be_bytes(6));
+        expected.extend("MyType".as_bytes());
+        expected.extend_from_slice(&u16::to_be_bytes(4));
+        expected.extend("test".as_bytes());
+        expected.push(markers::NUMBER_MARKER);
+        expected.extend_from_slice(&f64::to_be_bytes(1.0));
+        expected.extend_from_slice(&u16::to_be_bytes(markers::UTF_8_EMPTY_MARKER));
+        expected.push(markers::OBJECT_END_MARKER);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_xml_document() {
+        let value = "<a>test</a>";
+
+        let input = vec![Amf0Value::XmlDocument(value.to_string())];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::XML_DOCUMENT_MARKER);
+        expected.extend_from_slice(&u32::to_be_bytes(value.len() as u32));
+        expected.extend(value.as_bytes());
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "preserve_order")]
+    fn object_properties_are_serialized_in_insertion_order() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("z".to_string(), Amf0Value::Null);
+        properties.insert("a".to_string(), Amf0Value::Null);
+        properties.insert("m".to_string(), Amf0Value::Null);
+
+        let input = vec![Amf0Value::Object(properties)];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![markers::OBJECT_MARKER];
+        for name in &["z", "a", "m"] {
+            expected.extend_from_slice(&u16::to_be_bytes(1));
+            expected.extend(name.as_bytes());
+            expected.push(markers::NULL_MARKER);
+        }
+
+        expected.extend_from_slice(&u16::to_be_bytes(markers::UTF_8_EMPTY_MARKER));
+        expected.push(markers::OBJECT_END_MARKER);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn object_writer_matches_serialized_object() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("test".to_string(), Amf0Value::Number(332.0));
+
+        let mut bytes = Vec::new();
+        let mut writer = Amf0ObjectWriter::new(&mut bytes);
+        writer.number_property("test", 332.0).unwrap();
+        writer.finish();
+
+        let expected = serialize(&vec![Amf0Value::Object(properties)]).unwrap();
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn error_when_object_writer_property_name_is_empty() {
+        let mut bytes = Vec::new();
+        let mut writer = Amf0ObjectWriter::new(&mut bytes);
+
+        match writer.string_property("", "value") {
+            Err(Amf0SerializationError::EmptyObjectPropertyName) => (),
+            x => panic!("Expected empty property name error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn repeated_values_are_not_written_as_references_by_default() {
+        let array = Amf0Value::StrictArray(vec![Amf0Value::Number(1.0)]);
+        let input = vec![array.clone(), array];
+
+        let result = serialize(&input).unwrap();
+
+        assert!(!result.contains(&markers::REFERENCE_MARKER));
+    }
+
+    #[test]
+    fn repeated_values_are_written_as_references_to_their_first_copy() {
+        let mut properties = ObjectProperties::new();
+        properties.insert(
+            "name".to_string(),
+            Amf0Value::Utf8String("value".to_string()),
+        );
+        let object = Amf0Value::Object(properties);
+        let array = Amf0Value::StrictArray(vec![object.clone(), Amf0Value::Number(1.0)]);
+        let input = vec![array.clone(), object, array];
+
+        let result = serialize_with_references(&input).unwrap();
+
+        // The array is index 0 and the object inside of it index 1
+        let first_array = serialize(&vec![input[0].clone()]).unwrap();
+        let mut expected = first_array.clone();
+        expected.extend_from_slice(&[markers::REFERENCE_MARKER, 0, 1]);
+        expected.extend_from_slice(&[markers::REFERENCE_MARKER, 0, 0]);
+
+        assert_eq!(result, expected);
+        assert_eq!(deserialize_slice(&result).unwrap(), input);
+    }
+
+    #[test]
+    fn values_repeated_inside_an_array_are_written_as_references() {
+        let object = Amf0Value::Object(ObjectProperties::new());
+        let input = vec![Amf0Value::StrictArray(vec![
+            object.clone(),
+            object.clone(),
+            object,
+        ])];
+
+        let result = serialize_with_references(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::STRICT_ARRAY_MARKER);
+        expected.extend_from_slice(&u32::to_be_bytes(3));
+        expected.push(markers::OBJECT_MARKER);
+        expected.extend_from_slice(&u16::to_be_bytes(0));
+        expected.push(markers::OBJECT_END_MARKER);
+        expected.extend_from_slice(&[markers::REFERENCE_MARKER, 0, 1]);
+        expected.extend_from_slice(&[markers::REFERENCE_MARKER, 0, 1]);
 
         assert_eq!(result, expected);
+        assert_eq!(deserialize_slice(&result).unwrap(), input);
     }
 }
@@ -0,0 +1,190 @@
+//! Incremental deserialization of AMF0 values from bytes that arrive over time
+
+use alloc::vec::Vec;
+use deserialization::{read_next_value, DeserializationLimits, DeserializerState, Input};
+use errors::Amf0DeserializationError;
+#[cfg(feature = "std")]
+use std::io::ErrorKind;
+use Amf0Value;
+
+/// Deserializes AMF0 values from a stream of bytes that may be split at any point.
+///
+/// Bytes are added with `push()` as they are received, and each value is returned from
+/// `next_value()` as soon as all of its bytes are available.  Values that have been returned no
+/// longer occupy any buffer space, so only the bytes of the value currently being received are
+/// held onto.
+///
+/// Reference markers may refer to any value previously seen by the deserializer.  If an error is
+/// returned the stream is in an unknown state and the deserializer should be discarded.
+///
+/// # Examples
+/// ```
+/// use rml_amf0::{serialize, Amf0Value, StreamingDeserializer};
+///
+/// let bytes = serialize(&vec![Amf0Value::Number(1.0), Amf0Value::Boolean(true)]).unwrap();
+/// let (first, second) = bytes.split_at(5);
+///
+/// let mut deserializer = StreamingDeserializer::new();
+/// deserializer.push(first);
+/// assert_eq!(deserializer.next_value().unwrap(), None);
+///
+/// deserializer.push(second);
+/// assert_eq!(deserializer.next_value().unwrap(), Some(Amf0Value::Number(1.0)));
+/// assert_eq!(deserializer.next_value().unwrap(), Some(Amf0Value::Boolean(true)));
+/// assert_eq!(deserializer.next_value().unwrap(), None);
+/// ```
+pub struct StreamingDeserializer {
+    buffer: Vec<u8>,
+    state: DeserializerState,
+}
+
+impl StreamingDeserializer {
+    /// Creates a new deserializer with no buffered bytes
+    pub fn new() -> StreamingDeserializer {
+        StreamingDeserializer::with_limits(DeserializationLimits::default())
+    }
+
+    /// Creates a new deserializer that fails if any value exceeds the provided limits
+    pub fn with_limits(limits: DeserializationLimits) -> StreamingDeserializer {
+        StreamingDeserializer {
+            buffer: Vec::new(),
+            state: DeserializerState::new(limits),
+        }
+    }
+
+    /// Adds bytes that were received to the end of the stream
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// The number of bytes that have been pushed but not yet returned as part of a value
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the next value if all of its bytes have been received, or `None` if more bytes
+    /// are needed.
+    pub fn next_value(&mut self) -> Result<Option<Amf0Value>, Amf0DeserializationError> {
+        loop {
+            if self.buffer.is_empty() {
+                return Ok(None);
+            }
+
+            let checkpoint = self.state.checkpoint();
+            let mut input = Input::new(&self.buffer);
+            let result = read_next_value(&mut input, &mut self.state);
+            let position = input.position();
+
+            match result {
+                Ok(Some(value)) => {
+                    // Referenced values are read from the buffer, so they need to be copied out
+                    // of it before it's drained.
+                    self.state.detach_references(&input)?;
+                    self.buffer.drain(..position);
+                    return Ok(Some(value));
+                }
+
+                // A stray object end marker between values, which `deserialize()` treats as the
+                // end of the data.  Skip it so values after it are still read.
+                Ok(None) => {
+                    self.buffer.drain(..position);
+                }
+
+                Err(ref error) if position == self.buffer.len() && is_incomplete(error) => {
+                    // Try again from the start of the value once more bytes arrive
+                    self.state.restore(checkpoint);
+                    return Ok(None);
+                }
+
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+impl Default for StreamingDeserializer {
+    fn default() -> Self {
+        StreamingDeserializer::new()
+    }
+}
+
+fn is_incomplete(error: &Amf0DeserializationError) -> bool {
+    match *error {
+        Amf0DeserializationError::UnexpectedEof => true,
+        #[cfg(feature = "std")]
+        Amf0DeserializationError::BufferReadError(ref error) => {
+            error.kind() == ErrorKind::UnexpectedEof
+        }
+
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use serialize;
+    use ObjectProperties;
+
+    #[test]
+    fn values_split_at_every_byte_are_returned_once_complete() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".to_string(), Amf0Value::Utf8String("test".to_string()));
+        properties.insert(
+            "b".to_string(),
+            Amf0Value::StrictArray(vec![Amf0Value::Number(1.0), Amf0Value::Null]),
+        );
+
+        let input = vec![
+            Amf0Value::Object(properties),
+            Amf0Value::Utf8String("second".to_string()),
+        ];
+
+        let bytes = serialize(&input).unwrap();
+        let mut deserializer = StreamingDeserializer::new();
+        let mut results = Vec::new();
+        for byte in bytes {
+            deserializer.push(&[byte]);
+            while let Some(value) = deserializer.next_value().unwrap() {
+                results.push(value);
+            }
+        }
+
+        assert_eq!(results, input);
+        assert_eq!(deserializer.buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn references_resolve_across_values() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".to_string(), Amf0Value::Boolean(true));
+        let mut bytes = serialize(&vec![Amf0Value::Object(properties.clone())]).unwrap();
+        bytes.extend_from_slice(&[7, 0, 0]);
+
+        let mut deserializer = StreamingDeserializer::new();
+        deserializer.push(&bytes[..bytes.len() - 1]);
+        assert_eq!(
+            deserializer.next_value().unwrap(),
+            Some(Amf0Value::Object(properties.clone()))
+        );
+        assert_eq!(deserializer.next_value().unwrap(), None);
+
+        deserializer.push(&bytes[bytes.len() - 1..]);
+        assert_eq!(
+            deserializer.next_value().unwrap(),
+            Some(Amf0Value::Object(properties))
+        );
+    }
+
+    #[test]
+    fn error_returned_for_unknown_marker() {
+        let mut deserializer = StreamingDeserializer::new();
+        deserializer.push(&[0xff]);
+
+        match deserializer.next_value() {
+            Err(Amf0DeserializationError::UnknownMarker { marker: 0xff }) => (),
+            x => panic!("Expected unknown marker error, instead got {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,934 @@
+//! This module contains functionality to deserialize values from bytes
+//! that were encoded via the AMF3 specification
+//! (https://www.adobe.com/content/dam/acom/en/devnet/pdf/amf-file-format-spec.pdf)
+
+use byteorder::{BigEndian, ReadBytesExt};
+use errors::Amf3DeserializationError;
+use markers;
+use rml_amf0::{Amf0DeserializationError, Amf0Value, AvmPlusLimits, DeserializationLimits};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use {Amf3Object, Amf3Value};
+
+// Resolving a reference copies the referenced value, so a small payload that references the
+// same values over and over can expand into an enormous amount of memory.  Limit how many values
+// can be created through references to keep that in check.
+const MAX_VALUES_FROM_REFERENCES: usize = 100_000;
+
+struct Traits {
+    class_name: Option<String>,
+    is_dynamic: bool,
+    sealed_names: Vec<String>,
+}
+
+struct Deserializer<'a, R: Read + 'a> {
+    bytes: &'a mut R,
+    strings: Vec<String>,
+    traits: Vec<Traits>,
+
+    // Objects are added to the table before their members are read, so a `None` entry is an
+    // object that is still being deserialized.  Complete objects are kept along with how many
+    // values they contain.
+    objects: Vec<Option<(Amf3Value, usize)>>,
+
+    limits: DeserializationLimits,
+    depth: usize,
+    values_from_references: usize,
+    max_values_from_references: usize,
+}
+
+/// Turns any readable byte stream and converts it into an array of AMF3 values, using the
+/// default `DeserializationLimits`.  All values share the same reference tables.
+pub fn deserialize<R: Read>(bytes: &mut R) -> Result<Vec<Amf3Value>, Amf3DeserializationError> {
+    deserialize_with_limits(bytes, DeserializationLimits::default())
+}
+
+/// Turns any readable byte stream and converts it into an array of AMF3 values, failing if
+/// the values exceed the specified limits
+pub fn deserialize_with_limits<R: Read>(
+    bytes: &mut R,
+    limits: DeserializationLimits,
+) -> Result<Vec<Amf3Value>, Amf3DeserializationError> {
+    let mut deserializer = Deserializer::new(bytes, limits, MAX_VALUES_FROM_REFERENCES);
+
+    let mut results = vec![];
+    loop {
+        let mut buffer: [u8; 1] = [0];
+        let bytes_read = deserializer.bytes.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+
+        results.push(deserializer.read_value_with_marker(buffer[0])?);
+    }
+
+    Ok(results)
+}
+
+/// Reads a single Amf3 value and converts it to an `Amf0Value`.  This is an `AvmPlusReader`, so
+/// it can be passed to `rml_amf0::deserialize_with_avmplus()` to read Amf0 data that switches to
+/// Amf3 via avmplus object markers.  The value is kept within the limits left over from the
+/// Amf0 payload, and any values its references create are taken out of the payload's budget.
+///
+/// # Examples
+/// ```
+/// extern crate rml_amf0;
+/// extern crate rml_amf3;
+///
+/// use rml_amf0::{deserialize_with_avmplus, Amf0Value, DeserializationLimits};
+/// use rml_amf3::read_avmplus_value;
+/// use std::io::Cursor;
+///
+/// // An Amf0 string followed by an avmplus marker and an Amf3 integer
+/// let bytes = vec![0x02, 0x00, 0x01, b'a', 0x11, 0x04, 0x05];
+/// let mut cursor = Cursor::new(bytes);
+/// let values =
+///     deserialize_with_avmplus(&mut cursor, DeserializationLimits::default(), read_avmplus_value)
+///         .unwrap();
+///
+/// assert_eq!(values, vec![Amf0Value::Utf8String("a".to_string()), Amf0Value::Number(5.0)]);
+/// ```
+pub fn read_avmplus_value(
+    mut bytes: &mut dyn Read,
+    limits: &mut AvmPlusLimits,
+) -> Result<Amf0Value, Amf0DeserializationError> {
+    // Each switch to Amf3 starts with empty reference tables
+    let mut deserializer = Deserializer::new(
+        &mut bytes,
+        limits.limits.clone(),
+        limits.remaining_values_from_references,
+    );
+
+    let result = deserializer.read_value();
+    limits.remaining_values_from_references -= deserializer.values_from_references;
+
+    match result {
+        Ok(value) => Ok(Amf0Value::from(value)),
+        Err(error) => Err(Amf0DeserializationError::InvalidAvmPlusValue(Box::new(
+            error,
+        ))),
+    }
+}
+
+impl<'a, R: Read> Deserializer<'a, R> {
+    fn new(
+        bytes: &'a mut R,
+        limits: DeserializationLimits,
+        max_values_from_references: usize,
+    ) -> Deserializer<'a, R> {
+        Deserializer {
+            bytes,
+            strings: Vec::new(),
+            traits: Vec::new(),
+            objects: Vec::new(),
+            limits,
+            depth: 0,
+            values_from_references: 0,
+            max_values_from_references,
+        }
+    }
+
+    fn read_value(&mut self) -> Result<Amf3Value, Amf3DeserializationError> {
+        let marker = self.bytes.read_u8()?;
+        self.read_value_with_marker(marker)
+    }
+
+    fn read_value_with_marker(
+        &mut self,
+        marker: u8,
+    ) -> Result<Amf3Value, Amf3DeserializationError> {
+        match marker {
+            markers::UNDEFINED_MARKER => Ok(Amf3Value::Undefined),
+            markers::NULL_MARKER => Ok(Amf3Value::Null),
+            markers::FALSE_MARKER => Ok(Amf3Value::Boolean(false)),
+            markers::TRUE_MARKER => Ok(Amf3Value::Boolean(true)),
+            markers::INTEGER_MARKER => self.parse_integer(),
+            markers::DOUBLE_MARKER => Ok(Amf3Value::Double(self.bytes.read_f64::<BigEndian>()?)),
+            markers::STRING_MARKER => Ok(Amf3Value::Utf8String(self.read_string()?)),
+            markers::XML_DOCUMENT_MARKER => self.parse_xml(Amf3Value::XmlDocument),
+            markers::DATE_MARKER => self.parse_date(),
+            markers::ARRAY_MARKER => self.parse_array(),
+            markers::OBJECT_MARKER => self.parse_object(),
+            markers::XML_MARKER => self.parse_xml(Amf3Value::Xml),
+            markers::BYTE_ARRAY_MARKER => self.parse_byte_array(),
+            _ => Err(Amf3DeserializationError::UnknownMarker { marker }),
+        }
+    }
+
+    fn read_u29(&mut self) -> Result<u32, Amf3DeserializationError> {
+        // The first 3 bytes contribute 7 bits each with the high bit flagging if another byte
+        // follows, and a 4th byte contributes all 8 of its bits.
+        let mut result = 0_u32;
+        for index in 0..4 {
+            let byte = self.bytes.read_u8()? as u32;
+            if index == 3 {
+                result = (result << 8) | byte;
+                break;
+            }
+
+            result = (result << 7) | (byte & 0x7f);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Reads a U29 value whose lowest bit flags if the value is inline (1) or a reference (0),
+    /// returning the remaining bits and if it was inline.
+    fn read_reference_flagged_u29(&mut self) -> Result<(usize, bool), Amf3DeserializationError> {
+        let value = self.read_u29()?;
+        Ok(((value >> 1) as usize, value & 1 == 1))
+    }
+
+    fn parse_integer(&mut self) -> Result<Amf3Value, Amf3DeserializationError> {
+        let value = self.read_u29()?;
+
+        // Sign extend the 29 bit value
+        let value = if value & 0x1000_0000 != 0 {
+            value as i32 - 0x2000_0000
+        } else {
+            value as i32
+        };
+
+        Ok(Amf3Value::Integer(value))
+    }
+
+    fn read_string(&mut self) -> Result<String, Amf3DeserializationError> {
+        let (value, is_inline) = self.read_reference_flagged_u29()?;
+        if !is_inline {
+            return match self.strings.get(value) {
+                Some(string) => {
+                    let string = string.clone();
+                    self.add_values_from_reference(1)?;
+                    Ok(string)
+                }
+
+                None => Err(Amf3DeserializationError::InvalidReference { index: value }),
+            };
+        }
+
+        self.check_string_length(value)?;
+        let string = self.read_utf8(value)?;
+        if !string.is_empty() {
+            // Empty strings are never sent as references
+            self.strings.push(string.clone());
+        }
+
+        Ok(string)
+    }
+
+    fn read_utf8(&mut self, length: usize) -> Result<String, Amf3DeserializationError> {
+        Ok(String::from_utf8(self.read_bytes(length)?)?)
+    }
+
+    /// Reads bytes whose count came from the input.  The buffer grows as bytes arrive rather
+    /// than being allocated up front, so a bogus length can't allocate more memory than the
+    /// input actually contains.
+    fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>, Amf3DeserializationError> {
+        let mut buffer = Vec::new();
+        (&mut *self.bytes)
+            .take(length as u64)
+            .read_to_end(&mut buffer)?;
+
+        if buffer.len() < length {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        Ok(buffer)
+    }
+
+    fn get_object_reference(
+        &mut self,
+        index: usize,
+    ) -> Result<Amf3Value, Amf3DeserializationError> {
+        let (value, count) = match self.objects.get(index) {
+            Some(Some((value, count))) => (value.clone(), *count),
+            Some(None) => return Err(Amf3DeserializationError::CyclicReference { index }),
+            None => return Err(Amf3DeserializationError::InvalidReference { index }),
+        };
+
+        self.add_values_from_reference(count)?;
+        Ok(value)
+    }
+
+    fn add_object(&mut self, index: usize, value: &Amf3Value) {
+        self.objects[index] = Some((value.clone(), count_values(value)));
+    }
+
+    fn add_values_from_reference(&mut self, count: usize) -> Result<(), Amf3DeserializationError> {
+        if count > self.max_values_from_references - self.values_from_references {
+            return Err(Amf3DeserializationError::TooManyReferencedValues);
+        }
+
+        self.values_from_references += count;
+        Ok(())
+    }
+
+    fn enter_nested_value(&mut self) -> Result<(), Amf3DeserializationError> {
+        if self.depth >= self.limits.max_depth {
+            return Err(Amf3DeserializationError::NestingTooDeep {
+                max_depth: self.limits.max_depth,
+            });
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn exit_nested_value(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn check_string_length(&self, length: usize) -> Result<(), Amf3DeserializationError> {
+        if length > self.limits.max_string_length {
+            return Err(Amf3DeserializationError::StringTooLong {
+                length,
+                max_length: self.limits.max_string_length,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn check_element_count(&self, count: usize) -> Result<(), Amf3DeserializationError> {
+        if count > self.limits.max_element_count {
+            return Err(Amf3DeserializationError::TooManyElements {
+                max_count: self.limits.max_element_count,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn parse_xml<F>(&mut self, create: F) -> Result<Amf3Value, Amf3DeserializationError>
+    where
+        F: Fn(String) -> Amf3Value,
+    {
+        let (value, is_inline) = self.read_reference_flagged_u29()?;
+        if !is_inline {
+            return self.get_object_reference(value);
+        }
+
+        self.check_string_length(value)?;
+        let xml = create(self.read_utf8(value)?);
+        self.objects.push(Some((xml.clone(), 1)));
+        Ok(xml)
+    }
+
+    fn parse_date(&mut self) -> Result<Amf3Value, Amf3DeserializationError> {
+        let (value, is_inline) = self.read_reference_flagged_u29()?;
+        if !is_inline {
+            return self.get_object_reference(value);
+        }
+
+        let date = Amf3Value::Date {
+            unix_time_millis: self.bytes.read_f64::<BigEndian>()?,
+        };
+
+        self.objects.push(Some((date.clone(), 1)));
+        Ok(date)
+    }
+
+    fn parse_byte_array(&mut self) -> Result<Amf3Value, Amf3DeserializationError> {
+        let (value, is_inline) = self.read_reference_flagged_u29()?;
+        if !is_inline {
+            return self.get_object_reference(value);
+        }
+
+        let byte_array = Amf3Value::ByteArray(self.read_bytes(value)?);
+        self.objects.push(Some((byte_array.clone(), 1)));
+        Ok(byte_array)
+    }
+
+    fn parse_array(&mut self) -> Result<Amf3Value, Amf3DeserializationError> {
+        let (dense_count, is_inline) = self.read_reference_flagged_u29()?;
+        if !is_inline {
+            return self.get_object_reference(dense_count);
+        }
+
+        self.check_element_count(dense_count)?;
+        self.enter_nested_value()?;
+        let object_index = self.objects.len();
+        self.objects.push(None);
+
+        // The associative portion is terminated by an empty key
+        let mut associative = HashMap::new();
+        loop {
+            let key = self.read_string()?;
+            if key.is_empty() {
+                break;
+            }
+
+            let value = self.read_value()?;
+            associative.insert(key, value);
+            self.check_element_count(associative.len())?;
+        }
+
+        let mut dense = Vec::new();
+        for _ in 0..dense_count {
+            dense.push(self.read_value()?);
+        }
+
+        self.exit_nested_value();
+        let array = Amf3Value::Array { associative, dense };
+        self.add_object(object_index, &array);
+        Ok(array)
+    }
+
+    fn parse_object(&mut self) -> Result<Amf3Value, Amf3DeserializationError> {
+        let value = self.read_u29()?;
+        if value & 1 == 0 {
+            return self.get_object_reference((value >> 1) as usize);
+        }
+
+        let traits_index = if value & 2 == 0 {
+            let index = (value >> 2) as usize;
+            if index >= self.traits.len() {
+                return Err(Amf3DeserializationError::InvalidReference { index });
+            }
+
+            index
+        } else {
+            let class_name = self.read_string()?;
+            if value & 4 != 0 {
+                return Err(Amf3DeserializationError::ExternalizableObject { class_name });
+            }
+
+            let sealed_count = (value >> 4) as usize;
+            self.check_element_count(sealed_count)?;
+            let mut sealed_names = Vec::new();
+            for _ in 0..sealed_count {
+                sealed_names.push(self.read_string()?);
+            }
+
+            self.traits.push(Traits {
+                class_name: if class_name.is_empty() {
+                    None
+                } else {
+                    Some(class_name)
+                },
+                is_dynamic: value & 8 != 0,
+                sealed_names,
+            });
+
+            self.traits.len() - 1
+        };
+
+        self.enter_nested_value()?;
+        let object_index = self.objects.len();
+        self.objects.push(None);
+
+        let sealed_names = self.traits[traits_index].sealed_names.clone();
+        let mut sealed_properties = Vec::with_capacity(sealed_names.len());
+        for name in sealed_names {
+            let value = self.read_value()?;
+            sealed_properties.push((name, value));
+        }
+
+        let mut dynamic_properties = HashMap::new();
+        if self.traits[traits_index].is_dynamic {
+            loop {
+                let key = self.read_string()?;
+                if key.is_empty() {
+                    break;
+                }
+
+                let value = self.read_value()?;
+                dynamic_properties.insert(key, value);
+                self.check_element_count(dynamic_properties.len())?;
+            }
+        }
+
+        self.exit_nested_value();
+
+        let traits = &self.traits[traits_index];
+        let object = Amf3Value::Object(Amf3Object {
+            class_name: traits.class_name.clone(),
+            is_dynamic: traits.is_dynamic,
+            sealed_properties,
+            dynamic_properties,
+        });
+
+        self.add_object(object_index, &object);
+        Ok(object)
+    }
+}
+
+fn count_values(value: &Amf3Value) -> usize {
+    match *value {
+        Amf3Value::Array {
+            ref associative,
+            ref dense,
+        } => {
+            1 + associative.values().map(count_values).sum::<usize>()
+                + dense.iter().map(count_values).sum::<usize>()
+        }
+
+        Amf3Value::Object(ref object) => {
+            1 + object
+                .sealed_properties
+                .iter()
+                .map(|(_, value)| count_values(value))
+                .sum::<usize>()
+                + object
+                    .dynamic_properties
+                    .values()
+                    .map(count_values)
+                    .sum::<usize>()
+        }
+
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deserialize, deserialize_with_limits, read_avmplus_value};
+    use errors::Amf3DeserializationError;
+    use markers;
+    use rml_amf0::{
+        deserialize_with_avmplus, Amf0DeserializationError, Amf0Value, AvmPlusLimits,
+        DeserializationLimits,
+    };
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use {Amf3Object, Amf3Value};
+
+    const AMF0_STRICT_ARRAY_MARKER: u8 = 0x0a;
+    const AMF0_AVMPLUS_OBJECT_MARKER: u8 = 0x11;
+
+    fn avmplus_limits() -> AvmPlusLimits {
+        AvmPlusLimits {
+            limits: DeserializationLimits::default(),
+            remaining_values_from_references: 100_000,
+        }
+    }
+
+    /// Gets the amf3 error an avmplus value failed with
+    fn get_amf3_error(error: &Amf0DeserializationError) -> &Amf3DeserializationError {
+        match *error {
+            Amf0DeserializationError::InvalidAvmPlusValue(ref inner) => inner
+                .downcast_ref::<Amf3DeserializationError>()
+                .expect("Expected an amf3 error"),
+
+            ref x => panic!("Expected invalid avmplus value error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn can_deserialize_integers() {
+        let vector = vec![
+            markers::INTEGER_MARKER,
+            0x05,
+            markers::INTEGER_MARKER,
+            0x81,
+            0x00,
+            markers::INTEGER_MARKER,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            markers::INTEGER_MARKER,
+            0xbf,
+            0xff,
+            0xff,
+            0xff,
+        ];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![
+            Amf3Value::Integer(5),
+            Amf3Value::Integer(128),
+            Amf3Value::Integer(-1),
+            Amf3Value::Integer(0x0fff_ffff),
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_double() {
+        let mut vector = vec![markers::DOUBLE_MARKER];
+        vector.extend_from_slice(&1.5_f64.to_bits().to_be_bytes());
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        assert_eq!(result, vec![Amf3Value::Double(1.5)]);
+    }
+
+    #[test]
+    fn can_deserialize_string_and_string_reference() {
+        let mut vector = vec![markers::STRING_MARKER, (4 << 1) | 1];
+        vector.extend("test".as_bytes());
+        vector.push(markers::STRING_MARKER);
+        vector.push(0); // reference to index 0
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![
+            Amf3Value::Utf8String("test".to_string()),
+            Amf3Value::Utf8String("test".to_string()),
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_date() {
+        let mut vector = vec![markers::DATE_MARKER, 1];
+        vector.extend_from_slice(&1000.0_f64.to_bits().to_be_bytes());
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![Amf3Value::Date {
+            unix_time_millis: 1000.0,
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_byte_array_and_object_reference() {
+        let vector = vec![
+            markers::BYTE_ARRAY_MARKER,
+            (3 << 1) | 1,
+            1,
+            2,
+            3,
+            markers::BYTE_ARRAY_MARKER,
+            0, // reference to object 0
+        ];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![
+            Amf3Value::ByteArray(vec![1, 2, 3]),
+            Amf3Value::ByteArray(vec![1, 2, 3]),
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_array() {
+        let mut vector = vec![markers::ARRAY_MARKER, (1 << 1) | 1, (1 << 1) | 1];
+        vector.extend("a".as_bytes());
+        vector.push(markers::TRUE_MARKER);
+        vector.push(1); // empty string ends the associative portion
+        vector.push(markers::INTEGER_MARKER);
+        vector.push(7);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let mut associative = HashMap::new();
+        associative.insert("a".to_string(), Amf3Value::Boolean(true));
+        let expected = vec![Amf3Value::Array {
+            associative,
+            dense: vec![Amf3Value::Integer(7)],
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_typed_objects_with_traits_reference() {
+        // Traits with 1 sealed member, not dynamic, inline
+        let mut vector = vec![markers::OBJECT_MARKER, (1 << 4) | 3, (4 << 1) | 1];
+        vector.extend("Test".as_bytes());
+        vector.push((1 << 1) | 1);
+        vector.extend("a".as_bytes());
+        vector.push(markers::INTEGER_MARKER);
+        vector.push(1);
+
+        // Second object using a reference to traits 0
+        vector.push(markers::OBJECT_MARKER);
+        vector.push(1);
+        vector.push(markers::INTEGER_MARKER);
+        vector.push(2);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let create_object = |value| {
+            Amf3Value::Object(Amf3Object {
+                class_name: Some("Test".to_string()),
+                is_dynamic: false,
+                sealed_properties: vec![("a".to_string(), Amf3Value::Integer(value))],
+                dynamic_properties: HashMap::new(),
+            })
+        };
+
+        assert_eq!(result, vec![create_object(1), create_object(2)]);
+    }
+
+    #[test]
+    fn can_deserialize_anonymous_dynamic_object() {
+        let mut vector = vec![markers::OBJECT_MARKER, 0x0b, 1, (1 << 1) | 1];
+        vector.extend("b".as_bytes());
+        vector.push(markers::NULL_MARKER);
+        vector.push(1);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let mut properties = HashMap::new();
+        properties.insert("b".to_string(), Amf3Value::Null);
+
+        assert_eq!(
+            result,
+            vec![Amf3Value::Object(Amf3Object::anonymous(properties))]
+        );
+    }
+
+    #[test]
+    fn error_when_object_references_itself() {
+        // Anonymous dynamic object whose property "a" is a reference to object 0
+        let mut vector = vec![markers::OBJECT_MARKER, 0x0b, 1, (1 << 1) | 1];
+        vector.extend("a".as_bytes());
+        vector.push(markers::OBJECT_MARKER);
+        vector.push(0);
+
+        let mut input = Cursor::new(vector);
+        match deserialize(&mut input) {
+            Err(Amf3DeserializationError::CyclicReference { index: 0 }) => (),
+            x => panic!("Expected cyclic reference error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_string_reference_is_invalid() {
+        let vector = vec![markers::STRING_MARKER, 2 << 1];
+
+        let mut input = Cursor::new(vector);
+        match deserialize(&mut input) {
+            Err(Amf3DeserializationError::InvalidReference { index: 2 }) => (),
+            x => panic!("Expected invalid reference error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_byte_array_is_longer_than_the_input() {
+        // A length near the largest a U29 can hold, followed by only a few bytes
+        let vector = vec![markers::BYTE_ARRAY_MARKER, 0xff, 0xff, 0xff, 0xff, 1, 2, 3];
+
+        let mut input = Cursor::new(vector);
+        match deserialize(&mut input) {
+            Err(Amf3DeserializationError::BufferReadError(_)) => (),
+            x => panic!("Expected buffer read error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_string_is_longer_than_limit() {
+        let mut vector = vec![markers::STRING_MARKER, (4 << 1) | 1];
+        vector.extend("test".as_bytes());
+
+        let limits = DeserializationLimits {
+            max_string_length: 3,
+            ..DeserializationLimits::default()
+        };
+
+        let mut input = Cursor::new(vector);
+        match deserialize_with_limits(&mut input, limits) {
+            Err(Amf3DeserializationError::StringTooLong {
+                length: 4,
+                max_length: 3,
+            }) => (),
+            x => panic!("Expected string too long error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_array_has_more_elements_than_limit() {
+        let vector = vec![markers::ARRAY_MARKER, 0xbf, 0xff, 0xff, 0xff];
+
+        let mut input = Cursor::new(vector);
+        match deserialize(&mut input) {
+            Err(Amf3DeserializationError::TooManyElements { .. }) => (),
+            x => panic!("Expected too many elements error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_arrays_are_nested_too_deeply() {
+        // Arrays with one dense element, each containing the next array
+        let mut vector = Vec::new();
+        for _ in 0..10_000 {
+            vector.extend_from_slice(&[markers::ARRAY_MARKER, (1 << 1) | 1, 1]);
+        }
+
+        vector.push(markers::NULL_MARKER);
+
+        let mut input = Cursor::new(vector);
+        match deserialize(&mut input) {
+            Err(Amf3DeserializationError::NestingTooDeep { max_depth: 100 }) => (),
+            x => panic!("Expected nesting too deep error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_references_expand_into_too_many_values() {
+        // Array 0 contains 10 integers, and every array after it contains 10 references to the
+        // array before it, so each array has roughly 10 times the values of the last.
+        let mut vector = vec![markers::ARRAY_MARKER, (10 << 1) | 1, 1];
+        for _ in 0..10 {
+            vector.extend_from_slice(&[markers::INTEGER_MARKER, 0]);
+        }
+
+        for index in 0..5 {
+            vector.extend_from_slice(&[markers::ARRAY_MARKER, (10 << 1) | 1, 1]);
+            for _ in 0..10 {
+                vector.extend_from_slice(&[markers::ARRAY_MARKER, index << 1]);
+            }
+        }
+
+        let mut input = Cursor::new(vector);
+        match deserialize(&mut input) {
+            Err(Amf3DeserializationError::TooManyReferencedValues) => (),
+            x => panic!(
+                "Expected too many referenced values error, instead got {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn avmplus_value_is_converted_to_amf0() {
+        let mut vector = vec![markers::STRING_MARKER, (4 << 1) | 1];
+        vector.extend("test".as_bytes());
+
+        let mut input = Cursor::new(vector);
+        let result = read_avmplus_value(&mut input, &mut avmplus_limits()).unwrap();
+
+        assert_eq!(result, Amf0Value::Utf8String("test".to_string()));
+    }
+
+    #[test]
+    fn error_when_avmplus_value_is_invalid() {
+        let mut input = Cursor::new(vec![0xff]);
+        match read_avmplus_value(&mut input, &mut avmplus_limits()) {
+            Err(error @ Amf0DeserializationError::InvalidAvmPlusValue(_)) => {
+                assert!(
+                    std::error::Error::source(&error).is_some(),
+                    "Expected the amf3 error to be kept as the source"
+                );
+            }
+
+            x => panic!("Expected invalid avmplus value error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn avmplus_value_counts_towards_amf0_nesting_depth() {
+        // Two amf0 strict arrays, with the inner one containing amf3 arrays nested two deep
+        let vector = vec![
+            AMF0_STRICT_ARRAY_MARKER,
+            0,
+            0,
+            0,
+            1,
+            AMF0_STRICT_ARRAY_MARKER,
+            0,
+            0,
+            0,
+            1,
+            AMF0_AVMPLUS_OBJECT_MARKER,
+            markers::ARRAY_MARKER,
+            (1 << 1) | 1,
+            1,
+            markers::ARRAY_MARKER,
+            (1 << 1) | 1,
+            1,
+            markers::NULL_MARKER,
+        ];
+
+        let limits = DeserializationLimits {
+            max_depth: 3,
+            ..DeserializationLimits::default()
+        };
+
+        let mut input = Cursor::new(vector);
+        let error = deserialize_with_avmplus(&mut input, limits, read_avmplus_value).unwrap_err();
+        match *get_amf3_error(&error) {
+            Amf3DeserializationError::NestingTooDeep { max_depth: 1 } => (),
+            ref x => panic!("Expected nesting too deep error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn avmplus_values_share_the_reference_budget() {
+        // Each avmplus value is an array chain where every array holds references to the one
+        // before it, creating about 68,000 values through references.  That is within the
+        // budget on its own, but not twice.
+        let mut vector = Vec::new();
+        for _ in 0..2 {
+            // Array 0 holds arrays 1 through 4, followed by 5 references to array 4
+            vector.extend_from_slice(&[
+                AMF0_AVMPLUS_OBJECT_MARKER,
+                markers::ARRAY_MARKER,
+                (9 << 1) | 1,
+                1,
+            ]);
+
+            vector.extend_from_slice(&[markers::ARRAY_MARKER, (10 << 1) | 1, 1]);
+            vector.extend_from_slice(&[markers::INTEGER_MARKER, 0].repeat(10));
+            for index in 1..4 {
+                vector.extend_from_slice(&[markers::ARRAY_MARKER, (10 << 1) | 1, 1]);
+                vector.extend_from_slice(&[markers::ARRAY_MARKER, index << 1].repeat(10));
+            }
+
+            vector.extend_from_slice(&[markers::ARRAY_MARKER, 4 << 1].repeat(5));
+        }
+
+        let mut input = Cursor::new(vector);
+        let error = deserialize_with_avmplus(
+            &mut input,
+            DeserializationLimits::default(),
+            read_avmplus_value,
+        )
+        .unwrap_err();
+
+        match *get_amf3_error(&error) {
+            Amf3DeserializationError::TooManyReferencedValues => (),
+            ref x => panic!(
+                "Expected too many referenced values error, instead got {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn error_when_avmplus_byte_array_is_longer_than_the_payload() {
+        let vector = vec![
+            AMF0_AVMPLUS_OBJECT_MARKER,
+            markers::BYTE_ARRAY_MARKER,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            1,
+        ];
+
+        let mut input = Cursor::new(vector);
+        let error = deserialize_with_avmplus(
+            &mut input,
+            DeserializationLimits::default(),
+            read_avmplus_value,
+        )
+        .unwrap_err();
+
+        match *get_amf3_error(&error) {
+            Amf3DeserializationError::BufferReadError(_) => (),
+            ref x => panic!("Expected buffer read error, instead got {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::{io, string};
+use thiserror::Error;
+
+/// Errors that can occur during the deserialization process
+#[derive(Debug, Error)]
+pub enum Amf3DeserializationError {
+    /// Every Amf3 value starts with a marker byte describing the type of value that was
+    /// encoded.  For example a marker of `0x04` is an integer, `0x06` is a string, etc..
+    ///
+    /// This error is encountered when we see a maker value that we do not recognize or support.
+    #[error("Encountered unknown marker: {marker}")]
+    UnknownMarker { marker: u8 },
+
+    /// Strings, objects, and traits can be sent as references to values that were previously
+    /// deserialized.  This error is raised when a reference points to a value that does not exist.
+    #[error("Encountered a reference to index {index} which has not been deserialized")]
+    InvalidReference { index: usize },
+
+    /// This is raised when an object references itself (directly or indirectly), as the
+    /// resulting value could not be represented as an owned `Amf3Value`.
+    #[error("Encountered a reference to object index {index} while it was being deserialized")]
+    CyclicReference { index: usize },
+
+    /// Externalizable objects rely on custom serialization logic defined by the class that sent
+    /// them, and therefore can not be read generically.
+    #[error("Externalizable object of class '{class_name}' cannot be deserialized")]
+    ExternalizableObject { class_name: String },
+
+    /// Resolving references copies the referenced values, and too many values were created
+    /// this way.  This protects against small payloads expanding into enormous structures.
+    #[error("Too many values were created by resolving references")]
+    TooManyReferencedValues,
+
+    /// Arrays and objects were nested more deeply than the deserialization limits allow
+    #[error("Values were nested more than {max_depth} levels deep")]
+    NestingTooDeep { max_depth: usize },
+
+    /// A string's length was larger than the deserialization limits allow.  This is raised
+    /// before the string is read, so the length may not match the bytes that actually follow.
+    #[error("String length of {length} is greater than the maximum of {max_length}")]
+    StringTooLong { length: usize, max_length: usize },
+
+    /// An array or object contained more elements than the deserialization limits allow
+    #[error("Array or object contained more than {max_count} elements")]
+    TooManyElements { max_count: usize },
+
+    /// An I/O Error occurred while reading the data buffer
+    #[error("Failed to read byte buffer: {0}")]
+    BufferReadError(#[from] io::Error),
+
+    /// Strings in AMF3 are UTF-8 encoded, so if the bytes read are not valid
+    /// UTF-8 this error will be raised.
+    #[error("Failed to read a utf8 string from the byte buffer: {0}")]
+    StringParseError(#[from] string::FromUtf8Error),
+}
+
+/// Errors raised during to the serialization process
+#[derive(Debug, Error)]
+pub enum Amf3SerializationError {
+    /// Lengths and reference indexes in Amf3 are encoded as 29 bit integers with the lowest bit
+    /// used as a flag, so lengths of 268,435,456 or more can not be represented.
+    #[error("Length of {length} is too large to be encoded")]
+    LengthTooLarge { length: usize },
+
+    /// An I/O error occurred while writing to the output buffer.
+    #[error("Failed to write to byte buffer")]
+    BufferWriteError(#[from] io::Error),
+}
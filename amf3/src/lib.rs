@@ -0,0 +1,328 @@
+//! This crate provides functionality for serializing and deserializing data
+//! based on the Adobe AMF3 encoding specification located at
+//! <https://www.adobe.com/content/dam/acom/en/devnet/pdf/amf-file-format-spec.pdf>
+//!
+//! Strings, objects and object traits are tracked in reference tables, so repeated values are
+//! sent as references when serializing and references are resolved when deserializing.  The
+//! reference tables only live for a single call to `serialize()` or `deserialize()`.
+//!
+//! # Examples
+//! ```
+//! use std::io::Cursor;
+//! use std::collections::HashMap;
+//! use rml_amf3::{Amf3Value, Amf3Object, serialize, deserialize};
+//!
+//! // Put some data into the Amf3Value types
+//! let mut properties = HashMap::new();
+//! properties.insert("app".to_string(), Amf3Value::Integer(99));
+//! properties.insert("second".to_string(), Amf3Value::Utf8String("test".to_string()));
+//!
+//! let value1 = Amf3Value::Double(32.5);
+//! let value2 = Amf3Value::ByteArray(vec![1, 2, 3]);
+//! let object = Amf3Value::Object(Amf3Object::anonymous(properties));
+//!
+//! let input = vec![value1, object, value2];
+//!
+//! // Serialize the values into a vector of bytes
+//! let serialized_data = serialize(&input).unwrap();
+//!
+//! // Deserialize the vector of bytes back into Amf3Value types
+//! let mut serialized_cursor = Cursor::new(serialized_data);
+//! let results = deserialize(&mut serialized_cursor).unwrap();
+//!
+//! assert_eq!(input, results);
+//! ```
+
+extern crate byteorder;
+extern crate rml_amf0;
+extern crate thiserror;
+
+mod deserialization;
+mod errors;
+mod serialization;
+
+pub use deserialization::{deserialize, deserialize_with_limits, read_avmplus_value};
+pub use errors::{Amf3DeserializationError, Amf3SerializationError};
+pub use serialization::serialize;
+
+use rml_amf0::{Amf0Value, ObjectProperties};
+use std::collections::HashMap;
+
+/// An Enum representing the different supported types of Amf3 values
+#[derive(PartialEq, Debug, Clone)]
+pub enum Amf3Value {
+    Undefined,
+    Null,
+    Boolean(bool),
+
+    /// A 29 bit signed integer.  Integers outside of that range are serialized as doubles.
+    Integer(i32),
+    Double(f64),
+    Utf8String(String),
+    XmlDocument(String),
+
+    /// A date represented as the number of milliseconds since the unix epoch (in UTC)
+    Date {
+        unix_time_millis: f64,
+    },
+
+    /// An array with both an associative (string keyed) portion and a dense portion
+    Array {
+        associative: HashMap<String, Amf3Value>,
+        dense: Vec<Amf3Value>,
+    },
+
+    Object(Amf3Object),
+    Xml(String),
+    ByteArray(Vec<u8>),
+}
+
+/// An Amf3 object, along with the traits that describe it
+#[derive(PartialEq, Debug, Clone)]
+pub struct Amf3Object {
+    /// The name of the object's class, or `None` for anonymous objects
+    pub class_name: Option<String>,
+
+    /// If true then the object can contain dynamic properties in addition to its sealed ones
+    pub is_dynamic: bool,
+
+    /// Properties that are part of the object's traits, in the order they are defined
+    pub sealed_properties: Vec<(String, Amf3Value)>,
+
+    /// Properties that were added to a dynamic object.  These are not serialized if the object
+    /// is not dynamic.
+    pub dynamic_properties: HashMap<String, Amf3Value>,
+}
+
+impl Amf3Object {
+    /// Creates an anonymous dynamic object with the specified properties, which is the Amf3
+    /// equivalent of an Amf0 object.
+    pub fn anonymous(properties: HashMap<String, Amf3Value>) -> Amf3Object {
+        Amf3Object {
+            class_name: None,
+            is_dynamic: true,
+            sealed_properties: Vec::new(),
+            dynamic_properties: properties,
+        }
+    }
+}
+
+impl From<Amf0Value> for Amf3Value {
+    fn from(value: Amf0Value) -> Self {
+        match value {
+            Amf0Value::Number(value) => Amf3Value::Double(value),
+            Amf0Value::Boolean(value) => Amf3Value::Boolean(value),
+            Amf0Value::Utf8String(value) => Amf3Value::Utf8String(value),
+            Amf0Value::Null => Amf3Value::Null,
+            Amf0Value::Undefined => Amf3Value::Undefined,
+            Amf0Value::XmlDocument(value) => Amf3Value::XmlDocument(value),
+            Amf0Value::Date { unix_time, .. } => Amf3Value::Date {
+                unix_time_millis: rml_amf0::unix_time_millis(&unix_time),
+            },
+
+            Amf0Value::StrictArray(values) => Amf3Value::Array {
+                associative: HashMap::new(),
+                dense: values.into_iter().map(Amf3Value::from).collect(),
+            },
+
+            Amf0Value::Object(properties) => {
+                let properties = properties
+                    .into_iter()
+                    .map(|(key, value)| (key, Amf3Value::from(value)))
+                    .collect();
+
+                Amf3Value::Object(Amf3Object::anonymous(properties))
+            }
+
+            Amf0Value::TypedObject {
+                class_name,
+                properties,
+            } => {
+                let properties = properties
+                    .into_iter()
+                    .map(|(key, value)| (key, Amf3Value::from(value)))
+                    .collect();
+
+                let mut object = Amf3Object::anonymous(properties);
+                object.class_name = Some(class_name);
+                Amf3Value::Object(object)
+            }
+        }
+    }
+}
+
+/// Converts Amf3 values to their closest Amf0 equivalent.  Amf0 has fewer types than Amf3 so
+/// this conversion is lossy:
+///
+/// * Integers and doubles both become numbers
+/// * Both xml types become xml documents
+/// * Dates that can't be represented as a `SystemTime` become the number of milliseconds since
+///   the unix epoch
+/// * Byte arrays become strict arrays of numbers
+/// * Arrays with an associative portion become objects, with dense values keyed by their index
+/// * Objects have their sealed and dynamic properties combined, and become typed objects if
+///   they have a class name
+impl From<Amf3Value> for Amf0Value {
+    fn from(value: Amf3Value) -> Self {
+        match value {
+            Amf3Value::Undefined => Amf0Value::Undefined,
+            Amf3Value::Null => Amf0Value::Null,
+            Amf3Value::Boolean(value) => Amf0Value::Boolean(value),
+            Amf3Value::Integer(value) => Amf0Value::Number(value as f64),
+            Amf3Value::Double(value) => Amf0Value::Number(value),
+            Amf3Value::Utf8String(value) => Amf0Value::Utf8String(value),
+            Amf3Value::XmlDocument(value) => Amf0Value::XmlDocument(value),
+            Amf3Value::Xml(value) => Amf0Value::XmlDocument(value),
+            Amf3Value::Date { unix_time_millis } => {
+                match rml_amf0::from_unix_time_millis(unix_time_millis) {
+                    Some(unix_time) => Amf0Value::Date {
+                        unix_time,
+                        time_zone: 0,
+                    },
+                    None => Amf0Value::Number(unix_time_millis),
+                }
+            }
+
+            Amf3Value::ByteArray(bytes) => Amf0Value::StrictArray(
+                bytes
+                    .into_iter()
+                    .map(|x| Amf0Value::Number(x as f64))
+                    .collect(),
+            ),
+
+            Amf3Value::Array { associative, dense } => {
+                if associative.is_empty() {
+                    return Amf0Value::StrictArray(
+                        dense.into_iter().map(Amf0Value::from).collect(),
+                    );
+                }
+
+                let mut properties: ObjectProperties = associative
+                    .into_iter()
+                    .map(|(key, value)| (key, Amf0Value::from(value)))
+                    .collect();
+
+                for (index, value) in dense.into_iter().enumerate() {
+                    properties.insert(index.to_string(), Amf0Value::from(value));
+                }
+
+                Amf0Value::Object(properties)
+            }
+
+            Amf3Value::Object(object) => {
+                let properties = object
+                    .sealed_properties
+                    .into_iter()
+                    .chain(object.dynamic_properties)
+                    .map(|(key, value)| (key, Amf0Value::from(value)))
+                    .collect();
+
+                match object.class_name {
+                    Some(class_name) => Amf0Value::TypedObject {
+                        class_name,
+                        properties,
+                    },
+                    None => Amf0Value::Object(properties),
+                }
+            }
+        }
+    }
+}
+
+mod markers {
+    pub const UNDEFINED_MARKER: u8 = 0x00;
+    pub const NULL_MARKER: u8 = 0x01;
+    pub const FALSE_MARKER: u8 = 0x02;
+    pub const TRUE_MARKER: u8 = 0x03;
+    pub const INTEGER_MARKER: u8 = 0x04;
+    pub const DOUBLE_MARKER: u8 = 0x05;
+    pub const STRING_MARKER: u8 = 0x06;
+    pub const XML_DOCUMENT_MARKER: u8 = 0x07;
+    pub const DATE_MARKER: u8 = 0x08;
+    pub const ARRAY_MARKER: u8 = 0x09;
+    pub const OBJECT_MARKER: u8 = 0x0a;
+    pub const XML_MARKER: u8 = 0x0b;
+    pub const BYTE_ARRAY_MARKER: u8 = 0x0c;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn amf0_object_converts_to_anonymous_dynamic_object() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("a".to_string(), Amf0Value::Number(1.0));
+
+        let result = Amf3Value::from(Amf0Value::Object(properties));
+
+        let mut expected_properties = HashMap::new();
+        expected_properties.insert("a".to_string(), Amf3Value::Double(1.0));
+        let expected = Amf3Value::Object(Amf3Object::anonymous(expected_properties));
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn amf3_array_with_associative_values_converts_to_amf0_object() {
+        let mut associative = HashMap::new();
+        associative.insert("key".to_string(), Amf3Value::Boolean(true));
+        let array = Amf3Value::Array {
+            associative,
+            dense: vec![Amf3Value::Integer(5)],
+        };
+
+        let result = Amf0Value::from(array);
+
+        let mut expected = ObjectProperties::new();
+        expected.insert("key".to_string(), Amf0Value::Boolean(true));
+        expected.insert("0".to_string(), Amf0Value::Number(5.0));
+
+        assert_eq!(result, Amf0Value::Object(expected));
+    }
+
+    #[test]
+    fn typed_object_converts_to_amf0_typed_object_with_all_properties() {
+        let mut dynamic_properties = HashMap::new();
+        dynamic_properties.insert("b".to_string(), Amf3Value::Null);
+        let object = Amf3Value::Object(Amf3Object {
+            class_name: Some("Test".to_string()),
+            is_dynamic: true,
+            sealed_properties: vec![("a".to_string(), Amf3Value::Integer(1))],
+            dynamic_properties,
+        });
+
+        let result = Amf0Value::from(object);
+
+        let mut expected = ObjectProperties::new();
+        expected.insert("a".to_string(), Amf0Value::Number(1.0));
+        expected.insert("b".to_string(), Amf0Value::Null);
+
+        assert_eq!(
+            result,
+            Amf0Value::TypedObject {
+                class_name: "Test".to_string(),
+                properties: expected,
+            }
+        );
+    }
+
+    #[test]
+    fn dates_convert_between_amf0_and_amf3() {
+        let amf0 = Amf0Value::Date {
+            unix_time: UNIX_EPOCH + Duration::from_millis(1500),
+            time_zone: 0,
+        };
+
+        let amf3 = Amf3Value::from(amf0.clone());
+        assert_eq!(
+            amf3,
+            Amf3Value::Date {
+                unix_time_millis: 1500.0
+            }
+        );
+
+        assert_eq!(Amf0Value::from(amf3), amf0);
+    }
+}
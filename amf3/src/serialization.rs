@@ -0,0 +1,394 @@
+//! Module contains functionality for serializing values into bytes
+//! based on the AMF3 specification
+//! (https://www.adobe.com/content/dam/acom/en/devnet/pdf/amf-file-format-spec.pdf)
+
+use byteorder::{BigEndian, WriteBytesExt};
+use errors::Amf3SerializationError;
+use markers;
+use std::collections::HashMap;
+use {Amf3Object, Amf3Value};
+
+const MAX_U29: u32 = 0x1fff_ffff;
+const MAX_INLINE_LENGTH: usize = 0x0fff_ffff;
+const MIN_INTEGER: i32 = -0x1000_0000;
+const MAX_INTEGER: i32 = 0x0fff_ffff;
+
+#[derive(PartialEq, Eq, Hash)]
+struct TraitsKey {
+    class_name: Option<String>,
+    is_dynamic: bool,
+    sealed_names: Vec<String>,
+}
+
+struct Serializer {
+    bytes: Vec<u8>,
+    strings: HashMap<String, usize>,
+    traits: HashMap<TraitsKey, usize>,
+}
+
+/// Serializes values into an amf3 encoded vector of bytes.  Repeated strings and object traits
+/// are written as references to their first occurrence.
+pub fn serialize(values: &[Amf3Value]) -> Result<Vec<u8>, Amf3SerializationError> {
+    let mut serializer = Serializer {
+        bytes: Vec::new(),
+        strings: HashMap::new(),
+        traits: HashMap::new(),
+    };
+
+    for value in values {
+        serializer.write_value(value)?;
+    }
+
+    Ok(serializer.bytes)
+}
+
+impl Serializer {
+    fn write_value(&mut self, value: &Amf3Value) -> Result<(), Amf3SerializationError> {
+        match *value {
+            Amf3Value::Undefined => self.bytes.push(markers::UNDEFINED_MARKER),
+            Amf3Value::Null => self.bytes.push(markers::NULL_MARKER),
+            Amf3Value::Boolean(false) => self.bytes.push(markers::FALSE_MARKER),
+            Amf3Value::Boolean(true) => self.bytes.push(markers::TRUE_MARKER),
+            Amf3Value::Integer(value) => self.write_integer(value)?,
+            Amf3Value::Double(value) => self.write_double(value)?,
+            Amf3Value::Utf8String(ref value) => {
+                self.bytes.push(markers::STRING_MARKER);
+                self.write_string(value)?;
+            }
+
+            Amf3Value::XmlDocument(ref value) => {
+                self.bytes.push(markers::XML_DOCUMENT_MARKER);
+                self.write_inline_bytes(value.as_bytes())?;
+            }
+
+            Amf3Value::Date { unix_time_millis } => {
+                self.bytes.push(markers::DATE_MARKER);
+                self.write_u29(1)?;
+                self.bytes.write_f64::<BigEndian>(unix_time_millis)?;
+            }
+
+            Amf3Value::Array {
+                ref associative,
+                ref dense,
+            } => self.write_array(associative, dense)?,
+
+            Amf3Value::Object(ref object) => self.write_object(object)?,
+            Amf3Value::Xml(ref value) => {
+                self.bytes.push(markers::XML_MARKER);
+                self.write_inline_bytes(value.as_bytes())?;
+            }
+
+            Amf3Value::ByteArray(ref value) => {
+                self.bytes.push(markers::BYTE_ARRAY_MARKER);
+                self.write_inline_bytes(value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_u29(&mut self, value: u32) -> Result<(), Amf3SerializationError> {
+        if value > MAX_U29 {
+            return Err(Amf3SerializationError::LengthTooLarge {
+                length: value as usize,
+            });
+        }
+
+        if value < 0x80 {
+            self.bytes.push(value as u8);
+        } else if value < 0x4000 {
+            self.bytes.push(((value >> 7) | 0x80) as u8);
+            self.bytes.push((value & 0x7f) as u8);
+        } else if value < 0x20_0000 {
+            self.bytes.push(((value >> 14) | 0x80) as u8);
+            self.bytes.push(((value >> 7) | 0x80) as u8);
+            self.bytes.push((value & 0x7f) as u8);
+        } else {
+            self.bytes.push(((value >> 22) | 0x80) as u8);
+            self.bytes.push(((value >> 15) | 0x80) as u8);
+            self.bytes.push(((value >> 8) | 0x80) as u8);
+            self.bytes.push(value as u8);
+        }
+
+        Ok(())
+    }
+
+    /// Writes a length (or count) with the low bit set to mark the value as inline
+    fn write_inline_length(&mut self, length: usize) -> Result<(), Amf3SerializationError> {
+        if length > MAX_INLINE_LENGTH {
+            return Err(Amf3SerializationError::LengthTooLarge { length });
+        }
+
+        self.write_u29(((length as u32) << 1) | 1)
+    }
+
+    fn write_inline_bytes(&mut self, bytes: &[u8]) -> Result<(), Amf3SerializationError> {
+        self.write_inline_length(bytes.len())?;
+        self.bytes.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_integer(&mut self, value: i32) -> Result<(), Amf3SerializationError> {
+        if !(MIN_INTEGER..=MAX_INTEGER).contains(&value) {
+            return self.write_double(value as f64);
+        }
+
+        self.bytes.push(markers::INTEGER_MARKER);
+        self.write_u29((value as u32) & MAX_U29)
+    }
+
+    fn write_double(&mut self, value: f64) -> Result<(), Amf3SerializationError> {
+        self.bytes.push(markers::DOUBLE_MARKER);
+        self.bytes.write_f64::<BigEndian>(value)?;
+        Ok(())
+    }
+
+    fn write_string(&mut self, value: &str) -> Result<(), Amf3SerializationError> {
+        if let Some(index) = self.strings.get(value) {
+            let index = *index;
+            return self.write_u29((index as u32) << 1);
+        }
+
+        self.write_inline_bytes(value.as_bytes())?;
+
+        // Empty strings are never sent as references
+        if !value.is_empty() {
+            let index = self.strings.len();
+            self.strings.insert(value.to_string(), index);
+        }
+
+        Ok(())
+    }
+
+    fn write_array(
+        &mut self,
+        associative: &HashMap<String, Amf3Value>,
+        dense: &[Amf3Value],
+    ) -> Result<(), Amf3SerializationError> {
+        self.bytes.push(markers::ARRAY_MARKER);
+        self.write_inline_length(dense.len())?;
+
+        for (key, value) in associative {
+            self.write_string(key)?;
+            self.write_value(value)?;
+        }
+
+        self.write_string("")?;
+
+        for value in dense {
+            self.write_value(value)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_object(&mut self, object: &Amf3Object) -> Result<(), Amf3SerializationError> {
+        self.bytes.push(markers::OBJECT_MARKER);
+
+        let key = TraitsKey {
+            class_name: object.class_name.clone(),
+            is_dynamic: object.is_dynamic,
+            sealed_names: object
+                .sealed_properties
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+        };
+
+        if let Some(index) = self.traits.get(&key) {
+            let index = *index as u32;
+            self.write_u29((index << 2) | 1)?;
+        } else {
+            let sealed_count = key.sealed_names.len();
+            if sealed_count > (MAX_U29 >> 4) as usize {
+                return Err(Amf3SerializationError::LengthTooLarge {
+                    length: sealed_count,
+                });
+            }
+
+            let dynamic_flag = if key.is_dynamic { 8 } else { 0 };
+            self.write_u29(((sealed_count as u32) << 4) | dynamic_flag | 3)?;
+
+            let class_name = key.class_name.as_ref().map_or("", |x| x.as_str());
+            self.write_string(class_name)?;
+            for name in &key.sealed_names {
+                self.write_string(name)?;
+            }
+
+            let index = self.traits.len();
+            self.traits.insert(key, index);
+        }
+
+        for (_, value) in &object.sealed_properties {
+            self.write_value(value)?;
+        }
+
+        if object.is_dynamic {
+            for (name, value) in &object.dynamic_properties {
+                self.write_string(name)?;
+                self.write_value(value)?;
+            }
+
+            self.write_string("")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::serialize;
+    use byteorder::{BigEndian, WriteBytesExt};
+    use deserialize;
+    use markers;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use {Amf3Object, Amf3Value};
+
+    #[test]
+    fn can_serialize_integers() {
+        let input = vec![
+            Amf3Value::Integer(5),
+            Amf3Value::Integer(128),
+            Amf3Value::Integer(-1),
+            Amf3Value::Integer(0x0fff_ffff),
+        ];
+
+        let result = serialize(&input).unwrap();
+
+        let expected = vec![
+            markers::INTEGER_MARKER,
+            0x05,
+            markers::INTEGER_MARKER,
+            0x81,
+            0x00,
+            markers::INTEGER_MARKER,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+            markers::INTEGER_MARKER,
+            0xbf,
+            0xff,
+            0xff,
+            0xff,
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn integers_outside_of_29_bits_are_serialized_as_doubles() {
+        let input = vec![Amf3Value::Integer(0x1000_0000)];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.write_u8(markers::DOUBLE_MARKER).unwrap();
+        expected.write_f64::<BigEndian>(268_435_456.0).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn repeated_strings_are_serialized_as_references() {
+        let input = vec![
+            Amf3Value::Utf8String("test".to_string()),
+            Amf3Value::Utf8String("test".to_string()),
+        ];
+
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![markers::STRING_MARKER, (4 << 1) | 1];
+        expected.extend("test".as_bytes());
+        expected.push(markers::STRING_MARKER);
+        expected.push(0);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_date() {
+        let input = vec![Amf3Value::Date {
+            unix_time_millis: 1000.0,
+        }];
+
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.write_u8(markers::DATE_MARKER).unwrap();
+        expected.write_u8(1).unwrap();
+        expected.write_f64::<BigEndian>(1000.0).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_byte_array() {
+        let input = vec![Amf3Value::ByteArray(vec![1, 2, 3])];
+        let result = serialize(&input).unwrap();
+
+        let expected = vec![markers::BYTE_ARRAY_MARKER, (3 << 1) | 1, 1, 2, 3];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn repeated_traits_are_serialized_as_references() {
+        let create_object = |value| {
+            Amf3Value::Object(Amf3Object {
+                class_name: Some("Test".to_string()),
+                is_dynamic: false,
+                sealed_properties: vec![("a".to_string(), Amf3Value::Integer(value))],
+                dynamic_properties: HashMap::new(),
+            })
+        };
+
+        let input = vec![create_object(1), create_object(2)];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![markers::OBJECT_MARKER, (1 << 4) | 3, (4 << 1) | 1];
+        expected.extend("Test".as_bytes());
+        expected.push((1 << 1) | 1);
+        expected.extend("a".as_bytes());
+        expected.push(markers::INTEGER_MARKER);
+        expected.push(1);
+        expected.push(markers::OBJECT_MARKER);
+        expected.push(1);
+        expected.push(markers::INTEGER_MARKER);
+        expected.push(2);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_round_trip_nested_values() {
+        let mut associative = HashMap::new();
+        associative.insert(
+            "key".to_string(),
+            Amf3Value::Utf8String("value".to_string()),
+        );
+
+        let mut properties = HashMap::new();
+        properties.insert(
+            "array".to_string(),
+            Amf3Value::Array {
+                associative,
+                dense: vec![Amf3Value::Double(1.5), Amf3Value::Null],
+            },
+        );
+        properties.insert("key".to_string(), Amf3Value::Xml("<a />".to_string()));
+
+        let input = vec![
+            Amf3Value::Object(Amf3Object::anonymous(properties)),
+            Amf3Value::XmlDocument("<b />".to_string()),
+            Amf3Value::Utf8String("value".to_string()),
+            Amf3Value::Undefined,
+        ];
+
+        let serialized = serialize(&input).unwrap();
+        let result = deserialize(&mut Cursor::new(serialized)).unwrap();
+
+        assert_eq!(result, input);
+    }
+}
@@ -3,10 +3,9 @@ extern crate rml_amf0;
 extern crate rml_rtmp;
 
 use bytes::Bytes;
-use std::collections::HashMap;
 use std::time::SystemTime;
 
-use rml_amf0::Amf0Value;
+use rml_amf0::{Amf0Value, ObjectProperties};
 use rml_rtmp::chunk_io::ChunkSerializer;
 use rml_rtmp::messages::{MessagePayload, RtmpMessage};
 use rml_rtmp::sessions::{
@@ -15,8 +14,8 @@ use rml_rtmp::sessions::{
 use rml_rtmp::time::RtmpTimestamp;
 
 const ITERATION_COUNT: u32 = 50_000;
-static APP_NAME: &'static str = "live";
-static STREAM_KEY: &'static str = "stream_key";
+static APP_NAME: &str = "live";
+static STREAM_KEY: &str = "stream_key";
 
 fn main() {
     let args: Vec<_> = std::env::args().collect();
@@ -53,23 +52,22 @@ fn main() {
             match result {
                 ServerSessionResult::OutboundResponse(_) => (),
                 ServerSessionResult::UnhandleableMessageReceived(_) => (),
-                ServerSessionResult::RaisedEvent(event) => match event {
-                    ServerSessionEvent::VideoDataReceived {
+                ServerSessionResult::RaisedEvent(event) => {
+                    if let ServerSessionEvent::VideoDataReceived {
                         app_name: _,
                         stream_key: _,
                         data,
                         timestamp,
-                    } => {
+                    } = event
+                    {
                         player1
-                            .send_video_data(1, data.clone(), timestamp.clone(), true)
+                            .send_video_data(1, data.clone(), timestamp, true)
                             .unwrap();
                         player2
-                            .send_video_data(1, data.clone(), timestamp.clone(), true)
+                            .send_video_data(1, data.clone(), timestamp, true)
                             .unwrap();
                     }
-
-                    _ => (),
-                },
+                }
             }
         }
     }
@@ -123,16 +121,15 @@ fn perform_connection(
         match result {
             ServerSessionResult::OutboundResponse(_) => (),
             ServerSessionResult::UnhandleableMessageReceived(_) => (),
-            ServerSessionResult::RaisedEvent(event) => match event {
-                ServerSessionEvent::ConnectionRequested {
+            ServerSessionResult::RaisedEvent(event) => {
+                if let ServerSessionEvent::ConnectionRequested {
                     app_name: _,
                     request_id,
-                } => {
+                } = event
+                {
                     session.accept_request(request_id).unwrap();
                 }
-
-                _ => (),
-            },
+            }
         }
     }
 }
@@ -143,7 +140,7 @@ fn create_connect_message(
     stream_id: u32,
     object_encoding: f64,
 ) -> MessagePayload {
-    let mut properties = HashMap::new();
+    let mut properties = ObjectProperties::new();
     properties.insert("app".to_string(), Amf0Value::Utf8String(app_name));
     properties.insert(
         "objectEncoding".to_string(),
@@ -158,8 +155,8 @@ fn create_connect_message(
     };
 
     let timestamp = RtmpTimestamp::new(timestamp);
-    let payload = message.into_message_payload(timestamp, stream_id).unwrap();
-    payload
+
+    message.into_message_payload(timestamp, stream_id).unwrap()
 }
 
 fn create_active_stream(session: &mut ServerSession, serializer: &mut ChunkSerializer) -> u32 {
@@ -202,18 +199,17 @@ fn start_publishing(session: &mut ServerSession, serializer: &mut ChunkSerialize
         match result {
             ServerSessionResult::OutboundResponse(_) => (),
             ServerSessionResult::UnhandleableMessageReceived(_) => (),
-            ServerSessionResult::RaisedEvent(event) => match event {
-                ServerSessionEvent::PublishStreamRequested {
+            ServerSessionResult::RaisedEvent(event) => {
+                if let ServerSessionEvent::PublishStreamRequested {
                     app_name: _,
                     stream_key: _,
                     mode: _,
                     request_id,
-                } => {
+                } = event
+                {
                     session.accept_request(request_id).unwrap();
                 }
-
-                _ => (),
-            },
+            }
         }
     }
 }
@@ -241,8 +237,8 @@ fn start_playing(session: &mut ServerSession, serializer: &mut ChunkSerializer)
         match result {
             ServerSessionResult::OutboundResponse(_) => (),
             ServerSessionResult::UnhandleableMessageReceived(_) => (),
-            ServerSessionResult::RaisedEvent(event) => match event {
-                ServerSessionEvent::PlayStreamRequested {
+            ServerSessionResult::RaisedEvent(event) => {
+                if let ServerSessionEvent::PlayStreamRequested {
                     app_name: _,
                     stream_key: _,
                     request_id,
@@ -250,12 +246,11 @@ fn start_playing(session: &mut ServerSession, serializer: &mut ChunkSerializer)
                     duration: _,
                     reset: _,
                     stream_id: _,
-                } => {
+                } = event
+                {
                     session.accept_request(request_id).unwrap();
                 }
-
-                _ => (),
-            },
+            }
         }
     }
 }
@@ -0,0 +1,143 @@
+use crate::gateway::SharedGateway;
+use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
+use rml_rtmp::names::{AppName, StreamName};
+use rml_rtmp::sessions::{
+    ServerSession, ServerSessionConfig, ServerSessionError, ServerSessionEvent, ServerSessionResult,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Accepts streams published by an RTMP client until it disconnects.  Requests to play streams
+/// are rejected, since they can only be played over HLS.
+pub async fn run(
+    connection_id: usize,
+    stream: TcpStream,
+    gateway: SharedGateway,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let result = handle_connection(connection_id, stream, &gateway).await;
+    gateway.lock().unwrap().connection_closed(connection_id);
+    println!("Connection {}: Disconnected", connection_id);
+
+    result
+}
+
+async fn handle_connection(
+    connection_id: usize,
+    mut stream: TcpStream,
+    gateway: &SharedGateway,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut buffer = [0; 4096];
+    let mut handshake = Handshake::new(PeerType::Server);
+    stream
+        .write_all(&handshake.generate_outbound_p0_and_p1()?)
+        .await?;
+
+    let remaining_bytes = loop {
+        let bytes_read = stream.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+
+        match handshake.process_bytes(&buffer[..bytes_read])? {
+            HandshakeProcessResult::InProgress { response_bytes } => {
+                stream.write_all(&response_bytes).await?;
+            }
+
+            HandshakeProcessResult::Completed {
+                response_bytes,
+                completion,
+            } => {
+                stream.write_all(&response_bytes).await?;
+                break completion.remaining_bytes;
+            }
+        }
+    };
+
+    let (mut session, mut results) = ServerSession::new(ServerSessionConfig::new())?;
+    results.extend(session.handle_input(&remaining_bytes)?);
+
+    loop {
+        // Handling events can produce more results, such as responses to accepted requests
+        while !results.is_empty() {
+            for result in std::mem::take(&mut results) {
+                match result {
+                    ServerSessionResult::OutboundResponse(packet) => {
+                        stream.write_all(&packet.bytes).await?;
+                    }
+
+                    ServerSessionResult::RaisedEvent(event) => {
+                        results.extend(handle_event(connection_id, &mut session, gateway, event)?);
+                    }
+
+                    ServerSessionResult::UnhandleableMessageReceived(_) => (),
+                }
+            }
+        }
+
+        let bytes_read = stream.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+
+        results = session.handle_input(&buffer[..bytes_read])?;
+    }
+}
+
+fn handle_event(
+    connection_id: usize,
+    session: &mut ServerSession,
+    gateway: &SharedGateway,
+    event: ServerSessionEvent,
+) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
+    match event {
+        ServerSessionEvent::ConnectionRequested { request_id, .. } => {
+            session.accept_request(request_id)
+        }
+
+        ServerSessionEvent::PublishStreamRequested {
+            request_id,
+            app_name,
+            stream_key,
+            ..
+        } => {
+            // Tokens and other parameters are not part of the stream's identity
+            let app_name = AppName::parse(&app_name).path();
+            let stream_key = StreamName::parse(&stream_key).name;
+
+            let mut gateway = gateway.lock().unwrap();
+            match gateway.start_publishing(connection_id, &app_name, &stream_key) {
+                Ok(()) => {
+                    println!(
+                        "Connection {}: Publishing {}/{}, which can be played from /{}/{}/index.m3u8",
+                        connection_id, app_name, stream_key, app_name, stream_key
+                    );
+
+                    session.accept_request(request_id)
+                }
+
+                Err(error) => {
+                    println!(
+                        "Connection {}: Can't publish {}/{}: {}",
+                        connection_id, app_name, stream_key, error
+                    );
+
+                    session.reject_request(request_id, &error)
+                }
+            }
+        }
+
+        ServerSessionEvent::PlayStreamRequested { request_id, .. } => {
+            session.reject_request(request_id, "Streams can only be played over HLS")
+        }
+
+        // Metadata, media, and the publisher stopping are routed by the hub
+        event => {
+            gateway
+                .lock()
+                .unwrap()
+                .handle_publisher_event(connection_id, &event);
+
+            Ok(Vec::new())
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use bytes::Bytes;
+use rml_hls::{HlsConfig, HlsError, HlsSegment, HlsSegmenter};
+use rml_rtmp::hub::{StreamHub, StreamHubResult, SubscriberDropPolicy};
+use rml_rtmp::sessions::ServerSessionEvent;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The gateway shared between every RTMP connection and the HTTP server
+pub type SharedGateway = Arc<Mutex<Gateway>>;
+
+/// Packages every stream published to the server into HLS.  Publishers publish into a stream
+/// hub, and each stream is packaged by a segmenter subscribed to the hub as if it was a player,
+/// so the hub takes care of routing the media and replaying sequence headers to it.
+///
+/// Streams stay subscribed after their publisher leaves, so players can finish the ended
+/// playlist, and they are packaged from scratch when they are published again.
+pub struct Gateway {
+    hub: StreamHub,
+    config: HlsConfig,
+    next_id: usize,
+
+    /// The id each stream's packager is subscribed to the hub with, by `<app>/<stream key>`
+    packager_ids: HashMap<String, usize>,
+    packagers: HashMap<usize, Packager>,
+}
+
+struct Packager {
+    path: String,
+    segmenter: HlsSegmenter,
+}
+
+impl Gateway {
+    pub fn new(config: HlsConfig) -> Self {
+        Gateway {
+            hub: StreamHub::new(),
+            config,
+            next_id: 0,
+            packager_ids: HashMap::new(),
+            packagers: HashMap::new(),
+        }
+    }
+
+    /// Returns an id for a new connection.  Connections and packagers share the hub, so they
+    /// get their ids from the same place.
+    pub fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Makes the connection the publisher of the stream and starts packaging it.  The app name
+    /// and stream key are expected to have had their query strings removed.
+    pub fn start_publishing(
+        &mut self,
+        connection_id: usize,
+        app_name: &str,
+        stream_key: &str,
+    ) -> Result<(), String> {
+        let segmenter = HlsSegmenter::new(self.config.clone()).map_err(|x| x.to_string())?;
+        self.hub
+            .join_as_publisher(connection_id, app_name, stream_key)
+            .map_err(|x| x.to_string())?;
+
+        let path = format!("{}/{}", app_name, stream_key);
+        if let Some(id) = self.packager_ids.get(&path) {
+            let packager = self.packagers.get_mut(id).unwrap();
+            packager.segmenter = segmenter;
+            return Ok(());
+        }
+
+        let id = self.next_id();
+        let results = self
+            .hub
+            .join_as_subscriber(
+                id,
+                app_name,
+                stream_key,
+                0,
+                SubscriberDropPolicy::DeliverAll,
+            )
+            .map_err(|x| x.to_string())?;
+
+        self.packager_ids.insert(path.clone(), id);
+        self.packagers.insert(id, Packager { path, segmenter });
+        self.handle_hub_results(results);
+
+        Ok(())
+    }
+
+    /// Routes an event raised by the connection's session to the stream it's publishing
+    pub fn handle_publisher_event(&mut self, connection_id: usize, event: &ServerSessionEvent) {
+        match self.hub.handle_publisher_event(connection_id, event) {
+            Ok(results) => self.handle_hub_results(results),
+            Err(error) => println!(
+                "Connection {}: Media could not be routed: {}",
+                connection_id, error
+            ),
+        }
+    }
+
+    pub fn connection_closed(&mut self, connection_id: usize) {
+        let results = self.hub.leave(connection_id);
+        self.handle_hub_results(results);
+    }
+
+    /// Gets a playlist or segment of a stream by the stream's `<app>/<stream key>` path and the
+    /// file's name
+    pub fn get_file(&self, path: &str, name: &str) -> Option<Bytes> {
+        let id = self.packager_ids.get(path)?;
+        self.packagers[id].segmenter.get_file(name)
+    }
+
+    fn handle_hub_results(&mut self, results: Vec<StreamHubResult>) {
+        for result in results {
+            let (subscriber_id, segment) = match result {
+                StreamHubResult::SendMetadata {
+                    subscriber_id,
+                    metadata,
+                    ..
+                } => {
+                    if let (Some(width), Some(height), Some(packager)) = (
+                        metadata.video_width,
+                        metadata.video_height,
+                        self.packagers.get_mut(&subscriber_id),
+                    ) {
+                        packager
+                            .segmenter
+                            .set_video_dimensions(width as u16, height as u16);
+                    }
+
+                    continue;
+                }
+
+                StreamHubResult::SendVideoData {
+                    subscriber_id,
+                    data,
+                    timestamp,
+                    ..
+                } => (
+                    subscriber_id,
+                    self.packagers
+                        .get_mut(&subscriber_id)
+                        .map(|packager| packager.segmenter.push_video(&data, timestamp)),
+                ),
+
+                StreamHubResult::SendAudioData {
+                    subscriber_id,
+                    data,
+                    timestamp,
+                    ..
+                } => (
+                    subscriber_id,
+                    self.packagers
+                        .get_mut(&subscriber_id)
+                        .map(|packager| packager.segmenter.push_audio(&data, timestamp)),
+                ),
+
+                StreamHubResult::PublishingFinished { subscriber_id, .. } => (
+                    subscriber_id,
+                    self.packagers
+                        .get_mut(&subscriber_id)
+                        .map(|packager| packager.segmenter.finish()),
+                ),
+            };
+
+            if let Some(segment) = segment {
+                self.log_segment(subscriber_id, segment);
+            }
+        }
+    }
+
+    fn log_segment(&self, packager_id: usize, segment: Result<Option<HlsSegment>, HlsError>) {
+        let path = &self.packagers[&packager_id].path;
+        match segment {
+            Ok(Some(segment)) => println!(
+                "Stream {}: Segment {} completed ({} ms)",
+                path, segment.name, segment.duration_ms
+            ),
+
+            Ok(None) => (),
+            Err(error) => println!("Stream {}: Packaging failed: {}", path, error),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::gateway::SharedGateway;
+use crate::spawn;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Requests with headers larger than this are not answered
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Serves the playlists and segments of every packaged stream from memory at
+/// `/<app>/<stream key>/<file name>`.  Only as much HTTP/1.1 is understood as is needed to
+/// answer `GET` requests from players, and the connection is closed after every response.
+pub async fn serve(
+    listener: TcpListener,
+    gateway: SharedGateway,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        spawn(respond(stream, gateway.clone()));
+    }
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    gateway: SharedGateway,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|bytes| bytes == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_SIZE {
+            return Err("HTTP request was too large".into());
+        }
+
+        let bytes_read = stream.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+
+        request.extend_from_slice(&buffer[..bytes_read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line
+        .next()
+        .and_then(|target| target.split('?').next())
+        .unwrap_or_default();
+
+    let file = match path.trim_start_matches('/').rsplit_once('/') {
+        Some((stream_path, name)) if method == "GET" => gateway
+            .lock()
+            .unwrap()
+            .get_file(stream_path, name)
+            .map(|data| (content_type(name), data)),
+
+        _ => None,
+    };
+
+    let (status, content_type, body) = match file {
+        Some((content_type, data)) => ("200 OK", content_type, data.to_vec()),
+        None if method != "GET" => (
+            "405 Method Not Allowed",
+            "text/plain",
+            b"Only GET requests are supported\n".to_vec(),
+        ),
+
+        None => ("404 Not Found", "text/plain", b"Not found\n".to_vec()),
+    };
+
+    // Players running in browsers are usually served from a different origin
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\n\
+         Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn content_type(name: &str) -> &'static str {
+    match name.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("m3u8") => "application/vnd.apple.mpegurl",
+        Some("mpd") => "application/dash+xml",
+        Some("mp4") | Some("m4s") => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,75 @@
+use crate::gateway::{Gateway, SharedGateway};
+use rml_hls::HlsConfig;
+use std::fmt::Display;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+mod connection;
+mod gateway;
+mod http;
+
+/// How long segments are cut at.  Players start a few segments behind the live edge, so this
+/// is shorter than the segmenter's default to keep the latency down.
+const SEGMENT_DURATION_MS: u32 = 2000;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args = std::env::args().skip(1);
+    let rtmp_address: SocketAddr = args
+        .next()
+        .unwrap_or_else(|| "0.0.0.0:1935".to_string())
+        .parse()?;
+
+    let http_address: SocketAddr = args
+        .next()
+        .unwrap_or_else(|| "0.0.0.0:8080".to_string())
+        .parse()?;
+
+    let mut config = HlsConfig::new();
+    config.target_segment_duration_ms = SEGMENT_DURATION_MS;
+    let gateway = Arc::new(Mutex::new(Gateway::new(config)));
+
+    let rtmp_listener = TcpListener::bind(rtmp_address).await?;
+    println!("Listening for RTMP connections on {}", rtmp_address);
+
+    let http_listener = TcpListener::bind(http_address).await?;
+    println!("Serving HLS on {}", http_address);
+
+    let http = tokio::spawn(http::serve(http_listener, gateway.clone()));
+    let rtmp = tokio::spawn(accept_connections(rtmp_listener, gateway));
+
+    rtmp.await??;
+    http.await??;
+
+    Ok(())
+}
+
+async fn accept_connections(
+    listener: TcpListener,
+    gateway: SharedGateway,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    loop {
+        let (stream, address) = listener.accept().await?;
+        let connection_id = gateway.lock().unwrap().next_id();
+        println!(
+            "Connection {}: Connection received from {}",
+            connection_id, address
+        );
+
+        spawn(connection::run(connection_id, stream, gateway.clone()));
+    }
+}
+
+fn spawn<F, E>(future: F)
+where
+    F: Future<Output = Result<(), E>> + Send + 'static,
+    E: Display,
+{
+    tokio::task::spawn(async {
+        if let Err(error) = future.await {
+            eprintln!("{}", error);
+        }
+    });
+}
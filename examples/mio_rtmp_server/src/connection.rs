@@ -1,3 +1,4 @@
+use bytes::Bytes;
 use mio::net::TcpStream;
 use mio::{Poll, PollOpt, Ready, Token};
 use rml_rtmp::chunk_io::Packet;
@@ -29,7 +30,7 @@ pub enum ReadResult {
 
 #[derive(Debug)]
 pub enum ConnectionError {
-    IoError(io::Error),
+    IoError(#[allow(dead_code)] io::Error),
     SocketClosed,
 }
 
@@ -40,7 +41,7 @@ impl From<io::Error> for ConnectionError {
 }
 
 enum SendablePacket {
-    RawBytes(Vec<u8>),
+    RawBytes(Bytes),
     Packet(Packet),
 }
 
@@ -117,13 +118,14 @@ impl Connection {
         let handshake_bytes = connection.handshake.generate_outbound_p0_and_p1().unwrap();
         connection
             .send_queue
-            .push_back(SendablePacket::RawBytes(handshake_bytes));
+            .push_back(SendablePacket::RawBytes(Bytes::from(handshake_bytes)));
         connection.interest.insert(Ready::writable());
         connection
     }
 
     pub fn enqueue_response(&mut self, poll: &mut Poll, bytes: Vec<u8>) -> io::Result<()> {
-        self.send_queue.push_back(SendablePacket::RawBytes(bytes));
+        self.send_queue
+            .push_back(SendablePacket::RawBytes(Bytes::from(bytes)));
         self.interest.insert(Ready::writable());
         self.register(poll)
     }
@@ -167,20 +169,19 @@ impl Connection {
                     },
                 };
 
-                match read_result {
-                    ReadResult::BytesReceived {
-                        buffer: read_buffer,
-                        byte_count,
-                    } => match self.debug_log_files {
+                if let ReadResult::BytesReceived {
+                    buffer: read_buffer,
+                    byte_count,
+                } = read_result
+                {
+                    match self.debug_log_files {
                         None => (),
                         Some(ref mut logs) => {
                             logs.rtmp_input_file
-                                .write(&read_buffer[..byte_count])
+                                .write_all(&read_buffer[..byte_count])
                                 .unwrap();
                         }
-                    },
-
-                    _ => (),
+                    }
                 }
 
                 self.register(poll)?;
@@ -198,7 +199,7 @@ impl Connection {
                         "Failed to send buffer for {:?} with error {}",
                         self.token, error
                     );
-                    return Err(ConnectionError::IoError(error));
+                    Err(ConnectionError::IoError(error))
                 }
             }
         }
@@ -226,7 +227,7 @@ impl Connection {
                     match self.debug_log_files {
                         None => (),
                         Some(ref mut logs) => {
-                            logs.rtmp_output_file.write(&bytes).unwrap();
+                            logs.rtmp_output_file.write_all(&bytes).unwrap();
                         }
                     }
                 }
@@ -290,7 +291,7 @@ impl Connection {
 
         match result {
             HandshakeProcessResult::InProgress { response_bytes } => {
-                if response_bytes.len() > 0 {
+                if !response_bytes.is_empty() {
                     self.enqueue_response(poll, response_bytes)?;
                 }
 
@@ -299,18 +300,19 @@ impl Connection {
 
             HandshakeProcessResult::Completed {
                 response_bytes,
-                remaining_bytes,
+                completion,
             } => {
-                println!("Handshake successful!");
-                if response_bytes.len() > 0 {
+                println!(
+                    "Handshake successful! ({:?} handshake)",
+                    completion.handshake_type
+                );
+                if !response_bytes.is_empty() {
                     self.enqueue_response(poll, response_bytes)?;
                 }
 
                 let mut buffer = [0; BUFFER_SIZE];
-                let buffer_size = remaining_bytes.len();
-                for (index, value) in remaining_bytes.into_iter().enumerate() {
-                    buffer[index] = value;
-                }
+                let buffer_size = completion.remaining_bytes.len();
+                buffer[..buffer_size].copy_from_slice(&completion.remaining_bytes);
 
                 self.handshake_completed = true;
 
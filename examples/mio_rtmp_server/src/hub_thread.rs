@@ -0,0 +1,120 @@
+use rml_rtmp::hub::{StreamHub, StreamHubResult, SubscriberDropPolicy};
+use rml_rtmp::sessions::ServerSessionEvent;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use worker::{WorkerHandle, WorkerMessage};
+
+/// Requests workers make of the stream hub on behalf of their connections
+pub enum HubCommand {
+    JoinAsPublisher {
+        connection_id: usize,
+        request_id: u32,
+        app_name: String,
+        stream_key: String,
+    },
+
+    JoinAsSubscriber {
+        connection_id: usize,
+        app_name: String,
+        stream_key: String,
+        stream_id: u32,
+    },
+
+    /// Metadata or media raised by a publishing connection's session
+    PublisherEvent {
+        connection_id: usize,
+        event: ServerSessionEvent,
+    },
+
+    Leave {
+        connection_id: usize,
+    },
+}
+
+/// Starts the thread that owns the stream hub.  Connections are pinned to the worker with the
+/// index of their id modulo the number of workers, which is how results are routed back to the
+/// worker owning each subscriber.
+pub fn start(commands: Receiver<HubCommand>, workers: Vec<WorkerHandle>) {
+    thread::spawn(move || run(commands, workers));
+}
+
+fn run(commands: Receiver<HubCommand>, workers: Vec<WorkerHandle>) {
+    let mut hub = StreamHub::new();
+    let send_to_owner = |connection_id: usize, message: WorkerMessage| {
+        workers[connection_id % workers.len()].send(message);
+    };
+
+    for command in commands {
+        let results = match command {
+            HubCommand::JoinAsPublisher {
+                connection_id,
+                request_id,
+                app_name,
+                stream_key,
+            } => {
+                let error = hub
+                    .join_as_publisher(connection_id, &app_name, &stream_key)
+                    .err()
+                    .map(|error| error.to_string());
+
+                send_to_owner(
+                    connection_id,
+                    WorkerMessage::PublishJoined {
+                        connection_id,
+                        request_id,
+                        error,
+                    },
+                );
+
+                Vec::new()
+            }
+
+            HubCommand::JoinAsSubscriber {
+                connection_id,
+                app_name,
+                stream_key,
+                stream_id,
+            } => hub
+                .join_as_subscriber(
+                    connection_id,
+                    &app_name,
+                    &stream_key,
+                    stream_id,
+                    SubscriberDropPolicy::DeliverAll,
+                )
+                .unwrap_or_else(|error| {
+                    println!(
+                        "Connection {} could not subscribe: {}",
+                        connection_id, error
+                    );
+                    Vec::new()
+                }),
+
+            HubCommand::PublisherEvent {
+                connection_id,
+                event,
+            } => hub
+                .handle_publisher_event(connection_id, &event)
+                .unwrap_or_else(|error| {
+                    println!(
+                        "Media from connection {} not routed: {}",
+                        connection_id, error
+                    );
+                    Vec::new()
+                }),
+
+            HubCommand::Leave { connection_id } => hub.leave(connection_id),
+        };
+
+        for result in results {
+            let subscriber_id = match result {
+                StreamHubResult::SendMetadata { subscriber_id, .. } => subscriber_id,
+                StreamHubResult::SendVideoData { subscriber_id, .. } => subscriber_id,
+                StreamHubResult::SendAudioData { subscriber_id, .. } => subscriber_id,
+                StreamHubResult::PublishingFinished { subscriber_id, .. } => subscriber_id,
+            };
+
+            send_to_owner(subscriber_id, WorkerMessage::HubResult(result));
+        }
+    }
+}
@@ -6,7 +6,9 @@ extern crate rml_rtmp;
 extern crate slab;
 
 mod connection;
+mod hub_thread;
 mod server;
+mod worker;
 
 use clap::App;
 use mio::net::{TcpListener, TcpStream};
@@ -15,14 +17,17 @@ use slab::Slab;
 use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::mpsc::channel;
 use std::time::SystemTime;
 
 use connection::{Connection, ConnectionError, ReadResult};
 use server::{Server, ServerResult};
+use worker::WorkerMessage;
 
-const SERVER: Token = Token(std::usize::MAX - 1);
+const SERVER: Token = Token(usize::MAX - 1);
 
 type ClosedTokens = HashSet<usize>;
+#[allow(clippy::large_enum_variant)]
 enum EventResult {
     None,
     ReadResult(ReadResult),
@@ -48,12 +53,17 @@ pub struct PushOptions {
 #[derive(Debug)]
 struct AppOptions {
     log_io: bool,
+    workers: usize,
     pull: Option<PullOptions>,
     push: Option<PushOptions>,
 }
 
 fn main() {
     let app_options = get_app_options();
+    if app_options.workers > 1 {
+        run_workers(&app_options);
+        return;
+    }
 
     let address = "0.0.0.0:1935".parse().unwrap();
     let listener = TcpListener::bind(&address).unwrap();
@@ -75,7 +85,7 @@ fn main() {
 
         let mut pull_host = pull.host.clone();
         if !pull_host.contains(":") {
-            pull_host = pull_host + ":1935";
+            pull_host += ":1935";
         }
 
         let addr = SocketAddr::from_str(&pull_host).unwrap();
@@ -180,10 +190,8 @@ fn main() {
 
         if outer_elapsed.as_secs() >= 10 {
             let seconds_since_start = outer_started_at.elapsed().unwrap().as_secs();
-            let seconds_doing_work =
-                (total_ns as f64) / (1000 as f64) / (1000 as f64) / (1000 as f64);
-            let percentage_doing_work =
-                (seconds_doing_work / seconds_since_start as f64) * 100 as f64;
+            let seconds_doing_work = (total_ns as f64) / 1000_f64 / 1000_f64 / 1000_f64;
+            let percentage_doing_work = (seconds_doing_work / seconds_since_start as f64) * 100_f64;
             println!("Spent {} ms ({}% of time) doing work over {} seconds (avg {} microseconds per iteration)",
                      total_ns / 1000 / 1000,
                      percentage_doing_work as u32,
@@ -203,36 +211,72 @@ fn get_app_options() -> AppOptions {
     let matches = App::from_yaml(yaml).get_matches();
 
     let log_io = matches.is_present("log-io");
-    let pull_options = match matches.subcommand_matches("pull") {
-        None => None,
-        Some(pull_matches) => Some(PullOptions {
+    let workers = matches
+        .value_of("workers")
+        .map(|workers| workers.parse().expect("Workers must be a number"))
+        .unwrap_or(1);
+
+    let pull_options = matches
+        .subcommand_matches("pull")
+        .map(|pull_matches| PullOptions {
             host: pull_matches.value_of("host").unwrap().to_string(),
             app: pull_matches.value_of("app").unwrap().to_string(),
             stream: pull_matches.value_of("stream").unwrap().to_string(),
             target: pull_matches.value_of("target").unwrap().to_string(),
-        }),
-    };
+        });
 
-    let push_options = match matches.subcommand_matches("push") {
-        None => None,
-        Some(push_matches) => Some(PushOptions {
+    let push_options = matches
+        .subcommand_matches("push")
+        .map(|push_matches| PushOptions {
             host: push_matches.value_of("host").unwrap().to_string(),
             app: push_matches.value_of("app").unwrap().to_string(),
             source_stream: push_matches.value_of("source_stream").unwrap().to_string(),
             target_stream: push_matches.value_of("target_stream").unwrap().to_string(),
-        }),
-    };
+        });
 
     let app_options = AppOptions {
         pull: pull_options,
         push: push_options,
         log_io,
+        workers,
     };
 
     println!("Application options: {:?}", app_options);
     app_options
 }
 
+/// Runs the server with the calling thread accepting connections, and pinning each one to one
+/// of the worker event loops.  Workers route media to each other through the hub thread.
+fn run_workers(app_options: &AppOptions) {
+    if app_options.pull.is_some() || app_options.push.is_some() {
+        println!("Pulling and pushing are only supported with a single worker");
+        return;
+    }
+
+    let (hub_sender, hub_receiver) = channel();
+    let workers = (0..app_options.workers)
+        .map(|index| worker::spawn(index, app_options.log_io, hub_sender.clone()))
+        .collect::<Vec<_>>();
+
+    hub_thread::start(hub_receiver, workers.clone());
+
+    let listener = std::net::TcpListener::bind("0.0.0.0:1935").unwrap();
+    println!("Listening for connections with {} workers", workers.len());
+    for (connection_id, stream) in listener.incoming().enumerate() {
+        match stream {
+            Ok(stream) => {
+                let worker = &workers[connection_id % workers.len()];
+                worker.send(WorkerMessage::NewConnection {
+                    connection_id,
+                    stream,
+                });
+            }
+
+            Err(error) => println!("Failed to accept connection: {}", error),
+        }
+    }
+}
+
 fn process_event(
     event: &Ready,
     connections: &mut Slab<Connection>,
@@ -293,10 +337,11 @@ fn handle_read_bytes(
             ServerResult::OutboundPacket {
                 target_connection_id,
                 packet,
-            } => match connections.get_mut(target_connection_id) {
-                Some(connection) => connection.enqueue_packet(poll, packet).unwrap(),
-                None => (),
-            },
+            } => {
+                if let Some(connection) = connections.get_mut(target_connection_id) {
+                    connection.enqueue_packet(poll, packet).unwrap()
+                }
+            }
 
             ServerResult::DisconnectConnection { connection_id } => {
                 closed_tokens.insert(connection_id);
@@ -311,7 +356,7 @@ fn handle_read_bytes(
 
                     let mut push_host = push.host.clone();
                     if !push_host.contains(":") {
-                        push_host = push_host + ":1935";
+                        push_host += ":1935";
                     }
 
                     let addr = SocketAddr::from_str(&push_host).unwrap();
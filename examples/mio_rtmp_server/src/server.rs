@@ -11,7 +11,7 @@ use rml_rtmp::sessions::{
 use rml_rtmp::time::RtmpTimestamp;
 use slab::Slab;
 use std::collections::{HashMap, HashSet};
-use std::rc::Rc;
+use std::sync::Arc;
 
 enum ReceivedDataType {
     Audio,
@@ -82,7 +82,7 @@ struct PushClient {
 struct MediaChannel {
     publishing_client_id: Option<usize>,
     watching_client_ids: HashSet<usize>,
-    metadata: Option<Rc<StreamMetadata>>,
+    metadata: Option<Arc<StreamMetadata>>,
     video_sequence_header: Option<Bytes>,
     audio_sequence_header: Option<Bytes>,
 }
@@ -109,17 +109,14 @@ pub struct Server {
 
 impl Server {
     pub fn new(push_options: &Option<PushOptions>) -> Server {
-        let push_client = match push_options {
-            &None => None,
-            &Some(ref options) => Some(PushClient {
-                push_app: options.app.clone(),
-                push_source_stream: options.source_stream.clone(),
-                push_target_stream: options.target_stream.clone(),
-                connection_id: None,
-                session: None,
-                state: PushState::Inactive,
-            }),
-        };
+        let push_client = push_options.as_ref().map(|options| PushClient {
+            push_app: options.app.clone(),
+            push_source_stream: options.source_stream.clone(),
+            push_target_stream: options.target_stream.clone(),
+            connection_id: None,
+            session: None,
+            state: PushState::Inactive,
+        });
 
         Server {
             clients: Slab::with_capacity(1024),
@@ -172,22 +169,13 @@ impl Server {
     ) -> Result<Vec<ServerResult>, String> {
         let mut server_results = Vec::new();
 
-        let push_client_connection_id = self.push_client.as_ref().map_or(None, |c| {
-            if let Some(connection_id) = c.connection_id {
-                Some(connection_id)
-            } else {
-                None
-            }
-        });
+        let push_client_connection_id = self.push_client.as_ref().and_then(|c| c.connection_id);
 
-        let pull_client_connection_id = self
-            .pull_client
-            .as_ref()
-            .map_or(None, |c| Some(c.connection_id));
+        let pull_client_connection_id = self.pull_client.as_ref().map(|c| c.connection_id);
 
         if pull_client_connection_id
             .as_ref()
-            .map_or(false, |id| *id == connection_id)
+            .is_some_and(|id| *id == connection_id)
         {
             // These bytes were received by the current pull client
 
@@ -217,14 +205,14 @@ impl Server {
                 Err(error) => return Err(error.to_string()),
             };
 
-            if initial_session_results.len() > 0 {
+            if !initial_session_results.is_empty() {
                 self.handle_push_session_results(initial_session_results, &mut server_results);
             }
 
             self.handle_pull_session_results(session_results, &mut server_results);
         } else if push_client_connection_id
             .as_ref()
-            .map_or(false, |id| *id == connection_id)
+            .is_some_and(|id| *id == connection_id)
         {
             // These bytes were received by the current push client
             let mut initial_session_results = Vec::new();
@@ -248,7 +236,7 @@ impl Server {
                 Vec::new()
             };
 
-            if initial_session_results.len() > 0 {
+            if !initial_session_results.is_empty() {
                 self.handle_push_session_results(initial_session_results, &mut server_results);
             }
 
@@ -274,9 +262,9 @@ impl Server {
                     has_received_video_keyframe: false,
                 };
 
-                let client_id = Some(self.clients.insert(client));
+                let client_id = self.clients.insert(client);
                 self.connection_to_client_map
-                    .insert(connection_id, client_id.unwrap());
+                    .insert(connection_id, client_id);
             }
 
             let client_results;
@@ -299,7 +287,7 @@ impl Server {
         if self
             .pull_client
             .as_ref()
-            .map_or(false, |c| c.connection_id == connection_id)
+            .is_some_and(|c| c.connection_id == connection_id)
         {
             self.pull_client = None;
         } else {
@@ -611,7 +599,7 @@ impl Server {
                     match channel.metadata {
                         None => (),
                         Some(ref metadata) => {
-                            let packet = match client.session.send_metadata(stream_id, &metadata) {
+                            let packet = match client.session.send_metadata(stream_id, metadata) {
                                 Ok(packet) => packet,
                                 Err(error) => {
                                     println!("Error occurred sending existing metadata to new client: {:?}", error);
@@ -693,8 +681,6 @@ impl Server {
                 server_results.push(ServerResult::DisconnectConnection {
                     connection_id: requested_connection_id,
                 });
-
-                return;
             }
 
             Ok(results) => {
@@ -711,7 +697,7 @@ impl Server {
         &mut self,
         app_name: String,
         stream_key: String,
-        metadata: StreamMetadata,
+        metadata: Arc<StreamMetadata>,
         server_results: &mut Vec<ServerResult>,
     ) {
         println!(
@@ -723,7 +709,6 @@ impl Server {
             None => return,
         };
 
-        let metadata = Rc::new(metadata);
         channel.metadata = Some(metadata.clone());
 
         // Send the metadata to all current watchers
@@ -818,7 +803,7 @@ impl Server {
                     ReceivedDataType::Audio => client.session.send_audio_data(
                         active_stream_id,
                         data.clone(),
-                        timestamp.clone(),
+                        timestamp,
                         true,
                     ),
                     ReceivedDataType::Video => {
@@ -829,7 +814,7 @@ impl Server {
                         client.session.send_video_data(
                             active_stream_id,
                             data.clone(),
-                            timestamp.clone(),
+                            timestamp,
                             true,
                         )
                     }
@@ -863,13 +848,13 @@ impl Server {
                             .session
                             .as_mut()
                             .unwrap()
-                            .publish_video_data(data.clone(), timestamp.clone(), true),
+                            .publish_video_data(data.clone(), timestamp, true),
 
                         ReceivedDataType::Audio => client
                             .session
                             .as_mut()
                             .unwrap()
-                            .publish_audio_data(data.clone(), timestamp.clone(), true),
+                            .publish_audio_data(data.clone(), timestamp, true),
                     };
 
                     match result {
@@ -931,22 +916,18 @@ impl Server {
                 }
             }
 
-            match client.state {
-                PullState::Handshaking => {
-                    // Since this was called we know we are no longer handshaking, so we need to
-                    // initiate the connect to the RTMP app
-                    client.state = PullState::Connecting;
-
-                    let result = client
-                        .session
-                        .as_mut()
-                        .unwrap()
-                        .request_connection(client.pull_app.clone())
-                        .unwrap();
-                    new_results.push(result);
-                }
-
-                _ => (),
+            if let PullState::Handshaking = client.state {
+                // Since this was called we know we are no longer handshaking, so we need to
+                // initiate the connect to the RTMP app
+                client.state = PullState::Connecting;
+
+                let result = client
+                    .session
+                    .as_mut()
+                    .unwrap()
+                    .request_connection(client.pull_app.clone())
+                    .unwrap();
+                new_results.push(result);
             }
         }
 
@@ -960,7 +941,7 @@ impl Server {
                     self.handle_pull_connection_accepted_event(server_results);
                 }
 
-                ClientSessionEvent::PlaybackRequestAccepted { .. } => {
+                ClientSessionEvent::PlaybackRequestAccepted => {
                     self.handle_pull_playback_accepted_event(server_results);
                 }
 
@@ -1042,7 +1023,7 @@ impl Server {
 
     fn handle_pull_metadata_received(
         &mut self,
-        metadata: StreamMetadata,
+        metadata: Arc<StreamMetadata>,
         server_results: &mut Vec<ServerResult>,
     ) {
         let (app_name, stream_key) = match self.pull_client {
@@ -1078,28 +1059,25 @@ impl Server {
                 }
             }
 
-            match client.state {
-                PushState::Handshaking => {
-                    // Since we got here we know handshaking was successful, so we need
-                    // to initiate the connection process
-                    client.state = PushState::Connecting;
-
-                    let result = match client
-                        .session
-                        .as_mut()
-                        .unwrap()
-                        .request_connection(client.push_app.clone())
-                    {
-                        Ok(result) => result,
-                        Err(error) => {
-                            println!("Failed to request connection for push client: {:?}", error);
-                            return;
-                        }
-                    };
+            if client.state == PushState::Handshaking {
+                // Since we got here we know handshaking was successful, so we need
+                // to initiate the connection process
+                client.state = PushState::Connecting;
+
+                let result = match client
+                    .session
+                    .as_mut()
+                    .unwrap()
+                    .request_connection(client.push_app.clone())
+                {
+                    Ok(result) => result,
+                    Err(error) => {
+                        println!("Failed to request connection for push client: {:?}", error);
+                        return;
+                    }
+                };
 
-                    new_results.push(result);
-                }
-                _ => (),
+                new_results.push(result);
             }
         }
 
@@ -1154,13 +1132,13 @@ impl Server {
             client.state = PushState::Pushing;
 
             // Send out any metadata or header information if we have any
-            if let Some(ref channel) = self.channels.get(&client.push_source_stream) {
+            if let Some(channel) = self.channels.get(&client.push_source_stream) {
                 if let Some(ref metadata) = channel.metadata {
                     let result = client
                         .session
                         .as_mut()
                         .unwrap()
-                        .publish_metadata(&metadata)
+                        .publish_metadata(metadata)
                         .unwrap();
                     new_results.push(result);
                 }
@@ -1197,15 +1175,15 @@ impl Server {
 
 fn is_video_sequence_header(data: Bytes) -> bool {
     // This is assuming h264.
-    return data.len() >= 2 && data[0] == 0x17 && data[1] == 0x00;
+    data.len() >= 2 && data[0] == 0x17 && data[1] == 0x00
 }
 
 fn is_audio_sequence_header(data: Bytes) -> bool {
     // This is assuming aac
-    return data.len() >= 2 && data[0] == 0xaf && data[1] == 0x00;
+    data.len() >= 2 && data[0] == 0xaf && data[1] == 0x00
 }
 
 fn is_video_keyframe(data: Bytes) -> bool {
     // assumings h264
-    return data.len() >= 2 && data[0] == 0x17 && data[1] != 0x00; // 0x00 is the sequence header, don't count that for now
+    data.len() >= 2 && data[0] == 0x17 && data[1] != 0x00 // 0x00 is the sequence header, don't count that for now
 }
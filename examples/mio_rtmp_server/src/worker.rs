@@ -0,0 +1,447 @@
+use connection::{Connection, ReadResult};
+use hub_thread::HubCommand;
+use mio::net::TcpStream;
+use mio::{Events, Poll, PollOpt, Ready, Registration, SetReadiness, Token};
+use rml_rtmp::chunk_io::Packet;
+use rml_rtmp::hub::StreamHubResult;
+use rml_rtmp::sessions::{
+    ServerSession, ServerSessionConfig, ServerSessionEvent, ServerSessionResult,
+};
+use std::collections::HashMap;
+use std::net;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+const WAKER: Token = Token(usize::MAX - 2);
+
+/// Messages sent to a worker by the acceptor and the hub thread
+pub enum WorkerMessage {
+    NewConnection {
+        connection_id: usize,
+        stream: net::TcpStream,
+    },
+
+    /// The hub has decided if the connection can publish the stream it requested
+    PublishJoined {
+        connection_id: usize,
+        request_id: u32,
+        error: Option<String>,
+    },
+
+    HubResult(StreamHubResult),
+}
+
+/// Sends messages to a worker, waking up its event loop so they are handled right away
+#[derive(Clone)]
+pub struct WorkerHandle {
+    sender: Sender<WorkerMessage>,
+    readiness: SetReadiness,
+}
+
+impl WorkerHandle {
+    pub fn send(&self, message: WorkerMessage) {
+        if self.sender.send(message).is_ok() {
+            let _ = self.readiness.set_readiness(Ready::readable());
+        }
+    }
+}
+
+struct WorkerConnection {
+    connection: Connection,
+    session: Option<ServerSession>,
+}
+
+/// An event loop that owns the sockets and sessions of the connections pinned to it.  Media
+/// is routed between connections by the hub thread, so publishers and their players can be on
+/// different workers.
+struct Worker {
+    index: usize,
+    log_io: bool,
+    poll: Poll,
+    connections: HashMap<usize, WorkerConnection>,
+    messages: Receiver<WorkerMessage>,
+    readiness: SetReadiness,
+    hub: Sender<HubCommand>,
+}
+
+/// Starts a worker on its own thread
+pub fn spawn(index: usize, log_io: bool, hub: Sender<HubCommand>) -> WorkerHandle {
+    let (registration, readiness) = Registration::new2();
+    let (sender, messages) = channel();
+    let handle = WorkerHandle {
+        sender,
+        readiness: readiness.clone(),
+    };
+
+    thread::spawn(move || {
+        let mut worker = Worker {
+            index,
+            log_io,
+            poll: Poll::new().unwrap(),
+            connections: HashMap::new(),
+            messages,
+            readiness,
+            hub,
+        };
+
+        worker
+            .poll
+            .register(&registration, WAKER, Ready::readable(), PollOpt::edge())
+            .unwrap();
+
+        worker.run();
+    });
+
+    handle
+}
+
+impl Worker {
+    fn run(&mut self) {
+        let mut events = Events::with_capacity(1024);
+
+        // Messages may have been sent before the waker was registered
+        self.handle_messages();
+
+        loop {
+            self.poll.poll(&mut events, None).unwrap();
+
+            for event in events.iter() {
+                match event.token() {
+                    WAKER => self.handle_messages(),
+                    Token(connection_id) => {
+                        if let Err(reason) = self.handle_readiness(connection_id, event.readiness())
+                        {
+                            self.close(connection_id, &reason);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_messages(&mut self) {
+        // Readiness is cleared before draining, so a message sent while draining wakes the
+        // event loop up again instead of being missed
+        let _ = self.readiness.set_readiness(Ready::empty());
+
+        while let Ok(message) = self.messages.try_recv() {
+            let (connection_id, result) = match message {
+                WorkerMessage::NewConnection {
+                    connection_id,
+                    stream,
+                } => (connection_id, self.add_connection(connection_id, stream)),
+
+                WorkerMessage::PublishJoined {
+                    connection_id,
+                    request_id,
+                    error,
+                } => (
+                    connection_id,
+                    self.publish_joined(connection_id, request_id, error),
+                ),
+
+                WorkerMessage::HubResult(result) => self.handle_hub_result(result),
+            };
+
+            if let Err(reason) = result {
+                self.close(connection_id, &reason);
+            }
+        }
+    }
+
+    fn add_connection(
+        &mut self,
+        connection_id: usize,
+        stream: net::TcpStream,
+    ) -> Result<(), String> {
+        let socket = TcpStream::from_stream(stream).map_err(|error| error.to_string())?;
+        let mut connection = Connection::new(socket, connection_id, self.log_io, true);
+        connection.token = Some(Token(connection_id));
+        connection
+            .register(&mut self.poll)
+            .map_err(|error| error.to_string())?;
+
+        println!(
+            "New connection (id {}) on worker {}",
+            connection_id, self.index
+        );
+        self.connections.insert(
+            connection_id,
+            WorkerConnection {
+                connection,
+                session: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn handle_readiness(&mut self, connection_id: usize, readiness: Ready) -> Result<(), String> {
+        let read_result = {
+            let entry = match self.connections.get_mut(&connection_id) {
+                Some(entry) => entry,
+                None => return Ok(()),
+            };
+
+            if readiness.is_writable() {
+                entry
+                    .connection
+                    .writable(&mut self.poll)
+                    .map_err(|error| error.to_string())?;
+            }
+
+            if !readiness.is_readable() {
+                return Ok(());
+            }
+
+            entry
+                .connection
+                .readable(&mut self.poll)
+                .map_err(|error| format!("{:?}", error))?
+        };
+
+        match read_result {
+            ReadResult::HandshakingInProgress | ReadResult::NoBytesReceived => Ok(()),
+            ReadResult::HandshakeCompleted { buffer, byte_count } => {
+                let (session, results) = ServerSession::new(ServerSessionConfig::new())
+                    .map_err(|error| error.to_string())?;
+
+                if let Some(entry) = self.connections.get_mut(&connection_id) {
+                    entry.session = Some(session);
+                }
+
+                self.handle_session_results(connection_id, results)?;
+                self.bytes_received(connection_id, &buffer[..byte_count])
+            }
+
+            ReadResult::BytesReceived { buffer, byte_count } => {
+                self.bytes_received(connection_id, &buffer[..byte_count])
+            }
+        }
+    }
+
+    fn bytes_received(&mut self, connection_id: usize, bytes: &[u8]) -> Result<(), String> {
+        let results = match self.session(connection_id) {
+            Some(session) => session
+                .handle_input(bytes)
+                .map_err(|error| error.to_string())?,
+            None => return Ok(()),
+        };
+
+        self.handle_session_results(connection_id, results)
+    }
+
+    fn handle_session_results(
+        &mut self,
+        connection_id: usize,
+        results: Vec<ServerSessionResult>,
+    ) -> Result<(), String> {
+        for result in results {
+            match result {
+                ServerSessionResult::OutboundResponse(packet) => {
+                    self.send_packet(connection_id, packet)?
+                }
+
+                ServerSessionResult::RaisedEvent(event) => {
+                    self.handle_event(connection_id, event)?
+                }
+
+                ServerSessionResult::UnhandleableMessageReceived(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_event(
+        &mut self,
+        connection_id: usize,
+        event: ServerSessionEvent,
+    ) -> Result<(), String> {
+        match event {
+            ServerSessionEvent::ConnectionRequested {
+                request_id,
+                app_name,
+            } => {
+                println!("Connection {} requested app '{}'", connection_id, app_name);
+                self.accept_request(connection_id, request_id)
+            }
+
+            ServerSessionEvent::PublishStreamRequested {
+                request_id,
+                app_name,
+                stream_key,
+                ..
+            } => {
+                println!(
+                    "Connection {} requested publishing to '{}/{}'",
+                    connection_id, app_name, stream_key
+                );
+
+                self.send_to_hub(HubCommand::JoinAsPublisher {
+                    connection_id,
+                    request_id,
+                    app_name,
+                    stream_key,
+                });
+
+                Ok(())
+            }
+
+            ServerSessionEvent::PlayStreamRequested {
+                request_id,
+                app_name,
+                stream_key,
+                stream_id,
+                ..
+            } => {
+                println!(
+                    "Connection {} requested playback of '{}/{}'",
+                    connection_id, app_name, stream_key
+                );
+
+                // Playback is accepted before joining the hub, so the responses are sent
+                // before any media the hub routes to the connection
+                self.accept_request(connection_id, request_id)?;
+                self.send_to_hub(HubCommand::JoinAsSubscriber {
+                    connection_id,
+                    app_name,
+                    stream_key,
+                    stream_id,
+                });
+
+                Ok(())
+            }
+
+            ServerSessionEvent::PublishStreamFinished { .. }
+            | ServerSessionEvent::PlayStreamFinished { .. } => {
+                self.send_to_hub(HubCommand::Leave { connection_id });
+                Ok(())
+            }
+
+            ServerSessionEvent::StreamMetadataChanged { .. }
+            | ServerSessionEvent::AudioDataReceived { .. }
+            | ServerSessionEvent::VideoDataReceived { .. } => {
+                self.send_to_hub(HubCommand::PublisherEvent {
+                    connection_id,
+                    event,
+                });
+
+                Ok(())
+            }
+
+            _ => Ok(()),
+        }
+    }
+
+    fn publish_joined(
+        &mut self,
+        connection_id: usize,
+        request_id: u32,
+        error: Option<String>,
+    ) -> Result<(), String> {
+        let results = {
+            let session = match self.session(connection_id) {
+                Some(session) => session,
+                None => return Ok(()),
+            };
+
+            match error {
+                None => session.accept_request(request_id),
+                Some(ref error) => session.reject_request(request_id, error),
+            }
+            .map_err(|error| error.to_string())?
+        };
+
+        self.handle_session_results(connection_id, results)
+    }
+
+    fn handle_hub_result(&mut self, result: StreamHubResult) -> (usize, Result<(), String>) {
+        let (subscriber_id, packet) = match result {
+            StreamHubResult::SendMetadata {
+                subscriber_id,
+                stream_id,
+                metadata,
+            } => (
+                subscriber_id,
+                self.session(subscriber_id)
+                    .map(|session| session.send_metadata(stream_id, &metadata)),
+            ),
+
+            StreamHubResult::SendVideoData {
+                subscriber_id,
+                stream_id,
+                data,
+                timestamp,
+                can_be_dropped,
+            } => (
+                subscriber_id,
+                self.session(subscriber_id).map(|session| {
+                    session.send_video_data(stream_id, data, timestamp, can_be_dropped)
+                }),
+            ),
+
+            StreamHubResult::SendAudioData {
+                subscriber_id,
+                stream_id,
+                data,
+                timestamp,
+                can_be_dropped,
+            } => (
+                subscriber_id,
+                self.session(subscriber_id).map(|session| {
+                    session.send_audio_data(stream_id, data, timestamp, can_be_dropped)
+                }),
+            ),
+
+            StreamHubResult::PublishingFinished { subscriber_id, .. } => (subscriber_id, None),
+        };
+
+        let result = match packet {
+            Some(Ok(packet)) => self.send_packet(subscriber_id, packet),
+            Some(Err(error)) => Err(error.to_string()),
+            None => Ok(()),
+        };
+
+        (subscriber_id, result)
+    }
+
+    fn accept_request(&mut self, connection_id: usize, request_id: u32) -> Result<(), String> {
+        let results = match self.session(connection_id) {
+            Some(session) => session
+                .accept_request(request_id)
+                .map_err(|error| error.to_string())?,
+            None => return Ok(()),
+        };
+
+        self.handle_session_results(connection_id, results)
+    }
+
+    fn send_packet(&mut self, connection_id: usize, packet: Packet) -> Result<(), String> {
+        match self.connections.get_mut(&connection_id) {
+            Some(entry) => entry
+                .connection
+                .enqueue_packet(&mut self.poll, packet)
+                .map_err(|error| error.to_string()),
+
+            None => Ok(()),
+        }
+    }
+
+    fn send_to_hub(&self, command: HubCommand) {
+        self.hub.send(command).expect("Hub thread stopped");
+    }
+
+    fn session(&mut self, connection_id: usize) -> Option<&mut ServerSession> {
+        self.connections
+            .get_mut(&connection_id)
+            .and_then(|entry| entry.session.as_mut())
+    }
+
+    fn close(&mut self, connection_id: usize, reason: &str) {
+        if self.connections.remove(&connection_id).is_some() {
+            println!("Closing connection id {}: {}", connection_id, reason);
+            self.send_to_hub(HubCommand::Leave { connection_id });
+        }
+    }
+}
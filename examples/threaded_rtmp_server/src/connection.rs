@@ -1,3 +1,4 @@
+use bytes::Bytes;
 use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
 use std::collections::VecDeque;
 use std::io;
@@ -9,6 +10,7 @@ use std::time::Duration;
 
 const BUFFER_SIZE: usize = 4096;
 
+#[allow(clippy::large_enum_variant)]
 pub enum ReadResult {
     HandshakingInProgress,
     NoBytesReceived,
@@ -20,7 +22,7 @@ pub enum ReadResult {
 
 #[derive(Debug)]
 pub enum ConnectionError {
-    IoError(io::Error),
+    IoError(#[allow(dead_code)] io::Error),
     SocketClosed,
 }
 
@@ -32,7 +34,7 @@ impl From<io::Error> for ConnectionError {
 
 pub struct Connection {
     pub connection_id: Option<usize>,
-    writer: Sender<Vec<u8>>,
+    writer: Sender<Bytes>,
     reader: Receiver<ReadResult>,
     handshake: Handshake,
     handshake_completed: bool,
@@ -55,7 +57,7 @@ impl Connection {
         }
     }
 
-    pub fn write(&self, bytes: Vec<u8>) {
+    pub fn write(&self, bytes: Bytes) {
         self.writer.send(bytes).unwrap();
     }
 
@@ -87,8 +89,8 @@ impl Connection {
 
         match result {
             HandshakeProcessResult::InProgress { response_bytes } => {
-                if response_bytes.len() > 0 {
-                    self.write(response_bytes);
+                if !response_bytes.is_empty() {
+                    self.write(Bytes::from(response_bytes));
                 }
 
                 Ok(ReadResult::HandshakingInProgress)
@@ -96,18 +98,19 @@ impl Connection {
 
             HandshakeProcessResult::Completed {
                 response_bytes,
-                remaining_bytes,
+                completion,
             } => {
-                println!("Handshake successful!");
-                if response_bytes.len() > 0 {
-                    self.write(response_bytes);
+                println!(
+                    "Handshake successful! ({:?} handshake)",
+                    completion.handshake_type
+                );
+                if !response_bytes.is_empty() {
+                    self.write(Bytes::from(response_bytes));
                 }
 
                 let mut buffer = [0; BUFFER_SIZE];
-                let buffer_size = remaining_bytes.len();
-                for (index, value) in remaining_bytes.into_iter().enumerate() {
-                    buffer[index] = value;
-                }
+                let buffer_size = completion.remaining_bytes.len();
+                buffer[..buffer_size].copy_from_slice(&completion.remaining_bytes);
 
                 self.handshake_completed = true;
                 Ok(ReadResult::BytesReceived {
@@ -119,7 +122,7 @@ impl Connection {
     }
 }
 
-fn start_byte_writer(byte_receiver: Receiver<Vec<u8>>, socket: &TcpStream) {
+fn start_byte_writer(byte_receiver: Receiver<Bytes>, socket: &TcpStream) {
     let mut socket = socket.try_clone().expect("failed to clone socket");
     thread::spawn(move || {
         let mut send_queue = VecDeque::new();
@@ -156,9 +159,7 @@ fn start_result_reader(sender: Sender<ReadResult>, socket: &TcpStream) {
                 Ok(0) => return, // socket closed
                 Ok(read_count) => {
                     let mut send_buffer = [0; BUFFER_SIZE];
-                    for x in 0..read_count {
-                        send_buffer[x] = buffer[x];
-                    }
+                    send_buffer[..read_count].copy_from_slice(&buffer[..read_count]);
 
                     let result = ReadResult::BytesReceived {
                         buffer: send_buffer,
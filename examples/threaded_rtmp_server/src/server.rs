@@ -7,7 +7,7 @@ use rml_rtmp::sessions::{
 use rml_rtmp::time::RtmpTimestamp;
 use slab::Slab;
 use std::collections::{HashMap, HashSet};
-use std::rc::Rc;
+use std::sync::Arc;
 
 enum ClientAction {
     Waiting,
@@ -43,7 +43,7 @@ impl Client {
 struct MediaChannel {
     publishing_client_id: Option<usize>,
     watching_client_ids: HashSet<usize>,
-    metadata: Option<Rc<StreamMetadata>>,
+    metadata: Option<Arc<StreamMetadata>>,
     video_sequence_header: Option<Bytes>,
     audio_sequence_header: Option<Bytes>,
 }
@@ -100,9 +100,9 @@ impl Server {
                 has_received_video_keyframe: false,
             };
 
-            let client_id = Some(self.clients.insert(client));
+            let client_id = self.clients.insert(client);
             self.connection_to_client_map
-                .insert(connection_id, client_id.unwrap());
+                .insert(connection_id, client_id);
         }
 
         let client_results: Vec<ServerSessionResult>;
@@ -402,7 +402,7 @@ impl Server {
                     match channel.metadata {
                         None => (),
                         Some(ref metadata) => {
-                            let packet = match client.session.send_metadata(stream_id, &metadata) {
+                            let packet = match client.session.send_metadata(stream_id, metadata) {
                                 Ok(packet) => packet,
                                 Err(error) => {
                                     println!("Error occurred sending existing metadata to new client: {:?}", error);
@@ -484,8 +484,6 @@ impl Server {
                 server_results.push(ServerResult::DisconnectConnection {
                     connection_id: requested_connection_id,
                 });
-
-                return;
             }
 
             Ok(results) => {
@@ -498,7 +496,7 @@ impl Server {
         &mut self,
         app_name: String,
         stream_key: String,
-        metadata: StreamMetadata,
+        metadata: Arc<StreamMetadata>,
         server_results: &mut Vec<ServerResult>,
     ) {
         println!(
@@ -510,7 +508,6 @@ impl Server {
             None => return,
         };
 
-        let metadata = Rc::new(metadata);
         channel.metadata = Some(metadata.clone());
 
         // Send the metadata to all current watchers
@@ -601,23 +598,19 @@ impl Server {
             }
 
             let send_result = match data_type {
-                ReceivedDataType::Audio => client.session.send_audio_data(
-                    active_stream_id,
-                    data.clone(),
-                    timestamp.clone(),
-                    true,
-                ),
+                ReceivedDataType::Audio => {
+                    client
+                        .session
+                        .send_audio_data(active_stream_id, data.clone(), timestamp, true)
+                }
                 ReceivedDataType::Video => {
                     if is_video_keyframe(data.clone()) {
                         client.has_received_video_keyframe = true;
                     }
 
-                    client.session.send_video_data(
-                        active_stream_id,
-                        data.clone(),
-                        timestamp.clone(),
-                        true,
-                    )
+                    client
+                        .session
+                        .send_video_data(active_stream_id, data.clone(), timestamp, true)
                 }
             };
 
@@ -662,15 +655,15 @@ impl Server {
 
 fn is_video_sequence_header(data: Bytes) -> bool {
     // This is assuming h264.
-    return data.len() >= 2 && data[0] == 0x17 && data[1] == 0x00;
+    data.len() >= 2 && data[0] == 0x17 && data[1] == 0x00
 }
 
 fn is_audio_sequence_header(data: Bytes) -> bool {
     // This is assuming aac
-    return data.len() >= 2 && data[0] == 0xaf && data[1] == 0x00;
+    data.len() >= 2 && data[0] == 0xaf && data[1] == 0x00
 }
 
 fn is_video_keyframe(data: Bytes) -> bool {
     // assumings h264
-    return data.len() >= 2 && data[0] == 0x17 && data[1] != 0x00; // 0x00 is the sequence header, don't count that for now
+    data.len() >= 2 && data[0] == 0x17 && data[1] != 0x00 // 0x00 is the sequence header, don't count that for now
 }
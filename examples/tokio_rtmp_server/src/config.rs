@@ -0,0 +1,277 @@
+use rml_rtmp::auth::TokenAuthorizer;
+use rml_rtmp::names::AppName;
+use rml_rtmp::sessions::{ServerSessionConfig, SessionConfigError};
+use rml_rtmp_tokio::rtmps::{self, rustls::ServerConfig};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Settings for the server, read from a TOML file.  Every section is optional, and anything
+/// left out of the file keeps the same value the server used before it had a config file.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Addresses to accept RTMP connections on
+    pub listen: Vec<SocketAddr>,
+
+    /// Apps clients are allowed to connect to.  Any app is allowed when this is empty.
+    pub allowed_apps: Vec<String>,
+
+    pub session: SessionSettings,
+    pub tls: Option<TlsSettings>,
+    pub auth: Option<AuthSettings>,
+    pub recording: Option<RecordingSettings>,
+    pub relay: RelaySettings,
+    pub http: Option<HttpSettings>,
+}
+
+/// Protocol settings applied to every server session
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionSettings {
+    pub chunk_size: u32,
+    pub window_ack_size: u32,
+    pub peer_bandwidth: u32,
+}
+
+/// Accepts RTMPS connections, which run RTMP inside of a TLS session
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TlsSettings {
+    /// Addresses to accept RTMPS connections on
+    pub listen: Vec<SocketAddr>,
+
+    /// PEM file with the certificate chain, starting with the server's certificate
+    pub certificate: PathBuf,
+
+    /// PEM file with the private key of the server's certificate
+    pub private_key: PathBuf,
+}
+
+/// Serves statistics about the server's connections and streams over HTTP
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HttpSettings {
+    /// Addresses to accept HTTP requests on
+    pub listen: Vec<SocketAddr>,
+}
+
+/// Requires requests to carry a token signed with the secret, as described by
+/// `rml_rtmp::auth::TokenAuthorizer`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AuthSettings {
+    pub secret: String,
+
+    #[serde(default)]
+    pub protect_connections: bool,
+
+    #[serde(default = "default_protect_publishing")]
+    pub protect_publishing: bool,
+
+    #[serde(default)]
+    pub protect_playback: bool,
+}
+
+/// Where published streams are recorded to, and which ones are recorded
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RecordingSettings {
+    pub directory: PathBuf,
+
+    /// `<app>/<stream key>` patterns of the streams to record, where either half can be `*`
+    /// to match anything
+    pub streams: Vec<String>,
+
+    /// How long each recording file can get before a new one is started
+    pub segment_duration_secs: Option<u64>,
+}
+
+/// Other servers the server pulls streams from, or pushes streams to
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelaySettings {
+    /// The origin streams are pulled from when they are requested by a player but nobody is
+    /// publishing them locally
+    pub pull: Option<RelayTarget>,
+
+    /// Servers every published stream is pushed to
+    pub push: Vec<RelayTarget>,
+}
+
+/// An app on a remote RTMP server
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RelayTarget {
+    /// The `host:port` of the remote server
+    pub address: String,
+    pub app: String,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Config {
+            listen: vec![SocketAddr::from(([0, 0, 0, 0], 1935))],
+            allowed_apps: Vec::new(),
+            session: SessionSettings::new(),
+            tls: None,
+            auth: None,
+            recording: None,
+            relay: RelaySettings::default(),
+            http: None,
+        }
+    }
+
+    /// Reads and validates the config file at the path
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|x| format!("Failed to read config file {}: {}", path.display(), x))?;
+
+        let config: Config = toml::from_str(&contents)
+            .map_err(|x| format!("Failed to parse config file {}: {}", path.display(), x))?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// True if clients can connect to the app.  Query strings and instance names on the app
+    /// are not considered.
+    pub fn is_app_allowed(&self, app_name: &str) -> bool {
+        self.allowed_apps.is_empty() || self.allowed_apps.contains(&AppName::parse(app_name).name)
+    }
+
+    pub fn authorizer(&self) -> Option<TokenAuthorizer> {
+        self.auth.as_ref().map(|auth| {
+            let mut authorizer = TokenAuthorizer::new(auth.secret.as_bytes());
+            authorizer.protect_connections = auth.protect_connections;
+            authorizer.protect_publishing = auth.protect_publishing;
+            authorizer.protect_playback = auth.protect_playback;
+            authorizer
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.listen.is_empty() {
+            return Err("At least one listen address is required".to_string());
+        }
+
+        if let Err(error) = self.session.session_config() {
+            return Err(format!("Invalid session settings: {}", error));
+        }
+
+        if let Some(tls) = &self.tls {
+            if tls.listen.is_empty() {
+                return Err("At least one TLS listen address is required".to_string());
+            }
+        }
+
+        if let Some(http) = &self.http {
+            if http.listen.is_empty() {
+                return Err("At least one HTTP listen address is required".to_string());
+            }
+        }
+
+        if let Some(auth) = &self.auth {
+            if auth.secret.is_empty() {
+                return Err("The auth secret can not be empty".to_string());
+            }
+        }
+
+        if let Some(recording) = &self.recording {
+            if recording.directory.as_os_str().is_empty() {
+                return Err("The recording directory can not be empty".to_string());
+            }
+
+            for pattern in &recording.streams {
+                match pattern.split_once('/') {
+                    Some((app, key)) if !app.is_empty() && !key.is_empty() => (),
+                    _ => {
+                        return Err(format!(
+                            "Recording pattern '{}' is not in the form <app>/<stream key>",
+                            pattern
+                        ))
+                    }
+                }
+            }
+
+            if recording.segment_duration_secs == Some(0) {
+                return Err("The recording segment duration must be above zero".to_string());
+            }
+        }
+
+        for target in self.relay.pull.iter().chain(self.relay.push.iter()) {
+            if target.address.is_empty() || target.app.is_empty() {
+                return Err("Relay targets need both an address and an app".to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+impl TlsSettings {
+    /// Reads the certificate chain and private key into a TLS configuration
+    pub fn server_config(
+        &self,
+    ) -> Result<Arc<ServerConfig>, Box<dyn std::error::Error + Sync + Send>> {
+        let read = |path: &Path| {
+            std::fs::read(path).map_err(|x| format!("Failed to read {}: {}", path.display(), x))
+        };
+
+        let certificates = rtmps::load_certificates(&read(&self.certificate)?)?;
+        let private_key = rtmps::load_private_key(&read(&self.private_key)?)?;
+        Ok(rtmps::server_tls_config(certificates, private_key)?)
+    }
+}
+
+impl RecordingSettings {
+    /// True if the stream matches one of the patterns.  The app name and stream key are
+    /// expected to have had their query strings removed.
+    pub fn should_record(&self, app_name: &str, stream_key: &str) -> bool {
+        self.streams
+            .iter()
+            .any(|pattern| match pattern.split_once('/') {
+                Some((app, key)) => {
+                    (app == "*" || app == app_name) && (key == "*" || key == stream_key)
+                }
+
+                None => false,
+            })
+    }
+}
+
+impl SessionSettings {
+    pub fn new() -> Self {
+        let defaults = ServerSessionConfig::new();
+        SessionSettings {
+            chunk_size: defaults.chunk_size,
+            window_ack_size: defaults.window_ack_size,
+            peer_bandwidth: defaults.peer_bandwidth,
+        }
+    }
+
+    pub fn session_config(&self) -> Result<ServerSessionConfig, SessionConfigError> {
+        ServerSessionConfig::builder()
+            .chunk_size(self.chunk_size)
+            .window_ack_size(self.window_ack_size)
+            .peer_bandwidth(self.peer_bandwidth)
+            .build()
+    }
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        SessionSettings::new()
+    }
+}
+
+fn default_protect_publishing() -> bool {
+    true
+}
@@ -3,17 +3,26 @@ mod state;
 
 use bytes::{Bytes, BytesMut};
 use futures::future::FutureExt;
+use rml_rtmp::admin::ConnectionRole;
+use rml_rtmp::auth::TokenAuthorizer;
 use rml_rtmp::chunk_io::Packet;
 use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
-use rml_rtmp::sessions::{
-    PublishMode, ServerSession, ServerSessionConfig, ServerSessionEvent, ServerSessionResult,
-};
+use rml_rtmp::names::{AppName, StreamName};
+use rml_rtmp::sessions::{ServerSession, ServerSessionEvent, ServerSessionResult};
+use rml_rtmp_tokio::rtmps::{self, rustls::ServerConfig};
 use std::collections::VecDeque;
-use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::UnboundedSender;
 
+use crate::config::Config;
+use crate::recording::Recording;
+use crate::relay::Push;
+use crate::stats::SharedStats;
 use crate::stream_manager::{ConnectionMessage, StreamManagerMessage};
 use crate::{send, spawn};
 
@@ -22,18 +31,36 @@ use state::State;
 
 pub struct Connection {
     id: i32,
+    remote_address: SocketAddr,
     session: Option<ServerSession>,
     stream_manager_sender: mpsc::UnboundedSender<StreamManagerMessage>,
     state: State,
+    config: Arc<Config>,
+    authorizer: Option<TokenAuthorizer>,
+    recording: Option<Recording>,
+    push: Option<Push>,
+    stats: SharedStats,
 }
 
 impl Connection {
-    pub fn new(id: i32, stream_manager: mpsc::UnboundedSender<StreamManagerMessage>) -> Self {
+    pub fn new(
+        id: i32,
+        remote_address: SocketAddr,
+        stream_manager: mpsc::UnboundedSender<StreamManagerMessage>,
+        config: Arc<Config>,
+        stats: SharedStats,
+    ) -> Self {
         Connection {
             id,
+            remote_address,
             session: None,
             stream_manager_sender: stream_manager,
             state: State::Waiting,
+            authorizer: config.authorizer(),
+            config,
+            recording: None,
+            push: None,
+            stats,
         }
     }
 
@@ -66,21 +93,39 @@ impl Connection {
 
                 HandshakeProcessResult::Completed {
                     response_bytes,
-                    remaining_bytes,
+                    completion,
                 } => {
                     stream.write_all(&response_bytes).await?;
-                    spawn(self.start_connection_manager(stream, remaining_bytes));
+                    spawn(self.start_connection_manager(stream, completion.remaining_bytes));
                     return Ok(());
                 }
             }
         }
     }
 
-    async fn start_connection_manager(
-        mut self,
+    /// Establishes the TLS session for a connection accepted on an RTMPS listener, then
+    /// performs the handshake inside of it
+    pub async fn start_rtmps(
+        self,
         stream: TcpStream,
-        received_bytes: Vec<u8>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        tls_config: Arc<ServerConfig>,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+        let (stream, remaining_bytes) = rtmps::accept_rtmps(stream, tls_config)
+            .await
+            .map_err(|x| format!("Connection {}: RTMPS failed: {}", self.id, x))?;
+
+        spawn(self.start_connection_manager(stream, remaining_bytes));
+        Ok(())
+    }
+
+    async fn start_connection_manager<S>(
+        mut self,
+        stream: S,
+        received_bytes: Bytes,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
         let (stream_reader, stream_writer) = tokio::io::split(stream);
         let (read_bytes_sender, mut read_bytes_receiver) = mpsc::unbounded_channel();
         let (mut write_bytes_sender, write_bytes_receiver) = mpsc::unbounded_channel();
@@ -99,14 +144,30 @@ impl Connection {
             return Ok(());
         }
 
-        spawn(connection_reader(self.id, stream_reader, read_bytes_sender));
+        self.stats
+            .lock()
+            .unwrap()
+            .connection_opened(self.id, self.remote_address);
+
+        spawn(connection_reader(
+            self.id,
+            stream_reader,
+            read_bytes_sender,
+            self.stats.clone(),
+        ));
+
         spawn(connection_writer(
             self.id,
             stream_writer,
             write_bytes_receiver,
+            self.stats.clone(),
         ));
 
-        let config = ServerSessionConfig::new();
+        let config = self
+            .config
+            .session
+            .session_config()
+            .map_err(|x| format!("Invalid session settings: {}", x))?;
         let (session, mut results) = ServerSession::new(config)
             .map_err(|x| format!("Server session error occurred: {:?}", x))?;
 
@@ -145,10 +206,7 @@ impl Connection {
                         None => break,
                         Some(message) => {
                             let (new_results, action) = self.handle_connection_message(message)?;
-                            match action {
-                                ConnectionAction::Disconnect => break,
-                                _ => (),
-                            };
+                            if action == ConnectionAction::Disconnect { break };
 
                             results = new_results;
                         }
@@ -169,9 +227,16 @@ impl Connection {
         (Vec<ServerSessionResult>, ConnectionAction),
         Box<dyn std::error::Error + Sync + Send>,
     > {
+        if let State::Playing { stream_id, .. } = &self.state {
+            self.stats
+                .lock()
+                .unwrap()
+                .record_media_sent(self.id, *stream_id, &message);
+        }
+
         match message {
-            ConnectionMessage::RequestAccepted { request_id: _ } => {
-                println!("Connection {}: Request accepted", self.id);
+            ConnectionMessage::RequestAccepted { request_id } => {
+                println!("Connection {}: Request {} accepted", self.id, request_id);
 
                 let (new_state, return_val) = match &self.state {
                     State::PublishRequested {
@@ -184,11 +249,25 @@ impl Connection {
                             stream_key: stream_key.clone(),
                         };
 
+                        let role = ConnectionRole::Publishing {
+                            app_name: app_name.clone(),
+                            stream_key: stream_key.clone(),
+                        };
+
+                        self.stats.lock().unwrap().set_role(self.id, role);
+
+                        let targets = &self.config.relay.push;
+                        if !targets.is_empty() {
+                            let push =
+                                Push::start(self.id, targets, app_name.clone(), stream_key.clone());
+                            self.push = Some(push);
+                        }
+
                         let results = self
                             .session
                             .as_mut()
                             .unwrap()
-                            .accept_request(request_id.clone())
+                            .accept_request(*request_id)
                             .map_err(|x| format!("Failed to accept request: {:?}", x))?;
 
                         (Some(new_state), (results, ConnectionAction::None))
@@ -203,14 +282,21 @@ impl Connection {
                         let new_state = State::Playing {
                             app_name: app_name.clone(),
                             stream_key: stream_key.clone(),
-                            stream_id: stream_id.clone(),
+                            stream_id: *stream_id,
                         };
 
+                        let role = ConnectionRole::Playing {
+                            app_name: app_name.clone(),
+                            stream_key: stream_key.clone(),
+                        };
+
+                        self.stats.lock().unwrap().set_role(self.id, role);
+
                         let results = self
                             .session
                             .as_mut()
                             .unwrap()
-                            .accept_request(request_id.clone())
+                            .accept_request(*request_id)
                             .map_err(|x| format!("Failed to accept request: {:?}", x))?;
 
                         (Some(new_state), (results, ConnectionAction::None))
@@ -222,17 +308,17 @@ impl Connection {
                     }
                 };
 
-                if new_state.is_some() {
-                    self.state = new_state.unwrap();
+                if let Some(new_state) = new_state {
+                    self.state = new_state;
                 }
 
                 Ok(return_val)
             }
 
-            ConnectionMessage::RequestDenied { request_id: _ } => {
-                println!("Connection {}: Request denied", self.id);
+            ConnectionMessage::RequestDenied { request_id } => {
+                println!("Connection {}: Request {} denied", self.id, request_id);
 
-                return match &self.state {
+                match &self.state {
                     State::PlaybackRequested { .. } => {
                         Ok((Vec::new(), ConnectionAction::Disconnect))
                     }
@@ -245,79 +331,73 @@ impl Connection {
                         eprintln!("Connection {}: Invalid state of {:?}", self.id, self.state);
                         Ok((Vec::new(), ConnectionAction::Disconnect))
                     }
-                };
+                }
             }
 
             ConnectionMessage::NewVideoData {
                 timestamp,
                 data,
                 can_be_dropped,
-            } => {
-                return match &self.state {
-                    State::Playing { stream_id, .. } => {
-                        let packet = self
-                            .session
-                            .as_mut()
-                            .unwrap()
-                            .send_video_data(*stream_id, data, timestamp, can_be_dropped)
-                            .map_err(|x| format!("Failed to send video data: {:?}", x))?;
+            } => match &self.state {
+                State::Playing { stream_id, .. } => {
+                    let packet = self
+                        .session
+                        .as_mut()
+                        .unwrap()
+                        .send_video_data(*stream_id, data, timestamp, can_be_dropped)
+                        .map_err(|x| format!("Failed to send video data: {:?}", x))?;
 
-                        let results = vec![ServerSessionResult::OutboundResponse(packet)];
-                        Ok((results, ConnectionAction::None))
-                    }
+                    let results = vec![ServerSessionResult::OutboundResponse(packet)];
+                    Ok((results, ConnectionAction::None))
+                }
 
-                    _ => {
-                        eprintln!("connection {}: expected video to be in playback state, instead was in {:?}", self.id, self.state);
-                        Ok((Vec::new(), ConnectionAction::Disconnect))
-                    }
+                _ => {
+                    eprintln!("connection {}: expected video to be in playback state, instead was in {:?}", self.id, self.state);
+                    Ok((Vec::new(), ConnectionAction::Disconnect))
                 }
-            }
+            },
 
             ConnectionMessage::NewAudioData {
                 timestamp,
                 data,
                 can_be_dropped,
-            } => {
-                return match &self.state {
-                    State::Playing { stream_id, .. } => {
-                        let packet = self
-                            .session
-                            .as_mut()
-                            .unwrap()
-                            .send_audio_data(*stream_id, data, timestamp, can_be_dropped)
-                            .map_err(|x| format!("Failed to send audio data: {:?}", x))?;
+            } => match &self.state {
+                State::Playing { stream_id, .. } => {
+                    let packet = self
+                        .session
+                        .as_mut()
+                        .unwrap()
+                        .send_audio_data(*stream_id, data, timestamp, can_be_dropped)
+                        .map_err(|x| format!("Failed to send audio data: {:?}", x))?;
 
-                        let results = vec![ServerSessionResult::OutboundResponse(packet)];
-                        Ok((results, ConnectionAction::None))
-                    }
+                    let results = vec![ServerSessionResult::OutboundResponse(packet)];
+                    Ok((results, ConnectionAction::None))
+                }
 
-                    _ => {
-                        eprintln!("connection {}: expected video to be in playback state, instead was in {:?}", self.id, self.state);
-                        Ok((Vec::new(), ConnectionAction::Disconnect))
-                    }
+                _ => {
+                    eprintln!("connection {}: expected video to be in playback state, instead was in {:?}", self.id, self.state);
+                    Ok((Vec::new(), ConnectionAction::Disconnect))
                 }
-            }
+            },
 
-            ConnectionMessage::NewMetadata { metadata } => {
-                return match &self.state {
-                    State::Playing { stream_id, .. } => {
-                        let packet = self
-                            .session
-                            .as_mut()
-                            .unwrap()
-                            .send_metadata(*stream_id, &metadata)
-                            .map_err(|x| format!("Failed to send metadata: {:?}", x))?;
+            ConnectionMessage::NewMetadata { metadata } => match &self.state {
+                State::Playing { stream_id, .. } => {
+                    let packet = self
+                        .session
+                        .as_mut()
+                        .unwrap()
+                        .send_metadata(*stream_id, &metadata)
+                        .map_err(|x| format!("Failed to send metadata: {:?}", x))?;
 
-                        let results = vec![ServerSessionResult::OutboundResponse(packet)];
-                        Ok((results, ConnectionAction::None))
-                    }
+                    let results = vec![ServerSessionResult::OutboundResponse(packet)];
+                    Ok((results, ConnectionAction::None))
+                }
 
-                    _ => {
-                        eprintln!("connection {}: expected video to be in playback state, instead was in {:?}", self.id, self.state);
-                        Ok((Vec::new(), ConnectionAction::Disconnect))
-                    }
+                _ => {
+                    eprintln!("connection {}: expected video to be in playback state, instead was in {:?}", self.id, self.state);
+                    Ok((Vec::new(), ConnectionAction::Disconnect))
                 }
-            }
+            },
         }
     }
 
@@ -326,7 +406,7 @@ impl Connection {
         results: &mut Vec<ServerSessionResult>,
         byte_writer: &mut UnboundedSender<Packet>,
     ) -> Result<ConnectionAction, Box<dyn std::error::Error + Sync + Send>> {
-        if results.len() == 0 {
+        if results.is_empty() {
             return Ok(ConnectionAction::None);
         }
 
@@ -334,7 +414,7 @@ impl Connection {
         for result in results.drain(..) {
             match result {
                 ServerSessionResult::OutboundResponse(packet) => {
-                    if !send(&byte_writer, packet) {
+                    if !send(byte_writer, packet) {
                         break;
                     }
                 }
@@ -365,6 +445,33 @@ impl Connection {
         event: ServerSessionEvent,
         new_results: &mut Vec<ServerSessionResult>,
     ) -> Result<ConnectionAction, Box<dyn std::error::Error + Sync + Send>> {
+        if let Some((request_id, reason)) = self.find_rejection_reason(&event) {
+            println!(
+                "Connection {}: Rejecting request {}: {}",
+                self.id, request_id, reason
+            );
+
+            new_results.extend(
+                self.session
+                    .as_mut()
+                    .unwrap()
+                    .reject_request(request_id, &reason)
+                    .map_err(|x| format!("Failed to reject request: {:?}", x))?,
+            );
+
+            return Ok(ConnectionAction::None);
+        }
+
+        self.stats.lock().unwrap().record_event(self.id, &event);
+
+        if let Some(recording) = &self.recording {
+            recording.record(&event);
+        }
+
+        if let Some(push) = &self.push {
+            push.send(&event);
+        }
+
         match event {
             ServerSessionEvent::ConnectionRequested {
                 request_id,
@@ -396,6 +503,7 @@ impl Connection {
                         })?,
                 );
 
+                self.stats.lock().unwrap().set_app_name(self.id, &app_name);
                 self.state = State::Connected { app_name };
             }
 
@@ -410,12 +518,17 @@ impl Connection {
                     self.id, app_name, stream_key, mode
                 );
 
-                if mode != PublishMode::Live {}
-
                 match &self.state {
                     State::Connected { .. } => {
+                        // The recorder needs the names the session raises media events with
+                        self.recording = self.start_recording(&app_name, &stream_key);
+
+                        // Tokens and other parameters are not part of the stream's identity
+                        let app_name = AppName::parse(&app_name).path();
+                        let stream_key = StreamName::parse(&stream_key).name;
+
                         self.state = State::PublishRequested {
-                            request_id: request_id.clone(),
+                            request_id,
                             app_name: app_name.clone(),
                             stream_key: stream_key.clone(),
                         };
@@ -451,10 +564,13 @@ impl Connection {
                     self.id, app_name, stream_key
                 );
 
+                let app_name = AppName::parse(&app_name).path();
+                let stream_key = StreamName::parse(&stream_key).name;
+
                 match &self.state {
                     State::Connected { .. } => {
                         self.state = State::PlaybackRequested {
-                            request_id: request_id.clone(),
+                            request_id,
                             app_name: app_name.clone(),
                             stream_key: stream_key.clone(),
                             stream_id,
@@ -567,6 +683,11 @@ impl Connection {
 
             ServerSessionEvent::PlayStreamFinished { .. } => match &self.state {
                 State::Playing { .. } => {
+                    self.stats
+                        .lock()
+                        .unwrap()
+                        .set_role(self.id, ConnectionRole::Idle);
+
                     let message = StreamManagerMessage::PlaybackFinished {
                         connection_id: self.id,
                     };
@@ -587,6 +708,13 @@ impl Connection {
 
             ServerSessionEvent::PublishStreamFinished { .. } => match &self.state {
                 State::Publishing { .. } => {
+                    self.recording = None;
+                    self.push = None;
+                    self.stats
+                        .lock()
+                        .unwrap()
+                        .set_role(self.id, ConnectionRole::Idle);
+
                     let message = StreamManagerMessage::PublishFinished {
                         connection_id: self.id,
                     };
@@ -610,12 +738,84 @@ impl Connection {
 
         Ok(ConnectionAction::None)
     }
+
+    /// Starts recording the stream the client requested to publish, if it matches one of the
+    /// configured patterns
+    fn start_recording(&self, app_name: &str, stream_key: &str) -> Option<Recording> {
+        let settings = self.config.recording.as_ref()?;
+        let stream_app_name = AppName::parse(app_name).path();
+        let stream_name = StreamName::parse(stream_key).name;
+        if !settings.should_record(&stream_app_name, &stream_name) {
+            return None;
+        }
+
+        match Recording::start(
+            self.id,
+            settings,
+            app_name.to_string(),
+            stream_key.to_string(),
+        ) {
+            Ok(recording) => {
+                println!(
+                    "Connection {}: Recording {}/{} to {}",
+                    self.id,
+                    stream_app_name,
+                    stream_name,
+                    settings.directory.display()
+                );
+
+                Some(recording)
+            }
+
+            Err(error) => {
+                eprintln!(
+                    "Connection {}: Failed to start recording: {}",
+                    self.id, error
+                );
+
+                None
+            }
+        }
+    }
+
+    /// Returns the id of the request the event was raised for, and why it should be rejected,
+    /// if the request is for an app that is not allowed or does not have a valid token.
+    fn find_rejection_reason(&self, event: &ServerSessionEvent) -> Option<(u32, String)> {
+        if let ServerSessionEvent::ConnectionRequested {
+            request_id,
+            app_name,
+        } = event
+        {
+            if !self.config.is_app_allowed(app_name) {
+                return Some((*request_id, format!("App '{}' is not allowed", app_name)));
+            }
+        }
+
+        let authorization = self
+            .authorizer
+            .as_ref()?
+            .authorize(event, SystemTime::now())?;
+
+        match authorization.result {
+            Ok(()) => None,
+            Err(error) => Some((authorization.request_id, error.to_string())),
+        }
+    }
 }
 
-async fn connection_reader(
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.connection_closed(self.id);
+        }
+    }
+}
+
+async fn connection_reader<S: AsyncRead>(
     connection_id: i32,
-    mut stream: ReadHalf<TcpStream>,
+    mut stream: ReadHalf<S>,
     manager: mpsc::UnboundedSender<Bytes>,
+    stats: SharedStats,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let mut buffer = BytesMut::with_capacity(4096);
 
@@ -625,6 +825,11 @@ async fn connection_reader(
             break;
         }
 
+        stats
+            .lock()
+            .unwrap()
+            .record_bytes_received(connection_id, bytes_read);
+
         let bytes = buffer.split_off(bytes_read);
         if !send(&manager, buffer.freeze()) {
             break;
@@ -637,10 +842,11 @@ async fn connection_reader(
     Ok(())
 }
 
-async fn connection_writer(
+async fn connection_writer<S: AsyncWrite>(
     connection_id: i32,
-    mut stream: WriteHalf<TcpStream>,
+    mut stream: WriteHalf<S>,
     mut packets_to_send: mpsc::UnboundedReceiver<Packet>,
+    stats: SharedStats,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     const BACKLOG_THRESHOLD: usize = 100;
     let mut send_queue = VecDeque::new();
@@ -675,6 +881,10 @@ async fn connection_writer(
         for packet in send_queue.drain(..) {
             if send_optional_packets || !packet.can_be_dropped {
                 stream.write_all(packet.bytes.as_ref()).await?;
+                stats
+                    .lock()
+                    .unwrap()
+                    .record_bytes_sent(connection_id, packet.bytes.len());
             }
         }
     }
@@ -0,0 +1,83 @@
+use crate::spawn;
+use crate::stats::SharedStats;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Requests with headers larger than this are not answered
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Serves the server's statistics over HTTP.  Only as much HTTP/1.1 is understood as is needed
+/// to answer `GET` requests from browsers, curl, and Prometheus, and the connection is closed
+/// after every response.
+///
+/// * `/stats` returns the connections and streams on the server as JSON
+/// * `/metrics` returns the metrics registry's counters for Prometheus to scrape
+pub async fn serve(
+    listener: TcpListener,
+    stats: SharedStats,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        spawn(respond(stream, stats.clone()));
+    }
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    stats: SharedStats,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|bytes| bytes == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_SIZE {
+            return Err("HTTP request was too large".into());
+        }
+
+        let bytes_read = stream.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+
+        request.extend_from_slice(&buffer[..bytes_read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line
+        .next()
+        .and_then(|target| target.split('?').next())
+        .unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/stats") => {
+            let snapshot = stats.lock().unwrap().snapshot();
+            let json = serde_json::to_string_pretty(&snapshot)?;
+            ("200 OK", "application/json", json)
+        }
+
+        ("GET", "/metrics") => {
+            let metrics = stats.lock().unwrap().render_prometheus();
+            ("200 OK", "text/plain; version=0.0.4", metrics)
+        }
+
+        ("GET", _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Only GET requests are supported\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
@@ -1,32 +1,119 @@
+use crate::config::Config;
 use crate::connection::Connection;
+use crate::stats::{ServerStats, SharedStats};
+use crate::stream_manager::StreamManagerMessage;
+use rml_rtmp_tokio::rtmps::rustls::ServerConfig;
 use std::fmt::Display;
 use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::net::TcpListener;
 use tokio::sync::mpsc::UnboundedSender;
 
+mod config;
 mod connection;
+mod http;
+mod recording;
+mod relay;
+mod stats;
 mod stream_manager;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let manager_sender = stream_manager::start();
+    let config = match std::env::args().nth(1) {
+        Some(path) => Config::load(Path::new(&path))?,
+        None => {
+            println!("No config file given, using the default settings");
+            Config::new()
+        }
+    };
+
+    let config = Arc::new(config);
+    let manager_sender = stream_manager::start(config.clone());
+    let next_id = Arc::new(AtomicI32::new(0));
+    let stats = Arc::new(Mutex::new(ServerStats::new()));
+
+    let mut listeners = Vec::new();
+    for address in &config.listen {
+        let listener = TcpListener::bind(address).await?;
+        println!("Listening for connections on {}", address);
 
-    println!("Listening for connections on port 1935");
-    let listener = TcpListener::bind("0.0.0.0:1935").await?;
-    let mut current_id = 0;
+        listeners.push(tokio::spawn(accept_connections(
+            listener,
+            None,
+            config.clone(),
+            manager_sender.clone(),
+            next_id.clone(),
+            stats.clone(),
+        )));
+    }
+
+    if let Some(tls) = &config.tls {
+        let tls_config = tls.server_config()?;
+        for address in &tls.listen {
+            let listener = TcpListener::bind(address).await?;
+            println!("Listening for RTMPS connections on {}", address);
 
+            listeners.push(tokio::spawn(accept_connections(
+                listener,
+                Some(tls_config.clone()),
+                config.clone(),
+                manager_sender.clone(),
+                next_id.clone(),
+                stats.clone(),
+            )));
+        }
+    }
+
+    if let Some(http) = &config.http {
+        for address in &http.listen {
+            let listener = TcpListener::bind(address).await?;
+            println!("Serving statistics over HTTP on {}", address);
+
+            listeners.push(tokio::spawn(http::serve(listener, stats.clone())));
+        }
+    }
+
+    for listener in listeners {
+        listener.await??;
+    }
+
+    Ok(())
+}
+
+/// Accepts connections on the listener, establishing a TLS session on each one first if there
+/// is a TLS configuration
+async fn accept_connections(
+    listener: TcpListener,
+    tls_config: Option<Arc<ServerConfig>>,
+    config: Arc<Config>,
+    manager_sender: UnboundedSender<StreamManagerMessage>,
+    next_id: Arc<AtomicI32>,
+    stats: SharedStats,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     loop {
         let (stream, connection_info) = listener.accept().await?;
+        let current_id = next_id.fetch_add(1, Ordering::Relaxed);
+
+        let connection = Connection::new(
+            current_id,
+            connection_info,
+            manager_sender.clone(),
+            config.clone(),
+            stats.clone(),
+        );
 
-        let connection = Connection::new(current_id, manager_sender.clone());
         println!(
             "Connection {}: Connection received from {}",
             current_id,
             connection_info.ip()
         );
 
-        spawn(connection.start_handshake(stream));
-        current_id = current_id + 1;
+        match &tls_config {
+            Some(tls_config) => spawn(connection.start_rtmps(stream, tls_config.clone())),
+            None => spawn(connection.start_handshake(stream)),
+        }
     }
 }
 
@@ -45,8 +132,5 @@ where
 /// Sends a message over an unbounded receiver and returns true if the message was sent
 /// or false if the channel has been closed.
 fn send<T>(sender: &UnboundedSender<T>, message: T) -> bool {
-    match sender.send(message) {
-        Ok(_) => true,
-        Err(_) => false,
-    }
+    sender.send(message).is_ok()
 }
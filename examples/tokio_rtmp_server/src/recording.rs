@@ -0,0 +1,103 @@
+use crate::config::RecordingSettings;
+use rml_flv::{RecordedSegment, SegmentedRecorder, SegmentedRecordingConfig, StreamRecorderError};
+use rml_rtmp::names::{AppName, StreamName};
+use rml_rtmp::sessions::ServerSessionEvent;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Records a stream being published by a connection.  Files are written from a dedicated
+/// thread, so a slow disk doesn't hold up the connection's task.  The recording is finished
+/// when the publisher stops or the `Recording` is dropped.
+pub struct Recording {
+    sender: Sender<ServerSessionEvent>,
+}
+
+impl Recording {
+    /// Starts recording the stream.  The app name and stream key must be the ones raised by the
+    /// publisher's session, query strings included, so the recorder matches the session's
+    /// events.
+    pub fn start(
+        connection_id: i32,
+        settings: &RecordingSettings,
+        app_name: String,
+        stream_key: String,
+    ) -> Result<Self, StreamRecorderError> {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        let file_prefix = format!(
+            "{}_{}_{}",
+            file_safe(&AppName::parse(&app_name).path()),
+            file_safe(&StreamName::parse(&stream_key).name),
+            started_at
+        );
+
+        let mut config = SegmentedRecordingConfig::new(settings.directory.clone(), file_prefix);
+        if let Some(seconds) = settings.segment_duration_secs {
+            config.max_segment_duration = Some(Duration::from_secs(seconds));
+        }
+
+        let recorder = SegmentedRecorder::new(config, app_name, stream_key)?;
+        let (sender, receiver) = channel();
+        thread::spawn(move || record(connection_id, recorder, receiver));
+
+        Ok(Recording { sender })
+    }
+
+    /// Passes an event raised by the publisher's session to the recorder
+    pub fn record(&self, event: &ServerSessionEvent) {
+        let _ = self.sender.send(event.clone());
+    }
+}
+
+fn record(
+    connection_id: i32,
+    mut recorder: SegmentedRecorder,
+    events: Receiver<ServerSessionEvent>,
+) {
+    for event in events {
+        match recorder.handle_event(&event) {
+            Ok(segment) => log_segment(connection_id, segment),
+            Err(error) => {
+                eprintln!("Connection {}: Recording failed: {}", connection_id, error);
+                return;
+            }
+        }
+
+        if recorder.is_finished() {
+            return;
+        }
+    }
+
+    // The connection went away without the publisher stopping first
+    match recorder.finish() {
+        Ok(segment) => log_segment(connection_id, segment),
+        Err(error) => eprintln!("Connection {}: Recording failed: {}", connection_id, error),
+    }
+}
+
+fn log_segment(connection_id: i32, segment: Option<RecordedSegment>) {
+    if let Some(segment) = segment {
+        println!(
+            "Connection {}: Recorded {} ({:?}, {} bytes)",
+            connection_id,
+            segment.path.display(),
+            segment.duration,
+            segment.bytes
+        );
+    }
+}
+
+/// Replaces characters that can't be used in file names on every platform
+fn file_safe(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,85 @@
+//! Moves streams between this server and other RTMP servers with the relays from
+//! `rml_rtmp::relay`.  The relays do no I/O themselves, so each one is run by a task that
+//! opens its connections and passes bytes between them and the relay.
+
+mod pull;
+mod push;
+
+pub use pull::start_pull;
+pub use push::Push;
+
+use bytes::{Bytes, BytesMut};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Something that happened on a connection to a remote server
+pub enum RemoteEvent {
+    Opened,
+    BytesReceived(Bytes),
+    Closed,
+}
+
+/// Opens a connection to the remote server after the delay.  Everything that happens on the
+/// connection is reported over the events channel along with the id, which tells apart the
+/// connections of relays with more than one.  Bytes sent over the returned sender are written to
+/// the connection, and dropping the sender closes it.  `Closed` is always the last event sent,
+/// including when the connection could not be opened.
+fn connect<T>(
+    address: String,
+    delay: Duration,
+    id: T,
+    events: UnboundedSender<(T, RemoteEvent)>,
+) -> UnboundedSender<Vec<u8>>
+where
+    T: Copy + Send + 'static,
+{
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        if let Err(error) = run_connection(&address, id, &events, receiver).await {
+            eprintln!("Relay connection to {} failed: {}", address, error);
+        }
+
+        let _ = events.send((id, RemoteEvent::Closed));
+    });
+
+    sender
+}
+
+async fn run_connection<T: Copy>(
+    address: &str,
+    id: T,
+    events: &UnboundedSender<(T, RemoteEvent)>,
+    mut outbound: mpsc::UnboundedReceiver<Vec<u8>>,
+) -> Result<(), std::io::Error> {
+    let mut stream = TcpStream::connect(address).await?;
+    if events.send((id, RemoteEvent::Opened)).is_err() {
+        return Ok(());
+    }
+
+    let mut buffer = BytesMut::with_capacity(4096);
+    loop {
+        tokio::select! {
+            bytes_read = stream.read_buf(&mut buffer) => {
+                if bytes_read? == 0 {
+                    return Ok(());
+                }
+
+                let bytes = buffer.split().freeze();
+                if events.send((id, RemoteEvent::BytesReceived(bytes))).is_err() {
+                    return Ok(());
+                }
+            }
+
+            bytes = outbound.recv() => {
+                match bytes {
+                    Some(bytes) => stream.write_all(&bytes).await?,
+                    None => return Ok(()),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use super::{connect, RemoteEvent};
+use crate::config::RelayTarget;
+use crate::send;
+use crate::stream_manager::{ConnectionMessage, StreamManagerMessage};
+use rml_rtmp::hub::{StreamHub, StreamHubResult, SubscriberDropPolicy};
+use rml_rtmp::relay::{PullRelay, PullRelayConfig, PullRelayResult};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// The id the relay publishes into its hub with
+const RELAY_ID: usize = 0;
+
+/// The id the task subscribes to the relay's hub with, to pass the stream to the stream manager
+const SUBSCRIBER_ID: usize = 1;
+
+/// Starts pulling the stream from the origin, publishing it to the stream manager as if it was
+/// published by a client with the connection id.  The pull stops once the stream manager
+/// forgets about the connection, which it does when the last player leaves the stream.
+pub fn start_pull(
+    connection_id: i32,
+    origin: &RelayTarget,
+    app_name: String,
+    stream_key: String,
+    manager: UnboundedSender<StreamManagerMessage>,
+) {
+    let mut config = PullRelayConfig::new(origin.app.clone(), stream_key);
+    config.local_app_name = app_name;
+    config.session_config.tc_url = Some(format!("rtmp://{}/{}", origin.address, origin.app));
+
+    tokio::spawn(run(connection_id, origin.address.clone(), config, manager));
+}
+
+async fn run(
+    connection_id: i32,
+    address: String,
+    config: PullRelayConfig,
+    manager: UnboundedSender<StreamManagerMessage>,
+) {
+    let (sender, mut messages) = mpsc::unbounded_channel();
+    let (_disconnection_sender, disconnection) = mpsc::unbounded_channel();
+    let message = StreamManagerMessage::NewConnection {
+        connection_id,
+        sender,
+        disconnection,
+    };
+
+    if !send(&manager, message) {
+        return;
+    }
+
+    let message = StreamManagerMessage::PublishRequest {
+        connection_id,
+        rtmp_app: config.local_app_name.clone(),
+        stream_key: config.local_stream_key.clone(),
+        request_id: 0,
+    };
+
+    if !send(&manager, message) {
+        return;
+    }
+
+    match messages.recv().await {
+        Some(ConnectionMessage::RequestAccepted { .. }) => (),
+        _ => {
+            println!(
+                "Pull {}: Not allowed to publish {}/{}",
+                connection_id, config.local_app_name, config.local_stream_key
+            );
+
+            return;
+        }
+    }
+
+    println!(
+        "Pull {}: Pulling {}/{} from {}",
+        connection_id, config.remote_app_name, config.remote_stream_key, address
+    );
+
+    let mut hub = StreamHub::new();
+    if let Err(error) = hub.join_as_subscriber(
+        SUBSCRIBER_ID,
+        &config.local_app_name,
+        &config.local_stream_key,
+        0,
+        SubscriberDropPolicy::DeliverAll,
+    ) {
+        eprintln!("Pull {}: Failed to join hub: {}", connection_id, error);
+        return;
+    }
+
+    let mut relay = PullRelay::new(config, RELAY_ID);
+    let (events_sender, mut events) = mpsc::unbounded_channel();
+    let mut writer = Some(connect(
+        address.clone(),
+        Duration::from_secs(0),
+        (),
+        events_sender.clone(),
+    ));
+
+    loop {
+        let event = tokio::select! {
+            message = messages.recv() => match message {
+                Some(_) => continue,
+                None => break, // the stream manager no longer needs the stream
+            },
+
+            event = events.recv() => match event {
+                Some((_, event)) => event,
+                None => break,
+            },
+        };
+
+        let results = match event {
+            RemoteEvent::Opened => relay
+                .connection_opened()
+                .map(|bytes| vec![PullRelayResult::OutboundBytes(bytes)]),
+
+            // Bytes still arriving after the connection was abandoned are ignored
+            RemoteEvent::BytesReceived(_) if writer.is_none() => continue,
+            RemoteEvent::BytesReceived(bytes) => relay.handle_input(&bytes, &mut hub),
+            RemoteEvent::Closed => Ok(relay.connection_closed(&mut hub)),
+        };
+
+        let results = match results {
+            Ok(results) => results,
+            Err(error) => {
+                eprintln!("Pull {}: Relay error: {}", connection_id, error);
+                writer = None;
+                continue;
+            }
+        };
+
+        for result in results {
+            match result {
+                PullRelayResult::OutboundBytes(bytes) => {
+                    if let Some(writer) = &writer {
+                        let _ = writer.send(bytes);
+                    }
+                }
+
+                PullRelayResult::HubResult(result) => {
+                    if let Some(message) = to_manager_message(connection_id, result) {
+                        if !send(&manager, message) {
+                            return;
+                        }
+                    }
+                }
+
+                PullRelayResult::ReconnectAfter(delay) => {
+                    println!("Pull {}: Reconnecting in {:?}", connection_id, delay);
+                    writer = Some(connect(address.clone(), delay, (), events_sender.clone()));
+                }
+            }
+        }
+    }
+
+    println!("Pull {}: Stopped", connection_id);
+}
+
+fn to_manager_message(connection_id: i32, result: StreamHubResult) -> Option<StreamManagerMessage> {
+    match result {
+        StreamHubResult::SendMetadata { metadata, .. } => {
+            Some(StreamManagerMessage::UpdatedStreamMetadata {
+                sending_connection_id: connection_id,
+                metadata: metadata.clone(),
+            })
+        }
+
+        StreamHubResult::SendVideoData {
+            data, timestamp, ..
+        } => Some(StreamManagerMessage::NewVideoData {
+            sending_connection_id: connection_id,
+            timestamp,
+            data,
+        }),
+
+        StreamHubResult::SendAudioData {
+            data, timestamp, ..
+        } => Some(StreamManagerMessage::NewAudioData {
+            sending_connection_id: connection_id,
+            timestamp,
+            data,
+        }),
+
+        StreamHubResult::PublishingFinished { .. } => None,
+    }
+}
@@ -0,0 +1,195 @@
+use super::{connect, RemoteEvent};
+use crate::config::RelayTarget;
+use rml_rtmp::hub::StreamHub;
+use rml_rtmp::relay::{PushDestinationConfig, PushRelay, PushRelayConfig, PushRelayResult};
+use rml_rtmp::sessions::ServerSessionEvent;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+/// The id the publisher's events are published into the relay's hub with
+const PUBLISHER_ID: usize = 0;
+
+/// The id the relay subscribes to its hub with
+const RELAY_ID: usize = 1;
+
+/// Pushes a stream being published by a connection to every configured upstream server.  The
+/// publisher's session events are passed to a task that owns the relay, which stops pushing
+/// when the `Push` is dropped.
+pub struct Push {
+    sender: UnboundedSender<ServerSessionEvent>,
+}
+
+impl Push {
+    pub fn start(
+        connection_id: i32,
+        targets: &[RelayTarget],
+        app_name: String,
+        stream_key: String,
+    ) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(
+            connection_id,
+            targets.to_vec(),
+            app_name,
+            stream_key,
+            receiver,
+        ));
+
+        Push { sender }
+    }
+
+    /// Passes an event raised by the publisher's session to the relay
+    pub fn send(&self, event: &ServerSessionEvent) {
+        let _ = self.sender.send(event.clone());
+    }
+}
+
+async fn run(
+    connection_id: i32,
+    targets: Vec<RelayTarget>,
+    app_name: String,
+    stream_key: String,
+    mut publisher_events: UnboundedReceiver<ServerSessionEvent>,
+) {
+    // The relay can only push streams from a hub, so the publisher gets a hub of its own
+    let mut hub = StreamHub::new();
+    if let Err(error) = hub.join_as_publisher(PUBLISHER_ID, &app_name, &stream_key) {
+        eprintln!("Push {}: Failed to join hub: {}", connection_id, error);
+        return;
+    }
+
+    let config = PushRelayConfig::new(app_name, stream_key.clone());
+    let mut relay = PushRelay::new(config, RELAY_ID);
+    let mut addresses = HashMap::new();
+    let mut writers = HashMap::new();
+    let (events_sender, mut events) = mpsc::unbounded_channel();
+
+    for target in &targets {
+        let mut config = PushDestinationConfig::new(target.app.clone(), stream_key.clone());
+        config.session_config.tc_url = Some(format!("rtmp://{}/{}", target.address, target.app));
+
+        let destination_id = relay.add_destination(config);
+        let writer = connect(
+            target.address.clone(),
+            Duration::from_secs(0),
+            destination_id,
+            events_sender.clone(),
+        );
+
+        println!(
+            "Push {}: Pushing to rtmp://{}/{}/{}",
+            connection_id, target.address, target.app, stream_key
+        );
+
+        addresses.insert(destination_id, target.address.clone());
+        writers.insert(destination_id, writer);
+    }
+
+    let mut results = match relay.join_hub(&mut hub) {
+        Ok(results) => results,
+        Err(error) => {
+            eprintln!("Push {}: Failed to join hub: {}", connection_id, error);
+            return;
+        }
+    };
+
+    loop {
+        for result in results.drain(..) {
+            match result {
+                PushRelayResult::OutboundBytes {
+                    destination_id,
+                    bytes,
+                } => {
+                    if let Some(writer) = writers.get(&destination_id) {
+                        let _ = writer.send(bytes);
+                    }
+                }
+
+                PushRelayResult::StatusChanged {
+                    destination_id,
+                    status,
+                } => println!(
+                    "Push {}: Destination {} is now {:?}",
+                    connection_id, addresses[&destination_id], status
+                ),
+
+                PushRelayResult::ReconnectAfter {
+                    destination_id,
+                    delay,
+                } => {
+                    let address = addresses[&destination_id].clone();
+                    let writer = connect(address, delay, destination_id, events_sender.clone());
+                    writers.insert(destination_id, writer);
+                }
+
+                PushRelayResult::DestinationFailed {
+                    destination_id,
+                    error,
+                } => {
+                    eprintln!(
+                        "Push {}: Destination {} failed: {}",
+                        connection_id, addresses[&destination_id], error
+                    );
+
+                    writers.remove(&destination_id);
+                }
+            }
+        }
+
+        tokio::select! {
+            event = publisher_events.recv() => {
+                let event = match event {
+                    Some(event) => event,
+                    None => break, // the publisher has gone away
+                };
+
+                let hub_results = match hub.handle_publisher_event(PUBLISHER_ID, &event) {
+                    Ok(hub_results) => hub_results,
+                    Err(error) => {
+                        eprintln!("Push {}: Failed to publish into hub: {}", connection_id, error);
+                        break;
+                    }
+                };
+
+                for hub_result in hub_results {
+                    results.extend(relay.handle_hub_result(hub_result));
+                }
+            }
+
+            event = events.recv() => {
+                let (destination_id, event) = match event {
+                    Some(event) => event,
+                    None => break,
+                };
+
+                let destination_results = match event {
+                    RemoteEvent::Opened => relay.connection_opened(destination_id),
+
+                    // Bytes still arriving after the connection was abandoned are ignored
+                    RemoteEvent::BytesReceived(_) if !writers.contains_key(&destination_id) => {
+                        continue
+                    }
+
+                    RemoteEvent::BytesReceived(bytes) => relay.handle_input(destination_id, &bytes),
+                    RemoteEvent::Closed => Ok(relay.connection_closed(destination_id)),
+                };
+
+                match destination_results {
+                    Ok(destination_results) => results.extend(destination_results),
+                    Err(error) => {
+                        eprintln!(
+                            "Push {}: Destination {} failed: {}",
+                            connection_id, addresses[&destination_id], error
+                        );
+
+                        writers.remove(&destination_id);
+                    }
+                }
+            }
+        }
+    }
+
+    println!("Push {}: Stopped", connection_id);
+}
@@ -0,0 +1,222 @@
+use crate::stream_manager::ConnectionMessage;
+use rml_rtmp::admin::{ConnectionRole, ServerSnapshot, StreamInfo};
+use rml_rtmp::hub::StreamHubResult;
+use rml_rtmp::metrics::MetricsRegistry;
+use rml_rtmp::sessions::ServerSessionEvent;
+use rml_rtmp::stats::{StreamStatsConfig, StreamStatsTracker};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// The statistics shared between every connection and the HTTP endpoint
+pub type SharedStats = Arc<Mutex<ServerStats>>;
+
+/// Keeps track of what every connection is doing, so the HTTP endpoint can report it with the
+/// admin data model from `rml_rtmp::admin` and the counters of `rml_rtmp::metrics`.  Streams
+/// pulled from an origin are not connections, so they show up with viewers but no publisher.
+pub struct ServerStats {
+    started_at: Instant,
+    metrics: MetricsRegistry,
+    connections: HashMap<i32, ConnectionStats>,
+}
+
+struct ConnectionStats {
+    remote_address: SocketAddr,
+    connected_at: Instant,
+    app_name: Option<String>,
+    role: ConnectionRole,
+
+    /// Measures the stream the connection is publishing, if it's publishing one
+    publishing_stats: Option<StreamStatsTracker>,
+}
+
+impl ServerStats {
+    pub fn new() -> Self {
+        ServerStats {
+            started_at: Instant::now(),
+            metrics: MetricsRegistry::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    pub fn connection_opened(&mut self, connection_id: i32, remote_address: SocketAddr) {
+        self.metrics.session_opened(connection_id as usize);
+        self.connections.insert(
+            connection_id,
+            ConnectionStats {
+                remote_address,
+                connected_at: Instant::now(),
+                app_name: None,
+                role: ConnectionRole::Idle,
+                publishing_stats: None,
+            },
+        );
+    }
+
+    pub fn connection_closed(&mut self, connection_id: i32) {
+        self.metrics.session_closed(connection_id as usize);
+        self.connections.remove(&connection_id);
+    }
+
+    pub fn record_bytes_received(&mut self, connection_id: i32, bytes: usize) {
+        self.metrics
+            .record_bytes_received(connection_id as usize, bytes);
+    }
+
+    pub fn record_bytes_sent(&mut self, connection_id: i32, bytes: usize) {
+        self.metrics
+            .record_bytes_sent(connection_id as usize, bytes);
+    }
+
+    /// Records an event raised by the connection's session
+    pub fn record_event(&mut self, connection_id: i32, event: &ServerSessionEvent) {
+        self.metrics
+            .record_server_event(connection_id as usize, event);
+
+        let tracker = match self
+            .connections
+            .get_mut(&connection_id)
+            .and_then(|connection| connection.publishing_stats.as_mut())
+        {
+            Some(tracker) => tracker,
+            None => return,
+        };
+
+        match event {
+            ServerSessionEvent::VideoDataReceived {
+                data, timestamp, ..
+            } => tracker.record_video(data, *timestamp, Instant::now()),
+
+            ServerSessionEvent::AudioDataReceived {
+                data, timestamp, ..
+            } => tracker.record_audio(data, *timestamp, Instant::now()),
+
+            _ => (),
+        }
+    }
+
+    /// Records media the stream manager passed to a connection that is playing a stream
+    pub fn record_media_sent(
+        &mut self,
+        connection_id: i32,
+        stream_id: u32,
+        message: &ConnectionMessage,
+    ) {
+        // The registry counts media by the hub results it would have been routed with
+        let result = match message {
+            ConnectionMessage::NewVideoData {
+                timestamp,
+                data,
+                can_be_dropped,
+            } => StreamHubResult::SendVideoData {
+                subscriber_id: connection_id as usize,
+                stream_id,
+                data: data.clone(),
+                timestamp: *timestamp,
+                can_be_dropped: *can_be_dropped,
+            },
+
+            ConnectionMessage::NewAudioData {
+                timestamp,
+                data,
+                can_be_dropped,
+            } => StreamHubResult::SendAudioData {
+                subscriber_id: connection_id as usize,
+                stream_id,
+                data: data.clone(),
+                timestamp: *timestamp,
+                can_be_dropped: *can_be_dropped,
+            },
+
+            _ => return,
+        };
+
+        self.metrics.record_hub_result(&result);
+    }
+
+    pub fn set_app_name(&mut self, connection_id: i32, app_name: &str) {
+        if let Some(connection) = self.connections.get_mut(&connection_id) {
+            connection.app_name = Some(app_name.to_string());
+        }
+    }
+
+    /// Changes what the connection is doing.  Measuring a published stream starts over each
+    /// time the connection starts publishing.
+    pub fn set_role(&mut self, connection_id: i32, role: ConnectionRole) {
+        if let Some(connection) = self.connections.get_mut(&connection_id) {
+            connection.publishing_stats = match role {
+                ConnectionRole::Publishing { .. } => {
+                    Some(StreamStatsTracker::new(StreamStatsConfig::new()))
+                }
+
+                _ => None,
+            };
+
+            connection.role = role;
+        }
+    }
+
+    /// The connections and streams on the server right now
+    pub fn snapshot(&mut self) -> ServerSnapshot {
+        let now = Instant::now();
+        let mut snapshot = ServerSnapshot::new(now.duration_since(self.started_at));
+
+        for (id, connection) in &mut self.connections {
+            let info = snapshot.connection_mut(*id as usize);
+            info.remote_address = Some(connection.remote_address.to_string());
+            info.app_name = connection.app_name.clone();
+            info.role = connection.role.clone();
+            info.connected_for = now.duration_since(connection.connected_at);
+
+            if let Some(metrics) = self.metrics.session(*id as usize) {
+                info.bytes_received = metrics.bytes_received;
+                info.bytes_sent = metrics.bytes_sent;
+            }
+
+            let (app_name, stream_key) = match &connection.role {
+                ConnectionRole::Publishing {
+                    app_name,
+                    stream_key,
+                }
+                | ConnectionRole::Playing {
+                    app_name,
+                    stream_key,
+                } => (app_name, stream_key),
+
+                ConnectionRole::Idle => continue,
+            };
+
+            if snapshot.stream_mut(app_name, stream_key).is_none() {
+                snapshot.streams.push(StreamInfo::new(app_name, stream_key));
+            }
+
+            let stream = snapshot.stream_mut(app_name, stream_key).unwrap();
+            match &mut connection.publishing_stats {
+                Some(tracker) => {
+                    stream.publisher_id = Some(*id as usize);
+                    stream.apply_stats(&tracker.snapshot(now));
+                }
+
+                None => stream.viewers += 1,
+            }
+        }
+
+        snapshot
+            .streams
+            .sort_by(|a, b| (&a.app_name, &a.stream_key).cmp(&(&b.app_name, &b.stream_key)));
+
+        snapshot
+    }
+
+    /// The metrics registry's counters in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        self.metrics.render_prometheus()
+    }
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        ServerStats::new()
+    }
+}
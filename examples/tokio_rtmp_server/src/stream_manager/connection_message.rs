@@ -1,6 +1,7 @@
 use bytes::Bytes;
 use rml_rtmp::sessions::StreamMetadata;
 use rml_rtmp::time::RtmpTimestamp;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub enum ConnectionMessage {
@@ -25,6 +26,6 @@ pub enum ConnectionMessage {
     },
 
     NewMetadata {
-        metadata: StreamMetadata,
+        metadata: Arc<StreamMetadata>,
     },
 }
@@ -3,6 +3,8 @@ mod player_details;
 mod publish_details;
 mod stream_manager_message;
 
+use crate::config::Config;
+use crate::relay::start_pull;
 use crate::send;
 use bytes::Bytes;
 use futures::future::select_all;
@@ -10,6 +12,7 @@ use futures::future::BoxFuture;
 use rml_rtmp::sessions::StreamMetadata;
 use rml_rtmp::time::RtmpTimestamp;
 use std::collections::hash_map::HashMap;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
@@ -19,10 +22,10 @@ pub use player_details::PlayerDetails;
 pub use publish_details::PublishDetails;
 pub use stream_manager_message::StreamManagerMessage;
 
-pub fn start() -> mpsc::UnboundedSender<StreamManagerMessage> {
+pub fn start(config: Arc<Config>) -> mpsc::UnboundedSender<StreamManagerMessage> {
     let (sender, receiver) = mpsc::unbounded_channel();
 
-    let manager = StreamManager::new();
+    let manager = StreamManager::new(config, sender.clone());
     tokio::spawn(manager.run(receiver));
 
     sender
@@ -44,16 +47,28 @@ struct StreamManager<'a> {
     sender_by_connection_id: HashMap<i32, mpsc::UnboundedSender<ConnectionMessage>>,
     key_by_connection_id: HashMap<i32, String>,
     new_disconnect_futures: Vec<BoxFuture<'a, FutureResult>>,
+    config: Arc<Config>,
+
+    // Pulls publish to the stream manager like clients do, so they need a sender of their own
+    own_sender: UnboundedSender<StreamManagerMessage>,
+    pull_id_by_key: HashMap<String, i32>,
+    next_pull_id: i32,
 }
 
 impl<'a> StreamManager<'a> {
-    fn new() -> Self {
+    fn new(config: Arc<Config>, own_sender: UnboundedSender<StreamManagerMessage>) -> Self {
         StreamManager {
             publish_details: HashMap::new(),
             players_by_key: HashMap::new(),
             sender_by_connection_id: HashMap::new(),
             key_by_connection_id: HashMap::new(),
             new_disconnect_futures: Vec::new(),
+            config,
+            own_sender,
+            pull_id_by_key: HashMap::new(),
+
+            // Negative ids can't clash with the ids of client connections
+            next_pull_id: -1,
         }
     }
 
@@ -71,7 +86,45 @@ impl<'a> StreamManager<'a> {
                     self.publish_details.remove(&key);
                 }
             }
+
+            if self.pull_id_by_key.get(&key) == Some(&connection_id) {
+                self.pull_id_by_key.remove(&key);
+            }
+
+            // Pulled streams are only needed while someone is watching them
+            let has_players = self.players_by_key.get(&key).is_some_and(|x| !x.is_empty());
+            if !has_players {
+                if let Some(pull_id) = self.pull_id_by_key.remove(&key) {
+                    self.cleanup_connection(pull_id);
+                }
+            }
+        }
+    }
+
+    /// Starts pulling the stream from the origin, if an origin is configured and the stream
+    /// isn't already being published or pulled
+    fn start_pull_if_needed(&mut self, rtmp_app: &str, stream_key: &str) {
+        let origin = match &self.config.relay.pull {
+            Some(origin) => origin,
+            None => return,
+        };
+
+        let key = format!("{}/{}", rtmp_app, stream_key);
+        if self.publish_details.contains_key(&key) || self.pull_id_by_key.contains_key(&key) {
+            return;
         }
+
+        let pull_id = self.next_pull_id;
+        self.next_pull_id -= 1;
+        self.pull_id_by_key.insert(key, pull_id);
+
+        start_pull(
+            pull_id,
+            origin,
+            rtmp_app.to_string(),
+            stream_key.to_string(),
+            self.own_sender.clone(),
+        );
     }
 
     async fn run(mut self, receiver: UnboundedReceiver<StreamManagerMessage>) {
@@ -89,7 +142,7 @@ impl<'a> StreamManager<'a> {
 
         loop {
             let (result, _index, remaining_futures) = futures.await;
-            let mut new_futures = Vec::from(remaining_futures);
+            let mut new_futures = remaining_futures;
 
             match result {
                 FutureResult::MessageReceived { receiver, message } => {
@@ -207,7 +260,7 @@ impl<'a> StreamManager<'a> {
                 "Connection {} is requesting to publish, but its already being tracked",
                 connection_id
             );
-            if !send(&sender, ConnectionMessage::RequestDenied { request_id }) {
+            if !send(sender, ConnectionMessage::RequestDenied { request_id }) {
                 self.cleanup_connection(connection_id);
             }
 
@@ -221,7 +274,7 @@ impl<'a> StreamManager<'a> {
                 println!("Publish request by connection {} for stream '{}' rejected as it's already being published by connection {}",
                          connection_id, key, details.connection_id);
 
-                if !send(&sender, ConnectionMessage::RequestDenied { request_id }) {
+                if !send(sender, ConnectionMessage::RequestDenied { request_id }) {
                     self.cleanup_connection(connection_id);
                 }
 
@@ -240,7 +293,7 @@ impl<'a> StreamManager<'a> {
             },
         );
 
-        if !send(&sender, ConnectionMessage::RequestAccepted { request_id }) {
+        if !send(sender, ConnectionMessage::RequestAccepted { request_id }) {
             self.cleanup_connection(connection_id);
         }
     }
@@ -266,7 +319,7 @@ impl<'a> StreamManager<'a> {
                 "Playback requested by connection {} but its already being tracked",
                 connection_id
             );
-            if !send(&sender, ConnectionMessage::RequestDenied { request_id }) {
+            if !send(sender, ConnectionMessage::RequestDenied { request_id }) {
                 self.cleanup_connection(connection_id);
             }
 
@@ -274,19 +327,22 @@ impl<'a> StreamManager<'a> {
         }
 
         let key = format!("{}/{}", rtmp_app, stream_key);
-        let connection_ids = self
-            .players_by_key
-            .entry(key.clone())
-            .or_insert(HashMap::new());
+        let connection_ids = self.players_by_key.entry(key.clone()).or_default();
         connection_ids.insert(connection_id, PlayerDetails::new(connection_id));
         self.key_by_connection_id.insert(connection_id, key.clone());
 
-        if !send(&sender, ConnectionMessage::RequestAccepted { request_id }) {
+        if !send(sender, ConnectionMessage::RequestAccepted { request_id }) {
             self.cleanup_connection(connection_id);
 
             return;
         }
 
+        self.start_pull_if_needed(&rtmp_app, &stream_key);
+        let sender = match self.sender_by_connection_id.get(&connection_id) {
+            Some(x) => x,
+            None => return,
+        };
+
         // If someone is publishing on this stream already, send the latest audio and video
         // sequence headers, so the client can view them.
 
@@ -300,7 +356,7 @@ impl<'a> StreamManager<'a> {
                 metadata: metadata.clone(),
             };
 
-            if !send(&sender, message) {
+            if !send(sender, message) {
                 self.cleanup_connection(connection_id);
                 return;
             }
@@ -313,7 +369,7 @@ impl<'a> StreamManager<'a> {
                 can_be_dropped: false,
             };
 
-            if !send(&sender, message) {
+            if !send(sender, message) {
                 self.cleanup_connection(connection_id);
                 return;
             }
@@ -326,9 +382,8 @@ impl<'a> StreamManager<'a> {
                 can_be_dropped: false,
             };
 
-            if !send(&sender, message) {
+            if !send(sender, message) {
                 self.cleanup_connection(connection_id);
-                return;
             }
         }
     }
@@ -352,7 +407,7 @@ impl<'a> StreamManager<'a> {
             None => return,
         };
 
-        let mut details = match self.publish_details.get_mut(key) {
+        let details = match self.publish_details.get_mut(key) {
             Some(x) => x,
             None => return,
         };
@@ -362,7 +417,7 @@ impl<'a> StreamManager<'a> {
         }
 
         if let Some(players) = self.players_by_key.get(key.as_str()) {
-            for (player_id, _) in players {
+            for player_id in players.keys() {
                 let sender = match self.sender_by_connection_id.get_mut(player_id) {
                     Some(x) => x,
                     None => return,
@@ -374,7 +429,7 @@ impl<'a> StreamManager<'a> {
                     can_be_dropped: true,
                 };
 
-                send(&sender, message);
+                send(sender, message);
             }
         }
     }
@@ -390,7 +445,7 @@ impl<'a> StreamManager<'a> {
             None => return,
         };
 
-        let mut details = match self.publish_details.get_mut(key) {
+        let details = match self.publish_details.get_mut(key) {
             Some(x) => x,
             None => return,
         };
@@ -406,7 +461,7 @@ impl<'a> StreamManager<'a> {
         }
 
         if let Some(players) = self.players_by_key.get_mut(key.as_str()) {
-            for (player_id, mut details) in players {
+            for (player_id, details) in players {
                 let sender = match self.sender_by_connection_id.get_mut(player_id) {
                     Some(x) => x,
                     None => return,
@@ -429,18 +484,18 @@ impl<'a> StreamManager<'a> {
                     can_be_dropped,
                 };
 
-                send(&sender, message);
+                send(sender, message);
             }
         }
     }
 
-    fn handle_new_metadata(&mut self, sending_connection_id: i32, metadata: StreamMetadata) {
+    fn handle_new_metadata(&mut self, sending_connection_id: i32, metadata: Arc<StreamMetadata>) {
         let key = match self.key_by_connection_id.get(&sending_connection_id) {
             Some(x) => x,
             None => return,
         };
 
-        let mut details = match self.publish_details.get_mut(key) {
+        let details = match self.publish_details.get_mut(key) {
             Some(x) => x,
             None => return,
         };
@@ -448,7 +503,7 @@ impl<'a> StreamManager<'a> {
         details.metadata = Some(metadata.clone());
 
         if let Some(players) = self.players_by_key.get(key.as_str()) {
-            for (player_id, _) in players {
+            for player_id in players.keys() {
                 let sender = match self.sender_by_connection_id.get_mut(player_id) {
                     Some(x) => x,
                     None => return,
@@ -457,7 +512,7 @@ impl<'a> StreamManager<'a> {
                 let message = ConnectionMessage::NewMetadata {
                     metadata: metadata.clone(),
                 };
-                send(&sender, message);
+                send(sender, message);
             }
         }
     }
@@ -465,17 +520,17 @@ impl<'a> StreamManager<'a> {
 
 fn is_video_sequence_header(data: &Bytes) -> bool {
     // This is assuming h264.
-    return data.len() >= 2 && data[0] == 0x17 && data[1] == 0x00;
+    data.len() >= 2 && data[0] == 0x17 && data[1] == 0x00
 }
 
 fn is_audio_sequence_header(data: &Bytes) -> bool {
     // This is assuming aac
-    return data.len() >= 2 && data[0] == 0xaf && data[1] == 0x00;
+    data.len() >= 2 && data[0] == 0xaf && data[1] == 0x00
 }
 
 fn is_video_keyframe(data: &Bytes) -> bool {
     // assumings h264
-    return data.len() >= 2 && data[0] == 0x17 && data[1] != 0x00; // 0x00 is the sequence header, don't count that for now
+    data.len() >= 2 && data[0] == 0x17 && data[1] != 0x00 // 0x00 is the sequence header, don't count that for now
 }
 
 async fn wait_for_client_disconnection(
@@ -1,3 +1,4 @@
+#[allow(dead_code)]
 pub struct PlayerDetails {
     pub connection_id: i32,
     pub has_received_video_keyframe: bool,
@@ -1,9 +1,10 @@
 use bytes::Bytes;
 use rml_rtmp::sessions::StreamMetadata;
+use std::sync::Arc;
 
 pub struct PublishDetails {
     pub video_sequence_header: Option<Bytes>,
     pub audio_sequence_header: Option<Bytes>,
-    pub metadata: Option<StreamMetadata>,
+    pub metadata: Option<Arc<StreamMetadata>>,
     pub connection_id: i32,
 }
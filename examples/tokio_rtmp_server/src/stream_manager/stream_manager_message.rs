@@ -2,6 +2,7 @@ use super::ConnectionMessage;
 use bytes::Bytes;
 use rml_rtmp::sessions::StreamMetadata;
 use rml_rtmp::time::RtmpTimestamp;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 #[derive(Debug)]
@@ -28,7 +29,7 @@ pub enum StreamManagerMessage {
 
     UpdatedStreamMetadata {
         sending_connection_id: i32,
-        metadata: StreamMetadata,
+        metadata: Arc<StreamMetadata>,
     },
 
     NewVideoData {
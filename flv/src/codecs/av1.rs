@@ -0,0 +1,301 @@
+use super::bit_reader::BitReader;
+use bytes::Bytes;
+use errors::CodecConfigError;
+use rml_rtmp::sessions::StreamMetadata;
+
+const RECORD_HEADER_LENGTH: usize = 4;
+const SEQUENCE_HEADER_OBU_TYPE: u8 = 1;
+
+/// The AV1CodecConfigurationRecord (`av1C`) carried in the sequence start packets of enhanced
+/// RTMP AV1 streams, which is everything after the first 5 bytes of the video data
+#[derive(PartialEq, Debug, Clone)]
+pub struct Av1DecoderConfig {
+    pub seq_profile: u8,
+    pub seq_level_idx: u8,
+    pub seq_tier: bool,
+    pub high_bitdepth: bool,
+    pub twelve_bit: bool,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    pub chroma_sample_position: u8,
+
+    /// The OBUs following the record's fields, which normally contain the sequence header
+    pub config_obus: Bytes,
+}
+
+/// The stream properties read from an AV1 sequence header OBU
+#[derive(PartialEq, Debug, Clone)]
+pub struct Av1SequenceHeader {
+    pub seq_profile: u8,
+    pub seq_level_idx: u8,
+    pub seq_tier: bool,
+
+    /// The largest width of any frame in the stream, in pixels
+    pub max_frame_width: u32,
+
+    /// The largest height of any frame in the stream, in pixels
+    pub max_frame_height: u32,
+
+    /// The frame rate, if the encoder included timing information with a constant frame rate
+    pub frame_rate: Option<f32>,
+}
+
+impl Av1DecoderConfig {
+    /// Parses an AV1CodecConfigurationRecord
+    pub fn parse(record: &[u8]) -> Result<Av1DecoderConfig, CodecConfigError> {
+        if record.len() < RECORD_HEADER_LENGTH {
+            return Err(CodecConfigError::Truncated);
+        }
+
+        // The first bit is a marker that's always set, followed by the version
+        let version = record[0] & 0x7f;
+        if record[0] & 0x80 == 0 || version != 1 {
+            return Err(CodecConfigError::UnsupportedVersion { version });
+        }
+
+        Ok(Av1DecoderConfig {
+            seq_profile: record[1] >> 5,
+            seq_level_idx: record[1] & 0x1f,
+            seq_tier: record[2] & 0x80 != 0,
+            high_bitdepth: record[2] & 0x40 != 0,
+            twelve_bit: record[2] & 0x20 != 0,
+            monochrome: record[2] & 0x10 != 0,
+            chroma_subsampling_x: record[2] & 0x08 != 0,
+            chroma_subsampling_y: record[2] & 0x04 != 0,
+            chroma_sample_position: record[2] & 0x03,
+            config_obus: Bytes::copy_from_slice(&record[RECORD_HEADER_LENGTH..]),
+        })
+    }
+
+    /// Parses the sequence header OBU contained in the record
+    pub fn sequence_header(&self) -> Result<Av1SequenceHeader, CodecConfigError> {
+        let mut position = 0;
+        while position < self.config_obus.len() {
+            let header = self.config_obus[position];
+            let obu_type = (header >> 3) & 0x0f;
+            let has_extension = header & 0x04 != 0;
+            let has_size = header & 0x02 != 0;
+            position += 1 + has_extension as usize;
+
+            // OBUs without a size run to the end of the data
+            let size = if has_size {
+                read_leb128(&self.config_obus, &mut position)?
+            } else {
+                self.config_obus.len().saturating_sub(position)
+            };
+
+            let end = position + size;
+            if end > self.config_obus.len() {
+                return Err(CodecConfigError::Truncated);
+            }
+
+            if obu_type == SEQUENCE_HEADER_OBU_TYPE {
+                return Av1SequenceHeader::parse(&self.config_obus[position..end]);
+            }
+
+            position = end;
+        }
+
+        Err(CodecConfigError::MissingParameterSet)
+    }
+
+    /// The RFC 6381 codec string (e.g. `av01.0.04M.08`) used by HLS and DASH manifests
+    pub fn codec_string(&self) -> String {
+        let bit_depth = match (self.high_bitdepth, self.twelve_bit) {
+            (true, true) => 12,
+            (true, false) => 10,
+            _ => 8,
+        };
+
+        format!(
+            "av01.{}.{:02}{}.{:02}",
+            self.seq_profile,
+            self.seq_level_idx,
+            if self.seq_tier { "H" } else { "M" },
+            bit_depth
+        )
+    }
+}
+
+impl Av1SequenceHeader {
+    /// Parses the payload of a sequence header OBU, after its OBU header and size
+    pub fn parse(payload: &[u8]) -> Result<Av1SequenceHeader, CodecConfigError> {
+        let mut reader = BitReader::new(payload);
+        let seq_profile = reader.read_bits(3)? as u8;
+        reader.skip_bits(1)?; // still_picture
+        let reduced_still_picture_header = reader.read_bit()?;
+
+        let mut seq_level_idx = 0;
+        let mut seq_tier = false;
+        let mut frame_rate = None;
+        if reduced_still_picture_header {
+            seq_level_idx = reader.read_bits(5)? as u8;
+        } else {
+            let mut buffer_delay_length = 0;
+            let mut decoder_model_info_present = false;
+            if reader.read_bit()? {
+                frame_rate = read_timing_info(&mut reader)?;
+
+                decoder_model_info_present = reader.read_bit()?;
+                if decoder_model_info_present {
+                    buffer_delay_length = reader.read_bits(5)? as usize + 1;
+                    reader.skip_bits(32 + 5 + 5)?;
+                }
+            }
+
+            let initial_display_delay_present = reader.read_bit()?;
+            let operating_point_count = reader.read_bits(5)? + 1;
+            for index in 0..operating_point_count {
+                reader.skip_bits(12)?; // operating_point_idc
+                let level = reader.read_bits(5)? as u8;
+                let tier = level > 7 && reader.read_bit()?;
+
+                // The first operating point describes the whole stream
+                if index == 0 {
+                    seq_level_idx = level;
+                    seq_tier = tier;
+                }
+
+                if decoder_model_info_present && reader.read_bit()? {
+                    reader.skip_bits(2 * buffer_delay_length + 1)?;
+                }
+
+                if initial_display_delay_present && reader.read_bit()? {
+                    reader.skip_bits(4)?;
+                }
+            }
+        }
+
+        let width_bits = reader.read_bits(4)? as u8 + 1;
+        let height_bits = reader.read_bits(4)? as u8 + 1;
+        let max_frame_width = reader.read_bits(width_bits)? as u64 + 1;
+        let max_frame_height = reader.read_bits(height_bits)? as u64 + 1;
+
+        Ok(Av1SequenceHeader {
+            seq_profile,
+            seq_level_idx,
+            seq_tier,
+            max_frame_width: max_frame_width as u32,
+            max_frame_height: max_frame_height as u32,
+            frame_rate,
+        })
+    }
+
+    /// Fills in the video properties of the metadata that the encoder did not provide
+    pub fn fill_metadata(&self, metadata: &mut StreamMetadata) {
+        if metadata.video_width.is_none() {
+            metadata.video_width = Some(self.max_frame_width);
+        }
+
+        if metadata.video_height.is_none() {
+            metadata.video_height = Some(self.max_frame_height);
+        }
+
+        if metadata.video_frame_rate.is_none() {
+            metadata.video_frame_rate = self.frame_rate;
+        }
+
+        if metadata.video_codec.is_none() {
+            metadata.video_codec = Some("av01".to_string());
+        }
+    }
+}
+
+/// Reads the timing_info() fields, returning the frame rate if every frame has the same duration
+fn read_timing_info(reader: &mut BitReader) -> Result<Option<f32>, CodecConfigError> {
+    let num_units_in_display_tick = reader.read_bits(32)?;
+    let time_scale = reader.read_bits(32)?;
+    if !reader.read_bit()? {
+        return Ok(None);
+    }
+
+    let ticks_per_picture = read_uvlc(reader)? as u64 + 1;
+    if num_units_in_display_tick == 0 {
+        return Ok(None);
+    }
+
+    let frame_duration = num_units_in_display_tick as u64 * ticks_per_picture;
+    Ok(Some(time_scale as f32 / frame_duration as f32))
+}
+
+fn read_uvlc(reader: &mut BitReader) -> Result<u32, CodecConfigError> {
+    let mut leading_zeros = 0;
+    while !reader.read_bit()? {
+        leading_zeros += 1;
+        if leading_zeros >= 32 {
+            return Ok(u32::MAX);
+        }
+    }
+
+    let value = reader.read_bits(leading_zeros)?;
+    Ok(value + ((1u64 << leading_zeros) - 1) as u32)
+}
+
+fn read_leb128(data: &[u8], position: &mut usize) -> Result<usize, CodecConfigError> {
+    let mut value = 0u64;
+    for index in 0..8 {
+        let byte = match data.get(*position) {
+            Some(byte) => *byte,
+            None => return Err(CodecConfigError::Truncated),
+        };
+
+        *position += 1;
+        value |= ((byte & 0x7f) as u64) << (index * 7);
+        if byte & 0x80 == 0 {
+            return Ok(value as usize);
+        }
+    }
+
+    Err(CodecConfigError::InvalidParameterSet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Main profile 1920x1080 at 30000/1001 fps, level 4.0
+    const SEQUENCE_HEADER_OBU: [u8; 19] = [
+        0x0a, 0x11, 0x04, 0x00, 0x00, 0x0f, 0xa4, 0x00, 0x03, 0xa9, 0x82, 0x80, 0x00, 0x02, 0x15,
+        0x5d, 0xfe, 0x1b, 0xbc,
+    ];
+
+    fn record() -> Vec<u8> {
+        let mut record = vec![0x81, 0x08, 0x0c, 0x00];
+        record.extend_from_slice(&SEQUENCE_HEADER_OBU);
+        record
+    }
+
+    #[test]
+    fn can_parse_decoder_config_record() {
+        let config = Av1DecoderConfig::parse(&record()).unwrap();
+
+        assert_eq!(config.seq_profile, 0);
+        assert_eq!(config.seq_level_idx, 8);
+        assert!(!config.high_bitdepth);
+        assert!(config.chroma_subsampling_x);
+        assert!(config.chroma_subsampling_y);
+        assert_eq!(config.codec_string(), "av01.0.08M.08");
+    }
+
+    #[test]
+    fn can_parse_sequence_header_from_config_obus() {
+        let config = Av1DecoderConfig::parse(&record()).unwrap();
+        let header = config.sequence_header().unwrap();
+
+        assert_eq!(header.seq_level_idx, 8);
+        assert_eq!(header.max_frame_width, 1920);
+        assert_eq!(header.max_frame_height, 1080);
+        assert_eq!(header.frame_rate, Some(60_000.0 / 2002.0));
+    }
+
+    #[test]
+    fn record_without_sequence_header_is_an_error() {
+        let config = Av1DecoderConfig::parse(&[0x81, 0x08, 0x0c, 0x00]).unwrap();
+
+        match config.sequence_header() {
+            Err(CodecConfigError::MissingParameterSet) => (),
+            x => panic!("Expected missing parameter set error, instead got {:?}", x),
+        }
+    }
+}
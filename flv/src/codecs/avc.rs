@@ -0,0 +1,372 @@
+use super::bit_reader::{remove_emulation_prevention, BitReader};
+use bytes::Bytes;
+use errors::CodecConfigError;
+use rml_rtmp::sessions::StreamMetadata;
+
+// Profiles that carry chroma format, bit depth, and scaling matrix fields in their SPS
+const HIGH_PROFILES: [u8; 13] = [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135];
+const EXTENDED_SAR: u32 = 255;
+
+/// The AVCDecoderConfigurationRecord carried in H.264 sequence headers, which is everything
+/// after the first 5 bytes of the video data
+#[derive(PartialEq, Debug, Clone)]
+pub struct AvcDecoderConfig {
+    pub profile_indication: u8,
+    pub profile_compatibility: u8,
+    pub level_indication: u8,
+
+    /// The number of bytes used for the length prefix of each NAL unit in video frames
+    pub nal_unit_length_size: u8,
+
+    pub sequence_parameter_sets: Vec<Bytes>,
+    pub picture_parameter_sets: Vec<Bytes>,
+}
+
+/// The stream properties read from an H.264 sequence parameter set (SPS)
+#[derive(PartialEq, Debug, Clone)]
+pub struct AvcSpsInfo {
+    pub profile_idc: u8,
+    pub constraint_flags: u8,
+    pub level_idc: u8,
+
+    /// The width of the displayed picture in pixels, after cropping
+    pub width: u32,
+
+    /// The height of the displayed picture in pixels, after cropping
+    pub height: u32,
+
+    /// The frame rate, if the encoder included timing information
+    pub frame_rate: Option<f32>,
+}
+
+impl AvcDecoderConfig {
+    /// Parses an AVCDecoderConfigurationRecord
+    pub fn parse(record: &[u8]) -> Result<AvcDecoderConfig, CodecConfigError> {
+        if record.len() < 6 {
+            return Err(CodecConfigError::Truncated);
+        }
+
+        if record[0] != 1 {
+            return Err(CodecConfigError::UnsupportedVersion { version: record[0] });
+        }
+
+        let mut position = 5;
+        let sps_count = record[position] & 0x1f;
+        position += 1;
+        let sequence_parameter_sets = read_parameter_sets(record, &mut position, sps_count)?;
+
+        let pps_count = match record.get(position) {
+            Some(count) => *count,
+            None => return Err(CodecConfigError::Truncated),
+        };
+
+        position += 1;
+        let picture_parameter_sets = read_parameter_sets(record, &mut position, pps_count)?;
+
+        Ok(AvcDecoderConfig {
+            profile_indication: record[1],
+            profile_compatibility: record[2],
+            level_indication: record[3],
+            nal_unit_length_size: (record[4] & 0x03) + 1,
+            sequence_parameter_sets,
+            picture_parameter_sets,
+        })
+    }
+
+    /// Parses the first sequence parameter set in the record
+    pub fn sps_info(&self) -> Result<AvcSpsInfo, CodecConfigError> {
+        match self.sequence_parameter_sets.first() {
+            Some(sps) => AvcSpsInfo::parse(sps),
+            None => Err(CodecConfigError::MissingParameterSet),
+        }
+    }
+
+    /// The RFC 6381 codec string (e.g. `avc1.64001f`) used by HLS and DASH manifests
+    pub fn codec_string(&self) -> String {
+        format!(
+            "avc1.{:02x}{:02x}{:02x}",
+            self.profile_indication, self.profile_compatibility, self.level_indication
+        )
+    }
+}
+
+impl AvcSpsInfo {
+    /// Parses an SPS NAL unit, starting with its NAL unit header byte
+    pub fn parse(nal_unit: &[u8]) -> Result<AvcSpsInfo, CodecConfigError> {
+        let payload = remove_emulation_prevention(nal_unit);
+        let mut reader = BitReader::new(&payload);
+        reader.skip_bits(8)?;
+
+        let profile_idc = reader.read_bits(8)? as u8;
+        let constraint_flags = reader.read_bits(8)? as u8;
+        let level_idc = reader.read_bits(8)? as u8;
+        reader.read_unsigned_exp_golomb()?; // seq_parameter_set_id
+
+        let mut chroma_format_idc = 1;
+        let mut separate_colour_plane = false;
+        if HIGH_PROFILES.contains(&profile_idc) {
+            chroma_format_idc = reader.read_unsigned_exp_golomb()?;
+            if chroma_format_idc == 3 {
+                separate_colour_plane = reader.read_bit()?;
+            }
+
+            reader.read_unsigned_exp_golomb()?; // bit_depth_luma_minus8
+            reader.read_unsigned_exp_golomb()?; // bit_depth_chroma_minus8
+            reader.skip_bits(1)?; // qpprime_y_zero_transform_bypass_flag
+            if reader.read_bit()? {
+                let list_count = if chroma_format_idc == 3 { 12 } else { 8 };
+                for index in 0..list_count {
+                    if reader.read_bit()? {
+                        skip_scaling_list(&mut reader, if index < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+
+        reader.read_unsigned_exp_golomb()?; // log2_max_frame_num_minus4
+        match reader.read_unsigned_exp_golomb()? {
+            0 => {
+                reader.read_unsigned_exp_golomb()?; // log2_max_pic_order_cnt_lsb_minus4
+            }
+
+            1 => {
+                reader.skip_bits(1)?; // delta_pic_order_always_zero_flag
+                reader.read_signed_exp_golomb()?; // offset_for_non_ref_pic
+                reader.read_signed_exp_golomb()?; // offset_for_top_to_bottom_field
+                let cycle_length = reader.read_unsigned_exp_golomb()?;
+                for _ in 0..cycle_length {
+                    reader.read_signed_exp_golomb()?;
+                }
+            }
+
+            _ => (),
+        }
+
+        reader.read_unsigned_exp_golomb()?; // max_num_ref_frames
+        reader.skip_bits(1)?; // gaps_in_frame_num_value_allowed_flag
+        let width_in_macroblocks = reader.read_unsigned_exp_golomb()? as u64 + 1;
+        let height_in_map_units = reader.read_unsigned_exp_golomb()? as u64 + 1;
+        let frame_mbs_only = reader.read_bit()?;
+        if !frame_mbs_only {
+            reader.skip_bits(1)?; // mb_adaptive_frame_field_flag
+        }
+
+        reader.skip_bits(1)?; // direct_8x8_inference_flag
+        let mut crop = [0u64; 4];
+        if reader.read_bit()? {
+            for value in crop.iter_mut() {
+                *value = reader.read_unsigned_exp_golomb()? as u64;
+            }
+        }
+
+        // Cropping is in units of chroma samples, which depend on the chroma subsampling, and
+        // vertically in field pairs for interlaced video
+        let field_multiplier = if frame_mbs_only { 1 } else { 2 };
+        let chroma_array_type = if separate_colour_plane {
+            0
+        } else {
+            chroma_format_idc
+        };
+
+        let (crop_unit_x, crop_unit_y) = match chroma_array_type {
+            1 => (2, 2 * field_multiplier),
+            2 => (2, field_multiplier),
+            _ => (1, field_multiplier),
+        };
+
+        let width = (width_in_macroblocks * 16).saturating_sub(crop_unit_x * (crop[0] + crop[1]));
+        let height = (field_multiplier * height_in_map_units * 16)
+            .saturating_sub(crop_unit_y * (crop[2] + crop[3]));
+
+        let frame_rate = if reader.read_bit()? {
+            read_vui_frame_rate(&mut reader)?
+        } else {
+            None
+        };
+
+        Ok(AvcSpsInfo {
+            profile_idc,
+            constraint_flags,
+            level_idc,
+            width: width as u32,
+            height: height as u32,
+            frame_rate,
+        })
+    }
+
+    /// Fills in the video properties of the metadata that the encoder did not provide
+    pub fn fill_metadata(&self, metadata: &mut StreamMetadata) {
+        if metadata.video_width.is_none() {
+            metadata.video_width = Some(self.width);
+        }
+
+        if metadata.video_height.is_none() {
+            metadata.video_height = Some(self.height);
+        }
+
+        if metadata.video_frame_rate.is_none() {
+            metadata.video_frame_rate = self.frame_rate;
+        }
+
+        if metadata.video_codec.is_none() {
+            metadata.video_codec = Some("avc1".to_string());
+        }
+    }
+}
+
+fn read_parameter_sets(
+    record: &[u8],
+    position: &mut usize,
+    count: u8,
+) -> Result<Vec<Bytes>, CodecConfigError> {
+    let mut sets = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if *position + 2 > record.len() {
+            return Err(CodecConfigError::Truncated);
+        }
+
+        let length = ((record[*position] as usize) << 8) | record[*position + 1] as usize;
+        let start = *position + 2;
+        if start + length > record.len() {
+            return Err(CodecConfigError::Truncated);
+        }
+
+        sets.push(Bytes::copy_from_slice(&record[start..start + length]));
+        *position = start + length;
+    }
+
+    Ok(sets)
+}
+
+fn skip_scaling_list(reader: &mut BitReader, size: usize) -> Result<(), CodecConfigError> {
+    let mut last_scale = 8i32;
+    let mut next_scale = 8i32;
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta = reader.read_signed_exp_golomb()?;
+            next_scale = (last_scale + delta + 256) % 256;
+        }
+
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the video usability information up to its timing information
+fn read_vui_frame_rate(reader: &mut BitReader) -> Result<Option<f32>, CodecConfigError> {
+    if reader.read_bit()? && reader.read_bits(8)? == EXTENDED_SAR {
+        reader.skip_bits(32)?; // sar_width and sar_height
+    }
+
+    if reader.read_bit()? {
+        reader.skip_bits(1)?; // overscan_appropriate_flag
+    }
+
+    if reader.read_bit()? {
+        reader.skip_bits(4)?; // video_format and video_full_range_flag
+        if reader.read_bit()? {
+            reader.skip_bits(24)?; // colour primaries, transfer, and matrix
+        }
+    }
+
+    if reader.read_bit()? {
+        reader.read_unsigned_exp_golomb()?; // chroma_sample_loc_type_top_field
+        reader.read_unsigned_exp_golomb()?; // chroma_sample_loc_type_bottom_field
+    }
+
+    if !reader.read_bit()? {
+        return Ok(None);
+    }
+
+    let num_units_in_tick = reader.read_bits(32)?;
+    let time_scale = reader.read_bits(32)?;
+    if num_units_in_tick == 0 {
+        return Ok(None);
+    }
+
+    // Each frame is two ticks, one per field
+    Ok(Some(
+        time_scale as f32 / (2 * num_units_in_tick as u64) as f32,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // High profile 1920x1080 (1088 cropped by 8 rows) at 30fps, with a 1:1 aspect ratio
+    const HIGH_1080P_SPS: [u8; 24] = [
+        0x67, 0x64, 0x00, 0x28, 0xac, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5, 0xc0, 0x44, 0x00, 0x00,
+        0x03, 0x00, 0x04, 0x00, 0x00, 0x03, 0x00, 0xf2, 0x10,
+    ];
+
+    // Constrained baseline 704x576 interlaced, with pic_order_cnt_type 1 and no VUI
+    const BASELINE_INTERLACED_SPS: [u8; 9] = [0x67, 0x42, 0xc0, 0x1e, 0xd3, 0xa0, 0x2c, 0x09, 0x12];
+
+    fn record(sps: &[u8]) -> Vec<u8> {
+        let mut record = vec![1, sps[1], sps[2], sps[3], 0xff, 0xe1];
+        record.extend_from_slice(&[0, sps.len() as u8]);
+        record.extend_from_slice(sps);
+        record.extend_from_slice(&[1, 0, 4, 0x68, 0xeb, 0xe3, 0xcb]);
+        record
+    }
+
+    #[test]
+    fn can_parse_high_profile_sps_with_cropping_and_timing() {
+        let info = AvcSpsInfo::parse(&HIGH_1080P_SPS).unwrap();
+
+        assert_eq!(info.profile_idc, 100);
+        assert_eq!(info.level_idc, 40);
+        assert_eq!(info.width, 1920);
+        assert_eq!(info.height, 1080);
+        assert_eq!(info.frame_rate, Some(30.0));
+    }
+
+    #[test]
+    fn can_parse_interlaced_baseline_sps() {
+        let info = AvcSpsInfo::parse(&BASELINE_INTERLACED_SPS).unwrap();
+
+        assert_eq!(info.profile_idc, 66);
+        assert_eq!(info.constraint_flags, 0xc0);
+        assert_eq!(info.width, 704);
+        assert_eq!(info.height, 576);
+        assert_eq!(info.frame_rate, None);
+    }
+
+    #[test]
+    fn can_parse_decoder_config_record() {
+        let config = AvcDecoderConfig::parse(&record(&HIGH_1080P_SPS)).unwrap();
+
+        assert_eq!(config.nal_unit_length_size, 4);
+        assert_eq!(config.sequence_parameter_sets.len(), 1);
+        assert_eq!(config.picture_parameter_sets.len(), 1);
+        assert_eq!(config.codec_string(), "avc1.640028");
+        assert_eq!(config.sps_info().unwrap().width, 1920);
+    }
+
+    #[test]
+    fn truncated_record_is_an_error() {
+        let record = record(&HIGH_1080P_SPS);
+
+        match AvcDecoderConfig::parse(&record[..20]) {
+            Err(CodecConfigError::Truncated) => (),
+            x => panic!("Expected truncated error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn only_missing_metadata_is_filled_in() {
+        let info = AvcSpsInfo::parse(&HIGH_1080P_SPS).unwrap();
+        let mut metadata = StreamMetadata::new();
+        metadata.video_width = Some(1280);
+
+        info.fill_metadata(&mut metadata);
+
+        assert_eq!(metadata.video_width, Some(1280));
+        assert_eq!(metadata.video_height, Some(1080));
+        assert_eq!(metadata.video_frame_rate, Some(30.0));
+    }
+}
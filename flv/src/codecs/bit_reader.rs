@@ -0,0 +1,114 @@
+use errors::CodecConfigError;
+
+/// Reads big endian bit fields, including the exp-Golomb codes used by H.264 and HEVC
+/// parameter sets
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> BitReader<'a> {
+        BitReader { bytes, position: 0 }
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool, CodecConfigError> {
+        let byte = match self.bytes.get(self.position / 8) {
+            Some(byte) => *byte,
+            None => return Err(CodecConfigError::Truncated),
+        };
+
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        Ok(bit == 1)
+    }
+
+    /// Reads up to 32 bits as an unsigned integer
+    pub fn read_bits(&mut self, count: u8) -> Result<u32, CodecConfigError> {
+        let mut value = 0u32;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit()? as u32;
+        }
+
+        Ok(value)
+    }
+
+    pub fn skip_bits(&mut self, count: usize) -> Result<(), CodecConfigError> {
+        if self.position + count > self.bytes.len() * 8 {
+            return Err(CodecConfigError::Truncated);
+        }
+
+        self.position += count;
+        Ok(())
+    }
+
+    /// Reads an unsigned exp-Golomb code, `ue(v)`
+    pub fn read_unsigned_exp_golomb(&mut self) -> Result<u32, CodecConfigError> {
+        let mut leading_zeros = 0;
+        while !self.read_bit()? {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return Err(CodecConfigError::InvalidParameterSet);
+            }
+        }
+
+        let suffix = self.read_bits(leading_zeros)?;
+        Ok(((1u64 << leading_zeros) - 1 + suffix as u64) as u32)
+    }
+
+    /// Reads a signed exp-Golomb code, `se(v)`
+    pub fn read_signed_exp_golomb(&mut self) -> Result<i32, CodecConfigError> {
+        let code = self.read_unsigned_exp_golomb()? as i64;
+        let value = if code % 2 == 1 {
+            (code + 1) / 2
+        } else {
+            -(code / 2)
+        };
+
+        Ok(value as i32)
+    }
+}
+
+/// Removes the emulation prevention bytes (the `0x03` in `0x000003`) that NAL units contain so
+/// their payload never looks like a start code
+pub fn remove_emulation_prevention(nal_unit: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(nal_unit.len());
+    let mut zero_count = 0;
+    for &byte in nal_unit {
+        if zero_count >= 2 && byte == 3 {
+            zero_count = 0;
+            continue;
+        }
+
+        zero_count = if byte == 0 { zero_count + 1 } else { 0 };
+        payload.push(byte);
+    }
+
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_exp_golomb_codes() {
+        // 1 (0), 010 (1), 011 (2), 00100 (3), then se: 010 (+1), 011 (-1)
+        let bytes = [0b1010_0110, 0b0100_0100, 0b1100_0000];
+        let mut reader = BitReader::new(&bytes);
+
+        assert_eq!(reader.read_unsigned_exp_golomb().unwrap(), 0);
+        assert_eq!(reader.read_unsigned_exp_golomb().unwrap(), 1);
+        assert_eq!(reader.read_unsigned_exp_golomb().unwrap(), 2);
+        assert_eq!(reader.read_unsigned_exp_golomb().unwrap(), 3);
+        assert_eq!(reader.read_signed_exp_golomb().unwrap(), 1);
+        assert_eq!(reader.read_signed_exp_golomb().unwrap(), -1);
+    }
+
+    #[test]
+    fn emulation_prevention_bytes_are_removed() {
+        let payload = remove_emulation_prevention(&[0, 0, 3, 1, 0, 0, 3, 0, 3]);
+
+        assert_eq!(payload, vec![0, 0, 1, 0, 0, 0, 3]);
+    }
+}
@@ -0,0 +1,338 @@
+use super::bit_reader::{remove_emulation_prevention, BitReader};
+use bytes::Bytes;
+use errors::CodecConfigError;
+use rml_rtmp::sessions::StreamMetadata;
+
+const RECORD_HEADER_LENGTH: usize = 23;
+const VPS_NAL_UNIT_TYPE: u8 = 32;
+const SPS_NAL_UNIT_TYPE: u8 = 33;
+const PPS_NAL_UNIT_TYPE: u8 = 34;
+
+/// The HEVCDecoderConfigurationRecord (`hvcC`) carried in the sequence start packets of
+/// enhanced RTMP HEVC streams, which is everything after the first 5 bytes of the video data
+#[derive(PartialEq, Debug, Clone)]
+pub struct HevcDecoderConfig {
+    pub general_profile_space: u8,
+    pub general_tier_flag: bool,
+    pub general_profile_idc: u8,
+    pub general_profile_compatibility_flags: u32,
+
+    /// The 48 bits of general constraint indicator flags
+    pub general_constraint_indicator_flags: u64,
+
+    pub general_level_idc: u8,
+    pub chroma_format_idc: u8,
+    pub bit_depth_luma: u8,
+    pub bit_depth_chroma: u8,
+
+    /// The average frame rate, if the encoder specified one
+    pub average_frame_rate: Option<f32>,
+
+    /// The number of bytes used for the length prefix of each NAL unit in video frames
+    pub nal_unit_length_size: u8,
+
+    pub video_parameter_sets: Vec<Bytes>,
+    pub sequence_parameter_sets: Vec<Bytes>,
+    pub picture_parameter_sets: Vec<Bytes>,
+}
+
+/// The stream properties read from an HEVC sequence parameter set (SPS)
+#[derive(PartialEq, Debug, Clone)]
+pub struct HevcSpsInfo {
+    pub general_profile_idc: u8,
+    pub general_tier_flag: bool,
+    pub general_level_idc: u8,
+    pub chroma_format_idc: u32,
+
+    /// The width of the displayed picture in pixels, after the conformance window is applied
+    pub width: u32,
+
+    /// The height of the displayed picture in pixels, after the conformance window is applied
+    pub height: u32,
+}
+
+impl HevcDecoderConfig {
+    /// Parses an HEVCDecoderConfigurationRecord
+    pub fn parse(record: &[u8]) -> Result<HevcDecoderConfig, CodecConfigError> {
+        if record.len() < RECORD_HEADER_LENGTH {
+            return Err(CodecConfigError::Truncated);
+        }
+
+        if record[0] != 1 {
+            return Err(CodecConfigError::UnsupportedVersion { version: record[0] });
+        }
+
+        let mut config = HevcDecoderConfig {
+            general_profile_space: record[1] >> 6,
+            general_tier_flag: record[1] & 0x20 != 0,
+            general_profile_idc: record[1] & 0x1f,
+            general_profile_compatibility_flags: read_u32(&record[2..6]),
+            general_constraint_indicator_flags: record[6..12]
+                .iter()
+                .fold(0, |flags, byte| (flags << 8) | *byte as u64),
+            general_level_idc: record[12],
+            chroma_format_idc: record[16] & 0x03,
+            bit_depth_luma: (record[17] & 0x07) + 8,
+            bit_depth_chroma: (record[18] & 0x07) + 8,
+            average_frame_rate: None,
+            nal_unit_length_size: (record[21] & 0x03) + 1,
+            video_parameter_sets: Vec::new(),
+            sequence_parameter_sets: Vec::new(),
+            picture_parameter_sets: Vec::new(),
+        };
+
+        // Stored in frames per 256 seconds, with zero meaning unspecified
+        let average_frame_rate = ((record[19] as u16) << 8) | record[20] as u16;
+        if average_frame_rate > 0 {
+            config.average_frame_rate = Some(average_frame_rate as f32 / 256.0);
+        }
+
+        let array_count = record[22];
+        let mut position = RECORD_HEADER_LENGTH;
+        for _ in 0..array_count {
+            if position + 3 > record.len() {
+                return Err(CodecConfigError::Truncated);
+            }
+
+            let nal_unit_type = record[position] & 0x3f;
+            let nal_unit_count = ((record[position + 1] as u16) << 8) | record[position + 2] as u16;
+            position += 3;
+
+            for _ in 0..nal_unit_count {
+                let nal_unit = read_nal_unit(record, &mut position)?;
+                match nal_unit_type {
+                    VPS_NAL_UNIT_TYPE => config.video_parameter_sets.push(nal_unit),
+                    SPS_NAL_UNIT_TYPE => config.sequence_parameter_sets.push(nal_unit),
+                    PPS_NAL_UNIT_TYPE => config.picture_parameter_sets.push(nal_unit),
+                    _ => (), // SEI messages aren't needed to describe the stream
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Parses the first sequence parameter set in the record
+    pub fn sps_info(&self) -> Result<HevcSpsInfo, CodecConfigError> {
+        match self.sequence_parameter_sets.first() {
+            Some(sps) => HevcSpsInfo::parse(sps),
+            None => Err(CodecConfigError::MissingParameterSet),
+        }
+    }
+
+    /// The RFC 6381 codec string (e.g. `hvc1.1.6.L93.B0`) used by HLS and DASH manifests
+    pub fn codec_string(&self) -> String {
+        let profile_space = match self.general_profile_space {
+            1 => "A",
+            2 => "B",
+            3 => "C",
+            _ => "",
+        };
+
+        let tier = if self.general_tier_flag { "H" } else { "L" };
+        let mut codec = format!(
+            "hvc1.{}{}.{:x}.{}{}",
+            profile_space,
+            self.general_profile_idc,
+            self.general_profile_compatibility_flags.reverse_bits(),
+            tier,
+            self.general_level_idc
+        );
+
+        // Each byte of constraint flags is appended, leaving off trailing zero bytes
+        let constraint_bytes = (0..6)
+            .rev()
+            .map(|index| (self.general_constraint_indicator_flags >> (index * 8)) as u8)
+            .collect::<Vec<_>>();
+
+        let length = constraint_bytes
+            .iter()
+            .rposition(|byte| *byte != 0)
+            .map_or(0, |index| index + 1);
+
+        for byte in &constraint_bytes[..length] {
+            codec.push_str(&format!(".{:X}", byte));
+        }
+
+        codec
+    }
+}
+
+impl HevcSpsInfo {
+    /// Parses an SPS NAL unit, starting with its two byte NAL unit header
+    pub fn parse(nal_unit: &[u8]) -> Result<HevcSpsInfo, CodecConfigError> {
+        let payload = remove_emulation_prevention(nal_unit);
+        let mut reader = BitReader::new(&payload);
+        reader.skip_bits(16)?;
+
+        reader.skip_bits(4)?; // sps_video_parameter_set_id
+        let max_sub_layers_minus1 = reader.read_bits(3)? as usize;
+        reader.skip_bits(1)?; // sps_temporal_id_nesting_flag
+
+        // profile_tier_level()
+        reader.skip_bits(2)?; // general_profile_space
+        let general_tier_flag = reader.read_bit()?;
+        let general_profile_idc = reader.read_bits(5)? as u8;
+        reader.skip_bits(32 + 48)?; // compatibility and constraint flags
+        let general_level_idc = reader.read_bits(8)? as u8;
+
+        let mut sub_layer_flags = Vec::with_capacity(max_sub_layers_minus1);
+        for _ in 0..max_sub_layers_minus1 {
+            let profile_present = reader.read_bit()?;
+            let level_present = reader.read_bit()?;
+            sub_layer_flags.push((profile_present, level_present));
+        }
+
+        if max_sub_layers_minus1 > 0 {
+            reader.skip_bits(2 * (8 - max_sub_layers_minus1))?; // reserved_zero_2bits
+        }
+
+        for (profile_present, level_present) in sub_layer_flags {
+            if profile_present {
+                reader.skip_bits(88)?;
+            }
+
+            if level_present {
+                reader.skip_bits(8)?;
+            }
+        }
+
+        reader.read_unsigned_exp_golomb()?; // sps_seq_parameter_set_id
+        let chroma_format_idc = reader.read_unsigned_exp_golomb()?;
+        let mut separate_colour_plane = false;
+        if chroma_format_idc == 3 {
+            separate_colour_plane = reader.read_bit()?;
+        }
+
+        let width = reader.read_unsigned_exp_golomb()? as u64;
+        let height = reader.read_unsigned_exp_golomb()? as u64;
+        let mut window = [0u64; 4];
+        if reader.read_bit()? {
+            for value in window.iter_mut() {
+                *value = reader.read_unsigned_exp_golomb()? as u64;
+            }
+        }
+
+        // The conformance window is in units of chroma samples
+        let chroma_array_type = if separate_colour_plane {
+            0
+        } else {
+            chroma_format_idc
+        };
+
+        let (unit_x, unit_y) = match chroma_array_type {
+            1 => (2, 2),
+            2 => (2, 1),
+            _ => (1, 1),
+        };
+
+        let width = width.saturating_sub(unit_x * (window[0] + window[1]));
+        let height = height.saturating_sub(unit_y * (window[2] + window[3]));
+
+        Ok(HevcSpsInfo {
+            general_profile_idc,
+            general_tier_flag,
+            general_level_idc,
+            chroma_format_idc,
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+
+    /// Fills in the video properties of the metadata that the encoder did not provide
+    pub fn fill_metadata(&self, metadata: &mut StreamMetadata) {
+        if metadata.video_width.is_none() {
+            metadata.video_width = Some(self.width);
+        }
+
+        if metadata.video_height.is_none() {
+            metadata.video_height = Some(self.height);
+        }
+
+        if metadata.video_codec.is_none() {
+            metadata.video_codec = Some("hvc1".to_string());
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 8) | *byte as u32)
+}
+
+fn read_nal_unit(record: &[u8], position: &mut usize) -> Result<Bytes, CodecConfigError> {
+    if *position + 2 > record.len() {
+        return Err(CodecConfigError::Truncated);
+    }
+
+    let length = ((record[*position] as usize) << 8) | record[*position + 1] as usize;
+    let start = *position + 2;
+    if start + length > record.len() {
+        return Err(CodecConfigError::Truncated);
+    }
+
+    *position = start + length;
+    Ok(Bytes::copy_from_slice(&record[start..start + length]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Main profile 1920x1080 (1088 with a conformance window of 8 rows), level 4
+    const MAIN_1080P_SPS: [u8; 26] = [
+        0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x78, 0xa0, 0x03, 0xc0, 0x80, 0x11, 0x07, 0xcb, 0x96,
+    ];
+
+    fn record() -> Vec<u8> {
+        let mut record = vec![
+            0x01, 0x01, 0x60, 0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x78, 0xf0,
+            0x00, 0xfc, 0xfd, 0xf8, 0xf8, 0x1e, 0x00, 0x0f, 0x03,
+        ];
+
+        record.extend_from_slice(&[0xa0, 0x00, 0x01, 0x00, 0x02, 0x40, 0x01]);
+        record.extend_from_slice(&[0xa1, 0x00, 0x01, 0x00, MAIN_1080P_SPS.len() as u8]);
+        record.extend_from_slice(&MAIN_1080P_SPS);
+        record.extend_from_slice(&[0xa2, 0x00, 0x01, 0x00, 0x03, 0x44, 0x01, 0xc1]);
+        record
+    }
+
+    #[test]
+    fn can_parse_decoder_config_record() {
+        let config = HevcDecoderConfig::parse(&record()).unwrap();
+
+        assert_eq!(config.general_profile_idc, 1);
+        assert_eq!(config.general_level_idc, 120);
+        assert_eq!(config.chroma_format_idc, 1);
+        assert_eq!(config.bit_depth_luma, 8);
+        assert_eq!(config.average_frame_rate, Some(30.0));
+        assert_eq!(config.nal_unit_length_size, 4);
+        assert_eq!(config.video_parameter_sets.len(), 1);
+        assert_eq!(config.sequence_parameter_sets.len(), 1);
+        assert_eq!(config.picture_parameter_sets.len(), 1);
+        assert_eq!(config.codec_string(), "hvc1.1.6.L120.90");
+    }
+
+    #[test]
+    fn can_parse_sps_with_conformance_window() {
+        let info = HevcSpsInfo::parse(&MAIN_1080P_SPS).unwrap();
+
+        assert_eq!(info.general_profile_idc, 1);
+        assert!(!info.general_tier_flag);
+        assert_eq!(info.general_level_idc, 120);
+        assert_eq!(info.width, 1920);
+        assert_eq!(info.height, 1080);
+    }
+
+    #[test]
+    fn truncated_record_is_an_error() {
+        let record = record();
+
+        match HevcDecoderConfig::parse(&record[..40]) {
+            Err(CodecConfigError::Truncated) => (),
+            x => panic!("Expected truncated error, instead got {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Parsers for the decoder configuration that publishers send in video sequence headers.  AVC
+//! sequence headers use the legacy FLV video header, while HEVC and AV1 are sent in enhanced
+//! RTMP sequence start packets.
+//!
+//! Encoders don't always send `onMetaData`, and when they do it isn't guaranteed to be accurate.
+//! The sequence header has to be correct for the video to be decoded at all, so the properties
+//! read from it can be used to validate the metadata or fill in what's missing.
+//!
+//! # Examples
+//! ```
+//! extern crate rml_flv;
+//! extern crate rml_rtmp;
+//!
+//! use rml_flv::codecs::AvcDecoderConfig;
+//! use rml_rtmp::sessions::StreamMetadata;
+//!
+//! // An AVC sequence header: the 5 byte video tag header followed by the configuration record
+//! let video_data = [
+//!     0x17, 0, 0, 0, 0, 0x01, 0x42, 0xc0, 0x1e, 0xff, 0xe1, 0x00, 0x09, 0x67, 0x42, 0xc0,
+//!     0x1e, 0xd3, 0xa0, 0x2c, 0x09, 0x12, 0x01, 0x00, 0x04, 0x68, 0xeb, 0xe3, 0xcb,
+//! ];
+//!
+//! let config = AvcDecoderConfig::parse(&video_data[5..]).unwrap();
+//! let sps = config.sps_info().unwrap();
+//!
+//! let mut metadata = StreamMetadata::new();
+//! sps.fill_metadata(&mut metadata);
+//! assert_eq!(metadata.video_width, Some(704));
+//! assert_eq!(metadata.video_height, Some(576));
+//! ```
+
+mod av1;
+mod avc;
+mod bit_reader;
+mod hevc;
+
+pub use self::av1::{Av1DecoderConfig, Av1SequenceHeader};
+pub use self::avc::{AvcDecoderConfig, AvcSpsInfo};
+pub use self::hevc::{HevcDecoderConfig, HevcSpsInfo};
+
+use errors::CodecConfigError;
+use rml_rtmp::sessions::StreamMetadata;
+
+const AVC_CODEC_ID: u8 = 7;
+const ENHANCED_VIDEO_FLAG: u8 = 0x80;
+const SEQUENCE_START_PACKET_TYPE: u8 = 0;
+
+/// The decoder configuration of a video stream, for any of the codecs that can be parsed
+#[derive(PartialEq, Debug, Clone)]
+pub enum VideoDecoderConfig {
+    Avc(AvcDecoderConfig),
+    Hevc(HevcDecoderConfig),
+    Av1(Av1DecoderConfig),
+}
+
+impl VideoDecoderConfig {
+    /// Parses the decoder configuration from the data of a video tag or RTMP video message.
+    /// `None` is returned if the data isn't a sequence header, or is for a codec that can't be
+    /// parsed.
+    pub fn from_video_data(data: &[u8]) -> Result<Option<VideoDecoderConfig>, CodecConfigError> {
+        if data.len() < 5 {
+            return Ok(None);
+        }
+
+        if data[0] & ENHANCED_VIDEO_FLAG == 0 {
+            if data[0] & 0x0f != AVC_CODEC_ID || data[1] != 0 {
+                return Ok(None);
+            }
+
+            let config = AvcDecoderConfig::parse(&data[5..])?;
+            return Ok(Some(VideoDecoderConfig::Avc(config)));
+        }
+
+        // Enhanced headers have the packet type in the lower 4 bits, followed by a FourCC
+        if data[0] & 0x0f != SEQUENCE_START_PACKET_TYPE {
+            return Ok(None);
+        }
+
+        let config = match &data[1..5] {
+            b"avc1" => VideoDecoderConfig::Avc(AvcDecoderConfig::parse(&data[5..])?),
+            b"hvc1" => VideoDecoderConfig::Hevc(HevcDecoderConfig::parse(&data[5..])?),
+            b"av01" => VideoDecoderConfig::Av1(Av1DecoderConfig::parse(&data[5..])?),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(config))
+    }
+
+    /// The RFC 6381 codec string used by HLS and DASH manifests
+    pub fn codec_string(&self) -> String {
+        match *self {
+            VideoDecoderConfig::Avc(ref config) => config.codec_string(),
+            VideoDecoderConfig::Hevc(ref config) => config.codec_string(),
+            VideoDecoderConfig::Av1(ref config) => config.codec_string(),
+        }
+    }
+
+    /// Fills in the video properties of the metadata that the encoder did not provide, using
+    /// the stream's parameter sets or sequence header
+    pub fn fill_metadata(&self, metadata: &mut StreamMetadata) -> Result<(), CodecConfigError> {
+        match *self {
+            VideoDecoderConfig::Avc(ref config) => config.sps_info()?.fill_metadata(metadata),
+            VideoDecoderConfig::Hevc(ref config) => {
+                config.sps_info()?.fill_metadata(metadata);
+                if metadata.video_frame_rate.is_none() {
+                    metadata.video_frame_rate = config.average_frame_rate;
+                }
+            }
+
+            VideoDecoderConfig::Av1(ref config) => {
+                config.sequence_header()?.fill_metadata(metadata)
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_avc_sequence_header_is_parsed() {
+        let data = [
+            0x17, 0, 0, 0, 0, 0x01, 0x42, 0xc0, 0x1e, 0xff, 0xe1, 0x00, 0x00, 0x00,
+        ];
+
+        match VideoDecoderConfig::from_video_data(&data).unwrap() {
+            Some(VideoDecoderConfig::Avc(config)) => assert_eq!(config.level_indication, 0x1e),
+            x => panic!("Expected AVC config, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn enhanced_av1_sequence_start_is_parsed() {
+        let data = [0x90, b'a', b'v', b'0', b'1', 0x81, 0x08, 0x0c, 0x00];
+
+        match VideoDecoderConfig::from_video_data(&data).unwrap() {
+            Some(VideoDecoderConfig::Av1(config)) => assert_eq!(config.seq_level_idx, 8),
+            x => panic!("Expected AV1 config, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn frames_and_unknown_codecs_have_no_config() {
+        // An AVC frame, an enhanced HEVC coded frame, and an enhanced VP9 sequence start
+        let payloads: [&[u8]; 3] = [
+            &[0x17, 1, 0, 0, 0, 0],
+            &[0x91, b'h', b'v', b'c', b'1', 0, 0, 0],
+            &[0x90, b'v', b'p', b'0', b'9', 1, 0, 0],
+        ];
+
+        for payload in payloads.iter() {
+            assert_eq!(VideoDecoderConfig::from_video_data(payload).unwrap(), None);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use byteorder::{BigEndian, ByteOrder};
+use bytes::{Buf, BytesMut};
+use errors::FlvReadError;
+use reader::{check_previous_tag_size, parse_header, parse_tag_header};
+use {FlvHeader, FlvTag, HEADER_LENGTH, TAG_HEADER_LENGTH};
+
+/// Reads FLV tags from bytes as they are received, such as from an HTTP-FLV stream.
+///
+/// Bytes are added with `push()` and each tag is returned from `next_tag()` once all of its
+/// bytes have arrived.  Tag data is split off of the internal buffer without being copied.
+///
+/// # Examples
+/// ```
+/// extern crate bytes;
+/// extern crate rml_flv;
+/// extern crate rml_rtmp;
+///
+/// use bytes::Bytes;
+/// use rml_flv::{FlvDemuxer, FlvHeader, FlvTag, FlvTagType};
+/// use rml_rtmp::time::RtmpTimestamp;
+///
+/// let tag = FlvTag::new(FlvTagType::Audio, RtmpTimestamp::new(0), Bytes::from(vec![0xaf, 1]));
+/// let mut bytes = FlvHeader::new(true, false).to_bytes().to_vec();
+/// bytes.extend_from_slice(&tag.to_bytes().unwrap());
+///
+/// let mut demuxer = FlvDemuxer::new();
+/// demuxer.push(&bytes[..10]);
+/// assert_eq!(demuxer.next_tag().unwrap(), None);
+///
+/// demuxer.push(&bytes[10..]);
+/// assert_eq!(demuxer.next_tag().unwrap(), Some(tag));
+/// assert_eq!(demuxer.header(), Some(FlvHeader::new(true, false)));
+/// ```
+pub struct FlvDemuxer {
+    buffer: BytesMut,
+    header: Option<FlvHeader>,
+}
+
+impl FlvDemuxer {
+    /// Creates a demuxer that expects the stream to start with an FLV header
+    pub fn new() -> FlvDemuxer {
+        FlvDemuxer {
+            buffer: BytesMut::new(),
+            header: None,
+        }
+    }
+
+    /// Adds bytes that were received to the end of the stream
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// The stream's header, once it has been received
+    pub fn header(&self) -> Option<FlvHeader> {
+        self.header
+    }
+
+    /// Returns the next tag if all of its bytes have been received, or `None` if more bytes are
+    /// needed.  If an error is returned the stream is corrupt and the demuxer should be
+    /// discarded.
+    pub fn next_tag(&mut self) -> Result<Option<FlvTag>, FlvReadError> {
+        if self.header.is_none() {
+            if self.buffer.len() < HEADER_LENGTH {
+                return Ok(None);
+            }
+
+            let (header, length) = parse_header(&self.buffer[..HEADER_LENGTH])?;
+
+            // Wait for any extra header bytes and the first previous tag size
+            if self.buffer.len() < length + 4 {
+                return Ok(None);
+            }
+
+            self.buffer.advance(length + 4);
+            self.header = Some(header);
+        }
+
+        if self.buffer.len() < TAG_HEADER_LENGTH {
+            return Ok(None);
+        }
+
+        let (tag_type, size, timestamp) = parse_tag_header(&self.buffer[..TAG_HEADER_LENGTH])?;
+        if self.buffer.len() < TAG_HEADER_LENGTH + size + 4 {
+            return Ok(None);
+        }
+
+        self.buffer.advance(TAG_HEADER_LENGTH);
+        let data = self.buffer.split_to(size).freeze();
+        let previous_tag_size = BigEndian::read_u32(&self.buffer[..4]);
+        self.buffer.advance(4);
+        check_previous_tag_size(size, previous_tag_size)?;
+
+        Ok(Some(FlvTag::new(tag_type, timestamp, data)))
+    }
+}
+
+impl Default for FlvDemuxer {
+    fn default() -> Self {
+        FlvDemuxer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use rml_rtmp::time::RtmpTimestamp;
+    use FlvTagType;
+
+    #[test]
+    fn tags_split_at_every_byte_are_returned_once_complete() {
+        let tags = vec![
+            FlvTag::new(
+                FlvTagType::Video,
+                RtmpTimestamp::new(0),
+                Bytes::from(vec![0x17, 0, 0, 0, 0]),
+            ),
+            FlvTag::new(
+                FlvTagType::Audio,
+                RtmpTimestamp::new(23),
+                Bytes::from(vec![0xaf, 1, 2]),
+            ),
+        ];
+
+        let mut bytes = FlvHeader::new(true, true).to_bytes().to_vec();
+        for tag in &tags {
+            bytes.extend_from_slice(&tag.to_bytes().unwrap());
+        }
+
+        let mut demuxer = FlvDemuxer::new();
+        let mut results = Vec::new();
+        for byte in bytes {
+            demuxer.push(&[byte]);
+            while let Some(tag) = demuxer.next_tag().unwrap() {
+                results.push(tag);
+            }
+        }
+
+        assert_eq!(results, tags);
+    }
+
+    #[test]
+    fn error_when_tag_type_is_unknown() {
+        let mut bytes = FlvHeader::new(true, true).to_bytes().to_vec();
+        bytes.extend_from_slice(&[0x28, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 11]);
+
+        let mut demuxer = FlvDemuxer::new();
+        demuxer.push(&bytes);
+        match demuxer.next_tag() {
+            Err(FlvReadError::UnknownTagType { tag_type: 0x28 }) => (),
+            x => panic!("Expected unknown tag type error, instead got {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use rml_amf0::{Amf0DeserializationError, Amf0SerializationError};
+use rml_rtmp::sessions::ServerSessionError;
+use std::io;
+use thiserror::Error;
+
+/// Errors that can occur while reading FLV data
+#[derive(Debug, Error)]
+pub enum FlvReadError {
+    /// Every FLV file starts with the bytes `FLV`, and this is raised if they are missing
+    #[error("Data does not start with an FLV signature")]
+    InvalidSignature,
+
+    /// The header declared that it is shorter than the fields every header contains
+    #[error("FLV header length of {length} is too short")]
+    InvalidHeaderLength { length: u32 },
+
+    /// Tags are followed by the size of the tag that was just read, which didn't match the tag.
+    /// This usually means the data is corrupt or not an FLV file at all.
+    #[error("Previous tag size was {actual} but the tag was {expected} bytes")]
+    PreviousTagSizeMismatch { expected: u32, actual: u32 },
+
+    /// A tag had a type other than audio, video, or script data.  Encrypted tags also fall into
+    /// this category.
+    #[error("Unknown tag type of {tag_type}")]
+    UnknownTagType { tag_type: u8 },
+
+    /// An I/O error occurred while reading from the input
+    #[error("Failed to read FLV data: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Errors that can occur while writing FLV data
+#[derive(Debug, Error)]
+pub enum FlvWriteError {
+    /// A tag's size is stored in 24 bits, so payloads larger than 16,777,215 bytes can't be
+    /// written
+    #[error("Tag data of {size} bytes is too large to be written")]
+    TagTooLarge { size: usize },
+
+    /// An I/O error occurred while writing to the output
+    #[error("Failed to write FLV data: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Errors that can occur while recording a stream
+#[derive(Debug, Error)]
+pub enum StreamRecorderError {
+    /// A tag could not be written to the output
+    #[error("Failed to write tag: {0}")]
+    Write(#[from] FlvWriteError),
+
+    /// The stream's metadata could not be serialized into a script data tag
+    #[error("Failed to serialize metadata: {0}")]
+    MetadataSerialization(#[from] Amf0SerializationError),
+
+    /// An I/O error occurred while updating the header or metadata of a finished recording
+    #[error("Failed to finalize recording: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Errors that can occur while converting a live stream into FLV tags for viewers
+#[derive(Debug, Error)]
+pub enum LiveFlvError {
+    /// A tag could not be serialized
+    #[error("Failed to serialize tag: {0}")]
+    Write(#[from] FlvWriteError),
+
+    /// The stream's metadata could not be serialized into a script data tag
+    #[error("Failed to serialize metadata: {0}")]
+    MetadataSerialization(#[from] Amf0SerializationError),
+}
+
+/// Errors that can occur while parsing a codec's decoder configuration or parameter sets
+#[derive(Debug, Error)]
+pub enum CodecConfigError {
+    /// The data ended before all of the fields it declared could be read
+    #[error("Codec configuration is truncated")]
+    Truncated,
+
+    /// The configuration record has a version other than the one that is supported
+    #[error("Unsupported configuration record version of {version}")]
+    UnsupportedVersion { version: u8 },
+
+    /// The configuration record did not contain the sequence parameter set (or AV1 sequence
+    /// header) needed
+    #[error("Configuration record contains no sequence parameter set or header")]
+    MissingParameterSet,
+
+    /// A parameter set contained a value that is out of range
+    #[error("Parameter set contains an invalid value")]
+    InvalidParameterSet,
+}
+
+/// Errors that can occur while playing an FLV file to a server session
+#[derive(Debug, Error)]
+pub enum VodSourceError {
+    /// The file could not be read
+    #[error("Failed to read FLV file: {0}")]
+    Read(#[from] FlvReadError),
+
+    /// A script data tag could not be deserialized
+    #[error("Failed to deserialize script data: {0}")]
+    ScriptData(#[from] Amf0DeserializationError),
+
+    /// The server session could not create a packet for a tag
+    #[error("Failed to create packet: {0}")]
+    Session(#[from] ServerSessionError),
+
+    /// An I/O error occurred while seeking in the file
+    #[error("Failed to seek in FLV file: {0}")]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,260 @@
+//! This crate provides functionality for reading and writing data in Adobe's Flash Video (FLV)
+//! container format, based on the specification located at
+//! <https://www.adobe.com/content/dam/acom/en/devnet/flv/video_file_format_spec_v10.pdf>
+//!
+//! An FLV file (or HTTP-FLV stream) is a header followed by a series of tags, each of which
+//! contains one audio packet, video packet, or block of Amf0 encoded script data.  These are the
+//! same payloads RTMP sends in audio, video, and Amf0 data messages, so tags can be converted to
+//! and from `RtmpMessage`s without touching the media itself.
+//!
+//! * `FlvWriter` writes a header and tags to any `Write` implementation
+//! * `FlvReader` reads a header and tags from any `Read` implementation
+//! * `FlvDemuxer` reads tags from bytes as they are received, for when blocking on a `Read`
+//!   isn't an option
+//! * `StreamRecorder` records a stream published to an RTMP server session into an FLV file
+//! * `VodSource` plays an FLV file to an RTMP server session at the rate it should be
+//!   played, with seeking
+//! * `SegmentedRecorder` records a stream into a series of FLV files, rotating them by duration
+//!   or size
+//! * `LiveFlvStream` turns a stream published to an RTMP server session into FLV byte streams
+//!   for live viewers, such as HTTP-FLV players
+//! * The `websocket` module frames those same streams for WebSocket (WS-FLV) players
+//! * `StreamRecorder` and `LiveFlvStream` are `rml_rtmp::pipeline::MediaSink`s, and `VodSource`
+//!   is a `MediaSource`, so they can be connected to other parts of a media pipeline directly
+//! * The `codecs` module reads video properties such as the resolution and frame rate from
+//!   AVC sequence headers and enhanced RTMP HEVC and AV1 sequence start packets
+//!
+//! # Examples
+//! ```
+//! extern crate bytes;
+//! extern crate rml_flv;
+//! extern crate rml_rtmp;
+//!
+//! use bytes::Bytes;
+//! use rml_flv::{FlvHeader, FlvReader, FlvTag, FlvWriter};
+//! use rml_rtmp::messages::RtmpMessage;
+//! use rml_rtmp::time::RtmpTimestamp;
+//! use std::io::Cursor;
+//!
+//! let message = RtmpMessage::VideoData { data: Bytes::from(vec![0x17, 0x01, 0, 0, 0]) };
+//! let tag = FlvTag::from_rtmp_message(&message, RtmpTimestamp::new(40)).unwrap();
+//!
+//! let mut writer = FlvWriter::new(Vec::new(), FlvHeader::new(true, true)).unwrap();
+//! writer.write_tag(&tag).unwrap();
+//! let bytes = writer.into_inner();
+//!
+//! let mut reader = FlvReader::new(Cursor::new(bytes)).unwrap();
+//! let read_tag = reader.read_tag().unwrap().unwrap();
+//! assert_eq!(read_tag.to_rtmp_message().unwrap(), message);
+//! assert_eq!(read_tag.timestamp, RtmpTimestamp::new(40));
+//! ```
+
+extern crate byteorder;
+extern crate bytes;
+extern crate rml_amf0;
+extern crate rml_rtmp;
+extern crate thiserror;
+
+pub mod codecs;
+mod demuxer;
+mod errors;
+mod live;
+mod media;
+mod reader;
+mod recorder;
+mod segmented_recorder;
+#[cfg(test)]
+mod test_support;
+mod vod;
+pub mod websocket;
+mod writer;
+
+pub use demuxer::FlvDemuxer;
+pub use errors::{
+    CodecConfigError, FlvReadError, FlvWriteError, LiveFlvError, StreamRecorderError,
+    VodSourceError,
+};
+pub use live::{FlvViewerId, LiveFlvStream};
+pub use media::{
+    AacPacketType, AudioTagHeader, AvcPacketType, SoundFormat, VideoCodec, VideoFrameType,
+    VideoTagHeader,
+};
+pub use reader::FlvReader;
+pub use recorder::StreamRecorder;
+pub use segmented_recorder::{RecordedSegment, SegmentedRecorder, SegmentedRecordingConfig};
+pub use vod::VodSource;
+pub use writer::FlvWriter;
+
+use bytes::Bytes;
+use rml_amf0::Amf0DeserializationError;
+use rml_rtmp::messages::RtmpMessage;
+use rml_rtmp::time::RtmpTimestamp;
+use std::io::Cursor;
+
+/// The number of bytes in an FLV header
+pub const HEADER_LENGTH: usize = 9;
+
+/// The number of bytes in the header of each tag
+pub const TAG_HEADER_LENGTH: usize = 11;
+
+/// The header at the start of every FLV file, declaring what kind of media it contains
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct FlvHeader {
+    pub has_audio: bool,
+    pub has_video: bool,
+}
+
+impl FlvHeader {
+    /// Creates a header for a file with the specified kinds of media
+    pub fn new(has_audio: bool, has_video: bool) -> FlvHeader {
+        FlvHeader {
+            has_audio,
+            has_video,
+        }
+    }
+}
+
+/// The kind of payload carried by a tag
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum FlvTagType {
+    Audio,
+    Video,
+    ScriptData,
+}
+
+impl FlvTagType {
+    /// The value used for this tag type in a tag's header
+    pub fn to_u8(self) -> u8 {
+        match self {
+            FlvTagType::Audio => 8,
+            FlvTagType::Video => 9,
+            FlvTagType::ScriptData => 18,
+        }
+    }
+
+    /// Gets the tag type for a tag header value, if it's one that is known
+    pub fn from_u8(value: u8) -> Option<FlvTagType> {
+        match value {
+            8 => Some(FlvTagType::Audio),
+            9 => Some(FlvTagType::Video),
+            18 => Some(FlvTagType::ScriptData),
+            _ => None,
+        }
+    }
+}
+
+/// A single audio packet, video packet, or block of script data, along with the time it should
+/// be presented at
+#[derive(PartialEq, Debug, Clone)]
+pub struct FlvTag {
+    pub tag_type: FlvTagType,
+    pub timestamp: RtmpTimestamp,
+    pub data: Bytes,
+}
+
+impl FlvTag {
+    /// Creates a new tag
+    pub fn new(tag_type: FlvTagType, timestamp: RtmpTimestamp, data: Bytes) -> FlvTag {
+        FlvTag {
+            tag_type,
+            timestamp,
+            data,
+        }
+    }
+
+    /// Creates a tag containing the payload of an RTMP audio, video, or Amf0 data message.
+    /// `None` is returned for all other messages, since they have no place in an FLV file.
+    ///
+    /// Data messages sent by publishers usually start with `@setDataFrame`, which tells the
+    /// server to store the remaining values (e.g. `onMetaData` and its properties) and send
+    /// them to players.  Only the stored values belong in the file, so it is removed.
+    pub fn from_rtmp_message(message: &RtmpMessage, timestamp: RtmpTimestamp) -> Option<FlvTag> {
+        match *message {
+            RtmpMessage::AudioData { ref data } => {
+                Some(FlvTag::new(FlvTagType::Audio, timestamp, data.clone()))
+            }
+
+            RtmpMessage::VideoData { ref data } => {
+                Some(FlvTag::new(FlvTagType::Video, timestamp, data.clone()))
+            }
+
+            RtmpMessage::Amf0Data { ref values } => {
+                let values = match values.first().and_then(|x| x.as_str()) {
+                    Some("@setDataFrame") => &values[1..],
+                    _ => &values[..],
+                };
+
+                // Values that can't be serialized can't be sent over RTMP either, so this
+                // only fails for values built by hand.
+                let data = rml_amf0::serialize(&values.to_vec()).ok()?;
+                Some(FlvTag::new(
+                    FlvTagType::ScriptData,
+                    timestamp,
+                    Bytes::from(data),
+                ))
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Converts the tag into the RTMP message that carries its payload
+    pub fn to_rtmp_message(&self) -> Result<RtmpMessage, Amf0DeserializationError> {
+        let message = match self.tag_type {
+            FlvTagType::Audio => RtmpMessage::AudioData {
+                data: self.data.clone(),
+            },
+
+            FlvTagType::Video => RtmpMessage::VideoData {
+                data: self.data.clone(),
+            },
+
+            FlvTagType::ScriptData => {
+                let mut cursor = Cursor::new(&self.data[..]);
+                let values = rml_amf0::deserialize(&mut cursor)?;
+                RtmpMessage::Amf0Data { values }
+            }
+        };
+
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rml_amf0::Amf0Value;
+
+    #[test]
+    fn set_data_frame_is_removed_from_script_data() {
+        let message = RtmpMessage::Amf0Data {
+            values: vec![
+                Amf0Value::Utf8String("@setDataFrame".to_string()),
+                Amf0Value::Utf8String("onMetaData".to_string()),
+                Amf0Value::Number(5.0),
+            ],
+        };
+
+        let tag = FlvTag::from_rtmp_message(&message, RtmpTimestamp::new(0)).unwrap();
+
+        let expected = RtmpMessage::Amf0Data {
+            values: vec![
+                Amf0Value::Utf8String("onMetaData".to_string()),
+                Amf0Value::Number(5.0),
+            ],
+        };
+
+        assert_eq!(tag.tag_type, FlvTagType::ScriptData);
+        assert_eq!(tag.to_rtmp_message().unwrap(), expected);
+    }
+
+    #[test]
+    fn non_media_messages_have_no_tag() {
+        let message = RtmpMessage::SetChunkSize { size: 4096 };
+
+        assert_eq!(
+            FlvTag::from_rtmp_message(&message, RtmpTimestamp::new(0)),
+            None
+        );
+    }
+}
@@ -0,0 +1,543 @@
+use bytes::{Bytes, BytesMut};
+use errors::LiveFlvError;
+use media::{AudioTagHeader, VideoTagHeader};
+use recorder::create_metadata_properties;
+use rml_amf0::Amf0Value;
+use rml_rtmp::pipeline::{MediaItem, MediaSink};
+use rml_rtmp::sessions::{ServerSessionEvent, StreamMetadata};
+use rml_rtmp::time::RtmpTimestamp;
+use std::collections::{HashMap, VecDeque};
+use {FlvHeader, FlvTag, FlvTagType};
+
+const DEFAULT_MAX_BUFFERED_BYTES: usize = 4 * 1024 * 1024;
+
+/// Identifies a viewer added to a `LiveFlvStream`
+pub type FlvViewerId = usize;
+
+/// Converts a stream being published to a server session into continuous FLV byte streams for
+/// any number of viewers, such as HTTP-FLV responses consumed by flv.js.
+///
+/// Events raised by the server session are passed into `handle_event()`, just like with
+/// `StreamRecorder`, and each viewer's bytes are retrieved with `take_buffered()` whenever its
+/// connection is ready to be written to.  Each tag is serialized once and shared by every
+/// viewer, so timestamps are passed through as the publisher sent them.
+///
+/// Viewers can join at any time.  Each one receives the FLV header straight away, and then
+/// waits for the next keyframe (or the next audio frame if the stream has no video), where it
+/// is sent the most recent metadata and sequence headers followed by the frame itself.
+///
+/// Viewers that can't keep up are not allowed to buffer without limit.  Once a viewer has more
+/// than the maximum number of bytes waiting, its audio and video are dropped until the next
+/// keyframe that fits, so playback resumes cleanly instead of stalling further behind.
+/// Metadata and sequence headers are never dropped.
+///
+/// # Examples
+/// ```
+/// extern crate bytes;
+/// extern crate rml_flv;
+/// extern crate rml_rtmp;
+///
+/// use bytes::Bytes;
+/// use rml_flv::LiveFlvStream;
+/// use rml_rtmp::sessions::ServerSessionEvent;
+/// use rml_rtmp::time::RtmpTimestamp;
+///
+/// let video = |data: Vec<u8>, timestamp| ServerSessionEvent::VideoDataReceived {
+///     app_name: "live".to_string(),
+///     stream_key: "key".to_string(),
+///     data: Bytes::from(data),
+///     timestamp: RtmpTimestamp::new(timestamp),
+/// };
+///
+/// let mut stream = LiveFlvStream::new("live".to_string(), "key".to_string());
+/// stream.handle_event(&video(vec![0x17, 0, 0, 0, 0, 1], 0)).unwrap();
+///
+/// // The viewer joins late, but still receives the sequence header before the keyframe
+/// let viewer = stream.add_viewer();
+/// stream.handle_event(&video(vec![0x17, 1, 0, 0, 0, 2], 2000)).unwrap();
+///
+/// let bytes = stream.take_buffered(viewer).unwrap();
+/// assert_eq!(&bytes[..3], b"FLV");
+/// assert_eq!(stream.take_buffered(viewer), None);
+/// ```
+pub struct LiveFlvStream {
+    app_name: String,
+    stream_key: String,
+    max_buffered_bytes: usize,
+    metadata: Option<Bytes>,
+    audio_sequence_header: Option<Bytes>,
+    video_sequence_header: Option<Bytes>,
+    expects_video: bool,
+    last_timestamp: RtmpTimestamp,
+    viewers: HashMap<FlvViewerId, Viewer>,
+    next_viewer_id: FlvViewerId,
+    is_finished: bool,
+}
+
+struct Viewer {
+    chunks: VecDeque<Bytes>,
+    buffered_bytes: usize,
+    has_started: bool,
+    is_dropping: bool,
+}
+
+impl Viewer {
+    fn push(&mut self, chunk: Bytes) {
+        self.buffered_bytes += chunk.len();
+        self.chunks.push_back(chunk);
+    }
+}
+
+impl LiveFlvStream {
+    /// Creates an FLV stream for the specified published stream
+    pub fn new(app_name: String, stream_key: String) -> LiveFlvStream {
+        LiveFlvStream {
+            app_name,
+            stream_key,
+            max_buffered_bytes: DEFAULT_MAX_BUFFERED_BYTES,
+            metadata: None,
+            audio_sequence_header: None,
+            video_sequence_header: None,
+            expects_video: false,
+            last_timestamp: RtmpTimestamp::new(0),
+            viewers: HashMap::new(),
+            next_viewer_id: 0,
+            is_finished: false,
+        }
+    }
+
+    /// Sets how many bytes can be waiting for a viewer before its media starts being dropped.
+    /// Defaults to 4MB.
+    pub fn set_max_buffered_bytes(&mut self, max_buffered_bytes: usize) {
+        self.max_buffered_bytes = max_buffered_bytes;
+    }
+
+    /// Adds a viewer, whose stream starts with the FLV header
+    pub fn add_viewer(&mut self) -> FlvViewerId {
+        let id = self.next_viewer_id;
+        self.next_viewer_id += 1;
+
+        let mut viewer = Viewer {
+            chunks: VecDeque::new(),
+            buffered_bytes: 0,
+            has_started: false,
+            is_dropping: false,
+        };
+
+        viewer.push(FlvHeader::new(true, true).to_bytes());
+        self.viewers.insert(id, viewer);
+        id
+    }
+
+    /// Removes a viewer, discarding anything that was waiting to be sent to it
+    pub fn remove_viewer(&mut self, viewer_id: FlvViewerId) {
+        self.viewers.remove(&viewer_id);
+    }
+
+    /// The number of viewers that have been added and not removed
+    pub fn viewer_count(&self) -> usize {
+        self.viewers.len()
+    }
+
+    /// Adds any media or metadata contained in the event to each viewer's stream
+    pub fn handle_event(&mut self, event: &ServerSessionEvent) -> Result<(), LiveFlvError> {
+        match *event {
+            ServerSessionEvent::StreamMetadataChanged {
+                ref app_name,
+                ref stream_key,
+                ref metadata,
+            } if self.is_live_stream(app_name, stream_key) => self.handle_metadata(metadata),
+
+            ServerSessionEvent::AudioDataReceived {
+                ref app_name,
+                ref stream_key,
+                ref data,
+                timestamp,
+            } if self.is_live_stream(app_name, stream_key) => {
+                self.handle_audio(data.clone(), timestamp)
+            }
+
+            ServerSessionEvent::VideoDataReceived {
+                ref app_name,
+                ref stream_key,
+                ref data,
+                timestamp,
+            } if self.is_live_stream(app_name, stream_key) => {
+                self.handle_video(data.clone(), timestamp)
+            }
+
+            ServerSessionEvent::PublishStreamFinished {
+                ref app_name,
+                ref stream_key,
+            } if self.is_live_stream(app_name, stream_key) => {
+                self.is_finished = true;
+                Ok(())
+            }
+
+            _ => Ok(()),
+        }
+    }
+
+    /// True once the publisher has finished.  Viewers should be sent whatever remains buffered
+    /// for them and then have their connections closed.
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// The number of bytes waiting to be sent to a viewer
+    pub fn buffered_bytes(&self, viewer_id: FlvViewerId) -> usize {
+        self.viewers.get(&viewer_id).map_or(0, |x| x.buffered_bytes)
+    }
+
+    /// Takes the next piece of a viewer's stream, which is either the FLV header or a single
+    /// tag (including the size that follows it).  `None` is returned if nothing is waiting.
+    pub fn next_chunk(&mut self, viewer_id: FlvViewerId) -> Option<Bytes> {
+        let viewer = self.viewers.get_mut(&viewer_id)?;
+        let chunk = viewer.chunks.pop_front()?;
+        viewer.buffered_bytes -= chunk.len();
+        Some(chunk)
+    }
+
+    /// Takes everything waiting to be sent to a viewer as one block of bytes, to be written to
+    /// its connection.  `None` is returned if nothing is waiting.
+    pub fn take_buffered(&mut self, viewer_id: FlvViewerId) -> Option<Bytes> {
+        let viewer = self.viewers.get_mut(&viewer_id)?;
+        if viewer.chunks.len() <= 1 {
+            viewer.buffered_bytes = 0;
+            return viewer.chunks.pop_front();
+        }
+
+        let mut bytes = BytesMut::with_capacity(viewer.buffered_bytes);
+        for chunk in viewer.chunks.drain(..) {
+            bytes.extend_from_slice(&chunk);
+        }
+
+        viewer.buffered_bytes = 0;
+        Some(bytes.freeze())
+    }
+
+    fn is_live_stream(&self, app_name: &str, stream_key: &str) -> bool {
+        !self.is_finished && self.app_name == app_name && self.stream_key == stream_key
+    }
+
+    fn handle_metadata(&mut self, metadata: &StreamMetadata) -> Result<(), LiveFlvError> {
+        if metadata.video_codec.is_some() || metadata.video_width.is_some() {
+            self.expects_video = true;
+        }
+
+        let values = vec![
+            Amf0Value::Utf8String("onMetaData".to_string()),
+            Amf0Value::Object(create_metadata_properties(metadata)),
+        ];
+
+        let data = Bytes::from(rml_amf0::serialize(&values)?);
+        self.metadata = Some(data.clone());
+
+        // Viewers that haven't started yet receive the metadata with their first frame
+        let timestamp = self.last_timestamp;
+        let tag = FlvTag::new(FlvTagType::ScriptData, timestamp, data).to_bytes()?;
+        for viewer in self.viewers.values_mut().filter(|x| x.has_started) {
+            viewer.push(tag.clone());
+        }
+
+        Ok(())
+    }
+
+    fn handle_audio(&mut self, data: Bytes, timestamp: RtmpTimestamp) -> Result<(), LiveFlvError> {
+        let header = match AudioTagHeader::parse(&data) {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+
+        if header.is_sequence_header() {
+            self.audio_sequence_header = Some(data.clone());
+            return self.send_sequence_header(FlvTagType::Audio, timestamp, data);
+        }
+
+        // Audio only starts (or resumes) streams without video, since otherwise viewers would
+        // hear audio over a frozen or blank picture until the next keyframe
+        let can_start = !self.expects_video;
+        self.send_media(FlvTagType::Audio, timestamp, data, can_start)
+    }
+
+    fn handle_video(&mut self, data: Bytes, timestamp: RtmpTimestamp) -> Result<(), LiveFlvError> {
+        let header = match VideoTagHeader::parse(&data) {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+
+        self.expects_video = true;
+        if header.is_sequence_header() {
+            self.video_sequence_header = Some(data.clone());
+            return self.send_sequence_header(FlvTagType::Video, timestamp, data);
+        }
+
+        let can_start = header.is_keyframe();
+        self.send_media(FlvTagType::Video, timestamp, data, can_start)
+    }
+
+    fn send_sequence_header(
+        &mut self,
+        tag_type: FlvTagType,
+        timestamp: RtmpTimestamp,
+        data: Bytes,
+    ) -> Result<(), LiveFlvError> {
+        let tag = FlvTag::new(tag_type, timestamp, data).to_bytes()?;
+        for viewer in self.viewers.values_mut().filter(|x| x.has_started) {
+            viewer.push(tag.clone());
+        }
+
+        Ok(())
+    }
+
+    fn send_media(
+        &mut self,
+        tag_type: FlvTagType,
+        timestamp: RtmpTimestamp,
+        data: Bytes,
+        can_start: bool,
+    ) -> Result<(), LiveFlvError> {
+        let tag = FlvTag::new(tag_type, timestamp, data).to_bytes()?;
+        self.last_timestamp = timestamp;
+
+        // The tags a viewer needs before its first frame are only serialized if someone joined
+        let mut start_tags = None;
+        for viewer in self.viewers.values_mut() {
+            let has_room = viewer.buffered_bytes + tag.len() <= self.max_buffered_bytes;
+            if !viewer.has_started {
+                if !can_start {
+                    continue;
+                }
+
+                if start_tags.is_none() {
+                    start_tags = Some(create_start_tags(
+                        timestamp,
+                        &self.metadata,
+                        &self.video_sequence_header,
+                        &self.audio_sequence_header,
+                    )?);
+                }
+
+                for start_tag in start_tags.iter().flatten() {
+                    viewer.push(start_tag.clone());
+                }
+
+                viewer.has_started = true;
+                viewer.push(tag.clone());
+                continue;
+            }
+
+            if !has_room {
+                viewer.is_dropping = true;
+                continue;
+            }
+
+            if viewer.is_dropping {
+                if !can_start {
+                    continue;
+                }
+
+                viewer.is_dropping = false;
+            }
+
+            viewer.push(tag.clone());
+        }
+
+        Ok(())
+    }
+}
+
+impl MediaSink for LiveFlvStream {
+    type Error = LiveFlvError;
+
+    /// Adds the item to each viewer's stream.  Items sent after the end of the stream are
+    /// ignored.
+    fn send_media(&mut self, item: MediaItem) -> Result<(), Self::Error> {
+        if self.is_finished {
+            return Ok(());
+        }
+
+        match item {
+            MediaItem::Metadata(metadata) => self.handle_metadata(&metadata),
+            MediaItem::Video { data, timestamp } => self.handle_video(data, timestamp),
+            MediaItem::Audio { data, timestamp } => self.handle_audio(data, timestamp),
+            MediaItem::EndOfStream => {
+                self.is_finished = true;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn create_start_tags(
+    timestamp: RtmpTimestamp,
+    metadata: &Option<Bytes>,
+    video_sequence_header: &Option<Bytes>,
+    audio_sequence_header: &Option<Bytes>,
+) -> Result<Vec<Bytes>, LiveFlvError> {
+    let tags = [
+        (FlvTagType::ScriptData, metadata),
+        (FlvTagType::Video, video_sequence_header),
+        (FlvTagType::Audio, audio_sequence_header),
+    ];
+
+    let mut start_tags = Vec::new();
+    for (tag_type, data) in tags.iter() {
+        if let Some(ref data) = **data {
+            start_tags.push(FlvTag::new(*tag_type, timestamp, data.clone()).to_bytes()?);
+        }
+    }
+
+    Ok(start_tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use demuxer::FlvDemuxer;
+    use std::sync::Arc;
+    use test_support::{audio, video, APP, KEY};
+
+    fn read_tags(bytes: &[u8]) -> Vec<(FlvTagType, u32, u8)> {
+        let mut demuxer = FlvDemuxer::new();
+        demuxer.push(bytes);
+
+        let mut tags = Vec::new();
+        while let Some(tag) = demuxer.next_tag().unwrap() {
+            tags.push((tag.tag_type, tag.timestamp.value, tag.data[1]));
+        }
+
+        tags
+    }
+
+    fn new_stream() -> LiveFlvStream {
+        LiveFlvStream::new(APP.to_string(), KEY.to_string())
+    }
+
+    #[test]
+    fn late_joiner_receives_metadata_and_sequence_headers_at_next_keyframe() {
+        let mut stream = new_stream();
+        stream
+            .handle_event(&ServerSessionEvent::StreamMetadataChanged {
+                app_name: APP.to_string(),
+                stream_key: KEY.to_string(),
+                metadata: Arc::new(StreamMetadata::new()),
+            })
+            .unwrap();
+
+        stream
+            .handle_event(&video(vec![0x17, 0, 0, 0, 0], 0))
+            .unwrap();
+        stream.handle_event(&audio(vec![0xaf, 0, 8], 0)).unwrap();
+        stream
+            .handle_event(&video(vec![0x17, 1, 0, 0, 0], 0))
+            .unwrap();
+
+        let viewer = stream.add_viewer();
+        stream
+            .handle_event(&video(vec![0x27, 1, 0, 0, 0], 40))
+            .unwrap();
+        stream.handle_event(&audio(vec![0xaf, 1, 2], 50)).unwrap();
+        stream
+            .handle_event(&video(vec![0x17, 1, 0, 0, 0], 2000))
+            .unwrap();
+        stream.handle_event(&audio(vec![0xaf, 1, 3], 2010)).unwrap();
+
+        let bytes = stream.take_buffered(viewer).unwrap();
+        let tags = read_tags(&bytes);
+
+        assert_eq!(tags[0].0, FlvTagType::ScriptData);
+        assert_eq!(
+            tags[1..].to_vec(),
+            vec![
+                (FlvTagType::Video, 2000, 0),
+                (FlvTagType::Audio, 2000, 0),
+                (FlvTagType::Video, 2000, 1),
+                (FlvTagType::Audio, 2010, 1),
+            ]
+        );
+
+        assert_eq!(stream.buffered_bytes(viewer), 0);
+    }
+
+    #[test]
+    fn audio_starts_viewers_of_streams_without_video() {
+        let mut stream = new_stream();
+        let viewer = stream.add_viewer();
+        assert_eq!(
+            stream.next_chunk(viewer),
+            Some(FlvHeader::new(true, true).to_bytes())
+        );
+
+        stream.handle_event(&audio(vec![0x2f, 1, 7], 100)).unwrap();
+
+        let chunk = stream.next_chunk(viewer).unwrap();
+        assert_eq!(chunk[0], FlvTagType::Audio.to_u8());
+        assert_eq!(stream.next_chunk(viewer), None);
+    }
+
+    #[test]
+    fn slow_viewer_drops_media_until_keyframe_that_fits() {
+        let mut stream = new_stream();
+        stream.set_max_buffered_bytes(100);
+
+        let viewer = stream.add_viewer();
+        stream
+            .handle_event(&video(vec![0x17, 1, 0, 0, 0], 0))
+            .unwrap();
+        for x in 1..10 {
+            stream
+                .handle_event(&video(vec![0x27, x, 0, 0, 0], x as u32 * 40))
+                .unwrap();
+        }
+
+        stream.handle_event(&audio(vec![0xaf, 1, 2], 400)).unwrap();
+        let buffered = stream.buffered_bytes(viewer);
+        assert!(buffered <= 100);
+
+        // Once drained, delta frames are still skipped until the next keyframe
+        let bytes = stream.take_buffered(viewer).unwrap();
+        stream
+            .handle_event(&video(vec![0x27, 1, 0, 0, 0], 440))
+            .unwrap();
+        assert_eq!(stream.buffered_bytes(viewer), 0);
+
+        stream
+            .handle_event(&video(vec![0x17, 1, 0, 0, 0], 480))
+            .unwrap();
+        stream
+            .handle_event(&video(vec![0x27, 2, 0, 0, 0], 520))
+            .unwrap();
+
+        let mut all_bytes = bytes.to_vec();
+        all_bytes.extend_from_slice(&stream.take_buffered(viewer).unwrap());
+        let timestamps = read_tags(&all_bytes)
+            .iter()
+            .map(|x| x.1)
+            .collect::<Vec<_>>();
+
+        assert_eq!(timestamps, vec![0, 40, 80, 120, 480, 520]);
+    }
+
+    #[test]
+    fn finishes_when_publisher_stops() {
+        let mut stream = new_stream();
+        stream
+            .handle_event(&ServerSessionEvent::PublishStreamFinished {
+                app_name: APP.to_string(),
+                stream_key: "other".to_string(),
+            })
+            .unwrap();
+
+        assert!(!stream.is_finished());
+
+        stream
+            .handle_event(&ServerSessionEvent::PublishStreamFinished {
+                app_name: APP.to_string(),
+                stream_key: KEY.to_string(),
+            })
+            .unwrap();
+
+        assert!(stream.is_finished());
+    }
+}
@@ -0,0 +1,275 @@
+use byteorder::{BigEndian, ByteOrder};
+
+/// The codec used for the audio in an audio tag
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SoundFormat {
+    LinearPcmPlatformEndian,
+    Adpcm,
+    Mp3,
+    LinearPcmLittleEndian,
+    Nellymoser16KhzMono,
+    Nellymoser8KhzMono,
+    Nellymoser,
+    G711ALaw,
+    G711MuLaw,
+    Aac,
+    Speex,
+    Mp38Khz,
+    DeviceSpecific,
+    Unknown(u8),
+}
+
+impl SoundFormat {
+    fn from_u8(value: u8) -> SoundFormat {
+        match value {
+            0 => SoundFormat::LinearPcmPlatformEndian,
+            1 => SoundFormat::Adpcm,
+            2 => SoundFormat::Mp3,
+            3 => SoundFormat::LinearPcmLittleEndian,
+            4 => SoundFormat::Nellymoser16KhzMono,
+            5 => SoundFormat::Nellymoser8KhzMono,
+            6 => SoundFormat::Nellymoser,
+            7 => SoundFormat::G711ALaw,
+            8 => SoundFormat::G711MuLaw,
+            10 => SoundFormat::Aac,
+            11 => SoundFormat::Speex,
+            14 => SoundFormat::Mp38Khz,
+            15 => SoundFormat::DeviceSpecific,
+            x => SoundFormat::Unknown(x),
+        }
+    }
+}
+
+/// What an AAC audio tag contains
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AacPacketType {
+    /// The AudioSpecificConfig decoders need before any audio can be played
+    SequenceHeader,
+
+    /// Raw AAC frame data
+    Raw,
+
+    Unknown(u8),
+}
+
+/// The information at the start of every audio tag's data (and RTMP audio message)
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct AudioTagHeader {
+    pub sound_format: SoundFormat,
+
+    /// The sample rate in hertz.  AAC always reports 44,100 regardless of the real sample rate,
+    /// which is contained in the sequence header instead.
+    pub sound_rate: u32,
+
+    /// The size of each sample in bits (8 or 16)
+    pub sound_size: u8,
+
+    pub is_stereo: bool,
+
+    /// Only present when the sound format is AAC
+    pub aac_packet_type: Option<AacPacketType>,
+}
+
+impl AudioTagHeader {
+    /// Parses the header from the start of an audio tag's data, returning `None` if there are
+    /// not enough bytes
+    pub fn parse(data: &[u8]) -> Option<AudioTagHeader> {
+        let flags = *data.first()?;
+        let sound_format = SoundFormat::from_u8(flags >> 4);
+        let sound_rate = match (flags >> 2) & 0x03 {
+            0 => 5_500,
+            1 => 11_025,
+            2 => 22_050,
+            _ => 44_100,
+        };
+
+        let aac_packet_type = match sound_format {
+            SoundFormat::Aac => Some(match *data.get(1)? {
+                0 => AacPacketType::SequenceHeader,
+                1 => AacPacketType::Raw,
+                x => AacPacketType::Unknown(x),
+            }),
+
+            _ => None,
+        };
+
+        Some(AudioTagHeader {
+            sound_format,
+            sound_rate,
+            sound_size: if flags & 0x02 != 0 { 16 } else { 8 },
+            is_stereo: flags & 0x01 != 0,
+            aac_packet_type,
+        })
+    }
+
+    /// True if this is an AAC sequence header, which must be sent to new players before any
+    /// other audio
+    pub fn is_sequence_header(&self) -> bool {
+        self.aac_packet_type == Some(AacPacketType::SequenceHeader)
+    }
+}
+
+/// Whether a video tag can be decoded on its own
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum VideoFrameType {
+    Keyframe,
+    InterFrame,
+    DisposableInterFrame,
+    GeneratedKeyframe,
+    VideoInfoOrCommand,
+    Unknown(u8),
+}
+
+/// The codec used for the video in a video tag
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum VideoCodec {
+    SorensonH263,
+    ScreenVideo,
+    On2Vp6,
+    On2Vp6WithAlpha,
+    ScreenVideo2,
+    Avc,
+    Unknown(u8),
+}
+
+/// What an AVC (H.264) video tag contains
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AvcPacketType {
+    /// The AVCDecoderConfigurationRecord (SPS and PPS) decoders need before any video can be
+    /// played
+    SequenceHeader,
+
+    /// One or more NAL units
+    Nalu,
+
+    EndOfSequence,
+    Unknown(u8),
+}
+
+/// The information at the start of every video tag's data (and RTMP video message)
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct VideoTagHeader {
+    pub frame_type: VideoFrameType,
+    pub codec: VideoCodec,
+
+    /// Only present when the codec is AVC
+    pub avc_packet_type: Option<AvcPacketType>,
+
+    /// The offset in milliseconds between the tag's timestamp and when the frame should be
+    /// presented.  Always zero unless the codec is AVC.
+    pub composition_time: i32,
+}
+
+impl VideoTagHeader {
+    /// Parses the header from the start of a video tag's data, returning `None` if there are
+    /// not enough bytes
+    pub fn parse(data: &[u8]) -> Option<VideoTagHeader> {
+        let flags = *data.first()?;
+        let frame_type = match flags >> 4 {
+            1 => VideoFrameType::Keyframe,
+            2 => VideoFrameType::InterFrame,
+            3 => VideoFrameType::DisposableInterFrame,
+            4 => VideoFrameType::GeneratedKeyframe,
+            5 => VideoFrameType::VideoInfoOrCommand,
+            x => VideoFrameType::Unknown(x),
+        };
+
+        let codec = match flags & 0x0f {
+            2 => VideoCodec::SorensonH263,
+            3 => VideoCodec::ScreenVideo,
+            4 => VideoCodec::On2Vp6,
+            5 => VideoCodec::On2Vp6WithAlpha,
+            6 => VideoCodec::ScreenVideo2,
+            7 => VideoCodec::Avc,
+            x => VideoCodec::Unknown(x),
+        };
+
+        let (avc_packet_type, composition_time) = match codec {
+            VideoCodec::Avc => {
+                if data.len() < 5 {
+                    return None;
+                }
+
+                let packet_type = match data[1] {
+                    0 => AvcPacketType::SequenceHeader,
+                    1 => AvcPacketType::Nalu,
+                    2 => AvcPacketType::EndOfSequence,
+                    x => AvcPacketType::Unknown(x),
+                };
+
+                // Stored as a signed 24 bit value, so shift it into the top of an i32 and back
+                // down to extend the sign
+                let composition_time = (BigEndian::read_u24(&data[2..5]) << 8) as i32 >> 8;
+                (Some(packet_type), composition_time)
+            }
+
+            _ => (None, 0),
+        };
+
+        Some(VideoTagHeader {
+            frame_type,
+            codec,
+            avc_packet_type,
+            composition_time,
+        })
+    }
+
+    /// True if this is an AVC sequence header, which must be sent to new players before any
+    /// other video
+    pub fn is_sequence_header(&self) -> bool {
+        self.avc_packet_type == Some(AvcPacketType::SequenceHeader)
+    }
+
+    /// True if the frame can be decoded without any previous frames, making it a place new
+    /// players can start from
+    pub fn is_keyframe(&self) -> bool {
+        self.frame_type == VideoFrameType::Keyframe
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_aac_audio_header() {
+        let header = AudioTagHeader::parse(&[0xaf, 0x00, 0x12, 0x10]).unwrap();
+
+        assert_eq!(header.sound_format, SoundFormat::Aac);
+        assert_eq!(header.sound_rate, 44_100);
+        assert_eq!(header.sound_size, 16);
+        assert!(header.is_stereo);
+        assert_eq!(header.aac_packet_type, Some(AacPacketType::SequenceHeader));
+        assert!(header.is_sequence_header());
+    }
+
+    #[test]
+    fn can_parse_mp3_audio_header() {
+        let header = AudioTagHeader::parse(&[0x26, 0xff]).unwrap();
+
+        assert_eq!(header.sound_format, SoundFormat::Mp3);
+        assert_eq!(header.sound_rate, 11_025);
+        assert_eq!(header.sound_size, 16);
+        assert!(!header.is_stereo);
+        assert_eq!(header.aac_packet_type, None);
+    }
+
+    #[test]
+    fn can_parse_avc_video_header_with_negative_composition_time() {
+        let header = VideoTagHeader::parse(&[0x27, 0x01, 0xff, 0xff, 0xd8, 0x00]).unwrap();
+
+        assert_eq!(header.frame_type, VideoFrameType::InterFrame);
+        assert_eq!(header.codec, VideoCodec::Avc);
+        assert_eq!(header.avc_packet_type, Some(AvcPacketType::Nalu));
+        assert_eq!(header.composition_time, -40);
+        assert!(!header.is_keyframe());
+        assert!(!header.is_sequence_header());
+    }
+
+    #[test]
+    fn truncated_headers_are_not_parsed() {
+        assert_eq!(AudioTagHeader::parse(&[]), None);
+        assert_eq!(AudioTagHeader::parse(&[0xaf]), None);
+        assert_eq!(VideoTagHeader::parse(&[0x17, 0x00, 0x00]), None);
+    }
+}
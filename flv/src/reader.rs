@@ -0,0 +1,197 @@
+use byteorder::{BigEndian, ByteOrder};
+use bytes::Bytes;
+use errors::FlvReadError;
+use rml_rtmp::time::RtmpTimestamp;
+use std::io::{ErrorKind, Read};
+use {FlvHeader, FlvTag, FlvTagType, HEADER_LENGTH, TAG_HEADER_LENGTH};
+
+/// Parses the fixed size portion of the header, returning the header and the offset of the
+/// first tag's previous tag size
+pub(crate) fn parse_header(bytes: &[u8]) -> Result<(FlvHeader, usize), FlvReadError> {
+    if &bytes[0..3] != b"FLV" {
+        return Err(FlvReadError::InvalidSignature);
+    }
+
+    let flags = bytes[4];
+    let header = FlvHeader::new(flags & 0x04 != 0, flags & 0x01 != 0);
+    let length = BigEndian::read_u32(&bytes[5..9]);
+    if (length as usize) < HEADER_LENGTH {
+        return Err(FlvReadError::InvalidHeaderLength { length });
+    }
+
+    Ok((header, length as usize))
+}
+
+/// Parses a tag header, returning the tag's type, data size, and timestamp
+pub(crate) fn parse_tag_header(
+    bytes: &[u8],
+) -> Result<(FlvTagType, usize, RtmpTimestamp), FlvReadError> {
+    // The upper 2 bits are reserved, and the bit after them flags an encrypted tag.  Encrypted
+    // tags can't be handled, so leave that bit in so they are reported as unknown.
+    let tag_type = bytes[0] & 0x3f;
+    let tag_type = match FlvTagType::from_u8(tag_type) {
+        Some(tag_type) => tag_type,
+        None => return Err(FlvReadError::UnknownTagType { tag_type }),
+    };
+
+    let size = BigEndian::read_u24(&bytes[1..4]) as usize;
+    let timestamp = BigEndian::read_u24(&bytes[4..7]) | ((bytes[7] as u32) << 24);
+
+    Ok((tag_type, size, RtmpTimestamp::new(timestamp)))
+}
+
+pub(crate) fn check_previous_tag_size(
+    data_size: usize,
+    previous_tag_size: u32,
+) -> Result<(), FlvReadError> {
+    let expected = (TAG_HEADER_LENGTH + data_size) as u32;
+    if previous_tag_size != expected {
+        return Err(FlvReadError::PreviousTagSizeMismatch {
+            expected,
+            actual: previous_tag_size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Reads an FLV header and tags from an input, such as a file
+pub struct FlvReader<R: Read> {
+    reader: R,
+    header: FlvHeader,
+}
+
+impl<R: Read> FlvReader<R> {
+    /// Creates a reader, immediately reading the header from the input
+    pub fn new(mut reader: R) -> Result<FlvReader<R>, FlvReadError> {
+        let mut buffer = [0_u8; HEADER_LENGTH];
+        reader.read_exact(&mut buffer)?;
+        let (header, length) = parse_header(&buffer)?;
+
+        // Skip any header fields added by later versions, along with the first previous tag
+        // size (which is always zero)
+        let mut remaining = vec![0_u8; length - HEADER_LENGTH + 4];
+        reader.read_exact(&mut remaining)?;
+
+        Ok(FlvReader { reader, header })
+    }
+
+    /// The header that was read from the start of the input
+    pub fn header(&self) -> FlvHeader {
+        self.header
+    }
+
+    /// Reads the next tag, returning `None` if the input ended cleanly between tags
+    pub fn read_tag(&mut self) -> Result<Option<FlvTag>, FlvReadError> {
+        let mut tag_header = [0_u8; TAG_HEADER_LENGTH];
+        match self.reader.read_exact(&mut tag_header[..1]) {
+            Ok(()) => (),
+            Err(ref error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error.into()),
+        }
+
+        self.reader.read_exact(&mut tag_header[1..])?;
+        let (tag_type, size, timestamp) = parse_tag_header(&tag_header)?;
+
+        let mut data = vec![0_u8; size + 4];
+        self.reader.read_exact(&mut data)?;
+        let previous_tag_size = BigEndian::read_u32(&data[size..]);
+        check_previous_tag_size(size, previous_tag_size)?;
+        data.truncate(size);
+
+        Ok(Some(FlvTag::new(tag_type, timestamp, Bytes::from(data))))
+    }
+
+    /// Gets a reference to the input
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the input.  Reading from or seeking it directly will cause
+    /// tags to be read incorrectly, unless it's left at the start of a tag.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the input, consuming the reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for FlvReader<R> {
+    type Item = Result<FlvTag, FlvReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_tag() {
+            Ok(Some(tag)) => Some(Ok(tag)),
+            Ok(None) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use FlvWriter;
+
+    #[test]
+    fn written_tags_can_be_read_back() {
+        let tags = vec![
+            FlvTag::new(
+                FlvTagType::ScriptData,
+                RtmpTimestamp::new(0),
+                Bytes::from(vec![1]),
+            ),
+            FlvTag::new(
+                FlvTagType::Audio,
+                RtmpTimestamp::new(20),
+                Bytes::from(vec![2, 3]),
+            ),
+            FlvTag::new(
+                FlvTagType::Video,
+                RtmpTimestamp::new(0x0100_0000),
+                Bytes::from(vec![4, 5, 6]),
+            ),
+        ];
+
+        let mut writer = FlvWriter::new(Vec::new(), FlvHeader::new(true, true)).unwrap();
+        for tag in &tags {
+            writer.write_tag(tag).unwrap();
+        }
+
+        let reader = FlvReader::new(Cursor::new(writer.into_inner())).unwrap();
+        assert_eq!(reader.header(), FlvHeader::new(true, true));
+
+        let results = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(results, tags);
+    }
+
+    #[test]
+    fn error_when_signature_is_missing() {
+        let bytes = vec![b'F', b'L', b'X', 1, 5, 0, 0, 0, 9, 0, 0, 0, 0];
+
+        match FlvReader::new(Cursor::new(bytes)) {
+            Err(FlvReadError::InvalidSignature) => (),
+            Err(x) => panic!("Expected invalid signature error, instead got {:?}", x),
+            Ok(_) => panic!("Expected invalid signature error, instead got a reader"),
+        }
+    }
+
+    #[test]
+    fn error_when_previous_tag_size_does_not_match() {
+        let mut bytes = FlvHeader::new(true, true).to_bytes().to_vec();
+        bytes.extend_from_slice(&[8, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0xaf, 0, 0, 0, 99]);
+
+        let mut reader = FlvReader::new(Cursor::new(bytes)).unwrap();
+        match reader.read_tag() {
+            Err(FlvReadError::PreviousTagSizeMismatch {
+                expected: 12,
+                actual: 99,
+            }) => (),
+            x => panic!("Expected previous tag size mismatch, instead got {:?}", x),
+        }
+    }
+}
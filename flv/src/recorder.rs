@@ -0,0 +1,525 @@
+use bytes::Bytes;
+use errors::StreamRecorderError;
+use media::{AudioTagHeader, VideoTagHeader};
+use rml_amf0::{Amf0Value, ObjectProperties};
+use rml_rtmp::pipeline::{MediaItem, MediaSink};
+use rml_rtmp::sessions::{ServerSessionEvent, StreamMetadata};
+use rml_rtmp::time::{RtmpTimestamp, TimestampRebaser};
+use std::io::{Seek, SeekFrom, Write};
+use std::time::Duration;
+use writer::FlvWriter;
+use {FlvHeader, FlvTag, FlvTagType, TAG_HEADER_LENGTH};
+
+const HEADER_FLAGS_OFFSET: u64 = 4;
+
+/// Records a stream being published to a server session into an FLV file.
+///
+/// Events raised by the server session are passed into `handle_event()`, and the metadata,
+/// audio, and video for the recorded stream are written as tags.  Events for other streams
+/// are ignored, so every session's events can be passed in without filtering them first.
+///
+/// Recordings can start in the middle of a stream, so a few things are done to make sure the
+/// file plays from the start:
+///
+/// * Timestamps are rebased so the first frame is at zero, and so the file's timeline stays
+///   continuous if the publisher's timestamps jump
+/// * Video frames are skipped until the first keyframe, as are audio frames if the stream has
+///   video (so the file doesn't start with audio over a blank screen)
+/// * The most recent audio and video sequence headers are written before the first frame
+///
+/// When the publisher finishes (or `finish()` is called) the header's audio and video flags
+/// and the metadata's duration are updated to match what was recorded, which is why the output
+/// must be seekable.
+pub struct StreamRecorder<W: Write + Seek> {
+    writer: FlvWriter<W>,
+    app_name: String,
+    stream_key: String,
+    rebaser: TimestampRebaser,
+    is_started: bool,
+    last_timestamp: RtmpTimestamp,
+    audio_sequence_header: Option<Bytes>,
+    video_sequence_header: Option<Bytes>,
+    expects_video: bool,
+    has_audio: bool,
+    has_video: bool,
+    duration_offset: Option<u64>,
+    bytes_written: u64,
+    is_finished: bool,
+}
+
+impl<W: Write + Seek> StreamRecorder<W> {
+    /// Creates a recorder for the specified stream, immediately writing the FLV header to the
+    /// output
+    pub fn new(
+        writer: W,
+        app_name: String,
+        stream_key: String,
+    ) -> Result<StreamRecorder<W>, StreamRecorderError> {
+        let header = FlvHeader::new(true, true);
+        let bytes_written = header.to_bytes().len() as u64;
+        let writer = FlvWriter::new(writer, header)?;
+
+        Ok(StreamRecorder {
+            writer,
+            app_name,
+            stream_key,
+            rebaser: TimestampRebaser::new(),
+            is_started: false,
+            last_timestamp: RtmpTimestamp::new(0),
+            audio_sequence_header: None,
+            video_sequence_header: None,
+            expects_video: false,
+            has_audio: false,
+            has_video: false,
+            duration_offset: None,
+            bytes_written,
+            is_finished: false,
+        })
+    }
+
+    /// Records any media or metadata contained in the event, finishing the recording if the
+    /// publisher has stopped
+    pub fn handle_event(&mut self, event: &ServerSessionEvent) -> Result<(), StreamRecorderError> {
+        match *event {
+            ServerSessionEvent::StreamMetadataChanged {
+                ref app_name,
+                ref stream_key,
+                ref metadata,
+            } if self.is_recorded_stream(app_name, stream_key) => self.record_metadata(metadata),
+
+            ServerSessionEvent::AudioDataReceived {
+                ref app_name,
+                ref stream_key,
+                ref data,
+                timestamp,
+            } if self.is_recorded_stream(app_name, stream_key) => {
+                self.record_audio(data.clone(), timestamp)
+            }
+
+            ServerSessionEvent::VideoDataReceived {
+                ref app_name,
+                ref stream_key,
+                ref data,
+                timestamp,
+            } if self.is_recorded_stream(app_name, stream_key) => {
+                self.record_video(data.clone(), timestamp)
+            }
+
+            ServerSessionEvent::PublishStreamFinished {
+                ref app_name,
+                ref stream_key,
+            } if self.is_recorded_stream(app_name, stream_key) => self.finish(),
+
+            _ => Ok(()),
+        }
+    }
+
+    /// Updates the header and metadata to match what was recorded and flushes the output.  Any
+    /// events handled afterwards are ignored.
+    pub fn finish(&mut self) -> Result<(), StreamRecorderError> {
+        if self.is_finished {
+            return Ok(());
+        }
+
+        self.is_finished = true;
+
+        let mut flags = 0_u8;
+        if self.has_audio {
+            flags |= 0x04;
+        }
+
+        if self.has_video {
+            flags |= 0x01;
+        }
+
+        let duration = self.last_timestamp.value as f64 / 1000.0;
+        let duration_offset = self.duration_offset;
+        let output = self.writer.get_mut();
+        output.seek(SeekFrom::Start(HEADER_FLAGS_OFFSET))?;
+        output.write_all(&[flags])?;
+
+        if let Some(offset) = duration_offset {
+            output.seek(SeekFrom::Start(offset))?;
+            output.write_all(&duration.to_bits().to_be_bytes())?;
+        }
+
+        output.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    /// True once the publisher has finished or `finish()` has been called
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// The span of the media recorded so far
+    pub fn duration(&self) -> Duration {
+        Duration::from(self.last_timestamp)
+    }
+
+    /// The number of bytes written to the output so far
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Returns the output, consuming the recorder.  This does not finish the recording.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+
+    fn is_recorded_stream(&self, app_name: &str, stream_key: &str) -> bool {
+        !self.is_finished && self.app_name == app_name && self.stream_key == stream_key
+    }
+
+    fn record_metadata(&mut self, metadata: &StreamMetadata) -> Result<(), StreamRecorderError> {
+        if metadata.video_codec.is_some() || metadata.video_width.is_some() {
+            self.expects_video = true;
+        }
+
+        let values = vec![
+            Amf0Value::Utf8String("onMetaData".to_string()),
+            Amf0Value::Object(create_metadata_properties(metadata)),
+        ];
+
+        let data = rml_amf0::serialize(&values)?;
+
+        // Only the first metadata tag's duration is updated, since that's the one players read
+        if self.duration_offset.is_none() {
+            let start = self.writer.get_mut().stream_position()?;
+            self.duration_offset = find_duration_value(&data)
+                .map(|index| start + TAG_HEADER_LENGTH as u64 + index as u64);
+        }
+
+        let timestamp = self.last_timestamp;
+        self.write(FlvTagType::ScriptData, timestamp, Bytes::from(data))
+    }
+
+    fn record_audio(
+        &mut self,
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    ) -> Result<(), StreamRecorderError> {
+        let header = match AudioTagHeader::parse(&data) {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+
+        if header.is_sequence_header() {
+            self.audio_sequence_header = Some(data.clone());
+            if !self.is_started {
+                return Ok(());
+            }
+
+            return self.write_media(FlvTagType::Audio, timestamp, data);
+        }
+
+        if !self.is_started {
+            if self.expects_video {
+                return Ok(());
+            }
+
+            self.start()?;
+        }
+
+        self.write_media(FlvTagType::Audio, timestamp, data)
+    }
+
+    fn record_video(
+        &mut self,
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    ) -> Result<(), StreamRecorderError> {
+        let header = match VideoTagHeader::parse(&data) {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+
+        self.expects_video = true;
+        if header.is_sequence_header() {
+            self.video_sequence_header = Some(data.clone());
+            if !self.is_started {
+                return Ok(());
+            }
+
+            return self.write_media(FlvTagType::Video, timestamp, data);
+        }
+
+        if !self.is_started {
+            if !header.is_keyframe() {
+                return Ok(());
+            }
+
+            self.start()?;
+        }
+
+        self.write_media(FlvTagType::Video, timestamp, data)
+    }
+
+    fn start(&mut self) -> Result<(), StreamRecorderError> {
+        self.is_started = true;
+
+        let start = RtmpTimestamp::new(0);
+        if let Some(data) = self.video_sequence_header.clone() {
+            self.has_video = true;
+            self.write(FlvTagType::Video, start, data)?;
+        }
+
+        if let Some(data) = self.audio_sequence_header.clone() {
+            self.has_audio = true;
+            self.write(FlvTagType::Audio, start, data)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_media(
+        &mut self,
+        tag_type: FlvTagType,
+        timestamp: RtmpTimestamp,
+        data: Bytes,
+    ) -> Result<(), StreamRecorderError> {
+        let timestamp = self.rebaser.rebase(timestamp);
+        if timestamp > self.last_timestamp {
+            self.last_timestamp = timestamp;
+        }
+
+        match tag_type {
+            FlvTagType::Audio => self.has_audio = true,
+            FlvTagType::Video => self.has_video = true,
+            FlvTagType::ScriptData => (),
+        }
+
+        self.write(tag_type, timestamp, data)
+    }
+
+    fn write(
+        &mut self,
+        tag_type: FlvTagType,
+        timestamp: RtmpTimestamp,
+        data: Bytes,
+    ) -> Result<(), StreamRecorderError> {
+        let tag = FlvTag::new(tag_type, timestamp, data);
+        self.writer.write_tag(&tag)?;
+        self.bytes_written += (TAG_HEADER_LENGTH + tag.data.len() + 4) as u64;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> MediaSink for StreamRecorder<W> {
+    type Error = StreamRecorderError;
+
+    /// Records the item, finishing the recording at the end of the stream.  Items sent after
+    /// the recording has finished are ignored.
+    fn send_media(&mut self, item: MediaItem) -> Result<(), Self::Error> {
+        if self.is_finished {
+            return Ok(());
+        }
+
+        match item {
+            MediaItem::Metadata(metadata) => self.record_metadata(&metadata),
+            MediaItem::Video { data, timestamp } => self.record_video(data, timestamp),
+            MediaItem::Audio { data, timestamp } => self.record_audio(data, timestamp),
+            MediaItem::EndOfStream => self.finish(),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+pub(crate) fn create_metadata_properties(metadata: &StreamMetadata) -> ObjectProperties {
+    let mut properties = ObjectProperties::new();
+    properties.insert("duration".to_string(), Amf0Value::Number(0.0));
+
+    if let Some(x) = metadata.video_width {
+        properties.insert("width".to_string(), Amf0Value::Number(x as f64));
+    }
+
+    if let Some(x) = metadata.video_height {
+        properties.insert("height".to_string(), Amf0Value::Number(x as f64));
+    }
+
+    if let Some(ref x) = metadata.video_codec {
+        properties.insert("videocodecid".to_string(), Amf0Value::Utf8String(x.clone()));
+    }
+
+    if let Some(x) = metadata.video_bitrate_kbps {
+        properties.insert("videodatarate".to_string(), Amf0Value::Number(x as f64));
+    }
+
+    if let Some(x) = metadata.video_frame_rate {
+        properties.insert("framerate".to_string(), Amf0Value::Number(x as f64));
+    }
+
+    if let Some(ref x) = metadata.audio_codec {
+        properties.insert("audiocodecid".to_string(), Amf0Value::Utf8String(x.clone()));
+    }
+
+    if let Some(x) = metadata.audio_bitrate_kbps {
+        properties.insert("audiodatarate".to_string(), Amf0Value::Number(x as f64));
+    }
+
+    if let Some(x) = metadata.audio_sample_rate {
+        properties.insert("audiosamplerate".to_string(), Amf0Value::Number(x as f64));
+    }
+
+    if let Some(x) = metadata.audio_channels {
+        properties.insert("audiochannels".to_string(), Amf0Value::Number(x as f64));
+    }
+
+    if let Some(x) = metadata.audio_is_stereo {
+        properties.insert("stereo".to_string(), Amf0Value::Boolean(x));
+    }
+
+    if let Some(ref x) = metadata.encoder {
+        properties.insert("encoder".to_string(), Amf0Value::Utf8String(x.clone()));
+    }
+
+    properties
+}
+
+/// Finds where the duration's number is in serialized metadata, by looking for the property
+/// name followed by a number marker
+fn find_duration_value(data: &[u8]) -> Option<usize> {
+    let pattern = b"\x00\x08duration\x00";
+    data.windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|index| index + pattern.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reader::FlvReader;
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use test_support::{audio, finished, video, APP, KEY};
+
+    fn record(events: Vec<ServerSessionEvent>) -> (FlvHeader, Vec<FlvTag>) {
+        let output = Cursor::new(Vec::new());
+        let mut recorder = StreamRecorder::new(output, APP.to_string(), KEY.to_string()).unwrap();
+        for event in &events {
+            recorder.handle_event(event).unwrap();
+        }
+
+        assert!(recorder.is_finished());
+
+        let mut output = recorder.into_inner();
+        output.set_position(0);
+        let reader = FlvReader::new(output).unwrap();
+        let header = reader.header();
+        let tags = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        (header, tags)
+    }
+
+    #[test]
+    fn recording_starts_at_first_keyframe_with_sequence_headers_and_rebased_timestamps() {
+        let (header, tags) = record(vec![
+            video(vec![0x17, 0, 0, 0, 0, 9], 0),
+            audio(vec![0xaf, 0, 8], 0),
+            video(vec![0x27, 1, 0, 0, 0, 1], 5000),
+            audio(vec![0xaf, 1, 2], 5010),
+            video(vec![0x17, 1, 0, 0, 0, 3], 5020),
+            audio(vec![0xaf, 1, 4], 5030),
+            finished(),
+        ]);
+
+        let summary = tags
+            .iter()
+            .map(|tag| (tag.tag_type, tag.timestamp.value, tag.data[1]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(header, FlvHeader::new(true, true));
+        assert_eq!(
+            summary,
+            vec![
+                (FlvTagType::Video, 0, 0),
+                (FlvTagType::Audio, 0, 0),
+                (FlvTagType::Video, 0, 1),
+                (FlvTagType::Audio, 10, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn metadata_duration_and_header_flags_are_updated_when_finished() {
+        let mut metadata = StreamMetadata::new();
+        metadata.audio_codec = Some("mp4a".to_string());
+
+        let (header, tags) = record(vec![
+            ServerSessionEvent::StreamMetadataChanged {
+                app_name: APP.to_string(),
+                stream_key: KEY.to_string(),
+                metadata: Arc::new(metadata),
+            },
+            audio(vec![0x2f, 0xff], 1000),
+            audio(vec![0x2f, 0xff], 3500),
+            finished(),
+        ]);
+
+        assert_eq!(header, FlvHeader::new(true, false));
+
+        let values = match tags[0].to_rtmp_message().unwrap() {
+            rml_rtmp::messages::RtmpMessage::Amf0Data { values } => values,
+            x => panic!("Expected Amf0 data, instead got {:?}", x),
+        };
+
+        match values[1] {
+            Amf0Value::Object(ref properties) => {
+                assert_eq!(properties.get("duration"), Some(&Amf0Value::Number(2.5)));
+            }
+
+            ref x => panic!("Expected metadata object, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn events_for_other_streams_are_ignored() {
+        let (_, tags) = record(vec![
+            ServerSessionEvent::AudioDataReceived {
+                app_name: APP.to_string(),
+                stream_key: "other".to_string(),
+                data: Bytes::from(vec![0x2f, 0xff]),
+                timestamp: RtmpTimestamp::new(0),
+            },
+            ServerSessionEvent::PublishStreamFinished {
+                app_name: APP.to_string(),
+                stream_key: "other".to_string(),
+            },
+            finished(),
+        ]);
+
+        assert_eq!(tags, Vec::new());
+    }
+
+    #[test]
+    fn media_items_are_recorded_until_end_of_stream() {
+        let output = Cursor::new(Vec::new());
+        let mut recorder = StreamRecorder::new(output, APP.to_string(), KEY.to_string()).unwrap();
+        let events = vec![
+            video(vec![0x17, 0, 0, 0, 0, 9], 0),
+            video(vec![0x17, 1, 0, 0, 0, 3], 40),
+            finished(),
+            video(vec![0x27, 1, 0, 0, 0, 4], 80),
+        ];
+
+        for event in &events {
+            let item = MediaItem::from_server_event(event).unwrap();
+            recorder.send_media(item).unwrap();
+        }
+
+        assert!(recorder.is_finished());
+
+        let mut output = recorder.into_inner();
+        output.set_position(0);
+        let tags = FlvReader::new(output)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(tags.len(), 2);
+    }
+}
@@ -0,0 +1,375 @@
+use bytes::Bytes;
+use errors::StreamRecorderError;
+use media::{AudioTagHeader, VideoTagHeader};
+use recorder::StreamRecorder;
+use rml_rtmp::sessions::{ServerSessionEvent, StreamMetadata};
+use rml_rtmp::time::RtmpTimestamp;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+const PARTIAL_EXTENSION: &str = "partial";
+
+/// Configuration options for a `SegmentedRecorder`
+#[derive(Debug, Clone)]
+pub struct SegmentedRecordingConfig {
+    /// The directory segments are written to
+    pub directory: PathBuf,
+
+    /// The start of each segment's file name, which is followed by the segment's index
+    pub file_prefix: String,
+
+    /// Segments are rotated on the first keyframe after they reach this duration
+    pub max_segment_duration: Option<Duration>,
+
+    /// Segments are rotated on the first keyframe after they reach this many bytes
+    pub max_segment_bytes: Option<u64>,
+}
+
+impl SegmentedRecordingConfig {
+    /// Creates a configuration that rotates segments every hour, with no size limit
+    pub fn new(directory: PathBuf, file_prefix: String) -> SegmentedRecordingConfig {
+        SegmentedRecordingConfig {
+            directory,
+            file_prefix,
+            max_segment_duration: Some(Duration::from_secs(60 * 60)),
+            max_segment_bytes: None,
+        }
+    }
+}
+
+/// A segment that has been completed and moved to its final path
+#[derive(PartialEq, Debug, Clone)]
+pub struct RecordedSegment {
+    pub path: PathBuf,
+
+    /// The position of the segment in the recording, starting at 1
+    pub index: u32,
+
+    pub duration: Duration,
+    pub bytes: u64,
+}
+
+struct CurrentSegment {
+    recorder: StreamRecorder<BufWriter<File>>,
+    index: u32,
+    partial_path: PathBuf,
+    final_path: PathBuf,
+}
+
+/// Records a stream being published to a server session into a series of FLV files, starting a
+/// new file whenever the current one reaches a maximum duration or size.
+///
+/// Each segment is recorded with a `StreamRecorder`, and is a complete file that plays on its
+/// own: segments are only rotated on keyframes (or any audio frame for streams without video),
+/// and each one starts with the stream's metadata and sequence headers.
+///
+/// Segments are written to `<prefix>-<index>.flv.partial` and renamed to `<prefix>-<index>.flv`
+/// once they are finalized, so anything watching the directory never sees an incomplete
+/// segment under its final name.  A `RecordedSegment` is returned each time a segment is
+/// finalized, so it can be uploaded or indexed.
+pub struct SegmentedRecorder {
+    config: SegmentedRecordingConfig,
+    app_name: String,
+    stream_key: String,
+    current: Option<CurrentSegment>,
+    next_index: u32,
+    metadata: Option<Arc<StreamMetadata>>,
+    video_sequence_header: Option<(Bytes, RtmpTimestamp)>,
+    audio_sequence_header: Option<(Bytes, RtmpTimestamp)>,
+    has_video: bool,
+    is_finished: bool,
+}
+
+impl SegmentedRecorder {
+    /// Creates a recorder for the specified stream, creating the output directory if needed.
+    /// The first segment is created once the first event for the stream is handled.
+    pub fn new(
+        config: SegmentedRecordingConfig,
+        app_name: String,
+        stream_key: String,
+    ) -> Result<SegmentedRecorder, StreamRecorderError> {
+        fs::create_dir_all(&config.directory)?;
+
+        Ok(SegmentedRecorder {
+            config,
+            app_name,
+            stream_key,
+            current: None,
+            next_index: 1,
+            metadata: None,
+            video_sequence_header: None,
+            audio_sequence_header: None,
+            has_video: false,
+            is_finished: false,
+        })
+    }
+
+    /// Records any media or metadata contained in the event.  If this completes a segment,
+    /// either by rotating to a new one or because the publisher has stopped, it's returned.
+    pub fn handle_event(
+        &mut self,
+        event: &ServerSessionEvent,
+    ) -> Result<Option<RecordedSegment>, StreamRecorderError> {
+        let mut completed = None;
+        match *event {
+            ServerSessionEvent::StreamMetadataChanged {
+                ref app_name,
+                ref stream_key,
+                ref metadata,
+            } if self.is_recorded_stream(app_name, stream_key) => {
+                self.metadata = Some(metadata.clone());
+            }
+
+            ServerSessionEvent::VideoDataReceived {
+                ref app_name,
+                ref stream_key,
+                ref data,
+                timestamp,
+            } if self.is_recorded_stream(app_name, stream_key) => {
+                self.has_video = true;
+                match VideoTagHeader::parse(data) {
+                    Some(header) if header.is_sequence_header() => {
+                        self.video_sequence_header = Some((data.clone(), timestamp));
+                    }
+
+                    Some(header) if header.is_keyframe() && self.should_rotate() => {
+                        completed = self.finish_segment()?;
+                    }
+
+                    _ => (),
+                }
+            }
+
+            ServerSessionEvent::AudioDataReceived {
+                ref app_name,
+                ref stream_key,
+                ref data,
+                timestamp,
+            } if self.is_recorded_stream(app_name, stream_key) => {
+                match AudioTagHeader::parse(data) {
+                    Some(header) if header.is_sequence_header() => {
+                        self.audio_sequence_header = Some((data.clone(), timestamp));
+                    }
+
+                    Some(_) if !self.has_video && self.should_rotate() => {
+                        completed = self.finish_segment()?;
+                    }
+
+                    _ => (),
+                }
+            }
+
+            ServerSessionEvent::PublishStreamFinished {
+                ref app_name,
+                ref stream_key,
+            } if self.is_recorded_stream(app_name, stream_key) => return self.finish(),
+
+            _ => return Ok(None),
+        }
+
+        if self.current.is_none() {
+            self.start_segment()?;
+        }
+
+        if let Some(ref mut current) = self.current {
+            current.recorder.handle_event(event)?;
+        }
+
+        Ok(completed)
+    }
+
+    /// Finalizes the current segment.  Any events handled afterwards are ignored.
+    pub fn finish(&mut self) -> Result<Option<RecordedSegment>, StreamRecorderError> {
+        self.is_finished = true;
+        self.finish_segment()
+    }
+
+    /// True once the publisher has finished or `finish()` has been called
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    fn is_recorded_stream(&self, app_name: &str, stream_key: &str) -> bool {
+        !self.is_finished && self.app_name == app_name && self.stream_key == stream_key
+    }
+
+    fn should_rotate(&self) -> bool {
+        let recorder = match self.current {
+            Some(ref current) => &current.recorder,
+            None => return false,
+        };
+
+        let too_long = self
+            .config
+            .max_segment_duration
+            .is_some_and(|max| recorder.duration() >= max);
+
+        let too_large = self
+            .config
+            .max_segment_bytes
+            .is_some_and(|max| recorder.bytes_written() >= max);
+
+        too_long || too_large
+    }
+
+    fn start_segment(&mut self) -> Result<(), StreamRecorderError> {
+        let index = self.next_index;
+        self.next_index += 1;
+
+        let file_name = format!("{}-{:05}.flv", self.config.file_prefix, index);
+        let final_path = self.config.directory.join(&file_name);
+        let partial_path = final_path.with_extension(format!("flv.{}", PARTIAL_EXTENSION));
+        let output = BufWriter::new(File::create(&partial_path)?);
+        let mut recorder =
+            StreamRecorder::new(output, self.app_name.clone(), self.stream_key.clone())?;
+
+        // Each segment needs the metadata and sequence headers to be playable on its own
+        if let Some(ref metadata) = self.metadata {
+            recorder.handle_event(&ServerSessionEvent::StreamMetadataChanged {
+                app_name: self.app_name.clone(),
+                stream_key: self.stream_key.clone(),
+                metadata: metadata.clone(),
+            })?;
+        }
+
+        if let Some((ref data, timestamp)) = self.video_sequence_header {
+            recorder.handle_event(&ServerSessionEvent::VideoDataReceived {
+                app_name: self.app_name.clone(),
+                stream_key: self.stream_key.clone(),
+                data: data.clone(),
+                timestamp,
+            })?;
+        }
+
+        if let Some((ref data, timestamp)) = self.audio_sequence_header {
+            recorder.handle_event(&ServerSessionEvent::AudioDataReceived {
+                app_name: self.app_name.clone(),
+                stream_key: self.stream_key.clone(),
+                data: data.clone(),
+                timestamp,
+            })?;
+        }
+
+        self.current = Some(CurrentSegment {
+            recorder,
+            index,
+            partial_path,
+            final_path,
+        });
+
+        Ok(())
+    }
+
+    fn finish_segment(&mut self) -> Result<Option<RecordedSegment>, StreamRecorderError> {
+        let mut current = match self.current.take() {
+            Some(current) => current,
+            None => return Ok(None),
+        };
+
+        current.recorder.finish()?;
+        let duration = current.recorder.duration();
+        let bytes = current.recorder.bytes_written();
+
+        // Close the file before it's renamed, since that fails on some platforms while open
+        drop(current.recorder);
+        fs::rename(&current.partial_path, &current.final_path)?;
+
+        Ok(Some(RecordedSegment {
+            path: current.final_path,
+            index: current.index,
+            duration,
+            bytes,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reader::FlvReader;
+    use std::process;
+    use test_support::{video, APP, KEY};
+
+    fn output_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("rml_flv_{}_{}", name, process::id()));
+
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn segments_rotate_on_keyframe_after_max_duration() {
+        let directory = output_directory("rotation");
+        let mut config = SegmentedRecordingConfig::new(directory.clone(), "stream".to_string());
+        config.max_segment_duration = Some(Duration::from_secs(2));
+        let mut recorder =
+            SegmentedRecorder::new(config, APP.to_string(), KEY.to_string()).unwrap();
+
+        let events = vec![
+            video(vec![0x17, 0, 0, 0, 0, 9], 0),
+            video(vec![0x17, 1, 0, 0, 0, 1], 0),
+            video(vec![0x27, 1, 0, 0, 0, 2], 1000),
+            video(vec![0x27, 1, 0, 0, 0, 3], 2500),
+            video(vec![0x17, 1, 0, 0, 0, 4], 3000),
+            video(vec![0x27, 1, 0, 0, 0, 5], 3500),
+        ];
+
+        let mut completed = Vec::new();
+        for event in &events {
+            completed.extend(recorder.handle_event(event).unwrap());
+        }
+
+        completed.extend(recorder.finish().unwrap());
+
+        assert_eq!(completed.len(), 2);
+        assert_eq!(completed[0].index, 1);
+        assert_eq!(completed[0].duration, Duration::from_millis(2500));
+        assert_eq!(completed[1].duration, Duration::from_millis(500));
+        assert!(!directory.join("stream-00002.flv.partial").exists());
+
+        // The second segment starts with the sequence header, followed by the keyframe
+        let file = File::open(&completed[1].path).unwrap();
+        assert_eq!(file.metadata().unwrap().len(), completed[1].bytes);
+
+        let tags = FlvReader::new(file)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let payloads = tags.iter().map(|tag| tag.data[5]).collect::<Vec<_>>();
+        assert_eq!(payloads, vec![9, 4, 5]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn segment_is_partial_until_finalized() {
+        let directory = output_directory("partial");
+        let config = SegmentedRecordingConfig::new(directory.clone(), "stream".to_string());
+        let mut recorder =
+            SegmentedRecorder::new(config, APP.to_string(), KEY.to_string()).unwrap();
+
+        recorder
+            .handle_event(&video(vec![0x17, 1, 0, 0, 0, 1], 0))
+            .unwrap();
+
+        assert!(directory.join("stream-00001.flv.partial").exists());
+        assert!(!directory.join("stream-00001.flv").exists());
+
+        let finished = ServerSessionEvent::PublishStreamFinished {
+            app_name: APP.to_string(),
+            stream_key: KEY.to_string(),
+        };
+
+        let segment = recorder.handle_event(&finished).unwrap().unwrap();
+
+        assert_eq!(segment.path, directory.join("stream-00001.flv"));
+        assert!(segment.path.exists());
+        assert!(recorder.is_finished());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
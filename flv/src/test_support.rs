@@ -0,0 +1,33 @@
+//! Server session events shared by the tests of the components that consume them
+
+use bytes::Bytes;
+use rml_rtmp::sessions::ServerSessionEvent;
+use rml_rtmp::time::RtmpTimestamp;
+
+pub const APP: &str = "live";
+pub const KEY: &str = "key";
+
+pub fn audio(data: Vec<u8>, timestamp: u32) -> ServerSessionEvent {
+    ServerSessionEvent::AudioDataReceived {
+        app_name: APP.to_string(),
+        stream_key: KEY.to_string(),
+        data: Bytes::from(data),
+        timestamp: RtmpTimestamp::new(timestamp),
+    }
+}
+
+pub fn video(data: Vec<u8>, timestamp: u32) -> ServerSessionEvent {
+    ServerSessionEvent::VideoDataReceived {
+        app_name: APP.to_string(),
+        stream_key: KEY.to_string(),
+        data: Bytes::from(data),
+        timestamp: RtmpTimestamp::new(timestamp),
+    }
+}
+
+pub fn finished() -> ServerSessionEvent {
+    ServerSessionEvent::PublishStreamFinished {
+        app_name: APP.to_string(),
+        stream_key: KEY.to_string(),
+    }
+}
@@ -0,0 +1,439 @@
+use bytes::Bytes;
+use errors::VodSourceError;
+use media::{AudioTagHeader, VideoTagHeader};
+use reader::FlvReader;
+use rml_amf0::Amf0Value;
+use rml_rtmp::chunk_io::Packet;
+use rml_rtmp::messages::RtmpMessage;
+use rml_rtmp::pipeline::{MediaItem, MediaSource};
+use rml_rtmp::sessions::{ServerSession, StreamMetadata};
+use rml_rtmp::time::RtmpTimestamp;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use {FlvTag, FlvTagType};
+
+// Seek points for files without video, since there are no keyframes to seek to
+const AUDIO_SEEK_POINT_INTERVAL_MS: u32 = 1000;
+
+/// Plays an FLV file to a server session, as if it were being published live.
+///
+/// When the source is opened the whole file is scanned once, to read its metadata and sequence
+/// headers and to build an index of the places it can be seeked to (keyframes, or once a second
+/// for files without video).  Tags are then returned by `poll()` (or converted into packets for
+/// a play stream by `poll_packets()`) as their timestamps come due, so players receive the file
+/// at the rate it should be played rather than all at once.  Tags are returned up to the read
+/// ahead duration early, so players can keep a buffer filled.
+///
+/// Seeking moves to the last seek point at or before the requested time.  Timestamps are left
+/// as they are in the file, so players show the correct position after seeking, and the
+/// sequence headers are sent again before the first tag after a seek.
+///
+/// # Examples
+/// ```no_run
+/// # extern crate rml_flv;
+/// # extern crate rml_rtmp;
+/// use rml_flv::VodSource;
+/// use rml_rtmp::time::RtmpTimestamp;
+/// use std::fs::File;
+/// use std::time::Instant;
+///
+/// # fn main() {
+/// let mut source = VodSource::open(File::open("movie.flv").unwrap()).unwrap();
+/// source.seek(RtmpTimestamp::new(60_000)).unwrap();
+///
+/// while !source.is_finished() {
+///     for tag in source.poll(Instant::now()).unwrap() {
+///         // Send the tag to the player
+///     }
+///
+///     std::thread::sleep(std::time::Duration::from_millis(10));
+/// }
+/// # }
+/// ```
+pub struct VodSource<R: Read + Seek> {
+    reader: FlvReader<R>,
+    metadata: Option<StreamMetadata>,
+    duration: Duration,
+    seek_points: Vec<(RtmpTimestamp, u64)>,
+    video_sequence_header: Option<Bytes>,
+    audio_sequence_header: Option<Bytes>,
+    read_ahead: Duration,
+    clock: Option<(Instant, RtmpTimestamp)>,
+    next_tag: Option<FlvTag>,
+    send_sequence_headers: bool,
+    is_finished: bool,
+}
+
+impl<R: Read + Seek> VodSource<R> {
+    /// Opens a file, scanning it to build the seek index
+    pub fn open(input: R) -> Result<VodSource<R>, VodSourceError> {
+        let mut reader = FlvReader::new(input)?;
+        let first_tag_offset = reader.get_mut().stream_position()?;
+
+        let mut metadata = None;
+        let mut video_sequence_header = None;
+        let mut audio_sequence_header = None;
+        let mut keyframes = Vec::new();
+        let mut audio_points: Vec<(RtmpTimestamp, u64)> = Vec::new();
+        let mut last_timestamp = RtmpTimestamp::new(0);
+        loop {
+            let offset = reader.get_mut().stream_position()?;
+            let tag = match reader.read_tag()? {
+                Some(tag) => tag,
+                None => break,
+            };
+
+            if tag.timestamp > last_timestamp {
+                last_timestamp = tag.timestamp;
+            }
+
+            match tag.tag_type {
+                FlvTagType::ScriptData if metadata.is_none() => metadata = parse_metadata(&tag)?,
+                FlvTagType::ScriptData => (),
+                FlvTagType::Video => match VideoTagHeader::parse(&tag.data) {
+                    Some(header) if header.is_sequence_header() => {
+                        video_sequence_header.get_or_insert(tag.data);
+                    }
+
+                    Some(header) if header.is_keyframe() => keyframes.push((tag.timestamp, offset)),
+                    _ => (),
+                },
+
+                FlvTagType::Audio => match AudioTagHeader::parse(&tag.data) {
+                    Some(header) if header.is_sequence_header() => {
+                        audio_sequence_header.get_or_insert(tag.data);
+                    }
+
+                    _ => {
+                        let is_due = match audio_points.last() {
+                            Some(&(previous, _)) => {
+                                tag.timestamp >= previous + AUDIO_SEEK_POINT_INTERVAL_MS
+                            }
+                            None => true,
+                        };
+
+                        if is_due {
+                            audio_points.push((tag.timestamp, offset));
+                        }
+                    }
+                },
+            }
+        }
+
+        reader.get_mut().seek(SeekFrom::Start(first_tag_offset))?;
+
+        Ok(VodSource {
+            reader,
+            metadata,
+            duration: Duration::from(last_timestamp),
+            seek_points: if keyframes.is_empty() {
+                audio_points
+            } else {
+                keyframes
+            },
+            video_sequence_header,
+            audio_sequence_header,
+            read_ahead: Duration::from_secs(1),
+            clock: None,
+            next_tag: None,
+            send_sequence_headers: false,
+            is_finished: false,
+        })
+    }
+
+    /// Sets how far ahead of their timestamps tags are returned.  Defaults to one second.
+    pub fn set_read_ahead(&mut self, read_ahead: Duration) {
+        self.read_ahead = read_ahead;
+    }
+
+    /// The metadata in the file's first `onMetaData` script data tag, if it has one
+    pub fn metadata(&self) -> Option<&StreamMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// The timestamp of the last tag in the file
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// True once every tag in the file has been returned
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// Moves playback to the last seek point at or before the timestamp, returning the
+    /// timestamp playback will resume from
+    pub fn seek(&mut self, timestamp: RtmpTimestamp) -> Result<RtmpTimestamp, VodSourceError> {
+        let index = self
+            .seek_points
+            .iter()
+            .rposition(|&(point, _)| point <= timestamp)
+            .unwrap_or(0);
+
+        let (point, offset) = match self.seek_points.get(index) {
+            Some(&point) => point,
+            None => return Ok(RtmpTimestamp::new(0)),
+        };
+
+        self.reader.get_mut().seek(SeekFrom::Start(offset))?;
+        self.next_tag = None;
+        self.clock = None;
+        self.send_sequence_headers = true;
+        self.is_finished = false;
+        Ok(point)
+    }
+
+    /// Returns the tags that are due to be sent to the player.  Playback starts from the first
+    /// call after the source is opened or seeked.
+    pub fn poll(&mut self, now: Instant) -> Result<Vec<FlvTag>, VodSourceError> {
+        let mut tags = Vec::new();
+        loop {
+            if self.next_tag.is_none() {
+                self.next_tag = self.reader.read_tag()?;
+            }
+
+            let timestamp = match self.next_tag {
+                Some(ref tag) => tag.timestamp,
+                None => {
+                    self.is_finished = true;
+                    break;
+                }
+            };
+
+            let (started_at, start_timestamp) = *self.clock.get_or_insert((now, timestamp));
+            if self.send_sequence_headers {
+                self.send_sequence_headers = false;
+                if let Some(ref data) = self.video_sequence_header {
+                    tags.push(FlvTag::new(FlvTagType::Video, timestamp, data.clone()));
+                }
+
+                if let Some(ref data) = self.audio_sequence_header {
+                    tags.push(FlvTag::new(FlvTagType::Audio, timestamp, data.clone()));
+                }
+            }
+
+            let elapsed = now.duration_since(started_at) + self.read_ahead;
+            let offset = if timestamp > start_timestamp {
+                (timestamp - start_timestamp).value as u64
+            } else {
+                0
+            };
+
+            if Duration::from_millis(offset) > elapsed {
+                break;
+            }
+
+            tags.extend(self.next_tag.take());
+        }
+
+        Ok(tags)
+    }
+
+    /// Returns the packets for the tags that are due to be sent on a server session's play
+    /// stream.  Script data other than `onMetaData` is skipped.
+    pub fn poll_packets(
+        &mut self,
+        session: &mut ServerSession,
+        stream_id: u32,
+        now: Instant,
+    ) -> Result<Vec<Packet>, VodSourceError> {
+        let mut packets = Vec::new();
+        for tag in self.poll(now)? {
+            let packet = match tag.tag_type {
+                FlvTagType::Video => {
+                    // Only interframes can be dropped without breaking decoding
+                    let can_be_dropped = VideoTagHeader::parse(&tag.data)
+                        .is_some_and(|x| !x.is_keyframe() && !x.is_sequence_header());
+
+                    session.send_video_data(stream_id, tag.data, tag.timestamp, can_be_dropped)?
+                }
+
+                FlvTagType::Audio => {
+                    session.send_audio_data(stream_id, tag.data, tag.timestamp, false)?
+                }
+
+                FlvTagType::ScriptData => match parse_metadata(&tag)? {
+                    Some(metadata) => session.send_metadata(stream_id, &metadata)?,
+                    None => continue,
+                },
+            };
+
+            packets.push(packet);
+        }
+
+        Ok(packets)
+    }
+}
+
+impl<R: Read + Seek> MediaSource for VodSource<R> {
+    type Error = VodSourceError;
+
+    /// Returns the tags that are due as media items, the same as `poll()`.  Script data other
+    /// than `onMetaData` is skipped, and the end of the stream follows the last tag.
+    fn poll_media(&mut self, now: Instant) -> Result<Vec<MediaItem>, Self::Error> {
+        let was_finished = self.is_finished;
+        let mut items = Vec::new();
+        for tag in self.poll(now)? {
+            match tag.tag_type {
+                FlvTagType::Video => items.push(MediaItem::Video {
+                    data: tag.data,
+                    timestamp: tag.timestamp,
+                }),
+
+                FlvTagType::Audio => items.push(MediaItem::Audio {
+                    data: tag.data,
+                    timestamp: tag.timestamp,
+                }),
+
+                FlvTagType::ScriptData => {
+                    if let Some(metadata) = parse_metadata(&tag)? {
+                        items.push(MediaItem::Metadata(Arc::new(metadata)));
+                    }
+                }
+            }
+        }
+
+        if self.is_finished && !was_finished {
+            items.push(MediaItem::EndOfStream);
+        }
+
+        Ok(items)
+    }
+}
+
+/// Reads the metadata from a script data tag, if it's an `onMetaData` tag
+fn parse_metadata(tag: &FlvTag) -> Result<Option<StreamMetadata>, VodSourceError> {
+    let values = match tag.to_rtmp_message()? {
+        RtmpMessage::Amf0Data { values } => values,
+        _ => return Ok(None),
+    };
+
+    match (values.first().and_then(|x| x.as_str()), values.get(1)) {
+        (Some("onMetaData"), Some(Amf0Value::Object(properties))) => {
+            let mut metadata = StreamMetadata::new();
+            metadata.apply_metadata_values(properties.clone());
+            Ok(Some(metadata))
+        }
+
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rml_amf0::ObjectProperties;
+    use std::io::Cursor;
+    use writer::FlvWriter;
+    use FlvHeader;
+
+    fn video(timestamp: u32, data: Vec<u8>) -> FlvTag {
+        FlvTag::new(
+            FlvTagType::Video,
+            RtmpTimestamp::new(timestamp),
+            Bytes::from(data),
+        )
+    }
+
+    fn file() -> Cursor<Vec<u8>> {
+        let mut properties = ObjectProperties::new();
+        properties.insert("width".to_string(), Amf0Value::Number(1280.0));
+        let values = vec![
+            Amf0Value::Utf8String("onMetaData".to_string()),
+            Amf0Value::Object(properties),
+        ];
+
+        let script_data = Bytes::from(rml_amf0::serialize(&values).unwrap());
+        let tags = vec![
+            FlvTag::new(FlvTagType::ScriptData, RtmpTimestamp::new(0), script_data),
+            video(0, vec![0x17, 0, 0, 0, 0]),
+            video(0, vec![0x17, 1, 0, 0, 0]),
+            video(1000, vec![0x27, 1, 0, 0, 0]),
+            video(2000, vec![0x17, 1, 0, 0, 0]),
+            video(3000, vec![0x27, 1, 0, 0, 0]),
+        ];
+
+        let mut writer = FlvWriter::new(Vec::new(), FlvHeader::new(false, true)).unwrap();
+        for tag in &tags {
+            writer.write_tag(tag).unwrap();
+        }
+
+        Cursor::new(writer.into_inner())
+    }
+
+    fn timestamps(tags: &[FlvTag]) -> Vec<(u32, u8)> {
+        tags.iter()
+            .map(|tag| (tag.timestamp.value, tag.data[0]))
+            .collect()
+    }
+
+    #[test]
+    fn metadata_and_duration_are_read_when_opened() {
+        let source = VodSource::open(file()).unwrap();
+
+        assert_eq!(source.metadata().unwrap().video_width, Some(1280));
+        assert_eq!(source.duration(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn tags_are_returned_as_they_come_due() {
+        let start = Instant::now();
+        let mut source = VodSource::open(file()).unwrap();
+        source.set_read_ahead(Duration::from_millis(0));
+
+        assert_eq!(source.poll(start).unwrap().len(), 3);
+        assert!(source
+            .poll(start + Duration::from_millis(999))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            timestamps(&source.poll(start + Duration::from_millis(2000)).unwrap()),
+            vec![(1000, 0x27), (2000, 0x17)]
+        );
+
+        source.poll(start + Duration::from_millis(3000)).unwrap();
+        source.poll(start + Duration::from_millis(3000)).unwrap();
+        assert!(source.is_finished());
+    }
+
+    #[test]
+    fn seeking_resumes_from_previous_keyframe_with_sequence_header() {
+        let start = Instant::now();
+        let mut source = VodSource::open(file()).unwrap();
+        source.set_read_ahead(Duration::from_millis(0));
+
+        assert_eq!(source.seek(RtmpTimestamp::new(2500)).unwrap(), 2000);
+
+        let tags = source.poll(start).unwrap();
+        assert_eq!(timestamps(&tags), vec![(2000, 0x17), (2000, 0x17)]);
+        assert_eq!(tags[0].data[1], 0);
+        assert_eq!(
+            timestamps(&source.poll(start + Duration::from_millis(1000)).unwrap()),
+            vec![(3000, 0x27)]
+        );
+    }
+
+    #[test]
+    fn media_items_end_with_end_of_stream() {
+        let start = Instant::now();
+        let mut source = VodSource::open(file()).unwrap();
+        source.set_read_ahead(Duration::from_millis(0));
+
+        let items = source.poll_media(start).unwrap();
+        match items[0] {
+            MediaItem::Metadata(ref metadata) => assert_eq!(metadata.video_width, Some(1280)),
+            ref x => panic!("Unexpected item: {:?}", x),
+        }
+
+        let items = source
+            .poll_media(start + Duration::from_millis(3000))
+            .unwrap();
+
+        assert_eq!(items.last(), Some(&MediaItem::EndOfStream));
+        assert!(source
+            .poll_media(start + Duration::from_millis(4000))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! Framing for delivering live FLV streams over WebSocket connections (WS-FLV).
+//!
+//! Web players that receive FLV over a WebSocket expect each binary message to contain either
+//! the FLV header or a single tag, which is exactly how `LiveFlvStream::next_chunk()` splits a
+//! viewer's stream.  Applications using a WebSocket library can send those chunks as binary
+//! messages directly.  The functions here build the frames themselves, for applications that
+//! write to the underlying connection after performing the upgrade handshake on their own.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use live::{FlvViewerId, LiveFlvStream};
+
+const FINAL_FRAGMENT: u8 = 0x80;
+const BINARY_OPCODE: u8 = 0x2;
+const CLOSE_OPCODE: u8 = 0x8;
+
+/// The status code sent in the close frame when the publisher has finished
+pub const NORMAL_CLOSURE_CODE: u16 = 1000;
+
+/// Creates an unfragmented binary frame containing the payload.  Frames sent by servers are
+/// never masked.
+pub fn binary_frame(payload: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(payload.len() + 10);
+    put_frame_header(&mut frame, BINARY_OPCODE, payload.len());
+    frame.put_slice(payload);
+    frame.freeze()
+}
+
+/// Creates a close frame with the specified status code, to be sent once the stream has ended
+pub fn close_frame(status_code: u16) -> Bytes {
+    let mut frame = BytesMut::with_capacity(4);
+    put_frame_header(&mut frame, CLOSE_OPCODE, 2);
+    frame.put_u16(status_code);
+    frame.freeze()
+}
+
+impl LiveFlvStream {
+    /// Takes everything waiting to be sent to a viewer as WebSocket binary frames, with the FLV
+    /// header and each tag in their own message.  `None` is returned if nothing is waiting.
+    pub fn take_websocket_frames(&mut self, viewer_id: FlvViewerId) -> Option<Bytes> {
+        let mut frames = BytesMut::new();
+        while let Some(chunk) = self.next_chunk(viewer_id) {
+            put_frame_header(&mut frames, BINARY_OPCODE, chunk.len());
+            frames.put_slice(&chunk);
+        }
+
+        if frames.is_empty() {
+            None
+        } else {
+            Some(frames.freeze())
+        }
+    }
+}
+
+fn put_frame_header(frame: &mut BytesMut, opcode: u8, payload_length: usize) {
+    frame.put_u8(FINAL_FRAGMENT | opcode);
+
+    // Lengths that don't fit in 7 bits are marked with 126 or 127 and follow as 16 or 64 bits
+    if payload_length < 126 {
+        frame.put_u8(payload_length as u8);
+    } else if payload_length <= u16::MAX as usize {
+        frame.put_u8(126);
+        frame.put_u16(payload_length as u16);
+    } else {
+        frame.put_u8(127);
+        frame.put_u64(payload_length as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use FlvHeader;
+
+    #[test]
+    fn frame_length_uses_smallest_encoding() {
+        let small = binary_frame(&[1, 2, 3]);
+        let medium = binary_frame(&[0; 300]);
+        let large = binary_frame(&vec![0; 70_000]);
+
+        assert_eq!(&small[..], &[0x82, 3, 1, 2, 3]);
+        assert_eq!(&medium[..4], &[0x82, 126, 0x01, 0x2c]);
+        assert_eq!(medium.len(), 304);
+        assert_eq!(&large[..10], &[0x82, 127, 0, 0, 0, 0, 0, 0x01, 0x11, 0x70]);
+        assert_eq!(large.len(), 70_010);
+    }
+
+    #[test]
+    fn close_frame_contains_status_code() {
+        let frame = close_frame(NORMAL_CLOSURE_CODE);
+
+        assert_eq!(&frame[..], &[0x88, 2, 0x03, 0xe8]);
+    }
+
+    #[test]
+    fn viewer_stream_is_framed_per_chunk() {
+        let mut stream = LiveFlvStream::new("live".to_string(), "key".to_string());
+        let viewer = stream.add_viewer();
+
+        let frames = stream.take_websocket_frames(viewer).unwrap();
+
+        assert_eq!(frames, binary_frame(&FlvHeader::new(true, true).to_bytes()));
+        assert_eq!(stream.take_websocket_frames(viewer), None);
+    }
+}
@@ -0,0 +1,138 @@
+use byteorder::{BigEndian, WriteBytesExt};
+use bytes::{BufMut, Bytes, BytesMut};
+use errors::FlvWriteError;
+use std::io::Write;
+use {FlvHeader, FlvTag, HEADER_LENGTH, TAG_HEADER_LENGTH};
+
+const MAX_TAG_DATA_SIZE: usize = 0x00ff_ffff;
+
+impl FlvHeader {
+    /// Serializes the header, along with the always zero size of the (non-existent) tag before
+    /// the first one.  This is what goes at the very start of a file or HTTP-FLV stream.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut flags = 0;
+        if self.has_audio {
+            flags |= 0x04;
+        }
+
+        if self.has_video {
+            flags |= 0x01;
+        }
+
+        let mut bytes = BytesMut::with_capacity(HEADER_LENGTH + 4);
+        bytes.put_slice(b"FLV");
+        bytes.put_u8(1);
+        bytes.put_u8(flags);
+        bytes.put_u32(HEADER_LENGTH as u32);
+        bytes.put_u32(0);
+        bytes.freeze()
+    }
+}
+
+impl FlvTag {
+    /// Serializes the tag, followed by its size so readers can walk backwards through a file
+    pub fn to_bytes(&self) -> Result<Bytes, FlvWriteError> {
+        let size = self.data.len();
+        if size > MAX_TAG_DATA_SIZE {
+            return Err(FlvWriteError::TagTooLarge { size });
+        }
+
+        // Timestamps are stored as the lower 24 bits followed by the upper 8 bits
+        let timestamp = self.timestamp.value;
+        let mut bytes = Vec::with_capacity(TAG_HEADER_LENGTH + size + 4);
+        bytes.push(self.tag_type.to_u8());
+        bytes.write_u24::<BigEndian>(size as u32)?;
+        bytes.write_u24::<BigEndian>(timestamp & 0x00ff_ffff)?;
+        bytes.push((timestamp >> 24) as u8);
+        bytes.write_u24::<BigEndian>(0)?; // stream id is always 0
+        bytes.extend_from_slice(&self.data);
+        bytes.write_u32::<BigEndian>((TAG_HEADER_LENGTH + size) as u32)?;
+
+        Ok(Bytes::from(bytes))
+    }
+}
+
+/// Writes an FLV header and tags to an output, such as a file
+pub struct FlvWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> FlvWriter<W> {
+    /// Creates a writer and immediately writes the header to the output
+    pub fn new(mut writer: W, header: FlvHeader) -> Result<FlvWriter<W>, FlvWriteError> {
+        writer.write_all(&header.to_bytes())?;
+        Ok(FlvWriter { writer })
+    }
+
+    /// Writes a tag to the output
+    pub fn write_tag(&mut self, tag: &FlvTag) -> Result<(), FlvWriteError> {
+        self.writer.write_all(&tag.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Flushes any buffered data to the output
+    pub fn flush(&mut self) -> Result<(), FlvWriteError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Gets a reference to the output
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Gets a mutable reference to the output.  Writing to it directly will corrupt the file
+    /// unless it's done in terms of whole tags.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the output, consuming the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rml_rtmp::time::RtmpTimestamp;
+    use FlvTagType;
+
+    #[test]
+    fn header_is_serialized_with_media_flags() {
+        let bytes = FlvHeader::new(true, false).to_bytes();
+
+        assert_eq!(
+            &bytes[..],
+            &[b'F', b'L', b'V', 1, 4, 0, 0, 0, 9, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn tag_timestamp_is_split_into_lower_and_extended_bytes() {
+        let tag = FlvTag::new(
+            FlvTagType::Video,
+            RtmpTimestamp::new(0x1234_5678),
+            Bytes::from(vec![1, 2]),
+        );
+
+        let bytes = tag.to_bytes().unwrap();
+
+        let expected = [
+            9, 0, 0, 2, 0x34, 0x56, 0x78, 0x12, 0, 0, 0, 1, 2, 0, 0, 0, 13,
+        ];
+        assert_eq!(&bytes[..], &expected);
+    }
+
+    #[test]
+    fn error_when_tag_data_is_too_large() {
+        let data = Bytes::from(vec![0; MAX_TAG_DATA_SIZE + 1]);
+        let tag = FlvTag::new(FlvTagType::Audio, RtmpTimestamp::new(0), data);
+
+        match tag.to_bytes() {
+            Err(FlvWriteError::TagTooLarge { size }) if size == MAX_TAG_DATA_SIZE + 1 => (),
+            x => panic!("Expected tag too large error, instead got {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! Serialization of the ISO BMFF boxes that make up init and media segments.  Only the boxes
+//! (and fields) needed for fragmented files are written, with every track using a millisecond
+//! timescale so RTMP timestamps can be used as is.
+
+use bytes::{BufMut, Bytes};
+use codecs::{AacConfig, AvcConfig};
+
+pub(crate) const TIMESCALE: u32 = 1000;
+
+const SYNC_SAMPLE_FLAGS: u32 = 0x0200_0000;
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+const MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+pub(crate) enum TrackConfig {
+    Avc {
+        config: AvcConfig,
+        width: u16,
+        height: u16,
+    },
+    Aac(AacConfig),
+}
+
+pub(crate) struct Track<'a> {
+    pub id: u32,
+    pub config: &'a TrackConfig,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Sample {
+    pub duration: u32,
+    pub composition_offset: i32,
+    pub is_sync: bool,
+    pub data: Bytes,
+}
+
+pub(crate) struct TrackFragment<'a> {
+    pub track_id: u32,
+    pub base_decode_time: u64,
+    pub samples: &'a [Sample],
+}
+
+/// Serializes the `ftyp` and `moov` boxes players need before any media segment
+pub(crate) fn write_init_segment(tracks: &[Track]) -> Bytes {
+    let mut buffer = Vec::new();
+    write_box(&mut buffer, b"ftyp", |buffer| {
+        buffer.put_slice(b"iso6");
+        buffer.put_u32(0);
+        for brand in &[b"iso6", b"cmfc", b"mp41"] {
+            buffer.put_slice(*brand);
+        }
+    });
+
+    write_box(&mut buffer, b"moov", |buffer| {
+        write_full_box(buffer, b"mvhd", 0, 0, |buffer| {
+            buffer.put_u32(0); // creation time
+            buffer.put_u32(0); // modification time
+            buffer.put_u32(TIMESCALE);
+            buffer.put_u32(0); // duration
+            buffer.put_u32(0x0001_0000); // rate
+            buffer.put_u16(0x0100); // volume
+            buffer.put_slice(&[0; 10]);
+            write_matrix(buffer);
+            buffer.put_slice(&[0; 24]);
+            buffer.put_u32(tracks.len() as u32 + 1); // next track id
+        });
+
+        for track in tracks {
+            write_trak(buffer, track);
+        }
+
+        write_box(buffer, b"mvex", |buffer| {
+            for track in tracks {
+                write_full_box(buffer, b"trex", 0, 0, |buffer| {
+                    buffer.put_u32(track.id);
+                    buffer.put_u32(1); // sample description index
+                    buffer.put_u32(0); // default duration
+                    buffer.put_u32(0); // default size
+                    buffer.put_u32(0); // default flags
+                });
+            }
+        });
+    });
+
+    Bytes::from(buffer)
+}
+
+/// Serializes a `moof` and `mdat` pair containing the samples of each track fragment
+pub(crate) fn write_media_segment(sequence_number: u32, fragments: &[TrackFragment]) -> Bytes {
+    let mut buffer = Vec::new();
+    let mut data_offset_positions = Vec::with_capacity(fragments.len());
+
+    write_box(&mut buffer, b"moof", |buffer| {
+        write_full_box(buffer, b"mfhd", 0, 0, |buffer| {
+            buffer.put_u32(sequence_number);
+        });
+
+        for fragment in fragments {
+            write_box(buffer, b"traf", |buffer| {
+                // Sample data offsets are relative to the start of the moof
+                write_full_box(buffer, b"tfhd", 0, 0x02_0000, |buffer| {
+                    buffer.put_u32(fragment.track_id);
+                });
+
+                write_full_box(buffer, b"tfdt", 1, 0, |buffer| {
+                    buffer.put_u64(fragment.base_decode_time);
+                });
+
+                // Version 1 allows negative composition offsets.  Flags are data offset,
+                // sample duration, size, flags, and composition offset.
+                write_full_box(buffer, b"trun", 1, 0x0f01, |buffer| {
+                    buffer.put_u32(fragment.samples.len() as u32);
+                    data_offset_positions.push(buffer.len());
+                    buffer.put_i32(0);
+
+                    for sample in fragment.samples {
+                        buffer.put_u32(sample.duration);
+                        buffer.put_u32(sample.data.len() as u32);
+                        buffer.put_u32(if sample.is_sync {
+                            SYNC_SAMPLE_FLAGS
+                        } else {
+                            NON_SYNC_SAMPLE_FLAGS
+                        });
+
+                        buffer.put_i32(sample.composition_offset);
+                    }
+                });
+            });
+        }
+    });
+
+    // Now that the moof's size is known, each track's data offset can be filled in
+    let mut data_offset = buffer.len() + 8;
+    for (fragment, position) in fragments.iter().zip(data_offset_positions) {
+        let offset = (data_offset as i32).to_be_bytes();
+        buffer[position..position + 4].copy_from_slice(&offset);
+        data_offset += fragment
+            .samples
+            .iter()
+            .map(|sample| sample.data.len())
+            .sum::<usize>();
+    }
+
+    write_box(&mut buffer, b"mdat", |buffer| {
+        for fragment in fragments {
+            for sample in fragment.samples {
+                buffer.put_slice(&sample.data);
+            }
+        }
+    });
+
+    Bytes::from(buffer)
+}
+
+fn write_trak(buffer: &mut Vec<u8>, track: &Track) {
+    let (handler, handler_name, width, height) = match *track.config {
+        TrackConfig::Avc { width, height, .. } => (b"vide", "VideoHandler", width, height),
+        TrackConfig::Aac(_) => (b"soun", "SoundHandler", 0, 0),
+    };
+
+    write_box(buffer, b"trak", |buffer| {
+        // Enabled and in movie
+        write_full_box(buffer, b"tkhd", 0, 0x03, |buffer| {
+            buffer.put_u32(0); // creation time
+            buffer.put_u32(0); // modification time
+            buffer.put_u32(track.id);
+            buffer.put_u32(0);
+            buffer.put_u32(0); // duration
+            buffer.put_slice(&[0; 8]);
+            buffer.put_u16(0); // layer
+            buffer.put_u16(0); // alternate group
+            buffer.put_u16(if handler == b"soun" { 0x0100 } else { 0 });
+            buffer.put_u16(0);
+            write_matrix(buffer);
+            buffer.put_u32((width as u32) << 16);
+            buffer.put_u32((height as u32) << 16);
+        });
+
+        write_box(buffer, b"mdia", |buffer| {
+            write_full_box(buffer, b"mdhd", 0, 0, |buffer| {
+                buffer.put_u32(0); // creation time
+                buffer.put_u32(0); // modification time
+                buffer.put_u32(TIMESCALE);
+                buffer.put_u32(0); // duration
+                buffer.put_u16(0x55c4); // "und" language
+                buffer.put_u16(0);
+            });
+
+            write_full_box(buffer, b"hdlr", 0, 0, |buffer| {
+                buffer.put_u32(0);
+                buffer.put_slice(handler);
+                buffer.put_slice(&[0; 12]);
+                buffer.put_slice(handler_name.as_bytes());
+                buffer.put_u8(0);
+            });
+
+            write_box(buffer, b"minf", |buffer| {
+                match *track.config {
+                    TrackConfig::Avc { .. } => write_full_box(buffer, b"vmhd", 0, 1, |buffer| {
+                        buffer.put_slice(&[0; 8]);
+                    }),
+
+                    TrackConfig::Aac(_) => write_full_box(buffer, b"smhd", 0, 0, |buffer| {
+                        buffer.put_u32(0);
+                    }),
+                }
+
+                write_box(buffer, b"dinf", |buffer| {
+                    write_full_box(buffer, b"dref", 0, 0, |buffer| {
+                        buffer.put_u32(1);
+                        // Media data is in the same file
+                        write_full_box(buffer, b"url ", 0, 1, |_| ());
+                    });
+                });
+
+                write_stbl(buffer, track.config);
+            });
+        });
+    });
+}
+
+fn write_stbl(buffer: &mut Vec<u8>, config: &TrackConfig) {
+    write_box(buffer, b"stbl", |buffer| {
+        write_full_box(buffer, b"stsd", 0, 0, |buffer| {
+            buffer.put_u32(1);
+            match *config {
+                TrackConfig::Avc {
+                    ref config,
+                    width,
+                    height,
+                } => write_avc1(buffer, config, width, height),
+
+                TrackConfig::Aac(ref config) => write_mp4a(buffer, config),
+            }
+        });
+
+        // Samples are all described in fragments, so these tables are left empty
+        for box_type in &[b"stts", b"stsc", b"stco"] {
+            write_full_box(buffer, box_type, 0, 0, |buffer| buffer.put_u32(0));
+        }
+
+        write_full_box(buffer, b"stsz", 0, 0, |buffer| {
+            buffer.put_u32(0);
+            buffer.put_u32(0);
+        });
+    });
+}
+
+fn write_avc1(buffer: &mut Vec<u8>, config: &AvcConfig, width: u16, height: u16) {
+    write_box(buffer, b"avc1", |buffer| {
+        buffer.put_slice(&[0; 6]);
+        buffer.put_u16(1); // data reference index
+        buffer.put_slice(&[0; 16]);
+        buffer.put_u16(width);
+        buffer.put_u16(height);
+        buffer.put_u32(0x0048_0000); // 72 dpi horizontally
+        buffer.put_u32(0x0048_0000); // and vertically
+        buffer.put_u32(0);
+        buffer.put_u16(1); // frame count
+        buffer.put_slice(&[0; 32]); // compressor name
+        buffer.put_u16(0x0018); // depth
+        buffer.put_i16(-1);
+
+        write_box(buffer, b"avcC", |buffer| buffer.put_slice(&config.record));
+    });
+}
+
+fn write_mp4a(buffer: &mut Vec<u8>, config: &AacConfig) {
+    write_box(buffer, b"mp4a", |buffer| {
+        buffer.put_slice(&[0; 6]);
+        buffer.put_u16(1); // data reference index
+        buffer.put_slice(&[0; 8]);
+        buffer.put_u16(config.channel_count as u16);
+        buffer.put_u16(16); // sample size
+        buffer.put_u32(0);
+        buffer.put_u32(config.sample_rate.min(0xffff) << 16);
+
+        write_full_box(buffer, b"esds", 0, 0, |buffer| {
+            let specific_info = &config.audio_specific_config;
+
+            let mut decoder_config = vec![0x40, 0x15, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+            write_descriptor(&mut decoder_config, 0x05, specific_info);
+
+            let mut es = vec![0, 0, 0]; // es id and flags
+            write_descriptor(&mut es, 0x04, &decoder_config);
+            write_descriptor(&mut es, 0x06, &[0x02]);
+
+            write_descriptor(buffer, 0x03, &es);
+        });
+    });
+}
+
+fn write_descriptor(buffer: &mut Vec<u8>, tag: u8, contents: &[u8]) {
+    buffer.put_u8(tag);
+
+    // Sizes are written 7 bits at a time, with the high bit set on all but the last byte
+    let size = contents.len() as u32;
+    for shift in &[21, 14, 7] {
+        if size >> shift != 0 {
+            buffer.put_u8(0x80 | ((size >> shift) & 0x7f) as u8);
+        }
+    }
+
+    buffer.put_u8((size & 0x7f) as u8);
+    buffer.put_slice(contents);
+}
+
+fn write_matrix(buffer: &mut Vec<u8>) {
+    for value in &MATRIX {
+        buffer.put_u32(*value);
+    }
+}
+
+pub(crate) fn write_box<F>(buffer: &mut Vec<u8>, box_type: &[u8; 4], write_contents: F)
+where
+    F: FnOnce(&mut Vec<u8>),
+{
+    let start = buffer.len();
+    buffer.put_u32(0);
+    buffer.put_slice(box_type);
+    write_contents(buffer);
+
+    let size = (buffer.len() - start) as u32;
+    buffer[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+pub(crate) fn write_full_box<F>(
+    buffer: &mut Vec<u8>,
+    box_type: &[u8; 4],
+    version: u8,
+    flags: u32,
+    write_contents: F,
+) where
+    F: FnOnce(&mut Vec<u8>),
+{
+    write_box(buffer, box_type, |buffer| {
+        buffer.put_u32(((version as u32) << 24) | (flags & 0x00ff_ffff));
+        write_contents(buffer);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_size_includes_header() {
+        let mut buffer = Vec::new();
+        write_box(&mut buffer, b"free", |buffer| buffer.put_slice(&[1, 2, 3]));
+
+        assert_eq!(buffer, vec![0, 0, 0, 11, b'f', b'r', b'e', b'e', 1, 2, 3]);
+    }
+
+    #[test]
+    fn large_descriptor_sizes_use_multiple_bytes() {
+        let mut buffer = Vec::new();
+        write_descriptor(&mut buffer, 0x05, &[0; 200]);
+
+        assert_eq!(&buffer[..3], &[0x05, 0x81, 0x48]);
+        assert_eq!(buffer.len(), 203);
+    }
+
+    #[test]
+    fn trun_data_offset_points_at_samples_in_mdat() {
+        let samples = vec![Sample {
+            duration: 33,
+            composition_offset: 0,
+            is_sync: true,
+            data: Bytes::from(vec![9, 8, 7]),
+        }];
+
+        let fragments = [TrackFragment {
+            track_id: 1,
+            base_decode_time: 0,
+            samples: &samples,
+        }];
+
+        let segment = write_media_segment(1, &fragments);
+
+        // trun is the last box in the moof, so the data offset is right after the sample count
+        // and 4 fields of the one sample
+        let moof_size = u32::from_be_bytes([segment[0], segment[1], segment[2], segment[3]]);
+        let offset_position = moof_size as usize - 4 * 4 - 4;
+        let offset = &segment[offset_position..offset_position + 4];
+        let offset = i32::from_be_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize;
+
+        assert_eq!(&segment[offset..], &[9, 8, 7]);
+    }
+}
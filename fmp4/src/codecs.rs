@@ -0,0 +1,133 @@
+use bytes::Bytes;
+use errors::Fmp4Error;
+
+const AAC_SAMPLE_RATES: [u32; 13] = [
+    96_000, 88_200, 64_000, 48_000, 44_100, 32_000, 24_000, 22_050, 16_000, 12_000, 11_025, 8_000,
+    7_350,
+];
+
+/// The AVCDecoderConfigurationRecord carried by an AVC sequence header
+#[derive(PartialEq, Debug, Clone)]
+pub struct AvcConfig {
+    pub record: Bytes,
+}
+
+impl AvcConfig {
+    /// Validates a decoder configuration record, which is everything after the first 5 bytes
+    /// of an AVC sequence header's video data
+    pub fn parse(record: Bytes) -> Result<AvcConfig, Fmp4Error> {
+        if record.len() < 7 || record[0] != 1 {
+            return Err(Fmp4Error::InvalidSequenceHeader);
+        }
+
+        Ok(AvcConfig { record })
+    }
+
+    /// The RFC 6381 codec string (e.g. `avc1.64001f`) used by HLS and DASH manifests
+    pub fn codec_string(&self) -> String {
+        format!(
+            "avc1.{:02x}{:02x}{:02x}",
+            self.record[1], self.record[2], self.record[3]
+        )
+    }
+}
+
+/// The AudioSpecificConfig carried by an AAC sequence header
+#[derive(PartialEq, Debug, Clone)]
+pub struct AacConfig {
+    pub audio_specific_config: Bytes,
+    pub object_type: u8,
+    pub sample_rate: u32,
+    pub channel_count: u8,
+}
+
+impl AacConfig {
+    /// Parses an AudioSpecificConfig, which is everything after the first 2 bytes of an AAC
+    /// sequence header's audio data
+    pub fn parse(audio_specific_config: Bytes) -> Result<AacConfig, Fmp4Error> {
+        let bytes = &audio_specific_config;
+        if bytes.len() < 2 {
+            return Err(Fmp4Error::InvalidSequenceHeader);
+        }
+
+        // 5 bits of object type, 4 bits of sample rate index, and then 4 bits of channel
+        // configuration, unless the index is 15 which means the rate is explicitly listed in
+        // the 24 bits after it.
+        let object_type = bytes[0] >> 3;
+        let rate_index = ((bytes[0] & 0x07) << 1) | (bytes[1] >> 7);
+        let (sample_rate, channel_byte) = if rate_index == 15 {
+            if bytes.len() < 5 {
+                return Err(Fmp4Error::InvalidSequenceHeader);
+            }
+
+            let rate = ((bytes[1] as u32 & 0x7f) << 17)
+                | ((bytes[2] as u32) << 9)
+                | ((bytes[3] as u32) << 1)
+                | (bytes[4] as u32 >> 7);
+
+            (rate, bytes[4])
+        } else {
+            match AAC_SAMPLE_RATES.get(rate_index as usize) {
+                Some(rate) => (*rate, bytes[1]),
+                None => return Err(Fmp4Error::InvalidSequenceHeader),
+            }
+        };
+
+        let channel_count = (channel_byte >> 3) & 0x0f;
+
+        Ok(AacConfig {
+            object_type,
+            sample_rate,
+            channel_count,
+            audio_specific_config,
+        })
+    }
+
+    /// The RFC 6381 codec string (e.g. `mp4a.40.2`) used by HLS and DASH manifests
+    pub fn codec_string(&self) -> String {
+        format!("mp4a.40.{}", self.object_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_aac_lc_stereo_config() {
+        let config = AacConfig::parse(Bytes::from(vec![0x12, 0x10])).unwrap();
+
+        assert_eq!(config.object_type, 2);
+        assert_eq!(config.sample_rate, 44_100);
+        assert_eq!(config.channel_count, 2);
+        assert_eq!(config.codec_string(), "mp4a.40.2");
+    }
+
+    #[test]
+    fn can_parse_aac_config_with_explicit_sample_rate() {
+        // object type 2, index 15, rate 44,100 (0x00ac44), 1 channel
+        let config = AacConfig::parse(Bytes::from(vec![0x17, 0x80, 0x56, 0x22, 0x08])).unwrap();
+
+        assert_eq!(config.sample_rate, 44_100);
+        assert_eq!(config.channel_count, 1);
+    }
+
+    #[test]
+    fn avc_codec_string_uses_profile_and_level() {
+        let record = Bytes::from(vec![1, 0x64, 0x00, 0x1f, 0xff, 0xe1, 0x00]);
+        let config = AvcConfig::parse(record).unwrap();
+
+        assert_eq!(config.codec_string(), "avc1.64001f");
+    }
+
+    #[test]
+    fn error_when_avc_record_is_truncated() {
+        match AvcConfig::parse(Bytes::from(vec![1, 0x64])) {
+            Err(Fmp4Error::InvalidSequenceHeader) => (),
+            x => panic!(
+                "Expected invalid sequence header error, instead got {:?}",
+                x
+            ),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use rml_flv::{SoundFormat, VideoCodec};
+use thiserror::Error;
+
+/// Errors that can occur while muxing audio and video into fragmented MP4
+#[derive(Debug, Error)]
+pub enum Fmp4Error {
+    /// The video is encoded with a codec that can't be carried in fragmented MP4
+    #[error("Video codec {0:?} is not supported")]
+    UnsupportedVideoCodec(VideoCodec),
+
+    /// The audio is encoded with a format that can't be carried in fragmented MP4
+    #[error("Sound format {0:?} is not supported")]
+    UnsupportedSoundFormat(SoundFormat),
+
+    /// A sequence header did not contain a valid decoder configuration
+    #[error("Sequence header does not contain a valid decoder configuration")]
+    InvalidSequenceHeader,
+
+    /// Audio or video data was too short to contain the header every payload starts with
+    #[error("Media payload is too short to contain its header")]
+    TruncatedPayload,
+}
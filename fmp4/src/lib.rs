@@ -0,0 +1,50 @@
+//! This crate packages the audio and video of RTMP streams into fragmented MP4 (the CMAF
+//! container), which is what HLS and DASH players expect media segments to be in.
+//!
+//! Fragmented MP4 output consists of a single initialization segment, describing each track and
+//! containing the decoder configurations from the stream's sequence headers, followed by any
+//! number of media segments.  Each media segment is a `moof` box describing the samples it
+//! contains and an `mdat` box containing the samples themselves.
+//!
+//! Currently AVC (H.264) video and AAC audio are supported.
+//!
+//! # Examples
+//! ```
+//! extern crate bytes;
+//! extern crate rml_fmp4;
+//! extern crate rml_rtmp;
+//!
+//! use bytes::Bytes;
+//! use rml_fmp4::Fmp4Muxer;
+//! use rml_rtmp::time::RtmpTimestamp;
+//!
+//! let sequence_header = vec![0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 0];
+//! let keyframe = vec![0x17, 1, 0, 0, 0, 0, 0, 0, 1, 0x65];
+//! let inter_frame = vec![0x27, 1, 0, 0, 0, 0, 0, 0, 1, 0x41];
+//!
+//! let mut muxer = Fmp4Muxer::new();
+//! muxer.push_video(&Bytes::from(sequence_header), RtmpTimestamp::new(0)).unwrap();
+//! muxer.push_video(&Bytes::from(keyframe), RtmpTimestamp::new(0)).unwrap();
+//! muxer.push_video(&Bytes::from(inter_frame), RtmpTimestamp::new(33)).unwrap();
+//!
+//! let init_segment = muxer.init_segment().unwrap();
+//! let media_segment = muxer.flush_segment().unwrap();
+//!
+//! assert_eq!(muxer.codecs(), vec!["avc1.64001f".to_string()]);
+//! assert_eq!(media_segment.duration, 33);
+//! assert!(media_segment.is_independent);
+//! ```
+
+extern crate bytes;
+extern crate rml_flv;
+extern crate rml_rtmp;
+extern crate thiserror;
+
+mod boxes;
+mod codecs;
+mod errors;
+mod muxer;
+
+pub use codecs::{AacConfig, AvcConfig};
+pub use errors::Fmp4Error;
+pub use muxer::{Fmp4Muxer, MediaSegment};
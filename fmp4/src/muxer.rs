@@ -0,0 +1,449 @@
+use boxes::{self, Sample, Track, TrackConfig, TrackFragment};
+use bytes::Bytes;
+use codecs::{AacConfig, AvcConfig};
+use errors::Fmp4Error;
+use rml_flv::{
+    AacPacketType, AudioTagHeader, AvcPacketType, SoundFormat, VideoCodec, VideoTagHeader,
+};
+use rml_rtmp::time::RtmpTimestamp;
+use std::cmp::max;
+
+const VIDEO_TRACK_ID: u32 = 1;
+const AUDIO_TRACK_ID: u32 = 2;
+
+/// A `moof` and `mdat` pair containing every sample completed since the previous segment
+#[derive(PartialEq, Debug, Clone)]
+pub struct MediaSegment {
+    /// Starts at 1 and increases with every segment
+    pub sequence_number: u32,
+
+    /// Decode time of the segment's earliest sample, in milliseconds since the start of the
+    /// stream
+    pub start_time: u64,
+
+    /// Length of the segment in milliseconds
+    pub duration: u64,
+
+    /// True if the segment can be decoded without any segments before it, which is the case
+    /// if its video starts with a keyframe (or it has no video at all)
+    pub is_independent: bool,
+
+    pub data: Bytes,
+}
+
+struct PendingSample {
+    timestamp: RtmpTimestamp,
+    decode_time: u64,
+    composition_offset: i32,
+    is_sync: bool,
+    data: Bytes,
+}
+
+struct TrackState {
+    config: Option<TrackConfig>,
+    pending: Option<PendingSample>,
+    samples: Vec<Sample>,
+    samples_start: u64,
+    last_duration: u32,
+}
+
+impl TrackState {
+    fn new() -> TrackState {
+        TrackState {
+            config: None,
+            pending: None,
+            samples: Vec::new(),
+            samples_start: 0,
+            last_duration: 0,
+        }
+    }
+
+    /// Adds a sample, completing the previous one now that its duration is known
+    fn push(&mut self, sample: PendingSample) {
+        if let Some(previous) = self.pending.take() {
+            let duration = if sample.timestamp < previous.timestamp {
+                0
+            } else {
+                (sample.timestamp - previous.timestamp).value
+            };
+
+            self.complete(previous, duration);
+        }
+
+        self.pending = Some(sample);
+    }
+
+    fn complete(&mut self, sample: PendingSample, duration: u32) {
+        if self.samples.is_empty() {
+            self.samples_start = sample.decode_time;
+        }
+
+        self.last_duration = duration;
+        self.samples.push(Sample {
+            duration,
+            composition_offset: sample.composition_offset,
+            is_sync: sample.is_sync,
+            data: sample.data,
+        });
+    }
+
+    /// Decode time the next sample should have, given the timestamp it was received with
+    fn next_decode_time(&self, timestamp: RtmpTimestamp, base: RtmpTimestamp) -> u64 {
+        match self.pending {
+            Some(ref pending) if timestamp < pending.timestamp => pending.decode_time,
+            Some(ref pending) => pending.decode_time + (timestamp - pending.timestamp).value as u64,
+            None if timestamp < base => 0,
+            None => (timestamp - base).value as u64,
+        }
+    }
+
+    fn end_time(&self) -> u64 {
+        self.samples_start
+            + self
+                .samples
+                .iter()
+                .map(|sample| sample.duration as u64)
+                .sum::<u64>()
+    }
+}
+
+/// Packages the AVC video and AAC audio carried by RTMP video and audio messages into
+/// fragmented MP4, as used by CMAF based HLS and DASH.
+///
+/// Video and audio payloads (including sequence headers) are passed in as they are received.
+/// Once the sequence headers have arrived `init_segment()` returns the initialization segment
+/// players need before anything else, and `flush_segment()` returns a media segment with every
+/// sample received since the last one.
+///
+/// A sample's duration isn't known until the next sample on its track arrives, so the most
+/// recent sample of each track is held back until then.  This means flushing right after
+/// pushing a keyframe creates a segment that ends just before that keyframe, and the next
+/// segment will start with it.
+///
+/// Samples received before their track's sequence header can't be decoded, and are dropped.
+pub struct Fmp4Muxer {
+    video: TrackState,
+    audio: TrackState,
+    video_width: u16,
+    video_height: u16,
+    base_timestamp: Option<RtmpTimestamp>,
+    sequence_number: u32,
+}
+
+impl Fmp4Muxer {
+    /// Creates a muxer with no tracks configured
+    pub fn new() -> Fmp4Muxer {
+        Fmp4Muxer {
+            video: TrackState::new(),
+            audio: TrackState::new(),
+            video_width: 0,
+            video_height: 0,
+            base_timestamp: None,
+            sequence_number: 0,
+        }
+    }
+
+    /// Sets the width and height reported in the init segment.  Players read the real
+    /// dimensions from the sequence header, so these are informational.
+    pub fn set_video_dimensions(&mut self, width: u16, height: u16) {
+        self.video_width = width;
+        self.video_height = height;
+        if let Some(TrackConfig::Avc {
+            ref mut width,
+            ref mut height,
+            ..
+        }) = self.video.config
+        {
+            *width = self.video_width;
+            *height = self.video_height;
+        }
+    }
+
+    /// Adds the payload of an RTMP video message
+    pub fn push_video(&mut self, data: &Bytes, timestamp: RtmpTimestamp) -> Result<(), Fmp4Error> {
+        let header = match VideoTagHeader::parse(data) {
+            Some(header) => header,
+            None => return Err(Fmp4Error::TruncatedPayload),
+        };
+
+        if header.codec != VideoCodec::Avc {
+            return Err(Fmp4Error::UnsupportedVideoCodec(header.codec));
+        }
+
+        match header.avc_packet_type {
+            Some(AvcPacketType::SequenceHeader) => {
+                let config = AvcConfig::parse(data.slice(5..))?;
+                self.video.config = Some(TrackConfig::Avc {
+                    config,
+                    width: self.video_width,
+                    height: self.video_height,
+                });
+            }
+
+            Some(AvcPacketType::Nalu) if self.video.config.is_some() => {
+                let base = self.base_timestamp(timestamp);
+                let sample = PendingSample {
+                    timestamp,
+                    decode_time: self.video.next_decode_time(timestamp, base),
+                    composition_offset: header.composition_time,
+                    is_sync: header.is_keyframe(),
+                    data: data.slice(5..),
+                };
+
+                self.video.push(sample);
+            }
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Adds the payload of an RTMP audio message
+    pub fn push_audio(&mut self, data: &Bytes, timestamp: RtmpTimestamp) -> Result<(), Fmp4Error> {
+        let header = match AudioTagHeader::parse(data) {
+            Some(header) => header,
+            None => return Err(Fmp4Error::TruncatedPayload),
+        };
+
+        if header.sound_format != SoundFormat::Aac {
+            return Err(Fmp4Error::UnsupportedSoundFormat(header.sound_format));
+        }
+
+        match header.aac_packet_type {
+            Some(AacPacketType::SequenceHeader) => {
+                let config = AacConfig::parse(data.slice(2..))?;
+                self.audio.config = Some(TrackConfig::Aac(config));
+            }
+
+            Some(AacPacketType::Raw) if self.audio.config.is_some() => {
+                let base = self.base_timestamp(timestamp);
+                let sample = PendingSample {
+                    timestamp,
+                    decode_time: self.audio.next_decode_time(timestamp, base),
+                    composition_offset: 0,
+                    is_sync: true,
+                    data: data.slice(2..),
+                };
+
+                self.audio.push(sample);
+            }
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// True once a video sequence header has been received
+    pub fn has_video(&self) -> bool {
+        self.video.config.is_some()
+    }
+
+    /// True once an audio sequence header has been received
+    pub fn has_audio(&self) -> bool {
+        self.audio.config.is_some()
+    }
+
+    /// The RFC 6381 codec strings of the configured tracks, for the `CODECS` attribute of HLS
+    /// playlists and the `codecs` attribute of DASH representations
+    pub fn codecs(&self) -> Vec<String> {
+        let mut codecs = Vec::new();
+        if let Some(TrackConfig::Avc { ref config, .. }) = self.video.config {
+            codecs.push(config.codec_string());
+        }
+
+        if let Some(TrackConfig::Aac(ref config)) = self.audio.config {
+            codecs.push(config.codec_string());
+        }
+
+        codecs
+    }
+
+    /// Creates the initialization segment for the tracks that have been configured so far, or
+    /// `None` if no sequence headers have been received
+    pub fn init_segment(&self) -> Option<Bytes> {
+        let mut tracks = Vec::with_capacity(2);
+        if let Some(ref config) = self.video.config {
+            tracks.push(Track {
+                id: VIDEO_TRACK_ID,
+                config,
+            });
+        }
+
+        if let Some(ref config) = self.audio.config {
+            tracks.push(Track {
+                id: AUDIO_TRACK_ID,
+                config,
+            });
+        }
+
+        if tracks.is_empty() {
+            return None;
+        }
+
+        Some(boxes::write_init_segment(&tracks))
+    }
+
+    /// Creates a media segment from every completed sample, or `None` if there aren't any
+    pub fn flush_segment(&mut self) -> Option<MediaSegment> {
+        if self.video.samples.is_empty() && self.audio.samples.is_empty() {
+            return None;
+        }
+
+        let mut fragments = Vec::with_capacity(2);
+        let mut start_time = u64::MAX;
+        let mut end_time = 0;
+        for &(id, track) in &[(VIDEO_TRACK_ID, &self.video), (AUDIO_TRACK_ID, &self.audio)] {
+            if track.samples.is_empty() {
+                continue;
+            }
+
+            start_time = start_time.min(track.samples_start);
+            end_time = max(end_time, track.end_time());
+            fragments.push(TrackFragment {
+                track_id: id,
+                base_decode_time: track.samples_start,
+                samples: &track.samples,
+            });
+        }
+
+        self.sequence_number += 1;
+        let segment = MediaSegment {
+            sequence_number: self.sequence_number,
+            start_time,
+            duration: end_time - start_time,
+            is_independent: self
+                .video
+                .samples
+                .first()
+                .is_none_or(|sample| sample.is_sync),
+            data: boxes::write_media_segment(self.sequence_number, &fragments),
+        };
+
+        self.video.samples.clear();
+        self.audio.samples.clear();
+
+        Some(segment)
+    }
+
+    /// Completes the samples being held back, giving each the same duration as the sample
+    /// before it, and flushes them into a final segment.  Used when the stream has ended.
+    pub fn finish(&mut self) -> Option<MediaSegment> {
+        for track in &mut [&mut self.video, &mut self.audio] {
+            if let Some(pending) = track.pending.take() {
+                let duration = track.last_duration;
+                track.complete(pending, duration);
+            }
+        }
+
+        self.flush_segment()
+    }
+
+    fn base_timestamp(&mut self, timestamp: RtmpTimestamp) -> RtmpTimestamp {
+        *self.base_timestamp.get_or_insert(timestamp)
+    }
+}
+
+impl Default for Fmp4Muxer {
+    fn default() -> Self {
+        Fmp4Muxer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn avc_sequence_header() -> Bytes {
+        Bytes::from(vec![0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 0])
+    }
+
+    fn aac_sequence_header() -> Bytes {
+        Bytes::from(vec![0xaf, 0, 0x12, 0x10])
+    }
+
+    fn frame(is_keyframe: bool, value: u8) -> Bytes {
+        let flags = if is_keyframe { 0x17 } else { 0x27 };
+        Bytes::from(vec![flags, 1, 0, 0, 0, value])
+    }
+
+    fn contains_box(data: &[u8], box_type: &[u8]) -> bool {
+        data.windows(4).any(|window| window == box_type)
+    }
+
+    #[test]
+    fn no_init_segment_before_sequence_headers() {
+        let mut muxer = Fmp4Muxer::new();
+        muxer
+            .push_video(&frame(true, 1), RtmpTimestamp::new(0))
+            .unwrap();
+
+        assert_eq!(muxer.init_segment(), None);
+        assert_eq!(muxer.finish(), None);
+    }
+
+    #[test]
+    fn init_segment_contains_both_tracks() {
+        let mut muxer = Fmp4Muxer::new();
+        muxer
+            .push_video(&avc_sequence_header(), RtmpTimestamp::new(0))
+            .unwrap();
+        muxer
+            .push_audio(&aac_sequence_header(), RtmpTimestamp::new(0))
+            .unwrap();
+
+        let init = muxer.init_segment().unwrap();
+
+        assert_eq!(&init[4..8], b"ftyp");
+        assert!(contains_box(&init, b"avcC"));
+        assert!(contains_box(&init, b"esds"));
+        assert_eq!(muxer.codecs(), vec!["avc1.64001f", "mp4a.40.2"]);
+    }
+
+    #[test]
+    fn flushed_segment_holds_back_latest_sample() {
+        let mut muxer = Fmp4Muxer::new();
+        muxer
+            .push_video(&avc_sequence_header(), RtmpTimestamp::new(1000))
+            .unwrap();
+
+        let frames = [(true, 1000), (false, 1033), (false, 1066), (true, 1100)];
+        for &(is_keyframe, time) in &frames {
+            muxer
+                .push_video(&frame(is_keyframe, 0), RtmpTimestamp::new(time))
+                .unwrap();
+        }
+
+        let segment = muxer.flush_segment().unwrap();
+        assert_eq!(segment.sequence_number, 1);
+        assert_eq!(segment.start_time, 0);
+        assert_eq!(segment.duration, 100);
+        assert!(segment.is_independent);
+        assert_eq!(&segment.data[4..8], b"moof");
+        assert!(contains_box(&segment.data, b"mdat"));
+
+        muxer
+            .push_video(&frame(false, 0), RtmpTimestamp::new(1133))
+            .unwrap();
+
+        let last = muxer.finish().unwrap();
+        assert_eq!(last.sequence_number, 2);
+        assert_eq!(last.start_time, 100);
+        assert_eq!(last.duration, 66);
+        assert!(last.is_independent);
+    }
+
+    #[test]
+    fn error_for_non_aac_audio() {
+        let mut muxer = Fmp4Muxer::new();
+
+        match muxer.push_audio(&Bytes::from(vec![0x2f, 0xff]), RtmpTimestamp::new(0)) {
+            Err(Fmp4Error::UnsupportedSoundFormat(SoundFormat::Mp3)) => (),
+            x => panic!(
+                "Expected unsupported sound format error, instead got {:?}",
+                x
+            ),
+        }
+    }
+}
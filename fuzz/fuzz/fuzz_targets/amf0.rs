@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rml_fuzz::amf::{self, Amf0Input};
+
+fuzz_target!(|input: Amf0Input| {
+    amf::run_amf0(&input);
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rml_fuzz::amf;
+
+fuzz_target!(|data: &[u8]| {
+    amf::run_amf3(data);
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rml_fuzz::chunk_stream::{self, ChunkStreamInput};
+
+fuzz_target!(|input: ChunkStreamInput| {
+    chunk_stream::run(&input);
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rml_fuzz::handshake::{self, HandshakeInput};
+
+fuzz_target!(|input: HandshakeInput| {
+    handshake::run(&input);
+});
@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rml_fuzz::session::{self, SessionInput};
+
+fuzz_target!(|input: SessionInput| {
+    session::run(&input);
+});
@@ -0,0 +1,288 @@
+//! AMF0 and AMF3 values, decoded from raw bytes or generated and round tripped
+
+use arbitrary::Arbitrary;
+use rml_amf0::{Amf0Value, BorrowedDeserializer, DeserializationLimits, ObjectProperties};
+use rml_amf3::Amf3Value;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// The latest date that's generated, so dates stay within the millisecond precision of an
+/// AMF0 number
+const MAX_DATE_MILLIS: u64 = 1 << 52;
+
+#[derive(Arbitrary, Debug)]
+pub enum Amf0Input {
+    /// Bytes that are decoded as they are
+    Raw(Vec<u8>),
+
+    /// Values that are serialized and then decoded
+    Values(Vec<FuzzAmf0Value>),
+}
+
+/// A mirror of `Amf0Value` that can be generated
+#[derive(Arbitrary, Debug)]
+pub enum FuzzAmf0Value {
+    Number(f64),
+    Boolean(bool),
+    Utf8String(String),
+    Object(Vec<(String, FuzzAmf0Value)>),
+    StrictArray(Vec<FuzzAmf0Value>),
+    Null,
+    Undefined,
+    Date {
+        unix_time_millis: u64,
+        time_zone: i16,
+    },
+    XmlDocument(String),
+    TypedObject {
+        class_name: String,
+        properties: Vec<(String, FuzzAmf0Value)>,
+    },
+}
+
+impl FuzzAmf0Value {
+    pub fn to_value(&self) -> Amf0Value {
+        match self {
+            FuzzAmf0Value::Number(x) => Amf0Value::Number(*x),
+            FuzzAmf0Value::Boolean(x) => Amf0Value::Boolean(*x),
+            FuzzAmf0Value::Utf8String(x) => Amf0Value::Utf8String(x.clone()),
+            FuzzAmf0Value::Object(x) => Amf0Value::Object(to_properties(x)),
+            FuzzAmf0Value::StrictArray(x) => {
+                Amf0Value::StrictArray(x.iter().map(FuzzAmf0Value::to_value).collect())
+            }
+
+            FuzzAmf0Value::Null => Amf0Value::Null,
+            FuzzAmf0Value::Undefined => Amf0Value::Undefined,
+            FuzzAmf0Value::Date {
+                unix_time_millis,
+                time_zone,
+            } => Amf0Value::Date {
+                unix_time: UNIX_EPOCH + Duration::from_millis(unix_time_millis % MAX_DATE_MILLIS),
+                time_zone: *time_zone,
+            },
+
+            FuzzAmf0Value::XmlDocument(x) => Amf0Value::XmlDocument(x.clone()),
+            FuzzAmf0Value::TypedObject {
+                class_name,
+                properties,
+            } => Amf0Value::TypedObject {
+                class_name: class_name.clone(),
+                properties: to_properties(properties),
+            },
+        }
+    }
+}
+
+fn to_properties(properties: &[(String, FuzzAmf0Value)]) -> ObjectProperties {
+    properties
+        .iter()
+        .map(|(key, value)| (key.clone(), value.to_value()))
+        .collect()
+}
+
+/// Decodes AMF0 bytes, panicking if the decoders misbehave.  Anything that can be decoded must
+/// survive being serialized and decoded again, and the owned and borrowed deserializers must
+/// agree on it.
+pub fn run_amf0(input: &Amf0Input) {
+    let bytes = match input {
+        Amf0Input::Raw(bytes) => bytes.clone(),
+        Amf0Input::Values(values) => {
+            let values = values.iter().map(FuzzAmf0Value::to_value).collect();
+            // Values that can't be encoded, such as objects with empty property names, are
+            // rejected by the serializer
+            let bytes = match rml_amf0::serialize(&values) {
+                Ok(bytes) => bytes,
+                Err(_) => return,
+            };
+
+            let limits = DeserializationLimits::default();
+            if values.iter().all(|value| depth(value) <= limits.max_depth) {
+                let decoded = rml_amf0::deserialize(&mut &bytes[..]).unwrap();
+                assert_values_match(&decoded, &values);
+            }
+
+            bytes
+        }
+    };
+
+    let values = match rml_amf0::deserialize(&mut &bytes[..]) {
+        Ok(values) => values,
+        Err(_) => return,
+    };
+
+    let mut borrowed = BorrowedDeserializer::new(&bytes);
+    let mut borrowed_values = Vec::new();
+    while let Ok(Some(value)) = borrowed.next_value() {
+        match value.to_owned_value() {
+            Ok(value) => borrowed_values.push(value),
+            Err(_) => break,
+        }
+    }
+
+    if borrowed.remaining().is_empty() {
+        assert_values_match(&borrowed_values, &values);
+    }
+
+    let reserialized = rml_amf0::serialize(&values).unwrap();
+    let decoded = rml_amf0::deserialize(&mut &reserialized[..]).unwrap();
+    assert_values_match(&decoded, &values);
+}
+
+/// Decodes AMF3 bytes, panicking if the decoder misbehaves.  Anything that can be decoded must
+/// survive being serialized and decoded again.
+pub fn run_amf3(bytes: &[u8]) {
+    let values = match rml_amf3::deserialize(&mut &bytes[..]) {
+        Ok(values) => values,
+        Err(_) => return,
+    };
+
+    let reserialized = rml_amf3::serialize(&values).unwrap();
+    let decoded = rml_amf3::deserialize(&mut &reserialized[..]).unwrap();
+    assert_eq!(decoded.len(), values.len());
+    for (decoded, value) in decoded.iter().zip(values.iter()) {
+        assert!(
+            amf3_values_match(decoded, value),
+            "{:?} was decoded as {:?}",
+            value,
+            decoded
+        );
+    }
+}
+
+fn depth(value: &Amf0Value) -> usize {
+    let children = match value {
+        Amf0Value::Object(properties) | Amf0Value::TypedObject { properties, .. } => {
+            properties.values().map(depth).max()
+        }
+
+        Amf0Value::StrictArray(values) => values.iter().map(depth).max(),
+        _ => return 0,
+    };
+
+    children.unwrap_or(0) + 1
+}
+
+fn assert_values_match(actual: &[Amf0Value], expected: &[Amf0Value]) {
+    assert_eq!(actual.len(), expected.len());
+    for (actual, expected) in actual.iter().zip(expected.iter()) {
+        assert!(
+            amf0_values_match(actual, expected),
+            "Expected {:?} but got {:?}",
+            expected,
+            actual
+        );
+    }
+}
+
+/// Compares values the way `PartialEq` does, except that NaN numbers are equal to each other
+fn amf0_values_match(first: &Amf0Value, second: &Amf0Value) -> bool {
+    match (first, second) {
+        (Amf0Value::Number(x), Amf0Value::Number(y)) => numbers_match(*x, *y),
+        (Amf0Value::Object(x), Amf0Value::Object(y)) => properties_match(x, y),
+        (Amf0Value::StrictArray(x), Amf0Value::StrictArray(y)) => {
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(x, y)| amf0_values_match(x, y))
+        }
+
+        (
+            Amf0Value::TypedObject {
+                class_name: first_class,
+                properties: first_properties,
+            },
+            Amf0Value::TypedObject {
+                class_name: second_class,
+                properties: second_properties,
+            },
+        ) => first_class == second_class && properties_match(first_properties, second_properties),
+
+        (x, y) => x == y,
+    }
+}
+
+fn properties_match(first: &ObjectProperties, second: &ObjectProperties) -> bool {
+    first.len() == second.len()
+        && first.iter().all(|(key, value)| match second.get(key) {
+            Some(other) => amf0_values_match(value, other),
+            None => false,
+        })
+}
+
+fn amf3_values_match(first: &Amf3Value, second: &Amf3Value) -> bool {
+    match (first, second) {
+        (Amf3Value::Double(x), Amf3Value::Double(y)) => numbers_match(*x, *y),
+        (
+            Amf3Value::Date {
+                unix_time_millis: x,
+            },
+            Amf3Value::Date {
+                unix_time_millis: y,
+            },
+        ) => numbers_match(*x, *y),
+
+        (
+            Amf3Value::Array {
+                associative: first_associative,
+                dense: first_dense,
+            },
+            Amf3Value::Array {
+                associative: second_associative,
+                dense: second_dense,
+            },
+        ) => {
+            first_associative.len() == second_associative.len()
+                && first_associative
+                    .iter()
+                    .all(|(key, value)| match second_associative.get(key) {
+                        Some(other) => amf3_values_match(value, other),
+                        None => false,
+                    })
+                && first_dense.len() == second_dense.len()
+                && first_dense
+                    .iter()
+                    .zip(second_dense.iter())
+                    .all(|(x, y)| amf3_values_match(x, y))
+        }
+
+        (Amf3Value::Object(x), Amf3Value::Object(y)) => {
+            x.class_name == y.class_name
+                && x.is_dynamic == y.is_dynamic
+                && x.sealed_properties.len() == y.sealed_properties.len()
+                && x.sealed_properties
+                    .iter()
+                    .zip(y.sealed_properties.iter())
+                    .all(|((first_key, first_value), (second_key, second_value))| {
+                        first_key == second_key && amf3_values_match(first_value, second_value)
+                    })
+                && x.dynamic_properties.len() == y.dynamic_properties.len()
+                && x.dynamic_properties.iter().all(|(key, value)| {
+                    match y.dynamic_properties.get(key) {
+                        Some(other) => amf3_values_match(value, other),
+                        None => false,
+                    }
+                })
+        }
+
+        (x, y) => x == y,
+    }
+}
+
+fn numbers_match(first: f64, second: f64) -> bool {
+    first == second || (first.is_nan() && second.is_nan())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_values_round_trip() {
+        run_amf0(&Amf0Input::Values(vec![
+            FuzzAmf0Value::Number(f64::NAN),
+            FuzzAmf0Value::Object(vec![(
+                "key".to_string(),
+                FuzzAmf0Value::StrictArray(vec![FuzzAmf0Value::Date {
+                    unix_time_millis: 1_500_000_000_123,
+                    time_zone: 0,
+                }]),
+            )]),
+        ]));
+    }
+}
@@ -0,0 +1,142 @@
+//! RTMP messages sent through the chunk serializer and read back by the chunk deserializer
+
+use crate::tampering::{Link, Tampering};
+use arbitrary::Arbitrary;
+use bytes::Bytes;
+use rml_rtmp::chunk_io::{ChunkDeserializer, ChunkSerializer};
+use rml_rtmp::messages::MessagePayload;
+use rml_rtmp::time::RtmpTimestamp;
+
+/// The type id of the `SetChunkSize` message
+const SET_CHUNK_SIZE_TYPE_ID: u8 = 1;
+
+#[derive(Arbitrary, Debug)]
+pub struct ChunkMessage {
+    pub timestamp: u32,
+    pub type_id: u8,
+    pub message_stream_id: u32,
+    pub data: Vec<u8>,
+    pub force_uncompressed: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct ChunkStreamInput {
+    /// If set, the serializer switches to this chunk size before sending the messages
+    pub max_chunk_size: Option<u16>,
+    pub messages: Vec<ChunkMessage>,
+    pub tampering: Tampering,
+}
+
+/// Serializes the messages and deserializes the resulting chunks, panicking if either side
+/// misbehaves.  Messages that weren't tampered with must come out of the deserializer exactly
+/// as they went in.
+pub fn run(input: &ChunkStreamInput) {
+    let mut serializer = ChunkSerializer::new();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut link = Link::new(&input.tampering);
+    let mut expected = Vec::new();
+    let mut packets = Vec::new();
+
+    if let Some(size) = input.max_chunk_size {
+        let size = size.max(1) as u32;
+        let packet = serializer
+            .set_max_chunk_size(size, RtmpTimestamp::new(0))
+            .unwrap();
+
+        packets.push(packet.bytes);
+        expected.push(None);
+    }
+
+    for message in &input.messages {
+        // Chunk size changes are made through the serializer, since sending one as a plain
+        // message would leave the two sides disagreeing on the chunk size
+        if message.type_id == SET_CHUNK_SIZE_TYPE_ID {
+            continue;
+        }
+
+        let payload = MessagePayload {
+            timestamp: RtmpTimestamp::new(message.timestamp),
+            type_id: message.type_id,
+            message_stream_id: message.message_stream_id,
+            data: Bytes::from(message.data.clone()),
+        };
+
+        let packet = serializer
+            .serialize(&payload, message.force_uncompressed, false)
+            .unwrap();
+
+        packets.push(packet.bytes);
+        expected.push(Some(payload));
+    }
+
+    let mut received = Vec::new();
+    'packets: for packet in &packets {
+        for read in link.transfer(packet) {
+            let mut bytes = &read[..];
+            loop {
+                let payload = match deserializer.get_next_message(bytes) {
+                    Ok(Some(payload)) => payload,
+                    Ok(None) => break,
+                    Err(_) => break 'packets,
+                };
+
+                if payload.type_id == SET_CHUNK_SIZE_TYPE_ID && payload.data.len() >= 4 {
+                    let size = u32::from_be_bytes([
+                        payload.data[0],
+                        payload.data[1],
+                        payload.data[2],
+                        payload.data[3],
+                    ]);
+
+                    if deserializer.set_max_chunk_size(size as usize).is_err() {
+                        break 'packets;
+                    }
+                }
+
+                received.push(payload);
+                bytes = &[];
+            }
+        }
+    }
+
+    if input.tampering.is_clean() {
+        assert_eq!(received.len(), expected.len());
+        for (received, expected) in received.iter().zip(expected.iter()) {
+            if let Some(expected) = expected {
+                assert_eq!(received, expected);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untampered_messages_round_trip() {
+        run(&ChunkStreamInput {
+            max_chunk_size: Some(5),
+            messages: vec![
+                ChunkMessage {
+                    timestamp: 10,
+                    type_id: 9,
+                    message_stream_id: 1,
+                    data: vec![1; 12],
+                    force_uncompressed: false,
+                },
+                ChunkMessage {
+                    timestamp: 0x01ff_ffff,
+                    type_id: 8,
+                    message_stream_id: 1,
+                    data: vec![2; 3],
+                    force_uncompressed: false,
+                },
+            ],
+            tampering: Tampering {
+                corruptions: Vec::new(),
+                read_sizes: vec![3],
+            },
+        });
+    }
+}
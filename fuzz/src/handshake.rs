@@ -0,0 +1,147 @@
+//! Handshakes between the handshake being fuzzed and a well behaved peer
+
+use crate::tampering::{Link, Tampering};
+use arbitrary::Arbitrary;
+use rml_rtmp::handshake::{Handshake, HandshakeError, HandshakeProcessResult, PeerType};
+
+/// The most packets the peers can exchange before the handshake is considered stuck
+const MAX_ROUNDS: usize = 8;
+
+#[derive(Arbitrary, Debug)]
+pub struct HandshakeInput {
+    /// Which side of the handshake is fuzzed.  The other side is played by a handshake that
+    /// isn't tampered with.
+    pub fuzzed_peer_is_server: bool,
+    pub simple_handshake_fallback: bool,
+    pub legacy_quirks: bool,
+
+    /// Applied to the bytes sent to the fuzzed handshake
+    pub tampering: Tampering,
+
+    /// Bytes the peer sends after its side of the handshake, which the fuzzed handshake
+    /// should hand back as left over
+    pub trailing_bytes: Vec<u8>,
+}
+
+/// Runs a handshake, panicking if it misbehaves.  A handshake that hasn't been tampered with
+/// must complete, with any trailing bytes left over.
+pub fn run(input: &HandshakeInput) {
+    let (fuzzed_type, peer_type) = if input.fuzzed_peer_is_server {
+        (PeerType::Server, PeerType::Client)
+    } else {
+        (PeerType::Client, PeerType::Server)
+    };
+
+    let mut fuzzed = Handshake::new(fuzzed_type);
+    fuzzed.set_simple_handshake_fallback(input.simple_handshake_fallback);
+    fuzzed.set_legacy_quirks(input.legacy_quirks);
+
+    let mut peer = Handshake::new(peer_type);
+    let mut link = Link::new(&input.tampering);
+    let mut to_fuzzed = Vec::new();
+    let mut to_peer = Vec::new();
+    if input.fuzzed_peer_is_server {
+        to_fuzzed = peer.generate_outbound_p0_and_p1().unwrap();
+    } else {
+        to_peer = fuzzed.generate_outbound_p0_and_p1().unwrap();
+    }
+
+    let mut peer_is_complete = false;
+    let mut left_over = None;
+    for _ in 0..MAX_ROUNDS {
+        if !to_peer.is_empty() && !peer_is_complete {
+            match peer.process_bytes(&to_peer) {
+                Ok(HandshakeProcessResult::InProgress { response_bytes }) => {
+                    to_fuzzed.extend_from_slice(&response_bytes);
+                }
+
+                Ok(HandshakeProcessResult::Completed { response_bytes, .. }) => {
+                    to_fuzzed.extend_from_slice(&response_bytes);
+                    to_fuzzed.extend_from_slice(&input.trailing_bytes);
+                    peer_is_complete = true;
+                }
+
+                // The peer only gets bytes from the fuzzed handshake, so it can rightfully
+                // reject them if the fuzzed handshake was fed corrupted bytes
+                Err(_) => break,
+            }
+
+            to_peer.clear();
+        }
+
+        if to_fuzzed.is_empty() {
+            break;
+        }
+
+        match feed(&mut fuzzed, link.transfer(&to_fuzzed)) {
+            Ok(Fed::InProgress(response_bytes)) => to_peer = response_bytes,
+            Ok(Fed::Completed(bytes)) => {
+                left_over = Some((bytes, peer_is_complete));
+                break;
+            }
+
+            Err(_) => break,
+        }
+
+        to_fuzzed.clear();
+    }
+
+    if input.tampering.is_clean() {
+        match left_over {
+            None => panic!("Handshake did not complete without tampering"),
+            Some((bytes, true)) => assert_eq!(bytes, input.trailing_bytes),
+            Some((_, false)) => (),
+        }
+    }
+}
+
+enum Fed {
+    InProgress(Vec<u8>),
+
+    /// The handshake completed, with the bytes left over after it
+    Completed(Vec<u8>),
+}
+
+fn feed(handshake: &mut Handshake, reads: Vec<Vec<u8>>) -> Result<Fed, HandshakeError> {
+    let mut response = Vec::new();
+    let mut reads = reads.into_iter();
+    while let Some(read) = reads.next() {
+        match handshake.process_bytes(&read)? {
+            HandshakeProcessResult::InProgress { response_bytes } => {
+                response.extend_from_slice(&response_bytes);
+            }
+
+            HandshakeProcessResult::Completed { completion, .. } => {
+                let mut left_over = completion.remaining_bytes.to_vec();
+                for read in reads {
+                    left_over.extend_from_slice(&read);
+                }
+
+                return Ok(Fed::Completed(left_over));
+            }
+        }
+    }
+
+    Ok(Fed::InProgress(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untampered_handshakes_complete() {
+        for &fuzzed_peer_is_server in &[true, false] {
+            run(&HandshakeInput {
+                fuzzed_peer_is_server,
+                simple_handshake_fallback: false,
+                legacy_quirks: false,
+                tampering: Tampering {
+                    corruptions: Vec::new(),
+                    read_sizes: vec![7, 1500],
+                },
+                trailing_bytes: vec![1, 2, 3],
+            });
+        }
+    }
+}
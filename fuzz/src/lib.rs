@@ -0,0 +1,33 @@
+//! Structured fuzzing harness for the rust media libraries.
+//!
+//! Each module contains an input type that can be generated with the `arbitrary` crate, and a
+//! `run()` function that drives the libraries with it.  The inputs describe well formed
+//! traffic (handshakes between two real peers, serialized RTMP chunks, AMF values, and
+//! conversations between a client and server session) along with a `Tampering` that corrupts
+//! bytes and splits the traffic into reads of arbitrary sizes.  This lets the fuzzer get past
+//! the early validation that random bytes would fail, while still exploring malformed input.
+//!
+//! Any panic raised by `run()` is a bug.  When the traffic has not been tampered with the
+//! harnesses also check that it was understood correctly, such as messages coming out of the
+//! chunk deserializer exactly as they went into the serializer.
+//!
+//! The `cargo fuzz` targets live in the `fuzz` directory of this crate, and are started with
+//! `cargo fuzz run <target>` from this crate's directory.  Inputs that have caused crashes are
+//! kept in the `regressions` directory, in a sub-directory named after their target, and are
+//! replayed by this crate's tests.
+
+pub mod amf;
+pub mod chunk_stream;
+pub mod handshake;
+pub mod session;
+mod tampering;
+
+pub use crate::tampering::{Corruption, Tampering};
+
+use arbitrary::{Arbitrary, Unstructured};
+
+/// Builds a structured input out of the raw bytes a fuzzer produced, the same way the
+/// `fuzz_target!` macro does.  `None` is returned if there weren't enough bytes.
+pub fn structured_input<'a, T: Arbitrary<'a>>(data: &'a [u8]) -> Option<T> {
+    T::arbitrary_take_rest(Unstructured::new(data)).ok()
+}
@@ -0,0 +1,351 @@
+//! Conversations between a client session and a server session
+
+use crate::tampering::{Link, Tampering};
+use arbitrary::Arbitrary;
+use bytes::Bytes;
+use rml_rtmp::sessions::{
+    ClientSession, ClientSessionConfig, ClientSessionResult, PublishRequestType, ServerSession,
+    ServerSessionConfig, ServerSessionEvent, ServerSessionResult, StreamMetadata,
+};
+use rml_rtmp::time::RtmpTimestamp;
+
+/// The most times traffic can go back and forth after an action before the sessions are
+/// considered stuck
+const MAX_EXCHANGES: usize = 32;
+
+#[derive(Arbitrary, Debug)]
+pub enum SessionAction {
+    Connect(String),
+    RequestPublishing(String),
+    RequestPlayback(String),
+    StopPublishing,
+    StopPlayback,
+    PublishMetadata {
+        video_width: Option<u32>,
+        video_height: Option<u32>,
+        video_codec: Option<String>,
+        video_frame_rate: Option<f32>,
+        encoder: Option<String>,
+    },
+    PublishVideo {
+        data: Vec<u8>,
+        timestamp: u32,
+    },
+    PublishAudio {
+        data: Vec<u8>,
+        timestamp: u32,
+    },
+
+    /// Sends video to every stream the server has accepted playback on
+    PlayVideo {
+        data: Vec<u8>,
+        timestamp: u32,
+    },
+    PlayAudio {
+        data: Vec<u8>,
+        timestamp: u32,
+    },
+    ClientPing,
+    ServerPing,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct SessionInput {
+    /// If false, every request the server session raises is left outstanding
+    pub accept_requests: bool,
+    pub client_chunk_size: u16,
+    pub server_chunk_size: u16,
+    pub client_window_ack_size: Option<u32>,
+    pub server_window_ack_size: Option<u32>,
+    pub actions: Vec<SessionAction>,
+    pub client_to_server: Tampering,
+    pub server_to_client: Tampering,
+}
+
+/// Performs the actions on a client session talking to a server session, panicking if either
+/// of them misbehaves.  Neither session may fail to handle traffic that wasn't tampered with.
+pub fn run(input: &SessionInput) {
+    let mut client_config = ClientSessionConfig::new();
+    client_config.chunk_size = input.client_chunk_size.max(1) as u32;
+    if let Some(size) = input.client_window_ack_size {
+        client_config.window_ack_size = size.max(1);
+    }
+
+    let mut server_config = ServerSessionConfig::new();
+    server_config.chunk_size = input.server_chunk_size.max(1) as u32;
+    if let Some(size) = input.server_window_ack_size {
+        server_config.window_ack_size = size.max(1);
+    }
+
+    let (client, client_results) = ClientSession::new(client_config).unwrap();
+    let (server, server_results) = ServerSession::new(server_config).unwrap();
+    let mut conversation = Conversation {
+        client,
+        server,
+        client_to_server: Link::new(&input.client_to_server),
+        server_to_client: Link::new(&input.server_to_client),
+        accept_requests: input.accept_requests,
+        to_server: Vec::new(),
+        to_client: Vec::new(),
+        playing_stream_ids: Vec::new(),
+    };
+
+    conversation.handle_client_results(client_results);
+    conversation.handle_server_results(server_results);
+
+    let is_clean = input.client_to_server.is_clean() && input.server_to_client.is_clean();
+    for action in &input.actions {
+        conversation.perform(action);
+        if let Err(error) = conversation.exchange() {
+            assert!(!is_clean, "Untampered traffic failed: {}", error);
+            return;
+        }
+    }
+}
+
+struct Conversation<'a> {
+    client: ClientSession,
+    server: ServerSession,
+    client_to_server: Link<'a>,
+    server_to_client: Link<'a>,
+    accept_requests: bool,
+    to_server: Vec<Bytes>,
+    to_client: Vec<Bytes>,
+    playing_stream_ids: Vec<u32>,
+}
+
+impl<'a> Conversation<'a> {
+    /// Performs an action.  Actions the sessions refuse to perform, such as publishing before
+    /// connecting, are ignored.
+    fn perform(&mut self, action: &SessionAction) {
+        let result = match action {
+            SessionAction::Connect(app_name) => self.client.request_connection(app_name.clone()),
+            SessionAction::RequestPublishing(stream_key) => self
+                .client
+                .request_publishing(stream_key.clone(), PublishRequestType::Live),
+
+            SessionAction::RequestPlayback(stream_key) => {
+                self.client.request_playback(stream_key.clone())
+            }
+
+            SessionAction::StopPublishing => {
+                if let Ok(results) = self.client.stop_publishing() {
+                    self.handle_client_results(results);
+                }
+
+                return;
+            }
+
+            SessionAction::StopPlayback => {
+                if let Ok(results) = self.client.stop_playback() {
+                    self.handle_client_results(results);
+                }
+
+                return;
+            }
+
+            SessionAction::PublishMetadata {
+                video_width,
+                video_height,
+                video_codec,
+                video_frame_rate,
+                encoder,
+            } => {
+                let mut metadata = StreamMetadata::new();
+                metadata.video_width = *video_width;
+                metadata.video_height = *video_height;
+                metadata.video_codec = video_codec.clone();
+                metadata.video_frame_rate = *video_frame_rate;
+                metadata.encoder = encoder.clone();
+                self.client.publish_metadata(&metadata)
+            }
+
+            SessionAction::PublishVideo { data, timestamp } => self.client.publish_video_data(
+                Bytes::from(data.clone()),
+                RtmpTimestamp::new(*timestamp),
+                false,
+            ),
+
+            SessionAction::PublishAudio { data, timestamp } => self.client.publish_audio_data(
+                Bytes::from(data.clone()),
+                RtmpTimestamp::new(*timestamp),
+                false,
+            ),
+
+            SessionAction::PlayVideo { data, timestamp } => {
+                for stream_id in self.playing_stream_ids.clone() {
+                    let packet = self
+                        .server
+                        .send_video_data(
+                            stream_id,
+                            Bytes::from(data.clone()),
+                            RtmpTimestamp::new(*timestamp),
+                            false,
+                        )
+                        .unwrap();
+
+                    self.to_client.push(packet.bytes);
+                }
+
+                return;
+            }
+
+            SessionAction::PlayAudio { data, timestamp } => {
+                for stream_id in self.playing_stream_ids.clone() {
+                    let packet = self
+                        .server
+                        .send_audio_data(
+                            stream_id,
+                            Bytes::from(data.clone()),
+                            RtmpTimestamp::new(*timestamp),
+                            false,
+                        )
+                        .unwrap();
+
+                    self.to_client.push(packet.bytes);
+                }
+
+                return;
+            }
+
+            SessionAction::ClientPing => {
+                if let Ok((packet, _)) = self.client.send_ping_request() {
+                    self.to_server.push(packet.bytes);
+                }
+
+                return;
+            }
+
+            SessionAction::ServerPing => {
+                if let Ok((packet, _)) = self.server.send_ping_request() {
+                    self.to_client.push(packet.bytes);
+                }
+
+                return;
+            }
+        };
+
+        if let Ok(result) = result {
+            self.handle_client_results(vec![result]);
+        }
+    }
+
+    /// Delivers traffic between the sessions until neither has anything left to send
+    fn exchange(&mut self) -> Result<(), String> {
+        for _ in 0..MAX_EXCHANGES {
+            if self.to_server.is_empty() && self.to_client.is_empty() {
+                return Ok(());
+            }
+
+            for packet in std::mem::take(&mut self.to_server) {
+                for read in self.client_to_server.transfer(&packet) {
+                    let results = self
+                        .server
+                        .handle_input(&read)
+                        .map_err(|error| format!("Server session failed: {}", error))?;
+
+                    self.handle_server_results(results);
+                }
+            }
+
+            for packet in std::mem::take(&mut self.to_client) {
+                for read in self.server_to_client.transfer(&packet) {
+                    let results = self
+                        .client
+                        .handle_input(&read)
+                        .map_err(|error| format!("Client session failed: {}", error))?;
+
+                    self.handle_client_results(results);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_client_results(&mut self, results: Vec<ClientSessionResult>) {
+        for result in results {
+            if let ClientSessionResult::OutboundResponse(packet) = result {
+                self.to_server.push(packet.bytes);
+            }
+        }
+    }
+
+    fn handle_server_results(&mut self, results: Vec<ServerSessionResult>) {
+        let mut requests = Vec::new();
+        for result in results {
+            match result {
+                ServerSessionResult::OutboundResponse(packet) => self.to_client.push(packet.bytes),
+                ServerSessionResult::RaisedEvent(event) => match event {
+                    ServerSessionEvent::ConnectionRequested { request_id, .. }
+                    | ServerSessionEvent::PublishStreamRequested { request_id, .. } => {
+                        requests.push(request_id);
+                    }
+
+                    ServerSessionEvent::PlayStreamRequested {
+                        request_id,
+                        stream_id,
+                        ..
+                    } => {
+                        requests.push(request_id);
+                        if self.accept_requests {
+                            self.playing_stream_ids.push(stream_id);
+                        }
+                    }
+
+                    _ => (),
+                },
+
+                ServerSessionResult::UnhandleableMessageReceived(_) => (),
+            }
+        }
+
+        if self.accept_requests {
+            for request_id in requests {
+                if let Ok(results) = self.server.accept_request(request_id) {
+                    self.handle_server_results(results);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untampered_publish_and_playback() {
+        let video = SessionAction::PublishVideo {
+            data: vec![0x17, 1, 0, 0, 0],
+            timestamp: 33,
+        };
+
+        let played_video = SessionAction::PlayVideo {
+            data: vec![0x17, 1, 0, 0, 0],
+            timestamp: 33,
+        };
+
+        run(&SessionInput {
+            accept_requests: true,
+            client_chunk_size: 3,
+            server_chunk_size: 4096,
+            client_window_ack_size: Some(10),
+            server_window_ack_size: Some(10),
+            actions: vec![
+                SessionAction::Connect("live".to_string()),
+                SessionAction::RequestPublishing("key".to_string()),
+                video,
+                SessionAction::StopPublishing,
+                SessionAction::RequestPlayback("key".to_string()),
+                played_video,
+                SessionAction::ServerPing,
+            ],
+            client_to_server: Tampering::new(),
+            server_to_client: Tampering {
+                corruptions: Vec::new(),
+                read_sizes: vec![1],
+            },
+        });
+    }
+}
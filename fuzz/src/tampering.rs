@@ -0,0 +1,89 @@
+use arbitrary::Arbitrary;
+
+/// Flips bits of the byte at an offset into the traffic
+#[derive(Arbitrary, Debug, Clone)]
+pub struct Corruption {
+    pub offset: u16,
+    pub mask: u8,
+}
+
+/// Describes how traffic between two peers is damaged and broken up before it's read
+#[derive(Arbitrary, Debug, Clone)]
+pub struct Tampering {
+    pub corruptions: Vec<Corruption>,
+
+    /// The size of each read the traffic is split into.  The sizes are reused from the start
+    /// once they run out, and a size of zero reads a single byte.  When no sizes are given the
+    /// traffic is read as the packets it was sent in.
+    pub read_sizes: Vec<u16>,
+}
+
+impl Tampering {
+    /// Creates a tampering that leaves the traffic intact and reads it one packet at a time
+    pub fn new() -> Tampering {
+        Tampering {
+            corruptions: Vec::new(),
+            read_sizes: Vec::new(),
+        }
+    }
+
+    /// True if none of the traffic's bytes are changed
+    pub fn is_clean(&self) -> bool {
+        self.corruptions
+            .iter()
+            .all(|corruption| corruption.mask == 0)
+    }
+}
+
+impl Default for Tampering {
+    fn default() -> Self {
+        Tampering::new()
+    }
+}
+
+/// Carries traffic in one direction, applying a tampering to it
+pub(crate) struct Link<'a> {
+    tampering: &'a Tampering,
+    position: usize,
+    read_index: usize,
+}
+
+impl<'a> Link<'a> {
+    pub fn new(tampering: &'a Tampering) -> Link<'a> {
+        Link {
+            tampering,
+            position: 0,
+            read_index: 0,
+        }
+    }
+
+    /// Corrupts the bytes of a packet and splits them into the reads the receiver sees
+    pub fn transfer(&mut self, packet: &[u8]) -> Vec<Vec<u8>> {
+        let mut bytes = packet.to_vec();
+        let end = self.position + bytes.len();
+        for corruption in &self.tampering.corruptions {
+            let offset = corruption.offset as usize;
+            if offset >= self.position && offset < end {
+                bytes[offset - self.position] ^= corruption.mask;
+            }
+        }
+
+        self.position = end;
+        if self.tampering.read_sizes.is_empty() {
+            return vec![bytes];
+        }
+
+        let mut reads = Vec::new();
+        let mut remaining = &bytes[..];
+        while !remaining.is_empty() {
+            let sizes = &self.tampering.read_sizes;
+            let size = (sizes[self.read_index % sizes.len()] as usize).clamp(1, remaining.len());
+            self.read_index += 1;
+
+            reads.push(remaining[..size].to_vec());
+            remaining = &remaining[size..];
+        }
+
+        reads
+    }
+}
@@ -0,0 +1,73 @@
+//! Replays inputs that have caused crashes while fuzzing.  Each sub-directory of `regressions`
+//! holds the raw fuzzer inputs of the target it's named after, so a crash can be kept from
+//! coming back by copying the artifact `cargo fuzz` saved for it into the matching directory.
+
+use rml_fuzz::amf::{self, Amf0Input};
+use rml_fuzz::chunk_stream::{self, ChunkStreamInput};
+use rml_fuzz::handshake::{self, HandshakeInput};
+use rml_fuzz::session::{self, SessionInput};
+use rml_fuzz::structured_input;
+use std::fs;
+use std::path::Path;
+
+fn replay(target: &str, run: impl Fn(&[u8])) {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("regressions")
+        .join(target);
+
+    let entries = match fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries {
+        let path = entry.unwrap().path();
+        if path.file_name().is_none_or(|name| name == ".gitkeep") {
+            continue;
+        }
+
+        println!("Replaying {}", path.display());
+        run(&fs::read(&path).unwrap());
+    }
+}
+
+#[test]
+fn handshake_regressions() {
+    replay("handshake", |data| {
+        if let Some(input) = structured_input::<HandshakeInput>(data) {
+            handshake::run(&input);
+        }
+    });
+}
+
+#[test]
+fn chunk_stream_regressions() {
+    replay("chunk_stream", |data| {
+        if let Some(input) = structured_input::<ChunkStreamInput>(data) {
+            chunk_stream::run(&input);
+        }
+    });
+}
+
+#[test]
+fn amf0_regressions() {
+    replay("amf0", |data| {
+        if let Some(input) = structured_input::<Amf0Input>(data) {
+            amf::run_amf0(&input);
+        }
+    });
+}
+
+#[test]
+fn amf3_regressions() {
+    replay("amf3", amf::run_amf3);
+}
+
+#[test]
+fn session_regressions() {
+    replay("session", |data| {
+        if let Some(input) = structured_input::<SessionInput>(data) {
+            session::run(&input);
+        }
+    });
+}
@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+/// Where the segmenter stores segments and playlists
+#[derive(PartialEq, Debug, Clone)]
+pub enum HlsOutput {
+    /// Files are kept in memory and retrieved with `HlsSegmenter::get_file()`
+    Memory,
+
+    /// Files are written to (and expired segments deleted from) the specified directory
+    Directory(PathBuf),
+}
+
+/// Configuration options for an HLS segmenter
+#[derive(PartialEq, Debug, Clone)]
+pub struct HlsConfig {
+    /// The duration segments should be cut at.  Segments can only start on keyframes, so they
+    /// will be longer if keyframes are further apart than this.
+    pub target_segment_duration_ms: u32,
+
+    /// The number of segments listed in the media playlist
+    pub playlist_segment_count: usize,
+
+    /// Enables Low-Latency HLS, splitting segments into parts of up to this duration.  Values
+    /// between 200 and 1000 milliseconds are typical.
+    pub part_target_duration_ms: Option<u32>,
+
+    pub output: HlsOutput,
+}
+
+impl HlsConfig {
+    /// Creates a new HLS config with overridable defaults
+    pub fn new() -> HlsConfig {
+        HlsConfig {
+            target_segment_duration_ms: 6000,
+            playlist_segment_count: 6,
+            part_target_duration_ms: None,
+            output: HlsOutput::Memory,
+        }
+    }
+}
+
+impl Default for HlsConfig {
+    fn default() -> Self {
+        HlsConfig::new()
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use {HlsSegment, INIT_SEGMENT_NAME};
+
+/// The contents of a DASH manifest describing the same segments as the HLS media playlist
+pub(crate) struct DashManifest<'a> {
+    pub segments: Vec<&'a HlsSegment>,
+    pub codecs: &'a [String],
+    pub bandwidth: u64,
+    pub resolution: Option<(u16, u16)>,
+    pub target_duration_ms: u64,
+
+    /// The wall clock time the stream's first sample would have been available at
+    pub availability_start_time: SystemTime,
+
+    pub publish_time: SystemTime,
+    pub is_finished: bool,
+}
+
+impl<'a> DashManifest<'a> {
+    pub fn render(&self) -> String {
+        let start_number = self.segments.first().map_or(1, |x| x.sequence_number);
+        let listed_duration_ms = self.segments.iter().map(|x| x.duration_ms).sum::<u64>();
+
+        let mut manifest = String::new();
+        manifest.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        manifest.push_str("<MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\"");
+        manifest.push_str(" profiles=\"urn:mpeg:dash:profile:isoff-live:2011\"");
+
+        if self.is_finished {
+            let end_ms = self
+                .segments
+                .last()
+                .map_or(0, |x| x.start_time_ms + x.duration_ms);
+
+            let _ = write!(
+                manifest,
+                " type=\"static\" mediaPresentationDuration=\"{}\"",
+                format_duration(end_ms)
+            );
+        } else {
+            let _ = write!(
+                manifest,
+                " type=\"dynamic\" availabilityStartTime=\"{}\" publishTime=\"{}\" \
+                 minimumUpdatePeriod=\"{}\" timeShiftBufferDepth=\"{}\" \
+                 suggestedPresentationDelay=\"{}\"",
+                format_utc(self.availability_start_time),
+                format_utc(self.publish_time),
+                format_duration(self.target_duration_ms),
+                format_duration(listed_duration_ms),
+                format_duration(self.target_duration_ms * 3),
+            );
+        }
+
+        let _ = writeln!(
+            manifest,
+            " minBufferTime=\"{}\">",
+            format_duration(self.target_duration_ms)
+        );
+
+        manifest.push_str("  <Period id=\"0\" start=\"PT0S\">\n");
+        manifest.push_str(
+            "    <AdaptationSet mimeType=\"video/mp4\" segmentAlignment=\"true\" startWithSAP=\"1\">\n",
+        );
+
+        let _ = write!(
+            manifest,
+            "      <Representation id=\"0\" codecs=\"{}\" bandwidth=\"{}\"",
+            self.codecs.join(","),
+            self.bandwidth
+        );
+
+        if let Some((width, height)) = self.resolution {
+            let _ = write!(manifest, " width=\"{}\" height=\"{}\"", width, height);
+        }
+
+        manifest.push_str(">\n");
+        let _ = writeln!(
+            manifest,
+            "        <SegmentTemplate timescale=\"1000\" initialization=\"{}\" \
+             media=\"segment$Number$.m4s\" startNumber=\"{}\">",
+            INIT_SEGMENT_NAME, start_number
+        );
+
+        manifest.push_str("          <SegmentTimeline>\n");
+        for segment in &self.segments {
+            let _ = writeln!(
+                manifest,
+                "            <S t=\"{}\" d=\"{}\"/>",
+                segment.start_time_ms, segment.duration_ms
+            );
+        }
+
+        manifest.push_str("          </SegmentTimeline>\n");
+        manifest.push_str("        </SegmentTemplate>\n");
+        manifest.push_str("      </Representation>\n");
+        manifest.push_str("    </AdaptationSet>\n");
+        manifest.push_str("  </Period>\n");
+        manifest.push_str("</MPD>\n");
+        manifest
+    }
+}
+
+fn format_duration(milliseconds: u64) -> String {
+    format!("PT{}.{:03}S", milliseconds / 1000, milliseconds % 1000)
+}
+
+/// Formats a time as an ISO 8601 UTC date and time, such as `2021-03-04T05:06:07.890Z`
+fn format_utc(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let days = (seconds / 86_400) as i64;
+    let seconds_of_day = seconds % 86_400;
+
+    // Converts days since the epoch to a civil date, from Howard Hinnant's date algorithms
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::time::Duration;
+
+    fn segment(sequence_number: u32, start_time_ms: u64, duration_ms: u64) -> HlsSegment {
+        HlsSegment {
+            sequence_number,
+            name: format!("segment{}.m4s", sequence_number),
+            start_time_ms,
+            duration_ms,
+            data: Bytes::new(),
+            parts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn utc_times_are_formatted_as_iso_8601() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_614_834_367_890);
+
+        assert_eq!(format_utc(time), "2021-03-04T05:06:07.890Z");
+        assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn live_manifest_has_timeline_of_listed_segments() {
+        let segments = [segment(4, 6000, 2000), segment(5, 8000, 2500)];
+        let codecs = vec!["avc1.64001f".to_string(), "mp4a.40.2".to_string()];
+        let manifest = DashManifest {
+            segments: segments.iter().collect(),
+            codecs: &codecs,
+            bandwidth: 1_000_000,
+            resolution: Some((1280, 720)),
+            target_duration_ms: 2000,
+            availability_start_time: UNIX_EPOCH,
+            publish_time: UNIX_EPOCH + Duration::from_secs(11),
+            is_finished: false,
+        };
+
+        let manifest = manifest.render();
+
+        assert!(manifest
+            .contains(" type=\"dynamic\" availabilityStartTime=\"1970-01-01T00:00:00.000Z\""));
+        assert!(manifest.contains(" timeShiftBufferDepth=\"PT4.500S\""));
+        assert!(manifest.contains(" codecs=\"avc1.64001f,mp4a.40.2\" bandwidth=\"1000000\" width=\"1280\" height=\"720\">"));
+        assert!(manifest.contains(" startNumber=\"4\">"));
+        assert!(manifest
+            .contains("<S t=\"6000\" d=\"2000\"/>\n            <S t=\"8000\" d=\"2500\"/>\n"));
+    }
+
+    #[test]
+    fn finished_manifest_is_static() {
+        let segments = [segment(1, 0, 2000)];
+        let codecs = vec!["mp4a.40.2".to_string()];
+        let manifest = DashManifest {
+            segments: segments.iter().collect(),
+            codecs: &codecs,
+            bandwidth: 128_000,
+            resolution: None,
+            target_duration_ms: 2000,
+            availability_start_time: UNIX_EPOCH,
+            publish_time: UNIX_EPOCH,
+            is_finished: true,
+        };
+
+        let manifest = manifest.render();
+
+        assert!(manifest.contains(" type=\"static\" mediaPresentationDuration=\"PT2.000S\""));
+        assert!(!manifest.contains("availabilityStartTime"));
+    }
+}
@@ -0,0 +1,15 @@
+use rml_fmp4::Fmp4Error;
+use std::io;
+use thiserror::Error;
+
+/// Errors that can occur while packaging a stream for HLS
+#[derive(Debug, Error)]
+pub enum HlsError {
+    /// The audio or video could not be packaged into fragmented MP4
+    #[error("Failed to package media: {0}")]
+    Muxing(#[from] Fmp4Error),
+
+    /// A segment or playlist could not be written to the output directory
+    #[error("Failed to write HLS output: {0}")]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,66 @@
+//! This crate packages streams received over RTMP for delivery with HTTP Live Streaming (HLS).
+//!
+//! `HlsSegmenter` takes the audio and video payloads of a published stream, cuts them into
+//! fragmented MP4 segments on keyframe boundaries, and maintains the playlists players use to
+//! find them.  It does no networking itself, so segments and playlists can be served by
+//! whichever HTTP server the application already uses, either from memory or from a directory.
+//! The segments are also described by a DASH manifest, so the same output can serve DASH
+//! players.
+//!
+//! # Examples
+//! ```
+//! extern crate bytes;
+//! extern crate rml_hls;
+//! extern crate rml_rtmp;
+//!
+//! use bytes::Bytes;
+//! use rml_hls::{HlsConfig, HlsSegmenter};
+//! use rml_rtmp::time::RtmpTimestamp;
+//!
+//! let mut config = HlsConfig::new();
+//! config.target_segment_duration_ms = 2000;
+//! let mut segmenter = HlsSegmenter::new(config).unwrap();
+//!
+//! let sequence_header = vec![0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 0];
+//! let keyframe = vec![0x17, 1, 0, 0, 0, 0, 0, 0, 1, 0x65];
+//! segmenter.push_video(&Bytes::from(sequence_header), RtmpTimestamp::new(0)).unwrap();
+//! segmenter.push_video(&Bytes::from(keyframe.clone()), RtmpTimestamp::new(0)).unwrap();
+//!
+//! // The next keyframe after 2 seconds completes the first segment
+//! let segment = segmenter.push_video(&Bytes::from(keyframe), RtmpTimestamp::new(2000))
+//!     .unwrap()
+//!     .unwrap();
+//!
+//! assert_eq!(segment.duration_ms, 2000);
+//! assert!(segmenter.media_playlist().contains(&segment.name));
+//! assert_eq!(segmenter.get_file(&segment.name), Some(segment.data));
+//! ```
+
+extern crate bytes;
+extern crate rml_flv;
+extern crate rml_fmp4;
+extern crate rml_rtmp;
+extern crate thiserror;
+
+mod config;
+mod dash;
+mod errors;
+mod playlist;
+mod segmenter;
+
+pub use config::{HlsConfig, HlsOutput};
+pub use errors::HlsError;
+pub use playlist::BlockingReloadRequest;
+pub use segmenter::{HlsPart, HlsSegment, HlsSegmenter};
+
+/// The file name of the init segment, which the media playlist points players to
+pub const INIT_SEGMENT_NAME: &str = "init.mp4";
+
+/// The file name of the media playlist listing the most recent segments
+pub const MEDIA_PLAYLIST_NAME: &str = "index.m3u8";
+
+/// The file name of the master playlist, which describes the stream's codecs and bandwidth
+pub const MASTER_PLAYLIST_NAME: &str = "master.m3u8";
+
+/// The file name of the DASH manifest describing the same segments as the media playlist
+pub const DASH_MANIFEST_NAME: &str = "manifest.mpd";
@@ -0,0 +1,276 @@
+use std::fmt::Write;
+use {HlsPart, HlsSegment, INIT_SEGMENT_NAME, MEDIA_PLAYLIST_NAME};
+
+/// A low latency player's request for a playlist that contains a specific segment or part.
+///
+/// Players add `_HLS_msn` (and optionally `_HLS_part`) to the media playlist's query string,
+/// and the server is expected to hold the response until the playlist contains what was asked
+/// for (see `HlsSegmenter::can_serve()`).
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct BlockingReloadRequest {
+    pub media_sequence_number: u32,
+    pub part: Option<u32>,
+}
+
+impl BlockingReloadRequest {
+    /// Parses the request from a playlist URL's query string (without the leading `?`),
+    /// returning `None` if it doesn't contain `_HLS_msn`
+    pub fn from_query(query: &str) -> Option<BlockingReloadRequest> {
+        let mut media_sequence_number = None;
+        let mut part = None;
+        for pair in query.split('&') {
+            let mut parts = pair.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("_HLS_msn"), Some(value)) => media_sequence_number = value.parse().ok(),
+                (Some("_HLS_part"), Some(value)) => part = value.parse().ok(),
+                _ => (),
+            }
+        }
+
+        Some(BlockingReloadRequest {
+            media_sequence_number: media_sequence_number?,
+            part,
+        })
+    }
+}
+
+/// Partial segment information for low latency playlists
+pub(crate) struct PartialSegments<'a> {
+    pub part_target_duration_ms: u32,
+
+    /// Parts of the segment currently being built
+    pub current_parts: &'a [HlsPart],
+
+    /// The name the next part will be available at
+    pub next_part_name: String,
+}
+
+/// The contents of a media playlist
+pub(crate) struct MediaPlaylist<'a> {
+    pub segments: Vec<&'a HlsSegment>,
+
+    /// Used as the media sequence when no segments have been completed
+    pub next_sequence_number: u32,
+
+    pub target_duration_ms: u64,
+    pub is_finished: bool,
+    pub partial_segments: Option<PartialSegments<'a>>,
+}
+
+impl<'a> MediaPlaylist<'a> {
+    pub fn render(&self) -> String {
+        let media_sequence = self
+            .segments
+            .first()
+            .map_or(self.next_sequence_number, |x| x.sequence_number);
+
+        let mut playlist = String::new();
+        playlist.push_str("#EXTM3U\n");
+        playlist.push_str("#EXT-X-VERSION:7\n");
+        let _ = writeln!(
+            playlist,
+            "#EXT-X-TARGETDURATION:{}",
+            self.target_duration_ms.div_ceil(1000)
+        );
+
+        if let Some(ref partial) = self.partial_segments {
+            // Players must stay at least 3 part durations behind the end of the playlist
+            let part_target = partial.part_target_duration_ms as f64 / 1000.0;
+            let _ = writeln!(
+                playlist,
+                "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK={:.3}",
+                part_target * 3.0
+            );
+
+            let _ = writeln!(playlist, "#EXT-X-PART-INF:PART-TARGET={:.3}", part_target);
+        }
+
+        let _ = writeln!(playlist, "#EXT-X-MEDIA-SEQUENCE:{}", media_sequence);
+        playlist.push_str("#EXT-X-INDEPENDENT-SEGMENTS\n");
+        let _ = writeln!(playlist, "#EXT-X-MAP:URI=\"{}\"", INIT_SEGMENT_NAME);
+
+        for segment in &self.segments {
+            write_parts(&mut playlist, &segment.parts);
+            let _ = writeln!(
+                playlist,
+                "#EXTINF:{:.3},\n{}",
+                segment.duration_ms as f64 / 1000.0,
+                segment.name
+            );
+        }
+
+        if let Some(ref partial) = self.partial_segments {
+            write_parts(&mut playlist, partial.current_parts);
+            if !self.is_finished {
+                let _ = writeln!(
+                    playlist,
+                    "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"{}\"",
+                    partial.next_part_name
+                );
+            }
+        }
+
+        if self.is_finished {
+            playlist.push_str("#EXT-X-ENDLIST\n");
+        }
+
+        playlist
+    }
+}
+
+fn write_parts(playlist: &mut String, parts: &[HlsPart]) {
+    for part in parts {
+        let _ = write!(
+            playlist,
+            "#EXT-X-PART:DURATION={:.3},URI=\"{}\"",
+            part.duration_ms as f64 / 1000.0,
+            part.name
+        );
+
+        if part.is_independent {
+            playlist.push_str(",INDEPENDENT=YES");
+        }
+
+        playlist.push('\n');
+    }
+}
+
+/// Renders a master playlist with the media playlist as its only variant
+pub(crate) fn master_playlist(
+    bandwidth: u64,
+    codecs: &[String],
+    resolution: Option<(u16, u16)>,
+) -> String {
+    let mut playlist = String::new();
+    playlist.push_str("#EXTM3U\n");
+    playlist.push_str("#EXT-X-VERSION:7\n");
+    playlist.push_str("#EXT-X-INDEPENDENT-SEGMENTS\n");
+    let _ = write!(
+        playlist,
+        "#EXT-X-STREAM-INF:BANDWIDTH={},CODECS=\"{}\"",
+        bandwidth,
+        codecs.join(",")
+    );
+
+    if let Some((width, height)) = resolution {
+        let _ = write!(playlist, ",RESOLUTION={}x{}", width, height);
+    }
+
+    let _ = writeln!(playlist, "\n{}", MEDIA_PLAYLIST_NAME);
+    playlist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn segment(sequence_number: u32, duration_ms: u64) -> HlsSegment {
+        HlsSegment {
+            sequence_number,
+            name: format!("segment{}.m4s", sequence_number),
+            start_time_ms: 0,
+            duration_ms,
+            data: Bytes::new(),
+            parts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn media_playlist_lists_segments_from_first_sequence_number() {
+        let segments = [segment(3, 6000), segment(4, 6500)];
+        let playlist = MediaPlaylist {
+            segments: segments.iter().collect(),
+            next_sequence_number: 5,
+            target_duration_ms: 6500,
+            is_finished: true,
+            partial_segments: None,
+        };
+
+        let playlist = playlist.render();
+
+        let expected = "#EXTM3U\n\
+                        #EXT-X-VERSION:7\n\
+                        #EXT-X-TARGETDURATION:7\n\
+                        #EXT-X-MEDIA-SEQUENCE:3\n\
+                        #EXT-X-INDEPENDENT-SEGMENTS\n\
+                        #EXT-X-MAP:URI=\"init.mp4\"\n\
+                        #EXTINF:6.000,\n\
+                        segment3.m4s\n\
+                        #EXTINF:6.500,\n\
+                        segment4.m4s\n\
+                        #EXT-X-ENDLIST\n";
+
+        assert_eq!(playlist, expected);
+    }
+
+    #[test]
+    fn can_parse_blocking_reload_query() {
+        let request = BlockingReloadRequest::from_query("a=b&_HLS_part=3&_HLS_msn=27");
+
+        let expected = BlockingReloadRequest {
+            media_sequence_number: 27,
+            part: Some(3),
+        };
+
+        assert_eq!(request, Some(expected));
+        assert_eq!(BlockingReloadRequest::from_query("_HLS_part=3"), None);
+    }
+
+    #[test]
+    fn low_latency_playlist_lists_parts_and_preload_hint() {
+        let part = |name: &str, is_independent| HlsPart {
+            name: name.to_string(),
+            duration_ms: 500,
+            is_independent,
+            data: Bytes::new(),
+        };
+
+        let mut completed = segment(1, 1000);
+        completed.parts = vec![part("segment1.0.m4s", true), part("segment1.1.m4s", false)];
+        let current_parts = vec![part("segment2.0.m4s", true)];
+        let playlist = MediaPlaylist {
+            segments: vec![&completed],
+            next_sequence_number: 2,
+            target_duration_ms: 1000,
+            is_finished: false,
+            partial_segments: Some(PartialSegments {
+                part_target_duration_ms: 500,
+                current_parts: &current_parts,
+                next_part_name: "segment2.1.m4s".to_string(),
+            }),
+        };
+
+        let expected = "#EXTM3U\n\
+                        #EXT-X-VERSION:7\n\
+                        #EXT-X-TARGETDURATION:1\n\
+                        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.500\n\
+                        #EXT-X-PART-INF:PART-TARGET=0.500\n\
+                        #EXT-X-MEDIA-SEQUENCE:1\n\
+                        #EXT-X-INDEPENDENT-SEGMENTS\n\
+                        #EXT-X-MAP:URI=\"init.mp4\"\n\
+                        #EXT-X-PART:DURATION=0.500,URI=\"segment1.0.m4s\",INDEPENDENT=YES\n\
+                        #EXT-X-PART:DURATION=0.500,URI=\"segment1.1.m4s\"\n\
+                        #EXTINF:1.000,\n\
+                        segment1.m4s\n\
+                        #EXT-X-PART:DURATION=0.500,URI=\"segment2.0.m4s\",INDEPENDENT=YES\n\
+                        #EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"segment2.1.m4s\"\n";
+
+        assert_eq!(playlist.render(), expected);
+    }
+
+    #[test]
+    fn master_playlist_includes_codecs_and_resolution() {
+        let codecs = vec!["avc1.64001f".to_string(), "mp4a.40.2".to_string()];
+
+        let playlist = master_playlist(2_500_000, &codecs, Some((1280, 720)));
+
+        let expected = "#EXTM3U\n\
+                        #EXT-X-VERSION:7\n\
+                        #EXT-X-INDEPENDENT-SEGMENTS\n\
+                        #EXT-X-STREAM-INF:BANDWIDTH=2500000,CODECS=\"avc1.64001f,mp4a.40.2\",RESOLUTION=1280x720\n\
+                        index.m3u8\n";
+
+        assert_eq!(playlist, expected);
+    }
+}
@@ -0,0 +1,750 @@
+use bytes::Bytes;
+use config::{HlsConfig, HlsOutput};
+use dash::DashManifest;
+use errors::HlsError;
+use playlist::{self, BlockingReloadRequest, MediaPlaylist, PartialSegments};
+use rml_flv::{AudioTagHeader, VideoTagHeader};
+use rml_fmp4::{Fmp4Muxer, MediaSegment};
+use rml_rtmp::pipeline::{MediaItem, MediaSink};
+use rml_rtmp::time::{RtmpTimestamp, TimestampRebaser};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use {DASH_MANIFEST_NAME, INIT_SEGMENT_NAME, MASTER_PLAYLIST_NAME, MEDIA_PLAYLIST_NAME};
+
+/// A completed media segment
+#[derive(PartialEq, Debug, Clone)]
+pub struct HlsSegment {
+    pub sequence_number: u32,
+
+    /// The file name the segment is listed under in the media playlist
+    pub name: String,
+
+    /// Decode time of the segment's first sample, in milliseconds since the start of the
+    /// stream
+    pub start_time_ms: u64,
+
+    pub duration_ms: u64,
+    pub data: Bytes,
+
+    /// The partial segments the segment was built from, if low latency is enabled.  These are
+    /// only kept for the most recent segments, since players only use them near the end of the
+    /// playlist.
+    pub parts: Vec<HlsPart>,
+}
+
+/// A portion of a segment that low latency players can download before the segment has been
+/// completed
+#[derive(PartialEq, Debug, Clone)]
+pub struct HlsPart {
+    /// The file name the part is listed under in the media playlist
+    pub name: String,
+
+    pub duration_ms: u64,
+
+    /// True if the part starts with a keyframe, so players can start playback from it
+    pub is_independent: bool,
+
+    pub data: Bytes,
+}
+
+/// The number of most recent segments whose parts are kept and listed in the playlist
+const SEGMENTS_WITH_PARTS: usize = 2;
+
+/// Packages the audio and video of a published stream into fragmented MP4 segments, and
+/// maintains a rolling media playlist (along with a master playlist) listing the most recent
+/// of them.
+///
+/// Audio and video payloads are passed in as they are received from the publisher.  Segments
+/// are cut on the first keyframe after the target duration has passed (or on any audio frame
+/// for streams without video), and each completed segment is returned so it can be handed to
+/// whatever is serving the stream.  Depending on the configured output, segments and playlists
+/// are either kept in memory for `get_file()` or written to a directory.
+///
+/// If a part target duration is configured, segments are also split into partial segments for
+/// Low-Latency HLS.  Parts are cut on any frame once the part target duration has passed, and
+/// the media playlist lists them (along with a preload hint for the next one) so players can
+/// start downloading a segment before it's completed.  Players waiting for a specific part use
+/// blocking playlist reloads, which `can_serve()` helps HTTP servers implement.
+///
+/// The same segments are also described by a DASH manifest, so DASH players can be served
+/// without packaging the stream a second time.
+///
+/// Timestamps are rebased so the stream starts at zero and jumps in the publisher's timestamps
+/// (such as from an encoder restarting) don't create segments with bogus durations.  Media
+/// received before the first keyframe is dropped, so the first segment is playable on its own.  The init segment is created from the sequence headers received before the first
+/// segment is cut, so publishers changing codec settings mid-stream are not supported.
+pub struct HlsSegmenter {
+    config: HlsConfig,
+    muxer: Fmp4Muxer,
+    rebaser: TimestampRebaser,
+    init_segment: Option<Bytes>,
+    segments: VecDeque<HlsSegment>,
+    segment_start: Option<RtmpTimestamp>,
+    part_start: Option<RtmpTimestamp>,
+    last_sample_timestamp: Option<RtmpTimestamp>,
+    current_parts: Vec<HlsPart>,
+    current_segment_start_ms: u64,
+    next_sequence_number: u32,
+    availability_start_time: Option<SystemTime>,
+    expects_video: bool,
+    video_dimensions: Option<(u16, u16)>,
+    max_segment_duration_ms: u64,
+    peak_bandwidth: u64,
+    is_finished: bool,
+}
+
+impl HlsSegmenter {
+    /// Creates a segmenter, creating the output directory if one was configured
+    pub fn new(config: HlsConfig) -> Result<HlsSegmenter, HlsError> {
+        if let HlsOutput::Directory(ref path) = config.output {
+            fs::create_dir_all(path)?;
+        }
+
+        Ok(HlsSegmenter {
+            max_segment_duration_ms: config.target_segment_duration_ms as u64,
+            config,
+            muxer: Fmp4Muxer::new(),
+            rebaser: TimestampRebaser::new(),
+            init_segment: None,
+            segments: VecDeque::new(),
+            segment_start: None,
+            part_start: None,
+            last_sample_timestamp: None,
+            current_parts: Vec::new(),
+            current_segment_start_ms: 0,
+            next_sequence_number: 1,
+            availability_start_time: None,
+            expects_video: false,
+            video_dimensions: None,
+            peak_bandwidth: 0,
+            is_finished: false,
+        })
+    }
+
+    /// Sets the video's width and height, usually from the stream's metadata, so they can be
+    /// included in the init segment and master playlist
+    pub fn set_video_dimensions(&mut self, width: u16, height: u16) {
+        self.video_dimensions = Some((width, height));
+        self.muxer.set_video_dimensions(width, height);
+    }
+
+    /// Adds the payload of a video message, returning a segment if one was completed
+    pub fn push_video(
+        &mut self,
+        data: &Bytes,
+        timestamp: RtmpTimestamp,
+    ) -> Result<Option<HlsSegment>, HlsError> {
+        let timestamp = self.rebaser.rebase(timestamp);
+        let header = VideoTagHeader::parse(data);
+        let is_sequence_header = header.is_some_and(|x| x.is_sequence_header());
+        let is_keyframe = header.is_some_and(|x| x.is_keyframe()) && !is_sequence_header;
+
+        self.expects_video = true;
+        if !is_sequence_header && !is_keyframe && self.segment_start.is_none() {
+            return Ok(None);
+        }
+
+        self.muxer.push_video(data, timestamp)?;
+        if is_sequence_header || !self.muxer.has_video() {
+            return Ok(None);
+        }
+
+        self.handle_sample(timestamp, is_keyframe)
+    }
+
+    /// Adds the payload of an audio message, returning a segment if one was completed
+    pub fn push_audio(
+        &mut self,
+        data: &Bytes,
+        timestamp: RtmpTimestamp,
+    ) -> Result<Option<HlsSegment>, HlsError> {
+        let timestamp = self.rebaser.rebase(timestamp);
+        let is_sequence_header =
+            AudioTagHeader::parse(data).is_some_and(|x| x.is_sequence_header());
+
+        // Audio can't be dropped once it's been passed to the muxer, so hold off until the
+        // video has started
+        if !is_sequence_header && self.expects_video && self.segment_start.is_none() {
+            return Ok(None);
+        }
+
+        self.muxer.push_audio(data, timestamp)?;
+        if is_sequence_header || self.expects_video || !self.muxer.has_audio() {
+            return Ok(None);
+        }
+
+        self.handle_sample(timestamp, true)
+    }
+
+    /// Completes the final segment and marks the playlist as ended
+    pub fn finish(&mut self) -> Result<Option<HlsSegment>, HlsError> {
+        if self.is_finished {
+            return Ok(None);
+        }
+
+        if let Some(media) = self.muxer.finish() {
+            self.add_part(media)?;
+        }
+
+        let segment = self.complete_segment()?;
+        self.is_finished = true;
+        self.write_playlists()?;
+        Ok(segment)
+    }
+
+    /// The init segment, once the first media segment has been cut
+    pub fn init_segment(&self) -> Option<Bytes> {
+        self.init_segment.clone()
+    }
+
+    /// The segments currently available, oldest first.  This includes one more segment than is
+    /// listed in the media playlist, for players that loaded the playlist before it changed.
+    pub fn segments(&self) -> impl Iterator<Item = &HlsSegment> {
+        self.segments.iter()
+    }
+
+    /// Renders the media playlist
+    pub fn media_playlist(&self) -> String {
+        let skip = self
+            .segments
+            .len()
+            .saturating_sub(self.config.playlist_segment_count);
+
+        let partial_segments = self
+            .config
+            .part_target_duration_ms
+            .map(|part_target_duration_ms| PartialSegments {
+                part_target_duration_ms,
+                current_parts: &self.current_parts,
+                next_part_name: self.part_name(self.current_parts.len()),
+            });
+
+        let playlist = MediaPlaylist {
+            segments: self.segments.iter().skip(skip).collect(),
+            next_sequence_number: self.next_sequence_number,
+            target_duration_ms: self.max_segment_duration_ms,
+            is_finished: self.is_finished,
+            partial_segments,
+        };
+
+        playlist.render()
+    }
+
+    /// True if the media playlist contains the segment (or part) a blocking playlist reload is
+    /// waiting for.  HTTP servers should hold the playlist response until this is true, or
+    /// until 3 target durations have passed.
+    pub fn can_serve(&self, request: &BlockingReloadRequest) -> bool {
+        if self.is_finished || request.media_sequence_number < self.next_sequence_number {
+            return true;
+        }
+
+        match request.part {
+            Some(part) if request.media_sequence_number == self.next_sequence_number => {
+                (part as usize) < self.current_parts.len()
+            }
+
+            _ => false,
+        }
+    }
+
+    /// Renders the master playlist, which is only possible once the codecs are known
+    pub fn master_playlist(&self) -> Option<String> {
+        self.init_segment.as_ref()?;
+
+        let bandwidth = self.peak_bandwidth.max(1);
+        Some(playlist::master_playlist(
+            bandwidth,
+            &self.muxer.codecs(),
+            self.resolution(),
+        ))
+    }
+
+    /// Renders a DASH manifest listing the same segments as the media playlist, which is only
+    /// possible once the codecs are known
+    pub fn dash_manifest(&self) -> Option<String> {
+        self.init_segment.as_ref()?;
+
+        let skip = self
+            .segments
+            .len()
+            .saturating_sub(self.config.playlist_segment_count);
+
+        let manifest = DashManifest {
+            segments: self.segments.iter().skip(skip).collect(),
+            codecs: &self.muxer.codecs(),
+            bandwidth: self.peak_bandwidth.max(1),
+            resolution: self.resolution(),
+            target_duration_ms: self.max_segment_duration_ms,
+            availability_start_time: self.availability_start_time?,
+            publish_time: SystemTime::now(),
+            is_finished: self.is_finished,
+        };
+
+        Some(manifest.render())
+    }
+
+    /// Gets the contents of the init segment, a media segment, or a playlist by its file name.
+    /// This makes serving the stream from an HTTP server a matter of passing it the last
+    /// portion of the request's path.
+    pub fn get_file(&self, name: &str) -> Option<Bytes> {
+        match name {
+            MEDIA_PLAYLIST_NAME => Some(Bytes::from(self.media_playlist())),
+            MASTER_PLAYLIST_NAME => self.master_playlist().map(Bytes::from),
+            DASH_MANIFEST_NAME => self.dash_manifest().map(Bytes::from),
+            INIT_SEGMENT_NAME => self.init_segment(),
+            _ => {
+                let segment = self.segments.iter().find(|segment| segment.name == name);
+                if let Some(segment) = segment {
+                    return Some(segment.data.clone());
+                }
+
+                self.segments
+                    .iter()
+                    .flat_map(|segment| segment.parts.iter())
+                    .chain(self.current_parts.iter())
+                    .find(|part| part.name == name)
+                    .map(|part| part.data.clone())
+            }
+        }
+    }
+
+    fn handle_sample(
+        &mut self,
+        timestamp: RtmpTimestamp,
+        is_keyframe: bool,
+    ) -> Result<Option<HlsSegment>, HlsError> {
+        let (segment_start, part_start) = match (self.segment_start, self.part_start) {
+            (Some(segment_start), Some(part_start)) => (segment_start, part_start),
+            _ => {
+                if is_keyframe {
+                    self.segment_start = Some(timestamp);
+                    self.part_start = Some(timestamp);
+                    self.last_sample_timestamp = Some(timestamp);
+                }
+
+                return Ok(None);
+            }
+        };
+
+        // Parts can't be longer than the part target, so one is cut when the next sample is
+        // expected to push it over (assuming the same gap as between the last two samples)
+        let interval = elapsed(self.last_sample_timestamp, timestamp);
+        let part_duration = elapsed(Some(part_start), timestamp);
+        self.last_sample_timestamp = Some(timestamp);
+
+        let target = self.config.target_segment_duration_ms;
+        let is_segment_boundary = is_keyframe && elapsed(Some(segment_start), timestamp) >= target;
+        let is_part_boundary = match self.config.part_target_duration_ms {
+            Some(part_target) => part_duration > 0 && part_duration + interval > part_target,
+            None => false,
+        };
+
+        if !is_segment_boundary && !is_part_boundary {
+            return Ok(None);
+        }
+
+        // The muxer holds back the sample just pushed, so it will start the next part
+        self.part_start = Some(timestamp);
+        if let Some(media) = self.muxer.flush_segment() {
+            self.add_part(media)?;
+        }
+
+        let segment = if is_segment_boundary {
+            self.segment_start = Some(timestamp);
+            self.complete_segment()?
+        } else {
+            None
+        };
+
+        self.write_playlists()?;
+        Ok(segment)
+    }
+
+    fn resolution(&self) -> Option<(u16, u16)> {
+        if self.muxer.has_video() {
+            self.video_dimensions
+        } else {
+            None
+        }
+    }
+
+    fn part_name(&self, index: usize) -> String {
+        format!("segment{}.{}.m4s", self.next_sequence_number, index)
+    }
+
+    fn add_part(&mut self, media: MediaSegment) -> Result<(), HlsError> {
+        if self.init_segment.is_none() {
+            self.init_segment = self.muxer.init_segment();
+            if let Some(ref data) = self.init_segment {
+                if let HlsOutput::Directory(ref path) = self.config.output {
+                    fs::write(path.join(INIT_SEGMENT_NAME), data)?;
+                }
+            }
+        }
+
+        if self.current_parts.is_empty() {
+            self.current_segment_start_ms = media.start_time;
+        }
+
+        let part = HlsPart {
+            name: self.part_name(self.current_parts.len()),
+            duration_ms: media.duration,
+            is_independent: media.is_independent,
+            data: media.data,
+        };
+
+        if self.config.part_target_duration_ms.is_some() {
+            if let HlsOutput::Directory(ref path) = self.config.output {
+                fs::write(path.join(&part.name), &part.data)?;
+            }
+        }
+
+        self.current_parts.push(part);
+        Ok(())
+    }
+
+    fn complete_segment(&mut self) -> Result<Option<HlsSegment>, HlsError> {
+        if self.current_parts.is_empty() {
+            return Ok(None);
+        }
+
+        let parts = std::mem::take(&mut self.current_parts);
+        let data = if parts.len() == 1 {
+            parts[0].data.clone()
+        } else {
+            let mut data = Vec::with_capacity(parts.iter().map(|x| x.data.len()).sum());
+            for part in &parts {
+                data.extend_from_slice(&part.data);
+            }
+
+            Bytes::from(data)
+        };
+
+        let segment = HlsSegment {
+            sequence_number: self.next_sequence_number,
+            name: format!("segment{}.m4s", self.next_sequence_number),
+            start_time_ms: self.current_segment_start_ms,
+            duration_ms: parts.iter().map(|x| x.duration_ms).sum(),
+            data,
+            parts: match self.config.part_target_duration_ms {
+                Some(_) => parts,
+                None => Vec::new(),
+            },
+        };
+
+        self.next_sequence_number += 1;
+
+        // DASH players work out which segments are available from the wall clock, so pin the
+        // start of the stream to when this segment became available
+        if self.availability_start_time.is_none() {
+            let end = segment.start_time_ms + segment.duration_ms;
+            let now = SystemTime::now();
+            self.availability_start_time =
+                Some(now.checked_sub(Duration::from_millis(end)).unwrap_or(now));
+        }
+
+        let bits = segment.data.len() as u64 * 8 * 1000;
+        if let Some(bandwidth) = bits.checked_div(segment.duration_ms) {
+            self.peak_bandwidth = self.peak_bandwidth.max(bandwidth);
+        }
+
+        self.max_segment_duration_ms = self.max_segment_duration_ms.max(segment.duration_ms);
+
+        if let HlsOutput::Directory(ref path) = self.config.output {
+            fs::write(path.join(&segment.name), &segment.data)?;
+        }
+
+        self.segments.push_back(segment.clone());
+
+        let mut expired_files = Vec::new();
+        while self.segments.len() > self.config.playlist_segment_count + 1 {
+            if let Some(expired) = self.segments.pop_front() {
+                expired_files.push(expired.name);
+                expired_files.extend(expired.parts.into_iter().map(|x| x.name));
+            }
+        }
+
+        let with_parts = self.segments.len().saturating_sub(SEGMENTS_WITH_PARTS);
+        for segment in self.segments.iter_mut().take(with_parts) {
+            expired_files.extend(segment.parts.drain(..).map(|x| x.name));
+        }
+
+        if let HlsOutput::Directory(ref path) = self.config.output {
+            for name in expired_files {
+                match fs::remove_file(path.join(name)) {
+                    Ok(()) => (),
+                    Err(ref error) if error.kind() == ErrorKind::NotFound => (),
+                    Err(error) => return Err(error.into()),
+                }
+            }
+        }
+
+        Ok(Some(segment))
+    }
+
+    fn write_playlists(&self) -> Result<(), HlsError> {
+        let path = match self.config.output {
+            HlsOutput::Directory(ref path) => path,
+            HlsOutput::Memory => return Ok(()),
+        };
+
+        write_atomically(path, MEDIA_PLAYLIST_NAME, &self.media_playlist())?;
+        if let Some(master) = self.master_playlist() {
+            write_atomically(path, MASTER_PLAYLIST_NAME, &master)?;
+        }
+
+        if let Some(manifest) = self.dash_manifest() {
+            write_atomically(path, DASH_MANIFEST_NAME, &manifest)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MediaSink for HlsSegmenter {
+    type Error = HlsError;
+
+    /// Adds the item to the segments, completing the final segment at the end of the stream.
+    /// Completed segments are available from `segments()`.
+    fn send_media(&mut self, item: MediaItem) -> Result<(), Self::Error> {
+        match item {
+            MediaItem::Metadata(metadata) => {
+                if let (Some(width), Some(height)) = (metadata.video_width, metadata.video_height) {
+                    self.set_video_dimensions(width as u16, height as u16);
+                }
+            }
+
+            MediaItem::Video { data, timestamp } => {
+                self.push_video(&data, timestamp)?;
+            }
+
+            MediaItem::Audio { data, timestamp } => {
+                self.push_audio(&data, timestamp)?;
+            }
+
+            MediaItem::EndOfStream => {
+                self.finish()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Milliseconds from the start to the timestamp, or zero if the timestamp is earlier
+fn elapsed(start: Option<RtmpTimestamp>, timestamp: RtmpTimestamp) -> u32 {
+    match start {
+        Some(start) if timestamp >= start => (timestamp - start).value,
+        _ => 0,
+    }
+}
+
+/// Writes to a temporary file and renames it, so web servers never serve a partial playlist
+fn write_atomically(directory: &Path, name: &str, contents: &str) -> Result<(), HlsError> {
+    let temporary = directory.join(format!("{}.tmp", name));
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, directory.join(name))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video_sequence_header() -> Bytes {
+        Bytes::from(vec![0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 0])
+    }
+
+    fn audio_sequence_header() -> Bytes {
+        Bytes::from(vec![0xaf, 0, 0x12, 0x10])
+    }
+
+    fn frame(is_keyframe: bool) -> Bytes {
+        let flags = if is_keyframe { 0x17 } else { 0x27 };
+        Bytes::from(vec![flags, 1, 0, 0, 0, 0, 0, 0, 1, 0x41])
+    }
+
+    fn audio_frame() -> Bytes {
+        Bytes::from(vec![0xaf, 1, 0x21])
+    }
+
+    fn segmenter() -> HlsSegmenter {
+        let mut config = HlsConfig::new();
+        config.target_segment_duration_ms = 1000;
+        config.playlist_segment_count = 2;
+        HlsSegmenter::new(config).unwrap()
+    }
+
+    /// Pushes 30fps video with a keyframe every second, returning the cut segments
+    fn push_video_seconds(segmenter: &mut HlsSegmenter, seconds: u32) -> Vec<HlsSegment> {
+        let mut segments = Vec::new();
+        for index in 0..seconds * 30 {
+            let time = RtmpTimestamp::new(index * 1000 / 30);
+            let data = frame(index % 30 == 0);
+            if let Some(segment) = segmenter.push_video(&data, time).unwrap() {
+                segments.push(segment);
+            }
+        }
+
+        segments
+    }
+
+    #[test]
+    fn segments_are_cut_on_keyframes_after_target_duration() {
+        let mut segmenter = segmenter();
+        segmenter
+            .push_video(&video_sequence_header(), RtmpTimestamp::new(0))
+            .unwrap();
+
+        let segments = push_video_seconds(&mut segmenter, 3);
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].duration_ms, 1000);
+        assert_eq!(segments[0].name, "segment1.m4s");
+        assert_eq!(segments[1].start_time_ms, 1000);
+        assert!(segmenter.init_segment().is_some());
+        assert_eq!(
+            segmenter.get_file("segment2.m4s"),
+            Some(segments[1].data.clone())
+        );
+    }
+
+    #[test]
+    fn playlist_only_lists_most_recent_segments() {
+        let mut segmenter = segmenter();
+        segmenter
+            .push_video(&video_sequence_header(), RtmpTimestamp::new(0))
+            .unwrap();
+
+        push_video_seconds(&mut segmenter, 5);
+        segmenter.finish().unwrap();
+
+        let playlist = segmenter.media_playlist();
+        assert!(playlist.contains("#EXT-X-MEDIA-SEQUENCE:4\n"));
+        assert!(!playlist.contains("segment3.m4s"));
+        assert!(playlist.contains("segment5.m4s"));
+        assert!(playlist.ends_with("#EXT-X-ENDLIST\n"));
+
+        // One more segment than listed is kept for players with the previous playlist
+        assert!(segmenter.get_file("segment3.m4s").is_some());
+        assert!(segmenter.get_file("segment2.m4s").is_none());
+
+        let manifest = segmenter.dash_manifest().unwrap();
+        assert!(manifest.contains(" startNumber=\"4\">"));
+        assert!(manifest.contains("<S t=\"3000\" d=\"1000\"/>"));
+    }
+
+    #[test]
+    fn media_before_first_keyframe_is_dropped() {
+        let mut segmenter = segmenter();
+        segmenter
+            .push_video(&video_sequence_header(), RtmpTimestamp::new(0))
+            .unwrap();
+        segmenter
+            .push_audio(&audio_sequence_header(), RtmpTimestamp::new(0))
+            .unwrap();
+        segmenter
+            .push_video(&frame(false), RtmpTimestamp::new(0))
+            .unwrap();
+        segmenter
+            .push_audio(&audio_frame(), RtmpTimestamp::new(10))
+            .unwrap();
+        segmenter
+            .push_video(&frame(true), RtmpTimestamp::new(500))
+            .unwrap();
+        segmenter
+            .push_video(&frame(false), RtmpTimestamp::new(533))
+            .unwrap();
+
+        let segment = segmenter.finish().unwrap().unwrap();
+
+        assert_eq!(segment.duration_ms, 66);
+        assert!(segmenter
+            .master_playlist()
+            .unwrap()
+            .contains("CODECS=\"avc1.64001f,mp4a.40.2\""));
+    }
+
+    #[test]
+    fn low_latency_segments_are_built_from_parts_within_part_target() {
+        let mut config = HlsConfig::new();
+        config.target_segment_duration_ms = 1000;
+        config.part_target_duration_ms = Some(300);
+        let mut segmenter = HlsSegmenter::new(config).unwrap();
+        segmenter
+            .push_video(&video_sequence_header(), RtmpTimestamp::new(0))
+            .unwrap();
+
+        let segments = push_video_seconds(&mut segmenter, 2);
+        let segment = &segments[0];
+
+        let durations = segment
+            .parts
+            .iter()
+            .map(|part| part.duration_ms)
+            .collect::<Vec<_>>();
+
+        assert_eq!(durations, vec![300, 300, 300, 100]);
+        assert!(segment.parts[0].is_independent);
+        assert!(!segment.parts[1].is_independent);
+        assert_eq!(segment.parts[1].name, "segment1.1.m4s");
+
+        let parts_length = segment
+            .parts
+            .iter()
+            .map(|part| part.data.len())
+            .sum::<usize>();
+        assert_eq!(segment.data.len(), parts_length);
+
+        let playlist = segmenter.media_playlist();
+        assert!(playlist
+            .contains("#EXT-X-PART:DURATION=0.300,URI=\"segment2.0.m4s\",INDEPENDENT=YES\n"));
+        assert!(playlist.contains("#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"segment2.3.m4s\"\n"));
+        assert!(segmenter.get_file("segment2.2.m4s").is_some());
+    }
+
+    #[test]
+    fn blocking_requests_can_be_served_once_part_exists() {
+        let mut config = HlsConfig::new();
+        config.target_segment_duration_ms = 1000;
+        config.part_target_duration_ms = Some(300);
+        let mut segmenter = HlsSegmenter::new(config).unwrap();
+        segmenter
+            .push_video(&video_sequence_header(), RtmpTimestamp::new(0))
+            .unwrap();
+
+        push_video_seconds(&mut segmenter, 2);
+
+        let request = |media_sequence_number, part| BlockingReloadRequest {
+            media_sequence_number,
+            part,
+        };
+
+        assert!(segmenter.can_serve(&request(1, None)));
+        assert!(segmenter.can_serve(&request(2, Some(2))));
+        assert!(!segmenter.can_serve(&request(2, Some(3))));
+        assert!(!segmenter.can_serve(&request(2, None)));
+    }
+
+    #[test]
+    fn audio_only_streams_are_cut_on_any_frame() {
+        let mut segmenter = segmenter();
+        segmenter
+            .push_audio(&audio_sequence_header(), RtmpTimestamp::new(0))
+            .unwrap();
+
+        let mut segments = Vec::new();
+        for index in 0..100 {
+            let time = RtmpTimestamp::new(index * 23);
+            if let Some(segment) = segmenter.push_audio(&audio_frame(), time).unwrap() {
+                segments.push(segment);
+            }
+        }
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].duration_ms, 1012);
+    }
+}
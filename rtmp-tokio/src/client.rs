@@ -0,0 +1,349 @@
+use crate::errors::RtmpClientError;
+use crate::handshake::perform_handshake;
+use bytes::Bytes;
+use rml_rtmp::handshake::PeerType;
+use rml_rtmp::sessions::{
+    ClientSession, ClientSessionConfig, ClientSessionEvent, ClientSessionResult,
+    PublishRequestType, StatusLevel, StreamMetadata,
+};
+use rml_rtmp::time::RtmpTimestamp;
+use std::collections::VecDeque;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+const READ_BUFFER_SIZE: usize = 4096;
+
+/// An RTMP client connection driven by tokio.
+///
+/// This performs the handshake and pumps bytes between the connection and a `ClientSession`,
+/// so requests can be awaited instead of having to react to session results by hand.  Any
+/// events raised while waiting for a response (such as media arriving right after a play
+/// request was accepted) are held onto and returned by `next_event()`.
+///
+/// Any stream that implements `AsyncRead` and `AsyncWrite` can be used, which allows the
+/// client to run over transports other than plain TCP.
+///
+/// # Examples
+/// ```no_run
+/// use rml_rtmp::sessions::{ClientSessionConfig, ClientSessionEvent};
+/// use rml_rtmp_tokio::RtmpClient;
+///
+/// # async fn example() -> Result<(), rml_rtmp_tokio::RtmpClientError> {
+/// let config = ClientSessionConfig::new();
+/// let mut client = RtmpClient::connect_tcp("localhost:1935", config).await?;
+/// client.connect("live").await?;
+/// client.play("my_stream").await?;
+///
+/// while let Some(event) = client.next_event().await? {
+///     if let ClientSessionEvent::VideoDataReceived { timestamp, data } = event {
+///         println!("Received {} bytes of video at {:?}", data.len(), timestamp);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct RtmpClient<S> {
+    stream: S,
+    session: ClientSession,
+    events: VecDeque<ClientSessionEvent>,
+    read_buffer: Vec<u8>,
+}
+
+impl RtmpClient<TcpStream> {
+    /// Opens a TCP connection to the server and performs the handshake
+    pub async fn connect_tcp<A: ToSocketAddrs>(
+        address: A,
+        config: ClientSessionConfig,
+    ) -> Result<RtmpClient<TcpStream>, RtmpClientError> {
+        let stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        RtmpClient::new(stream, config).await
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> RtmpClient<S> {
+    /// Performs the handshake over an already established connection and creates the session
+    pub async fn new(
+        mut stream: S,
+        config: ClientSessionConfig,
+    ) -> Result<RtmpClient<S>, RtmpClientError> {
+        let remaining_bytes =
+            perform_handshake::<_, RtmpClientError>(&mut stream, PeerType::Client).await?;
+        let (session, results) = ClientSession::new(config)?;
+        let mut client = RtmpClient {
+            stream,
+            session,
+            events: VecDeque::new(),
+            read_buffer: vec![0; READ_BUFFER_SIZE],
+        };
+
+        client.handle_results(results).await?;
+        if !remaining_bytes.is_empty() {
+            let results = client.session.handle_input(&remaining_bytes)?;
+            client.handle_results(results).await?;
+        }
+
+        Ok(client)
+    }
+
+    /// Requests a connection to the specified application, returning once the server has
+    /// accepted it
+    pub async fn connect(&mut self, app_name: &str) -> Result<(), RtmpClientError> {
+        let result = self.session.request_connection(app_name.to_string())?;
+        self.handle_results(vec![result]).await?;
+        self.wait_for_response(|event| match *event {
+            ClientSessionEvent::ConnectionRequestAccepted => Some(Ok(())),
+            ClientSessionEvent::ConnectionRequestRejected { ref description } => {
+                Some(Err(RtmpClientError::ConnectionRequestRejected {
+                    description: description.clone(),
+                }))
+            }
+
+            _ => None,
+        })
+        .await
+    }
+
+    /// Requests playback of the specified stream key, returning once the server has accepted
+    /// it.  The stream's metadata, audio, and video are then returned by `next_event()`.
+    pub async fn play(&mut self, stream_key: &str) -> Result<(), RtmpClientError> {
+        let result = self.session.request_playback(stream_key.to_string())?;
+        self.handle_results(vec![result]).await?;
+        self.wait_for_response(|event| match *event {
+            ClientSessionEvent::PlaybackRequestAccepted => Some(Ok(())),
+            _ => check_for_failure(event),
+        })
+        .await
+    }
+
+    /// Requests to publish on the specified stream key, returning once the server has accepted
+    /// it
+    pub async fn publish(
+        &mut self,
+        stream_key: &str,
+        publish_type: PublishRequestType,
+    ) -> Result<(), RtmpClientError> {
+        let result = self
+            .session
+            .request_publishing(stream_key.to_string(), publish_type)?;
+
+        self.handle_results(vec![result]).await?;
+        self.wait_for_response(|event| match *event {
+            ClientSessionEvent::PublishRequestAccepted => Some(Ok(())),
+            _ => check_for_failure(event),
+        })
+        .await
+    }
+
+    /// Sends metadata describing the stream being published
+    pub async fn publish_metadata(
+        &mut self,
+        metadata: &StreamMetadata,
+    ) -> Result<(), RtmpClientError> {
+        let result = self.session.publish_metadata(metadata)?;
+        self.handle_results(vec![result]).await
+    }
+
+    /// Sends video data on the stream being published
+    pub async fn publish_video_data(
+        &mut self,
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    ) -> Result<(), RtmpClientError> {
+        let result = self.session.publish_video_data(data, timestamp, false)?;
+        self.handle_results(vec![result]).await
+    }
+
+    /// Sends audio data on the stream being published
+    pub async fn publish_audio_data(
+        &mut self,
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    ) -> Result<(), RtmpClientError> {
+        let result = self.session.publish_audio_data(data, timestamp, false)?;
+        self.handle_results(vec![result]).await
+    }
+
+    /// Tells the server we no longer want to play the stream
+    pub async fn stop_playback(&mut self) -> Result<(), RtmpClientError> {
+        let results = self.session.stop_playback()?;
+        self.handle_results(results).await
+    }
+
+    /// Tells the server we are finished publishing
+    pub async fn stop_publishing(&mut self) -> Result<(), RtmpClientError> {
+        let results = self.session.stop_publishing()?;
+        self.handle_results(results).await
+    }
+
+    /// Waits for the next event raised by the session, such as received audio or video.
+    /// `None` is returned once the server has closed the connection.
+    pub async fn next_event(&mut self) -> Result<Option<ClientSessionEvent>, RtmpClientError> {
+        match self.read_event().await {
+            Ok(event) => Ok(Some(event)),
+            Err(RtmpClientError::ConnectionClosed) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Gets a reference to the underlying session
+    pub fn session(&self) -> &ClientSession {
+        &self.session
+    }
+
+    /// Gets a reference to the underlying connection
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Flushes and shuts down the connection
+    pub async fn shutdown(&mut self) -> Result<(), RtmpClientError> {
+        self.stream.shutdown().await?;
+        Ok(())
+    }
+
+    /// Reads from the connection until the session raises an event that `check` returns a
+    /// result for.  That event is consumed, while every other event is held for `next_event()`.
+    async fn wait_for_response<F>(&mut self, check: F) -> Result<(), RtmpClientError>
+    where
+        F: Fn(&ClientSessionEvent) -> Option<Result<(), RtmpClientError>>,
+    {
+        // Events held from before the request was made can't be its response
+        let mut checked_count = self.events.len();
+        loop {
+            while checked_count < self.events.len() {
+                if let Some(result) = check(&self.events[checked_count]) {
+                    self.events.remove(checked_count);
+                    return result;
+                }
+
+                checked_count += 1;
+            }
+
+            self.read_input().await?;
+        }
+    }
+
+    /// Returns the oldest held event, or reads from the connection until the session raises one
+    async fn read_event(&mut self) -> Result<ClientSessionEvent, RtmpClientError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+
+            self.read_input().await?;
+        }
+    }
+
+    /// Reads whatever bytes are available from the connection and passes them to the session
+    async fn read_input(&mut self) -> Result<(), RtmpClientError> {
+        let bytes_read = self.stream.read(&mut self.read_buffer).await?;
+        if bytes_read == 0 {
+            return Err(RtmpClientError::ConnectionClosed);
+        }
+
+        let results = self.session.handle_input(&self.read_buffer[..bytes_read])?;
+        self.handle_results(results).await
+    }
+
+    /// Sends outbound packets to the server and holds onto raised events
+    async fn handle_results(
+        &mut self,
+        results: Vec<ClientSessionResult>,
+    ) -> Result<(), RtmpClientError> {
+        let mut has_written = false;
+        for result in results {
+            match result {
+                ClientSessionResult::OutboundResponse(packet) => {
+                    self.stream.write_all(&packet.bytes).await?;
+                    has_written = true;
+                }
+
+                ClientSessionResult::RaisedEvent(event) => self.events.push_back(event),
+                ClientSessionResult::UnhandleableMessageReceived(_) => (),
+            }
+        }
+
+        if has_written {
+            self.stream.flush().await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Turns `onStatus` codes that servers use to say a play or publish request has failed into an
+/// error
+fn check_for_failure(event: &ClientSessionEvent) -> Option<Result<(), RtmpClientError>> {
+    match *event {
+        ClientSessionEvent::UnhandleableOnStatusCode { ref code }
+            if code.level() == StatusLevel::Error =>
+        {
+            Some(Err(RtmpClientError::RequestFailed { code: code.clone() }))
+        }
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{run_server, PLAYED_VIDEO};
+    use tokio::io::{duplex, DuplexStream};
+
+    async fn connected_client() -> RtmpClient<DuplexStream> {
+        let (client_stream, mut server_stream) = duplex(64 * 1024);
+        tokio::spawn(async move {
+            let received_bytes =
+                perform_handshake::<_, RtmpClientError>(&mut server_stream, PeerType::Server)
+                    .await
+                    .unwrap();
+
+            run_server(server_stream, received_bytes).await;
+        });
+
+        let mut client = RtmpClient::new(client_stream, ClientSessionConfig::new())
+            .await
+            .unwrap();
+
+        client.connect("live").await.unwrap();
+        client
+    }
+
+    #[tokio::test]
+    async fn can_play_stream_and_receive_video() {
+        let mut client = connected_client().await;
+
+        client.play("key").await.unwrap();
+
+        loop {
+            match client.next_event().await.unwrap() {
+                Some(ClientSessionEvent::VideoDataReceived { timestamp, data }) => {
+                    assert_eq!(timestamp, RtmpTimestamp::new(40));
+                    assert_eq!(&data[..], &PLAYED_VIDEO);
+                    break;
+                }
+
+                Some(_) => (),
+                None => panic!("Connection closed before video was received"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn can_publish_after_request_is_accepted() {
+        let mut client = connected_client().await;
+
+        client
+            .publish("key", PublishRequestType::Live)
+            .await
+            .unwrap();
+        client
+            .publish_video_data(Bytes::from(vec![0x17, 1]), RtmpTimestamp::new(0))
+            .await
+            .unwrap();
+
+        client.stop_publishing().await.unwrap();
+    }
+}
@@ -0,0 +1,33 @@
+use rml_rtmp::handshake::HandshakeError;
+use rml_rtmp::sessions::{ClientSessionError, StatusCode};
+use std::io;
+use thiserror::Error;
+
+/// Errors that can occur while communicating with an RTMP server
+#[derive(Debug, Error)]
+pub enum RtmpClientError {
+    /// The handshake with the server failed
+    #[error("Handshake failed: {0}")]
+    Handshake(#[from] HandshakeError),
+
+    /// The client session could not process the server's data or create a request
+    #[error("Client session error: {0}")]
+    Session(#[from] ClientSessionError),
+
+    /// The server rejected the request to connect to an application
+    #[error("Connection request rejected: {description}")]
+    ConnectionRequestRejected { description: String },
+
+    /// The server responded to a play or publish request with a status code that signals it
+    /// has failed, such as `NetStream.Publish.BadName`
+    #[error("Request failed with status code {code}")]
+    RequestFailed { code: StatusCode },
+
+    /// The server closed the connection while a response was still expected
+    #[error("The server closed the connection")]
+    ConnectionClosed,
+
+    /// An I/O error occurred on the connection
+    #[error("Connection I/O error: {0}")]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,51 @@
+use bytes::Bytes;
+use rml_rtmp::handshake::{Handshake, HandshakeError, HandshakeProcessResult, PeerType};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const READ_BUFFER_SIZE: usize = 4096;
+
+/// Performs the RTMP handshake over the stream, returning any bytes received after it which
+/// must be passed to the session
+pub(crate) async fn perform_handshake<S, E>(stream: &mut S, peer_type: PeerType) -> Result<Bytes, E>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    E: From<HandshakeError> + From<io::Error>,
+{
+    let mut buffer = [0; READ_BUFFER_SIZE];
+    let is_client = peer_type == PeerType::Client;
+    let mut handshake = Handshake::new(peer_type);
+
+    // Servers send their packets 0 and 1 in response to the client's
+    if is_client {
+        let p0_and_p1 = handshake.generate_outbound_p0_and_p1()?;
+        stream.write_all(&p0_and_p1).await?;
+    }
+
+    loop {
+        let bytes_read = stream.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            let error = io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed during handshake",
+            );
+
+            return Err(error.into());
+        }
+
+        match handshake.process_bytes(&buffer[..bytes_read])? {
+            HandshakeProcessResult::InProgress { response_bytes } => {
+                stream.write_all(&response_bytes).await?;
+            }
+
+            HandshakeProcessResult::Completed {
+                response_bytes,
+                completion,
+            } => {
+                stream.write_all(&response_bytes).await?;
+                stream.flush().await?;
+                return Ok(completion.remaining_bytes);
+            }
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! This crate runs the sessions from `rml_rtmp` over tokio connections.
+//!
+//! The `rml_rtmp` sessions are deliberately unaware of how bytes get to and from the peer,
+//! which keeps them usable from any I/O model but leaves every application to write the same
+//! read/write pumping code.  This crate provides that code for async applications.
+//!
+//! * `RtmpClient` connects to a server, performs the handshake, and exposes the client
+//!   session's requests as async methods
+//! * The `rtmps` module (behind the `rtmps` feature) establishes TLS sessions with rustls for
+//!   clients and servers to run RTMP inside of
+
+mod client;
+mod errors;
+mod handshake;
+
+#[cfg(feature = "rtmps")]
+pub mod rtmps;
+
+#[cfg(test)]
+mod test_server;
+
+pub use crate::client::RtmpClient;
+pub use crate::errors::RtmpClientError;
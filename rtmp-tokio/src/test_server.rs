@@ -0,0 +1,67 @@
+use bytes::Bytes;
+use rml_rtmp::sessions::{
+    ServerSession, ServerSessionConfig, ServerSessionEvent, ServerSessionResult,
+};
+use rml_rtmp::time::RtmpTimestamp;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// The video packet the test server sends to players once their request is accepted
+pub const PLAYED_VIDEO: [u8; 5] = [0x17, 1, 0, 0, 0];
+
+/// Acts as a server that accepts every request and sends one video packet to players.  The
+/// handshake must have already been completed, with any bytes received after it passed in.
+pub async fn run_server<S>(mut stream: S, received_bytes: Bytes)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0; 4096];
+    let (mut session, mut results) = ServerSession::new(ServerSessionConfig::new()).unwrap();
+    results.extend(session.handle_input(&received_bytes).unwrap());
+    loop {
+        let mut responses = Vec::new();
+        for result in results.drain(..) {
+            match result {
+                ServerSessionResult::OutboundResponse(packet) => {
+                    stream.write_all(&packet.bytes).await.unwrap();
+                }
+
+                ServerSessionResult::RaisedEvent(ServerSessionEvent::ConnectionRequested {
+                    request_id,
+                    ..
+                })
+                | ServerSessionResult::RaisedEvent(ServerSessionEvent::PublishStreamRequested {
+                    request_id,
+                    ..
+                }) => responses.extend(session.accept_request(request_id).unwrap()),
+
+                ServerSessionResult::RaisedEvent(ServerSessionEvent::PlayStreamRequested {
+                    request_id,
+                    stream_id,
+                    ..
+                }) => {
+                    responses.extend(session.accept_request(request_id).unwrap());
+                    let data = Bytes::from(PLAYED_VIDEO.to_vec());
+                    let packet = session
+                        .send_video_data(stream_id, data, RtmpTimestamp::new(40), false)
+                        .unwrap();
+
+                    responses.push(ServerSessionResult::OutboundResponse(packet));
+                }
+
+                _ => (),
+            }
+        }
+
+        if !responses.is_empty() {
+            results = responses;
+            continue;
+        }
+
+        let bytes_read = match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(x) => x,
+        };
+
+        results = session.handle_input(&buffer[..bytes_read]).unwrap();
+    }
+}
@@ -0,0 +1,505 @@
+//! Runs FFmpeg against a server built from `rml_rtmp`, to catch interop problems with real
+//! encoders and players that the unit tests can't.  FFmpeg publishes a generated test pattern
+//! to the server, or plays it back from the server, and the frames and metadata that make it
+//! through are compared with what was generated.
+//!
+//! These tests need `ffmpeg` and `ffprobe` (built with libx264) on the path, so they only run
+//! when the `RML_FFMPEG_TESTS` environment variable is set:
+//!
+//! ```text
+//! RML_FFMPEG_TESTS=1 cargo test -p rml_rtmp_tokio --test ffmpeg_interop
+//! ```
+
+use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
+use rml_rtmp::hub::media::{is_audio_sequence_header, is_video_sequence_header};
+use rml_rtmp::hub::{StreamHub, StreamHubResult, SubscriberDropPolicy};
+use rml_rtmp::sessions::{
+    ServerSession, ServerSessionConfig, ServerSessionEvent, ServerSessionResult, StreamMetadata,
+};
+use rml_rtmp::time::RtmpTimestamp;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+const ENABLE_VARIABLE: &str = "RML_FFMPEG_TESTS";
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+const FRAME_RATE: u32 = 30;
+const SAMPLE_RATE: u32 = 44100;
+const DURATION_SECS: u32 = 2;
+const VIDEO_FRAMES: usize = (FRAME_RATE * DURATION_SECS) as usize;
+
+/// How long FFmpeg gets to finish before the test fails
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ffmpeg_can_publish_to_server() {
+    if !is_enabled() {
+        return;
+    }
+
+    let server = TestServer::start().await;
+    run_to_completion(ffmpeg_publisher(server.address, "interop")).await;
+
+    let published = server.published("live", "interop");
+    assert_eq!(
+        published.video_frames, VIDEO_FRAMES,
+        "video frames received"
+    );
+    assert!(published.audio_frames > 0, "no audio frames were received");
+
+    // Timestamps should cover the duration of the stream, within a few frames
+    let expected = DURATION_SECS * 1000;
+    let last_video = published.last_video_timestamp.unwrap().value;
+    let last_audio = published.last_audio_timestamp.unwrap().value;
+    assert!(
+        last_video.abs_diff(expected) <= 100,
+        "last video timestamp was {}",
+        last_video
+    );
+
+    assert!(
+        last_audio.abs_diff(expected) <= 100,
+        "last audio timestamp was {}",
+        last_audio
+    );
+
+    let metadata = published.metadata.expect("no metadata was received");
+    assert_eq!(metadata.video_width, Some(WIDTH));
+    assert_eq!(metadata.video_height, Some(HEIGHT));
+    assert_eq!(metadata.video_frame_rate, Some(FRAME_RATE as f32));
+    assert_eq!(metadata.audio_sample_rate, Some(SAMPLE_RATE));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ffmpeg_can_play_stream_published_by_ffmpeg() {
+    if !is_enabled() {
+        return;
+    }
+
+    let server = TestServer::start().await;
+    let output = std::env::temp_dir().join(format!("rml_interop_{}.flv", std::process::id()));
+    let player = ffmpeg_player(server.address, "played", &output)
+        .spawn()
+        .expect("failed to start ffmpeg");
+
+    // The player has to be watching before publishing starts, so it receives every frame
+    server.wait_for_subscriber("live", "played").await;
+    run_to_completion(ffmpeg_publisher(server.address, "played")).await;
+
+    // The server disconnects players when their publisher finishes
+    tokio::time::timeout(TIMEOUT, player.wait_with_output())
+        .await
+        .expect("ffmpeg player did not finish")
+        .expect("failed to wait for ffmpeg player");
+
+    let video = probe_video_stream(&output).await;
+    let _ = std::fs::remove_file(&output);
+
+    assert_eq!(video.get("width"), Some(&WIDTH.to_string()));
+    assert_eq!(video.get("height"), Some(&HEIGHT.to_string()));
+    assert_eq!(
+        video.get("nb_read_packets"),
+        Some(&VIDEO_FRAMES.to_string()),
+        "video frames played"
+    );
+}
+
+fn is_enabled() -> bool {
+    if std::env::var_os(ENABLE_VARIABLE).is_none() {
+        println!(
+            "Skipping, set {} to run FFmpeg interop tests",
+            ENABLE_VARIABLE
+        );
+        return false;
+    }
+
+    true
+}
+
+/// Publishes a test pattern and a tone to the stream for `DURATION_SECS`
+fn ffmpeg_publisher(address: SocketAddr, stream_key: &str) -> Command {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-f", "lavfi", "-i"])
+        .arg(format!(
+            "testsrc2=size={}x{}:rate={}",
+            WIDTH, HEIGHT, FRAME_RATE
+        ))
+        .args(["-f", "lavfi", "-i"])
+        .arg(format!("sine=frequency=440:sample_rate={}", SAMPLE_RATE))
+        .arg("-t")
+        .arg(DURATION_SECS.to_string())
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p", "-g"])
+        .arg(FRAME_RATE.to_string())
+        .args(["-c:a", "aac", "-f", "flv"])
+        .arg(format!("rtmp://{}/live/{}", address, stream_key))
+        .stdin(Stdio::null());
+
+    command
+}
+
+/// Plays the stream and copies it into an FLV file without re-encoding it
+fn ffmpeg_player(address: SocketAddr, stream_key: &str, output: &Path) -> Command {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(format!("rtmp://{}/live/{}", address, stream_key))
+        .args(["-c", "copy", "-f", "flv"])
+        .arg(output)
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    command
+}
+
+async fn run_to_completion(mut command: Command) {
+    let output = tokio::time::timeout(TIMEOUT, command.output())
+        .await
+        .expect("ffmpeg did not finish")
+        .expect("failed to run ffmpeg");
+
+    assert!(
+        output.status.success(),
+        "ffmpeg failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Returns the width, height, and packet count of the file's video stream
+async fn probe_video_stream(path: &Path) -> HashMap<String, String> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-count_packets"])
+        .args(["-show_entries", "stream=width,height,nb_read_packets"])
+        .args(["-of", "default=noprint_wrappers=1"])
+        .arg(path)
+        .output()
+        .await
+        .expect("failed to run ffprobe");
+
+    assert!(
+        output.status.success(),
+        "ffprobe failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// What the server received from a stream's publisher
+#[derive(Default, Clone)]
+struct PublishedStream {
+    video_frames: usize,
+    audio_frames: usize,
+    last_video_timestamp: Option<RtmpTimestamp>,
+    last_audio_timestamp: Option<RtmpTimestamp>,
+    metadata: Option<Arc<StreamMetadata>>,
+}
+
+/// State shared by every connection to the test server
+struct ServerState {
+    hub: StreamHub,
+    subscribers: HashMap<usize, mpsc::UnboundedSender<StreamHubResult>>,
+    published: HashMap<(String, String), PublishedStream>,
+    next_connection_id: usize,
+}
+
+impl ServerState {
+    fn route(&self, results: Vec<StreamHubResult>) {
+        for result in results {
+            let subscriber_id = match &result {
+                StreamHubResult::SendMetadata { subscriber_id, .. }
+                | StreamHubResult::SendVideoData { subscriber_id, .. }
+                | StreamHubResult::SendAudioData { subscriber_id, .. }
+                | StreamHubResult::PublishingFinished { subscriber_id, .. } => *subscriber_id,
+            };
+
+            if let Some(sender) = self.subscribers.get(&subscriber_id) {
+                let _ = sender.send(result);
+            }
+        }
+    }
+}
+
+/// An RTMP server that accepts every request, routing published streams to their players
+/// through a `StreamHub`
+struct TestServer {
+    address: SocketAddr,
+    state: Arc<Mutex<ServerState>>,
+}
+
+impl TestServer {
+    async fn start() -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(ServerState {
+            hub: StreamHub::new(),
+            subscribers: HashMap::new(),
+            published: HashMap::new(),
+            next_connection_id: 0,
+        }));
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let connection_id = {
+                    let mut state = server_state.lock().unwrap();
+                    state.next_connection_id += 1;
+                    state.next_connection_id
+                };
+
+                let state = server_state.clone();
+                tokio::spawn(async move {
+                    let _ = run_connection(connection_id, stream, &state).await;
+                    let mut state = state.lock().unwrap();
+                    state.subscribers.remove(&connection_id);
+                    let results = state.hub.leave(connection_id);
+                    state.route(results);
+                });
+            }
+        });
+
+        TestServer { address, state }
+    }
+
+    fn published(&self, app_name: &str, stream_key: &str) -> PublishedStream {
+        let state = self.state.lock().unwrap();
+        let name = (app_name.to_string(), stream_key.to_string());
+        state.published.get(&name).cloned().unwrap_or_default()
+    }
+
+    async fn wait_for_subscriber(&self, app_name: &str, stream_key: &str) {
+        for _ in 0..100 {
+            let count = self
+                .state
+                .lock()
+                .unwrap()
+                .hub
+                .subscriber_count(app_name, stream_key);
+
+            if count > 0 {
+                return;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        panic!("ffmpeg never started playing {}/{}", app_name, stream_key);
+    }
+}
+
+type ConnectionError = Box<dyn std::error::Error + Send + Sync>;
+
+async fn run_connection(
+    connection_id: usize,
+    mut stream: TcpStream,
+    state: &Mutex<ServerState>,
+) -> Result<(), ConnectionError> {
+    let mut buffer = vec![0; 4096];
+    let mut handshake = Handshake::new(PeerType::Server);
+    stream
+        .write_all(&handshake.generate_outbound_p0_and_p1()?)
+        .await?;
+
+    let remaining_bytes = loop {
+        let bytes_read = stream.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+
+        match handshake.process_bytes(&buffer[..bytes_read])? {
+            HandshakeProcessResult::InProgress { response_bytes } => {
+                stream.write_all(&response_bytes).await?;
+            }
+
+            HandshakeProcessResult::Completed {
+                response_bytes,
+                completion,
+            } => {
+                stream.write_all(&response_bytes).await?;
+                break completion.remaining_bytes;
+            }
+        }
+    };
+
+    let (mut session, mut results) = ServerSession::new(ServerSessionConfig::new())?;
+    results.extend(session.handle_input(&remaining_bytes)?);
+
+    // Only used once the connection starts playing a stream
+    let (hub_sender, mut hub_results) = mpsc::unbounded_channel();
+    let mut hub_sender = Some(hub_sender);
+
+    loop {
+        while !results.is_empty() {
+            for result in std::mem::take(&mut results) {
+                match result {
+                    ServerSessionResult::OutboundResponse(packet) => {
+                        stream.write_all(&packet.bytes).await?;
+                    }
+
+                    ServerSessionResult::RaisedEvent(event) => {
+                        let new_results = handle_event(
+                            connection_id,
+                            &mut session,
+                            state,
+                            &mut hub_sender,
+                            event,
+                        )?;
+
+                        results.extend(new_results);
+                    }
+
+                    ServerSessionResult::UnhandleableMessageReceived(_) => (),
+                }
+            }
+        }
+
+        tokio::select! {
+            bytes_read = stream.read(&mut buffer) => {
+                let bytes_read = bytes_read?;
+                if bytes_read == 0 {
+                    return Ok(());
+                }
+
+                results = session.handle_input(&buffer[..bytes_read])?;
+            }
+
+            Some(result) = hub_results.recv() => {
+                let packet = match result {
+                    StreamHubResult::SendMetadata { stream_id, metadata, .. } => {
+                        session.send_metadata(stream_id, &metadata)?
+                    }
+
+                    StreamHubResult::SendVideoData { stream_id, data, timestamp, .. } => {
+                        session.send_video_data(stream_id, data, timestamp, false)?
+                    }
+
+                    StreamHubResult::SendAudioData { stream_id, data, timestamp, .. } => {
+                        session.send_audio_data(stream_id, data, timestamp, false)?
+                    }
+
+                    // Disconnecting is how players find out the stream has ended
+                    StreamHubResult::PublishingFinished { .. } => return Ok(()),
+                };
+
+                stream.write_all(&packet.bytes).await?;
+            }
+        }
+    }
+}
+
+fn handle_event(
+    connection_id: usize,
+    session: &mut ServerSession,
+    state: &Mutex<ServerState>,
+    hub_sender: &mut Option<mpsc::UnboundedSender<StreamHubResult>>,
+    event: ServerSessionEvent,
+) -> Result<Vec<ServerSessionResult>, ConnectionError> {
+    let mut state = state.lock().unwrap();
+    match event {
+        ServerSessionEvent::ConnectionRequested { request_id, .. } => {
+            Ok(session.accept_request(request_id)?)
+        }
+
+        ServerSessionEvent::PublishStreamRequested {
+            request_id,
+            app_name,
+            stream_key,
+            ..
+        } => {
+            state
+                .hub
+                .join_as_publisher(connection_id, &app_name, &stream_key)?;
+
+            Ok(session.accept_request(request_id)?)
+        }
+
+        ServerSessionEvent::PlayStreamRequested {
+            request_id,
+            app_name,
+            stream_key,
+            stream_id,
+            ..
+        } => {
+            let sender = hub_sender.take().ok_or("Already playing a stream")?;
+            state.subscribers.insert(connection_id, sender);
+
+            // Accept before any media from the hub is sent
+            let accepted = session.accept_request(request_id)?;
+            let results = state.hub.join_as_subscriber(
+                connection_id,
+                &app_name,
+                &stream_key,
+                stream_id,
+                SubscriberDropPolicy::DeliverAll,
+            )?;
+
+            state.route(results);
+            Ok(accepted)
+        }
+
+        event => {
+            record_published(&mut state, &event);
+            let results = state.hub.handle_publisher_event(connection_id, &event)?;
+            state.route(results);
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn record_published(state: &mut ServerState, event: &ServerSessionEvent) {
+    let (app_name, stream_key) = match event {
+        ServerSessionEvent::StreamMetadataChanged {
+            app_name,
+            stream_key,
+            ..
+        }
+        | ServerSessionEvent::VideoDataReceived {
+            app_name,
+            stream_key,
+            ..
+        }
+        | ServerSessionEvent::AudioDataReceived {
+            app_name,
+            stream_key,
+            ..
+        } => (app_name.clone(), stream_key.clone()),
+
+        _ => return,
+    };
+
+    let published = state.published.entry((app_name, stream_key)).or_default();
+    match event {
+        ServerSessionEvent::StreamMetadataChanged { metadata, .. } => {
+            published.metadata = Some(metadata.clone());
+        }
+
+        ServerSessionEvent::VideoDataReceived {
+            data, timestamp, ..
+        } if !is_video_sequence_header(data) => {
+            published.video_frames += 1;
+            published.last_video_timestamp = Some(*timestamp);
+        }
+
+        ServerSessionEvent::AudioDataReceived {
+            data, timestamp, ..
+        } if !is_audio_sequence_header(data) => {
+            published.audio_frames += 1;
+            published.last_audio_timestamp = Some(*timestamp);
+        }
+
+        _ => (),
+    }
+}
@@ -0,0 +1,102 @@
+#[macro_use]
+extern crate criterion;
+extern crate bytes;
+extern crate rml_rtmp;
+
+use bytes::Bytes;
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+use rml_rtmp::chunk_io::{ChunkDeserializer, ChunkSerializer};
+use rml_rtmp::messages::MessagePayload;
+use rml_rtmp::time::RtmpTimestamp;
+
+/// The default chunk size from the RTMP specification, the size most encoders switch to, and
+/// the largest size commonly seen
+const CHUNK_SIZES: [u32; 3] = [128, 4096, 65536];
+
+/// The size of a typical 2.5 megabit/s video frame at 30 frames per second
+const VIDEO_FRAME_SIZE: usize = 10_000;
+
+fn video_payload(timestamp: u32) -> MessagePayload {
+    MessagePayload {
+        timestamp: RtmpTimestamp::new(timestamp),
+        type_id: 9,
+        message_stream_id: 1,
+        data: Bytes::from(vec![1_u8; VIDEO_FRAME_SIZE]),
+    }
+}
+
+fn serialization_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunk serialization");
+    group.throughput(Throughput::Bytes(VIDEO_FRAME_SIZE as u64));
+
+    for chunk_size in CHUNK_SIZES.iter() {
+        let mut serializer = ChunkSerializer::new();
+        serializer
+            .set_max_chunk_size(*chunk_size, RtmpTimestamp::new(0))
+            .unwrap();
+
+        let payload = video_payload(0);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(chunk_size),
+            chunk_size,
+            |b, _| {
+                b.iter(|| {
+                    serializer
+                        .serialize(black_box(&payload), false, true)
+                        .unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn deserialization_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunk deserialization");
+    group.throughput(Throughput::Bytes(VIDEO_FRAME_SIZE as u64));
+
+    for chunk_size in CHUNK_SIZES.iter() {
+        let mut serializer = ChunkSerializer::new();
+        let mut deserializer = ChunkDeserializer::new();
+        serializer
+            .set_max_chunk_size(*chunk_size, RtmpTimestamp::new(0))
+            .unwrap();
+
+        deserializer
+            .set_max_chunk_size(*chunk_size as usize)
+            .unwrap();
+
+        // The first packet has a full header and every packet after it is compressed, so
+        // prime the deserializer with the first one to measure the steady state
+        let first_packet = serializer
+            .serialize(&video_payload(0), false, false)
+            .unwrap();
+        let packet = serializer
+            .serialize(&video_payload(33), false, false)
+            .unwrap();
+        deserializer.get_next_message(&first_packet.bytes).unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(chunk_size),
+            chunk_size,
+            |b, _| {
+                b.iter(|| {
+                    deserializer
+                        .get_next_message(black_box(&packet.bytes))
+                        .unwrap()
+                        .unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    serialization_benchmarks,
+    deserialization_benchmarks
+);
+criterion_main!(benches);
@@ -0,0 +1,92 @@
+#[macro_use]
+extern crate criterion;
+extern crate rml_amf0;
+extern crate rml_rtmp;
+
+use criterion::{black_box, Criterion};
+use rml_amf0::{Amf0Value, ObjectProperties};
+use rml_rtmp::messages::RtmpMessage;
+use rml_rtmp::time::RtmpTimestamp;
+
+/// The `connect` command sent by a typical publishing client
+fn connect_command() -> RtmpMessage {
+    let mut properties = ObjectProperties::new();
+    properties.insert("app".to_string(), Amf0Value::from("live"));
+    properties.insert("type".to_string(), Amf0Value::from("nonprivate"));
+    properties.insert(
+        "flashVer".to_string(),
+        Amf0Value::from("FMLE/3.0 (compatible; FMSc/1.0)"),
+    );
+    properties.insert(
+        "tcUrl".to_string(),
+        Amf0Value::from("rtmp://localhost/live"),
+    );
+    properties.insert("capabilities".to_string(), Amf0Value::Number(239.0));
+    properties.insert("audioCodecs".to_string(), Amf0Value::Number(3575.0));
+    properties.insert("videoCodecs".to_string(), Amf0Value::Number(252.0));
+    properties.insert("objectEncoding".to_string(), Amf0Value::Number(0.0));
+
+    RtmpMessage::Amf0Command {
+        command_name: "connect".to_string(),
+        transaction_id: 1.0,
+        command_object: Amf0Value::Object(properties),
+        additional_arguments: Vec::new(),
+    }
+}
+
+/// The `@setDataFrame` data message sent by a typical encoder
+fn set_data_frame() -> RtmpMessage {
+    let mut properties = ObjectProperties::new();
+    properties.insert("width".to_string(), Amf0Value::Number(1920.0));
+    properties.insert("height".to_string(), Amf0Value::Number(1080.0));
+    properties.insert("videocodecid".to_string(), Amf0Value::from("avc1"));
+    properties.insert("videodatarate".to_string(), Amf0Value::Number(2500.0));
+    properties.insert("framerate".to_string(), Amf0Value::Number(30.0));
+    properties.insert("audiocodecid".to_string(), Amf0Value::from("mp4a"));
+    properties.insert("audiodatarate".to_string(), Amf0Value::Number(160.0));
+    properties.insert("audiosamplerate".to_string(), Amf0Value::Number(48000.0));
+    properties.insert("audiochannels".to_string(), Amf0Value::Number(2.0));
+    properties.insert("stereo".to_string(), Amf0Value::Boolean(true));
+    properties.insert(
+        "encoder".to_string(),
+        Amf0Value::from("obs-output module (libobs version 27.2.4)"),
+    );
+
+    RtmpMessage::Amf0Data {
+        values: vec![
+            Amf0Value::from("@setDataFrame"),
+            Amf0Value::from("onMetaData"),
+            Amf0Value::Object(properties),
+        ],
+    }
+}
+
+fn bench_message(c: &mut Criterion, name: &str, message: RtmpMessage) {
+    let payload = message
+        .clone()
+        .into_message_payload(RtmpTimestamp::new(0), 0)
+        .unwrap();
+
+    c.bench_function(&format!("encode {}", name), |b| {
+        b.iter(|| {
+            black_box(message.clone())
+                .into_message_payload(RtmpTimestamp::new(0), 0)
+                .unwrap()
+        })
+    });
+
+    c.bench_function(&format!("decode {}", name), |b| {
+        b.iter(|| black_box(&payload).to_rtmp_message().unwrap())
+    });
+}
+
+fn connect_benchmarks(c: &mut Criterion) {
+    bench_message(c, "connect command", connect_command());
+}
+
+fn metadata_benchmarks(c: &mut Criterion) {
+    bench_message(c, "@setDataFrame", set_data_frame());
+}
+
+criterion_group!(benches, connect_benchmarks, metadata_benchmarks);
+criterion_main!(benches);
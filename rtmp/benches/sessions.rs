@@ -0,0 +1,186 @@
+#[macro_use]
+extern crate criterion;
+extern crate bytes;
+extern crate rml_rtmp;
+
+use bytes::Bytes;
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+use rml_rtmp::sessions::{
+    ClientSession, ClientSessionConfig, ClientSessionEvent, ClientSessionResult,
+    PublishRequestType, ServerSession, ServerSessionConfig, ServerSessionEvent,
+    ServerSessionResult,
+};
+use rml_rtmp::time::RtmpTimestamp;
+
+const APP_NAME: &str = "live";
+const STREAM_KEY: &str = "stream_key";
+
+/// Video frame sizes of roughly 500 kilobit/s, 2.5 megabit/s, and 25 megabit/s streams at
+/// 30 frames per second
+const FRAME_SIZES: [usize; 3] = [2_000, 10_000, 100_000];
+
+/// A client session connected to a server session, with the server accepting every request
+struct Connection {
+    client: ClientSession,
+    server: ServerSession,
+    client_events: Vec<ClientSessionEvent>,
+    server_events: Vec<ServerSessionEvent>,
+}
+
+impl Connection {
+    fn new() -> Connection {
+        let (client, client_results) = ClientSession::new(ClientSessionConfig::new()).unwrap();
+        let (server, server_results) = ServerSession::new(ServerSessionConfig::new()).unwrap();
+        let mut connection = Connection {
+            client,
+            server,
+            client_events: Vec::new(),
+            server_events: Vec::new(),
+        };
+
+        connection.exchange(client_results, server_results);
+        let result = connection
+            .client
+            .request_connection(APP_NAME.to_string())
+            .unwrap();
+
+        connection.exchange(vec![result], Vec::new());
+        connection
+    }
+
+    /// Delivers the outbound packets of each session to the other until neither has anything
+    /// left to send
+    fn exchange(
+        &mut self,
+        mut client_results: Vec<ClientSessionResult>,
+        mut server_results: Vec<ServerSessionResult>,
+    ) {
+        while !client_results.is_empty() || !server_results.is_empty() {
+            let mut next_server_results = Vec::new();
+            for result in client_results.drain(..) {
+                match result {
+                    ClientSessionResult::OutboundResponse(packet) => {
+                        let results = self.server.handle_input(&packet.bytes).unwrap();
+                        next_server_results.extend(results);
+                    }
+
+                    ClientSessionResult::RaisedEvent(event) => self.client_events.push(event),
+                    ClientSessionResult::UnhandleableMessageReceived(_) => (),
+                }
+            }
+
+            let mut next_client_results = Vec::new();
+            let mut accepted_results = Vec::new();
+            for result in server_results.drain(..).chain(next_server_results) {
+                match result {
+                    ServerSessionResult::OutboundResponse(packet) => {
+                        let results = self.client.handle_input(&packet.bytes).unwrap();
+                        next_client_results.extend(results);
+                    }
+
+                    ServerSessionResult::RaisedEvent(event) => {
+                        let request_id = match event {
+                            ServerSessionEvent::ConnectionRequested { request_id, .. } => {
+                                Some(request_id)
+                            }
+                            ServerSessionEvent::PublishStreamRequested { request_id, .. } => {
+                                Some(request_id)
+                            }
+                            ServerSessionEvent::PlayStreamRequested { request_id, .. } => {
+                                Some(request_id)
+                            }
+                            _ => None,
+                        };
+
+                        if let Some(request_id) = request_id {
+                            let results = self.server.accept_request(request_id).unwrap();
+                            accepted_results.extend(results);
+                        }
+
+                        self.server_events.push(event);
+                    }
+
+                    ServerSessionResult::UnhandleableMessageReceived(_) => (),
+                }
+            }
+
+            client_results = next_client_results;
+            server_results = accepted_results;
+        }
+    }
+}
+
+/// Measures a video frame going from a publishing client, through the server sessions of the
+/// publisher and a player, and being received by the playing client
+fn relay_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("session relay");
+
+    for frame_size in FRAME_SIZES.iter() {
+        let mut publisher = Connection::new();
+        let result = publisher
+            .client
+            .request_publishing(STREAM_KEY.to_string(), PublishRequestType::Live)
+            .unwrap();
+        publisher.exchange(vec![result], Vec::new());
+
+        let mut player = Connection::new();
+        let result = player
+            .client
+            .request_playback(STREAM_KEY.to_string())
+            .unwrap();
+        player.exchange(vec![result], Vec::new());
+
+        let stream_id = player
+            .server_events
+            .iter()
+            .filter_map(|event| match event {
+                ServerSessionEvent::PlayStreamRequested { stream_id, .. } => Some(*stream_id),
+                _ => None,
+            })
+            .next()
+            .unwrap();
+
+        let frame = Bytes::from(vec![1_u8; *frame_size]);
+        let mut timestamp = 0;
+        group.throughput(Throughput::Bytes(*frame_size as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(frame_size),
+            frame_size,
+            |b, _| {
+                b.iter(|| {
+                    timestamp += 33;
+                    let result = publisher
+                        .client
+                        .publish_video_data(frame.clone(), RtmpTimestamp::new(timestamp), false)
+                        .unwrap();
+
+                    let packet = match result {
+                        ClientSessionResult::OutboundResponse(packet) => packet,
+                        _ => panic!("Expected an outbound packet"),
+                    };
+
+                    for result in publisher.server.handle_input(&packet.bytes).unwrap() {
+                        if let ServerSessionResult::RaisedEvent(
+                            ServerSessionEvent::VideoDataReceived {
+                                data, timestamp, ..
+                            },
+                        ) = result
+                        {
+                            let packet = player
+                                .server
+                                .send_video_data(stream_id, data, timestamp, false)
+                                .unwrap();
+
+                            black_box(player.client.handle_input(&packet.bytes).unwrap());
+                        }
+                    }
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, relay_benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+/*!
+This module contains the data model admin endpoints and tools use to report on a running
+server.
+
+A `ServerSnapshot` lists the server's connections and streams at a point in time.  It is built
+from the `StreamHub`, which knows who is publishing and playing each stream, and is then
+filled in with details from each connection's `ServerSession` and each stream's
+`StreamStatsTracker`.  With the `serde` feature enabled every type can be serialized, so the
+snapshot can be returned from an HTTP endpoint as JSON or read back by a CLI.
+*/
+
+mod snapshot;
+
+pub use self::snapshot::{ConnectionInfo, ConnectionRole, ServerSnapshot, StreamInfo};
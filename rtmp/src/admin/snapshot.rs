@@ -0,0 +1,292 @@
+use hub::StreamHub;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sessions::ServerSession;
+use stats::StreamStatsSnapshot;
+use std::time::Duration;
+
+/// What a connection is doing with the streams on the server
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ConnectionRole {
+    /// The connection is not publishing or playing a stream
+    Idle,
+
+    Publishing {
+        app_name: String,
+        stream_key: String,
+    },
+    Playing {
+        app_name: String,
+        stream_key: String,
+    },
+}
+
+/// The state of a single connection to the server
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConnectionInfo {
+    /// The id the application gave the connection, which is the same id used with the hub
+    pub connection_id: usize,
+
+    /// The address of the peer, if the application knows it
+    pub remote_address: Option<String>,
+
+    /// The application the connection is connected to, once it has been accepted
+    pub app_name: Option<String>,
+
+    pub role: ConnectionRole,
+
+    /// How long the connection has been open
+    pub connected_for: Duration,
+
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+}
+
+/// The state of a single stream on the server
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StreamInfo {
+    pub app_name: String,
+    pub stream_key: String,
+
+    /// The id of the connection publishing the stream, if anyone is
+    pub publisher_id: Option<usize>,
+
+    /// How many connections are playing the stream
+    pub viewers: usize,
+
+    /// Video bits received per second
+    pub video_bitrate: u64,
+
+    /// Audio bits received per second
+    pub audio_bitrate: u64,
+
+    pub video_frame_rate: f32,
+
+    /// How long media has been flowing on the stream
+    pub uptime: Option<Duration>,
+}
+
+/// The connections and streams of a server at a point in time
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ServerSnapshot {
+    /// How long the server has been running
+    pub uptime: Duration,
+
+    pub connections: Vec<ConnectionInfo>,
+    pub streams: Vec<StreamInfo>,
+}
+
+impl ConnectionInfo {
+    /// Creates the information for an idle connection that has not sent or received anything
+    pub fn new(connection_id: usize) -> ConnectionInfo {
+        ConnectionInfo {
+            connection_id,
+            remote_address: None,
+            app_name: None,
+            role: ConnectionRole::Idle,
+            connected_for: Duration::from_secs(0),
+            bytes_received: 0,
+            bytes_sent: 0,
+        }
+    }
+
+    /// Fills in the application name and bytes received from the connection's session
+    pub fn apply_session(&mut self, session: &ServerSession) {
+        self.app_name = session.connected_app_name().map(|name| name.to_string());
+        self.bytes_received = session.bytes_received();
+    }
+}
+
+impl StreamInfo {
+    /// Creates the information for a stream without a publisher or viewers
+    pub fn new(app_name: &str, stream_key: &str) -> StreamInfo {
+        StreamInfo {
+            app_name: app_name.to_string(),
+            stream_key: stream_key.to_string(),
+            publisher_id: None,
+            viewers: 0,
+            video_bitrate: 0,
+            audio_bitrate: 0,
+            video_frame_rate: 0.0,
+            uptime: None,
+        }
+    }
+
+    /// Fills in the bitrates, frame rate, and uptime from the stream's statistics
+    pub fn apply_stats(&mut self, stats: &StreamStatsSnapshot) {
+        self.video_bitrate = stats.video_bitrate;
+        self.audio_bitrate = stats.audio_bitrate;
+        self.video_frame_rate = stats.video_frame_rate;
+        self.uptime = stats.uptime;
+    }
+}
+
+impl ServerSnapshot {
+    /// Creates a snapshot without any connections or streams
+    pub fn new(uptime: Duration) -> ServerSnapshot {
+        ServerSnapshot {
+            uptime,
+            connections: Vec::new(),
+            streams: Vec::new(),
+        }
+    }
+
+    /// Creates a snapshot with every stream in the hub and every connection publishing or
+    /// playing them.  Streams are sorted by name and connections by id.  Connections that have
+    /// not joined the hub can be added with `connection_mut()`.
+    pub fn from_hub(hub: &StreamHub, uptime: Duration) -> ServerSnapshot {
+        let mut snapshot = ServerSnapshot::new(uptime);
+        let mut names = hub.stream_names();
+        names.sort();
+
+        for (app_name, stream_key) in names {
+            let mut stream = StreamInfo::new(&app_name, &stream_key);
+            stream.publisher_id = hub.publisher_id(&app_name, &stream_key);
+
+            if let Some(publisher_id) = stream.publisher_id {
+                snapshot.connection_mut(publisher_id).role = ConnectionRole::Publishing {
+                    app_name: app_name.clone(),
+                    stream_key: stream_key.clone(),
+                };
+            }
+
+            let subscriber_ids = hub.subscriber_ids(&app_name, &stream_key);
+            stream.viewers = subscriber_ids.len();
+            for subscriber_id in subscriber_ids {
+                snapshot.connection_mut(subscriber_id).role = ConnectionRole::Playing {
+                    app_name: app_name.clone(),
+                    stream_key: stream_key.clone(),
+                };
+            }
+
+            snapshot.streams.push(stream);
+        }
+
+        snapshot
+    }
+
+    /// Returns the information for the connection, adding an idle connection to the snapshot
+    /// if it isn't already in it
+    pub fn connection_mut(&mut self, connection_id: usize) -> &mut ConnectionInfo {
+        let index = match self
+            .connections
+            .binary_search_by_key(&connection_id, |connection| connection.connection_id)
+        {
+            Ok(index) => index,
+            Err(index) => {
+                self.connections
+                    .insert(index, ConnectionInfo::new(connection_id));
+                index
+            }
+        };
+
+        &mut self.connections[index]
+    }
+
+    /// Returns the information for the stream, if it is in the snapshot
+    pub fn stream_mut(&mut self, app_name: &str, stream_key: &str) -> Option<&mut StreamInfo> {
+        self.streams
+            .iter_mut()
+            .find(|stream| stream.app_name == app_name && stream.stream_key == stream_key)
+    }
+
+    /// The total number of connections playing streams
+    pub fn total_viewers(&self) -> usize {
+        self.streams.iter().map(|stream| stream.viewers).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hub::SubscriberDropPolicy;
+    use sessions::ServerSessionConfig;
+
+    #[test]
+    fn snapshot_from_hub_lists_streams_and_their_connections() {
+        let mut hub = StreamHub::new();
+        hub.join_as_publisher(5, "live", "b").unwrap();
+        hub.join_as_publisher(1, "live", "a").unwrap();
+        hub.join_as_subscriber(3, "live", "a", 1, SubscriberDropPolicy::DeliverAll)
+            .unwrap();
+        hub.join_as_subscriber(2, "live", "a", 1, SubscriberDropPolicy::DeliverAll)
+            .unwrap();
+
+        let snapshot = ServerSnapshot::from_hub(&hub, Duration::from_secs(10));
+
+        assert_eq!(snapshot.uptime, Duration::from_secs(10));
+        assert_eq!(snapshot.streams.len(), 2, "Unexpected number of streams");
+        assert_eq!(snapshot.streams[0].stream_key, "a");
+        assert_eq!(snapshot.streams[0].publisher_id, Some(1));
+        assert_eq!(snapshot.streams[0].viewers, 2);
+        assert_eq!(snapshot.streams[1].stream_key, "b");
+        assert_eq!(snapshot.streams[1].viewers, 0);
+        assert_eq!(snapshot.total_viewers(), 2);
+
+        let ids = snapshot
+            .connections
+            .iter()
+            .map(|connection| connection.connection_id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3, 5]);
+        assert_eq!(
+            snapshot.connections[1].role,
+            ConnectionRole::Playing {
+                app_name: "live".to_string(),
+                stream_key: "a".to_string(),
+            }
+        );
+        assert_eq!(
+            snapshot.connections[3].role,
+            ConnectionRole::Publishing {
+                app_name: "live".to_string(),
+                stream_key: "b".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn connections_not_in_hub_are_added_as_idle() {
+        let mut snapshot = ServerSnapshot::new(Duration::from_secs(1));
+        snapshot.connection_mut(7).bytes_sent = 100;
+        snapshot.connection_mut(4);
+
+        assert_eq!(snapshot.connections.len(), 2);
+        assert_eq!(snapshot.connections[0].connection_id, 4);
+        assert_eq!(snapshot.connections[1].bytes_sent, 100);
+        assert_eq!(snapshot.connections[1].role, ConnectionRole::Idle);
+    }
+
+    #[test]
+    fn session_details_are_applied_to_connection() {
+        let (session, _) = ServerSession::new(ServerSessionConfig::new()).unwrap();
+
+        let mut connection = ConnectionInfo::new(1);
+        connection.apply_session(&session);
+
+        assert_eq!(connection.app_name, None);
+        assert_eq!(connection.bytes_received, 0);
+    }
+
+    #[test]
+    fn stream_stats_are_applied_to_stream() {
+        use stats::{StreamStatsConfig, StreamStatsTracker};
+        use std::time::Instant;
+        use time::RtmpTimestamp;
+
+        let start = Instant::now();
+        let mut tracker = StreamStatsTracker::new(StreamStatsConfig::new());
+        tracker.record_video(&[0x17, 1, 0, 0], RtmpTimestamp::new(0), start);
+        tracker.record_video(&[0x27, 1, 0, 0], RtmpTimestamp::new(500), start);
+
+        let mut stream = StreamInfo::new("live", "key");
+        stream.apply_stats(&tracker.snapshot(start + Duration::from_secs(2)));
+
+        assert_eq!(stream.uptime, Some(Duration::from_secs(2)));
+        assert!(stream.video_bitrate > 0, "Expected a video bitrate");
+    }
+}
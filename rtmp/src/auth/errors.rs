@@ -0,0 +1,26 @@
+use thiserror::Error;
+
+/// Reasons a request's token could not be validated
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum TokenError {
+    /// The request did not come with a `token` parameter
+    #[error("No token was provided")]
+    MissingToken,
+
+    /// The request did not come with an `expires` parameter
+    #[error("No token expiration was provided")]
+    MissingExpiration,
+
+    /// The `expires` parameter was not a unix timestamp
+    #[error("The token expiration '{0}' is not a valid unix timestamp")]
+    InvalidExpiration(String),
+
+    /// The token's expiration time has passed
+    #[error("The token has expired")]
+    Expired,
+
+    /// The token was not signed with the authorizer's secret for the requested resource
+    #[error("The token's signature is invalid")]
+    InvalidSignature,
+}
@@ -0,0 +1,33 @@
+/*!
+This module contains the `TokenAuthorizer`, which secures ingest and playback with expiring
+tokens signed by a shared secret.
+
+Applications hand out urls such as `rtmp://host/live/stream_key?token=...&expires=...`, where
+the token is an HMAC-SHA256 signature of the stream being accessed and the time the url stops
+working.  When a server session raises a request event, the authorizer checks the token that
+came with the request's stream key (or the app name of a connection request), and the
+resulting `Authorization` accepts or rejects the request on the session.
+
+```
+# extern crate rml_rtmp;
+# fn main() {
+use rml_rtmp::auth::TokenAuthorizer;
+use rml_rtmp::names::StreamName;
+use std::time::{Duration, SystemTime};
+
+let authorizer = TokenAuthorizer::new(b"shared secret");
+let expires_at = SystemTime::now() + Duration::from_secs(3600);
+let stream_key = format!("my_stream?{}", authorizer.signed_query("live/my_stream", expires_at));
+
+let stream_name = StreamName::parse(&stream_key);
+assert_eq!(stream_name.name, "my_stream");
+assert!(authorizer.validate("live/my_stream", &stream_name.params, SystemTime::now()).is_ok());
+# }
+```
+*/
+
+mod errors;
+mod token;
+
+pub use self::errors::TokenError;
+pub use self::token::{Authorization, TokenAuthorizer};
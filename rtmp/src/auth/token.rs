@@ -0,0 +1,386 @@
+use super::TokenError;
+use hmac::{Hmac, Mac, NewMac};
+use names::{AppName, QueryParams, StreamName};
+use sessions::{ServerSession, ServerSessionError, ServerSessionEvent, ServerSessionResult};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TOKEN_PARAMETER: &str = "token";
+const EXPIRES_PARAMETER: &str = "expires";
+
+/// Validates the tokens that come with connection, publish, and play requests.
+///
+/// Tokens are the hex encoded HMAC-SHA256 signature of `<resource>:<expires>`, where `expires`
+/// is the unix timestamp (in seconds) that the token stops being valid at.  The resource is the
+/// app name for connection requests, and `<app name>/<stream key>` for publish and play
+/// requests, with any query strings removed.
+pub struct TokenAuthorizer {
+    secret: Vec<u8>,
+
+    /// If connection requests need a token in their app name's query string
+    pub protect_connections: bool,
+
+    /// If publish requests need a token in their stream key's query string
+    pub protect_publishing: bool,
+
+    /// If play requests need a token in their stream key's query string
+    pub protect_playback: bool,
+}
+
+/// The outcome of checking the token of a request raised by a `ServerSession`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Authorization {
+    pub request_id: u32,
+    pub result: Result<(), TokenError>,
+}
+
+impl TokenAuthorizer {
+    /// Creates an authorizer that only requires tokens for publishing
+    pub fn new(secret: &[u8]) -> TokenAuthorizer {
+        TokenAuthorizer {
+            secret: secret.to_vec(),
+            protect_connections: false,
+            protect_publishing: true,
+            protect_playback: false,
+        }
+    }
+
+    /// Creates the hex encoded token that grants access to the resource until it expires
+    pub fn sign(&self, resource: &str, expires: u64) -> String {
+        let signature = self.create_mac(resource, expires).finalize().into_bytes();
+        signature
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Creates the `token=...&expires=...` query string that grants access to the resource
+    /// until the expiration time
+    pub fn signed_query(&self, resource: &str, expires_at: SystemTime) -> String {
+        let expires = unix_seconds(expires_at);
+        format!(
+            "{}={}&{}={}",
+            TOKEN_PARAMETER,
+            self.sign(resource, expires),
+            EXPIRES_PARAMETER,
+            expires
+        )
+    }
+
+    /// Checks that the query string parameters contain a token for the resource that has not
+    /// expired
+    pub fn validate(
+        &self,
+        resource: &str,
+        params: &QueryParams,
+        now: SystemTime,
+    ) -> Result<(), TokenError> {
+        let token = params
+            .get(TOKEN_PARAMETER)
+            .ok_or(TokenError::MissingToken)?;
+        let raw_expires = params
+            .get(EXPIRES_PARAMETER)
+            .ok_or(TokenError::MissingExpiration)?;
+
+        let expires = raw_expires
+            .parse::<u64>()
+            .map_err(|_| TokenError::InvalidExpiration(raw_expires.to_string()))?;
+
+        let signature = decode_hex(token).ok_or(TokenError::InvalidSignature)?;
+        self.create_mac(resource, expires)
+            .verify(&signature)
+            .map_err(|_| TokenError::InvalidSignature)?;
+
+        if unix_seconds(now) >= expires {
+            return Err(TokenError::Expired);
+        }
+
+        Ok(())
+    }
+
+    /// Checks the token of the request the event was raised for.  Requests that are not
+    /// protected are always authorized, and `None` is returned for events that are not requests.
+    pub fn authorize(&self, event: &ServerSessionEvent, now: SystemTime) -> Option<Authorization> {
+        let (request_id, result) = match *event {
+            ServerSessionEvent::ConnectionRequested {
+                request_id,
+                ref app_name,
+            } => {
+                let result = if self.protect_connections {
+                    let app_name = AppName::parse(app_name);
+                    self.validate(&app_name.path(), &app_name.params, now)
+                } else {
+                    Ok(())
+                };
+
+                (request_id, result)
+            }
+
+            ServerSessionEvent::PublishStreamRequested {
+                request_id,
+                ref app_name,
+                ref stream_key,
+                ..
+            } => {
+                let result = if self.protect_publishing {
+                    self.validate_stream_key(app_name, stream_key, now)
+                } else {
+                    Ok(())
+                };
+
+                (request_id, result)
+            }
+
+            ServerSessionEvent::PlayStreamRequested {
+                request_id,
+                ref app_name,
+                ref stream_key,
+                ..
+            } => {
+                let result = if self.protect_playback {
+                    self.validate_stream_key(app_name, stream_key, now)
+                } else {
+                    Ok(())
+                };
+
+                (request_id, result)
+            }
+
+            _ => return None,
+        };
+
+        Some(Authorization { request_id, result })
+    }
+
+    fn validate_stream_key(
+        &self,
+        app_name: &str,
+        stream_key: &str,
+        now: SystemTime,
+    ) -> Result<(), TokenError> {
+        let app_name = AppName::parse(app_name);
+        let stream_name = StreamName::parse(stream_key);
+        let resource = format!("{}/{}", app_name.path(), stream_name.name);
+
+        self.validate(&resource, &stream_name.params, now)
+    }
+
+    fn create_mac(&self, resource: &str, expires: u64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.secret).unwrap();
+        mac.update(resource.as_bytes());
+        mac.update(b":");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+}
+
+impl Authorization {
+    /// Accepts the request on the session if its token was valid, and rejects it with the
+    /// reason it was not otherwise
+    pub fn apply(
+        &self,
+        session: &mut ServerSession,
+    ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
+        match self.result {
+            Ok(()) => session.accept_request(self.request_id),
+            Err(ref error) => session.reject_request(self.request_id, &error.to_string()),
+        }
+    }
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| match *pair {
+            [high, low] => Some((hex_digit(high)? << 4) | hex_digit(low)?),
+            _ => None,
+        })
+        .collect()
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use names::parse_query;
+    use sessions::PublishMode;
+    use std::time::Duration;
+
+    fn publish_event(stream_key: &str) -> ServerSessionEvent {
+        ServerSessionEvent::PublishStreamRequested {
+            request_id: 3,
+            app_name: "live".to_string(),
+            stream_key: stream_key.to_string(),
+            mode: PublishMode::Live,
+        }
+    }
+
+    #[test]
+    fn signed_query_validates_for_same_resource() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let now = SystemTime::now();
+        let query = authorizer.signed_query("live/stream", now + Duration::from_secs(60));
+
+        assert_eq!(
+            authorizer.validate("live/stream", &parse_query(&query), now),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn token_for_different_resource_is_rejected() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let now = SystemTime::now();
+        let query = authorizer.signed_query("live/stream", now + Duration::from_secs(60));
+
+        assert_eq!(
+            authorizer.validate("live/other", &parse_query(&query), now),
+            Err(TokenError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn token_signed_with_different_secret_is_rejected() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let now = SystemTime::now();
+        let query = TokenAuthorizer::new(b"guess")
+            .signed_query("live/stream", now + Duration::from_secs(60));
+
+        assert_eq!(
+            authorizer.validate("live/stream", &parse_query(&query), now),
+            Err(TokenError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let now = SystemTime::now();
+        let query = authorizer.signed_query("live/stream", now - Duration::from_secs(1));
+
+        assert_eq!(
+            authorizer.validate("live/stream", &parse_query(&query), now),
+            Err(TokenError::Expired)
+        );
+    }
+
+    #[test]
+    fn extending_expiration_invalidates_token() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let token = authorizer.sign("live/stream", 1000);
+        let query = format!("token={}&expires=99999999999", token);
+
+        assert_eq!(
+            authorizer.validate("live/stream", &parse_query(&query), SystemTime::now()),
+            Err(TokenError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn missing_and_malformed_parameters_are_rejected() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let now = SystemTime::now();
+
+        assert_eq!(
+            authorizer.validate("live/stream", &parse_query(""), now),
+            Err(TokenError::MissingToken)
+        );
+        assert_eq!(
+            authorizer.validate("live/stream", &parse_query("token=abcd"), now),
+            Err(TokenError::MissingExpiration)
+        );
+        assert_eq!(
+            authorizer.validate("live/stream", &parse_query("token=abcd&expires=soon"), now),
+            Err(TokenError::InvalidExpiration("soon".to_string()))
+        );
+        assert_eq!(
+            authorizer.validate("live/stream", &parse_query("token=xyz&expires=10"), now),
+            Err(TokenError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn publish_request_authorized_by_token_in_stream_key() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let now = SystemTime::now();
+        let query = authorizer.signed_query("live/stream", now + Duration::from_secs(60));
+        let event = publish_event(&format!("stream?{}", query));
+
+        let authorization = authorizer.authorize(&event, now);
+        assert_eq!(
+            authorization,
+            Some(Authorization {
+                request_id: 3,
+                result: Ok(()),
+            })
+        );
+    }
+
+    #[test]
+    fn publish_request_without_token_is_not_authorized() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let authorization = authorizer.authorize(&publish_event("stream"), SystemTime::now());
+
+        assert_eq!(
+            authorization,
+            Some(Authorization {
+                request_id: 3,
+                result: Err(TokenError::MissingToken),
+            })
+        );
+    }
+
+    #[test]
+    fn unprotected_requests_are_authorized() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let event = ServerSessionEvent::ConnectionRequested {
+            request_id: 1,
+            app_name: "live".to_string(),
+        };
+
+        let authorization = authorizer.authorize(&event, SystemTime::now());
+        assert_eq!(
+            authorization,
+            Some(Authorization {
+                request_id: 1,
+                result: Ok(()),
+            })
+        );
+    }
+
+    #[test]
+    fn protected_connection_uses_app_name_query() {
+        let mut authorizer = TokenAuthorizer::new(b"secret");
+        authorizer.protect_connections = true;
+
+        let now = SystemTime::now();
+        let query = authorizer.signed_query("live", now + Duration::from_secs(60));
+        let event = ServerSessionEvent::ConnectionRequested {
+            request_id: 1,
+            app_name: format!("live?{}", query),
+        };
+
+        let authorization = authorizer.authorize(&event, now).unwrap();
+        assert_eq!(authorization.result, Ok(()));
+    }
+
+    #[test]
+    fn events_that_are_not_requests_are_ignored() {
+        let authorizer = TokenAuthorizer::new(b"secret");
+        let event = ServerSessionEvent::ClientChunkSizeChanged {
+            new_chunk_size: 4096,
+        };
+
+        assert_eq!(authorizer.authorize(&event, SystemTime::now()), None);
+    }
+}
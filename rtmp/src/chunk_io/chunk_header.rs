@@ -8,7 +8,7 @@ pub enum ChunkHeaderFormat {
     Empty,                           // Format 3
 }
 
-#[derive(Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct ChunkHeader {
     pub chunk_stream_id: u32,
     pub timestamp: RtmpTimestamp,
@@ -1,3 +1,4 @@
+#[cfg(feature = "std")]
 use std::io;
 use thiserror::Error;
 
@@ -5,6 +6,7 @@ use thiserror::Error;
 /// An enumeration defining all the possible errors that could occur while deserializing
 /// RTMP chunks.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum ChunkDeserializationError {
     /// The RTMP chunk format requires that RTMP chunks that are not type 0 utilize information
     /// from the previously received chunk on that same chunk stream id.  This error occurs when a
@@ -20,7 +22,13 @@ pub enum ChunkDeserializationError {
     #[error("Requested an invalid max chunk size of {chunk_size}.  The largest chunk size possible is 2147483647")]
     InvalidMaxChunkSize { chunk_size: usize },
 
+    /// The input would have made the deserializer hold on to more bytes than the limit it was
+    /// given with `set_memory_limit()`
+    #[error("Deserializing the input requires {required} bytes, which is over the memory limit of {limit} bytes")]
+    MemoryLimitExceeded { limit: usize, required: usize },
+
     /// An I/O error occurred while reading the input buffer
-    #[error("{0}")]
+    #[cfg(feature = "std")]
+    #[error("An IO error occurred while reading the input")]
     Io(#[from] io::Error),
 }
@@ -1,16 +1,20 @@
 use super::chunk_header::{ChunkHeader, ChunkHeaderFormat};
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
-use bytes::{BufMut, BytesMut};
+use alloc::collections::BTreeMap;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use bytes::{Buf, Bytes, BytesMut};
 use chunk_io::ChunkDeserializationError;
+use core::cmp::min;
+use core::mem;
 use messages::MessagePayload;
-use std::cmp::min;
-use std::collections::HashMap;
-use std::io::Cursor;
-use std::mem;
 
 const INITIAL_MAX_CHUNK_SIZE: usize = 128;
 const MAX_INITIAL_TIMESTAMP: u32 = 16777215;
 
+/// Single chunk messages are only shared with the input buffer when they are at least this
+/// fraction of its capacity.  Smaller ones are copied, since a shared message keeps the whole
+/// buffer allocated for as long as it's held on to.
+const SHARED_PAYLOAD_FRACTION: usize = 4;
+
 /// Allows deserializing bytes representing RTMP chunks into RTMP message payloads.
 ///
 /// Due to the nature of the RTMP chunk protocol it is required that every byte going through the
@@ -18,13 +22,13 @@ const MAX_INITIAL_TIMESTAMP: u32 = 16777215;
 /// chunks, so any chunks missing from the stream may cause deserialization errors.
 pub struct ChunkDeserializer {
     max_chunk_size: usize,
-    current_header_format: ChunkHeaderFormat,
     current_header: ChunkHeader,
     current_stage: ParseStage,
     current_payload: MessagePayload,
     current_payload_data: BytesMut,
     buffer: BytesMut,
-    previous_headers: HashMap<u32, ChunkHeader>,
+    previous_headers: BTreeMap<u32, ChunkHeader>,
+    memory_limit: Option<usize>,
 }
 
 enum ParsedValue<T> {
@@ -33,13 +37,8 @@ enum ParsedValue<T> {
 }
 
 enum ParseStage {
-    Csid,
-    InitialTimestamp,
-    MessageLength,
-    MessageTypeId,
-    MessageStreamId,
+    Header,
     MessagePayload,
-    ExtendedTimestamp,
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -56,13 +55,13 @@ impl ChunkDeserializer {
     pub fn new() -> ChunkDeserializer {
         ChunkDeserializer {
             max_chunk_size: INITIAL_MAX_CHUNK_SIZE,
-            current_header_format: ChunkHeaderFormat::Full,
             current_header: ChunkHeader::new(),
-            current_stage: ParseStage::Csid,
+            current_stage: ParseStage::Header,
             buffer: BytesMut::with_capacity(4096),
-            previous_headers: HashMap::new(),
+            previous_headers: BTreeMap::new(),
             current_payload: MessagePayload::new(),
             current_payload_data: BytesMut::new(),
+            memory_limit: None,
         }
     }
 
@@ -91,6 +90,12 @@ impl ChunkDeserializer {
     /// returned.  Since it is important not to keep sending it the same bytes over and over again
     /// an empty slice must be passed in for subsequent calls.
     ///
+    /// Messages that arrive in a single chunk and make up a large part of the deserializer's
+    /// input buffer share their data with it instead of being copied out of it, so media can be
+    /// passed from here through to session events and outbound messages without its payload
+    /// being copied again.  Smaller messages are copied so holding on to them doesn't keep the
+    /// rest of the buffer allocated.
+    ///
     /// ## Examples
     ///
     /// ```
@@ -123,14 +128,14 @@ impl ChunkDeserializer {
     /// };
     ///
     /// let mut serializer = ChunkSerializer::new();
-    /// let mut packet1 = serializer.serialize(&input1, false, false).unwrap();
-    /// let mut packet2 = serializer.serialize(&input2, false, false).unwrap();
-    /// let mut packet3 = serializer.serialize(&input3, false, false).unwrap();
+    /// let packet1 = serializer.serialize(&input1, false, false).unwrap();
+    /// let packet2 = serializer.serialize(&input2, false, false).unwrap();
+    /// let packet3 = serializer.serialize(&input3, false, false).unwrap();
     ///
     /// let mut all_bytes = Vec::new();
-    /// all_bytes.append(&mut packet1.bytes);
-    /// all_bytes.append(&mut packet2.bytes);
-    /// all_bytes.append(&mut packet3.bytes);
+    /// all_bytes.extend_from_slice(&packet1.bytes);
+    /// all_bytes.extend_from_slice(&packet2.bytes);
+    /// all_bytes.extend_from_slice(&packet3.bytes);
     ///
     /// let mut deserializer = ChunkDeserializer::new();
     /// let message1 = deserializer.get_next_message(&all_bytes[..]).unwrap();
@@ -148,17 +153,13 @@ impl ChunkDeserializer {
         &mut self,
         bytes: &[u8],
     ) -> Result<Option<MessagePayload>, ChunkDeserializationError> {
+        self.check_memory_limit(bytes.len())?;
         self.buffer.extend_from_slice(bytes);
 
         loop {
             let mut complete_message = None;
             let result = match self.current_stage {
-                ParseStage::Csid => self.form_header()?,
-                ParseStage::InitialTimestamp => self.get_initial_timestamp()?,
-                ParseStage::MessageLength => self.get_message_length()?,
-                ParseStage::MessageTypeId => self.get_message_type_id()?,
-                ParseStage::MessageStreamId => self.get_message_stream_id()?,
-                ParseStage::ExtendedTimestamp => self.get_extended_timestamp()?,
+                ParseStage::Header => self.read_header()?,
                 ParseStage::MessagePayload => self.get_message_data(&mut complete_message)?,
             };
 
@@ -196,168 +197,146 @@ impl ChunkDeserializer {
         self.max_chunk_size
     }
 
-    fn form_header(&mut self) -> Result<ParseStageResult, ChunkDeserializationError> {
-        if self.buffer.len() < 1 {
-            return Ok(ParseStageResult::NotEnoughBytes);
-        }
-
-        self.current_header_format = get_format(&self.buffer[0]);
-        let (csid, next_index) = match get_csid(&self.buffer[..]) {
-            ParsedValue::NotEnoughBytes => return Ok(ParseStageResult::NotEnoughBytes),
-            ParsedValue::Value { val, next_index } => (val, next_index),
-        };
-
-        self.current_header = match self.current_header_format {
-            ChunkHeaderFormat::Full => {
-                let mut new_header = ChunkHeader::new();
-                new_header.chunk_stream_id = csid;
-                new_header
-            }
-
-            _ => match self.previous_headers.remove(&csid) {
-                None => return Err(ChunkDeserializationError::NoPreviousChunkOnStream { csid }),
-                Some(header) => header,
-            },
-        };
+    /// Limits how many bytes the deserializer may hold on to, as counted by `memory_usage()`.
+    ///
+    /// Peers choose how large their messages are and how many chunk streams they use, so without
+    /// a limit a peer can make the deserializer buffer up to 16MB for a single message.  Once a
+    /// limit is set, input that takes the deserializer past it, and messages whose declared
+    /// length wouldn't fit in what's left of it, fail with a `MemoryLimitExceeded` error before
+    /// their data is buffered.  `None` removes the limit.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+    }
 
-        let _ = self.buffer.split_to(next_index as usize);
-        self.current_stage = ParseStage::InitialTimestamp;
-        Ok(ParseStageResult::Success)
+    /// The number of bytes the deserializer is holding on to.  This counts input that hasn't
+    /// been deserialized yet, the space set aside for the message being reassembled from
+    /// multiple chunks, and the headers remembered for each chunk stream id.
+    pub fn memory_usage(&self) -> usize {
+        self.buffer.len()
+            + self.current_payload_data.capacity()
+            + self.previous_headers.len() * mem::size_of::<ChunkHeader>()
     }
 
-    fn get_initial_timestamp(&mut self) -> Result<ParseStageResult, ChunkDeserializationError> {
-        if self.current_header_format == ChunkHeaderFormat::Empty {
-            // Some encoders send an empty header after a type 1 header due to a message split
-            // across multiple chunks.  We need to be careful *NOT* to apply the delta to each
-            // type 3 chunk that's trying to serve a single message, otherwise timestamps will
-            // get out of control.
-            if self.current_payload_data.len() == 0 {
-                // Since we don't have any payload data yet, that means this is the first
-                // chunk of the message.  As it's the first chunk this is the only time we should
-                // apply the previous header's delta to the timestamp
-                self.current_header.timestamp =
-                    self.current_header.timestamp + self.current_header.timestamp_field;
+    fn check_memory_limit(&self, additional: usize) -> Result<(), ChunkDeserializationError> {
+        if let Some(limit) = self.memory_limit {
+            let required = self.memory_usage() + additional;
+            if required > limit {
+                return Err(ChunkDeserializationError::MemoryLimitExceeded { limit, required });
             }
-
-            self.current_stage = ParseStage::MessageLength;
-            return Ok(ParseStageResult::Success);
-        }
-
-        if self.buffer.len() < 3 {
-            return Ok(ParseStageResult::NotEnoughBytes);
         }
 
-        let timestamp;
-        {
-            let bytes = self.buffer.split_to(3);
-            let mut cursor = Cursor::new(bytes);
-            timestamp = cursor.read_u24::<BigEndian>()?;
-        }
+        Ok(())
+    }
 
-        if self.current_header_format == ChunkHeaderFormat::Full {
-            self.current_header.timestamp.set(timestamp);
-        } else {
-            // Non full headers are deltas only
-            self.current_header.timestamp = self.current_header.timestamp + timestamp;
+    fn read_header(&mut self) -> Result<ParseStageResult, ChunkDeserializationError> {
+        if self.buffer.is_empty() {
+            return Ok(ParseStageResult::NotEnoughBytes);
         }
 
-        //apply the timestamp field
-        self.current_header.timestamp_field = timestamp;
+        let format = get_format(&self.buffer[0]);
+        let (csid, basic_header_length) = match get_csid(&self.buffer[..]) {
+            ParsedValue::NotEnoughBytes => return Ok(ParseStageResult::NotEnoughBytes),
+            ParsedValue::Value { val, next_index } => (val, next_index as usize),
+        };
 
-        self.current_stage = ParseStage::MessageLength;
-        Ok(ParseStageResult::Success)
-    }
+        let previous_timestamp_field = match self.previous_headers.get(&csid) {
+            Some(header) => header.timestamp_field,
+            None if format == ChunkHeaderFormat::Full => 0,
+            None => return Err(ChunkDeserializationError::NoPreviousChunkOnStream { csid }),
+        };
 
-    fn get_message_length(&mut self) -> Result<ParseStageResult, ChunkDeserializationError> {
-        if self.current_header_format == ChunkHeaderFormat::TimeDeltaOnly
-            || self.current_header_format == ChunkHeaderFormat::Empty
-        {
-            self.current_stage = ParseStage::MessageTypeId;
-            return Ok(ParseStageResult::Success);
-        }
+        // Nothing is consumed until the whole header is available.  A header split across reads
+        // is read again from its first byte once the rest of it arrives, which is cheaper than
+        // keeping track of every field that was read at small chunk sizes.
+        let message_header_length = match format {
+            ChunkHeaderFormat::Full => 11,
+            ChunkHeaderFormat::TimeDeltaWithoutMessageStreamId => 7,
+            ChunkHeaderFormat::TimeDeltaOnly => 3,
+            ChunkHeaderFormat::Empty => 0,
+        };
 
-        if self.buffer.len() < 3 {
+        let bytes = &self.buffer[basic_header_length..];
+        if bytes.len() < message_header_length {
             return Ok(ParseStageResult::NotEnoughBytes);
         }
 
-        let length;
-        {
-            let bytes = self.buffer.split_to(3);
-            let mut cursor = Cursor::new(bytes);
-            length = cursor.read_u24::<BigEndian>()?;
-        }
-
-        self.current_header.message_length = length;
-        self.current_stage = ParseStage::MessageTypeId;
-        Ok(ParseStageResult::Success)
-    }
+        // Empty headers don't have a timestamp field, but still have an extended timestamp if
+        // the previous header on the chunk stream did
+        let timestamp_field = match format {
+            ChunkHeaderFormat::Empty => previous_timestamp_field,
+            _ => BigEndian::read_u24(&bytes[0..3]),
+        };
 
-    fn get_message_type_id(&mut self) -> Result<ParseStageResult, ChunkDeserializationError> {
-        if self.current_header_format == ChunkHeaderFormat::TimeDeltaOnly
-            || self.current_header_format == ChunkHeaderFormat::Empty
-        {
-            self.current_stage = ParseStage::MessageStreamId;
-            return Ok(ParseStageResult::Success);
-        }
+        let extended_timestamp_length = if timestamp_field >= MAX_INITIAL_TIMESTAMP {
+            4
+        } else {
+            0
+        };
 
-        if self.buffer.len() < 1 {
+        if bytes.len() < message_header_length + extended_timestamp_length {
             return Ok(ParseStageResult::NotEnoughBytes);
         }
 
-        self.current_header.message_type_id = self.buffer[0];
-        let _ = self.buffer.split_to(1);
-        self.current_stage = ParseStage::MessageStreamId;
-        Ok(ParseStageResult::Success)
-    }
+        let mut header = match format {
+            ChunkHeaderFormat::Full => ChunkHeader::new(),
+            _ => self
+                .previous_headers
+                .remove(&csid)
+                .unwrap_or_else(ChunkHeader::new),
+        };
 
-    fn get_message_stream_id(&mut self) -> Result<ParseStageResult, ChunkDeserializationError> {
-        if self.current_header_format != ChunkHeaderFormat::Full {
-            self.current_stage = ParseStage::ExtendedTimestamp;
-            return Ok(ParseStageResult::Success);
-        }
+        header.chunk_stream_id = csid;
+        let is_first_chunk = self.current_payload_data.is_empty();
+        match format {
+            ChunkHeaderFormat::Full => header.timestamp.set(timestamp_field),
 
-        if self.buffer.len() < 4 {
-            return Ok(ParseStageResult::NotEnoughBytes);
-        }
+            // Some encoders send an empty header after a type 1 header due to a message split
+            // across multiple chunks.  We need to be careful *NOT* to apply the delta to each
+            // type 3 chunk that's trying to serve a single message, otherwise timestamps will
+            // get out of control.  The first chunk of the message is the only time we should
+            // apply the previous header's delta to the timestamp.
+            ChunkHeaderFormat::Empty => {
+                if is_first_chunk {
+                    header.timestamp += timestamp_field;
+                }
+            }
 
-        let stream_id;
-        {
-            let bytes = self.buffer.split_to(4);
-            let mut cursor = Cursor::new(bytes);
-            stream_id = cursor.read_u32::<LittleEndian>()?;
+            // Non full headers are deltas only
+            _ => header.timestamp += timestamp_field,
         }
 
-        self.current_header.message_stream_id = stream_id;
-        self.current_stage = ParseStage::ExtendedTimestamp;
-        Ok(ParseStageResult::Success)
-    }
+        header.timestamp_field = timestamp_field;
 
-    fn get_extended_timestamp(&mut self) -> Result<ParseStageResult, ChunkDeserializationError> {
-        if self.current_header.timestamp_field < MAX_INITIAL_TIMESTAMP {
-            self.current_stage = ParseStage::MessagePayload;
-            return Ok(ParseStageResult::Success);
+        if format == ChunkHeaderFormat::Full
+            || format == ChunkHeaderFormat::TimeDeltaWithoutMessageStreamId
+        {
+            header.message_length = BigEndian::read_u24(&bytes[3..6]);
+            header.message_type_id = bytes[6];
         }
 
-        if self.buffer.len() < 4 {
-            return Ok(ParseStageResult::NotEnoughBytes);
+        if format == ChunkHeaderFormat::Full {
+            header.message_stream_id = LittleEndian::read_u32(&bytes[7..11]);
         }
 
-        let timestamp;
-        {
-            let bytes = self.buffer.split_to(4);
-            let mut cursor = Cursor::new(bytes);
-            timestamp = cursor.read_u32::<BigEndian>()?;
+        if extended_timestamp_length > 0 {
+            let timestamp = BigEndian::read_u32(&bytes[message_header_length..]);
+
+            // If the type 3 chunk is not the first chunk of a message, we just ignore it's
+            // extended timestamp because the timestamp of this message was already deserialized.
+            if format == ChunkHeaderFormat::Full {
+                header.timestamp.set(timestamp);
+            } else if is_first_chunk {
+                // Since we already added the MAX_INITIAL_TIMESTAMP to the timestamp, only add the
+                // delta difference.  Peers aren't supposed to send extended deltas below the
+                // maximum initial timestamp, but if they do it has to wrap instead of overflowing.
+                header.timestamp = header.timestamp - MAX_INITIAL_TIMESTAMP + timestamp;
+            }
         }
 
-        // If the type 3 chunk is not the first chunk of a message, we just ignore it's extended timestamp because the timestamp of this message was already deserialized.
-        if self.current_header_format == ChunkHeaderFormat::Full {
-            self.current_header.timestamp.set(timestamp);
-        } else if self.current_payload_data.len() == 0 {
-            // Since we already added the MAX_INITIAL_TIMESTAMP to the timestamp, only add the delta difference
-            self.current_header.timestamp =
-                self.current_header.timestamp + (timestamp - MAX_INITIAL_TIMESTAMP);
-        }
+        self.buffer
+            .advance(basic_header_length + message_header_length + extended_timestamp_length);
 
+        self.current_header = header;
         self.current_stage = ParseStage::MessagePayload;
         Ok(ParseStageResult::Success)
     }
@@ -369,8 +348,8 @@ impl ChunkDeserializer {
         let mut length = self.current_header.message_length as usize;
         let current_payload_length = self.current_payload_data.len();
         let remaining_bytes = length - current_payload_length;
-        if length > self.max_chunk_size as usize {
-            length = min(remaining_bytes, self.max_chunk_size as usize);
+        if length > self.max_chunk_size {
+            length = min(remaining_bytes, self.max_chunk_size);
         }
 
         if self.buffer.len() < length {
@@ -381,22 +360,44 @@ impl ChunkDeserializer {
         self.current_payload.type_id = self.current_header.message_type_id;
         self.current_payload.message_stream_id = self.current_header.message_stream_id;
 
-        // Make sure the we have enough capacity for the whole message data.  This
-        // helps with performance when there are smaller chunk sizes.
-        if remaining_bytes > self.current_payload_data.remaining_mut() {
-            let capacity_needed = remaining_bytes - self.current_payload_data.remaining_mut();
-            self.current_payload_data.reserve(capacity_needed);
-        }
+        let message_length = self.current_header.message_length as usize;
+        let completed_data = if current_payload_length == 0 && length == message_length {
+            // The whole message is in this chunk, so its data can be handed out without being
+            // copied out of the input buffer, as long as it's large enough to be worth pinning
+            // the buffer's allocation for
+            if length * SHARED_PAYLOAD_FRACTION >= self.buffer.capacity() {
+                Some(self.buffer.split_to(length).freeze())
+            } else {
+                let data = Bytes::copy_from_slice(&self.buffer[..length]);
+                self.buffer.advance(length);
+                Some(data)
+            }
+        } else {
+            // Make sure the we have enough capacity for the whole message data.  This
+            // helps with performance when there are smaller chunk sizes, and means the whole
+            // message is checked against the memory limit before any of it is buffered.
+            let spare_capacity =
+                self.current_payload_data.capacity() - self.current_payload_data.len();
+            if remaining_bytes > spare_capacity {
+                self.check_memory_limit(remaining_bytes - spare_capacity)?;
+                self.current_payload_data.reserve(remaining_bytes);
+            }
 
-        let bytes = self.buffer.split_to(length as usize);
-        self.current_payload_data.extend_from_slice(&bytes[..]);
+            self.current_payload_data
+                .extend_from_slice(&self.buffer[..length]);
+            self.buffer.advance(length);
+            if self.current_payload_data.len() == message_length {
+                let data = mem::replace(&mut self.current_payload_data, BytesMut::new());
+                Some(data.freeze())
+            } else {
+                None
+            }
+        };
 
-        // Check if this completes the message
-        if self.current_payload_data.len() == self.current_header.message_length as usize {
-            let data = mem::replace(&mut self.current_payload_data, BytesMut::new());
-            self.current_payload.data = data.freeze();
+        if let Some(data) = completed_data {
+            self.current_payload.data = data;
 
-            let payload = mem::replace(&mut self.current_payload, MessagePayload::new());
+            let payload = mem::take(&mut self.current_payload);
             *message_to_return = Some(payload)
         }
 
@@ -404,11 +405,17 @@ impl ChunkDeserializer {
         let current_header = mem::replace(&mut self.current_header, ChunkHeader::new());
         self.previous_headers
             .insert(current_header.chunk_stream_id, current_header);
-        self.current_stage = ParseStage::Csid;
+        self.current_stage = ParseStage::Header;
         Ok(ParseStageResult::Success)
     }
 }
 
+impl Default for ChunkDeserializer {
+    fn default() -> Self {
+        ChunkDeserializer::new()
+    }
+}
+
 fn get_format(byte: &u8) -> ChunkHeaderFormat {
     const TYPE_0_MASK: u8 = 0b00000000;
     const TYPE_1_MASK: u8 = 0b01000000;
@@ -428,7 +435,7 @@ fn get_format(byte: &u8) -> ChunkHeaderFormat {
 fn get_csid(buffer: &[u8]) -> ParsedValue<u32> {
     const CSID_MASK: u8 = 0b00111111;
 
-    if buffer.len() < 1 {
+    if buffer.is_empty() {
         return ParsedValue::NotEnoughBytes;
     }
 
@@ -463,10 +470,15 @@ fn get_csid(buffer: &[u8]) -> ParsedValue<u32> {
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::identity_op,
+    clippy::partialeq_to_none,
+    clippy::single_match
+)]
 mod tests {
     use super::*;
-    use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
-    use std::io::{Cursor, Write};
+    use alloc::vec::Vec;
     use time::RtmpTimestamp;
 
     #[test]
@@ -763,6 +775,59 @@ mod tests {
         assert_eq!(&result.data[..], &payload[..], "Incorrect data");
     }
 
+    #[test]
+    fn type_2_chunk_delta_wraps_timestamp_around() {
+        let csid = 50;
+        let payload = [1_u8, 2_u8, 3_u8];
+
+        let chunk_0_bytes =
+            form_type_0_chunk(csid, u32::MAX - 4, 5, 3, &payload, INITIAL_MAX_CHUNK_SIZE);
+        let chunk_2_bytes = form_type_2_chunk(csid, 10, &payload);
+        let mut deserializer = ChunkDeserializer::new();
+        let _ = deserializer
+            .get_next_message(&chunk_0_bytes)
+            .unwrap()
+            .unwrap();
+        let result = deserializer
+            .get_next_message(&chunk_2_bytes)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            result.timestamp,
+            RtmpTimestamp::new(5),
+            "Incorrect timestamp"
+        );
+    }
+
+    #[test]
+    fn extended_delta_below_max_initial_timestamp_is_applied() {
+        let csid = 50;
+        let payload = [1_u8, 2_u8, 3_u8];
+
+        let chunk_0_bytes = form_type_0_chunk(csid, 25, 5, 3, &payload, INITIAL_MAX_CHUNK_SIZE);
+
+        // The delta is small enough that it shouldn't have been sent as an extended timestamp
+        let mut buffer = Vec::new();
+        buffer.push((csid as u8) | 0b10000000);
+        buffer.extend_from_slice(&u32::to_be_bytes(16777215)[1..]);
+        buffer.extend_from_slice(&u32::to_be_bytes(100));
+        buffer.extend_from_slice(&payload);
+
+        let mut deserializer = ChunkDeserializer::new();
+        let _ = deserializer
+            .get_next_message(&chunk_0_bytes)
+            .unwrap()
+            .unwrap();
+        let result = deserializer.get_next_message(&buffer).unwrap().unwrap();
+
+        assert_eq!(
+            result.timestamp,
+            RtmpTimestamp::new(125),
+            "Incorrect timestamp"
+        );
+    }
+
     #[test]
     fn can_read_type_3_chunk_with_small_chunk_stream_id_and_small_timestamp() {
         let csid = 50;
@@ -895,6 +960,34 @@ mod tests {
         assert_eq!(&result.data[..], &payload[..], "Incorrect data");
     }
 
+    #[test]
+    fn can_read_headers_split_across_every_byte() {
+        let payload = [7_u8; 10];
+        let timestamp = MAX_INITIAL_TIMESTAMP + 10;
+        let bytes = form_type_0_chunk(500, timestamp, 5, 9, &payload, 4);
+
+        let mut deserializer = ChunkDeserializer::new();
+        deserializer.set_max_chunk_size(4).unwrap();
+
+        let (last, rest) = bytes.split_last().unwrap();
+        for byte in rest {
+            if let Some(x) = deserializer.get_next_message(&[*byte]).unwrap() {
+                panic!("Expected None but received {:?}", x)
+            }
+        }
+
+        let result = deserializer.get_next_message(&[*last]).unwrap().unwrap();
+
+        assert_eq!(result.type_id, 9, "Incorrect type id");
+        assert_eq!(result.message_stream_id, 5, "Incorrect message stream id");
+        assert_eq!(
+            result.timestamp,
+            RtmpTimestamp::new(timestamp),
+            "Incorrect timestamp"
+        );
+        assert_eq!(&result.data[..], &payload[..], "Incorrect data");
+    }
+
     #[test]
     fn can_read_message_exceeding_maximum_chunk_size() {
         let csid = 50;
@@ -1001,6 +1094,137 @@ mod tests {
         );
     }
 
+    #[test]
+    fn single_chunk_payload_is_unaffected_by_later_input() {
+        let first = form_type_0_chunk(50, 25, 5, 9, &[1, 2, 3], 128);
+        let second = form_type_0_chunk(50, 50, 5, 9, &[4, 5, 6], 128);
+        let mut bytes = first.clone();
+        bytes.extend_from_slice(&second[..4]);
+
+        let mut deserializer = ChunkDeserializer::new();
+        let payload1 = deserializer.get_next_message(&bytes).unwrap().unwrap();
+        let payload2 = deserializer
+            .get_next_message(&second[4..])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            &payload1.data[..],
+            &[1, 2, 3],
+            "Incorrect first payload data"
+        );
+        assert_eq!(
+            &payload2.data[..],
+            &[4, 5, 6],
+            "Incorrect second payload data"
+        );
+    }
+
+    #[test]
+    fn large_single_chunk_message_shares_the_input_buffer() {
+        let payload = [100_u8; 2000];
+        let bytes = form_type_0_chunk(50, 25, 5, 9, &payload, 2000);
+
+        let mut deserializer = ChunkDeserializer::new();
+        deserializer.set_max_chunk_size(2000).unwrap();
+        let result = deserializer.get_next_message(&bytes).unwrap().unwrap();
+
+        assert_eq!(&result.data[..], &payload[..], "Incorrect data");
+        assert_eq!(
+            result.data.as_ptr_range().end,
+            deserializer.buffer.as_ptr(),
+            "Payload should be split off of the input buffer"
+        );
+    }
+
+    #[test]
+    fn small_single_chunk_message_is_copied_out_of_the_input_buffer() {
+        let payload = [100_u8; 10];
+        let mut bytes = form_type_0_chunk(50, 25, 5, 9, &payload, 100);
+        bytes.extend(form_type_0_chunk(50, 25, 5, 9, &[200_u8; 1000], 1000));
+
+        let mut deserializer = ChunkDeserializer::new();
+        deserializer.set_max_chunk_size(1000).unwrap();
+        let result = deserializer.get_next_message(&bytes).unwrap().unwrap();
+
+        assert_eq!(&result.data[..], &payload[..], "Incorrect data");
+        assert_ne!(
+            result.data.as_ptr_range().end,
+            deserializer.buffer.as_ptr(),
+            "Payload should not be sharing the input buffer"
+        );
+    }
+
+    #[test]
+    fn message_over_memory_limit_is_rejected_before_it_is_buffered() {
+        let payload = [100_u8; 5000];
+        let bytes = form_type_0_chunk(50, 25, 5, 9, &payload, 100);
+
+        let mut deserializer = ChunkDeserializer::new();
+        deserializer.set_max_chunk_size(100).unwrap();
+        deserializer.set_memory_limit(Some(1000));
+
+        match deserializer.get_next_message(&bytes[..200]) {
+            Err(ChunkDeserializationError::MemoryLimitExceeded {
+                limit: 1000,
+                required,
+            }) => {
+                assert!(
+                    required > 5000,
+                    "Required bytes of {} are too low",
+                    required
+                );
+            }
+            x => panic!("Expected memory limit exceeded error, instead got {:?}", x),
+        }
+
+        assert!(
+            deserializer.memory_usage() <= 1000,
+            "Memory usage of {} is over the limit",
+            deserializer.memory_usage()
+        );
+    }
+
+    #[test]
+    fn input_over_memory_limit_is_rejected_before_it_is_buffered() {
+        let bytes = [0_u8; 1500];
+
+        let mut deserializer = ChunkDeserializer::new();
+        deserializer.set_memory_limit(Some(1000));
+
+        match deserializer.get_next_message(&bytes) {
+            Err(ChunkDeserializationError::MemoryLimitExceeded {
+                limit: 1000,
+                required: 1500,
+            }) => (),
+            x => panic!("Expected memory limit exceeded error, instead got {:?}", x),
+        }
+
+        assert_eq!(
+            deserializer.memory_usage(),
+            0,
+            "Rejected input should not be buffered"
+        );
+    }
+
+    #[test]
+    fn messages_within_memory_limit_are_deserialized() {
+        let payload = [100_u8; 500];
+        let bytes = form_type_0_chunk(50, 25, 5, 9, &payload, 100);
+
+        let mut deserializer = ChunkDeserializer::new();
+        deserializer.set_max_chunk_size(100).unwrap();
+        deserializer.set_memory_limit(Some(2000));
+        let result = deserializer.get_next_message(&bytes).unwrap().unwrap();
+
+        assert_eq!(&result.data[..], &payload[..], "Incorrect data");
+        assert_eq!(
+            deserializer.memory_usage(),
+            mem::size_of::<ChunkHeader>(),
+            "Only the chunk stream's header should be held on to"
+        );
+    }
+
     fn form_type_0_chunk(
         csid: u32,
         timestamp: u32,
@@ -1009,15 +1233,15 @@ mod tests {
         payload: &[u8],
         max_chunk_length: usize,
     ) -> Vec<u8> {
-        let mut cursor = Cursor::new(Vec::new());
+        let mut buffer = Vec::new();
         if csid < 64 {
-            cursor.write_u8(csid as u8).unwrap();
+            buffer.push(csid as u8);
         } else if csid < 319 {
-            cursor.write_u8(0_u8).unwrap();
-            cursor.write_u8((csid - 64) as u8).unwrap();
+            buffer.push(0_u8);
+            buffer.push((csid - 64) as u8);
         } else {
-            cursor.write_u8(1_u8).unwrap();
-            cursor.write_u16::<BigEndian>((csid - 64) as u16).unwrap();
+            buffer.push(1_u8);
+            buffer.extend_from_slice(&u16::to_be_bytes((csid - 64) as u16));
         }
 
         let standard_timestamp = if timestamp >= 16777215 {
@@ -1025,14 +1249,14 @@ mod tests {
         } else {
             timestamp
         };
-        cursor.write_u24::<BigEndian>(standard_timestamp).unwrap();
-        cursor.write_u24::<BigEndian>(payload.len() as u32).unwrap();
-        cursor.write_u8(type_id).unwrap();
-        cursor.write_u32::<LittleEndian>(message_stream_id).unwrap();
+        buffer.extend_from_slice(&u32::to_be_bytes(standard_timestamp)[1..]);
+        buffer.extend_from_slice(&u32::to_be_bytes(payload.len() as u32)[1..]);
+        buffer.push(type_id);
+        buffer.extend_from_slice(&u32::to_le_bytes(message_stream_id));
 
         let mut option_extended_timestamp = None;
         if timestamp > 16777215 {
-            cursor.write_u32::<BigEndian>(timestamp).unwrap();
+            buffer.extend_from_slice(&u32::to_be_bytes(timestamp));
             option_extended_timestamp = Some(timestamp);
         }
 
@@ -1040,7 +1264,7 @@ mod tests {
         // and therefore need to only write the max chunk amount of the payload in this request
         // and append a type 3 chunk with the rest
         if payload.len() > max_chunk_length {
-            cursor.write(&payload[..max_chunk_length]).unwrap();
+            buffer.extend_from_slice(&payload[..max_chunk_length]);
 
             let next_chunk = form_type_3_chunk(
                 csid,
@@ -1048,62 +1272,62 @@ mod tests {
                 max_chunk_length,
                 option_extended_timestamp,
             );
-            cursor.write(&next_chunk).unwrap();
+            buffer.extend_from_slice(&next_chunk);
         } else {
-            cursor.write(payload).unwrap();
+            buffer.extend_from_slice(payload);
         }
 
-        cursor.into_inner()
+        buffer
     }
 
     fn form_type_1_chunk(csid: u32, delta: u32, type_id: u8, payload: &[u8]) -> Vec<u8> {
-        let mut cursor = Cursor::new(Vec::new());
+        let mut buffer = Vec::new();
         if csid < 64 {
-            cursor.write_u8((csid as u8) | 0b01000000).unwrap();
+            buffer.push((csid as u8) | 0b01000000);
         } else if csid < 319 {
-            cursor.write_u8(0_u8 | 0b01000000).unwrap();
-            cursor.write_u8((csid - 64) as u8).unwrap();
+            buffer.push(0_u8 | 0b01000000);
+            buffer.push((csid - 64) as u8);
         } else {
-            cursor.write_u8(1_u8 | 0b01000000).unwrap();
-            cursor.write_u16::<BigEndian>((csid - 64) as u16).unwrap();
+            buffer.push(1_u8 | 0b01000000);
+            buffer.extend_from_slice(&u16::to_be_bytes((csid - 64) as u16));
         }
 
         let standard_timestamp = if delta >= 16777215 { 16777215 } else { delta };
-        cursor.write_u24::<BigEndian>(standard_timestamp).unwrap();
-        cursor.write_u24::<BigEndian>(payload.len() as u32).unwrap();
-        cursor.write_u8(type_id).unwrap();
+        buffer.extend_from_slice(&u32::to_be_bytes(standard_timestamp)[1..]);
+        buffer.extend_from_slice(&u32::to_be_bytes(payload.len() as u32)[1..]);
+        buffer.push(type_id);
 
         if delta > 16777215 {
-            cursor.write_u32::<BigEndian>(delta).unwrap();
+            buffer.extend_from_slice(&u32::to_be_bytes(delta));
         }
 
-        cursor.write(payload).unwrap();
+        buffer.extend_from_slice(payload);
 
-        cursor.into_inner()
+        buffer
     }
 
     fn form_type_2_chunk(csid: u32, delta: u32, payload: &[u8]) -> Vec<u8> {
-        let mut cursor = Cursor::new(Vec::new());
+        let mut buffer = Vec::new();
         if csid < 64 {
-            cursor.write_u8((csid as u8) | 0b10000000).unwrap();
+            buffer.push((csid as u8) | 0b10000000);
         } else if csid < 319 {
-            cursor.write_u8(0_u8 | 0b10000000).unwrap();
-            cursor.write_u8((csid - 64) as u8).unwrap();
+            buffer.push(0_u8 | 0b10000000);
+            buffer.push((csid - 64) as u8);
         } else {
-            cursor.write_u8(1_u8 | 0b10000000).unwrap();
-            cursor.write_u16::<BigEndian>((csid - 64) as u16).unwrap();
+            buffer.push(1_u8 | 0b10000000);
+            buffer.extend_from_slice(&u16::to_be_bytes((csid - 64) as u16));
         }
 
         let standard_timestamp = if delta >= 16777215 { 16777215 } else { delta };
-        cursor.write_u24::<BigEndian>(standard_timestamp).unwrap();
+        buffer.extend_from_slice(&u32::to_be_bytes(standard_timestamp)[1..]);
 
         if delta > 16777215 {
-            cursor.write_u32::<BigEndian>(delta).unwrap();
+            buffer.extend_from_slice(&u32::to_be_bytes(delta));
         }
 
-        cursor.write(payload).unwrap();
+        buffer.extend_from_slice(payload);
 
-        cursor.into_inner()
+        buffer
     }
 
     fn form_type_3_chunk(
@@ -1112,15 +1336,15 @@ mod tests {
         max_chunk_length: usize,
         option_extended_timestamp: Option<u32>,
     ) -> Vec<u8> {
-        let mut cursor = Cursor::new(Vec::new());
+        let mut buffer = Vec::new();
         if csid < 64 {
-            cursor.write_u8((csid as u8) | 0b11000000).unwrap();
+            buffer.push((csid as u8) | 0b11000000);
         } else if csid < 319 {
-            cursor.write_u8(0_u8 | 0b11000000).unwrap();
-            cursor.write_u8((csid - 64) as u8).unwrap();
+            buffer.push(0_u8 | 0b11000000);
+            buffer.push((csid - 64) as u8);
         } else {
-            cursor.write_u8(1_u8 | 0b11000000).unwrap();
-            cursor.write_u16::<BigEndian>((csid - 64) as u16).unwrap();
+            buffer.push(1_u8 | 0b11000000);
+            buffer.extend_from_slice(&u16::to_be_bytes((csid - 64) as u16));
         }
 
         if option_extended_timestamp != None {
@@ -1129,16 +1353,14 @@ mod tests {
                 true,
                 "timestamp was less than 0xffffff"
             );
-            cursor
-                .write_u32::<BigEndian>(option_extended_timestamp.unwrap())
-                .unwrap();
+            buffer.extend_from_slice(&u32::to_be_bytes(option_extended_timestamp.unwrap()));
         }
 
         // If the payload is over max_chunk_length, assume we want to form a split message
         // and therefore need to only write the max chunk amount of the payload in this request
         // and append a type 3 chunk with the rest
         if payload.len() > max_chunk_length {
-            cursor.write(&payload[..max_chunk_length]).unwrap();
+            buffer.extend_from_slice(&payload[..max_chunk_length]);
 
             let next_chunk = form_type_3_chunk(
                 csid,
@@ -1146,11 +1368,11 @@ mod tests {
                 max_chunk_length,
                 option_extended_timestamp,
             );
-            cursor.write(&next_chunk).unwrap();
+            buffer.extend_from_slice(&next_chunk);
         } else {
-            cursor.write(payload).unwrap();
+            buffer.extend_from_slice(payload);
         }
 
-        cursor.into_inner()
+        buffer
     }
 }
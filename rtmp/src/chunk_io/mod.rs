@@ -18,7 +18,10 @@ around the bytes representing the RTMP chunk that was created, but also creates
 the packet is allowed to be dropped or not.  Video and Audio data can uaually be marked as able
 to be dropped in case bandwidth limitations are encountered between the client and the server.  Any
 packet that is *not* marked as being able to be dropped should not be dropped, as that is a pretty
-sure way to cause deserialization errors with the peer.
+sure way to cause deserialization errors with the peer.  Packets also carry a `PacketPriority` and
+an optional deadline, so transports that queue packets can send the most important ones first and
+discard droppable ones that are too late to be useful, and a flush hint marking where transports
+that batch writes should flush them.
 
 Inbound and outbound binary data relies on the [bytes crate](https://crates.io/crates/bytes) to
 provide input and output buffers with minimal allocations.
@@ -61,7 +64,7 @@ mod serializer;
 pub use self::deserialization_errors::ChunkDeserializationError;
 pub use self::deserializer::ChunkDeserializer;
 pub use self::serialization_errors::ChunkSerializationError;
-pub use self::serializer::{ChunkSerializer, Packet};
+pub use self::serializer::{ChunkSerializer, Packet, PacketPriority, PreparedPacket};
 
 #[cfg(test)]
 mod tests {
@@ -181,4 +184,66 @@ mod tests {
             "Third message was not deserialized as expected"
         );
     }
+
+    #[test]
+    fn can_deserialize_prepared_packets_sent_between_messages() {
+        let input1 = MessagePayload {
+            timestamp: RtmpTimestamp::new(55),
+            message_stream_id: 1,
+            type_id: 9,
+            data: Bytes::from(vec![1; 300]),
+        };
+
+        let input2 = MessagePayload {
+            timestamp: RtmpTimestamp::new(20),
+            message_stream_id: 2,
+            type_id: 9,
+            data: Bytes::from(vec![2; 200]),
+        };
+
+        let input3 = MessagePayload {
+            timestamp: RtmpTimestamp::new(75),
+            message_stream_id: 2,
+            type_id: 9,
+            data: Bytes::from(vec![3; 10]),
+        };
+
+        let prepared = PreparedPacket::new(&input2, 128).unwrap();
+        let mut serializer = ChunkSerializer::new();
+        let packet1 = serializer.serialize(&input1, false, false).unwrap();
+        let packet2 = serializer.serialize_prepared(&prepared).unwrap();
+        let packet3 = serializer.serialize(&input3, false, false).unwrap();
+
+        assert_eq!(packet2.bytes.as_ptr(), prepared.bytes().as_ptr());
+
+        let mut deserializer = ChunkDeserializer::new();
+        let output1 = deserializer.get_next_message(&packet1.bytes).unwrap();
+        let output2 = deserializer.get_next_message(&packet2.bytes).unwrap();
+        let output3 = deserializer.get_next_message(&packet3.bytes).unwrap();
+
+        assert_eq!(output1, Some(input1));
+        assert_eq!(output2, Some(input2));
+        assert_eq!(output3, Some(input3));
+    }
+
+    #[test]
+    fn prepared_packet_for_other_chunk_size_is_rejected() {
+        let input = MessagePayload {
+            timestamp: RtmpTimestamp::new(0),
+            message_stream_id: 1,
+            type_id: 9,
+            data: Bytes::from(vec![1, 2, 3]),
+        };
+
+        let prepared = PreparedPacket::new(&input, 4096).unwrap();
+        let mut serializer = ChunkSerializer::new();
+
+        match serializer.serialize_prepared(&prepared) {
+            Err(ChunkSerializationError::PreparedChunkSizeMismatch {
+                prepared_chunk_size: 4096,
+                max_chunk_size: 128,
+            }) => (),
+            x => panic!("Expected chunk size mismatch error, instead got {:?}", x),
+        }
+    }
 }
@@ -1,12 +1,14 @@
 use messages::MessageSerializationError;
 use thiserror::Error;
 
+#[cfg(feature = "std")]
 use std::io;
 
 /// Data for when an error occurs while attempting to serialize an RTMP message into RTMP chunks.
 /// An enumeration defining all the possible errors that could occur while serializing
 /// RTMP messages into RTMP chunks.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum ChunkSerializationError {
     /// Te RTMP specification states that a message cannot be more than 16,777,215 bytes, even
     /// when split across multiple RTMP chunks.  This error is returned if an RTMP message is passed
@@ -21,8 +23,17 @@ pub enum ChunkSerializationError {
     )]
     InvalidMaxChunkSize { attempted_chunk_size: u32 },
 
+    /// A prepared packet was sent with a serializer using a different max chunk size than the
+    /// packet was prepared for
+    #[error("Packet was prepared for a max chunk size of {prepared_chunk_size}, but the serializer is using {max_chunk_size}")]
+    PreparedChunkSizeMismatch {
+        prepared_chunk_size: u32,
+        max_chunk_size: u32,
+    },
+
     /// An I/O error occurred while writing the output buffer
-    #[error("{0}")]
+    #[cfg(feature = "std")]
+    #[error("An IO error occurred while writing the output")]
     Io(#[from] io::Error),
 
     /// Occurs when an error is returned when trying to create a set chunk size message
@@ -1,24 +1,158 @@
 use super::chunk_header::{ChunkHeader, ChunkHeaderFormat};
-use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use bytes::{BufMut, Bytes, BytesMut};
 use chunk_io::ChunkSerializationError;
+use core::cmp::min;
+use core::mem;
 use messages::{MessagePayload, RtmpMessage};
-use std::cmp::min;
-use std::collections::HashMap;
-use std::io::{Cursor, Write};
 use time::RtmpTimestamp;
 
 const INITIAL_MAX_CHUNK_SIZE: u32 = 128;
 const MAX_INITIAL_TIMESTAMP: u32 = 16777215;
 
+// The largest chunk header, with a 3 byte chunk stream id and an extended timestamp
+const MAX_CHUNK_HEADER_SIZE: usize = 18;
+
+const AUDIO_TYPE_ID: u8 = 8;
+const VIDEO_TYPE_ID: u8 = 9;
+const VIDEO_KEYFRAME: u8 = 1;
+
 /// An outbound data packet containing the at least one RTMP chunk with a single RTMP message.
 /// The packet can be flagged as droppable because video and audio packets may be allowed to be
 /// dropped if there is not enough bandwidth for the current bitrate.  This allows live video
 /// to be kept in real time and to prevent getting backed up when redistributing live video when
 /// the network conditions don't allow the current bitrate.
+///
+/// The bytes are shared with the serializer's buffer, so packets can be queued for several
+/// connections or held until they are written without being copied.
+///
+/// The packet's priority and optional deadline let network layers that queue packets decide
+/// which ones to send first, and which droppable packets are too late to be worth sending.
 #[derive(Debug, PartialEq)]
 pub struct Packet {
-    pub bytes: Vec<u8>,
+    pub bytes: Bytes,
     pub can_be_dropped: bool,
+    pub priority: PacketPriority,
+
+    /// The latest timestamp (on the same clock as the packet's message timestamps) at which the
+    /// packet is still worth sending.  The serializer does not set a deadline, as only the
+    /// application knows how much latency it will tolerate.
+    pub deadline: Option<RtmpTimestamp>,
+
+    /// Whether the packet ends a group of packets the peer needs together, such as the last
+    /// response to a request or a video keyframe, making it a good point to flush writes that
+    /// have been batched (or uncork the socket).  Packets without the hint can be held back while
+    /// more packets are queued, but should still be flushed once nothing else is waiting to be
+    /// written, or they only add latency.
+    pub flush_hint: bool,
+}
+
+impl Packet {
+    /// Returns if the packet has a deadline that has passed at the given time.  Packets without
+    /// a deadline are never late.
+    pub fn is_late(&self, now: RtmpTimestamp) -> bool {
+        match self.deadline {
+            Some(deadline) => now > deadline,
+            None => false,
+        }
+    }
+}
+
+/// How important a packet is to the peer, ordered from most to least important.
+///
+/// Control packets (commands, protocol control messages, metadata) are needed for the session to
+/// keep working.  Audio gaps are more noticeable than video ones, and losing a video keyframe
+/// (or sequence header) ruins every frame until the next keyframe, while losing an inter frame
+/// only affects the frames until then.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum PacketPriority {
+    Control,
+    Audio,
+    VideoKeyframe,
+    VideoInterframe,
+}
+
+impl PacketPriority {
+    /// Determines the priority of a message from its type id and payload
+    pub fn for_message(message: &MessagePayload) -> PacketPriority {
+        match message.type_id {
+            AUDIO_TYPE_ID => PacketPriority::Audio,
+            VIDEO_TYPE_ID => match message.data.first() {
+                Some(byte) if (byte >> 4) & 0x07 == VIDEO_KEYFRAME => PacketPriority::VideoKeyframe,
+                _ => PacketPriority::VideoInterframe,
+            },
+
+            _ => PacketPriority::Control,
+        }
+    }
+
+    /// Control messages are usually sent on their own, and a keyframe is what players are
+    /// waiting on to show anything, so both are worth flushing straight away.  Audio and inter
+    /// frames arrive steadily and are batched with whatever follows them.
+    fn is_flush_point(self) -> bool {
+        match self {
+            PacketPriority::Control | PacketPriority::VideoKeyframe => true,
+            PacketPriority::Audio | PacketPriority::VideoInterframe => false,
+        }
+    }
+}
+
+/// A message serialized into RTMP chunks on its own, so the same bytes can be sent to any number
+/// of peers instead of the message being serialized again for each of them.  This is meant for
+/// messages many peers are sent unchanged, such as the metadata and sequence headers every
+/// player of a stream receives when it starts playing.
+///
+/// Prepared packets only use full (type 0) chunk headers, as they can't rely on what any one peer
+/// has been sent before.  They are sent with `ChunkSerializer::serialize_prepared()`, which keeps
+/// the serializer's header compression correct for whatever it serializes afterwards, and only
+/// by serializers using the max chunk size the packet was prepared for.
+#[derive(PartialEq, Debug, Clone)]
+pub struct PreparedPacket {
+    bytes: Bytes,
+    max_chunk_size: u32,
+    priority: PacketPriority,
+    last_header: ChunkHeader,
+}
+
+impl PreparedPacket {
+    /// Serializes the message into chunks of up to the specified max chunk size
+    pub fn new(
+        message: &MessagePayload,
+        max_chunk_size: u32,
+    ) -> Result<PreparedPacket, ChunkSerializationError> {
+        if max_chunk_size == 0 || max_chunk_size > 2147483647 {
+            return Err(ChunkSerializationError::InvalidMaxChunkSize {
+                attempted_chunk_size: max_chunk_size,
+            });
+        }
+
+        let mut serializer = ChunkSerializer::new();
+        serializer.max_chunk_size = max_chunk_size;
+        let packet = serializer.serialize(message, true, false)?;
+        let csid = get_csid_for_message_type(message.type_id);
+        let last_header = serializer
+            .previous_headers
+            .remove(&csid)
+            .expect("Serializing a message did not leave a header for its chunk stream");
+
+        Ok(PreparedPacket {
+            bytes: packet.bytes,
+            max_chunk_size,
+            priority: packet.priority,
+            last_header,
+        })
+    }
+
+    /// The max chunk size the packet was prepared for
+    pub fn max_chunk_size(&self) -> u32 {
+        self.max_chunk_size
+    }
+
+    /// The serialized chunks, shared by every packet the prepared packet is sent as
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
 }
 
 /// Allows serializing RTMP messages into RTMP chunks.
@@ -26,8 +160,9 @@ pub struct Packet {
 /// Due to the nature of the RTMP chunking protocol, the same serializer should be used
 /// for all messages that need to be sent to the same peer.
 pub struct ChunkSerializer {
-    previous_headers: HashMap<u32, ChunkHeader>,
+    previous_headers: BTreeMap<u32, ChunkHeader>,
     max_chunk_size: u32,
+    buffer: BytesMut,
 }
 
 impl ChunkSerializer {
@@ -39,7 +174,8 @@ impl ChunkSerializer {
     pub fn new() -> ChunkSerializer {
         ChunkSerializer {
             max_chunk_size: INITIAL_MAX_CHUNK_SIZE,
-            previous_headers: HashMap::new(),
+            previous_headers: BTreeMap::new(),
+            buffer: BytesMut::new(),
         }
     }
 
@@ -68,6 +204,47 @@ impl ChunkSerializer {
         Ok(packet)
     }
 
+    /// The number of bytes the serializer is holding on to between calls, which is the buffer
+    /// packets are split from and the headers remembered for each chunk stream id.  Packets
+    /// that have been returned are owned by the caller and aren't counted.
+    pub fn memory_usage(&self) -> usize {
+        self.buffer.capacity() + self.previous_headers.len() * mem::size_of::<ChunkHeader>()
+    }
+
+    /// The maximum amount of bytes from RTMP messages the serializer puts in a single RTMP chunk
+    pub fn get_max_chunk_size(&self) -> u32 {
+        self.max_chunk_size
+    }
+
+    /// Creates a packet for a message that was serialized ahead of time, without copying its
+    /// bytes.  The serializer remembers the prepared packet's headers as the last ones sent, so
+    /// the prepared packet *must* be sent in order with the other packets the serializer creates.
+    ///
+    /// The packet is rejected if it was prepared for a different max chunk size than the
+    /// serializer is using, as the peer would not be able to deserialize it.
+    pub fn serialize_prepared(
+        &mut self,
+        prepared: &PreparedPacket,
+    ) -> Result<Packet, ChunkSerializationError> {
+        if prepared.max_chunk_size != self.max_chunk_size {
+            return Err(ChunkSerializationError::PreparedChunkSizeMismatch {
+                prepared_chunk_size: prepared.max_chunk_size,
+                max_chunk_size: self.max_chunk_size,
+            });
+        }
+
+        let header = prepared.last_header.clone();
+        self.previous_headers.insert(header.chunk_stream_id, header);
+
+        Ok(Packet {
+            bytes: prepared.bytes.clone(),
+            can_be_dropped: false,
+            priority: prepared.priority,
+            deadline: None,
+            flush_hint: prepared.priority.is_flush_point(),
+        })
+    }
+
     /// Turns an RTMP message payload into binary data (representing RTMP chunks) that can be
     /// sent over the network.
     ///
@@ -100,15 +277,20 @@ impl ChunkSerializer {
             });
         }
 
-        let mut bytes = Cursor::new(Vec::new());
+        // Packets are split off of a buffer that's kept between calls, so once the packets
+        // split from it earlier have been dropped its allocation is reused instead of a new one
+        // being made for every packet.
+        let mut buffer = mem::take(&mut self.buffer);
+        buffer.clear();
 
         // Since a message may have a payload greater than one chunk allows, we must
-        // split the payload into slices that don't exceed the max chunk length
+        // split the payload into slices that don't exceed the max chunk length.  Messages
+        // without a payload still need a chunk to carry their header.
         let mut slices = Vec::<&[u8]>::new();
         let mut iteration = 0;
         loop {
             let start_index = iteration * self.max_chunk_size as usize;
-            if start_index >= message.data.len() {
+            if start_index >= message.data.len() && iteration > 0 {
                 break;
             }
 
@@ -120,35 +302,43 @@ impl ChunkSerializer {
 
             slices.push(&message.data[start_index..end_index]);
 
-            iteration = iteration + 1;
+            iteration += 1;
         }
 
+        buffer.reserve(message.data.len() + slices.len() * MAX_CHUNK_HEADER_SIZE);
         for (idx, slice) in slices.into_iter().enumerate() {
             self.add_chunk(
-                &mut bytes,
+                &mut buffer,
                 force_uncompressed,
                 message,
                 idx > 0,
                 slice,
                 can_be_dropped,
-            )?;
+            );
         }
 
+        let bytes = buffer.split().freeze();
+        self.buffer = buffer;
+
+        let priority = PacketPriority::for_message(message);
         Ok(Packet {
-            bytes: bytes.into_inner(),
+            bytes,
             can_be_dropped,
+            priority,
+            deadline: None,
+            flush_hint: priority.is_flush_point(),
         })
     }
 
     fn add_chunk(
         &mut self,
-        bytes: &mut Cursor<Vec<u8>>,
+        bytes: &mut BytesMut,
         force_uncompressed: bool,
         message: &MessagePayload,
         continued_chunk: bool,
         data_to_write: &[u8],
         can_be_dropped: bool,
-    ) -> Result<(), ChunkSerializationError> {
+    ) {
         let mut header = ChunkHeader {
             chunk_stream_id: get_csid_for_message_type(message.type_id),
             timestamp: message.timestamp,
@@ -164,7 +354,7 @@ impl ChunkSerializer {
         } else {
             match self.previous_headers.get(&header.chunk_stream_id) {
                 None => ChunkHeaderFormat::Full,
-                Some(ref previous_header) => {
+                Some(previous_header) => {
                     if continued_chunk {
                         //  https://github.com/melpon/rfc/blob/master/rtmp.md#53124-type-3
                         //  Continued chunks should use Format Type 3.
@@ -177,10 +367,14 @@ impl ChunkSerializer {
                         // therefore the next packet must be a type 0 chunk as a precaution.  Otherwise
                         // we risk the peer not being able to deserialize this packet.
                         ChunkHeaderFormat::Full
+                    } else if header.timestamp < previous_header.timestamp {
+                        // Deltas can only move time forward, so a timestamp that went backwards
+                        // needs its absolute value sent.  Wrapping past the 32 bit limit is
+                        // still forward, and is covered by the delta.
+                        ChunkHeaderFormat::Full
                     } else {
-                        // TODO: Update to support rtmp time wrap-around
                         header.timestamp_field =
-                            (header.timestamp - previous_header.timestamp).value;
+                            header.timestamp.diff(previous_header.timestamp) as u32;
                         get_header_format(&mut header, previous_header)
                     }
                 }
@@ -191,28 +385,29 @@ impl ChunkSerializer {
             header.timestamp_field = header.timestamp.value;
         }
 
-        add_basic_header(bytes, &header_format, header.chunk_stream_id)?;
-        add_initial_timestamp(bytes, &header_format, &header)?;
+        add_basic_header(bytes, &header_format, header.chunk_stream_id);
+        add_initial_timestamp(bytes, &header_format, &header);
         add_message_length_and_type_id(
             bytes,
             &header_format,
             header.message_length,
             header.message_type_id,
-        )?;
-        add_message_stream_id(bytes, &header_format, header.message_stream_id)?;
-        add_extended_timestamp(bytes, &header)?;
-        add_message_payload(bytes, data_to_write)?;
+        );
+        add_message_stream_id(bytes, &header_format, header.message_stream_id);
+        add_extended_timestamp(bytes, &header);
+        bytes.put_slice(data_to_write);
 
         self.previous_headers.insert(header.chunk_stream_id, header);
-        Ok(())
     }
 }
 
-fn add_basic_header(
-    bytes: &mut dyn Write,
-    format: &ChunkHeaderFormat,
-    csid: u32,
-) -> Result<(), ChunkSerializationError> {
+impl Default for ChunkSerializer {
+    fn default() -> Self {
+        ChunkSerializer::new()
+    }
+}
+
+fn add_basic_header(bytes: &mut BytesMut, format: &ChunkHeaderFormat, csid: u32) {
     if csid <= 1 || csid >= 65600 {
         panic!("Attempted to serialize an RTMP chunk with a csid of {}, but only csids between 2 and 65600 are allowed", csid);
     }
@@ -226,82 +421,60 @@ fn add_basic_header(
 
     let mut first_byte = match csid {
         x if x <= 63 => x as u8,
-        x if x >= 64 && x <= 319 => 0,
+        x if (64..=319).contains(&x) => 0,
         _ => 1,
     };
 
-    first_byte = first_byte | format_mask;
-    bytes.write_u8(first_byte)?;
+    first_byte |= format_mask;
+    bytes.put_u8(first_byte);
 
     // Since get_csid_for_message_type only does csids up to 6, ignore 2 and 3 byte csid formats
-    Ok(())
 }
 
-fn add_initial_timestamp(
-    bytes: &mut Cursor<Vec<u8>>,
-    format: &ChunkHeaderFormat,
-    header: &ChunkHeader,
-) -> Result<(), ChunkSerializationError> {
+fn add_initial_timestamp(bytes: &mut BytesMut, format: &ChunkHeaderFormat, header: &ChunkHeader) {
     if *format == ChunkHeaderFormat::Empty {
-        return Ok(());
+        return;
     }
 
     let capped_value = min(header.timestamp_field, MAX_INITIAL_TIMESTAMP);
-    bytes.write_u24::<BigEndian>(capped_value)?;
-
-    Ok(())
+    bytes.put_uint(capped_value as u64, 3);
 }
 
 fn add_message_length_and_type_id(
-    bytes: &mut Cursor<Vec<u8>>,
+    bytes: &mut BytesMut,
     format: &ChunkHeaderFormat,
     length: u32,
     type_id: u8,
-) -> Result<(), ChunkSerializationError> {
+) {
     if *format == ChunkHeaderFormat::Empty || *format == ChunkHeaderFormat::TimeDeltaOnly {
-        return Ok(());
+        return;
     }
 
-    bytes.write_u24::<BigEndian>(length)?;
-    bytes.write_u8(type_id)?;
-    Ok(())
+    bytes.put_uint(length as u64, 3);
+    bytes.put_u8(type_id);
 }
 
-fn add_message_stream_id(
-    bytes: &mut dyn Write,
-    format: &ChunkHeaderFormat,
-    stream_id: u32,
-) -> Result<(), ChunkSerializationError> {
+fn add_message_stream_id(bytes: &mut BytesMut, format: &ChunkHeaderFormat, stream_id: u32) {
     if *format != ChunkHeaderFormat::Full {
-        return Ok(());
+        return;
     }
 
-    bytes.write_u32::<LittleEndian>(stream_id)?;
-    Ok(())
+    bytes.put_u32_le(stream_id);
 }
 
-fn add_extended_timestamp(
-    bytes: &mut dyn Write,
-    header: &ChunkHeader,
-) -> Result<(), ChunkSerializationError> {
+fn add_extended_timestamp(bytes: &mut BytesMut, header: &ChunkHeader) {
     if header.timestamp_field < MAX_INITIAL_TIMESTAMP {
-        return Ok(());
+        return;
     }
 
-    bytes.write_u32::<BigEndian>(header.timestamp_field)?;
-    Ok(())
-}
-
-fn add_message_payload(bytes: &mut dyn Write, data: &[u8]) -> Result<(), ChunkSerializationError> {
-    bytes.write(data)?;
-    Ok(())
+    bytes.put_u32(header.timestamp_field);
 }
 
 fn get_csid_for_message_type(message_type_id: u8) -> u32 {
     // Naive resolution, purpose (afaik) is to allow repeated messages
     // to utilize header compression by spreading them across chunk streams
     match message_type_id {
-        1 | 2 | 3 | 4 | 5 | 6 => 2,
+        1..=6 => 2,
         18 | 19 => 3,
         9 => 4,
         8 => 5,
@@ -331,13 +504,32 @@ fn get_header_format(
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::identity_op)]
 mod tests {
     use super::*;
-    use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+    use byteorder::{BigEndian, ByteOrder, LittleEndian};
     use bytes::Bytes;
     use std::io::{Cursor, Read};
     use time::RtmpTimestamp;
 
+    fn read_u8(cursor: &mut impl Read) -> u8 {
+        let mut buffer = [0_u8; 1];
+        cursor.read_exact(&mut buffer).unwrap();
+        buffer[0]
+    }
+
+    fn read_u24(cursor: &mut impl Read) -> u32 {
+        let mut buffer = [0_u8; 3];
+        cursor.read_exact(&mut buffer).unwrap();
+        BigEndian::read_u24(&buffer)
+    }
+
+    fn read_u32<B: ByteOrder>(cursor: &mut impl Read) -> u32 {
+        let mut buffer = [0_u8; 4];
+        cursor.read_exact(&mut buffer).unwrap();
+        B::read_u32(&buffer)
+    }
+
     #[test]
     fn type_0_chunk_for_first_message_with_small_timestamp() {
         let message1 = MessagePayload {
@@ -352,23 +544,15 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b00000000,
             "Unexpected csid value"
         );
+        assert_eq!(read_u24(&mut cursor), 72, "Unexpected timestamp value");
+        assert_eq!(read_u24(&mut cursor), 4, "Unexpected message length value");
+        assert_eq!(read_u8(&mut cursor), 50, "Unexpected type id");
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            72,
-            "Unexpected timestamp value"
-        );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            4,
-            "Unexpected message length value"
-        );
-        assert_eq!(cursor.read_u8().unwrap(), 50, "Unexpected type id");
-        assert_eq!(
-            cursor.read_u32::<LittleEndian>().unwrap(),
+            read_u32::<LittleEndian>(&mut cursor),
             12,
             "Unexpected message stream id"
         );
@@ -397,28 +581,24 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b00000000,
             "Unexpected csid value"
         );
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
+            read_u24(&mut cursor),
             16777215,
             "Unexpected timestamp value"
         );
+        assert_eq!(read_u24(&mut cursor), 4, "Unexpected message length value");
+        assert_eq!(read_u8(&mut cursor), 50, "Unexpected type id");
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            4,
-            "Unexpected message length value"
-        );
-        assert_eq!(cursor.read_u8().unwrap(), 50, "Unexpected type id");
-        assert_eq!(
-            cursor.read_u32::<LittleEndian>().unwrap(),
+            read_u32::<LittleEndian>(&mut cursor),
             12,
             "Unexpected message stream id"
         );
         assert_eq!(
-            cursor.read_u32::<BigEndian>().unwrap(),
+            read_u32::<BigEndian>(&mut cursor),
             16777216,
             "Unexpected extended timestamp"
         );
@@ -456,21 +636,13 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b01000000,
             "Unexpected csid value"
         );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            10,
-            "Unexpected timestamp value"
-        );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            3,
-            "Unexpected message length value"
-        );
-        assert_eq!(cursor.read_u8().unwrap(), 51, "Unexpected type id");
+        assert_eq!(read_u24(&mut cursor), 10, "Unexpected timestamp value");
+        assert_eq!(read_u24(&mut cursor), 3, "Unexpected message length value");
+        assert_eq!(read_u8(&mut cursor), 51, "Unexpected type id");
 
         let mut payload_bytes = [0_u8; 50];
         let bytes_read = cursor.read(&mut payload_bytes[..]).unwrap();
@@ -505,23 +677,19 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b01000000,
             "Unexpected csid value"
         );
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
+            read_u24(&mut cursor),
             16777215,
             "Unexpected timestamp value"
         );
+        assert_eq!(read_u24(&mut cursor), 3, "Unexpected message length value");
+        assert_eq!(read_u8(&mut cursor), 51, "Unexpected type id");
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            3,
-            "Unexpected message length value"
-        );
-        assert_eq!(cursor.read_u8().unwrap(), 51, "Unexpected type id");
-        assert_eq!(
-            cursor.read_u32::<BigEndian>().unwrap(),
+            read_u32::<BigEndian>(&mut cursor),
             16777216,
             "Unexpected extended timestamp"
         );
@@ -559,15 +727,11 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b10000000,
             "Unexpected csid value"
         );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            10,
-            "Unexpected timestamp value"
-        );
+        assert_eq!(read_u24(&mut cursor), 10, "Unexpected timestamp value");
 
         let mut payload_bytes = [0_u8; 50];
         let bytes_read = cursor.read(&mut payload_bytes[..]).unwrap();
@@ -602,17 +766,17 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b10000000,
             "Unexpected csid value"
         );
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
+            read_u24(&mut cursor),
             16777215,
             "Unexpected timestamp value"
         );
         assert_eq!(
-            cursor.read_u32::<BigEndian>().unwrap(),
+            read_u32::<BigEndian>(&mut cursor),
             16777216,
             "Unexpected extended timestamp"
         );
@@ -657,7 +821,7 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b11000000,
             "Unexpected csid value"
         );
@@ -694,23 +858,15 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             2 | 0b00000000,
             "Unexpected csid value"
         );
+        assert_eq!(read_u24(&mut cursor), 82, "Unexpected timestamp value");
+        assert_eq!(read_u24(&mut cursor), 4, "Unexpected message length value");
+        assert_eq!(read_u8(&mut cursor), 1, "Unexpected type id");
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            82,
-            "Unexpected timestamp value"
-        );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            4,
-            "Unexpected message length value"
-        );
-        assert_eq!(cursor.read_u8().unwrap(), 1, "Unexpected type id");
-        assert_eq!(
-            cursor.read_u32::<LittleEndian>().unwrap(),
+            read_u32::<LittleEndian>(&mut cursor),
             12,
             "Unexpected message stream id"
         );
@@ -725,6 +881,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn type_2_chunk_delta_for_timestamp_that_wraps_around() {
+        let message1 = MessagePayload {
+            timestamp: RtmpTimestamp::new(u32::MAX - 4),
+            type_id: 50,
+            message_stream_id: 12,
+            data: Bytes::from(vec![1_u8, 2_u8, 3_u8, 4_u8]),
+        };
+
+        let message2 = MessagePayload {
+            timestamp: RtmpTimestamp::new(5),
+            type_id: 50,
+            message_stream_id: 12,
+            data: Bytes::from(vec![5_u8, 6_u8, 7_u8, 8_u8]),
+        };
+
+        let mut serializer = ChunkSerializer::new();
+        let _ = serializer.serialize(&message1, false, false).unwrap();
+        let packet = serializer.serialize(&message2, false, false).unwrap();
+
+        let mut cursor = Cursor::new(packet.bytes);
+        assert_eq!(
+            read_u8(&mut cursor),
+            6 | 0b10000000,
+            "Unexpected csid value"
+        );
+        assert_eq!(read_u24(&mut cursor), 10, "Unexpected timestamp value");
+    }
+
+    #[test]
+    fn type_0_chunk_for_timestamp_that_goes_backwards() {
+        let message1 = MessagePayload {
+            timestamp: RtmpTimestamp::new(82),
+            type_id: 50,
+            message_stream_id: 12,
+            data: Bytes::from(vec![1_u8, 2_u8, 3_u8, 4_u8]),
+        };
+
+        let message2 = MessagePayload {
+            timestamp: RtmpTimestamp::new(72),
+            type_id: 50,
+            message_stream_id: 12,
+            data: Bytes::from(vec![5_u8, 6_u8, 7_u8, 8_u8]),
+        };
+
+        let mut serializer = ChunkSerializer::new();
+        let _ = serializer.serialize(&message1, false, false).unwrap();
+        let packet = serializer.serialize(&message2, false, false).unwrap();
+
+        let mut cursor = Cursor::new(packet.bytes);
+        assert_eq!(read_u8(&mut cursor), 6, "Unexpected csid value");
+        assert_eq!(read_u24(&mut cursor), 72, "Unexpected timestamp value");
+    }
+
     #[test]
     fn type_0_chunk_for_second_message_when_forcing_uncompressed() {
         let message1 = MessagePayload {
@@ -747,23 +957,15 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b00000000,
             "Unexpected csid value"
         );
+        assert_eq!(read_u24(&mut cursor), 82, "Unexpected timestamp value");
+        assert_eq!(read_u24(&mut cursor), 4, "Unexpected message length value");
+        assert_eq!(read_u8(&mut cursor), 50, "Unexpected type id");
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            82,
-            "Unexpected timestamp value"
-        );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            4,
-            "Unexpected message length value"
-        );
-        assert_eq!(cursor.read_u8().unwrap(), 50, "Unexpected type id");
-        assert_eq!(
-            cursor.read_u32::<LittleEndian>().unwrap(),
+            read_u32::<LittleEndian>(&mut cursor),
             12,
             "Unexpected message stream id"
         );
@@ -807,23 +1009,19 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b00000000,
             "Unexpected csid value"
         );
+        assert_eq!(read_u24(&mut cursor), 72, "Unexpected timestamp value");
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            72,
-            "Unexpected timestamp value"
-        );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
+            read_u24(&mut cursor),
             100,
             "Unexpected message length value"
         );
-        assert_eq!(cursor.read_u8().unwrap(), 50, "Unexpected type id");
+        assert_eq!(read_u8(&mut cursor), 50, "Unexpected type id");
         assert_eq!(
-            cursor.read_u32::<LittleEndian>().unwrap(),
+            read_u32::<LittleEndian>(&mut cursor),
             12,
             "Unexpected message stream id"
         );
@@ -838,7 +1036,7 @@ mod tests {
         );
 
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b11000000,
             "Unexpected 2nd csid value"
         );
@@ -853,15 +1051,11 @@ mod tests {
         let packet = serializer.serialize(&message2, false, false).unwrap();
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b10000000,
             "Unexpected csid value"
         );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            1,
-            "Unexpected timestamp value"
-        );
+        assert_eq!(read_u24(&mut cursor), 1, "Unexpected timestamp value");
 
         let mut payload_bytes = [0_u8; 75];
         let bytes_read = cursor.read(&mut payload_bytes[..]).unwrap();
@@ -873,7 +1067,7 @@ mod tests {
         );
 
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b11000000,
             "Unexpected chunk format"
         );
@@ -909,28 +1103,28 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b00000000,
             "Unexpected csid value"
         );
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
+            read_u24(&mut cursor),
             MAX_INITIAL_TIMESTAMP,
             "Unexpected timestamp value"
         );
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
+            read_u24(&mut cursor),
             100,
             "Unexpected message length value"
         );
-        assert_eq!(cursor.read_u8().unwrap(), 50, "Unexpected type id");
+        assert_eq!(read_u8(&mut cursor), 50, "Unexpected type id");
         assert_eq!(
-            cursor.read_u32::<LittleEndian>().unwrap(),
+            read_u32::<LittleEndian>(&mut cursor),
             12,
             "Unexpected message stream id"
         );
         assert_eq!(
-            cursor.read_u32::<BigEndian>().unwrap(),
+            read_u32::<BigEndian>(&mut cursor),
             timestamp_value,
             "Unexpected extended timestamp value"
         );
@@ -945,12 +1139,12 @@ mod tests {
         );
 
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b11000000,
             "Unexpected 2nd csid value"
         );
         assert_eq!(
-            cursor.read_u32::<BigEndian>().unwrap(),
+            read_u32::<BigEndian>(&mut cursor),
             timestamp_value,
             "Unexpected extended timestamp value on second chunk"
         );
@@ -972,28 +1166,20 @@ mod tests {
 
         let mut cursor = Cursor::new(packet.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             2 | 0b00000000,
             "Unexpected csid value"
         );
+        assert_eq!(read_u24(&mut cursor), 152, "Unexpected timestamp");
+        assert_eq!(read_u24(&mut cursor), 4, "Unexpected message length value");
+        assert_eq!(read_u8(&mut cursor), 1, "Unexpected type id");
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            152,
-            "Unexpected timestamp"
-        );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            4,
-            "Unexpected message length value"
-        );
-        assert_eq!(cursor.read_u8().unwrap(), 1, "Unexpected type id");
-        assert_eq!(
-            cursor.read_u32::<LittleEndian>().unwrap(),
+            read_u32::<LittleEndian>(&mut cursor),
             0,
             "Unexpected message stream id"
         );
         assert_eq!(
-            cursor.read_u32::<BigEndian>().unwrap(),
+            read_u32::<BigEndian>(&mut cursor),
             75,
             "Unexpected chunk size"
         );
@@ -1020,23 +1206,15 @@ mod tests {
 
         let mut cursor = Cursor::new(packet1.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b00000000,
             "Unexpected csid value"
         );
+        assert_eq!(read_u24(&mut cursor), 72, "Unexpected timestamp value");
+        assert_eq!(read_u24(&mut cursor), 4, "Unexpected message length value");
+        assert_eq!(read_u8(&mut cursor), 50, "Unexpected type id");
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            72,
-            "Unexpected timestamp value"
-        );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            4,
-            "Unexpected message length value"
-        );
-        assert_eq!(cursor.read_u8().unwrap(), 50, "Unexpected type id");
-        assert_eq!(
-            cursor.read_u32::<LittleEndian>().unwrap(),
+            read_u32::<LittleEndian>(&mut cursor),
             12,
             "Unexpected message stream id"
         );
@@ -1057,23 +1235,19 @@ mod tests {
         let packet2 = serializer.serialize(&message2, false, false).unwrap();
         let mut cursor = Cursor::new(packet2.bytes);
         assert_eq!(
-            cursor.read_u8().unwrap(),
+            read_u8(&mut cursor),
             6 | 0b00000000,
             "Unexpected 2nd csid value"
         );
+        assert_eq!(read_u24(&mut cursor), 82, "Unexpected 2nd timestamp value");
         assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
-            82,
-            "Unexpected 2nd timestamp value"
-        );
-        assert_eq!(
-            cursor.read_u24::<BigEndian>().unwrap(),
+            read_u24(&mut cursor),
             4,
             "Unexpected 2nd message length value"
         );
-        assert_eq!(cursor.read_u8().unwrap(), 50, "Unexpected 2nd type id");
+        assert_eq!(read_u8(&mut cursor), 50, "Unexpected 2nd type id");
         assert_eq!(
-            cursor.read_u32::<LittleEndian>().unwrap(),
+            read_u32::<LittleEndian>(&mut cursor),
             12,
             "Unexpected 2nd message stream id"
         );
@@ -1091,4 +1265,145 @@ mod tests {
             "Unexpected payload contents"
         );
     }
+
+    #[test]
+    fn message_without_payload_is_serialized_into_single_chunk() {
+        let message = MessagePayload {
+            timestamp: RtmpTimestamp::new(72),
+            type_id: 50,
+            message_stream_id: 12,
+            data: Bytes::new(),
+        };
+
+        let mut serializer = ChunkSerializer::new();
+        let packet = serializer.serialize(&message, false, false).unwrap();
+
+        let mut cursor = Cursor::new(packet.bytes);
+        assert_eq!(read_u8(&mut cursor), 6, "Unexpected csid value");
+        assert_eq!(read_u24(&mut cursor), 72, "Unexpected timestamp value");
+        assert_eq!(read_u24(&mut cursor), 0, "Unexpected message length value");
+        assert_eq!(read_u8(&mut cursor), 50, "Unexpected type id");
+        assert_eq!(
+            read_u32::<LittleEndian>(&mut cursor),
+            12,
+            "Unexpected message stream id"
+        );
+        assert_eq!(
+            cursor.read(&mut [0_u8; 1]).unwrap(),
+            0,
+            "Unexpected payload bytes"
+        );
+    }
+
+    #[test]
+    fn earlier_packets_are_unaffected_by_later_serialization() {
+        let message1 = MessagePayload {
+            timestamp: RtmpTimestamp::new(72),
+            type_id: 50,
+            message_stream_id: 12,
+            data: Bytes::from(vec![1_u8, 2_u8, 3_u8, 4_u8]),
+        };
+
+        let message2 = MessagePayload {
+            timestamp: RtmpTimestamp::new(72),
+            type_id: 50,
+            message_stream_id: 12,
+            data: Bytes::from(vec![5_u8, 6_u8, 7_u8, 8_u8]),
+        };
+
+        let mut serializer = ChunkSerializer::new();
+        let packet1 = serializer.serialize(&message1, true, false).unwrap();
+        let packet2 = serializer.serialize(&message2, true, false).unwrap();
+        drop(packet2);
+        let packet3 = serializer.serialize(&message2, true, false).unwrap();
+
+        assert_eq!(
+            &packet1.bytes[12..],
+            &[1, 2, 3, 4],
+            "Unexpected first payload"
+        );
+        assert_eq!(
+            &packet3.bytes[12..],
+            &[5, 6, 7, 8],
+            "Unexpected third payload"
+        );
+    }
+
+    #[test]
+    fn packets_are_prioritized_by_message_type() {
+        let message = |type_id, data: Vec<u8>| MessagePayload {
+            timestamp: RtmpTimestamp::new(0),
+            type_id,
+            message_stream_id: 1,
+            data: Bytes::from(data),
+        };
+
+        let mut serializer = ChunkSerializer::new();
+        let command = serializer
+            .serialize(&message(20, vec![2]), false, false)
+            .unwrap();
+        let audio = serializer
+            .serialize(&message(8, vec![0xaf, 1]), false, true)
+            .unwrap();
+        let keyframe = serializer
+            .serialize(&message(9, vec![0x17, 1]), false, false)
+            .unwrap();
+        let interframe = serializer
+            .serialize(&message(9, vec![0x27, 1]), false, true)
+            .unwrap();
+
+        assert_eq!(command.priority, PacketPriority::Control);
+        assert_eq!(audio.priority, PacketPriority::Audio);
+        assert_eq!(keyframe.priority, PacketPriority::VideoKeyframe);
+        assert_eq!(interframe.priority, PacketPriority::VideoInterframe);
+        assert!(PacketPriority::Control < PacketPriority::VideoInterframe);
+    }
+
+    #[test]
+    fn packets_are_only_late_after_their_deadline() {
+        let message = MessagePayload {
+            timestamp: RtmpTimestamp::new(0),
+            type_id: 9,
+            message_stream_id: 1,
+            data: Bytes::from(vec![0x27, 1]),
+        };
+
+        let mut serializer = ChunkSerializer::new();
+        let mut packet = serializer.serialize(&message, false, true).unwrap();
+        assert_eq!(packet.deadline, None);
+        assert!(!packet.is_late(RtmpTimestamp::new(1000)));
+
+        packet.deadline = Some(RtmpTimestamp::new(500));
+        assert!(!packet.is_late(RtmpTimestamp::new(500)));
+        assert!(packet.is_late(RtmpTimestamp::new(501)));
+    }
+
+    #[test]
+    fn only_control_and_keyframe_packets_are_hinted_to_flush() {
+        let message = |type_id, data: Vec<u8>| MessagePayload {
+            timestamp: RtmpTimestamp::new(0),
+            type_id,
+            message_stream_id: 1,
+            data: Bytes::from(data),
+        };
+
+        let mut serializer = ChunkSerializer::new();
+        let command = serializer
+            .serialize(&message(20, vec![2]), false, false)
+            .unwrap();
+        let audio = serializer
+            .serialize(&message(8, vec![0xaf, 1]), false, true)
+            .unwrap();
+        let keyframe = serializer
+            .serialize(&message(9, vec![0x17, 1]), false, false)
+            .unwrap();
+        let interframe = serializer
+            .serialize(&message(9, vec![0x27, 1]), false, true)
+            .unwrap();
+
+        assert!(command.flush_hint);
+        assert!(!audio.flush_hint);
+        assert!(keyframe.flush_hint);
+        assert!(!interframe.flush_hint);
+    }
 }
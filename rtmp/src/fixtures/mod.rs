@@ -0,0 +1,53 @@
+/*!
+This module contains fixtures for unit testing code that drives a session directly, without a
+peer on the other end.
+
+The response functions serialize the packets a typical server sends in reply to a client's
+requests, such as accepting a connection or starting playback, so a `ClientSession` can be
+walked through a workflow by handing them to `handle_input()`.  The split functions read back
+the packets a session produced and separate them from the events it raised, so tests can assert
+on the messages that would have been sent to the peer.
+
+Fixtures are meant for tests, so they panic instead of returning errors when a packet can't be
+serialized or read back.
+
+```
+use rml_rtmp::chunk_io::{ChunkDeserializer, ChunkSerializer};
+use rml_rtmp::fixtures;
+use rml_rtmp::messages::RtmpMessage;
+use rml_rtmp::sessions::{ClientSession, ClientSessionConfig, ClientSessionEvent};
+
+let mut serializer = ChunkSerializer::new();
+let mut deserializer = ChunkDeserializer::new();
+let (mut session, results) = ClientSession::new(ClientSessionConfig::new()).unwrap();
+fixtures::split_client_results(&mut deserializer, results);
+
+let result = session.request_connection("live".to_string()).unwrap();
+let (messages, _) = fixtures::split_client_results(&mut deserializer, vec![result]);
+match messages[0].1 {
+    RtmpMessage::Amf0Command { ref command_name, .. } => assert_eq!(command_name, "connect"),
+    ref x => panic!("Expected a connect command, instead got {:?}", x),
+}
+
+let response = fixtures::connect_success_response(&mut serializer);
+let results = session.handle_input(&response.bytes[..]).unwrap();
+let (_, events) = fixtures::split_client_results(&mut deserializer, results);
+assert!(events.contains(&ClientSessionEvent::ConnectionRequestAccepted));
+```
+*/
+
+mod responses;
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+mod results;
+
+pub use self::responses::{
+    connect_error_response, connect_success_response, create_stream_success_response,
+    play_start_response, publish_start_response, status_response,
+};
+
+#[cfg(feature = "client-session")]
+pub use self::results::split_client_results;
+#[cfg(feature = "server-session")]
+pub use self::results::split_server_results;
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+pub use self::results::OutboundMessages;
@@ -0,0 +1,108 @@
+use chunk_io::{ChunkSerializer, Packet};
+use messages::RtmpMessage;
+use rml_amf0::{Amf0Value, ObjectProperties};
+use sessions::StatusCode;
+use time::RtmpTimestamp;
+
+/// The `_result` response a server sends when it accepts a connection request
+pub fn connect_success_response(serializer: &mut ChunkSerializer) -> Packet {
+    connect_response(serializer, "_result", StatusCode::ConnectSuccess)
+}
+
+/// The `_error` response a server sends when a connection request fails
+pub fn connect_error_response(serializer: &mut ChunkSerializer) -> Packet {
+    connect_response(serializer, "_error", StatusCode::ConnectFailed)
+}
+
+/// The `_result` response a server sends when it creates a stream, giving the client the id of
+/// the new stream
+pub fn create_stream_success_response(
+    serializer: &mut ChunkSerializer,
+    transaction_id: f64,
+    stream_id: u32,
+) -> Packet {
+    let message = RtmpMessage::Amf0Command {
+        command_name: "_result".to_string(),
+        command_object: Amf0Value::Null,
+        additional_arguments: vec![Amf0Value::Number(stream_id as f64)],
+        transaction_id,
+    };
+
+    serialize(serializer, message, 0)
+}
+
+/// The `onStatus` command a server sends on a stream when playback of it starts
+pub fn play_start_response(serializer: &mut ChunkSerializer, stream_id: u32) -> Packet {
+    status_response(serializer, StatusCode::PlayStart, stream_id)
+}
+
+/// The `onStatus` command a server sends on a stream when publishing to it starts
+pub fn publish_start_response(serializer: &mut ChunkSerializer, stream_id: u32) -> Packet {
+    status_response(serializer, StatusCode::PublishStart, stream_id)
+}
+
+/// An `onStatus` command with the specified code, sent on the specified stream
+pub fn status_response(
+    serializer: &mut ChunkSerializer,
+    code: StatusCode,
+    stream_id: u32,
+) -> Packet {
+    let message = RtmpMessage::Amf0Command {
+        command_name: "onStatus".to_string(),
+        transaction_id: 0.0,
+        command_object: Amf0Value::Null,
+        additional_arguments: vec![Amf0Value::Object(status_properties(code))],
+    };
+
+    serialize(serializer, message, stream_id)
+}
+
+fn connect_response(
+    serializer: &mut ChunkSerializer,
+    command_name: &str,
+    code: StatusCode,
+) -> Packet {
+    let mut command_properties = ObjectProperties::new();
+    command_properties.insert(
+        "fmsVer".to_string(),
+        Amf0Value::Utf8String("fms".to_string()),
+    );
+    command_properties.insert("capabilities".to_string(), Amf0Value::Number(31.0));
+
+    let mut additional_properties = status_properties(code);
+    additional_properties.insert("objectEncoding".to_string(), Amf0Value::Number(0.0));
+
+    let message = RtmpMessage::Amf0Command {
+        command_name: command_name.to_string(),
+        transaction_id: 1.0,
+        command_object: Amf0Value::Object(command_properties),
+        additional_arguments: vec![Amf0Value::Object(additional_properties)],
+    };
+
+    serialize(serializer, message, 0)
+}
+
+fn status_properties(code: StatusCode) -> ObjectProperties {
+    let mut properties = ObjectProperties::new();
+    properties.insert(
+        "level".to_string(),
+        Amf0Value::Utf8String(code.level().as_str().to_string()),
+    );
+    properties.insert("code".to_string(), Amf0Value::Utf8String(code.into()));
+    properties.insert(
+        "description".to_string(),
+        Amf0Value::Utf8String("hi".to_string()),
+    );
+
+    properties
+}
+
+fn serialize(serializer: &mut ChunkSerializer, message: RtmpMessage, stream_id: u32) -> Packet {
+    let payload = message
+        .into_message_payload(RtmpTimestamp::new(0), stream_id)
+        .expect("Fixture message could not be converted to a payload");
+
+    serializer
+        .serialize(&payload, false, false)
+        .expect("Fixture payload could not be serialized")
+}
@@ -0,0 +1,68 @@
+use chunk_io::ChunkDeserializer;
+use messages::{MessagePayload, RtmpMessage};
+use sessions::handler::{IntoSessionOutput, SessionOutput};
+#[cfg(feature = "client-session")]
+use sessions::{ClientSessionEvent, ClientSessionResult};
+#[cfg(feature = "server-session")]
+use sessions::{ServerSessionEvent, ServerSessionResult};
+
+/// Outbound messages read back from session results, along with the payloads they came from
+pub type OutboundMessages = Vec<(MessagePayload, RtmpMessage)>;
+
+/// Splits a client session's results into the messages it sent and the events it raised.
+///
+/// The outbound packets are read back with the deserializer, which has to be the one used for
+/// every packet the session has sent so far, since later chunk headers are compressed against
+/// earlier ones.  `SetChunkSize` messages are applied to the deserializer as they're seen.
+/// Unhandleable messages are dropped.
+#[cfg(feature = "client-session")]
+pub fn split_client_results(
+    deserializer: &mut ChunkDeserializer,
+    results: Vec<ClientSessionResult>,
+) -> (OutboundMessages, Vec<ClientSessionEvent>) {
+    split_results(deserializer, results)
+}
+
+/// Splits a server session's results into the messages it sent and the events it raised, the
+/// same way `split_client_results()` does for client sessions.
+#[cfg(feature = "server-session")]
+pub fn split_server_results(
+    deserializer: &mut ChunkDeserializer,
+    results: Vec<ServerSessionResult>,
+) -> (OutboundMessages, Vec<ServerSessionEvent>) {
+    split_results(deserializer, results)
+}
+
+fn split_results<R: IntoSessionOutput>(
+    deserializer: &mut ChunkDeserializer,
+    results: Vec<R>,
+) -> (OutboundMessages, Vec<R::Event>) {
+    let mut responses = Vec::new();
+    let mut events = Vec::new();
+
+    for result in results {
+        match result.into_output() {
+            SessionOutput::Packet(packet) => {
+                let payload = deserializer
+                    .get_next_message(&packet.bytes[..])
+                    .expect("Outbound packet could not be deserialized")
+                    .expect("Outbound packet did not contain a whole message");
+                let message = payload
+                    .to_rtmp_message()
+                    .expect("Outbound payload was not a valid RTMP message");
+                if let RtmpMessage::SetChunkSize { size } = message {
+                    deserializer
+                        .set_max_chunk_size(size as usize)
+                        .expect("Outbound chunk size was invalid");
+                }
+
+                responses.push((payload, message));
+            }
+
+            SessionOutput::Event(event) => events.push(event),
+            SessionOutput::UnhandleableMessage(_) => (),
+        }
+    }
+
+    (responses, events)
+}
@@ -6,6 +6,7 @@ use thiserror::Error;
 /// Data pertaining to errors that occurred during the handshaking process.
 /// Enumeration that represents the various errors that can occur during the handshaking process
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum HandshakeError {
     /// The RTMP specification requires the first byte in the handshake process to start with a
     /// 3, so this error is encountered if any other value is in the first byte.
@@ -43,7 +44,31 @@ pub enum HandshakeError {
     #[error("Invalid handshake packet 2 received")]
     InvalidP2Packet,
 
+    /// This occurs when a client or server handshake is created from a `Handshake` that is for
+    /// the wrong peer type or that has already been started.
+    #[error("Handshake was not a new handshake for the expected peer type")]
+    InvalidInitialHandshake,
+
+    /// This occurs when the peer has sent more bytes than the handshake is willing to buffer
+    /// before the handshake has completed.
+    #[error("Handshake buffer would hold {buffered_bytes} bytes, which exceeds the maximum of {max_bytes}")]
+    MaxBufferedBytesExceeded {
+        max_bytes: usize,
+        buffered_bytes: usize,
+    },
+
+    /// Packets can't be generated because there is no source of random data.  This only
+    /// happens on `wasm32-unknown-unknown` when `Handshake::set_random_source()` hasn't been
+    /// called, as that target has no default source.
+    #[error("No random source has been set for the handshake")]
+    NoRandomSource,
+
+    /// The peer's RTMPE Diffie-Hellman public key was missing or was not a usable value.
+    #[cfg(feature = "rtmpe")]
+    #[error("Peer sent an invalid RTMPE public key")]
+    InvalidRtmpePublicKey,
+
     /// This occurs when an IO error is encountered while reading the input.
-    #[error("_0")]
+    #[error("An IO error occurred while reading the input: {0}")]
     Io(#[from] io::Error),
 }
@@ -19,18 +19,33 @@ exactly and verifies that bytes 4-7 are zeroes.  At this point in time any (with
 of h.264 video) all clients and servers should work against the fp9 method so this should not
 be an issue.
 
-**Note:** At this point of time we only accept (and send) command bytes of 3, meaning that
-no encryption is used.
+**Note:** By default we only accept (and send) command bytes of 3, meaning that no encryption
+is used.  When the `rtmpe` feature is enabled a handshake can opt into the encrypted RTMPE
+variant (command byte 6) via `Handshake::set_rtmpe()`.  Once an RTMPE handshake completes, the
+`RtmpeCipher` obtained from `Handshake::take_rtmpe_cipher()` must be used to encrypt all bytes
+sent to the peer and decrypt all bytes received from it (including any bytes returned in
+the completion's `remaining_bytes`, which are decrypted already).
 
 */
 
 mod errors;
+#[cfg(feature = "rtmpe")]
+mod rtmpe;
+mod typestate;
 
 pub use self::errors::HandshakeError;
-
+#[cfg(feature = "rtmpe")]
+pub use self::rtmpe::RtmpeCipher;
+pub use self::typestate::{AwaitingC0C1, AwaitingC2, AwaitingS0S1, AwaitingS2, Complete};
+pub use self::typestate::{ClientHandshake, ClientS0S1Step, ClientS2Step};
+pub use self::typestate::{ServerC0C1Step, ServerC2Step, ServerHandshake};
+
+use byteorder::{BigEndian, ByteOrder};
+use bytes::{Bytes, BytesMut};
+use core::cmp::min;
+use core::mem;
 use hmac::{Hmac, Mac, NewMac};
-use rand;
-use rand::Rng;
+use random::DEFAULT_SOURCE;
 use sha2::Sha256;
 
 const RTMP_PACKET_SIZE: usize = 1536;
@@ -41,8 +56,17 @@ const RANDOM_CRUD: [u8; 32] = [
     0x02_u8, 0x9e_u8, 0x7e_u8, 0x57_u8, 0x6e_u8, 0xec_u8, 0x5d_u8, 0x2d_u8, 0x29_u8, 0x80_u8,
     0x6f_u8, 0xab_u8, 0x93_u8, 0xb8_u8, 0xe6_u8, 0x36_u8, 0xcf_u8, 0xeb_u8, 0x31_u8, 0xae_u8,
 ];
-const GENUINE_FMS_CONST: &'static str = "Genuine Adobe Flash Media Server 001";
-const GENUINE_FP_CONST: &'static str = "Genuine Adobe Flash Player 001";
+const GENUINE_FMS_CONST: &str = "Genuine Adobe Flash Media Server 001";
+const GENUINE_FP_CONST: &str = "Genuine Adobe Flash Player 001";
+
+/// Version bytes from before RTMP version 3, which the specification lists as deprecated values
+/// used by earlier proprietary products
+const LEGACY_VERSION_BYTES: [u8; 3] = [0, 1, 2];
+
+/// The default maximum number of bytes a `Handshake` will hold onto between calls.  Only the
+/// part of a packet that was split across reads is held, so this is the size of the largest
+/// handshake packet.
+pub const DEFAULT_MAX_BUFFERED_BYTES: usize = RTMP_PACKET_SIZE;
 
 /// Contains the result after processing bytes for the handshaking process
 #[derive(PartialEq, Eq, Debug)]
@@ -58,11 +82,46 @@ pub enum HandshakeProcessResult {
         /// Any bytes that should be sent to the peer as a response
         response_bytes: Vec<u8>,
 
-        /// Any bytes left over after completing the handshake
-        remaining_bytes: Vec<u8>,
+        /// Details about the completed handshake, including any bytes left over after it
+        completion: HandshakeCompletion,
     },
 }
 
+/// The type of handshake that was negotiated with the peer
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HandshakeType {
+    /// The handshake from the original RTMP specification, where packet 1 is echoed back as-is
+    Original,
+
+    /// The flash player 9 handshake, where packet 1 contains an HMAC digest
+    Digest,
+
+    /// The encrypted variant of the digest handshake
+    #[cfg(feature = "rtmpe")]
+    Rtmpe,
+}
+
+/// Information about a handshake that has successfully completed, so callers can log how the
+/// peer connected and make policy decisions based on it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct HandshakeCompletion {
+    /// Any bytes left over after completing the handshake.  These are split off of the
+    /// handshake's internal buffer without being copied.
+    pub remaining_bytes: Bytes,
+
+    /// The epoch (time field) the peer sent in its packet 1
+    pub peer_epoch: u32,
+
+    /// Which type of handshake was performed
+    pub handshake_type: HandshakeType,
+
+    /// True if the peer's packet 1 contained a digest that passed verification
+    pub peer_digest_valid: bool,
+
+    /// Any legacy quirks the peer exhibited that were tolerated
+    pub quirks: Vec<HandshakeQuirk>,
+}
+
 /// The type of peer being represented by the handshake.
 ///
 /// This only matters due to the FP9 handshaking process, where the client and server use different
@@ -76,10 +135,35 @@ pub enum PeerType {
     Client,
 }
 
-struct MessageParts {
-    before_digest: Vec<u8>,
-    after_digest: Vec<u8>,
-    digest: [u8; SHA256_DIGEST_LENGTH],
+/// A function that fills a buffer with random bytes, used to generate the random data that
+/// handshake packets are made of
+pub type RandomSource = fn(&mut [u8]);
+
+struct MessageParts<'a> {
+    before_digest: &'a [u8],
+    after_digest: &'a [u8],
+    digest: &'a [u8],
+}
+
+/// Known deviations from the handshake specification that older flash clients and encoders are
+/// known to send.  These are only tolerated when legacy quirks have been enabled on the handshake
+/// via `Handshake::set_legacy_quirks()`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HandshakeQuirk {
+    /// The peer's packet 0 contained one of the deprecated version bytes (0 to 2) that came
+    /// before RTMP version 3.  The value they sent is included.
+    UnexpectedVersionByte(u8),
+
+    /// The peer's packet 2 echoed back our packet 1 with its time2 field zeroed instead of set to
+    /// the time they read our packet 1 at.
+    ZeroedTime2,
+}
+
+/// Which of the two known packet 1 layouts a digest was found with
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DigestScheme {
+    ClientOffset,
+    ServerOffset,
 }
 
 #[derive(Eq, PartialEq, Debug, Clone)]
@@ -121,7 +205,7 @@ enum Stage {
 /// let c2 = match client.process_bytes(&s0_s1_and_s2[..]) {
 ///     Ok(HandshakeProcessResult::Completed {
 ///         response_bytes: bytes,
-///         remaining_bytes: _
+///         completion: _
 ///     }) => bytes,
 ///     x => panic!("Unexpected s0_s1_and_s2 process_bytes response: {:?}", x),
 /// };
@@ -129,7 +213,7 @@ enum Stage {
 /// match server.process_bytes(&c2[..]) {
 ///     Ok(HandshakeProcessResult::Completed {
 ///             response_bytes: _,
-///             remaining_bytes: _
+///             completion: _
 ///         }) => {},
 ///     x => panic!("Unexpected process_bytes response: {:?}", x),
 /// }
@@ -139,9 +223,19 @@ pub struct Handshake {
     current_stage: Stage,
     peer_type: PeerType,
     command_byte: u8,
-    input_buffer: Vec<u8>,
+    input_buffer: BytesMut,
     sent_p1: [u8; RTMP_PACKET_SIZE],
     sent_digest: [u8; SHA256_DIGEST_LENGTH],
+    max_buffered_bytes: usize,
+    allow_simple_fallback: bool,
+    allow_legacy_quirks: bool,
+    detected_quirks: Vec<HandshakeQuirk>,
+    peer_epoch: u32,
+    handshake_type: HandshakeType,
+    random_source: Option<RandomSource>,
+
+    #[cfg(feature = "rtmpe")]
+    rtmpe: rtmpe::RtmpeState,
 }
 
 impl Handshake {
@@ -154,49 +248,146 @@ impl Handshake {
         Handshake {
             current_stage: Stage::NeedToSendP0AndP1,
             command_byte: 0_u8,
-            input_buffer: Vec::with_capacity(RTMP_PACKET_SIZE),
+            input_buffer: BytesMut::with_capacity(RTMP_PACKET_SIZE + 1),
             sent_p1: [0_u8; RTMP_PACKET_SIZE],
             peer_type,
             sent_digest: [0_u8; SHA256_DIGEST_LENGTH],
+            max_buffered_bytes: DEFAULT_MAX_BUFFERED_BYTES,
+            allow_simple_fallback: true,
+            allow_legacy_quirks: false,
+            detected_quirks: Vec::new(),
+            peer_epoch: 0,
+            handshake_type: HandshakeType::Original,
+            random_source: DEFAULT_SOURCE,
+
+            #[cfg(feature = "rtmpe")]
+            rtmpe: rtmpe::RtmpeState::new(),
         }
     }
 
+    /// Sets the maximum number of bytes the handshake will buffer before it errors out.  Since
+    /// the handshake occurs before the peer has been authenticated in any way, this keeps a
+    /// hostile peer from being able to make us hold onto an unbounded amount of data.
+    ///
+    /// The limit only applies to the bytes held on to between calls, which are the parts of
+    /// packets that were split across reads.  Bytes that are parsed straight out of the input,
+    /// or handed back once the handshake completes, don't count towards it.
+    pub fn set_max_buffered_bytes(&mut self, max_buffered_bytes: usize) {
+        self.max_buffered_bytes = max_buffered_bytes;
+    }
+
+    /// Sets whether a packet 1 with a non-zero version that fails digest verification should
+    /// be accepted as an original (simple) handshake.  This is enabled by default, as some
+    /// encoders and servers (such as YouTube) send a non-zero version while still expecting the
+    /// original handshake, and nginx-rtmp accepts them the same way.
+    ///
+    /// When disabled, a packet 1 that contains a non-zero version is required to pass digest
+    /// verification, otherwise a `HandshakeError::UnknownPacket1Format` error is returned.  A
+    /// packet 1 with a zeroed version is always treated as an original handshake.
+    pub fn set_simple_handshake_fallback(&mut self, allow_fallback: bool) {
+        self.allow_simple_fallback = allow_fallback;
+    }
+
+    /// Sets whether known quirks from old flash clients should be tolerated instead of causing
+    /// the handshake to fail.  This is disabled by default.  Any quirks that were tolerated can be
+    /// retrieved with `detected_quirks()`.
+    ///
+    /// Packet 2 is not verified, so a zeroed time2 field is accepted whether or not this is
+    /// enabled, and is only reported when it is.
+    pub fn set_legacy_quirks(&mut self, allow_quirks: bool) {
+        self.allow_legacy_quirks = allow_quirks;
+    }
+
+    /// Sets the function used to generate the random data sent in packets 1 and 2, and the
+    /// keys of RTMPE handshakes.  By default this is seeded by the operating system, except on
+    /// `wasm32-unknown-unknown` where no operating system is available.  There is no default
+    /// source there, and generating packets fails with `HandshakeError::NoRandomSource` until
+    /// a source backed by the runtime, such as `crypto.getRandomValues()`, has been set.
+    ///
+    /// This must be called before any packets are generated.
+    pub fn set_random_source(&mut self, random_source: RandomSource) {
+        self.random_source = Some(random_source);
+    }
+
+    /// Returns the legacy quirks that the peer has exhibited so far during the handshake
+    pub fn detected_quirks(&self) -> &[HandshakeQuirk] {
+        &self.detected_quirks
+    }
+
+    /// Sets whether the encrypted RTMPE handshake should be used.  For clients this causes an
+    /// RTMPE handshake to be requested, which is required to connect to `rtmpe://` URLs.  For
+    /// servers this allows clients to request an RTMPE handshake.
+    ///
+    /// A server can only tell a client wants RTMPE from the client's packet 0, so servers which
+    /// allow RTMPE should not call `generate_outbound_p0_and_p1()` before calling
+    /// `process_bytes()` with the client's first bytes.
+    #[cfg(feature = "rtmpe")]
+    pub fn set_rtmpe(&mut self, enabled: bool) {
+        self.rtmpe.enabled = enabled;
+        self.rtmpe.active = enabled && self.peer_type == PeerType::Client;
+    }
+
+    /// Takes the RC4 cipher negotiated by a completed RTMPE handshake.  This returns `None` if
+    /// the handshake has not completed or did not use RTMPE.
+    #[cfg(feature = "rtmpe")]
+    pub fn take_rtmpe_cipher(&mut self) -> Option<RtmpeCipher> {
+        if self.current_stage != Stage::Complete {
+            return None;
+        }
+
+        self.rtmpe.cipher.take()
+    }
+
     /// Creates the packets 0 and 1 that should get sent to the peer.  This is only strictly
     /// required to be called by the client in the connection process to initiate the handshake
     /// process.  The server can wait until `process_bytes()` is called, and the outbound
     /// packets #0 and #1 will be included as the handshake's response.
     ///
-    /// For now this only sends a command byte of 3 (no encryption).
+    /// This sends a command byte of 3 (no encryption), unless an RTMPE handshake is being
+    /// performed, in which case a command byte of 6 is sent.
     pub fn generate_outbound_p0_and_p1(&mut self) -> Result<Vec<u8>, HandshakeError> {
         const ADOBE_VERSION: [u8; 4] = [128_u8, 0_u8, 7_u8, 2_u8]; // Copied from jw player handshake
 
         // Leave time field as zero, version field as ADOBE_VERSION, and the rest of the packet
         // should be random data.  Part of the random data will be used to determine placement
         // of the digest offset
-        fill_with_random_data(&mut self.sent_p1[8..1532]);
-        self.sent_p1[4] = ADOBE_VERSION[0];
-        self.sent_p1[5] = ADOBE_VERSION[1];
-        self.sent_p1[6] = ADOBE_VERSION[2];
-        self.sent_p1[7] = ADOBE_VERSION[3];
+        let random_source = self.random_source.ok_or(HandshakeError::NoRandomSource)?;
+        random_source(&mut self.sent_p1[8..1532]);
+        self.sent_p1[4..8].copy_from_slice(&ADOBE_VERSION);
+
+        #[cfg(feature = "rtmpe")]
+        {
+            if self.rtmpe.active {
+                // The public key must be in place before the digest is calculated
+                let scheme = match self.peer_type {
+                    PeerType::Server => DigestScheme::ServerOffset,
+                    PeerType::Client => DigestScheme::ClientOffset,
+                };
+
+                self.rtmpe
+                    .write_public_key(&mut self.sent_p1, scheme, random_source);
+            }
+        }
 
         let (digest_offset, constant_key) = match self.peer_type {
             PeerType::Server => (get_server_digest_offset(&self.sent_p1), GENUINE_FMS_CONST),
             PeerType::Client => (get_client_digest_offset(&self.sent_p1), GENUINE_FP_CONST),
         };
 
+        let digest_offset = digest_offset as usize;
         {
             let key_bytes = constant_key.as_bytes();
-            let pre_digest = &self.sent_p1[0..(digest_offset as usize)];
-            let post_digest = &self.sent_p1[(digest_offset as usize + SHA256_DIGEST_LENGTH)..];
-            self.sent_digest = calc_hmac_from_parts(&pre_digest, &post_digest, &key_bytes);
+            let pre_digest = &self.sent_p1[0..digest_offset];
+            let post_digest = &self.sent_p1[(digest_offset + SHA256_DIGEST_LENGTH)..];
+            self.sent_digest = calc_hmac_from_parts(pre_digest, post_digest, key_bytes);
         }
 
         // Form packet #1
-        for index in 0..SHA256_DIGEST_LENGTH {
-            self.sent_p1[(digest_offset as usize) + index] = self.sent_digest[index];
-        }
+        self.sent_p1[digest_offset..digest_offset + SHA256_DIGEST_LENGTH]
+            .copy_from_slice(&self.sent_digest);
 
-        let mut output = vec![3_u8];
+        let mut output = Vec::with_capacity(RTMP_PACKET_SIZE + 1);
+        output.push(self.expected_command_byte());
         output.extend_from_slice(&self.sent_p1);
 
         self.current_stage = Stage::WaitingForPacket0;
@@ -217,105 +408,187 @@ impl Handshake {
     /// If the `Handshake` has not generated the outbound packets 0 and 1 yet, then
     /// the first call to `process_bytes` will include packets 0 and 1 in the `response_bytes`
     /// field.
+    ///
+    /// Handshake packets are read straight out of `data`, and only a packet that is split across
+    /// calls is copied into the handshake's own buffer, so a peer sending each packet in one go
+    /// causes no copies.  Overflow bytes are copied out of `data` on completion, which
+    /// `process_shared_bytes()` avoids.
+    ///
+    /// If the bytes held on to for a split packet would grow past the handshake's maximum
+    /// buffered byte limit then a `HandshakeError::MaxBufferedBytesExceeded` error is returned
+    /// and no more bytes are buffered.
     pub fn process_bytes(&mut self, data: &[u8]) -> Result<HandshakeProcessResult, HandshakeError> {
-        self.input_buffer.extend_from_slice(data);
+        let (response_bytes, consumed) = self.process_input(data)?;
+        Ok(self.create_result(response_bytes, || Bytes::copy_from_slice(&data[consumed..])))
+    }
 
-        let mut bytes_for_response: Vec<u8> = Vec::new();
-        let mut left_over_bytes: Vec<u8> = Vec::new();
+    /// Works like `process_bytes()`, but takes the bytes as shared `Bytes` so the overflow
+    /// bytes returned on completion are split off of them instead of being copied.
+    pub fn process_shared_bytes(
+        &mut self,
+        data: Bytes,
+    ) -> Result<HandshakeProcessResult, HandshakeError> {
+        let (response_bytes, consumed) = self.process_input(&data)?;
+        Ok(self.create_result(response_bytes, || data.slice(consumed..)))
+    }
 
+    /// Runs the handshake over the input, returning the bytes to respond with and how many bytes
+    /// of `data` were used by the handshake
+    fn process_input(&mut self, data: &[u8]) -> Result<(Vec<u8>, usize), HandshakeError> {
+        if self.current_stage == Stage::Complete {
+            return Err(HandshakeError::HandshakeAlreadyCompleted);
+        }
+
+        #[cfg(feature = "rtmpe")]
+        {
+            // Servers need to know if the client is requesting RTMPE before generating their p1.
+            // Nothing has been buffered before then, as p0 and p1 are generated first.
+            let client_requested_rtmpe = self.peer_type == PeerType::Server
+                && self.current_stage == Stage::NeedToSendP0AndP1
+                && data.first() == Some(&rtmpe::RTMPE_COMMAND_BYTE);
+
+            if client_requested_rtmpe && self.rtmpe.enabled {
+                self.rtmpe.active = true;
+            }
+        }
+
+        let mut bytes_for_response: Vec<u8> = Vec::new();
+        let mut consumed = 0;
         loop {
-            let starting_stage = self.current_stage.clone();
-            let result = match self.current_stage {
-                Stage::NeedToSendP0AndP1 => match self.generate_outbound_p0_and_p1() {
-                    Err(x) => Err(x),
-                    Ok(bytes) => Ok(HandshakeProcessResult::InProgress {
-                        response_bytes: bytes,
-                    }),
-                },
-                Stage::WaitingForPacket0 => self.parse_p0(),
-                Stage::WaitingForPacket1 => self.parse_p1(),
-                Stage::WaitingForPacket2 => self.parse_p2(),
-                Stage::Complete => Err(HandshakeError::HandshakeAlreadyCompleted),
+            let packet_length = match self.current_stage {
+                Stage::NeedToSendP0AndP1 => {
+                    let response = self.generate_outbound_p0_and_p1()?;
+                    bytes_for_response.extend(response);
+                    continue;
+                }
+
+                Stage::WaitingForPacket0 => 1,
+                Stage::WaitingForPacket1 | Stage::WaitingForPacket2 => RTMP_PACKET_SIZE,
+                Stage::Complete => break,
             };
 
-            match result {
-                Err(x) => return Err(x),
-                Ok(x) => match x {
-                    HandshakeProcessResult::InProgress {
-                        response_bytes: bytes,
-                    } => bytes_for_response.extend(bytes),
-                    HandshakeProcessResult::Completed {
-                        response_bytes: _,
-                        remaining_bytes: bytes,
-                    } => left_over_bytes.extend(bytes),
-                },
+            let unread = &data[consumed..];
+            let response = if self.input_buffer.is_empty() && unread.len() >= packet_length {
+                consumed += packet_length;
+                self.parse_packet(&unread[..packet_length])?
+            } else {
+                // The packet is split across calls, so only the bytes it's missing are buffered
+                let missing = min(packet_length - self.input_buffer.len(), unread.len());
+                let buffered_bytes = self.input_buffer.len() + missing;
+                if buffered_bytes > self.max_buffered_bytes {
+                    return Err(HandshakeError::MaxBufferedBytesExceeded {
+                        max_bytes: self.max_buffered_bytes,
+                        buffered_bytes,
+                    });
+                }
+
+                self.input_buffer.extend_from_slice(&unread[..missing]);
+                consumed += missing;
+                if self.input_buffer.len() < packet_length {
+                    break;
+                }
+
+                // Taken out while it's parsed, then put back so its allocation is reused
+                let mut packet = mem::take(&mut self.input_buffer);
+                let response = self.parse_packet(&packet);
+                packet.clear();
+                self.input_buffer = packet;
+                response?
             };
 
-            if self.current_stage == Stage::Complete || starting_stage == self.current_stage {
-                // If we are still on the same stage assume that we didn't have
-                // enough bytes to process the current packet and wait to try again
-                break;
-            }
+            bytes_for_response.extend(response);
         }
 
-        if self.current_stage == Stage::Complete {
-            Ok(HandshakeProcessResult::Completed {
-                response_bytes: bytes_for_response,
-                remaining_bytes: left_over_bytes,
-            })
-        } else {
-            Ok(HandshakeProcessResult::InProgress {
-                response_bytes: bytes_for_response,
-            })
-        }
+        Ok((bytes_for_response, consumed))
     }
 
-    fn parse_p0(&mut self) -> Result<HandshakeProcessResult, HandshakeError> {
-        if self.input_buffer.len() == 0 {
-            return Ok(HandshakeProcessResult::InProgress {
-                response_bytes: Vec::new(),
-            });
+    fn create_result<F>(&mut self, response_bytes: Vec<u8>, remaining: F) -> HandshakeProcessResult
+    where
+        F: FnOnce() -> Bytes,
+    {
+        if self.current_stage != Stage::Complete {
+            return HandshakeProcessResult::InProgress { response_bytes };
         }
 
-        self.command_byte = self.input_buffer.remove(0);
-        if self.command_byte != 3_u8 {
-            return Err(HandshakeError::BadVersionId);
-        };
+        // Whatever is left of the input is not part of the handshake, so hand it back
+        #[allow(unused_mut)]
+        let mut remaining_bytes = remaining();
 
-        self.current_stage = Stage::WaitingForPacket1;
-        Ok(HandshakeProcessResult::InProgress {
-            response_bytes: Vec::new(),
-        })
-    }
+        #[cfg(feature = "rtmpe")]
+        {
+            if let Some(ref mut cipher) = self.rtmpe.cipher {
+                let mut decrypted = BytesMut::from(&remaining_bytes[..]);
+                cipher.decrypt(&mut decrypted);
+                remaining_bytes = decrypted.freeze();
+            }
+        }
 
-    fn parse_p1(&mut self) -> Result<HandshakeProcessResult, HandshakeError> {
-        if self.input_buffer.len() < RTMP_PACKET_SIZE {
-            return Ok(HandshakeProcessResult::InProgress {
-                response_bytes: Vec::new(),
-            });
+        HandshakeProcessResult::Completed {
+            response_bytes,
+            completion: HandshakeCompletion {
+                remaining_bytes,
+                peer_epoch: self.peer_epoch,
+                handshake_type: self.handshake_type,
+                peer_digest_valid: self.handshake_type != HandshakeType::Original,
+                quirks: self.detected_quirks.clone(),
+            },
         }
+    }
 
-        let received_packet_1: [u8; RTMP_PACKET_SIZE];
-        {
-            let mut handshake = [0_u8; RTMP_PACKET_SIZE];
-            let mut index = 0;
+    /// Parses a whole packet for the current stage, which moves the handshake to the next stage
+    fn parse_packet(&mut self, packet: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        match self.current_stage {
+            Stage::WaitingForPacket0 => self.parse_p0(packet),
+            Stage::WaitingForPacket1 => self.parse_p1(packet),
+            Stage::WaitingForPacket2 => self.parse_p2(packet),
+            Stage::NeedToSendP0AndP1 | Stage::Complete => Ok(Vec::new()),
+        }
+    }
 
-            for x in self.input_buffer.drain(..RTMP_PACKET_SIZE) {
-                handshake[index] = x;
-                index = index + 1;
+    fn parse_p0(&mut self, received_packet_0: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        self.command_byte = received_packet_0[0];
+        if self.command_byte != self.expected_command_byte() {
+            if !self.allow_legacy_quirks || !LEGACY_VERSION_BYTES.contains(&self.command_byte) {
+                return Err(HandshakeError::BadVersionId);
             }
 
-            received_packet_1 = handshake;
-        }
+            self.detected_quirks
+                .push(HandshakeQuirk::UnexpectedVersionByte(self.command_byte));
+        };
+
+        self.current_stage = Stage::WaitingForPacket1;
+        Ok(Vec::new())
+    }
+
+    fn parse_p1(&mut self, received_packet_1: &[u8]) -> Result<Vec<u8>, HandshakeError> {
+        self.peer_epoch = BigEndian::read_u32(&received_packet_1[0..4]);
 
         // Test against the expected constant string the peer sent over
         let p1_key = match self.peer_type {
-            PeerType::Server => GENUINE_FP_CONST.as_bytes().to_vec(),
-            PeerType::Client => GENUINE_FMS_CONST.as_bytes().to_vec(),
+            PeerType::Server => GENUINE_FP_CONST.as_bytes(),
+            PeerType::Client => GENUINE_FMS_CONST.as_bytes(),
         };
 
-        let received_digest = match get_digest_for_received_packet(&received_packet_1, &p1_key) {
-            Ok(digest) => digest,
+        let received_digest = match get_digest_for_received_packet(received_packet_1, p1_key) {
+            Ok((digest, _scheme)) => {
+                self.handshake_type = HandshakeType::Digest;
+
+                #[cfg(feature = "rtmpe")]
+                {
+                    if self.rtmpe.active {
+                        self.rtmpe.process_peer_packet(received_packet_1, _scheme)?;
+                        self.handshake_type = HandshakeType::Rtmpe;
+                    }
+                }
+
+                digest
+            }
+
+            Err(HandshakeError::UnknownPacket1Format) if self.expected_command_byte() != 3 => {
+                // Encrypted handshakes require a digest, so there's nothing to fall back to
+                return Err(HandshakeError::UnknownPacket1Format);
+            }
+
             Err(HandshakeError::UnknownPacket1Format) => {
                 // Since no digest was found chances are that this handshake is
                 // not a fp9 handshake but instead is the handshake from the
@@ -326,18 +599,23 @@ impl Handshake {
                 // Note that the original RTMP specification indicates a version
                 // of 0 should be specified in the p1 packet, but some RTMP
                 // destinations such as YouTube provide a non-zero version while
-                // still expecting an original handshake.
+                // still expecting an original handshake.  Only accept those if
+                // we've been configured to fall back to the simple handshake.
+                let version_is_zeroed = received_packet_1[4..8].iter().all(|x| *x == 0);
+                if !version_is_zeroed && !self.allow_simple_fallback {
+                    return Err(HandshakeError::UnknownPacket1Format);
+                }
+
                 self.current_stage = Stage::WaitingForPacket2;
-                return Ok(HandshakeProcessResult::InProgress {
-                    response_bytes: received_packet_1.to_vec(),
-                });
+                return Ok(received_packet_1.to_vec());
             }
             Err(x) => return Err(x),
         };
 
         // generate packet 2 for a response
-        let mut output_packet = [0_u8; RTMP_PACKET_SIZE];
-        fill_with_random_data(&mut output_packet);
+        let mut output_packet = vec![0_u8; RTMP_PACKET_SIZE];
+        let random_source = self.random_source.ok_or(HandshakeError::NoRandomSource)?;
+        random_source(&mut output_packet);
 
         let mut p2_key = match self.peer_type {
             PeerType::Server => GENUINE_FMS_CONST.as_bytes().to_vec(),
@@ -350,50 +628,42 @@ impl Handshake {
         let hmac2 = calc_hmac(&output_packet[..P2_SIG_START_INDEX], &hmac1);
 
         // the hmac2 signature is written to the end of the p2 packet
-        for index in 0..SHA256_DIGEST_LENGTH {
-            output_packet[P2_SIG_START_INDEX + index] = hmac2[index];
-        }
+        output_packet[P2_SIG_START_INDEX..].copy_from_slice(&hmac2);
 
         self.current_stage = Stage::WaitingForPacket2;
-        Ok(HandshakeProcessResult::InProgress {
-            response_bytes: output_packet.to_vec(),
-        })
+        Ok(output_packet)
     }
 
-    fn parse_p2(&mut self) -> Result<HandshakeProcessResult, HandshakeError> {
-        if self.input_buffer.len() < RTMP_PACKET_SIZE {
-            return Ok(HandshakeProcessResult::InProgress {
-                response_bytes: Vec::new(),
-            });
+    #[cfg(feature = "rtmpe")]
+    fn expected_command_byte(&self) -> u8 {
+        if self.rtmpe.active {
+            rtmpe::RTMPE_COMMAND_BYTE
+        } else {
+            3_u8
         }
+    }
 
-        let received_packet_2: [u8; RTMP_PACKET_SIZE];
-        {
-            let mut handshake = [0_u8; RTMP_PACKET_SIZE];
-            handshake.copy_from_slice(&self.input_buffer[..RTMP_PACKET_SIZE]);
-
-            received_packet_2 = handshake;
-            self.input_buffer.drain(..RTMP_PACKET_SIZE);
-        }
+    #[cfg(not(feature = "rtmpe"))]
+    fn expected_command_byte(&self) -> u8 {
+        3_u8
+    }
 
+    fn parse_p2(&mut self, received_packet_2: &[u8]) -> Result<Vec<u8>, HandshakeError> {
         // If the peer sent back a p2 that is an exact copy of our p1, accept it as that mean's it
         // is the old style handshake
-        if &self.sent_p1[..] == &received_packet_2[..] {
+        if self.sent_p1[..] == received_packet_2[..] {
             self.current_stage = Stage::Complete;
-            let remaining_bytes = self.input_buffer.drain(..).collect();
-            return Ok(HandshakeProcessResult::Completed {
-                response_bytes: Vec::new(),
-                remaining_bytes,
-            });
+            return Ok(Vec::new());
         }
 
-        // Not an exact match, so test the signature
-        let mut peer_key = match self.peer_type {
-            PeerType::Server => GENUINE_FP_CONST.as_bytes().to_vec(),
-            PeerType::Client => GENUINE_FMS_CONST.as_bytes().to_vec(),
-        };
-
-        peer_key.extend_from_slice(&RANDOM_CRUD[..]);
+        if self.allow_legacy_quirks
+            && received_packet_2[4..8] == [0, 0, 0, 0]
+            && self.sent_p1[4..8] != [0, 0, 0, 0]
+            && self.sent_p1[..4] == received_packet_2[..4]
+            && self.sent_p1[8..] == received_packet_2[8..]
+        {
+            self.detected_quirks.push(HandshakeQuirk::ZeroedTime2);
+        }
 
         // TODO: Re-enable P2 verification.
         // Verification of packet 2 had to be commented out for flash players to work.  For some
@@ -402,6 +672,11 @@ impl Handshake {
         // us is fine if they don't disconnect after we sent them our p2, and can look at this
         // later if there's a reason to really care.
 
+        //let mut peer_key = match self.peer_type {
+        //    PeerType::Server => GENUINE_FP_CONST.as_bytes().to_vec(),
+        //    PeerType::Client => GENUINE_FMS_CONST.as_bytes().to_vec(),
+        //};
+        //peer_key.extend_from_slice(&RANDOM_CRUD[..]);
         //let expected_hmac = &received_packet_2[P2_SIG_START_INDEX..RTMP_PACKET_SIZE];
         //let hmac1 = calc_hmac(&self.sent_digest, &peer_key[..]);
         //let hmac2 = calc_hmac(&received_packet_2[..P2_SIG_START_INDEX], &hmac1);
@@ -410,116 +685,96 @@ impl Handshake {
         //}
 
         self.current_stage = Stage::Complete;
-        let bytes_left = self.input_buffer.drain(..).collect();
-        Ok(HandshakeProcessResult::Completed {
-            response_bytes: Vec::new(),
-            remaining_bytes: bytes_left,
-        })
+        Ok(Vec::new())
     }
 }
 
 fn get_digest_for_received_packet(
-    packet: &[u8; RTMP_PACKET_SIZE],
+    packet: &[u8],
     key: &[u8],
-) -> Result<[u8; SHA256_DIGEST_LENGTH], HandshakeError> {
+) -> Result<([u8; SHA256_DIGEST_LENGTH], DigestScheme), HandshakeError> {
     // According to the unofficial specification, peers may send messages with the digest pointer
     // either at index 8 or 772 with no known reason for why one would be used over the other.  For
     // the best compatibility just try both.
 
-    let v1_offset = get_client_digest_offset(&packet);
-    let v1_parts = get_message_parts(&packet, v1_offset)?;
-    let v1_hmac = calc_hmac_from_parts(&v1_parts.before_digest, &v1_parts.after_digest, &key);
+    let v1_offset = get_client_digest_offset(packet);
+    let v1_parts = get_message_parts(packet, v1_offset)?;
+    let v1_hmac = calc_hmac_from_parts(v1_parts.before_digest, v1_parts.after_digest, key);
 
-    let v2_offset = get_server_digest_offset(&packet);
-    let v2_parts = get_message_parts(&packet, v2_offset)?;
-    let v2_hmac = calc_hmac_from_parts(&v2_parts.before_digest, &v2_parts.after_digest, &key);
+    let v2_offset = get_server_digest_offset(packet);
+    let v2_parts = get_message_parts(packet, v2_offset)?;
+    let v2_hmac = calc_hmac_from_parts(v2_parts.before_digest, v2_parts.after_digest, key);
 
     match true {
-        _ if v1_hmac == v1_parts.digest => Ok(v1_parts.digest),
-        _ if v2_hmac == v2_parts.digest => Ok(v2_parts.digest),
+        _ if v1_hmac[..] == v1_parts.digest[..] => Ok((v1_hmac, DigestScheme::ClientOffset)),
+        _ if v2_hmac[..] == v2_parts.digest[..] => Ok((v2_hmac, DigestScheme::ServerOffset)),
         _ => Err(HandshakeError::UnknownPacket1Format),
     }
 }
 
-fn get_server_digest_offset(data: &[u8; RTMP_PACKET_SIZE]) -> u32 {
+fn get_server_digest_offset(data: &[u8]) -> u32 {
     let first_four_byte_sum =
         (data[772] as u32) + (data[773] as u32) + (data[774] as u32) + (data[775] as u32);
-    let offset = (first_four_byte_sum % 728) + 776;
-    offset
+
+    (first_four_byte_sum % 728) + 776
 }
 
-fn get_client_digest_offset(data: &[u8; RTMP_PACKET_SIZE]) -> u32 {
+fn get_client_digest_offset(data: &[u8]) -> u32 {
     let first_four_byte_sum =
         (data[8] as u32) + (data[9] as u32) + (data[10] as u32) + (data[11] as u32);
-    let offset = (first_four_byte_sum % 728) + 12;
-    offset
+
+    (first_four_byte_sum % 728) + 12
 }
 
-fn get_message_parts(
-    handshake: &[u8; RTMP_PACKET_SIZE],
+fn get_message_parts<'a>(
+    handshake: &'a [u8],
     digest_offset: u32,
-) -> Result<MessageParts, HandshakeError> {
-    let (part1, rest) = handshake.split_at(digest_offset as usize);
-    let (raw_digest, part2) = rest.split_at(SHA256_DIGEST_LENGTH);
-
-    let mut digest = [0_u8; SHA256_DIGEST_LENGTH];
-    for index in 0..SHA256_DIGEST_LENGTH {
-        digest[index] = raw_digest[index];
-    }
+) -> Result<MessageParts<'a>, HandshakeError> {
+    let (before_digest, rest) = handshake.split_at(digest_offset as usize);
+    let (digest, after_digest) = rest.split_at(SHA256_DIGEST_LENGTH);
 
     Ok(MessageParts {
-        before_digest: Vec::from(part1),
-        after_digest: Vec::from(part2),
+        before_digest,
+        after_digest,
         digest,
     })
 }
 
 fn calc_hmac_from_parts(part1: &[u8], part2: &[u8], key: &[u8]) -> [u8; SHA256_DIGEST_LENGTH] {
-    let mut inputs = Vec::with_capacity(part1.len() + part2.len());
-    for index in 0..part1.len() {
-        inputs.push(part1[index]);
-    }
-
-    for index in 0..part2.len() {
-        inputs.push(part2[index]);
-    }
+    let mut mac = Hmac::<Sha256>::new_varkey(key).unwrap();
+    mac.update(part1);
+    mac.update(part2);
 
-    calc_hmac(&inputs, &key)
+    finalize_hmac(mac)
 }
 
 fn calc_hmac(input: &[u8], key: &[u8]) -> [u8; SHA256_DIGEST_LENGTH] {
     let mut mac = Hmac::<Sha256>::new_varkey(key).unwrap();
     mac.update(input);
 
+    finalize_hmac(mac)
+}
+
+fn finalize_hmac(mac: Hmac<Sha256>) -> [u8; SHA256_DIGEST_LENGTH] {
     let result = mac.finalize();
     let array = result.into_bytes();
 
-    if array.len() != 32 {
+    if array.len() != SHA256_DIGEST_LENGTH {
         panic!(
             "Expected hmac signature to be 32 byte array, instead it was a {} byte array",
             array.len()
         );
     }
 
-    let mut output = [0_u8; 32];
-    for index in 0..32 {
-        output[index] = array[index];
-    }
-
+    let mut output = [0_u8; SHA256_DIGEST_LENGTH];
+    output.copy_from_slice(&array);
     output
 }
 
-fn fill_with_random_data(buffer: &mut [u8]) {
-    let mut rng = rand::thread_rng();
-    for x in 0..buffer.len() {
-        let value = rng.gen();
-        buffer[x] = value;
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use random::fill_with_random_data;
 
     // Since the handshake requires SHA digest computations, the easiest way to test this
     // is to take network traffic via JWPlayer's flash player and validate directly against that
@@ -700,6 +955,66 @@ mod tests {
         }
     }
 
+    #[test]
+    fn unexpected_version_byte_tolerated_and_reported_when_legacy_quirks_enabled() {
+        let mut handshake = Handshake::new(PeerType::Server);
+        handshake.set_legacy_quirks(true);
+        let input = [2_u8];
+
+        match handshake.process_bytes(&input) {
+            Ok(HandshakeProcessResult::InProgress { response_bytes: _ }) => (),
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        }
+
+        assert_eq!(
+            handshake.detected_quirks(),
+            &[HandshakeQuirk::UnexpectedVersionByte(2)]
+        );
+        assert_eq!(handshake.current_stage, Stage::WaitingForPacket1);
+    }
+
+    #[test]
+    fn unknown_version_byte_rejected_when_legacy_quirks_enabled() {
+        let mut handshake = Handshake::new(PeerType::Server);
+        handshake.set_legacy_quirks(true);
+        let input = [4_u8];
+
+        match handshake.process_bytes(&input) {
+            Err(HandshakeError::BadVersionId) => (),
+            x => panic!("Expected bad version id error, instead got {:?}", x),
+        }
+
+        assert!(handshake.detected_quirks().is_empty());
+    }
+
+    #[test]
+    fn zeroed_time2_reported_when_legacy_quirks_enabled() {
+        let mut client = Handshake::new(PeerType::Client);
+        let mut server = Handshake::new(PeerType::Server);
+        client.set_legacy_quirks(true);
+
+        let c0_and_c1 = client.generate_outbound_p0_and_p1().unwrap();
+        let s0_s1_and_s2 = match server.process_bytes(&c0_and_c1[..]) {
+            Ok(HandshakeProcessResult::InProgress {
+                response_bytes: bytes,
+            }) => bytes,
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        };
+
+        let mut s2 = client.sent_p1;
+        s2[4..8].copy_from_slice(&[0, 0, 0, 0]);
+        let mut input = s0_s1_and_s2[..RTMP_PACKET_SIZE + 1].to_vec();
+        input.extend_from_slice(&s2[..]);
+
+        match client.process_bytes(&input[..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: _,
+                completion,
+            }) => assert_eq!(completion.quirks, vec![HandshakeQuirk::ZeroedTime2]),
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        }
+    }
+
     #[test]
     fn can_accept_jw_player_example_p0_and_p1() {
         let mut handshake = Handshake::new(PeerType::Server);
@@ -745,8 +1060,8 @@ mod tests {
         let remaining_bytes = match handshake.process_bytes(&s0_and_s1[1..]) {
             Ok(HandshakeProcessResult::Completed {
                 response_bytes: _,
-                remaining_bytes: data,
-            }) => data,
+                completion,
+            }) => completion.remaining_bytes,
             Ok(x) => panic!("Unexpected response of {:?}", x),
             Err(x) => panic!("Unexpected error of {:?}", x),
         };
@@ -786,8 +1101,8 @@ mod tests {
         let remaining_bytes = match handshake.process_bytes(&s0_and_s1[1..]) {
             Ok(HandshakeProcessResult::Completed {
                 response_bytes: _,
-                remaining_bytes: data,
-            }) => data,
+                completion,
+            }) => completion.remaining_bytes,
             Ok(x) => panic!("Unexpected response of {:?}", x),
             Err(x) => panic!("Unexpected error of {:?}", x),
         };
@@ -796,6 +1111,51 @@ mod tests {
         assert_eq!(handshake.current_stage, Stage::Complete);
     }
 
+    #[test]
+    fn error_when_digest_fails_with_non_zero_version_and_simple_fallback_disabled() {
+        let mut c0_and_c1 = [0_u8; RTMP_PACKET_SIZE + 1];
+        c0_and_c1[0] = 3;
+        c0_and_c1[5] = 1;
+        fill_with_random_data(&mut c0_and_c1[9..RTMP_PACKET_SIZE + 1]);
+
+        let mut handshake = Handshake::new(PeerType::Server);
+        handshake.set_simple_handshake_fallback(false);
+        handshake.generate_outbound_p0_and_p1().unwrap();
+
+        match handshake.process_bytes(&c0_and_c1) {
+            Err(HandshakeError::UnknownPacket1Format) => (),
+            x => panic!(
+                "Expected unknown packet 1 format error, instead got {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn zeroed_version_accepted_as_original_handshake_with_simple_fallback_disabled() {
+        let mut c0_and_c1 = [0_u8; RTMP_PACKET_SIZE + 1];
+        c0_and_c1[0] = 3;
+        fill_with_random_data(&mut c0_and_c1[9..RTMP_PACKET_SIZE + 1]);
+
+        let mut handshake = Handshake::new(PeerType::Server);
+        handshake.set_simple_handshake_fallback(false);
+        handshake.generate_outbound_p0_and_p1().unwrap();
+
+        let s2 = match handshake.process_bytes(&c0_and_c1) {
+            Ok(HandshakeProcessResult::InProgress {
+                response_bytes: data,
+            }) => data,
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        };
+
+        assert_eq!(
+            &s2[..],
+            &c0_and_c1[1..],
+            "Expected s2 value matching our c1"
+        );
+        assert_eq!(handshake.current_stage, Stage::WaitingForPacket2);
+    }
+
     #[test]
     fn can_handshake_with_itself() {
         // This is the best way to verify we can handle the fp9 handshake method
@@ -823,7 +1183,7 @@ mod tests {
         let c2 = match client.process_bytes(&s0_s1_and_s2[..]) {
             Ok(HandshakeProcessResult::Completed {
                 response_bytes: bytes,
-                remaining_bytes: _,
+                completion: _,
             }) => bytes,
             x => panic!("Unexpected s0_s1_and_s2 process_bytes response: {:?}", x),
         };
@@ -833,7 +1193,7 @@ mod tests {
         match server.process_bytes(&c2[..]) {
             Ok(HandshakeProcessResult::Completed {
                 response_bytes: _,
-                remaining_bytes: _,
+                completion: _,
             }) => {}
             x => panic!("Unexpected process_bytes response: {:?}", x),
         }
@@ -841,6 +1201,344 @@ mod tests {
         assert_eq!(server.current_stage, Stage::Complete);
     }
 
+    #[test]
+    fn packets_are_filled_from_the_configured_random_source() {
+        fn fill_with_pattern(buffer: &mut [u8]) {
+            for byte in buffer.iter_mut() {
+                *byte = 0x5a;
+            }
+        }
+
+        let mut client = Handshake::new(PeerType::Client);
+        client.set_random_source(fill_with_pattern);
+        let c0_and_c1 = client.generate_outbound_p0_and_p1().unwrap();
+
+        // Everything but the command byte, time, version, and digest comes from the source
+        let pattern_count = c0_and_c1.iter().filter(|&&x| x == 0x5a).count();
+        assert!(pattern_count >= RTMP_PACKET_SIZE - 8 - SHA256_DIGEST_LENGTH - 4);
+
+        let mut server = Handshake::new(PeerType::Server);
+        match server.process_bytes(&c0_and_c1[..]) {
+            Ok(HandshakeProcessResult::InProgress { .. }) => (),
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_generating_packets_without_a_random_source() {
+        // What a handshake on wasm32-unknown-unknown starts with
+        let mut client = Handshake::new(PeerType::Client);
+        client.random_source = None;
+
+        match client.generate_outbound_p0_and_p1() {
+            Err(HandshakeError::NoRandomSource) => (),
+            x => panic!("Expected no random source error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn completion_reports_digest_handshake_details() {
+        let mut client = Handshake::new(PeerType::Client);
+        let mut server = Handshake::new(PeerType::Server);
+
+        let c0_and_c1 = client.generate_outbound_p0_and_p1().unwrap();
+        let s0_s1_and_s2 = match server.process_bytes(&c0_and_c1[..]) {
+            Ok(HandshakeProcessResult::InProgress {
+                response_bytes: bytes,
+            }) => bytes,
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        };
+
+        let c2 = match client.process_bytes(&s0_s1_and_s2[..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: bytes,
+                completion,
+            }) => {
+                assert_eq!(completion.handshake_type, HandshakeType::Digest);
+                assert!(completion.peer_digest_valid, "Expected valid digest");
+                assert_eq!(completion.peer_epoch, 0);
+                bytes
+            }
+            x => panic!("Unexpected s0_s1_and_s2 process_bytes response: {:?}", x),
+        };
+
+        match server.process_bytes(&c2[..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: _,
+                completion,
+            }) => {
+                assert_eq!(completion.handshake_type, HandshakeType::Digest);
+                assert!(completion.peer_digest_valid, "Expected valid digest");
+                assert!(completion.quirks.is_empty(), "Expected no quirks");
+            }
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn completion_reports_original_handshake_details() {
+        let mut c0_and_c1 = [0_u8; RTMP_PACKET_SIZE + 1];
+        c0_and_c1[0] = 3;
+        c0_and_c1[4] = 1;
+        fill_with_random_data(&mut c0_and_c1[9..RTMP_PACKET_SIZE + 1]);
+
+        let mut handshake = Handshake::new(PeerType::Server);
+        let s0_and_s1 = handshake.generate_outbound_p0_and_p1().unwrap();
+        handshake.process_bytes(&c0_and_c1).unwrap();
+
+        match handshake.process_bytes(&s0_and_s1[1..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: _,
+                completion,
+            }) => {
+                assert_eq!(completion.handshake_type, HandshakeType::Original);
+                assert!(!completion.peer_digest_valid, "Expected no valid digest");
+                assert_eq!(completion.peer_epoch, 1);
+            }
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn bytes_received_after_p2_are_returned_as_remaining_bytes() {
+        let mut client = Handshake::new(PeerType::Client);
+        let mut server = Handshake::new(PeerType::Server);
+
+        let c0_and_c1 = client.generate_outbound_p0_and_p1().unwrap();
+        let s0_s1_and_s2 = match server.process_bytes(&c0_and_c1[..]) {
+            Ok(HandshakeProcessResult::InProgress {
+                response_bytes: bytes,
+            }) => bytes,
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        };
+
+        let mut c2 = match client.process_bytes(&s0_s1_and_s2[..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: bytes,
+                completion: _,
+            }) => bytes,
+            x => panic!("Unexpected s0_s1_and_s2 process_bytes response: {:?}", x),
+        };
+
+        c2.extend_from_slice(&[1, 2, 3]);
+        match server.process_bytes(&c2[..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: _,
+                completion,
+            }) => assert_eq!(&completion.remaining_bytes[..], &[1, 2, 3]),
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn shared_bytes_after_p2_are_returned_without_copying() {
+        let mut client = Handshake::new(PeerType::Client);
+        let mut server = Handshake::new(PeerType::Server);
+
+        let c0_and_c1 = client.generate_outbound_p0_and_p1().unwrap();
+        let s0_s1_and_s2 = match server.process_shared_bytes(Bytes::from(c0_and_c1)) {
+            Ok(HandshakeProcessResult::InProgress {
+                response_bytes: bytes,
+            }) => bytes,
+            x => panic!("Unexpected process_shared_bytes response: {:?}", x),
+        };
+
+        let mut c2 = match client.process_bytes(&s0_s1_and_s2[..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: bytes,
+                completion: _,
+            }) => bytes,
+            x => panic!("Unexpected s0_s1_and_s2 process_bytes response: {:?}", x),
+        };
+
+        c2.extend_from_slice(&[1, 2, 3]);
+        let input = Bytes::from(c2);
+        match server.process_shared_bytes(input.clone()) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: _,
+                completion,
+            }) => {
+                assert_eq!(&completion.remaining_bytes[..], &[1, 2, 3]);
+                assert_eq!(
+                    completion.remaining_bytes.as_ptr(),
+                    input[RTMP_PACKET_SIZE..].as_ptr()
+                );
+            }
+
+            x => panic!("Unexpected process_shared_bytes response: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn can_handshake_when_packets_arrive_one_byte_at_a_time() {
+        let mut client = Handshake::new(PeerType::Client);
+        let mut server = Handshake::new(PeerType::Server);
+
+        let c0_and_c1 = client.generate_outbound_p0_and_p1().unwrap();
+        let mut s0_s1_and_s2 = Vec::new();
+        for byte in c0_and_c1.chunks(1) {
+            match server.process_bytes(byte) {
+                Ok(HandshakeProcessResult::InProgress { response_bytes }) => {
+                    s0_s1_and_s2.extend(response_bytes)
+                }
+
+                x => panic!("Unexpected process_bytes response: {:?}", x),
+            }
+        }
+
+        assert_eq!(s0_s1_and_s2.len(), RTMP_PACKET_SIZE * 2 + 1);
+
+        let c2 = match client.process_bytes(&s0_s1_and_s2[..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: bytes,
+                completion: _,
+            }) => bytes,
+            x => panic!("Unexpected s0_s1_and_s2 process_bytes response: {:?}", x),
+        };
+
+        let (last_byte, c2) = c2.split_last().unwrap();
+        for byte in c2.chunks(1) {
+            match server.process_bytes(byte) {
+                Ok(HandshakeProcessResult::InProgress { .. }) => (),
+                x => panic!("Unexpected process_bytes response: {:?}", x),
+            }
+        }
+
+        match server.process_bytes(&[*last_byte]) {
+            Ok(HandshakeProcessResult::Completed { completion, .. }) => {
+                assert!(completion.remaining_bytes.is_empty())
+            }
+
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn error_when_buffered_bytes_exceed_maximum() {
+        let mut handshake = Handshake::new(PeerType::Server);
+        handshake.set_max_buffered_bytes(1000);
+
+        let mut input = vec![3_u8];
+        input.extend_from_slice(&JWPLAYER_C1[..500]);
+        match handshake.process_bytes(&input) {
+            Ok(HandshakeProcessResult::InProgress { response_bytes: _ }) => (),
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        }
+
+        match handshake.process_bytes(&JWPLAYER_C1[500..1100]) {
+            Err(HandshakeError::MaxBufferedBytesExceeded {
+                max_bytes: 1000,
+                buffered_bytes: 1100,
+            }) => (),
+            x => panic!("Expected max buffered bytes error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn unbuffered_input_does_not_count_towards_buffered_bytes_maximum() {
+        let mut client = Handshake::new(PeerType::Client);
+        let mut server = Handshake::new(PeerType::Server);
+        server.set_max_buffered_bytes(100);
+
+        let c0_and_c1 = client.generate_outbound_p0_and_p1().unwrap();
+        let s0_s1_and_s2 = match server.process_bytes(&c0_and_c1) {
+            Ok(HandshakeProcessResult::InProgress { response_bytes }) => response_bytes,
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        };
+
+        let mut c2_and_overflow = match client.process_bytes(&s0_s1_and_s2) {
+            Ok(HandshakeProcessResult::Completed { response_bytes, .. }) => response_bytes,
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        };
+
+        c2_and_overflow.extend_from_slice(&[1_u8; 500]);
+        match server.process_bytes(&c2_and_overflow) {
+            Ok(HandshakeProcessResult::Completed { completion, .. }) => {
+                assert_eq!(completion.remaining_bytes.len(), 500)
+            }
+
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rtmpe")]
+    fn can_perform_rtmpe_handshake_with_itself() {
+        let mut client = Handshake::new(PeerType::Client);
+        let mut server = Handshake::new(PeerType::Server);
+        client.set_rtmpe(true);
+        server.set_rtmpe(true);
+
+        let c0_and_c1 = client.generate_outbound_p0_and_p1().unwrap();
+        assert_eq!(c0_and_c1[0], 6, "Unexpected c0 value");
+
+        let s0_s1_and_s2 = match server.process_bytes(&c0_and_c1[..]) {
+            Ok(HandshakeProcessResult::InProgress {
+                response_bytes: bytes,
+            }) => bytes,
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        };
+
+        assert_eq!(s0_s1_and_s2[0], 6, "Unexpected s0 value");
+
+        let c2 = match client.process_bytes(&s0_s1_and_s2[..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: bytes,
+                completion: _,
+            }) => bytes,
+            x => panic!("Unexpected s0_s1_and_s2 process_bytes response: {:?}", x),
+        };
+
+        let mut client_cipher = client.take_rtmpe_cipher().unwrap();
+        let mut encrypted = *b"some chunk data";
+        client_cipher.encrypt(&mut encrypted);
+        assert_ne!(&encrypted, b"some chunk data");
+
+        let mut input = c2.clone();
+        input.extend_from_slice(&encrypted);
+        let remaining_bytes = match server.process_bytes(&input[..]) {
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes: _,
+                completion,
+            }) => completion.remaining_bytes,
+            x => panic!("Unexpected process_bytes response: {:?}", x),
+        };
+
+        assert_eq!(&remaining_bytes[..], b"some chunk data");
+
+        let mut server_cipher = server.take_rtmpe_cipher().unwrap();
+        let mut response = *b"response";
+        server_cipher.encrypt(&mut response);
+        client_cipher.decrypt(&mut response);
+        assert_eq!(&response, b"response");
+    }
+
+    #[test]
+    fn rtmpe_version_byte_rejected_when_legacy_quirks_enabled_without_rtmpe() {
+        let mut handshake = Handshake::new(PeerType::Server);
+        handshake.set_legacy_quirks(true);
+        let input = [6_u8];
+
+        match handshake.process_bytes(&input) {
+            Err(HandshakeError::BadVersionId) => (),
+            x => panic!("Expected bad version id error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rtmpe")]
+    fn server_without_rtmpe_enabled_rejects_rtmpe_client() {
+        let mut client = Handshake::new(PeerType::Client);
+        let mut server = Handshake::new(PeerType::Server);
+        client.set_rtmpe(true);
+
+        let c0_and_c1 = client.generate_outbound_p0_and_p1().unwrap();
+        match server.process_bytes(&c0_and_c1[..]) {
+            Err(HandshakeError::BadVersionId) => (),
+            x => panic!("Expected bad version id error, instead got {:?}", x),
+        }
+    }
+
     #[test]
     fn sends_outbound_p0_p1_if_p0_received_and_outbound_p0_and_p1_not_yet_sent() {
         let mut handshake = Handshake::new(PeerType::Server);
@@ -862,7 +1560,7 @@ mod tests {
         let data1 = "Hi ".as_bytes();
         let data2 = "There".as_bytes();
         let key = [0x0b; 20];
-        let hmac = calc_hmac_from_parts(&data1, &data2, &key);
+        let hmac = calc_hmac_from_parts(data1, data2, &key);
 
         let expected = [
             176_u8, 52_u8, 76_u8, 97_u8, 216_u8, 219_u8, 56_u8, 83_u8, 92_u8, 168_u8, 175_u8,
@@ -877,7 +1575,7 @@ mod tests {
     fn hmac_test2() {
         let data1 = "Hi There".as_bytes();
         let key = [0x0b; 20];
-        let hmac = calc_hmac(&data1, &key);
+        let hmac = calc_hmac(data1, &key);
 
         let expected = [
             176_u8, 52_u8, 76_u8, 97_u8, 216_u8, 219_u8, 56_u8, 83_u8, 92_u8, 168_u8, 175_u8,
@@ -906,7 +1604,7 @@ mod tests {
 
     #[test]
     fn can_get_digest_from_c1() {
-        match get_digest_for_received_packet(&JWPLAYER_C1, &(GENUINE_FP_CONST.as_bytes())) {
+        match get_digest_for_received_packet(&JWPLAYER_C1, GENUINE_FP_CONST.as_bytes()) {
             Ok(_) => {}
             Err(x) => panic!("Unexpected error: {:?}", x),
         }
@@ -917,8 +1615,8 @@ mod tests {
         let mut message = [0_u8; RTMP_PACKET_SIZE];
         let offset: u32 = 500;
 
-        for index in 0..message.len() {
-            message[index] = match index {
+        for (index, byte) in message.iter_mut().enumerate() {
+            *byte = match index {
                 x if x < (offset as usize) => 1,
                 x if x > (offset as usize) + SHA256_DIGEST_LENGTH - 1 => 3,
                 _ => 2,
@@ -935,20 +1633,16 @@ mod tests {
         let expected_after = [3_u8; RTMP_PACKET_SIZE - 500 - SHA256_DIGEST_LENGTH];
 
         assert_eq!(
-            &result.before_digest[..],
+            result.before_digest,
             &expected_before[..],
             "Before did not match"
         );
         assert_eq!(
-            &result.after_digest[..],
+            result.after_digest,
             &expected_after[..],
             "After did not match"
         );
-        assert_eq!(
-            &result.digest[..],
-            &expected_digest[..],
-            "Digest did not match"
-        );
+        assert_eq!(result.digest, &expected_digest[..], "Digest did not match");
     }
 
     #[test]
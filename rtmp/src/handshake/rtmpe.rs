@@ -0,0 +1,254 @@
+//! Support for the RTMPE (encrypted) variant of the fp9 handshake.
+//!
+//! RTMPE handshakes look like a digest handshake with a command byte of 6, with each peer placing
+//! a Diffie-Hellman public key into its packet 1.  Once both public keys have been exchanged each
+//! side derives a pair of RC4 keys from the shared secret, and every byte sent after the handshake
+//! is encrypted with them.
+
+use num_bigint::BigUint;
+
+use super::{calc_hmac, DigestScheme, HandshakeError, RandomSource};
+
+/// The command byte used by peers requesting an RTMPE handshake
+pub const RTMPE_COMMAND_BYTE: u8 = 6;
+
+const DH_KEY_LENGTH: usize = 128;
+const RC4_KEY_LENGTH: usize = 16;
+const RC4_DROP_LENGTH: usize = 1536;
+
+// 1024 bit MODP group from RFC 2409 (section 6.2), as used by flash players and servers.
+const DH_PRIME: &[u8] = b"FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74\
+                          020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437\
+                          4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED\
+                          EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE65381FFFFFFFFFFFFFFFF";
+const DH_GENERATOR: u32 = 2;
+
+/// Holds the RC4 streams negotiated by an RTMPE handshake.  Every byte sent to the peer after the
+/// handshake has completed must be passed through `encrypt()`, and every byte received from the
+/// peer must be passed through `decrypt()` before being handed to the chunk deserializer or
+/// session.
+pub struct RtmpeCipher {
+    encryptor: Rc4,
+    decryptor: Rc4,
+}
+
+impl RtmpeCipher {
+    /// Encrypts outbound bytes in place
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        self.encryptor.apply(data);
+    }
+
+    /// Decrypts inbound bytes in place
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        self.decryptor.apply(data);
+    }
+}
+
+/// Tracks the RTMPE specific parts of a handshake
+pub(super) struct RtmpeState {
+    pub(super) enabled: bool,
+    pub(super) active: bool,
+    key_pair: Option<DhKeyPair>,
+    pub(super) cipher: Option<RtmpeCipher>,
+}
+
+impl RtmpeState {
+    pub(super) fn new() -> RtmpeState {
+        RtmpeState {
+            enabled: false,
+            active: false,
+            key_pair: None,
+            cipher: None,
+        }
+    }
+
+    /// Generates our Diffie-Hellman key pair and writes the public key into the packet 1 we are
+    /// about to send, at the position dictated by the digest scheme being used.
+    pub(super) fn write_public_key(
+        &mut self,
+        packet: &mut [u8],
+        scheme: DigestScheme,
+        random_source: RandomSource,
+    ) {
+        let key_pair = DhKeyPair::generate(random_source);
+        let offset = get_dh_offset(packet, scheme);
+        packet[offset..offset + DH_KEY_LENGTH].copy_from_slice(&key_pair.public_key);
+        self.key_pair = Some(key_pair);
+    }
+
+    /// Reads the peer's public key out of their packet 1 and derives the RC4 streams from it
+    pub(super) fn process_peer_packet(
+        &mut self,
+        packet: &[u8],
+        scheme: DigestScheme,
+    ) -> Result<(), HandshakeError> {
+        let key_pair = match self.key_pair {
+            Some(ref key_pair) => key_pair,
+            None => return Err(HandshakeError::InvalidRtmpePublicKey),
+        };
+
+        let offset = get_dh_offset(packet, scheme);
+        let peer_public_key = &packet[offset..offset + DH_KEY_LENGTH];
+        let shared_secret = key_pair.compute_shared_secret(peer_public_key)?;
+
+        let outbound_key = calc_hmac(peer_public_key, &shared_secret);
+        let inbound_key = calc_hmac(&key_pair.public_key, &shared_secret);
+
+        // Both peers discard the first 1536 bytes of each stream before using them
+        let mut encryptor = Rc4::new(&outbound_key[..RC4_KEY_LENGTH]);
+        let mut decryptor = Rc4::new(&inbound_key[..RC4_KEY_LENGTH]);
+        encryptor.apply(&mut [0_u8; RC4_DROP_LENGTH]);
+        decryptor.apply(&mut [0_u8; RC4_DROP_LENGTH]);
+
+        self.cipher = Some(RtmpeCipher {
+            encryptor,
+            decryptor,
+        });
+
+        Ok(())
+    }
+}
+
+struct DhKeyPair {
+    private_key: BigUint,
+    public_key: [u8; DH_KEY_LENGTH],
+}
+
+impl DhKeyPair {
+    fn generate(random_source: RandomSource) -> DhKeyPair {
+        let prime = dh_prime();
+        loop {
+            let mut private_bytes = [0_u8; DH_KEY_LENGTH];
+            random_source(&mut private_bytes);
+
+            let private_key = BigUint::from_bytes_be(&private_bytes);
+            let public_key = BigUint::from(DH_GENERATOR).modpow(&private_key, &prime);
+            if is_valid_public_key(&public_key, &prime) {
+                return DhKeyPair {
+                    private_key,
+                    public_key: to_key_bytes(&public_key),
+                };
+            }
+        }
+    }
+
+    fn compute_shared_secret(
+        &self,
+        peer_public_key: &[u8],
+    ) -> Result<[u8; DH_KEY_LENGTH], HandshakeError> {
+        let prime = dh_prime();
+        let peer_public_key = BigUint::from_bytes_be(peer_public_key);
+        if !is_valid_public_key(&peer_public_key, &prime) {
+            return Err(HandshakeError::InvalidRtmpePublicKey);
+        }
+
+        let secret = peer_public_key.modpow(&self.private_key, &prime);
+        Ok(to_key_bytes(&secret))
+    }
+}
+
+struct Rc4 {
+    state: [u8; 256],
+    i: u8,
+    j: u8,
+}
+
+impl Rc4 {
+    fn new(key: &[u8]) -> Rc4 {
+        let mut state = [0_u8; 256];
+        for (index, value) in state.iter_mut().enumerate() {
+            *value = index as u8;
+        }
+
+        let mut j = 0_u8;
+        for index in 0..256 {
+            j = j
+                .wrapping_add(state[index])
+                .wrapping_add(key[index % key.len()]);
+            state.swap(index, j as usize);
+        }
+
+        Rc4 { state, i: 0, j: 0 }
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            self.i = self.i.wrapping_add(1);
+            self.j = self.j.wrapping_add(self.state[self.i as usize]);
+            self.state.swap(self.i as usize, self.j as usize);
+
+            let index = self.state[self.i as usize].wrapping_add(self.state[self.j as usize]);
+            *byte ^= self.state[index as usize];
+        }
+    }
+}
+
+fn dh_prime() -> BigUint {
+    BigUint::parse_bytes(DH_PRIME, 16).unwrap()
+}
+
+fn is_valid_public_key(key: &BigUint, prime: &BigUint) -> bool {
+    // Keys of 0, 1, or p - 1 (and anything outside of the group) would let a peer force the
+    // shared secret to a known value.
+    let one = BigUint::from(1_u32);
+    *key > one && *key < prime - &one
+}
+
+fn to_key_bytes(value: &BigUint) -> [u8; DH_KEY_LENGTH] {
+    let bytes = value.to_bytes_be();
+    let mut output = [0_u8; DH_KEY_LENGTH];
+    output[DH_KEY_LENGTH - bytes.len()..].copy_from_slice(&bytes);
+    output
+}
+
+fn get_dh_offset(packet: &[u8], scheme: DigestScheme) -> usize {
+    // The public key lives in the half of the packet that does not contain the digest
+    let (sum_start, base) = match scheme {
+        DigestScheme::ClientOffset => (1532, 772),
+        DigestScheme::ServerOffset => (768, 8),
+    };
+
+    let sum: usize = packet[sum_start..sum_start + 4]
+        .iter()
+        .map(|x| *x as usize)
+        .sum();
+
+    (sum % 632) + base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use random::fill_with_random_data;
+
+    #[test]
+    fn rc4_matches_known_test_vector() {
+        let mut data = *b"Plaintext";
+        Rc4::new(b"Key").apply(&mut data);
+
+        assert_eq!(data, [0xbb, 0xf3, 0x16, 0xe8, 0xd9, 0x40, 0xaf, 0x0a, 0xd3]);
+    }
+
+    #[test]
+    fn both_peers_derive_the_same_shared_secret() {
+        let first = DhKeyPair::generate(fill_with_random_data);
+        let second = DhKeyPair::generate(fill_with_random_data);
+
+        let first_secret = first.compute_shared_secret(&second.public_key).unwrap();
+        let second_secret = second.compute_shared_secret(&first.public_key).unwrap();
+
+        assert_eq!(&first_secret[..], &second_secret[..]);
+    }
+
+    #[test]
+    fn public_key_of_one_is_rejected() {
+        let key_pair = DhKeyPair::generate(fill_with_random_data);
+        let mut bad_key = [0_u8; DH_KEY_LENGTH];
+        bad_key[DH_KEY_LENGTH - 1] = 1;
+
+        match key_pair.compute_shared_secret(&bad_key) {
+            Err(HandshakeError::InvalidRtmpePublicKey) => (),
+            x => panic!("Expected invalid public key error, instead got {:?}", x),
+        }
+    }
+}
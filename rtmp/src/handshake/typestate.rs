@@ -0,0 +1,356 @@
+//! Client and server handshake types that encode the handshake's progress in the type system.
+//!
+//! Each state transition consumes the handshake and returns it in its next state, so bytes can
+//! only be fed into a handshake that is actually waiting for them.
+
+use super::{Handshake, HandshakeCompletion, HandshakeError, HandshakeProcessResult};
+use super::{PeerType, Stage};
+
+#[cfg(feature = "rtmpe")]
+use super::RtmpeCipher;
+
+/// State of a client handshake that has sent c0 and c1 and is waiting on s0 and s1
+pub struct AwaitingS0S1;
+
+/// State of a client handshake that has sent c2 and is waiting on s2
+pub struct AwaitingS2;
+
+/// State of a server handshake that is waiting on c0 and c1
+pub struct AwaitingC0C1;
+
+/// State of a server handshake that has sent s2 and is waiting on c2
+pub struct AwaitingC2;
+
+/// State of a handshake that has successfully completed
+pub struct Complete {
+    completion: HandshakeCompletion,
+}
+
+/// A handshake being performed from the client's side of the connection
+pub struct ClientHandshake<S> {
+    handshake: Handshake,
+    state: S,
+}
+
+/// A handshake being performed from the server's side of the connection
+pub struct ServerHandshake<S> {
+    handshake: Handshake,
+    state: S,
+}
+
+/// The result of passing bytes into a client handshake awaiting s0 and s1
+pub enum ClientS0S1Step {
+    /// Not enough bytes have been received to read s0 and s1 yet
+    InProgress(ClientHandshake<AwaitingS0S1>),
+
+    /// S0 and s1 were received, and c2 should be sent to the server
+    ReceivedS1 {
+        handshake: ClientHandshake<AwaitingS2>,
+        response_bytes: Vec<u8>,
+    },
+
+    /// S0, s1, and s2 were all received, and c2 should be sent to the server
+    Completed {
+        handshake: ClientHandshake<Complete>,
+        response_bytes: Vec<u8>,
+    },
+}
+
+/// The result of passing bytes into a client handshake awaiting s2
+pub enum ClientS2Step {
+    /// Not enough bytes have been received to read s2 yet
+    InProgress(ClientHandshake<AwaitingS2>),
+
+    /// S2 was received and the handshake is complete
+    Completed(ClientHandshake<Complete>),
+}
+
+/// The result of passing bytes into a server handshake awaiting c0 and c1
+pub enum ServerC0C1Step {
+    /// Not enough bytes have been received to read c0 and c1 yet.  Once c0 has been received the
+    /// response bytes will contain s0 and s1.
+    InProgress {
+        handshake: ServerHandshake<AwaitingC0C1>,
+        response_bytes: Vec<u8>,
+    },
+
+    /// C0 and c1 were received, and the response bytes should be sent to the client
+    ReceivedC1 {
+        handshake: ServerHandshake<AwaitingC2>,
+        response_bytes: Vec<u8>,
+    },
+
+    /// C0, c1, and c2 were all received, and the response bytes should be sent to the client
+    Completed {
+        handshake: ServerHandshake<Complete>,
+        response_bytes: Vec<u8>,
+    },
+}
+
+/// The result of passing bytes into a server handshake awaiting c2
+pub enum ServerC2Step {
+    /// Not enough bytes have been received to read c2 yet
+    InProgress(ServerHandshake<AwaitingC2>),
+
+    /// C2 was received and the handshake is complete
+    Completed(ServerHandshake<Complete>),
+}
+
+impl ClientHandshake<AwaitingS0S1> {
+    /// Starts a new client handshake, returning it along with the c0 and c1 bytes that must be
+    /// sent to the server.
+    pub fn new() -> Result<(ClientHandshake<AwaitingS0S1>, Vec<u8>), HandshakeError> {
+        ClientHandshake::from_handshake(Handshake::new(PeerType::Client))
+    }
+
+    /// Starts a client handshake from a `Handshake` that has already been configured, returning
+    /// it along with the c0 and c1 bytes that must be sent to the server.  The `Handshake` must be
+    /// a client handshake that has not been started yet.
+    pub fn from_handshake(
+        mut handshake: Handshake,
+    ) -> Result<(ClientHandshake<AwaitingS0S1>, Vec<u8>), HandshakeError> {
+        if handshake.peer_type != PeerType::Client
+            || handshake.current_stage != Stage::NeedToSendP0AndP1
+        {
+            return Err(HandshakeError::InvalidInitialHandshake);
+        }
+
+        let c0_and_c1 = handshake.generate_outbound_p0_and_p1()?;
+        let client = ClientHandshake {
+            handshake,
+            state: AwaitingS0S1,
+        };
+
+        Ok((client, c0_and_c1))
+    }
+
+    /// Processes bytes received from the server
+    pub fn process_bytes(mut self, data: &[u8]) -> Result<ClientS0S1Step, HandshakeError> {
+        match self.handshake.process_bytes(data)? {
+            HandshakeProcessResult::InProgress { response_bytes } => {
+                if self.handshake.current_stage == Stage::WaitingForPacket2 {
+                    Ok(ClientS0S1Step::ReceivedS1 {
+                        handshake: ClientHandshake {
+                            handshake: self.handshake,
+                            state: AwaitingS2,
+                        },
+                        response_bytes,
+                    })
+                } else {
+                    Ok(ClientS0S1Step::InProgress(self))
+                }
+            }
+
+            HandshakeProcessResult::Completed {
+                response_bytes,
+                completion,
+            } => Ok(ClientS0S1Step::Completed {
+                handshake: ClientHandshake {
+                    handshake: self.handshake,
+                    state: Complete { completion },
+                },
+                response_bytes,
+            }),
+        }
+    }
+}
+
+impl ClientHandshake<AwaitingS2> {
+    /// Processes bytes received from the server
+    pub fn process_bytes(mut self, data: &[u8]) -> Result<ClientS2Step, HandshakeError> {
+        match self.handshake.process_bytes(data)? {
+            HandshakeProcessResult::InProgress { .. } => Ok(ClientS2Step::InProgress(self)),
+            HandshakeProcessResult::Completed { completion, .. } => {
+                Ok(ClientS2Step::Completed(ClientHandshake {
+                    handshake: self.handshake,
+                    state: Complete { completion },
+                }))
+            }
+        }
+    }
+}
+
+impl ClientHandshake<Complete> {
+    /// Details about the completed handshake
+    pub fn completion(&self) -> &HandshakeCompletion {
+        &self.state.completion
+    }
+
+    /// Consumes the handshake, returning the details about how it completed
+    pub fn into_completion(self) -> HandshakeCompletion {
+        self.state.completion
+    }
+
+    /// Takes the RC4 cipher negotiated if the handshake used RTMPE
+    #[cfg(feature = "rtmpe")]
+    pub fn take_rtmpe_cipher(&mut self) -> Option<RtmpeCipher> {
+        self.handshake.take_rtmpe_cipher()
+    }
+}
+
+impl ServerHandshake<AwaitingC0C1> {
+    /// Creates a new server handshake that is waiting for the client's c0 and c1
+    pub fn new() -> ServerHandshake<AwaitingC0C1> {
+        ServerHandshake {
+            handshake: Handshake::new(PeerType::Server),
+            state: AwaitingC0C1,
+        }
+    }
+
+    /// Creates a server handshake from a `Handshake` that has already been configured.  The
+    /// `Handshake` must be a server handshake that has not been started yet.
+    pub fn from_handshake(
+        handshake: Handshake,
+    ) -> Result<ServerHandshake<AwaitingC0C1>, HandshakeError> {
+        if handshake.peer_type != PeerType::Server
+            || handshake.current_stage != Stage::NeedToSendP0AndP1
+        {
+            return Err(HandshakeError::InvalidInitialHandshake);
+        }
+
+        Ok(ServerHandshake {
+            handshake,
+            state: AwaitingC0C1,
+        })
+    }
+
+    /// Processes bytes received from the client
+    pub fn process_bytes(mut self, data: &[u8]) -> Result<ServerC0C1Step, HandshakeError> {
+        match self.handshake.process_bytes(data)? {
+            HandshakeProcessResult::InProgress { response_bytes } => {
+                if self.handshake.current_stage == Stage::WaitingForPacket2 {
+                    Ok(ServerC0C1Step::ReceivedC1 {
+                        handshake: ServerHandshake {
+                            handshake: self.handshake,
+                            state: AwaitingC2,
+                        },
+                        response_bytes,
+                    })
+                } else {
+                    Ok(ServerC0C1Step::InProgress {
+                        handshake: self,
+                        response_bytes,
+                    })
+                }
+            }
+
+            HandshakeProcessResult::Completed {
+                response_bytes,
+                completion,
+            } => Ok(ServerC0C1Step::Completed {
+                handshake: ServerHandshake {
+                    handshake: self.handshake,
+                    state: Complete { completion },
+                },
+                response_bytes,
+            }),
+        }
+    }
+}
+
+impl Default for ServerHandshake<AwaitingC0C1> {
+    fn default() -> Self {
+        ServerHandshake::new()
+    }
+}
+
+impl ServerHandshake<AwaitingC2> {
+    /// Processes bytes received from the client
+    pub fn process_bytes(mut self, data: &[u8]) -> Result<ServerC2Step, HandshakeError> {
+        match self.handshake.process_bytes(data)? {
+            HandshakeProcessResult::InProgress { .. } => Ok(ServerC2Step::InProgress(self)),
+            HandshakeProcessResult::Completed { completion, .. } => {
+                Ok(ServerC2Step::Completed(ServerHandshake {
+                    handshake: self.handshake,
+                    state: Complete { completion },
+                }))
+            }
+        }
+    }
+}
+
+impl ServerHandshake<Complete> {
+    /// Details about the completed handshake
+    pub fn completion(&self) -> &HandshakeCompletion {
+        &self.state.completion
+    }
+
+    /// Consumes the handshake, returning the details about how it completed
+    pub fn into_completion(self) -> HandshakeCompletion {
+        self.state.completion
+    }
+
+    /// Takes the RC4 cipher negotiated if the handshake used RTMPE
+    #[cfg(feature = "rtmpe")]
+    pub fn take_rtmpe_cipher(&mut self) -> Option<RtmpeCipher> {
+        self.handshake.take_rtmpe_cipher()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::RTMP_PACKET_SIZE;
+    use super::*;
+
+    #[test]
+    fn client_and_server_can_handshake_through_each_state() {
+        let (client, c0_and_c1) = ClientHandshake::new().unwrap();
+        let server = ServerHandshake::new();
+
+        let (server, s0_s1_and_s2) = match server.process_bytes(&c0_and_c1) {
+            Ok(ServerC0C1Step::ReceivedC1 {
+                handshake,
+                response_bytes,
+            }) => (handshake, response_bytes),
+            _ => panic!("Expected server to receive c1"),
+        };
+
+        assert_eq!(s0_s1_and_s2.len(), 1 + RTMP_PACKET_SIZE * 2);
+
+        let (client, c2) = match client.process_bytes(&s0_s1_and_s2) {
+            Ok(ClientS0S1Step::Completed {
+                handshake,
+                response_bytes,
+            }) => (handshake, response_bytes),
+            _ => panic!("Expected client to complete"),
+        };
+
+        let server = match server.process_bytes(&c2) {
+            Ok(ServerC2Step::Completed(handshake)) => handshake,
+            _ => panic!("Expected server to complete"),
+        };
+
+        assert_eq!(client.completion().remaining_bytes.len(), 0);
+        assert_eq!(server.completion().remaining_bytes.len(), 0);
+    }
+
+    #[test]
+    fn client_moves_to_awaiting_s2_when_s2_not_yet_received() {
+        let (client, c0_and_c1) = ClientHandshake::new().unwrap();
+        let server = ServerHandshake::new();
+
+        let s0_s1_and_s2 = match server.process_bytes(&c0_and_c1) {
+            Ok(ServerC0C1Step::ReceivedC1 { response_bytes, .. }) => response_bytes,
+            _ => panic!("Expected server to receive c1"),
+        };
+
+        let (s0_and_s1, s2) = s0_s1_and_s2.split_at(1 + RTMP_PACKET_SIZE);
+        let client = match client.process_bytes(s0_and_s1) {
+            Ok(ClientS0S1Step::ReceivedS1 { handshake, .. }) => handshake,
+            _ => panic!("Expected client to receive s1"),
+        };
+
+        match client.process_bytes(s2) {
+            Ok(ClientS2Step::Completed(_)) => (),
+            _ => panic!("Expected client to complete"),
+        }
+    }
+
+    #[test]
+    fn error_when_starting_client_handshake_with_server_handshake() {
+        match ClientHandshake::from_handshake(Handshake::new(PeerType::Server)) {
+            Err(HandshakeError::InvalidInitialHandshake) => (),
+            _ => panic!("Expected invalid initial handshake error"),
+        }
+    }
+}
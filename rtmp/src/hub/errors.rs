@@ -0,0 +1,36 @@
+use chunk_io::ChunkSerializationError;
+use messages::MessageSerializationError;
+use thiserror::Error;
+
+/// Errors that can occur when connections join or publish into a `StreamHub`
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum StreamHubError {
+    /// Another connection is already publishing on the stream
+    #[error("Stream '{app_name}/{stream_key}' already has a publisher")]
+    StreamAlreadyPublished {
+        app_name: String,
+        stream_key: String,
+    },
+
+    /// The connection is already publishing or subscribed to a stream, and must leave it before
+    /// joining another
+    #[error("Connection {connection_id} has already joined a stream")]
+    ConnectionAlreadyJoined { connection_id: usize },
+
+    /// Media was published by a connection that isn't the publisher of any stream
+    #[error("Connection {connection_id} is not publishing a stream")]
+    NotPublishing { connection_id: usize },
+
+    /// The stream's metadata or sequence headers could not be turned into a message payload
+    /// while preparing them for a new subscriber
+    #[error(
+        "An error occurred while attempting to turn an RTMP message into a message payload: {0}"
+    )]
+    MessageSerializationError(#[from] MessageSerializationError),
+
+    /// The stream's metadata or sequence headers could not be serialized into chunks while
+    /// preparing them for a new subscriber
+    #[error("An error occurred serializing packets for a new subscriber: {0}")]
+    ChunkSerializationError(#[from] ChunkSerializationError),
+}
@@ -0,0 +1,266 @@
+use super::media::{is_audio_sequence_header, is_video_keyframe, is_video_sequence_header};
+use bytes::Bytes;
+use sessions::StreamMetadata;
+use std::sync::Arc;
+use time::RtmpTimestamp;
+
+/// An item held by a `GopCache`, in the order it should be sent to a new player
+#[derive(PartialEq, Debug, Clone)]
+pub enum CachedMedia {
+    Metadata(Arc<StreamMetadata>),
+    Video {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+    Audio {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+}
+
+/// Holds what a player needs to start playing a published stream immediately: the stream's
+/// metadata, its sequence headers, and every frame since the most recent keyframe (the current
+/// group of pictures).
+///
+/// Without this, players that join part way through a stream receive nothing they can display
+/// until the publisher sends its next keyframe, which can be several seconds away.  The trade
+/// off is that replaying the group of pictures puts new players up to a keyframe interval
+/// behind the publisher.
+///
+/// Frames are only cached while they fit within the maximum size.  If a group of pictures
+/// grows past it, caching stops until the next keyframe.
+///
+/// # Examples
+/// ```
+/// # extern crate bytes;
+/// # extern crate rml_rtmp;
+/// use bytes::Bytes;
+/// use rml_rtmp::hub::GopCache;
+/// use rml_rtmp::time::RtmpTimestamp;
+///
+/// # fn main() {
+/// let mut cache = GopCache::new(1024 * 1024);
+/// cache.add_video(Bytes::from(vec![0x17, 0, 0, 0, 0]), RtmpTimestamp::new(0));
+/// cache.add_video(Bytes::from(vec![0x17, 1, 0, 0, 0]), RtmpTimestamp::new(0));
+/// cache.add_video(Bytes::from(vec![0x27, 1, 0, 0, 0]), RtmpTimestamp::new(33));
+///
+/// // A new player receives the sequence header, keyframe, and the frame after it
+/// assert_eq!(cache.replay().len(), 3);
+///
+/// // The previous group of pictures is discarded at the next keyframe
+/// cache.add_video(Bytes::from(vec![0x17, 1, 0, 0, 0]), RtmpTimestamp::new(2000));
+/// assert_eq!(cache.replay().len(), 2);
+/// # }
+/// ```
+pub struct GopCache {
+    max_bytes: usize,
+    metadata: Option<Arc<StreamMetadata>>,
+    video_sequence_header: Option<CachedMedia>,
+    audio_sequence_header: Option<CachedMedia>,
+    frames: Vec<CachedMedia>,
+    frame_bytes: usize,
+}
+
+impl GopCache {
+    /// Creates an empty cache that holds up to the specified number of bytes of frames.
+    /// Metadata and sequence headers don't count towards this.  A maximum of zero only caches
+    /// metadata and sequence headers.
+    pub fn new(max_bytes: usize) -> GopCache {
+        GopCache {
+            max_bytes,
+            metadata: None,
+            video_sequence_header: None,
+            audio_sequence_header: None,
+            frames: Vec::new(),
+            frame_bytes: 0,
+        }
+    }
+
+    /// Changes the maximum number of bytes of frames to cache
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        if self.frame_bytes > max_bytes {
+            self.clear_frames();
+        }
+    }
+
+    /// Replaces the cached metadata
+    pub fn set_metadata(&mut self, metadata: Arc<StreamMetadata>) {
+        self.metadata = Some(metadata);
+    }
+
+    /// Adds video published on the stream.  Sequence headers replace the cached one, while
+    /// keyframes start a new group of pictures.
+    pub fn add_video(&mut self, data: Bytes, timestamp: RtmpTimestamp) {
+        if is_video_sequence_header(&data) {
+            // Frames encoded with the old decoder configuration can't be replayed after the new one
+            self.clear_frames();
+            self.video_sequence_header = Some(CachedMedia::Video { data, timestamp });
+            return;
+        }
+
+        if is_video_keyframe(&data) {
+            self.clear_frames();
+        } else if self.frames.is_empty() {
+            return;
+        }
+
+        let length = data.len();
+        self.add_frame(CachedMedia::Video { data, timestamp }, length);
+    }
+
+    /// Adds audio published on the stream.  Audio is only cached alongside a group of pictures,
+    /// since streams without video can start playing from any audio frame.
+    pub fn add_audio(&mut self, data: Bytes, timestamp: RtmpTimestamp) {
+        if is_audio_sequence_header(&data) {
+            self.audio_sequence_header = Some(CachedMedia::Audio { data, timestamp });
+            return;
+        }
+
+        if self.frames.is_empty() {
+            return;
+        }
+
+        let length = data.len();
+        self.add_frame(CachedMedia::Audio { data, timestamp }, length);
+    }
+
+    /// Returns everything a new player should be sent, in order
+    pub fn replay(&self) -> Vec<CachedMedia> {
+        let mut items = self.replay_headers();
+        items.extend(self.frames.iter().cloned());
+        items
+    }
+
+    /// Returns the metadata and sequence headers a new player should be sent before any frames
+    pub fn replay_headers(&self) -> Vec<CachedMedia> {
+        let mut items = Vec::with_capacity(self.frames.len() + 3);
+        if let Some(ref metadata) = self.metadata {
+            items.push(CachedMedia::Metadata(metadata.clone()));
+        }
+
+        items.extend(self.video_sequence_header.iter().cloned());
+        items.extend(self.audio_sequence_header.iter().cloned());
+        items
+    }
+
+    /// Returns the cached frames, which a new player should be sent after the headers
+    pub fn replay_frames(&self) -> Vec<CachedMedia> {
+        self.frames.clone()
+    }
+
+    /// Returns if a keyframe is cached, so a player sent the replay does not need to wait for
+    /// the next one
+    pub fn has_keyframe(&self) -> bool {
+        !self.frames.is_empty()
+    }
+
+    /// Returns the number of bytes of frames that are cached
+    pub fn frame_bytes(&self) -> usize {
+        self.frame_bytes
+    }
+
+    /// Removes everything from the cache, such as when the publisher has stopped
+    pub fn clear(&mut self) {
+        self.metadata = None;
+        self.video_sequence_header = None;
+        self.audio_sequence_header = None;
+        self.clear_frames();
+    }
+
+    fn add_frame(&mut self, frame: CachedMedia, length: usize) {
+        if self.frame_bytes + length > self.max_bytes {
+            self.clear_frames();
+            return;
+        }
+
+        self.frame_bytes += length;
+        self.frames.push(frame);
+    }
+
+    fn clear_frames(&mut self) {
+        self.frames.clear();
+        self.frame_bytes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(bytes: &[u8]) -> Bytes {
+        Bytes::copy_from_slice(bytes)
+    }
+
+    #[test]
+    fn frames_before_first_keyframe_are_not_cached() {
+        let mut cache = GopCache::new(1000);
+        cache.add_video(payload(&[0x27, 1]), RtmpTimestamp::new(0));
+        cache.add_audio(payload(&[0xaf, 1]), RtmpTimestamp::new(0));
+
+        assert!(cache.replay().is_empty());
+        assert!(!cache.has_keyframe());
+    }
+
+    #[test]
+    fn replay_starts_with_metadata_and_sequence_headers() {
+        let mut cache = GopCache::new(1000);
+        cache.add_video(payload(&[0x17, 1]), RtmpTimestamp::new(10));
+        cache.add_audio(payload(&[0xaf, 1]), RtmpTimestamp::new(20));
+        cache.add_audio(payload(&[0xaf, 0]), RtmpTimestamp::new(0));
+        cache.set_metadata(Arc::new(StreamMetadata::new()));
+
+        let items = cache.replay();
+
+        assert_eq!(
+            items[0],
+            CachedMedia::Metadata(Arc::new(StreamMetadata::new()))
+        );
+        assert_eq!(
+            items[1],
+            CachedMedia::Audio {
+                data: payload(&[0xaf, 0]),
+                timestamp: RtmpTimestamp::new(0)
+            }
+        );
+
+        assert_eq!(
+            items[2],
+            CachedMedia::Video {
+                data: payload(&[0x17, 1]),
+                timestamp: RtmpTimestamp::new(10)
+            }
+        );
+
+        assert_eq!(items.len(), 4);
+    }
+
+    #[test]
+    fn group_larger_than_maximum_is_not_cached_until_next_keyframe() {
+        let mut cache = GopCache::new(5);
+        cache.add_video(payload(&[0x17, 1, 0]), RtmpTimestamp::new(0));
+        cache.add_video(payload(&[0x27, 1, 0]), RtmpTimestamp::new(33));
+        cache.add_video(payload(&[0x27, 1]), RtmpTimestamp::new(66));
+
+        assert!(!cache.has_keyframe());
+        assert_eq!(cache.frame_bytes(), 0);
+
+        cache.add_video(payload(&[0x17, 1]), RtmpTimestamp::new(100));
+        assert_eq!(cache.replay().len(), 1);
+    }
+
+    #[test]
+    fn new_video_sequence_header_discards_group() {
+        let mut cache = GopCache::new(1000);
+        cache.add_video(payload(&[0x17, 1]), RtmpTimestamp::new(0));
+        cache.add_video(payload(&[0x17, 0, 1]), RtmpTimestamp::new(10));
+
+        assert_eq!(
+            cache.replay(),
+            vec![CachedMedia::Video {
+                data: payload(&[0x17, 0, 1]),
+                timestamp: RtmpTimestamp::new(10)
+            }]
+        );
+    }
+}
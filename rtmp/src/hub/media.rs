@@ -0,0 +1,60 @@
+//! Classification of audio and video payloads, based on the FLV tag headers that RTMP media
+//! messages start with.  Both the legacy and enhanced RTMP video headers are understood.
+
+const ENHANCED_VIDEO_FLAG: u8 = 0x80;
+const KEYFRAME: u8 = 1;
+const AVC_CODEC_ID: u8 = 7;
+const AAC_FORMAT: u8 = 10;
+
+/// Returns if the video payload contains decoder configuration instead of a frame
+pub fn is_video_sequence_header(data: &[u8]) -> bool {
+    if data.is_empty() {
+        return false;
+    }
+
+    if data[0] & ENHANCED_VIDEO_FLAG != 0 {
+        // The lower 4 bits are the packet type, where 0 is `SequenceStart`
+        return data[0] & 0x0f == 0;
+    }
+
+    data.len() >= 2 && data[0] & 0x0f == AVC_CODEC_ID && data[1] == 0
+}
+
+/// Returns if the video payload is a keyframe that playback can start from.  Sequence headers
+/// are not counted as keyframes.
+pub fn is_video_keyframe(data: &[u8]) -> bool {
+    !data.is_empty() && (data[0] >> 4) & 0x07 == KEYFRAME && !is_video_sequence_header(data)
+}
+
+/// Returns if the audio payload contains decoder configuration (an AAC sequence header)
+pub fn is_audio_sequence_header(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] >> 4 == AAC_FORMAT && data[1] == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn avc_payloads_are_classified() {
+        assert!(is_video_sequence_header(&[0x17, 0]));
+        assert!(!is_video_keyframe(&[0x17, 0]));
+        assert!(is_video_keyframe(&[0x17, 1]));
+        assert!(!is_video_keyframe(&[0x27, 1]));
+    }
+
+    #[test]
+    fn enhanced_payloads_are_classified() {
+        // Keyframe sequence start and coded frames for HEVC
+        assert!(is_video_sequence_header(&[0x90, b'h', b'v', b'c', b'1']));
+        assert!(is_video_keyframe(&[0x91, b'h', b'v', b'c', b'1']));
+        assert!(!is_video_keyframe(&[0xa1, b'h', b'v', b'c', b'1']));
+    }
+
+    #[test]
+    fn aac_sequence_header_is_classified() {
+        assert!(is_audio_sequence_header(&[0xaf, 0]));
+        assert!(!is_audio_sequence_header(&[0xaf, 1]));
+        assert!(!is_audio_sequence_header(&[0x2f, 0]));
+    }
+}
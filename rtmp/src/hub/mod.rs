@@ -0,0 +1,18 @@
+/*!
+This module contains the `StreamHub`, which routes media from publishers to the subscribers of
+their streams.
+
+Every server built on `ServerSession` needs to track which connection is publishing each stream,
+which connections are watching it, and what a player needs to be sent when it joins part way
+through a stream.  The hub takes care of this so applications only need to feed it the events
+raised by their sessions and send what it returns.
+*/
+
+mod errors;
+mod gop_cache;
+pub mod media;
+mod stream_hub;
+
+pub use self::errors::StreamHubError;
+pub use self::gop_cache::{CachedMedia, GopCache};
+pub use self::stream_hub::{PreparedJoin, StreamHub, StreamHubResult, SubscriberDropPolicy};
@@ -0,0 +1,892 @@
+use super::errors::StreamHubError;
+use super::gop_cache::{CachedMedia, GopCache};
+use super::media::{is_audio_sequence_header, is_video_keyframe, is_video_sequence_header};
+use bytes::Bytes;
+use chunk_io::PreparedPacket;
+use messages::RtmpMessage;
+use pipeline::MediaItem;
+#[cfg(feature = "server-session")]
+use sessions::ServerSessionEvent;
+use sessions::StreamMetadata;
+use std::collections::HashMap;
+use std::sync::Arc;
+use time::RtmpTimestamp;
+
+type StreamName = (String, String);
+
+/// How media is dropped for a subscriber the application has marked as congested, such as when
+/// its connection's send buffer is backing up
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SubscriberDropPolicy {
+    /// Everything is delivered, and it's up to the application to drop packets that are marked
+    /// as droppable
+    DeliverAll,
+
+    /// Audio and video inter-frames are skipped while congested, but keyframes are still
+    /// delivered
+    DropWhileCongested,
+
+    /// All media is skipped once congested, and delivery only resumes at the first keyframe
+    /// after the congestion has cleared.  This avoids the visual corruption of decoding frames
+    /// whose references were dropped.
+    WaitForKeyframe,
+}
+
+/// Media or notifications the hub has routed to a subscriber.  The `stream_id` is the one the
+/// subscriber is playing on, so these can be passed to the subscriber's `ServerSession` as is.
+#[derive(PartialEq, Debug, Clone)]
+pub enum StreamHubResult {
+    /// Metadata should be sent to the subscriber
+    SendMetadata {
+        subscriber_id: usize,
+        stream_id: u32,
+        metadata: Arc<StreamMetadata>,
+    },
+
+    /// Video data should be sent to the subscriber
+    SendVideoData {
+        subscriber_id: usize,
+        stream_id: u32,
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+        can_be_dropped: bool,
+    },
+
+    /// Audio data should be sent to the subscriber
+    SendAudioData {
+        subscriber_id: usize,
+        stream_id: u32,
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+        can_be_dropped: bool,
+    },
+
+    /// The publisher of the stream the subscriber is playing has left.  The subscriber stays
+    /// joined and will receive media again if another connection starts publishing.
+    PublishingFinished {
+        subscriber_id: usize,
+        stream_id: u32,
+    },
+}
+
+/// What a subscriber joining with `StreamHub::join_as_subscriber_prepared()` should be sent, in
+/// order
+#[derive(PartialEq, Debug, Clone)]
+pub struct PreparedJoin {
+    /// The stream's metadata and sequence headers, already serialized for the subscriber's
+    /// stream id and chunk size.  These are sent with the subscriber's
+    /// `ServerSession::send_prepared_packet()`.
+    pub packets: Vec<PreparedPacket>,
+
+    /// The cached frames, which are sent after the packets
+    pub results: Vec<StreamHubResult>,
+}
+
+enum Membership {
+    Publisher(StreamName),
+    Subscriber(StreamName),
+}
+
+struct Subscriber {
+    stream_id: u32,
+    drop_policy: SubscriberDropPolicy,
+    is_congested: bool,
+    has_received_keyframe: bool,
+    is_skipping: bool,
+}
+
+impl Subscriber {
+    /// Decides if a video frame should be delivered, updating the keyframe state
+    fn accepts_video(&mut self, is_keyframe: bool) -> bool {
+        if !self.has_received_keyframe {
+            if !is_keyframe {
+                return false;
+            }
+
+            self.has_received_keyframe = true;
+        }
+
+        match self.drop_policy {
+            SubscriberDropPolicy::DeliverAll => true,
+            SubscriberDropPolicy::DropWhileCongested => is_keyframe || !self.is_congested,
+            SubscriberDropPolicy::WaitForKeyframe => {
+                if self.is_congested {
+                    self.is_skipping = true;
+                    return false;
+                }
+
+                if self.is_skipping && !is_keyframe {
+                    return false;
+                }
+
+                self.is_skipping = false;
+                true
+            }
+        }
+    }
+
+    fn accepts_audio(&self) -> bool {
+        match self.drop_policy {
+            SubscriberDropPolicy::DeliverAll => true,
+            SubscriberDropPolicy::DropWhileCongested => !self.is_congested,
+            SubscriberDropPolicy::WaitForKeyframe => !self.is_congested && !self.is_skipping,
+        }
+    }
+}
+
+struct HubStream {
+    publisher_id: Option<usize>,
+    subscribers: HashMap<usize, Subscriber>,
+    cache: GopCache,
+
+    // Keyed by the stream id subscribers play on and their chunk size, as both are part of the
+    // serialized chunks
+    prepared_joins: HashMap<(u32, u32), Vec<PreparedPacket>>,
+}
+
+impl HubStream {
+    fn new(gop_cache_max_bytes: usize) -> HubStream {
+        HubStream {
+            publisher_id: None,
+            subscribers: HashMap::new(),
+            cache: GopCache::new(gop_cache_max_bytes),
+            prepared_joins: HashMap::new(),
+        }
+    }
+
+    /// Returns the cached metadata and sequence headers serialized for a new subscriber,
+    /// serializing them only if no earlier subscriber used the same stream id and chunk size
+    fn prepare_join_packets(
+        &mut self,
+        stream_id: u32,
+        chunk_size: u32,
+    ) -> Result<Vec<PreparedPacket>, StreamHubError> {
+        if let Some(packets) = self.prepared_joins.get(&(stream_id, chunk_size)) {
+            return Ok(packets.clone());
+        }
+
+        let mut packets = Vec::new();
+        for item in self.cache.replay_headers() {
+            // Metadata has no timestamp of its own, and the subscriber's session epoch can't be
+            // shared between subscribers
+            let (message, timestamp) = match item {
+                CachedMedia::Metadata(metadata) => {
+                    (metadata.to_metadata_message(), RtmpTimestamp::new(0))
+                }
+
+                CachedMedia::Video { data, timestamp } => {
+                    (RtmpMessage::VideoData { data }, timestamp)
+                }
+
+                CachedMedia::Audio { data, timestamp } => {
+                    (RtmpMessage::AudioData { data }, timestamp)
+                }
+            };
+
+            let payload = message.into_message_payload(timestamp, stream_id)?;
+            packets.push(PreparedPacket::new(&payload, chunk_size)?);
+        }
+
+        self.prepared_joins
+            .insert((stream_id, chunk_size), packets.clone());
+
+        Ok(packets)
+    }
+}
+
+/// Routes media from the publisher of each stream to all of the stream's subscribers.
+///
+/// Streams are identified by their RTMP application name and stream key, and each can have one
+/// publisher and any number of subscribers.  Connections are identified by ids the application
+/// chooses, and can only publish or subscribe to one stream at a time.
+///
+/// The most recent metadata and sequence headers of each stream are kept, so subscribers that
+/// join part way through receive them before any media.  Video for new subscribers starts at
+/// the next keyframe, unless the GOP cache has been enabled with `set_gop_cache_max_bytes()`,
+/// in which case they are sent the current group of pictures and start playing immediately.
+///
+/// The hub does no I/O and doesn't own any sessions.  Every result says which subscriber it is
+/// for, and the application sends it with that subscriber's `ServerSession`.
+///
+/// # Examples
+/// ```
+/// # extern crate bytes;
+/// # extern crate rml_rtmp;
+/// use bytes::Bytes;
+/// use rml_rtmp::hub::{StreamHub, StreamHubResult, SubscriberDropPolicy};
+/// use rml_rtmp::time::RtmpTimestamp;
+///
+/// # fn main() {
+/// let mut hub = StreamHub::new();
+/// hub.join_as_publisher(1, "live", "key").unwrap();
+///
+/// let sequence_header = Bytes::from(vec![0x17, 0, 0, 0, 0]);
+/// hub.publish_video_data(1, sequence_header.clone(), RtmpTimestamp::new(0)).unwrap();
+///
+/// // The subscriber receives the sequence header as soon as it joins
+/// let results = hub
+///     .join_as_subscriber(2, "live", "key", 1, SubscriberDropPolicy::DeliverAll)
+///     .unwrap();
+///
+/// match results[0] {
+///     StreamHubResult::SendVideoData { subscriber_id, ref data, .. } => {
+///         assert_eq!(subscriber_id, 2);
+///         assert_eq!(data, &sequence_header);
+///     }
+///
+///     ref x => panic!("Unexpected result: {:?}", x),
+/// }
+/// # }
+/// ```
+pub struct StreamHub {
+    streams: HashMap<StreamName, HubStream>,
+    memberships: HashMap<usize, Membership>,
+    gop_cache_max_bytes: usize,
+}
+
+impl StreamHub {
+    /// Creates a hub without any streams
+    pub fn new() -> StreamHub {
+        StreamHub {
+            streams: HashMap::new(),
+            memberships: HashMap::new(),
+            gop_cache_max_bytes: 0,
+        }
+    }
+
+    /// Sets how many bytes of frames are cached for each stream, so they can be replayed to
+    /// new subscribers.  This is zero by default, which disables caching frames (metadata and
+    /// sequence headers are always cached).
+    pub fn set_gop_cache_max_bytes(&mut self, max_bytes: usize) {
+        self.gop_cache_max_bytes = max_bytes;
+        for stream in self.streams.values_mut() {
+            stream.cache.set_max_bytes(max_bytes);
+        }
+    }
+
+    /// Makes the connection the publisher of the stream.  This fails if the stream already has
+    /// a publisher, so the application can reject the publish request.
+    pub fn join_as_publisher(
+        &mut self,
+        connection_id: usize,
+        app_name: &str,
+        stream_key: &str,
+    ) -> Result<(), StreamHubError> {
+        self.ensure_not_joined(connection_id)?;
+
+        let name = (app_name.to_string(), stream_key.to_string());
+        let max_bytes = self.gop_cache_max_bytes;
+        let stream = self
+            .streams
+            .entry(name.clone())
+            .or_insert_with(|| HubStream::new(max_bytes));
+        if stream.publisher_id.is_some() {
+            return Err(StreamHubError::StreamAlreadyPublished {
+                app_name: name.0,
+                stream_key: name.1,
+            });
+        }
+
+        stream.publisher_id = Some(connection_id);
+        self.memberships
+            .insert(connection_id, Membership::Publisher(name));
+
+        Ok(())
+    }
+
+    /// Subscribes the connection to the stream, which it is playing on the specified stream id.
+    /// The stream does not need to have a publisher yet.  The returned results replay the
+    /// stream's current metadata, sequence headers, and cached frames to the new subscriber.
+    pub fn join_as_subscriber(
+        &mut self,
+        connection_id: usize,
+        app_name: &str,
+        stream_key: &str,
+        stream_id: u32,
+        drop_policy: SubscriberDropPolicy,
+    ) -> Result<Vec<StreamHubResult>, StreamHubError> {
+        self.ensure_not_joined(connection_id)?;
+
+        let name = (app_name.to_string(), stream_key.to_string());
+        let stream = self.add_subscriber(connection_id, name, stream_id, drop_policy);
+        let results = replay_results(stream.cache.replay(), connection_id, stream_id);
+
+        Ok(results)
+    }
+
+    /// Subscribes the connection to the stream like `join_as_subscriber()`, but returns the
+    /// stream's metadata and sequence headers already serialized into chunks of the specified
+    /// size, which should be the subscriber session's `outbound_chunk_size()`.
+    ///
+    /// The packets are serialized once and shared by every subscriber that joins with the same
+    /// stream id and chunk size, until the publisher sends new metadata or sequence headers.  This
+    /// saves serializing the same messages over and over when many players join a stream at once.
+    pub fn join_as_subscriber_prepared(
+        &mut self,
+        connection_id: usize,
+        app_name: &str,
+        stream_key: &str,
+        stream_id: u32,
+        drop_policy: SubscriberDropPolicy,
+        chunk_size: u32,
+    ) -> Result<PreparedJoin, StreamHubError> {
+        self.ensure_not_joined(connection_id)?;
+
+        // Packets are prepared before the connection is added, so it isn't left subscribed if
+        // they can't be
+        let name = (app_name.to_string(), stream_key.to_string());
+        let packets = match self.streams.get_mut(&name) {
+            Some(stream) => stream.prepare_join_packets(stream_id, chunk_size)?,
+            None => Vec::new(),
+        };
+
+        let stream = self.add_subscriber(connection_id, name, stream_id, drop_policy);
+        let results = replay_results(stream.cache.replay_frames(), connection_id, stream_id);
+
+        Ok(PreparedJoin { packets, results })
+    }
+
+    /// Removes the connection from whichever stream it joined, such as when it has finished
+    /// playing or publishing or has disconnected.  When a publisher leaves, the stream's cache
+    /// is cleared and its subscribers are notified.
+    pub fn leave(&mut self, connection_id: usize) -> Vec<StreamHubResult> {
+        let (name, was_publisher) = match self.memberships.remove(&connection_id) {
+            Some(Membership::Publisher(name)) => (name, true),
+            Some(Membership::Subscriber(name)) => (name, false),
+            None => return Vec::new(),
+        };
+
+        let mut results = Vec::new();
+        let is_empty = match self.streams.get_mut(&name) {
+            Some(stream) => {
+                if was_publisher {
+                    stream.publisher_id = None;
+                    stream.cache.clear();
+                    stream.prepared_joins.clear();
+
+                    for (subscriber_id, subscriber) in stream.subscribers.iter_mut() {
+                        subscriber.has_received_keyframe = false;
+                        subscriber.is_skipping = false;
+                        results.push(StreamHubResult::PublishingFinished {
+                            subscriber_id: *subscriber_id,
+                            stream_id: subscriber.stream_id,
+                        });
+                    }
+                } else {
+                    stream.subscribers.remove(&connection_id);
+                }
+
+                stream.publisher_id.is_none() && stream.subscribers.is_empty()
+            }
+
+            None => false,
+        };
+
+        if is_empty {
+            self.streams.remove(&name);
+        }
+
+        results
+    }
+
+    /// Marks whether a subscriber's connection is congested, which decides what is dropped
+    /// based on the subscriber's drop policy
+    pub fn set_subscriber_congested(&mut self, connection_id: usize, is_congested: bool) {
+        let name = match self.memberships.get(&connection_id) {
+            Some(Membership::Subscriber(name)) => name,
+            _ => return,
+        };
+
+        let subscriber = self
+            .streams
+            .get_mut(name)
+            .and_then(|stream| stream.subscribers.get_mut(&connection_id));
+
+        if let Some(subscriber) = subscriber {
+            subscriber.is_congested = is_congested;
+        }
+    }
+
+    /// Routes an item from a media pipeline, as if it had been raised by the publisher's
+    /// session.  The end of the stream removes the publisher from the hub.
+    pub fn publish_media(
+        &mut self,
+        connection_id: usize,
+        item: MediaItem,
+    ) -> Result<Vec<StreamHubResult>, StreamHubError> {
+        match item {
+            MediaItem::Metadata(metadata) => self.publish_metadata(connection_id, metadata),
+            MediaItem::Video { data, timestamp } => {
+                self.publish_video_data(connection_id, data, timestamp)
+            }
+
+            MediaItem::Audio { data, timestamp } => {
+                self.publish_audio_data(connection_id, data, timestamp)
+            }
+
+            MediaItem::EndOfStream => Ok(self.leave(connection_id)),
+        }
+    }
+
+    /// Routes new metadata from a publisher to the stream's subscribers.  The metadata is shared
+    /// with every subscriber instead of being copied for each of them.
+    pub fn publish_metadata(
+        &mut self,
+        connection_id: usize,
+        metadata: Arc<StreamMetadata>,
+    ) -> Result<Vec<StreamHubResult>, StreamHubError> {
+        let stream = self.get_published_stream(connection_id)?;
+        stream.cache.set_metadata(metadata.clone());
+        stream.prepared_joins.clear();
+
+        let results = stream
+            .subscribers
+            .iter()
+            .map(
+                |(subscriber_id, subscriber)| StreamHubResult::SendMetadata {
+                    subscriber_id: *subscriber_id,
+                    stream_id: subscriber.stream_id,
+                    metadata: metadata.clone(),
+                },
+            )
+            .collect();
+
+        Ok(results)
+    }
+
+    /// Routes video data from a publisher to the stream's subscribers
+    pub fn publish_video_data(
+        &mut self,
+        connection_id: usize,
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    ) -> Result<Vec<StreamHubResult>, StreamHubError> {
+        let stream = self.get_published_stream(connection_id)?;
+        let is_sequence_header = is_video_sequence_header(&data);
+        let is_keyframe = is_video_keyframe(&data);
+        stream.cache.add_video(data.clone(), timestamp);
+        if is_sequence_header {
+            stream.prepared_joins.clear();
+        }
+
+        let mut results = Vec::new();
+        for (subscriber_id, subscriber) in stream.subscribers.iter_mut() {
+            if !is_sequence_header && !subscriber.accepts_video(is_keyframe) {
+                continue;
+            }
+
+            results.push(StreamHubResult::SendVideoData {
+                subscriber_id: *subscriber_id,
+                stream_id: subscriber.stream_id,
+                data: data.clone(),
+                timestamp,
+                can_be_dropped: !is_sequence_header && !is_keyframe,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Routes audio data from a publisher to the stream's subscribers
+    pub fn publish_audio_data(
+        &mut self,
+        connection_id: usize,
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    ) -> Result<Vec<StreamHubResult>, StreamHubError> {
+        let stream = self.get_published_stream(connection_id)?;
+        let is_sequence_header = is_audio_sequence_header(&data);
+        stream.cache.add_audio(data.clone(), timestamp);
+        if is_sequence_header {
+            stream.prepared_joins.clear();
+        }
+
+        let results = stream
+            .subscribers
+            .iter()
+            .filter(|&(_, subscriber)| is_sequence_header || subscriber.accepts_audio())
+            .map(
+                |(subscriber_id, subscriber)| StreamHubResult::SendAudioData {
+                    subscriber_id: *subscriber_id,
+                    stream_id: subscriber.stream_id,
+                    data: data.clone(),
+                    timestamp,
+                    can_be_dropped: !is_sequence_header,
+                },
+            )
+            .collect();
+
+        Ok(results)
+    }
+
+    /// Routes the metadata and media in an event raised by a publisher's `ServerSession`, and
+    /// removes the publisher from the hub when it finishes publishing.  Other events are
+    /// ignored.
+    #[cfg(feature = "server-session")]
+    pub fn handle_publisher_event(
+        &mut self,
+        connection_id: usize,
+        event: &ServerSessionEvent,
+    ) -> Result<Vec<StreamHubResult>, StreamHubError> {
+        match MediaItem::from_server_event(event) {
+            Some(item) => self.publish_media(connection_id, item),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns if the stream currently has a publisher
+    pub fn has_publisher(&self, app_name: &str, stream_key: &str) -> bool {
+        self.get_stream(app_name, stream_key)
+            .is_some_and(|stream| stream.publisher_id.is_some())
+    }
+
+    /// Returns the number of connections subscribed to the stream
+    pub fn subscriber_count(&self, app_name: &str, stream_key: &str) -> usize {
+        self.get_stream(app_name, stream_key)
+            .map_or(0, |stream| stream.subscribers.len())
+    }
+
+    /// Returns the id of the connection publishing the stream, if it has a publisher
+    pub fn publisher_id(&self, app_name: &str, stream_key: &str) -> Option<usize> {
+        self.get_stream(app_name, stream_key)
+            .and_then(|stream| stream.publisher_id)
+    }
+
+    /// Returns the ids of the connections subscribed to the stream
+    pub fn subscriber_ids(&self, app_name: &str, stream_key: &str) -> Vec<usize> {
+        self.get_stream(app_name, stream_key)
+            .map_or_else(Vec::new, |stream| {
+                stream.subscribers.keys().cloned().collect()
+            })
+    }
+
+    /// Returns the application name and stream key of every stream that has a publisher or
+    /// subscribers
+    pub fn stream_names(&self) -> Vec<(String, String)> {
+        self.streams.keys().cloned().collect()
+    }
+
+    fn get_stream(&self, app_name: &str, stream_key: &str) -> Option<&HubStream> {
+        self.streams
+            .get(&(app_name.to_string(), stream_key.to_string()))
+    }
+
+    fn get_published_stream(
+        &mut self,
+        connection_id: usize,
+    ) -> Result<&mut HubStream, StreamHubError> {
+        let stream = match self.memberships.get(&connection_id) {
+            Some(Membership::Publisher(name)) => self.streams.get_mut(name),
+            _ => None,
+        };
+
+        stream.ok_or(StreamHubError::NotPublishing { connection_id })
+    }
+
+    fn add_subscriber(
+        &mut self,
+        connection_id: usize,
+        name: StreamName,
+        stream_id: u32,
+        drop_policy: SubscriberDropPolicy,
+    ) -> &HubStream {
+        let max_bytes = self.gop_cache_max_bytes;
+        self.memberships
+            .insert(connection_id, Membership::Subscriber(name.clone()));
+
+        let stream = self
+            .streams
+            .entry(name)
+            .or_insert_with(|| HubStream::new(max_bytes));
+        let subscriber = Subscriber {
+            stream_id,
+            drop_policy,
+            is_congested: false,
+            has_received_keyframe: stream.cache.has_keyframe(),
+            is_skipping: false,
+        };
+
+        stream.subscribers.insert(connection_id, subscriber);
+        stream
+    }
+
+    fn ensure_not_joined(&self, connection_id: usize) -> Result<(), StreamHubError> {
+        if self.memberships.contains_key(&connection_id) {
+            return Err(StreamHubError::ConnectionAlreadyJoined { connection_id });
+        }
+
+        Ok(())
+    }
+}
+
+fn replay_results(
+    items: Vec<CachedMedia>,
+    subscriber_id: usize,
+    stream_id: u32,
+) -> Vec<StreamHubResult> {
+    items
+        .into_iter()
+        .map(|item| match item {
+            CachedMedia::Metadata(metadata) => StreamHubResult::SendMetadata {
+                subscriber_id,
+                stream_id,
+                metadata,
+            },
+
+            CachedMedia::Video { data, timestamp } => StreamHubResult::SendVideoData {
+                subscriber_id,
+                stream_id,
+                data,
+                timestamp,
+                can_be_dropped: false,
+            },
+
+            CachedMedia::Audio { data, timestamp } => StreamHubResult::SendAudioData {
+                subscriber_id,
+                stream_id,
+                data,
+                timestamp,
+                can_be_dropped: false,
+            },
+        })
+        .collect()
+}
+
+impl Default for StreamHub {
+    fn default() -> Self {
+        StreamHub::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEQUENCE_HEADER: [u8; 5] = [0x17, 0, 0, 0, 0];
+    const KEYFRAME: [u8; 5] = [0x17, 1, 0, 0, 0];
+    const INTERFRAME: [u8; 5] = [0x27, 1, 0, 0, 0];
+
+    fn video(hub: &mut StreamHub, data: &[u8]) -> Vec<StreamHubResult> {
+        hub.publish_video_data(1, Bytes::copy_from_slice(data), RtmpTimestamp::new(0))
+            .unwrap()
+    }
+
+    fn received_video(results: &[StreamHubResult], subscriber: usize) -> Vec<Bytes> {
+        results
+            .iter()
+            .filter_map(|result| match *result {
+                StreamHubResult::SendVideoData {
+                    subscriber_id,
+                    ref data,
+                    ..
+                } if subscriber_id == subscriber => Some(data.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn published_hub() -> StreamHub {
+        let mut hub = StreamHub::new();
+        hub.join_as_publisher(1, "live", "key").unwrap();
+        hub
+    }
+
+    #[test]
+    fn second_publisher_is_rejected() {
+        let mut hub = published_hub();
+
+        match hub.join_as_publisher(2, "live", "key") {
+            Err(StreamHubError::StreamAlreadyPublished { .. }) => (),
+            x => panic!(
+                "Expected stream already published error, instead got {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn late_subscriber_receives_cached_headers_and_starts_at_keyframe() {
+        let mut hub = published_hub();
+        let mut metadata = StreamMetadata::new();
+        metadata.video_width = Some(1280);
+        hub.publish_metadata(1, Arc::new(metadata)).unwrap();
+        video(&mut hub, &SEQUENCE_HEADER);
+        video(&mut hub, &KEYFRAME);
+
+        let results = hub
+            .join_as_subscriber(2, "live", "key", 5, SubscriberDropPolicy::DeliverAll)
+            .unwrap();
+
+        let interframe_results = video(&mut hub, &INTERFRAME);
+        let keyframe_results = video(&mut hub, &KEYFRAME);
+
+        match results[0] {
+            StreamHubResult::SendMetadata {
+                stream_id,
+                ref metadata,
+                ..
+            } => {
+                assert_eq!(stream_id, 5);
+                assert_eq!(metadata.video_width, Some(1280));
+            }
+
+            ref x => panic!("Expected metadata, instead got {:?}", x),
+        }
+
+        assert_eq!(received_video(&results, 2), vec![&SEQUENCE_HEADER[..]]);
+        assert!(interframe_results.is_empty());
+        assert_eq!(received_video(&keyframe_results, 2), vec![&KEYFRAME[..]]);
+    }
+
+    #[test]
+    fn gop_cache_lets_late_subscriber_start_without_waiting_for_keyframe() {
+        let mut hub = published_hub();
+        hub.set_gop_cache_max_bytes(1024);
+        video(&mut hub, &SEQUENCE_HEADER);
+        video(&mut hub, &KEYFRAME);
+        video(&mut hub, &INTERFRAME);
+
+        let results = hub
+            .join_as_subscriber(2, "live", "key", 1, SubscriberDropPolicy::DeliverAll)
+            .unwrap();
+
+        let interframe_results = video(&mut hub, &INTERFRAME);
+
+        assert_eq!(
+            received_video(&results, 2),
+            vec![&SEQUENCE_HEADER[..], &KEYFRAME[..], &INTERFRAME[..]]
+        );
+
+        assert_eq!(
+            received_video(&interframe_results, 2),
+            vec![&INTERFRAME[..]]
+        );
+    }
+
+    #[test]
+    fn wait_for_keyframe_policy_resumes_at_keyframe_after_congestion() {
+        let mut hub = published_hub();
+        hub.join_as_subscriber(2, "live", "key", 1, SubscriberDropPolicy::WaitForKeyframe)
+            .unwrap();
+
+        hub.join_as_subscriber(3, "live", "key", 1, SubscriberDropPolicy::DeliverAll)
+            .unwrap();
+
+        video(&mut hub, &KEYFRAME);
+        hub.set_subscriber_congested(2, true);
+        let congested_results = video(&mut hub, &INTERFRAME);
+        hub.set_subscriber_congested(2, false);
+        let skipped_results = video(&mut hub, &INTERFRAME);
+        let resumed_results = video(&mut hub, &KEYFRAME);
+
+        assert!(received_video(&congested_results, 2).is_empty());
+        assert_eq!(received_video(&congested_results, 3).len(), 1);
+        assert!(received_video(&skipped_results, 2).is_empty());
+        assert_eq!(received_video(&resumed_results, 2), vec![&KEYFRAME[..]]);
+    }
+
+    #[test]
+    fn drop_while_congested_policy_still_delivers_keyframes() {
+        let mut hub = published_hub();
+        hub.join_as_subscriber(
+            2,
+            "live",
+            "key",
+            1,
+            SubscriberDropPolicy::DropWhileCongested,
+        )
+        .unwrap();
+
+        video(&mut hub, &KEYFRAME);
+        hub.set_subscriber_congested(2, true);
+
+        assert!(video(&mut hub, &INTERFRAME).is_empty());
+        assert_eq!(received_video(&video(&mut hub, &KEYFRAME), 2).len(), 1);
+    }
+
+    #[test]
+    fn publisher_leaving_notifies_subscribers_and_clears_cache() {
+        let mut hub = published_hub();
+        video(&mut hub, &SEQUENCE_HEADER);
+        hub.join_as_subscriber(2, "live", "key", 7, SubscriberDropPolicy::DeliverAll)
+            .unwrap();
+
+        let results = hub.leave(1);
+
+        let expected = StreamHubResult::PublishingFinished {
+            subscriber_id: 2,
+            stream_id: 7,
+        };
+
+        assert_eq!(results, vec![expected]);
+        assert!(!hub.has_publisher("live", "key"));
+
+        hub.join_as_publisher(1, "live", "key").unwrap();
+        hub.leave(2);
+        let results = hub
+            .join_as_subscriber(2, "live", "key", 7, SubscriberDropPolicy::DeliverAll)
+            .unwrap();
+
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn stream_is_removed_once_everyone_leaves() {
+        let mut hub = published_hub();
+        hub.join_as_subscriber(2, "live", "key", 1, SubscriberDropPolicy::DeliverAll)
+            .unwrap();
+
+        hub.leave(1);
+        assert_eq!(hub.subscriber_count("live", "key"), 1);
+
+        hub.leave(2);
+        assert!(hub.streams.is_empty());
+    }
+
+    #[test]
+    fn media_from_non_publisher_is_rejected() {
+        let mut hub = published_hub();
+        let result = hub.publish_video_data(9, Bytes::from(vec![1]), RtmpTimestamp::new(0));
+
+        match result {
+            Err(StreamHubError::NotPublishing { connection_id: 9 }) => (),
+            x => panic!("Expected not publishing error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn prepared_join_packets_are_shared_until_headers_change() {
+        let mut hub = published_hub();
+        hub.publish_metadata(1, Arc::new(StreamMetadata::new()))
+            .unwrap();
+        video(&mut hub, &SEQUENCE_HEADER);
+        video(&mut hub, &KEYFRAME);
+
+        let policy = SubscriberDropPolicy::DeliverAll;
+        let first = hub
+            .join_as_subscriber_prepared(2, "live", "key", 1, policy, 4096)
+            .unwrap();
+        let second = hub
+            .join_as_subscriber_prepared(3, "live", "key", 1, policy, 4096)
+            .unwrap();
+        let other_size = hub
+            .join_as_subscriber_prepared(4, "live", "key", 1, policy, 128)
+            .unwrap();
+
+        assert_eq!(first.packets.len(), 2);
+        assert!(first.results.is_empty());
+        assert_eq!(first.packets, second.packets);
+        assert_eq!(
+            first.packets[1].bytes().as_ptr(),
+            second.packets[1].bytes().as_ptr()
+        );
+        assert_eq!(other_size.packets[1].max_chunk_size(), 128);
+
+        video(&mut hub, &[0x17, 0, 0, 0, 0, 1]);
+        let after_change = hub
+            .join_as_subscriber_prepared(5, "live", "key", 1, policy, 4096)
+            .unwrap();
+
+        assert_ne!(first.packets[1].bytes(), after_change.packets[1].bytes());
+    }
+}
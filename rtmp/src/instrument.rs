@@ -0,0 +1,234 @@
+//! Optional instrumentation with the `tracing` and `log` crates.  When both features are
+//! disabled the macro and span here compile down to nothing, so call sites don't need their own
+//! `#[cfg]` attributes.
+//!
+//! `log` has no spans, so entering a `SessionSpan` makes it the current session of the thread
+//! instead, and every log line written while it's entered starts with the session's id,
+//! application name, and stream key.
+
+#[cfg(feature = "log")]
+use std::cell::RefCell;
+#[cfg(feature = "log")]
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "log")]
+use std::sync::Arc;
+
+/// Emits a `tracing` event and/or a `log` record at the specified level, using the same format
+/// string syntax as `format!()`
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::$level!($($arg)+);
+
+        #[cfg(feature = "log")]
+        ::log::$level!("{}{}", ::instrument::CurrentSession, format_args!($($arg)+));
+
+        #[cfg(not(any(feature = "tracing", feature = "log")))]
+        {
+            if false {
+                let _ = format!($($arg)+);
+            }
+        }
+    }};
+}
+
+static NEXT_SESSION_ID: AtomicUsize = AtomicUsize::new(1);
+
+#[cfg(feature = "log")]
+thread_local! {
+    static CURRENT_SESSION: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/// Writes the prefix of the session entered on this thread, if any, for log lines
+#[cfg(feature = "log")]
+pub struct CurrentSession;
+
+#[cfg(feature = "log")]
+impl fmt::Display for CurrentSession {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        CURRENT_SESSION.with(|current| match *current.borrow() {
+            Some(ref prefix) => formatter.write_str(prefix),
+            None => Ok(()),
+        })
+    }
+}
+
+/// The guard returned when entering a `SessionSpan`, which exits the span when dropped
+pub struct SpanGuard {
+    #[cfg(feature = "tracing")]
+    _span: ::tracing::span::EnteredSpan,
+
+    #[cfg(feature = "log")]
+    previous_session: Option<Arc<str>>,
+}
+
+#[cfg(feature = "log")]
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        let previous = self.previous_session.take();
+        CURRENT_SESSION.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// The span covering everything a session does.  It's created as a child of the span that's
+/// current when the session is created, so applications that create a span for each
+/// connection will see session events nested under it.
+pub struct SessionSpan {
+    id: u64,
+
+    #[cfg(feature = "tracing")]
+    span: ::tracing::Span,
+
+    #[cfg(feature = "log")]
+    side: &'static str,
+
+    #[cfg(feature = "log")]
+    app_name: Option<String>,
+
+    #[cfg(feature = "log")]
+    stream_key: Option<String>,
+
+    #[cfg(feature = "log")]
+    log_prefix: Arc<str>,
+}
+
+impl SessionSpan {
+    pub fn new(_side: &'static str) -> SessionSpan {
+        let id = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed) as u64;
+
+        #[allow(unused_mut)]
+        let mut span = SessionSpan {
+            id,
+
+            #[cfg(feature = "tracing")]
+            span: ::tracing::info_span!(
+                "rtmp_session",
+                session_id = id,
+                side = _side,
+                app_name = ::tracing::field::Empty,
+                stream_key = ::tracing::field::Empty,
+            ),
+
+            #[cfg(feature = "log")]
+            side: _side,
+
+            #[cfg(feature = "log")]
+            app_name: None,
+
+            #[cfg(feature = "log")]
+            stream_key: None,
+
+            #[cfg(feature = "log")]
+            log_prefix: Arc::from(""),
+        };
+
+        #[cfg(feature = "log")]
+        span.update_log_prefix();
+
+        span
+    }
+
+    /// The id that tags the session's events, unique among the sessions of this process
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Enters the span until the returned guard is dropped
+    pub fn enter(&self) -> SpanGuard {
+        SpanGuard {
+            #[cfg(feature = "tracing")]
+            _span: self.span.clone().entered(),
+
+            #[cfg(feature = "log")]
+            previous_session: CURRENT_SESSION
+                .with(|current| current.borrow_mut().replace(self.log_prefix.clone())),
+        }
+    }
+
+    pub fn record_app_name(&mut self, _app_name: &str) {
+        #[cfg(feature = "tracing")]
+        self.span.record("app_name", _app_name);
+
+        #[cfg(feature = "log")]
+        {
+            self.app_name = Some(_app_name.to_string());
+            self.update_log_prefix();
+        }
+    }
+
+    pub fn record_stream_key(&mut self, _stream_key: &str) {
+        #[cfg(feature = "tracing")]
+        self.span.record("stream_key", _stream_key);
+
+        #[cfg(feature = "log")]
+        {
+            self.stream_key = Some(_stream_key.to_string());
+            self.update_log_prefix();
+        }
+    }
+
+    /// Rebuilds the prefix log lines start with, replacing the old one if the session is the
+    /// one currently entered on this thread
+    #[cfg(feature = "log")]
+    fn update_log_prefix(&mut self) {
+        let mut prefix = format!("[{} session {}", self.side, self.id);
+        if let Some(ref app_name) = self.app_name {
+            prefix.push_str(" app=");
+            prefix.push_str(app_name);
+        }
+
+        if let Some(ref stream_key) = self.stream_key {
+            prefix.push_str(" stream_key=");
+            prefix.push_str(stream_key);
+        }
+
+        prefix.push_str("] ");
+
+        let old_prefix = ::std::mem::replace(&mut self.log_prefix, Arc::from(prefix));
+        CURRENT_SESSION.with(|current| {
+            let mut current = current.borrow_mut();
+            let is_entered = match *current {
+                Some(ref entered) => Arc::ptr_eq(entered, &old_prefix),
+                None => false,
+            };
+
+            if is_entered {
+                *current = Some(self.log_prefix.clone());
+            }
+        });
+    }
+}
+
+#[cfg(all(test, feature = "log"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_prefix_follows_entered_session() {
+        let mut span = SessionSpan::new("server");
+        let other_span = SessionSpan::new("client");
+        assert_eq!(CurrentSession.to_string(), "");
+
+        let guard = span.enter();
+        span.record_app_name("live");
+        span.record_stream_key("abc");
+        assert_eq!(
+            CurrentSession.to_string(),
+            format!("[server session {} app=live stream_key=abc] ", span.id())
+        );
+
+        {
+            let _other_guard = other_span.enter();
+            assert_eq!(
+                CurrentSession.to_string(),
+                format!("[client session {}] ", other_span.id())
+            );
+        }
+
+        assert!(CurrentSession.to_string().contains("app=live"));
+
+        drop(guard);
+        assert_eq!(CurrentSession.to_string(), "");
+    }
+}
@@ -63,27 +63,174 @@ pings, window acknowledgements, and other plumbing that are standard for most RT
 
 These higher level structs are meant to be integrated *AFTER* a successful handshaking process.
 
+The `hub` module builds on the server session with a `StreamHub`, which routes media from each
+stream's publisher to all of its players, and the `relay` module pulls streams from remote
+servers into the hub and pushes streams from it to remote servers.
+
+The `auth` module's `TokenAuthorizer` checks expiring, HMAC signed tokens passed in the query
+strings of stream keys and app names, and accepts or rejects the requests they came with.  The
+`names` module splits those app names and stream keys into their base names, application
+instances, and query string parameters.
+
+On the client side, the `playback` module's `PlaybackBuffer` paces the media received by a
+`ClientSession` so players present it at the rate it was published, and the `stats` module's
+`StreamStatsTracker` measures bitrates, frame rates, and other health indicators of a stream.
+The `admin` module's `ServerSnapshot` brings the hub, sessions, and stream statistics together
+into a report of everything happening on a server.
+
+The `pipeline` module's `MediaSource` and `MediaSink` traits give everything that produces or
+consumes a stream's media a common shape, and its `MediaTee` fans one stream out to many
+sinks, such as a recorder, an HLS packager, and a push relay at once.
+
+With the `metrics` feature enabled, the `metrics` module's `MetricsRegistry` aggregates
+counters from every session and stream on a server, and renders them in the Prometheus
+exposition format.
+
+With the `tracing` feature enabled, sessions emit `tracing` events for state changes, the
+commands they handle, requests they reject, protocol anomalies, and errors, all within a span
+for each session that records its id, application name, and stream key.  The `log` feature
+writes the same events as `log` records, each starting with the session's id, application
+name, and stream key, so lines from many connections can be told apart.  A session's id is
+returned by its `session_id()` method.
+
+The `transcript` module records everything a session received and sent, so connections that
+misbehave in production can be saved and replayed against a fresh session offline.
+
+The `loopback` module connects a client session to a server session through in-memory
+buffers, handshake included, so both sides of a conversation can be tested together without
+sockets.  The `fixtures` module, enabled by the `test-utils` feature, has canned server
+responses and helpers for reading back what a session sent, for unit tests that drive a single
+session with realistic packets.
+
+## RTMPT
+
+The `rtmpt` module contains the framing needed to tunnel RTMP connections through HTTP requests,
+for clients that can only make web traffic through their firewall.
+
+## Feature flags
+
+Applications that only need part of the crate can turn off default features and pick the parts
+they use, so that an encoder doesn't compile the server session or a server the client session:
+
+* `client-session` (default) - The `ClientSession` and the `relay` module that is built on it
+* `server-session` (default) - The `ServerSession` and the `auth` and `admin` modules that are
+  built on it
+* `amf3` (default) - Decoding of Amf3 command and data messages.  Without it they are returned
+  as `RtmpMessage::Unknown`.
+* `metrics` - The `metrics` module, which requires `server-session`
+* `rtmpe` - Encrypted RTMPE handshakes
+* `serde` - Serialization of the `admin` module's snapshots, and of the stream metadata, states,
+  and other values sessions raise in their events
+* `tracing` - The session events described above, as `tracing` events
+* `log` - The session events described above, as `log` records
+* `test-utils` - The `fixtures` module, for unit tests of code that drives a session.  Unlike the
+  other features it doesn't turn on `std`, so it has no effect without it.
+
+The `hub`, `playback`, and `pipeline` modules are available with either session, and only
+their methods that take a session's events require that session's feature.
+
+Tokio support lives in the separate `rml_rtmp_tokio` crate, so it is only compiled by
+applications that depend on it.
+
+## no_std
+
+Everything except the `chunk_io`, `messages`, and `time` modules requires the `std` feature,
+which is enabled by default and by each of the features above.  With default features turned
+off those three modules only need `alloc`, so chunks and messages can be parsed and produced on
+embedded encoders and in other `no_std` environments.
+
+## WebAssembly
+
+The handshake and `ClientSession` work on `wasm32-unknown-unknown`, for RTMP tooling that runs
+in browsers and edge runtimes.  That target has no system clock or operating system random
+source, so applications should supply their own with `ClientSessionConfig::clock` and
+`Handshake::set_random_source()`.  Handshakes fail with `HandshakeError::NoRandomSource` until
+a random source has been set.
+
 */
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
+// The test harness needs std, so unit tests can use it even when the crate is built without it
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
 extern crate byteorder;
 extern crate bytes;
+#[cfg(feature = "std")]
 extern crate hmac;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "rtmpe")]
+extern crate num_bigint;
+#[cfg(all(
+    feature = "std",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
 extern crate rand;
 extern crate rml_amf0;
+#[cfg(feature = "amf3")]
+extern crate rml_amf3;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "std")]
 extern crate sha2;
 extern crate thiserror;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
 #[cfg(test)]
 #[macro_use]
 mod test_utils {
     #[macro_use]
     pub mod assert_vec_match_macro;
+    #[cfg(feature = "server-session")]
     #[macro_use]
     pub mod assert_vec_contains_macro;
 }
 
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+#[macro_use]
+mod instrument;
+
+#[cfg(feature = "server-session")]
+pub mod admin;
+#[cfg(feature = "server-session")]
+pub mod auth;
 pub mod chunk_io;
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub mod fixtures;
+#[cfg(feature = "std")]
 pub mod handshake;
+#[cfg(feature = "std")]
+pub mod hub;
+#[cfg(all(feature = "client-session", feature = "server-session"))]
+pub mod loopback;
 pub mod messages;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod names;
+#[cfg(feature = "std")]
+pub mod pipeline;
+#[cfg(feature = "std")]
+pub mod playback;
+#[cfg(feature = "std")]
+mod random;
+#[cfg(feature = "client-session")]
+pub mod relay;
+#[cfg(feature = "std")]
+pub mod rtmpt;
+#[cfg(feature = "std")]
 pub mod sessions;
+#[cfg(feature = "std")]
+pub mod stats;
 pub mod time;
+#[cfg(feature = "std")]
+pub mod transcript;
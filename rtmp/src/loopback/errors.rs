@@ -0,0 +1,24 @@
+use handshake::HandshakeError;
+use sessions::{ClientSessionError, ServerSessionError};
+use thiserror::Error;
+
+/// Errors raised while passing bytes between the two sides of a loopback
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum LoopbackError {
+    /// One side of the handshake failed
+    #[error("Handshake failed: {0}")]
+    Handshake(#[from] HandshakeError),
+
+    /// Both sides ran out of bytes to send each other before the handshake completed
+    #[error("The handshake stopped before completing")]
+    HandshakeIncomplete,
+
+    /// The client session could not handle what the server sent it
+    #[error("Client session error: {0}")]
+    ClientSession(#[from] ClientSessionError),
+
+    /// The server session could not handle what the client sent it
+    #[error("Server session error: {0}")]
+    ServerSession(#[from] ServerSessionError),
+}
@@ -0,0 +1,341 @@
+/*!
+This module connects a `ClientSession` to a `ServerSession` through in-memory buffers, so the
+two sides of the protocol can be tested together without opening sockets.
+
+A `Loopback` performs the handshake between the two sides and creates both sessions.  From
+then on, results from one session are given to the loopback, which passes the bytes of their
+outbound packets to the other session, and keeps doing so with whatever that session sends in
+response until neither side has anything left to send.  The events either side raises along
+the way are kept until they're taken.
+
+The server session only accepts requests when it's told to, so `accept_server_requests()`
+accepts every connection, publish, and play request the server has raised, the same way an
+application that lets everyone in would.
+
+# Example
+
+```
+use rml_rtmp::loopback::Loopback;
+use rml_rtmp::sessions::{ClientSessionEvent, ServerSessionEvent};
+
+let mut loopback = Loopback::new().unwrap();
+let request = loopback.client().request_connection("live".to_string()).unwrap();
+loopback.send_to_server(vec![request]).unwrap();
+
+let server_events = loopback.accept_server_requests().unwrap();
+assert!(server_events.iter().any(|event| match *event {
+    ServerSessionEvent::ConnectionRequested { ref app_name, .. } => app_name == "live",
+    _ => false,
+}));
+
+let client_events = loopback.take_client_events();
+assert!(client_events.contains(&ClientSessionEvent::ConnectionRequestAccepted));
+```
+*/
+
+mod errors;
+
+pub use self::errors::LoopbackError;
+
+use handshake::{Handshake, HandshakeProcessResult, PeerType};
+use sessions::{
+    ClientSession, ClientSessionConfig, ClientSessionEvent, ClientSessionResult, ServerSession,
+    ServerSessionConfig, ServerSessionEvent, ServerSessionResult,
+};
+use std::mem;
+
+/// A client session and a server session connected to each other in memory
+pub struct Loopback {
+    client: ClientSession,
+    server: ServerSession,
+    client_events: Vec<ClientSessionEvent>,
+    server_events: Vec<ServerSessionEvent>,
+}
+
+impl Loopback {
+    /// Connects sessions created with default configs
+    pub fn new() -> Result<Loopback, LoopbackError> {
+        Loopback::with_configs(ClientSessionConfig::new(), ServerSessionConfig::new())
+    }
+
+    /// Performs the handshake and connects sessions created with the specified configs.  The
+    /// packets each session starts with, such as their chunk sizes, have been passed to the
+    /// other side by the time this returns.
+    pub fn with_configs(
+        client_config: ClientSessionConfig,
+        server_config: ServerSessionConfig,
+    ) -> Result<Loopback, LoopbackError> {
+        let (mut to_client, mut to_server) = perform_handshake()?;
+        let (client, client_results) = ClientSession::new(client_config)?;
+        let (server, server_results) = ServerSession::new(server_config)?;
+        let mut loopback = Loopback {
+            client,
+            server,
+            client_events: Vec::new(),
+            server_events: Vec::new(),
+        };
+
+        collect_client_results(client_results, &mut to_server, &mut loopback.client_events);
+        collect_server_results(server_results, &mut to_client, &mut loopback.server_events);
+        loopback.exchange(to_server, to_client)?;
+
+        Ok(loopback)
+    }
+
+    pub fn client(&mut self) -> &mut ClientSession {
+        &mut self.client
+    }
+
+    pub fn server(&mut self) -> &mut ServerSession {
+        &mut self.server
+    }
+
+    /// Passes the outbound packets in the client's results to the server, along with
+    /// everything the two sessions send each other in response.  Events in the results are
+    /// kept with the rest of the client's events.
+    pub fn send_to_server<I>(&mut self, results: I) -> Result<(), LoopbackError>
+    where
+        I: IntoIterator<Item = ClientSessionResult>,
+    {
+        let mut to_server = Vec::new();
+        collect_client_results(results, &mut to_server, &mut self.client_events);
+        self.exchange(to_server, Vec::new())
+    }
+
+    /// Passes the outbound packets in the server's results to the client, along with
+    /// everything the two sessions send each other in response.  Events in the results are
+    /// kept with the rest of the server's events.
+    pub fn send_to_client<I>(&mut self, results: I) -> Result<(), LoopbackError>
+    where
+        I: IntoIterator<Item = ServerSessionResult>,
+    {
+        let mut to_client = Vec::new();
+        collect_server_results(results, &mut to_client, &mut self.server_events);
+        self.exchange(Vec::new(), to_client)
+    }
+
+    /// Accepts every connection, publish, and play request the server has raised, and passes
+    /// the responses to the client.  Returns all of the server's events, including the
+    /// requests that were accepted.
+    pub fn accept_server_requests(&mut self) -> Result<Vec<ServerSessionEvent>, LoopbackError> {
+        let mut events = Vec::new();
+        while !self.server_events.is_empty() {
+            for event in mem::take(&mut self.server_events) {
+                if let Some(request_id) = request_id(&event) {
+                    let results = self.server.accept_request(request_id)?;
+                    self.send_to_client(results)?;
+                }
+
+                events.push(event);
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Returns the events the client has raised since they were last taken
+    pub fn take_client_events(&mut self) -> Vec<ClientSessionEvent> {
+        mem::take(&mut self.client_events)
+    }
+
+    /// Returns the events the server has raised since they were last taken
+    pub fn take_server_events(&mut self) -> Vec<ServerSessionEvent> {
+        mem::take(&mut self.server_events)
+    }
+
+    /// Hands bytes to each session until neither has anything left to send the other
+    fn exchange(
+        &mut self,
+        mut to_server: Vec<u8>,
+        mut to_client: Vec<u8>,
+    ) -> Result<(), LoopbackError> {
+        while !to_server.is_empty() || !to_client.is_empty() {
+            if !to_server.is_empty() {
+                let results = self.server.handle_input(&mem::take(&mut to_server))?;
+                collect_server_results(results, &mut to_client, &mut self.server_events);
+            }
+
+            if !to_client.is_empty() {
+                let results = self.client.handle_input(&mem::take(&mut to_client))?;
+                collect_client_results(results, &mut to_server, &mut self.client_events);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs a client and a server handshake against each other, returning any bytes the client
+/// and server are left with once both have completed
+fn perform_handshake() -> Result<(Vec<u8>, Vec<u8>), LoopbackError> {
+    let mut client = Handshake::new(PeerType::Client);
+    let mut server = Handshake::new(PeerType::Server);
+    let mut to_server = client.generate_outbound_p0_and_p1()?;
+    let mut to_client = Vec::new();
+    let mut client_remainder: Option<Vec<u8>> = None;
+    let mut server_remainder: Option<Vec<u8>> = None;
+
+    while !to_server.is_empty() || !to_client.is_empty() {
+        if !to_server.is_empty() {
+            let bytes = mem::take(&mut to_server);
+            match server_remainder {
+                Some(ref mut remainder) => remainder.extend_from_slice(&bytes),
+                None => match server.process_bytes(&bytes)? {
+                    HandshakeProcessResult::InProgress { response_bytes } => {
+                        to_client.extend(response_bytes)
+                    }
+
+                    HandshakeProcessResult::Completed {
+                        response_bytes,
+                        completion,
+                    } => {
+                        to_client.extend(response_bytes);
+                        server_remainder = Some(completion.remaining_bytes.to_vec());
+                    }
+                },
+            }
+        }
+
+        if !to_client.is_empty() {
+            let bytes = mem::take(&mut to_client);
+            match client_remainder {
+                Some(ref mut remainder) => remainder.extend_from_slice(&bytes),
+                None => match client.process_bytes(&bytes)? {
+                    HandshakeProcessResult::InProgress { response_bytes } => {
+                        to_server.extend(response_bytes)
+                    }
+
+                    HandshakeProcessResult::Completed {
+                        response_bytes,
+                        completion,
+                    } => {
+                        to_server.extend(response_bytes);
+                        client_remainder = Some(completion.remaining_bytes.to_vec());
+                    }
+                },
+            }
+        }
+    }
+
+    match (client_remainder, server_remainder) {
+        (Some(client_remainder), Some(server_remainder)) => {
+            Ok((client_remainder, server_remainder))
+        }
+
+        _ => Err(LoopbackError::HandshakeIncomplete),
+    }
+}
+
+fn collect_client_results<I>(
+    results: I,
+    to_server: &mut Vec<u8>,
+    events: &mut Vec<ClientSessionEvent>,
+) where
+    I: IntoIterator<Item = ClientSessionResult>,
+{
+    for result in results {
+        match result {
+            ClientSessionResult::OutboundResponse(packet) => {
+                to_server.extend_from_slice(&packet.bytes)
+            }
+
+            ClientSessionResult::RaisedEvent(event) => events.push(event),
+            ClientSessionResult::UnhandleableMessageReceived(_) => (),
+        }
+    }
+}
+
+fn collect_server_results<I>(
+    results: I,
+    to_client: &mut Vec<u8>,
+    events: &mut Vec<ServerSessionEvent>,
+) where
+    I: IntoIterator<Item = ServerSessionResult>,
+{
+    for result in results {
+        match result {
+            ServerSessionResult::OutboundResponse(packet) => {
+                to_client.extend_from_slice(&packet.bytes)
+            }
+
+            ServerSessionResult::RaisedEvent(event) => events.push(event),
+            ServerSessionResult::UnhandleableMessageReceived(_) => (),
+        }
+    }
+}
+
+/// The id of the request an event asks the application to accept or reject, if it's one
+fn request_id(event: &ServerSessionEvent) -> Option<u32> {
+    match *event {
+        ServerSessionEvent::ConnectionRequested { request_id, .. }
+        | ServerSessionEvent::ReleaseStreamRequested { request_id, .. }
+        | ServerSessionEvent::PublishStreamRequested { request_id, .. }
+        | ServerSessionEvent::PlayStreamRequested { request_id, .. } => Some(request_id),
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use sessions::{PublishRequestType, ServerState};
+    use time::RtmpTimestamp;
+
+    fn connected_loopback() -> Loopback {
+        let mut loopback = Loopback::new().unwrap();
+        let request = loopback
+            .client()
+            .request_connection("live".to_string())
+            .unwrap();
+
+        loopback.send_to_server(vec![request]).unwrap();
+        loopback.accept_server_requests().unwrap();
+        loopback.take_client_events();
+        loopback
+    }
+
+    #[test]
+    fn connection_requests_are_accepted() {
+        let mut loopback = connected_loopback();
+
+        assert_eq!(loopback.server().current_state(), ServerState::Connected);
+    }
+
+    #[test]
+    fn published_video_reaches_the_server() {
+        let mut loopback = connected_loopback();
+        let request = loopback
+            .client()
+            .request_publishing("key".to_string(), PublishRequestType::Live)
+            .unwrap();
+
+        loopback.send_to_server(vec![request]).unwrap();
+        loopback.accept_server_requests().unwrap();
+        assert!(loopback
+            .take_client_events()
+            .contains(&ClientSessionEvent::PublishRequestAccepted));
+
+        let data = Bytes::from(vec![0x17, 1, 0, 0, 0]);
+        let video = loopback
+            .client()
+            .publish_video_data(data.clone(), RtmpTimestamp::new(40), false)
+            .unwrap();
+
+        loopback.send_to_server(vec![video]).unwrap();
+        let events = loopback.take_server_events();
+        match events.as_slice() {
+            [ServerSessionEvent::VideoDataReceived {
+                data: ref received,
+                timestamp,
+                ..
+            }] => {
+                assert_eq!(received, &data);
+                assert_eq!(*timestamp, RtmpTimestamp::new(40));
+            }
+
+            x => panic!("Expected one video data event, instead got {:?}", x),
+        }
+    }
+}
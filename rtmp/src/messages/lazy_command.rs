@@ -0,0 +1,231 @@
+#[cfg(feature = "amf3")]
+use super::types::amf0_command;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use bytes::Bytes;
+use messages::{MessageDeserializationError, MessagePayload, RtmpMessage};
+use rml_amf0;
+use rml_amf0::{Amf0Value, BorrowedDeserializer};
+
+/// An Amf0 command whose name and transaction id have been read, but whose command object and
+/// additional arguments are only decoded once they are asked for.
+///
+/// Most commands can be routed (or ignored) on their name alone, so this avoids building the
+/// arguments' strings and objects for commands that never look at them.
+#[derive(PartialEq, Debug, Clone)]
+pub struct LazyAmf0Command {
+    pub command_name: String,
+    pub transaction_id: f64,
+    pub arguments: LazyAmf0Arguments,
+}
+
+/// The not yet decoded command object and additional arguments of a `LazyAmf0Command`
+#[derive(PartialEq, Debug, Clone)]
+pub struct LazyAmf0Arguments {
+    inner: Arguments,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Arguments {
+    Encoded(Bytes),
+    Decoded {
+        command_object: Amf0Value,
+        additional_arguments: Vec<Amf0Value>,
+    },
+}
+
+impl LazyAmf0Command {
+    /// Reads the name and transaction id of the command in the payload.  `None` is returned if
+    /// the payload does not contain an Amf0 or Amf3 command.
+    ///
+    /// Amf3 commands may switch any of their values to Amf3, so they are decoded in full.  They
+    /// require the `amf3` feature, and are treated as other messages without it.
+    pub fn from_payload(
+        payload: &MessagePayload,
+    ) -> Result<Option<LazyAmf0Command>, MessageDeserializationError> {
+        match payload.type_id {
+            20 => (),
+            #[cfg(feature = "amf3")]
+            17 => {
+                let message = amf0_command::deserialize_amf3(payload.data.clone())?;
+                return Ok(LazyAmf0Command::from_rtmp_message(message));
+            }
+
+            _ => return Ok(None),
+        }
+
+        let data = &payload.data;
+        let mut deserializer = BorrowedDeserializer::new(data);
+        let command_name = match deserializer.next_value()? {
+            Some(value) => value.as_str().map(|x| x.to_string()),
+            None => None,
+        };
+
+        let transaction_id = match deserializer.next_value()? {
+            Some(value) => value.as_number(),
+            None => None,
+        };
+
+        let arguments_start = data.len() - deserializer.remaining().len();
+        match (command_name, transaction_id) {
+            (Some(command_name), Some(transaction_id)) => Ok(Some(LazyAmf0Command {
+                command_name,
+                transaction_id,
+                arguments: LazyAmf0Arguments {
+                    inner: Arguments::Encoded(data.slice(arguments_start..)),
+                },
+            })),
+
+            _ => Err(MessageDeserializationError::InvalidMessageFormat),
+        }
+    }
+
+    /// Wraps an already decoded `Amf0Command` message.  `None` is returned for any other message.
+    pub fn from_rtmp_message(message: RtmpMessage) -> Option<LazyAmf0Command> {
+        match message {
+            RtmpMessage::Amf0Command {
+                command_name,
+                transaction_id,
+                command_object,
+                additional_arguments,
+            } => Some(LazyAmf0Command {
+                command_name,
+                transaction_id,
+                arguments: LazyAmf0Arguments {
+                    inner: Arguments::Decoded {
+                        command_object,
+                        additional_arguments,
+                    },
+                },
+            }),
+
+            _ => None,
+        }
+    }
+
+    /// Decodes the arguments and returns the command as an `Amf0Command` message
+    pub fn into_rtmp_message(self) -> Result<RtmpMessage, MessageDeserializationError> {
+        let (command_object, additional_arguments) = self.arguments.decode()?;
+        Ok(RtmpMessage::Amf0Command {
+            command_name: self.command_name,
+            transaction_id: self.transaction_id,
+            command_object,
+            additional_arguments,
+        })
+    }
+}
+
+impl LazyAmf0Arguments {
+    /// Decodes the command object and additional arguments.  Every command is required to have
+    /// a command object, even if it's null, so commands without one are rejected.
+    pub fn decode(self) -> Result<(Amf0Value, Vec<Amf0Value>), MessageDeserializationError> {
+        match self.inner {
+            Arguments::Decoded {
+                command_object,
+                additional_arguments,
+            } => Ok((command_object, additional_arguments)),
+
+            Arguments::Encoded(data) => {
+                let mut values = rml_amf0::deserialize_slice(&data)?;
+                if values.is_empty() {
+                    return Err(MessageDeserializationError::InvalidMessageFormat);
+                }
+
+                let command_object = values.remove(0);
+                Ok((command_object, values))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rml_amf0::ObjectProperties;
+    use time::RtmpTimestamp;
+
+    fn command_payload(values: Vec<Amf0Value>) -> MessagePayload {
+        MessagePayload {
+            timestamp: RtmpTimestamp::new(0),
+            type_id: 20,
+            message_stream_id: 0,
+            data: Bytes::from(rml_amf0::serialize(&values).unwrap()),
+        }
+    }
+
+    #[test]
+    fn name_and_transaction_id_are_read_before_arguments_are_decoded() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("app".to_string(), Amf0Value::from("live"));
+
+        let payload = command_payload(vec![
+            Amf0Value::from("connect"),
+            Amf0Value::Number(1.0),
+            Amf0Value::Object(properties.clone()),
+            Amf0Value::Boolean(true),
+        ]);
+
+        let command = LazyAmf0Command::from_payload(&payload).unwrap().unwrap();
+        assert_eq!(command.command_name, "connect");
+        assert_eq!(command.transaction_id, 1.0);
+
+        let (command_object, additional_arguments) = command.arguments.decode().unwrap();
+        assert_eq!(command_object, Amf0Value::Object(properties));
+        assert_eq!(additional_arguments, vec![Amf0Value::Boolean(true)]);
+    }
+
+    #[test]
+    fn malformed_arguments_are_only_reported_when_decoded() {
+        let mut payload = command_payload(vec![
+            Amf0Value::from("createStream"),
+            Amf0Value::Number(2.0),
+        ]);
+
+        let mut data = payload.data.to_vec();
+        data.push(0xff);
+        payload.data = Bytes::from(data);
+
+        let command = LazyAmf0Command::from_payload(&payload).unwrap().unwrap();
+        assert_eq!(command.command_name, "createStream");
+        assert!(command.arguments.decode().is_err());
+    }
+
+    #[test]
+    fn command_without_transaction_id_is_rejected() {
+        let payload = command_payload(vec![Amf0Value::from("connect")]);
+
+        match LazyAmf0Command::from_payload(&payload) {
+            Err(MessageDeserializationError::InvalidMessageFormat) => (),
+            x => panic!("Expected invalid message format error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn non_command_payloads_are_ignored() {
+        let payload = MessagePayload {
+            timestamp: RtmpTimestamp::new(0),
+            type_id: 9,
+            message_stream_id: 1,
+            data: Bytes::from(vec![0x17, 1]),
+        };
+
+        assert_eq!(LazyAmf0Command::from_payload(&payload).unwrap(), None);
+    }
+
+    #[test]
+    fn converts_to_same_message_as_eager_deserialization() {
+        let payload = command_payload(vec![
+            Amf0Value::from("play"),
+            Amf0Value::Number(0.0),
+            Amf0Value::Null,
+            Amf0Value::from("key"),
+        ]);
+
+        let command = LazyAmf0Command::from_payload(&payload).unwrap().unwrap();
+
+        assert_eq!(
+            command.into_rtmp_message().unwrap(),
+            payload.to_rtmp_message().unwrap()
+        );
+    }
+}
@@ -1,8 +1,8 @@
 use super::types;
 use bytes::Bytes;
+use core::fmt;
 use messages::RtmpMessage;
 use messages::{MessageDeserializationError, MessageSerializationError};
-use std::fmt;
 use time::RtmpTimestamp;
 
 /// Represents a raw RTMP message
@@ -41,9 +41,10 @@ impl MessagePayload {
     /// Deserializes the message data in the specified payload into its corresponding
     /// `RtmpMessage`.
     ///
-    /// Note that flash clients (like Wowza's test client) lie and mark amf0 data and commands as
-    /// amf3 values.  It is unknown why this happens, but any Amf3 command/data messages that are
-    /// seen are deserialized as amf0.  So far this has not caused any issues.
+    /// Amf3 command and data messages are Amf0 encoded, with any value that needs Amf3 encoding
+    /// sent as an avmplus object marker followed by the Amf3 value.  These values are converted
+    /// to their Amf0 equivalents, so Amf3 messages are returned as `Amf0Command` and `Amf0Data`
+    /// messages.  Without the `std` feature Amf3 messages are returned as `Unknown` messages.
     pub fn to_rtmp_message(&self) -> Result<RtmpMessage, MessageDeserializationError> {
         match self.type_id {
             1 => types::set_chunk_size::deserialize(self.data.clone()),
@@ -57,19 +58,10 @@ impl MessagePayload {
             18 => types::amf0_data::deserialize(self.data.clone()),
             20 => types::amf0_command::deserialize(self.data.clone()),
 
-            // For some reason Flash players (like wowza's test player) send messages
-            // that are flagged as amf3 encoded, but in reality they are amf0 encoded
-            15 => types::amf0_data::deserialize(self.data.clone()),
-
-            17 => {
-                // Fake amf3 commands usually seem to have a 0 in front of the amf0 data.
-                if self.data.len() > 0 && self.data[0] == 0x00 {
-                    let slice = self.data.slice(1..);
-                    types::amf0_command::deserialize(slice)
-                } else {
-                    types::amf0_command::deserialize(self.data.clone())
-                }
-            }
+            #[cfg(feature = "amf3")]
+            15 => types::amf0_data::deserialize_amf3(self.data.clone()),
+            #[cfg(feature = "amf3")]
+            17 => types::amf0_command::deserialize_amf3(self.data.clone()),
 
             _ => Ok(RtmpMessage::Unknown {
                 type_id: self.type_id,
@@ -143,12 +135,29 @@ impl MessagePayload {
     }
 }
 
+impl Default for MessagePayload {
+    fn default() -> Self {
+        MessagePayload::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{MessagePayload, RtmpMessage};
-    use bytes::{BufMut, Bytes, BytesMut};
+    use alloc::string::ToString;
+    use bytes::Bytes;
+    #[cfg(feature = "amf3")]
+    use bytes::{BufMut, BytesMut};
+    #[cfg(feature = "amf3")]
+    use messages::MessageDeserializationError;
     use messages::{PeerBandwidthLimitType, UserControlEventType};
+    #[cfg(feature = "amf3")]
+    use rml_amf0;
     use rml_amf0::Amf0Value;
+    #[cfg(feature = "amf3")]
+    use rml_amf3;
+    #[cfg(feature = "amf3")]
+    use rml_amf3::Amf3Value;
     use time::RtmpTimestamp;
 
     #[test]
@@ -477,6 +486,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "amf3")]
     fn can_get_rtmp_message_for_amf0_command_flagged_as_amf3() {
         let message = RtmpMessage::Amf0Command {
             command_name: "test".to_string(),
@@ -500,6 +510,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "amf3")]
     fn can_get_rtmp_message_for_amf0_data_payload_flagged_as_amf3() {
         let message = RtmpMessage::Amf0Data {
             values: vec![Amf0Value::Number(23.3)],
@@ -512,4 +523,69 @@ mod tests {
 
         assert_eq!(result, message);
     }
+
+    #[test]
+    #[cfg(feature = "amf3")]
+    fn can_get_rtmp_message_for_amf3_command_with_avmplus_values() {
+        let mut data = vec![0];
+        data.extend(
+            rml_amf0::serialize(&vec![
+                Amf0Value::Utf8String("test".to_string()),
+                Amf0Value::Number(15.0),
+            ])
+            .unwrap(),
+        );
+
+        data.push(0x11);
+        data.extend(rml_amf3::serialize(&[Amf3Value::Integer(23)]).unwrap());
+        data.push(0x11);
+        data.extend(rml_amf3::serialize(&[Amf3Value::Utf8String("abc".to_string())]).unwrap());
+
+        let mut payload = MessagePayload::new();
+        payload.type_id = 17;
+        payload.data = Bytes::from(data);
+
+        let result = payload.to_rtmp_message().unwrap();
+
+        let expected = RtmpMessage::Amf0Command {
+            command_name: "test".to_string(),
+            transaction_id: 15.0,
+            command_object: Amf0Value::Number(23.0),
+            additional_arguments: vec![Amf0Value::Utf8String("abc".to_string())],
+        };
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "amf3")]
+    fn error_when_amf3_command_nests_avmplus_values_too_deeply() {
+        let mut data = vec![0];
+        data.extend(
+            rml_amf0::serialize(&vec![
+                Amf0Value::Utf8String("test".to_string()),
+                Amf0Value::Number(15.0),
+            ])
+            .unwrap(),
+        );
+
+        // Amf3 arrays that each contain the next one, far deeper than the default limits allow
+        data.push(0x11);
+        for _ in 0..10_000 {
+            data.extend_from_slice(&[0x09, 0x03, 0x01]);
+        }
+
+        data.push(0x01);
+
+        let mut payload = MessagePayload::new();
+        payload.type_id = 17;
+        payload.data = Bytes::from(data);
+
+        match payload.to_rtmp_message() {
+            Err(MessageDeserializationError::Amf0DeserializationError(
+                rml_amf0::Amf0DeserializationError::InvalidAvmPlusValue(_),
+            )) => (),
+            x => panic!("Expected invalid avmplus value error, instead got {:?}", x),
+        }
+    }
 }
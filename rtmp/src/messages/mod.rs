@@ -4,16 +4,23 @@ and deserializing these messages into payloads.
 
 `MessagePayload`s have auxiliary data about an RTMP message, such as what message stream it is
 meant for, the timestamp for the message and what type of message it is.
+
+Commands can also be read as a `LazyAmf0Command`, which only decodes the command's name and
+transaction id until its arguments are needed.
 */
 
 mod deserialization_errors;
+mod lazy_command;
 mod message_payload;
 mod serialization_errors;
 mod types;
 
 pub use self::deserialization_errors::MessageDeserializationError;
+pub use self::lazy_command::{LazyAmf0Arguments, LazyAmf0Command};
 pub use self::message_payload::MessagePayload;
 pub use self::serialization_errors::MessageSerializationError;
+use alloc::string::String;
+use alloc::vec::Vec;
 use bytes::Bytes;
 use rml_amf0::Amf0Value;
 use time::RtmpTimestamp;
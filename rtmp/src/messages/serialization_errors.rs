@@ -1,12 +1,14 @@
 use rml_amf0::Amf0SerializationError;
 use thiserror::Error;
 
+#[cfg(feature = "std")]
 use std::io;
 
 /// Error state when serialization errors occur
 /// Enumeration that represents the various errors that may occur while trying to
 /// serialize a RTMP message into a raw RTMP payload.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum MessageSerializationError {
     /// An invalid chunk size value was provided
     #[error("Cannot serialize a SetChunkSize message with a size of 2147483648 or greater")]
@@ -17,6 +19,7 @@ pub enum MessageSerializationError {
     Amf0SerializationError(#[from] Amf0SerializationError),
 
     /// Failed to read the values from the input buffer
+    #[cfg(feature = "std")]
     #[error("An IO error occurred while writing the output")]
     Io(#[from] io::Error),
 }
@@ -1,41 +1,38 @@
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use bytes::Bytes;
-use std::io::Cursor;
+use super::read_u32;
+use bytes::{BufMut, Bytes, BytesMut};
 
 use messages::RtmpMessage;
 use messages::{MessageDeserializationError, MessageSerializationError};
 
 pub fn serialize(stream_id: u32) -> Result<Bytes, MessageSerializationError> {
-    let mut cursor = Cursor::new(Vec::new());
-    cursor.write_u32::<BigEndian>(stream_id)?;
+    let mut bytes = BytesMut::with_capacity(4);
+    bytes.put_u32(stream_id);
 
-    let bytes = Bytes::from(cursor.into_inner());
-    Ok(bytes)
+    Ok(bytes.freeze())
 }
 
 pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
-    let mut cursor = Cursor::new(data);
+    let mut data = data;
 
     Ok(RtmpMessage::Abort {
-        stream_id: cursor.read_u32::<BigEndian>()?,
+        stream_id: read_u32(&mut data)?,
     })
 }
 
 #[cfg(test)]
 mod tests {
     use super::{deserialize, serialize};
-    use byteorder::{BigEndian, WriteBytesExt};
+    use alloc::vec::Vec;
     use bytes::Bytes;
-    use std::io::Cursor;
 
     use messages::RtmpMessage;
 
     #[test]
     fn can_serialize_message() {
         let id = 523;
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(id).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(id));
+        let expected = buffer;
 
         let raw_message = serialize(id).unwrap();
 
@@ -47,10 +44,10 @@ mod tests {
         let id = 532;
         let expected = RtmpMessage::Abort { stream_id: id };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(id).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(id));
 
-        let bytes = Bytes::from(cursor.into_inner());
+        let bytes = Bytes::from(buffer);
         let result = deserialize(bytes).unwrap();
         assert_eq!(result, expected);
     }
@@ -1,32 +1,29 @@
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use bytes::Bytes;
-use std::io::Cursor;
+use super::read_u32;
+use bytes::{BufMut, Bytes, BytesMut};
 
 use messages::RtmpMessage;
 use messages::{MessageDeserializationError, MessageSerializationError};
 
 pub fn serialize(sequence_number: u32) -> Result<Bytes, MessageSerializationError> {
-    let mut cursor = Cursor::new(Vec::new());
-    cursor.write_u32::<BigEndian>(sequence_number)?;
+    let mut bytes = BytesMut::with_capacity(4);
+    bytes.put_u32(sequence_number);
 
-    let bytes = Bytes::from(cursor.into_inner());
-    Ok(bytes)
+    Ok(bytes.freeze())
 }
 
 pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
-    let mut cursor = Cursor::new(data);
+    let mut data = data;
 
     Ok(RtmpMessage::Acknowledgement {
-        sequence_number: cursor.read_u32::<BigEndian>()?,
+        sequence_number: read_u32(&mut data)?,
     })
 }
 
 #[cfg(test)]
 mod tests {
     use super::{deserialize, serialize};
-    use byteorder::{BigEndian, WriteBytesExt};
+    use alloc::vec::Vec;
     use bytes::Bytes;
-    use std::io::Cursor;
 
     use messages::RtmpMessage;
 
@@ -35,19 +32,19 @@ mod tests {
         let number = 523;
         let result = serialize(number).unwrap();
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(number).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(number));
 
-        assert_eq!(&cursor.into_inner()[..], &result[..]);
+        assert_eq!(&buffer[..], &result[..]);
     }
 
     #[test]
     fn can_deserialize_message() {
         let number = 532;
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(number).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(number));
 
-        let bytes = Bytes::from(cursor.into_inner());
+        let bytes = Bytes::from(buffer);
         let result = deserialize(bytes).unwrap();
 
         let expected = RtmpMessage::Acknowledgement {
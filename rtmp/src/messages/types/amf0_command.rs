@@ -1,6 +1,13 @@
+use alloc::string::String;
+use alloc::vec::Vec;
 use bytes::Bytes;
 use rml_amf0;
 use rml_amf0::Amf0Value;
+#[cfg(feature = "amf3")]
+use rml_amf0::DeserializationLimits;
+#[cfg(feature = "amf3")]
+use rml_amf3;
+#[cfg(feature = "amf3")]
 use std::io::Cursor;
 
 use messages::RtmpMessage;
@@ -25,9 +32,35 @@ pub fn serialize(
 }
 
 pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
+    let arguments = rml_amf0::deserialize_slice(&data)?;
+    from_arguments(arguments)
+}
+
+/// Deserializes an Amf3 command, which is Amf0 encoded data that may switch individual values to
+/// Amf3 with avmplus object markers.
+#[cfg(feature = "amf3")]
+pub fn deserialize_amf3(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
+    // Amf3 commands start with a format selector byte, which is 0 for Amf0 encoded data.  Some
+    // clients leave it off, which is safe to detect since the command name is always a string.
+    let data = if !data.is_empty() && data[0] == 0x00 {
+        data.slice(1..)
+    } else {
+        data
+    };
+
     let mut cursor = Cursor::new(data);
-    let mut arguments = rml_amf0::deserialize(&mut cursor)?;
+    let arguments = rml_amf0::deserialize_with_avmplus(
+        &mut cursor,
+        DeserializationLimits::default(),
+        rml_amf3::read_avmplus_value,
+    )?;
+
+    from_arguments(arguments)
+}
 
+fn from_arguments(
+    mut arguments: Vec<Amf0Value>,
+) -> Result<RtmpMessage, MessageDeserializationError> {
     let command_name: String;
     let transaction_id: f64;
     let command_object: Amf0Value;
@@ -66,24 +99,23 @@ pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationErr
 #[cfg(test)]
 mod tests {
     use super::{deserialize, serialize};
+    use alloc::string::ToString;
     use bytes::Bytes;
     use rml_amf0;
-    use rml_amf0::Amf0Value;
-    use std::collections::HashMap;
-    use std::io::Cursor;
+    use rml_amf0::{Amf0Value, ObjectProperties};
 
     use messages::RtmpMessage;
 
     #[test]
     fn can_serialize_message() {
-        let mut properties1 = HashMap::new();
+        let mut properties1 = ObjectProperties::new();
         properties1.insert(
             "prop1".to_string(),
             Amf0Value::Utf8String("abc".to_string()),
         );
         properties1.insert("prop2".to_string(), Amf0Value::Null);
 
-        let mut properties2 = HashMap::new();
+        let mut properties2 = ObjectProperties::new();
         properties2.insert(
             "prop1".to_string(),
             Amf0Value::Utf8String("abc".to_string()),
@@ -98,8 +130,7 @@ mod tests {
         )
         .unwrap();
 
-        let mut cursor = Cursor::new(raw_message);
-        let result = rml_amf0::deserialize(&mut cursor).unwrap();
+        let result = rml_amf0::deserialize_slice(&raw_message).unwrap();
 
         let expected = vec![
             Amf0Value::Utf8String("test".to_string()),
@@ -114,14 +145,14 @@ mod tests {
 
     #[test]
     fn can_deserialize_message() {
-        let mut properties1 = HashMap::new();
+        let mut properties1 = ObjectProperties::new();
         properties1.insert(
             "prop1".to_string(),
             Amf0Value::Utf8String("abc".to_string()),
         );
         properties1.insert("prop2".to_string(), Amf0Value::Null);
 
-        let mut properties2 = HashMap::new();
+        let mut properties2 = ObjectProperties::new();
         properties2.insert(
             "prop1".to_string(),
             Amf0Value::Utf8String("abc".to_string()),
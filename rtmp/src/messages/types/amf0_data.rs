@@ -1,6 +1,12 @@
+use alloc::vec::Vec;
 use bytes::Bytes;
 use rml_amf0;
 use rml_amf0::Amf0Value;
+#[cfg(feature = "amf3")]
+use rml_amf0::DeserializationLimits;
+#[cfg(feature = "amf3")]
+use rml_amf3;
+#[cfg(feature = "amf3")]
 use std::io::Cursor;
 
 use messages::RtmpMessage;
@@ -13,19 +19,32 @@ pub fn serialize(values: Vec<Amf0Value>) -> Result<Bytes, MessageSerializationEr
 }
 
 pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
+    let values = rml_amf0::deserialize_slice(&data)?;
+
+    Ok(RtmpMessage::Amf0Data { values })
+}
+
+/// Deserializes an Amf3 data message, which is Amf0 encoded data that may switch individual
+/// values to Amf3 with avmplus object markers.
+#[cfg(feature = "amf3")]
+pub fn deserialize_amf3(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
     let mut cursor = Cursor::new(data);
-    let values = rml_amf0::deserialize(&mut cursor)?;
+    let values = rml_amf0::deserialize_with_avmplus(
+        &mut cursor,
+        DeserializationLimits::default(),
+        rml_amf3::read_avmplus_value,
+    )?;
 
     Ok(RtmpMessage::Amf0Data { values })
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::{deserialize, serialize};
     use bytes::Bytes;
     use rml_amf0;
     use rml_amf0::Amf0Value;
-    use std::io::Cursor;
 
     use messages::RtmpMessage;
 
@@ -34,8 +53,7 @@ mod tests {
         let raw_message =
             serialize(vec![Amf0Value::Boolean(true), Amf0Value::Number(52.0)]).unwrap();
 
-        let mut cursor = Cursor::new(raw_message);
-        let result = rml_amf0::deserialize(&mut cursor).unwrap();
+        let result = rml_amf0::deserialize_slice(&raw_message).unwrap();
         let expected = vec![Amf0Value::Boolean(true), Amf0Value::Number(52.0)];
 
         assert_eq!(&expected[..], &result[..]);
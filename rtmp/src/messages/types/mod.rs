@@ -8,3 +8,33 @@ pub mod set_peer_bandwidth;
 pub mod user_control;
 pub mod video_data;
 pub mod window_acknowledgement_size;
+
+use bytes::{Buf, Bytes};
+use messages::MessageDeserializationError;
+
+/// Reads a byte from the front of the data, failing if the data has run out
+pub fn read_u8(data: &mut Bytes) -> Result<u8, MessageDeserializationError> {
+    if data.remaining() < 1 {
+        return Err(MessageDeserializationError::InvalidMessageFormat);
+    }
+
+    Ok(data.get_u8())
+}
+
+/// Reads a big endian u16 from the front of the data, failing if the data is too short
+pub fn read_u16(data: &mut Bytes) -> Result<u16, MessageDeserializationError> {
+    if data.remaining() < 2 {
+        return Err(MessageDeserializationError::InvalidMessageFormat);
+    }
+
+    Ok(data.get_u16())
+}
+
+/// Reads a big endian u32 from the front of the data, failing if the data is too short
+pub fn read_u32(data: &mut Bytes) -> Result<u32, MessageDeserializationError> {
+    if data.remaining() < 4 {
+        return Err(MessageDeserializationError::InvalidMessageFormat);
+    }
+
+    Ok(data.get_u32())
+}
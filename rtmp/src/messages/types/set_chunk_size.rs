@@ -1,6 +1,5 @@
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use bytes::Bytes;
-use std::io::Cursor;
+use super::read_u32;
+use bytes::{BufMut, Bytes, BytesMut};
 
 use messages::RtmpMessage;
 use messages::{MessageDeserializationError, MessageSerializationError};
@@ -12,16 +11,15 @@ pub fn serialize(size: u32) -> Result<Bytes, MessageSerializationError> {
         return Err(MessageSerializationError::InvalidChunkSize);
     }
 
-    let mut cursor = Cursor::new(Vec::new());
-    cursor.write_u32::<BigEndian>(size)?;
+    let mut bytes = BytesMut::with_capacity(4);
+    bytes.put_u32(size);
 
-    let bytes = Bytes::from(cursor.into_inner());
-    Ok(bytes)
+    Ok(bytes.freeze())
 }
 
 pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
-    let mut cursor = Cursor::new(data);
-    let size = cursor.read_u32::<BigEndian>()?;
+    let mut data = data;
+    let size = read_u32(&mut data)?;
 
     if size > MAX_SIZE {
         return Err(MessageDeserializationError::InvalidMessageFormat);
@@ -33,9 +31,8 @@ pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationErr
 #[cfg(test)]
 mod tests {
     use super::{deserialize, serialize};
-    use byteorder::{BigEndian, WriteBytesExt};
+    use alloc::vec::Vec;
     use bytes::Bytes;
-    use std::io::Cursor;
 
     use messages::RtmpMessage;
 
@@ -43,9 +40,9 @@ mod tests {
     fn can_serialize_message() {
         let size = 523;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
+        let expected = buffer;
 
         let raw_message = serialize(size).unwrap();
 
@@ -55,10 +52,10 @@ mod tests {
     #[test]
     fn can_deserialize_message() {
         let size = 532;
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
 
-        let bytes = Bytes::from(cursor.into_inner());
+        let bytes = Bytes::from(buffer);
         let result = deserialize(bytes).unwrap();
         let expected = RtmpMessage::SetChunkSize { size };
         assert_eq!(result, expected);
@@ -1,6 +1,5 @@
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use bytes::Bytes;
-use std::io::Cursor;
+use super::{read_u32, read_u8};
+use bytes::{BufMut, Bytes, BytesMut};
 
 use messages::{MessageDeserializationError, MessageSerializationError};
 use messages::{PeerBandwidthLimitType, RtmpMessage};
@@ -15,18 +14,17 @@ pub fn serialize(
         PeerBandwidthLimitType::Dynamic => 2,
     };
 
-    let mut cursor = Cursor::new(Vec::new());
-    cursor.write_u32::<BigEndian>(size)?;
-    cursor.write_u8(type_id)?;
+    let mut bytes = BytesMut::with_capacity(5);
+    bytes.put_u32(size);
+    bytes.put_u8(type_id);
 
-    let bytes = Bytes::from(cursor.into_inner());
-    Ok(bytes)
+    Ok(bytes.freeze())
 }
 
 pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
-    let mut cursor = Cursor::new(data);
-    let size = cursor.read_u32::<BigEndian>()?;
-    let limit_type = match cursor.read_u8()? {
+    let mut data = data;
+    let size = read_u32(&mut data)?;
+    let limit_type = match read_u8(&mut data)? {
         0 => PeerBandwidthLimitType::Hard,
         1 => PeerBandwidthLimitType::Soft,
         2 => PeerBandwidthLimitType::Dynamic,
@@ -41,20 +39,19 @@ pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationErr
 #[cfg(test)]
 mod tests {
     use super::{deserialize, serialize};
-    use byteorder::{BigEndian, WriteBytesExt};
+    use alloc::vec::Vec;
     use bytes::Bytes;
-    use std::io::Cursor;
 
-    use messages::{PeerBandwidthLimitType, RtmpMessage};
+    use messages::{MessageDeserializationError, PeerBandwidthLimitType, RtmpMessage};
 
     #[test]
     fn can_serialize_message_with_soft_limit_type() {
         let size = 523;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
-        cursor.write_u8(1).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
+        buffer.push(1);
+        let expected = buffer;
 
         let raw_message = serialize(PeerBandwidthLimitType::Soft, size).unwrap();
         assert_eq!(&raw_message[..], &expected[..]);
@@ -64,10 +61,10 @@ mod tests {
     fn can_serialize_message_with_hard_limit_type() {
         let size = 523;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
-        cursor.write_u8(0).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
+        buffer.push(0);
+        let expected = buffer;
 
         let raw_message = serialize(PeerBandwidthLimitType::Hard, size).unwrap();
         assert_eq!(&raw_message[..], &expected[..]);
@@ -77,10 +74,10 @@ mod tests {
     fn can_serialize_message_with_dynamic_limit_type() {
         let size = 523;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
-        cursor.write_u8(2).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
+        buffer.push(2);
+        let expected = buffer;
 
         let raw_message = serialize(PeerBandwidthLimitType::Dynamic, size).unwrap();
         assert_eq!(&raw_message[..], &expected[..]);
@@ -94,11 +91,11 @@ mod tests {
             limit_type: PeerBandwidthLimitType::Hard,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
-        cursor.write_u8(0).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
+        buffer.push(0);
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -111,11 +108,11 @@ mod tests {
             limit_type: PeerBandwidthLimitType::Soft,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
-        cursor.write_u8(1).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
+        buffer.push(1);
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -128,12 +125,22 @@ mod tests {
             limit_type: PeerBandwidthLimitType::Dynamic,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
-        cursor.write_u8(2).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
+        buffer.push(2);
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn truncated_message_is_rejected() {
+        let bytes = Bytes::from(vec![0, 0, 2, 11]);
+
+        match deserialize(bytes) {
+            Err(MessageDeserializationError::InvalidMessageFormat) => (),
+            x => panic!("Expected invalid message format error, instead got {:?}", x),
+        }
+    }
 }
@@ -1,6 +1,5 @@
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use bytes::Bytes;
-use std::io::{Cursor, Write};
+use super::{read_u16, read_u32};
+use bytes::{BufMut, Bytes, BytesMut};
 
 use messages::{MessageDeserializationError, MessageSerializationError};
 use messages::{RtmpMessage, UserControlEventType};
@@ -12,28 +11,27 @@ pub fn serialize(
     buffer_length: Option<u32>,
     timestamp: Option<RtmpTimestamp>,
 ) -> Result<Bytes, MessageSerializationError> {
-    let mut cursor = Cursor::new(Vec::new());
+    let mut bytes = BytesMut::with_capacity(10);
     match event_type {
-        UserControlEventType::StreamBegin => write_stream_event(&mut cursor, 0, stream_id)?,
-        UserControlEventType::StreamEof => write_stream_event(&mut cursor, 1, stream_id)?,
-        UserControlEventType::StreamDry => write_stream_event(&mut cursor, 2, stream_id)?,
+        UserControlEventType::StreamBegin => write_stream_event(&mut bytes, 0, stream_id),
+        UserControlEventType::StreamEof => write_stream_event(&mut bytes, 1, stream_id),
+        UserControlEventType::StreamDry => write_stream_event(&mut bytes, 2, stream_id),
         UserControlEventType::SetBufferLength => {
-            write_length_event(&mut cursor, 3, stream_id, buffer_length)?
+            write_length_event(&mut bytes, 3, stream_id, buffer_length)
         }
-        UserControlEventType::StreamIsRecorded => write_stream_event(&mut cursor, 4, stream_id)?,
-        UserControlEventType::PingRequest => write_timestamp_event(&mut cursor, 6, timestamp)?,
-        UserControlEventType::PingResponse => write_timestamp_event(&mut cursor, 7, timestamp)?,
-        UserControlEventType::BufferEmpty => write_stream_event(&mut cursor, 31, stream_id)?,
-        UserControlEventType::BufferReady => write_stream_event(&mut cursor, 32, stream_id)?,
+        UserControlEventType::StreamIsRecorded => write_stream_event(&mut bytes, 4, stream_id),
+        UserControlEventType::PingRequest => write_timestamp_event(&mut bytes, 6, timestamp),
+        UserControlEventType::PingResponse => write_timestamp_event(&mut bytes, 7, timestamp),
+        UserControlEventType::BufferEmpty => write_stream_event(&mut bytes, 31, stream_id),
+        UserControlEventType::BufferReady => write_stream_event(&mut bytes, 32, stream_id),
     };
 
-    let bytes = Bytes::from(cursor.into_inner());
-    Ok(bytes)
+    Ok(bytes.freeze())
 }
 
 pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
-    let mut cursor = Cursor::new(data);
-    let event_type = match cursor.read_u16::<BigEndian>()? {
+    let mut data = data;
+    let event_type = match read_u16(&mut data)? {
         0 => UserControlEventType::StreamBegin,
         1 => UserControlEventType::StreamEof,
         2 => UserControlEventType::StreamDry,
@@ -53,22 +51,22 @@ pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationErr
     let mut timestamp = None;
 
     match event_type {
-        UserControlEventType::StreamBegin => stream_id = Some(cursor.read_u32::<BigEndian>()?),
-        UserControlEventType::StreamEof => stream_id = Some(cursor.read_u32::<BigEndian>()?),
-        UserControlEventType::StreamDry => stream_id = Some(cursor.read_u32::<BigEndian>()?),
-        UserControlEventType::StreamIsRecorded => stream_id = Some(cursor.read_u32::<BigEndian>()?),
+        UserControlEventType::StreamBegin => stream_id = Some(read_u32(&mut data)?),
+        UserControlEventType::StreamEof => stream_id = Some(read_u32(&mut data)?),
+        UserControlEventType::StreamDry => stream_id = Some(read_u32(&mut data)?),
+        UserControlEventType::StreamIsRecorded => stream_id = Some(read_u32(&mut data)?),
         UserControlEventType::PingRequest => {
-            timestamp = Some(RtmpTimestamp::new(cursor.read_u32::<BigEndian>()?))
+            timestamp = Some(RtmpTimestamp::new(read_u32(&mut data)?))
         }
         UserControlEventType::PingResponse => {
-            timestamp = Some(RtmpTimestamp::new(cursor.read_u32::<BigEndian>()?))
+            timestamp = Some(RtmpTimestamp::new(read_u32(&mut data)?))
         }
         UserControlEventType::SetBufferLength => {
-            stream_id = Some(cursor.read_u32::<BigEndian>()?);
-            buffer_length = Some(cursor.read_u32::<BigEndian>()?);
+            stream_id = Some(read_u32(&mut data)?);
+            buffer_length = Some(read_u32(&mut data)?);
         }
-        UserControlEventType::BufferEmpty => stream_id = Some(cursor.read_u32::<BigEndian>()?),
-        UserControlEventType::BufferReady => stream_id = Some(cursor.read_u32::<BigEndian>()?),
+        UserControlEventType::BufferEmpty => stream_id = Some(read_u32(&mut data)?),
+        UserControlEventType::BufferReady => stream_id = Some(read_u32(&mut data)?),
     }
 
     Ok(RtmpMessage::UserControl {
@@ -79,31 +77,25 @@ pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationErr
     })
 }
 
-fn write_stream_event<W: Write>(
-    bytes: &mut W,
-    event_id: u16,
-    stream_id: Option<u32>,
-) -> Result<(), MessageSerializationError> {
+fn write_stream_event(bytes: &mut BytesMut, event_id: u16, stream_id: Option<u32>) {
     debug_assert!(
         stream_id.is_some(),
         "Stream event attempted to be serialized with a None stream id!"
     );
 
-    bytes.write_u16::<BigEndian>(event_id)?;
+    bytes.put_u16(event_id);
     match stream_id {
-        Some(x) => bytes.write_u32::<BigEndian>(x)?,
-        None => bytes.write_u32::<BigEndian>(0)?,
+        Some(x) => bytes.put_u32(x),
+        None => bytes.put_u32(0),
     };
-
-    Ok(())
 }
 
-fn write_length_event<W: Write>(
-    bytes: &mut W,
+fn write_length_event(
+    bytes: &mut BytesMut,
     event_id: u16,
     stream_id: Option<u32>,
     length: Option<u32>,
-) -> Result<(), MessageSerializationError> {
+) {
     debug_assert!(
         stream_id.is_some(),
         "Buffer length event attempted to be serialized with a None stream id!"
@@ -113,45 +105,36 @@ fn write_length_event<W: Write>(
         "Buffer length event attempted to be serialized with a None length value!"
     );
 
-    bytes.write_u16::<BigEndian>(event_id)?;
+    bytes.put_u16(event_id);
     match stream_id {
-        Some(x) => bytes.write_u32::<BigEndian>(x)?,
-        None => bytes.write_u32::<BigEndian>(0)?,
+        Some(x) => bytes.put_u32(x),
+        None => bytes.put_u32(0),
     };
 
     match length {
-        Some(x) => bytes.write_u32::<BigEndian>(x)?,
-        None => bytes.write_u32::<BigEndian>(0)?,
+        Some(x) => bytes.put_u32(x),
+        None => bytes.put_u32(0),
     };
-
-    Ok(())
 }
 
-fn write_timestamp_event<W: Write>(
-    bytes: &mut W,
-    event_id: u16,
-    timestamp: Option<RtmpTimestamp>,
-) -> Result<(), MessageSerializationError> {
+fn write_timestamp_event(bytes: &mut BytesMut, event_id: u16, timestamp: Option<RtmpTimestamp>) {
     debug_assert!(
         timestamp.is_some(),
         "Timestamp event attempted to be serialized with a None timestamp"
     );
 
-    bytes.write_u16::<BigEndian>(event_id)?;
+    bytes.put_u16(event_id);
     match timestamp {
-        Some(x) => bytes.write_u32::<BigEndian>(x.value)?,
-        None => bytes.write_u32::<BigEndian>(0)?,
+        Some(x) => bytes.put_u32(x.value),
+        None => bytes.put_u32(0),
     };
-
-    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::{deserialize, serialize};
-    use byteorder::{BigEndian, WriteBytesExt};
+    use alloc::vec::Vec;
     use bytes::Bytes;
-    use std::io::Cursor;
 
     use messages::{RtmpMessage, UserControlEventType};
     use time::RtmpTimestamp;
@@ -160,10 +143,10 @@ mod tests {
     fn can_serialize_stream_begin_message() {
         let stream_id = 555;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(0).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(0));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
+        let expected = buffer;
 
         let raw_message = serialize(
             UserControlEventType::StreamBegin,
@@ -180,10 +163,10 @@ mod tests {
     fn can_serialize_stream_eof_message() {
         let stream_id = 555;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(1).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(1));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
+        let expected = buffer;
 
         let raw_message =
             serialize(UserControlEventType::StreamEof, Some(stream_id), None, None).unwrap();
@@ -195,10 +178,10 @@ mod tests {
     fn can_serialize_stream_dry_message() {
         let stream_id = 555;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(2).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(2));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
+        let expected = buffer;
 
         let raw_message =
             serialize(UserControlEventType::StreamDry, Some(stream_id), None, None).unwrap();
@@ -211,11 +194,11 @@ mod tests {
         let stream_id = 555;
         let buffer_length = 666;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(3).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
-        cursor.write_u32::<BigEndian>(buffer_length).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(3));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
+        buffer.extend_from_slice(&u32::to_be_bytes(buffer_length));
+        let expected = buffer;
 
         let raw_message = serialize(
             UserControlEventType::SetBufferLength,
@@ -232,10 +215,10 @@ mod tests {
     fn can_serialize_stream_is_recorded_message() {
         let stream_id = 555;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(4).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(4));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
+        let expected = buffer;
 
         let raw_message = serialize(
             UserControlEventType::StreamIsRecorded,
@@ -252,10 +235,10 @@ mod tests {
     fn can_serialize_ping_request_message() {
         let time = 555;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(6).unwrap();
-        cursor.write_u32::<BigEndian>(time).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(6));
+        buffer.extend_from_slice(&u32::to_be_bytes(time));
+        let expected = buffer;
 
         let raw_message = serialize(
             UserControlEventType::PingRequest,
@@ -272,10 +255,10 @@ mod tests {
     fn can_serialize_ping_response_message() {
         let time = 555;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(7).unwrap();
-        cursor.write_u32::<BigEndian>(time).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(7));
+        buffer.extend_from_slice(&u32::to_be_bytes(time));
+        let expected = buffer;
 
         let raw_message = serialize(
             UserControlEventType::PingResponse,
@@ -292,10 +275,10 @@ mod tests {
     fn can_serialize_buffer_emtpy_message() {
         let stream_id = 555;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(31).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(31));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
+        let expected = buffer;
 
         let raw_message = serialize(
             UserControlEventType::BufferEmpty,
@@ -312,10 +295,10 @@ mod tests {
     fn can_serialize_buffer_ready_message() {
         let stream_id = 555;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(32).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(32));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
+        let expected = buffer;
 
         let raw_message = serialize(
             UserControlEventType::BufferReady,
@@ -338,11 +321,11 @@ mod tests {
             timestamp: None,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(31).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(31));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -357,11 +340,11 @@ mod tests {
             timestamp: None,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(32).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(32));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -376,11 +359,11 @@ mod tests {
             timestamp: None,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(0).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(0));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -395,11 +378,11 @@ mod tests {
             timestamp: None,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(1).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(1));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -414,11 +397,11 @@ mod tests {
             timestamp: None,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(2).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(2));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -434,12 +417,12 @@ mod tests {
             timestamp: None,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(3).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
-        cursor.write_u32::<BigEndian>(buffer_length).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(3));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
+        buffer.extend_from_slice(&u32::to_be_bytes(buffer_length));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -454,11 +437,11 @@ mod tests {
             timestamp: None,
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(4).unwrap();
-        cursor.write_u32::<BigEndian>(stream_id).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(4));
+        buffer.extend_from_slice(&u32::to_be_bytes(stream_id));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -473,11 +456,11 @@ mod tests {
             timestamp: Some(RtmpTimestamp::new(time)),
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(6).unwrap();
-        cursor.write_u32::<BigEndian>(time).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(6));
+        buffer.extend_from_slice(&u32::to_be_bytes(time));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
@@ -492,11 +475,11 @@ mod tests {
             timestamp: Some(RtmpTimestamp::new(time)),
         };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u16::<BigEndian>(7).unwrap();
-        cursor.write_u32::<BigEndian>(time).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u16::to_be_bytes(7));
+        buffer.extend_from_slice(&u32::to_be_bytes(time));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
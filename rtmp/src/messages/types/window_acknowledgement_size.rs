@@ -1,21 +1,19 @@
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use bytes::Bytes;
-use std::io::Cursor;
+use super::read_u32;
+use bytes::{BufMut, Bytes, BytesMut};
 
 use messages::RtmpMessage;
 use messages::{MessageDeserializationError, MessageSerializationError};
 
 pub fn serialize(size: u32) -> Result<Bytes, MessageSerializationError> {
-    let mut cursor = Cursor::new(Vec::new());
-    cursor.write_u32::<BigEndian>(size)?;
+    let mut bytes = BytesMut::with_capacity(4);
+    bytes.put_u32(size);
 
-    let bytes = Bytes::from(cursor.into_inner());
-    Ok(bytes)
+    Ok(bytes.freeze())
 }
 
 pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationError> {
-    let mut cursor = Cursor::new(data);
-    let size = cursor.read_u32::<BigEndian>()?;
+    let mut data = data;
+    let size = read_u32(&mut data)?;
 
     Ok(RtmpMessage::WindowAcknowledgement { size })
 }
@@ -23,9 +21,8 @@ pub fn deserialize(data: Bytes) -> Result<RtmpMessage, MessageDeserializationErr
 #[cfg(test)]
 mod tests {
     use super::{deserialize, serialize};
-    use byteorder::{BigEndian, WriteBytesExt};
+    use alloc::vec::Vec;
     use bytes::Bytes;
-    use std::io::Cursor;
 
     use messages::RtmpMessage;
 
@@ -33,9 +30,9 @@ mod tests {
     fn can_serialize_message() {
         let size = 523;
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
-        let expected = cursor.into_inner();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
+        let expected = buffer;
 
         let raw_message = serialize(size).unwrap();
         assert_eq!(&raw_message[..], &expected[..]);
@@ -46,10 +43,10 @@ mod tests {
         let size = 532;
         let expected = RtmpMessage::WindowAcknowledgement { size };
 
-        let mut cursor = Cursor::new(Vec::new());
-        cursor.write_u32::<BigEndian>(size).unwrap();
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&u32::to_be_bytes(size));
 
-        let data = Bytes::from(cursor.into_inner());
+        let data = Bytes::from(buffer);
         let result = deserialize(data).unwrap();
         assert_eq!(result, expected);
     }
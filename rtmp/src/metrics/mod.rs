@@ -0,0 +1,15 @@
+/*!
+This module contains the `MetricsRegistry`, which aggregates counters for every session and
+stream on a server so they can be scraped by Prometheus (or anything else that reads its text
+exposition format).
+
+It is only available when the `metrics` feature is enabled.  The registry doesn't depend on any
+HTTP or metrics library: the application passes it the events raised by its `ServerSession`s
+and the results returned by its `StreamHub`, and serves the output of `render_prometheus()`
+from whichever HTTP layer it already uses.
+*/
+
+mod prometheus;
+mod registry;
+
+pub use self::registry::{MetricsRegistry, SessionMetrics, StreamMetrics};
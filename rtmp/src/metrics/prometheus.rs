@@ -0,0 +1,76 @@
+use std::fmt::Write;
+
+/// Writes metrics in the Prometheus text exposition format
+pub(super) struct PrometheusWriter {
+    output: String,
+}
+
+impl PrometheusWriter {
+    pub fn new() -> PrometheusWriter {
+        PrometheusWriter {
+            output: String::new(),
+        }
+    }
+
+    /// Starts a metric family.  Every sample of the family has to be written before the next
+    /// family is started.
+    pub fn family(&mut self, name: &str, metric_type: &str, help: &str) {
+        let _ = writeln!(self.output, "# HELP {} {}", name, help);
+        let _ = writeln!(self.output, "# TYPE {} {}", name, metric_type);
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: u64) {
+        self.output.push_str(name);
+        if !labels.is_empty() {
+            self.output.push('{');
+            for (index, &(label, value)) in labels.iter().enumerate() {
+                if index > 0 {
+                    self.output.push(',');
+                }
+
+                let _ = write!(self.output, "{}=\"{}\"", label, escape_label_value(value));
+            }
+
+            self.output.push('}');
+        }
+
+        let _ = writeln!(self.output, " {}", value);
+    }
+
+    pub fn finish(self) -> String {
+        self.output
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            x => escaped.push(x),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_values_are_escaped() {
+        let mut writer = PrometheusWriter::new();
+        writer.family("rml_test_total", "counter", "A test counter");
+        writer.sample("rml_test_total", &[("app", "a\"b\\c\nd"), ("key", "x")], 5);
+
+        assert_eq!(
+            writer.finish(),
+            "# HELP rml_test_total A test counter\n\
+             # TYPE rml_test_total counter\n\
+             rml_test_total{app=\"a\\\"b\\\\c\\nd\",key=\"x\"} 5\n"
+        );
+    }
+}
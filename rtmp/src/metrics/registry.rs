@@ -0,0 +1,624 @@
+use super::prometheus::PrometheusWriter;
+use hub::StreamHubResult;
+use sessions::ServerSessionEvent;
+use std::collections::HashMap;
+
+type StreamName = (String, String);
+
+/// The name, help text, and value of a per session counter
+type SessionCounter = (&'static str, &'static str, fn(&SessionMetrics) -> u64);
+
+/// The name, type, help text, and value of a per stream metric
+type StreamMetric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&StreamMetrics) -> u64,
+);
+
+/// Counters for a single session
+#[derive(PartialEq, Debug, Clone)]
+pub struct SessionMetrics {
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    pub video_frames_received: u64,
+    pub audio_frames_received: u64,
+    pub video_frames_sent: u64,
+    pub audio_frames_sent: u64,
+
+    /// The application name and stream key of the stream the session is publishing or playing
+    pub stream: Option<(String, String)>,
+
+    /// True if the session is publishing its stream, rather than playing it
+    pub is_publisher: bool,
+}
+
+impl SessionMetrics {
+    pub fn new() -> SessionMetrics {
+        SessionMetrics {
+            bytes_received: 0,
+            bytes_sent: 0,
+            video_frames_received: 0,
+            audio_frames_received: 0,
+            video_frames_sent: 0,
+            audio_frames_sent: 0,
+            stream: None,
+            is_publisher: false,
+        }
+    }
+}
+
+impl Default for SessionMetrics {
+    fn default() -> Self {
+        SessionMetrics::new()
+    }
+}
+
+/// Counters for a single stream, across all of its publishers and subscribers
+#[derive(PartialEq, Debug, Clone)]
+pub struct StreamMetrics {
+    /// How many sessions are currently publishing the stream
+    pub publishers: u64,
+
+    /// How many sessions are currently playing the stream
+    pub subscribers: u64,
+
+    /// How many times publishing has started on the stream
+    pub publish_count: u64,
+
+    pub video_frames_received: u64,
+    pub audio_frames_received: u64,
+    pub bytes_received: u64,
+    pub video_frames_sent: u64,
+    pub audio_frames_sent: u64,
+    pub bytes_sent: u64,
+}
+
+impl StreamMetrics {
+    pub fn new() -> StreamMetrics {
+        StreamMetrics {
+            publishers: 0,
+            subscribers: 0,
+            publish_count: 0,
+            video_frames_received: 0,
+            audio_frames_received: 0,
+            bytes_received: 0,
+            video_frames_sent: 0,
+            audio_frames_sent: 0,
+            bytes_sent: 0,
+        }
+    }
+}
+
+impl Default for StreamMetrics {
+    fn default() -> Self {
+        StreamMetrics::new()
+    }
+}
+
+/// Aggregates counters for every session and stream on a server.
+///
+/// Sessions are identified by the same connection ids the application uses with its
+/// `StreamHub`.  The application reports when each session opens and closes, how many bytes
+/// it reads from and writes to each connection, every `ServerSessionEvent` its sessions raise,
+/// and every `StreamHubResult` it sends.  From those the registry works out which stream each
+/// session is publishing or playing, and counts the media flowing through it.
+///
+/// A stream's counters are kept until it has no publishers or subscribers left, and a session's
+/// until it closes, so the output of `render_prometheus()` only contains what's active.
+/// Stream keys are used as label values, so the metrics endpoint should not be public when
+/// stream keys are secret.
+///
+/// # Examples
+/// ```
+/// # extern crate bytes;
+/// # extern crate rml_rtmp;
+/// use bytes::Bytes;
+/// use rml_rtmp::metrics::MetricsRegistry;
+/// use rml_rtmp::sessions::{PublishMode, ServerSessionEvent};
+/// use rml_rtmp::time::RtmpTimestamp;
+///
+/// # fn main() {
+/// let mut registry = MetricsRegistry::new();
+/// registry.session_opened(1);
+/// registry.record_bytes_received(1, 4096);
+/// registry.record_server_event(1, &ServerSessionEvent::PublishStreamRequested {
+///     request_id: 0,
+///     app_name: "live".to_string(),
+///     stream_key: "abc".to_string(),
+///     mode: PublishMode::Live,
+/// });
+///
+/// registry.record_server_event(1, &ServerSessionEvent::VideoDataReceived {
+///     app_name: "live".to_string(),
+///     stream_key: "abc".to_string(),
+///     data: Bytes::from(vec![0x17, 1, 0, 0, 0]),
+///     timestamp: RtmpTimestamp::new(0),
+/// });
+///
+/// let output = registry.render_prometheus();
+/// assert!(output.contains("rml_rtmp_stream_video_frames_received_total{app=\"live\",stream_key=\"abc\"} 1\n"));
+/// # }
+/// ```
+pub struct MetricsRegistry {
+    sessions: HashMap<usize, SessionMetrics>,
+    streams: HashMap<StreamName, StreamMetrics>,
+    sessions_opened: u64,
+    sessions_closed: u64,
+}
+
+impl MetricsRegistry {
+    /// Creates a registry with no sessions or streams
+    pub fn new() -> MetricsRegistry {
+        MetricsRegistry {
+            sessions: HashMap::new(),
+            streams: HashMap::new(),
+            sessions_opened: 0,
+            sessions_closed: 0,
+        }
+    }
+
+    /// Starts tracking a session
+    pub fn session_opened(&mut self, connection_id: usize) {
+        self.sessions_opened += 1;
+        self.sessions.insert(connection_id, SessionMetrics::new());
+    }
+
+    /// Stops tracking a session, removing it from the stream it was publishing or playing
+    pub fn session_closed(&mut self, connection_id: usize) {
+        self.leave_stream(connection_id);
+        if self.sessions.remove(&connection_id).is_some() {
+            self.sessions_closed += 1;
+        }
+    }
+
+    /// Records bytes read from a session's connection
+    pub fn record_bytes_received(&mut self, connection_id: usize, bytes: usize) {
+        if let Some(session) = self.sessions.get_mut(&connection_id) {
+            session.bytes_received += bytes as u64;
+        }
+    }
+
+    /// Records bytes written to a session's connection
+    pub fn record_bytes_sent(&mut self, connection_id: usize, bytes: usize) {
+        if let Some(session) = self.sessions.get_mut(&connection_id) {
+            session.bytes_sent += bytes as u64;
+        }
+    }
+
+    /// Records an event raised by a session's `ServerSession`
+    pub fn record_server_event(&mut self, connection_id: usize, event: &ServerSessionEvent) {
+        match *event {
+            ServerSessionEvent::PublishStreamRequested {
+                ref app_name,
+                ref stream_key,
+                ..
+            } => self.join_stream(connection_id, app_name, stream_key, true),
+
+            ServerSessionEvent::PlayStreamRequested {
+                ref app_name,
+                ref stream_key,
+                ..
+            } => self.join_stream(connection_id, app_name, stream_key, false),
+
+            ServerSessionEvent::PublishStreamFinished { .. }
+            | ServerSessionEvent::PlayStreamFinished { .. } => self.leave_stream(connection_id),
+
+            ServerSessionEvent::VideoDataReceived {
+                ref app_name,
+                ref stream_key,
+                ref data,
+                ..
+            } => {
+                if let Some(session) = self.sessions.get_mut(&connection_id) {
+                    session.video_frames_received += 1;
+                }
+
+                let name = (app_name.clone(), stream_key.clone());
+                if let Some(stream) = self.streams.get_mut(&name) {
+                    stream.video_frames_received += 1;
+                    stream.bytes_received += data.len() as u64;
+                }
+            }
+
+            ServerSessionEvent::AudioDataReceived {
+                ref app_name,
+                ref stream_key,
+                ref data,
+                ..
+            } => {
+                if let Some(session) = self.sessions.get_mut(&connection_id) {
+                    session.audio_frames_received += 1;
+                }
+
+                let name = (app_name.clone(), stream_key.clone());
+                if let Some(stream) = self.streams.get_mut(&name) {
+                    stream.audio_frames_received += 1;
+                    stream.bytes_received += data.len() as u64;
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    /// Records media the `StreamHub` routed to a subscriber, once it has been sent
+    pub fn record_hub_result(&mut self, result: &StreamHubResult) {
+        let (subscriber_id, is_video, size) = match *result {
+            StreamHubResult::SendVideoData {
+                subscriber_id,
+                ref data,
+                ..
+            } => (subscriber_id, true, data.len()),
+
+            StreamHubResult::SendAudioData {
+                subscriber_id,
+                ref data,
+                ..
+            } => (subscriber_id, false, data.len()),
+
+            _ => return,
+        };
+
+        let streams = &mut self.streams;
+        let session = match self.sessions.get_mut(&subscriber_id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        if is_video {
+            session.video_frames_sent += 1;
+        } else {
+            session.audio_frames_sent += 1;
+        }
+
+        if let Some(stream) = session
+            .stream
+            .as_ref()
+            .and_then(|name| streams.get_mut(name))
+        {
+            if is_video {
+                stream.video_frames_sent += 1;
+            } else {
+                stream.audio_frames_sent += 1;
+            }
+
+            stream.bytes_sent += size as u64;
+        }
+    }
+
+    /// The counters of an open session
+    pub fn session(&self, connection_id: usize) -> Option<&SessionMetrics> {
+        self.sessions.get(&connection_id)
+    }
+
+    /// The counters of a stream that has a publisher or subscribers
+    pub fn stream(&self, app_name: &str, stream_key: &str) -> Option<&StreamMetrics> {
+        self.streams
+            .get(&(app_name.to_string(), stream_key.to_string()))
+    }
+
+    /// The number of sessions that are open
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Renders every metric in the Prometheus text exposition format, for serving from a
+    /// metrics endpoint with a content type of `text/plain; version=0.0.4`
+    pub fn render_prometheus(&self) -> String {
+        let mut writer = PrometheusWriter::new();
+        writer.family("rml_rtmp_sessions", "gauge", "Sessions currently open");
+        writer.sample("rml_rtmp_sessions", &[], self.sessions.len() as u64);
+        writer.family(
+            "rml_rtmp_sessions_opened_total",
+            "counter",
+            "Sessions opened since the server started",
+        );
+        writer.sample("rml_rtmp_sessions_opened_total", &[], self.sessions_opened);
+        writer.family(
+            "rml_rtmp_sessions_closed_total",
+            "counter",
+            "Sessions closed since the server started",
+        );
+        writer.sample("rml_rtmp_sessions_closed_total", &[], self.sessions_closed);
+
+        let mut sessions = self.sessions.iter().collect::<Vec<_>>();
+        sessions.sort_by_key(|&(id, _)| *id);
+        let sessions = sessions
+            .into_iter()
+            .map(|(id, session)| (id.to_string(), session))
+            .collect::<Vec<_>>();
+
+        let session_counters: [SessionCounter; 6] = [
+            (
+                "rml_rtmp_session_bytes_received_total",
+                "Bytes read from the session's connection",
+                |session| session.bytes_received,
+            ),
+            (
+                "rml_rtmp_session_bytes_sent_total",
+                "Bytes written to the session's connection",
+                |session| session.bytes_sent,
+            ),
+            (
+                "rml_rtmp_session_video_frames_received_total",
+                "Video frames published by the session",
+                |session| session.video_frames_received,
+            ),
+            (
+                "rml_rtmp_session_audio_frames_received_total",
+                "Audio frames published by the session",
+                |session| session.audio_frames_received,
+            ),
+            (
+                "rml_rtmp_session_video_frames_sent_total",
+                "Video frames sent to the session",
+                |session| session.video_frames_sent,
+            ),
+            (
+                "rml_rtmp_session_audio_frames_sent_total",
+                "Audio frames sent to the session",
+                |session| session.audio_frames_sent,
+            ),
+        ];
+
+        for &(name, help, value) in &session_counters {
+            writer.family(name, "counter", help);
+            for &(ref id, session) in &sessions {
+                writer.sample(name, &[("connection_id", id)], value(session));
+            }
+        }
+
+        let mut streams = self.streams.iter().collect::<Vec<_>>();
+        streams.sort_by_key(|&(name, _)| name);
+
+        let stream_metrics: [StreamMetric; 9] = [
+            (
+                "rml_rtmp_stream_publishers",
+                "gauge",
+                "Sessions publishing the stream",
+                |stream| stream.publishers,
+            ),
+            (
+                "rml_rtmp_stream_subscribers",
+                "gauge",
+                "Sessions playing the stream",
+                |stream| stream.subscribers,
+            ),
+            (
+                "rml_rtmp_stream_publishes_total",
+                "counter",
+                "Times publishing started on the stream",
+                |stream| stream.publish_count,
+            ),
+            (
+                "rml_rtmp_stream_video_frames_received_total",
+                "counter",
+                "Video frames published to the stream",
+                |stream| stream.video_frames_received,
+            ),
+            (
+                "rml_rtmp_stream_audio_frames_received_total",
+                "counter",
+                "Audio frames published to the stream",
+                |stream| stream.audio_frames_received,
+            ),
+            (
+                "rml_rtmp_stream_bytes_received_total",
+                "counter",
+                "Bytes of media published to the stream",
+                |stream| stream.bytes_received,
+            ),
+            (
+                "rml_rtmp_stream_video_frames_sent_total",
+                "counter",
+                "Video frames sent to the stream's subscribers",
+                |stream| stream.video_frames_sent,
+            ),
+            (
+                "rml_rtmp_stream_audio_frames_sent_total",
+                "counter",
+                "Audio frames sent to the stream's subscribers",
+                |stream| stream.audio_frames_sent,
+            ),
+            (
+                "rml_rtmp_stream_bytes_sent_total",
+                "counter",
+                "Bytes of media sent to the stream's subscribers",
+                |stream| stream.bytes_sent,
+            ),
+        ];
+
+        for &(name, metric_type, help, value) in &stream_metrics {
+            writer.family(name, metric_type, help);
+            for &((app_name, stream_key), stream) in &streams {
+                let labels = [
+                    ("app", app_name.as_str()),
+                    ("stream_key", stream_key.as_str()),
+                ];
+                writer.sample(name, &labels, value(stream));
+            }
+        }
+
+        writer.finish()
+    }
+
+    fn join_stream(
+        &mut self,
+        connection_id: usize,
+        app_name: &str,
+        stream_key: &str,
+        is_publisher: bool,
+    ) {
+        if !self.sessions.contains_key(&connection_id) {
+            return;
+        }
+
+        self.leave_stream(connection_id);
+
+        let name = (app_name.to_string(), stream_key.to_string());
+        let stream = self.streams.entry(name.clone()).or_default();
+
+        if is_publisher {
+            stream.publishers += 1;
+            stream.publish_count += 1;
+        } else {
+            stream.subscribers += 1;
+        }
+
+        if let Some(session) = self.sessions.get_mut(&connection_id) {
+            session.stream = Some(name);
+            session.is_publisher = is_publisher;
+        }
+    }
+
+    fn leave_stream(&mut self, connection_id: usize) {
+        let session = match self.sessions.get_mut(&connection_id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        let name = match session.stream.take() {
+            Some(name) => name,
+            None => return,
+        };
+
+        let is_idle = match self.streams.get_mut(&name) {
+            Some(stream) => {
+                if session.is_publisher {
+                    stream.publishers = stream.publishers.saturating_sub(1);
+                } else {
+                    stream.subscribers = stream.subscribers.saturating_sub(1);
+                }
+
+                stream.publishers == 0 && stream.subscribers == 0
+            }
+
+            None => false,
+        };
+
+        session.is_publisher = false;
+        if is_idle {
+            self.streams.remove(&name);
+        }
+    }
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> Self {
+        MetricsRegistry::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use sessions::{PlayStartValue, PublishMode};
+    use time::RtmpTimestamp;
+
+    fn publish(registry: &mut MetricsRegistry, connection_id: usize) {
+        registry.record_server_event(
+            connection_id,
+            &ServerSessionEvent::PublishStreamRequested {
+                request_id: 0,
+                app_name: "live".to_string(),
+                stream_key: "abc".to_string(),
+                mode: PublishMode::Live,
+            },
+        );
+    }
+
+    fn play(registry: &mut MetricsRegistry, connection_id: usize) {
+        registry.record_server_event(
+            connection_id,
+            &ServerSessionEvent::PlayStreamRequested {
+                request_id: 0,
+                app_name: "live".to_string(),
+                stream_key: "abc".to_string(),
+                start_at: PlayStartValue::LiveOrRecorded,
+                duration: None,
+                reset: false,
+                stream_id: 1,
+            },
+        );
+    }
+
+    #[test]
+    fn media_is_counted_for_sessions_and_streams() {
+        let mut registry = MetricsRegistry::new();
+        registry.session_opened(1);
+        registry.session_opened(2);
+        publish(&mut registry, 1);
+        play(&mut registry, 2);
+
+        let data = Bytes::from(vec![0x27, 1, 0, 0, 0, 1, 2, 3]);
+        registry.record_server_event(
+            1,
+            &ServerSessionEvent::VideoDataReceived {
+                app_name: "live".to_string(),
+                stream_key: "abc".to_string(),
+                data: data.clone(),
+                timestamp: RtmpTimestamp::new(0),
+            },
+        );
+
+        registry.record_hub_result(&StreamHubResult::SendVideoData {
+            subscriber_id: 2,
+            stream_id: 1,
+            data,
+            timestamp: RtmpTimestamp::new(0),
+            can_be_dropped: true,
+        });
+
+        let stream = registry.stream("live", "abc").unwrap();
+        assert_eq!(stream.publishers, 1);
+        assert_eq!(stream.subscribers, 1);
+        assert_eq!(stream.video_frames_received, 1);
+        assert_eq!(stream.bytes_received, 8);
+        assert_eq!(stream.video_frames_sent, 1);
+        assert_eq!(stream.bytes_sent, 8);
+        assert_eq!(registry.session(1).unwrap().video_frames_received, 1);
+        assert_eq!(registry.session(2).unwrap().video_frames_sent, 1);
+    }
+
+    #[test]
+    fn stream_is_removed_once_idle() {
+        let mut registry = MetricsRegistry::new();
+        registry.session_opened(1);
+        registry.session_opened(2);
+        publish(&mut registry, 1);
+        play(&mut registry, 2);
+
+        registry.session_closed(1);
+        assert_eq!(registry.stream("live", "abc").unwrap().publishers, 0);
+
+        registry.record_server_event(
+            2,
+            &ServerSessionEvent::PlayStreamFinished {
+                app_name: "live".to_string(),
+                stream_key: "abc".to_string(),
+            },
+        );
+
+        assert_eq!(registry.stream("live", "abc"), None);
+        assert_eq!(registry.session(2).unwrap().stream, None);
+    }
+
+    #[test]
+    fn prometheus_output_includes_every_session_and_stream() {
+        let mut registry = MetricsRegistry::new();
+        registry.session_opened(7);
+        registry.record_bytes_received(7, 100);
+        publish(&mut registry, 7);
+
+        let output = registry.render_prometheus();
+
+        assert!(output.contains("# TYPE rml_rtmp_sessions gauge\nrml_rtmp_sessions 1\n"));
+        assert!(output.contains("rml_rtmp_session_bytes_received_total{connection_id=\"7\"} 100\n"));
+        assert!(output.contains(
+            "# TYPE rml_rtmp_stream_publishers gauge\n\
+             rml_rtmp_stream_publishers{app=\"live\",stream_key=\"abc\"} 1\n"
+        ));
+    }
+}
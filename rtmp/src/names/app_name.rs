@@ -0,0 +1,90 @@
+use super::query::{parse_query, split_query, QueryParams};
+
+/// The parts of the app name a client connected to
+#[derive(Debug, PartialEq, Clone)]
+pub struct AppName {
+    /// The application, such as `live` in `live/_definst_?token=abc`
+    pub name: String,
+
+    /// The application instance, such as `_definst_` in `live/_definst_?token=abc`
+    pub instance: Option<String>,
+
+    /// The parameters of the query string, such as `token` in `live/_definst_?token=abc`
+    pub params: QueryParams,
+}
+
+impl AppName {
+    /// Splits an app name into its parts.  Leading and trailing slashes are ignored, and
+    /// everything after the first slash is treated as the instance.
+    pub fn parse(raw: &str) -> AppName {
+        let (path, query) = split_query(raw);
+        let path = path.trim_matches('/');
+        let (name, instance) = match path.find('/') {
+            Some(index) => (&path[..index], Some(path[index + 1..].to_string())),
+            None => (path, None),
+        };
+
+        AppName {
+            name: name.to_string(),
+            instance: instance.filter(|instance| !instance.is_empty()),
+            params: query.map(parse_query).unwrap_or_default(),
+        }
+    }
+
+    /// The application and instance without the query string, such as `live/_definst_`
+    pub fn path(&self) -> String {
+        match self.instance {
+            Some(ref instance) => format!("{}/{}", self.name, instance),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_app_name_has_no_instance_or_params() {
+        let app = AppName::parse("live");
+
+        assert_eq!(app.name, "live");
+        assert_eq!(app.instance, None);
+        assert!(app.params.is_empty(), "Expected no params");
+        assert_eq!(app.path(), "live");
+    }
+
+    #[test]
+    fn instance_and_params_are_split_out() {
+        let app = AppName::parse("live/instance?param=1");
+
+        assert_eq!(app.name, "live");
+        assert_eq!(app.instance, Some("instance".to_string()));
+        assert_eq!(app.params.get("param"), Some(&"1".to_string()));
+        assert_eq!(app.path(), "live/instance");
+    }
+
+    #[test]
+    fn params_directly_after_app_are_split_out() {
+        let app = AppName::parse("live?token=abc");
+
+        assert_eq!(app.name, "live");
+        assert_eq!(app.instance, None);
+        assert_eq!(app.params.get("token"), Some(&"abc".to_string()));
+    }
+
+    #[test]
+    fn surrounding_slashes_are_ignored() {
+        let app = AppName::parse("/live/_definst_/?a=b");
+
+        assert_eq!(app.name, "live");
+        assert_eq!(app.instance, Some("_definst_".to_string()));
+    }
+
+    #[test]
+    fn nested_instances_are_kept_together() {
+        let app = AppName::parse("live/region/eu");
+
+        assert_eq!(app.instance, Some("region/eu".to_string()));
+    }
+}
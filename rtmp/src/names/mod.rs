@@ -0,0 +1,33 @@
+/*!
+This module contains utilities for splitting the app names and stream keys that clients send
+into their structured parts.
+
+Encoders differ wildly in where they put extra parameters.  Some append a query string to the
+app name (`live?token=abc`), some to the stream key (`my_stream?token=abc`), and some connect to
+an application instance (`live/_definst_`) as well.  `AppName` and `StreamName` take care of
+these variations so servers can look at the base name and parameters directly.
+
+```
+# extern crate rml_rtmp;
+# fn main() {
+use rml_rtmp::names::{AppName, StreamName};
+
+let app = AppName::parse("live/instance?region=eu");
+assert_eq!(app.name, "live");
+assert_eq!(app.instance, Some("instance".to_string()));
+assert_eq!(app.params.get("region"), Some(&"eu".to_string()));
+
+let stream = StreamName::parse("my_stream?token=a%2Bb");
+assert_eq!(stream.name, "my_stream");
+assert_eq!(stream.params.get("token"), Some(&"a+b".to_string()));
+# }
+```
+*/
+
+mod app_name;
+mod query;
+mod stream_name;
+
+pub use self::app_name::AppName;
+pub use self::query::{parse_query, split_query, QueryParams};
+pub use self::stream_name::StreamName;
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+/// The parameters of a query string, keyed by their names
+pub type QueryParams = HashMap<String, String>;
+
+/// Splits an app name or stream key into the name and the query string that follows a `?`
+pub fn split_query(value: &str) -> (&str, Option<&str>) {
+    match value.find('?') {
+        Some(index) => (&value[..index], Some(&value[index + 1..])),
+        None => (value, None),
+    }
+}
+
+/// Parses a query string (without the leading `?`) into its parameters.  Percent encoded
+/// characters are decoded, parameters without a value are given an empty one, and only the
+/// first of any repeated parameters is kept.
+pub fn parse_query(query: &str) -> QueryParams {
+    let mut params = QueryParams::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let mut parts = pair.splitn(2, '=');
+        let name = percent_decode(parts.next().unwrap_or(""));
+        let value = percent_decode(parts.next().unwrap_or(""));
+
+        params.entry(name).or_insert(value);
+    }
+
+    params
+}
+
+/// Decodes `%XX` sequences, leaving any that are malformed as they are.  A `+` is left alone,
+/// since tokens are commonly base64 encoded.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let digits = (hex_digit(bytes[index + 1]), hex_digit(bytes[index + 2]));
+            if let (Some(high), Some(low)) = digits {
+                decoded.push((high << 4) | low);
+                index += 3;
+                continue;
+            }
+        }
+
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_query_returns_name_and_query() {
+        assert_eq!(split_query("key?a=1&b=2"), ("key", Some("a=1&b=2")));
+        assert_eq!(split_query("key"), ("key", None));
+        assert_eq!(split_query("key?"), ("key", Some("")));
+    }
+
+    #[test]
+    fn query_parameters_are_parsed() {
+        let params = parse_query("a=1&b=&c&&d=x=y");
+
+        assert_eq!(params.len(), 4, "Unexpected number of parameters");
+        assert_eq!(params.get("a"), Some(&"1".to_string()));
+        assert_eq!(params.get("b"), Some(&"".to_string()));
+        assert_eq!(params.get("c"), Some(&"".to_string()));
+        assert_eq!(params.get("d"), Some(&"x=y".to_string()));
+    }
+
+    #[test]
+    fn first_repeated_parameter_is_kept() {
+        let params = parse_query("token=first&token=second");
+
+        assert_eq!(params.get("token"), Some(&"first".to_string()));
+    }
+
+    #[test]
+    fn percent_encoded_characters_are_decoded() {
+        let params = parse_query("na%6De=a%2Fb+c&bad=100%&short=%4");
+
+        assert_eq!(params.get("name"), Some(&"a/b+c".to_string()));
+        assert_eq!(params.get("bad"), Some(&"100%".to_string()));
+        assert_eq!(params.get("short"), Some(&"%4".to_string()));
+    }
+}
@@ -0,0 +1,45 @@
+use super::query::{parse_query, split_query, QueryParams};
+
+/// The parts of the stream key a client is publishing or playing
+#[derive(Debug, PartialEq, Clone)]
+pub struct StreamName {
+    /// The stream key without its query string
+    pub name: String,
+
+    /// The parameters of the query string, such as `token` in `my_stream?token=abc`
+    pub params: QueryParams,
+}
+
+impl StreamName {
+    /// Splits a stream key into its name and query string parameters
+    pub fn parse(raw: &str) -> StreamName {
+        let (name, query) = split_query(raw);
+
+        StreamName {
+            name: name.to_string(),
+            params: query.map(parse_query).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_key_without_query_has_no_params() {
+        let stream = StreamName::parse("my_stream");
+
+        assert_eq!(stream.name, "my_stream");
+        assert!(stream.params.is_empty(), "Expected no params");
+    }
+
+    #[test]
+    fn params_are_split_from_stream_key() {
+        let stream = StreamName::parse("my_stream?token=abc&expires=100");
+
+        assert_eq!(stream.name, "my_stream");
+        assert_eq!(stream.params.get("token"), Some(&"abc".to_string()));
+        assert_eq!(stream.params.get("expires"), Some(&"100".to_string()));
+    }
+}
@@ -0,0 +1,15 @@
+use std::error::Error as StdError;
+use thiserror::Error;
+
+/// Errors that can occur while passing media through a pipeline
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PipelineError {
+    /// One of a tee's sinks failed to consume an item.  The item was still passed to the
+    /// tee's other sinks.
+    #[error("Media sink {sink_id} failed: {source}")]
+    SinkFailed {
+        sink_id: usize,
+        source: Box<dyn StdError + Send + Sync>,
+    },
+}
@@ -0,0 +1,212 @@
+use bytes::Bytes;
+use hub::media::{is_audio_sequence_header, is_video_keyframe, is_video_sequence_header};
+use hub::{CachedMedia, StreamHubResult};
+use playback::BufferedMedia;
+#[cfg(feature = "client-session")]
+use sessions::ClientSessionEvent;
+#[cfg(feature = "server-session")]
+use sessions::ServerSessionEvent;
+use sessions::StreamMetadata;
+use std::sync::Arc;
+use time::RtmpTimestamp;
+
+/// A single piece of a stream's media, as passed from a `MediaSource` to a `MediaSink`
+#[derive(PartialEq, Debug, Clone)]
+pub enum MediaItem {
+    /// The stream's metadata has changed
+    Metadata(Arc<StreamMetadata>),
+
+    /// The payload of a video message, starting with its FLV video tag header
+    Video {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+
+    /// The payload of an audio message, starting with its FLV audio tag header
+    Audio {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+
+    /// The stream has ended and no more items will follow
+    EndOfStream,
+}
+
+impl MediaItem {
+    /// Converts the metadata, media, or end of stream carried by a server session event.  The
+    /// event's application name and stream key are not checked, so events for other streams
+    /// must be filtered out by the caller.
+    #[cfg(feature = "server-session")]
+    pub fn from_server_event(event: &ServerSessionEvent) -> Option<MediaItem> {
+        match *event {
+            ServerSessionEvent::StreamMetadataChanged { ref metadata, .. } => {
+                Some(MediaItem::Metadata(metadata.clone()))
+            }
+
+            ServerSessionEvent::VideoDataReceived {
+                ref data,
+                timestamp,
+                ..
+            } => Some(MediaItem::Video {
+                data: data.clone(),
+                timestamp,
+            }),
+
+            ServerSessionEvent::AudioDataReceived {
+                ref data,
+                timestamp,
+                ..
+            } => Some(MediaItem::Audio {
+                data: data.clone(),
+                timestamp,
+            }),
+
+            ServerSessionEvent::PublishStreamFinished { .. } => Some(MediaItem::EndOfStream),
+            _ => None,
+        }
+    }
+
+    /// Converts the metadata or media carried by a client session event
+    #[cfg(feature = "client-session")]
+    pub fn from_client_event(event: &ClientSessionEvent) -> Option<MediaItem> {
+        match *event {
+            ClientSessionEvent::StreamMetadataReceived { ref metadata } => {
+                Some(MediaItem::Metadata(metadata.clone()))
+            }
+
+            ClientSessionEvent::VideoDataReceived {
+                ref data,
+                timestamp,
+            } => Some(MediaItem::Video {
+                data: data.clone(),
+                timestamp,
+            }),
+
+            ClientSessionEvent::AudioDataReceived {
+                ref data,
+                timestamp,
+            } => Some(MediaItem::Audio {
+                data: data.clone(),
+                timestamp,
+            }),
+
+            _ => None,
+        }
+    }
+
+    /// Converts the metadata, media, or end of stream the hub routed to a subscriber
+    pub fn from_hub_result(result: &StreamHubResult) -> MediaItem {
+        match *result {
+            StreamHubResult::SendMetadata { ref metadata, .. } => {
+                MediaItem::Metadata(metadata.clone())
+            }
+
+            StreamHubResult::SendVideoData {
+                ref data,
+                timestamp,
+                ..
+            } => MediaItem::Video {
+                data: data.clone(),
+                timestamp,
+            },
+
+            StreamHubResult::SendAudioData {
+                ref data,
+                timestamp,
+                ..
+            } => MediaItem::Audio {
+                data: data.clone(),
+                timestamp,
+            },
+
+            StreamHubResult::PublishingFinished { .. } => MediaItem::EndOfStream,
+        }
+    }
+
+    /// The timestamp of audio and video items
+    pub fn timestamp(&self) -> Option<RtmpTimestamp> {
+        match *self {
+            MediaItem::Video { timestamp, .. } | MediaItem::Audio { timestamp, .. } => {
+                Some(timestamp)
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Returns if the item carries decoder configuration instead of a frame.  Sinks that start
+    /// part way through a stream need the most recent sequence headers before any frames.
+    pub fn is_sequence_header(&self) -> bool {
+        match *self {
+            MediaItem::Video { ref data, .. } => is_video_sequence_header(data),
+            MediaItem::Audio { ref data, .. } => is_audio_sequence_header(data),
+            _ => false,
+        }
+    }
+
+    /// Returns if the item is a video keyframe that playback can start from
+    pub fn is_keyframe(&self) -> bool {
+        match *self {
+            MediaItem::Video { ref data, .. } => is_video_keyframe(data),
+            _ => false,
+        }
+    }
+}
+
+impl From<CachedMedia> for MediaItem {
+    fn from(media: CachedMedia) -> MediaItem {
+        match media {
+            CachedMedia::Metadata(metadata) => MediaItem::Metadata(metadata),
+            CachedMedia::Video { data, timestamp } => MediaItem::Video { data, timestamp },
+            CachedMedia::Audio { data, timestamp } => MediaItem::Audio { data, timestamp },
+        }
+    }
+}
+
+impl From<BufferedMedia> for MediaItem {
+    fn from(media: BufferedMedia) -> MediaItem {
+        match media {
+            BufferedMedia::Metadata(metadata) => MediaItem::Metadata(metadata),
+            BufferedMedia::Video { data, timestamp } => MediaItem::Video { data, timestamp },
+            BufferedMedia::Audio { data, timestamp } => MediaItem::Audio { data, timestamp },
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "client-session", feature = "server-session")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "server-session")]
+    fn server_events_are_converted() {
+        let event = ServerSessionEvent::VideoDataReceived {
+            app_name: "live".to_string(),
+            stream_key: "key".to_string(),
+            data: Bytes::from(vec![0x17, 0]),
+            timestamp: RtmpTimestamp::new(10),
+        };
+
+        let item = MediaItem::from_server_event(&event).unwrap();
+        assert!(item.is_sequence_header());
+        assert!(!item.is_keyframe());
+        assert_eq!(item.timestamp(), Some(RtmpTimestamp::new(10)));
+
+        let event = ServerSessionEvent::PublishStreamFinished {
+            app_name: "live".to_string(),
+            stream_key: "key".to_string(),
+        };
+
+        assert_eq!(
+            MediaItem::from_server_event(&event),
+            Some(MediaItem::EndOfStream)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "client-session")]
+    fn non_media_client_events_are_ignored() {
+        let event = ClientSessionEvent::PublishRequestAccepted;
+        assert_eq!(MediaItem::from_client_event(&event), None);
+    }
+}
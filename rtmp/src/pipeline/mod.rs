@@ -0,0 +1,85 @@
+/*!
+This module contains the `MediaSource` and `MediaSink` traits, which let the pieces of a media
+server be chained together without glue code written for every pair of them.
+
+Everything that produces or consumes a single stream's media works with the same `MediaItem`s:
+metadata, audio and video payloads (in the FLV tag format RTMP media messages use), and an end
+of stream marker.  Session events and hub results convert into items, sources such as
+`PlaybackBuffer` release them as they come due, and sinks such as the FLV recorder and the HLS
+segmenter consume them.  The `MediaTee` fans a single stream out to any number of sinks, so a
+pipeline like RTMP in → FLV file + HLS + push relay is just a loop passing each item to a tee.
+
+# Examples
+```
+# extern crate bytes;
+# extern crate rml_rtmp;
+use bytes::Bytes;
+use rml_rtmp::pipeline::{MediaItem, MediaSink, MediaTee};
+use rml_rtmp::time::RtmpTimestamp;
+
+# fn main() {
+let mut tee = MediaTee::new();
+let first = tee.add_sink(Vec::new()).unwrap();
+let second = tee.add_sink(Vec::new()).unwrap();
+
+let item = MediaItem::Video {
+    data: Bytes::from(vec![0x17, 1, 0, 0, 0]),
+    timestamp: RtmpTimestamp::new(0),
+};
+
+tee.send_media(item).unwrap();
+tee.send_media(MediaItem::EndOfStream).unwrap();
+
+tee.remove_sink(first);
+assert_eq!(tee.sink_count(), 1);
+# let _ = second;
+# }
+```
+*/
+
+mod errors;
+mod item;
+mod tee;
+
+pub use self::errors::PipelineError;
+pub use self::item::MediaItem;
+pub use self::tee::{MediaSinkId, MediaTee};
+
+use std::convert::Infallible;
+use std::time::Instant;
+
+/// Something that consumes the media of a single stream, such as a recorder or muxer
+pub trait MediaSink {
+    /// The error raised when an item can't be consumed
+    type Error;
+
+    /// Consumes the next item of the stream.  Items arrive in the order they should be
+    /// presented, with sequence headers ahead of the frames that depend on them, and
+    /// `MediaItem::EndOfStream` once the stream has ended.
+    fn send_media(&mut self, item: MediaItem) -> Result<(), Self::Error>;
+
+    /// Pushes anything the sink has buffered to its output
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Something that produces the media of a single stream at the pace it should be presented,
+/// such as a playback buffer or a file being played back
+pub trait MediaSource {
+    /// The error raised when media can't be produced
+    type Error;
+
+    /// Returns all media that is due to be presented by `now`.  Sources that have finished
+    /// return `MediaItem::EndOfStream` as their last item.
+    fn poll_media(&mut self, now: Instant) -> Result<Vec<MediaItem>, Self::Error>;
+}
+
+impl MediaSink for Vec<MediaItem> {
+    type Error = Infallible;
+
+    fn send_media(&mut self, item: MediaItem) -> Result<(), Self::Error> {
+        self.push(item);
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+use super::errors::PipelineError;
+use super::item::MediaItem;
+use super::MediaSink;
+use std::error::Error as StdError;
+
+/// Identifies a sink that has been added to a `MediaTee`
+pub type MediaSinkId = usize;
+
+type BoxedError = Box<dyn StdError + Send + Sync>;
+
+/// Wraps a sink so sinks with different error types can be held together
+struct ErasedSink<S>(S);
+
+impl<S> MediaSink for ErasedSink<S>
+where
+    S: MediaSink,
+    S::Error: StdError + Send + Sync + 'static,
+{
+    type Error = BoxedError;
+
+    fn send_media(&mut self, item: MediaItem) -> Result<(), Self::Error> {
+        self.0
+            .send_media(item)
+            .map_err(|x| Box::new(x) as BoxedError)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush().map_err(|x| Box::new(x) as BoxedError)
+    }
+}
+
+/// Passes every item of a stream to each of its sinks.
+///
+/// The tee remembers the stream's most recent metadata and sequence headers, and sends them to
+/// sinks as they are added, so sinks added part way through a stream can decode the frames
+/// that follow.  These are forgotten once the stream ends.
+///
+/// A failing sink doesn't stop the item from reaching the other sinks.  The first failure is
+/// returned, and the failing sink is left in place for the application to remove if it wants.
+///
+/// Sinks aren't required to be `Send`, so a tee is neither `Send` nor `Sync` and must stay on
+/// the thread it was created on.
+pub struct MediaTee {
+    sinks: Vec<(MediaSinkId, Box<dyn MediaSink<Error = BoxedError>>)>,
+    next_sink_id: MediaSinkId,
+    metadata: Option<MediaItem>,
+    video_sequence_header: Option<MediaItem>,
+    audio_sequence_header: Option<MediaItem>,
+}
+
+impl MediaTee {
+    /// Creates a tee without any sinks
+    pub fn new() -> MediaTee {
+        MediaTee {
+            sinks: Vec::new(),
+            next_sink_id: 1,
+            metadata: None,
+            video_sequence_header: None,
+            audio_sequence_header: None,
+        }
+    }
+
+    /// Adds a sink, sending it the stream's current metadata and sequence headers
+    pub fn add_sink<S>(&mut self, sink: S) -> Result<MediaSinkId, PipelineError>
+    where
+        S: MediaSink + 'static,
+        S::Error: StdError + Send + Sync + 'static,
+    {
+        let sink_id = self.next_sink_id;
+        self.next_sink_id += 1;
+
+        let mut sink = ErasedSink(sink);
+        let cached = [
+            &self.metadata,
+            &self.video_sequence_header,
+            &self.audio_sequence_header,
+        ];
+
+        for item in cached.iter().filter_map(|x| x.as_ref()) {
+            sink.send_media(item.clone())
+                .map_err(|source| PipelineError::SinkFailed { sink_id, source })?;
+        }
+
+        self.sinks.push((sink_id, Box::new(sink)));
+        Ok(sink_id)
+    }
+
+    /// Removes a sink.  Nothing is sent to it, so the sink does not see the end of the stream.
+    pub fn remove_sink(&mut self, sink_id: MediaSinkId) {
+        self.sinks.retain(|&(id, _)| id != sink_id);
+    }
+
+    /// The number of sinks that have been added and not removed
+    pub fn sink_count(&self) -> usize {
+        self.sinks.len()
+    }
+
+    fn remember(&mut self, item: &MediaItem) {
+        match *item {
+            MediaItem::Metadata(_) => self.metadata = Some(item.clone()),
+            MediaItem::Video { .. } if item.is_sequence_header() => {
+                self.video_sequence_header = Some(item.clone());
+            }
+
+            MediaItem::Audio { .. } if item.is_sequence_header() => {
+                self.audio_sequence_header = Some(item.clone());
+            }
+
+            MediaItem::EndOfStream => {
+                self.metadata = None;
+                self.video_sequence_header = None;
+                self.audio_sequence_header = None;
+            }
+
+            _ => (),
+        }
+    }
+}
+
+impl Default for MediaTee {
+    fn default() -> Self {
+        MediaTee::new()
+    }
+}
+
+impl MediaSink for MediaTee {
+    type Error = PipelineError;
+
+    fn send_media(&mut self, item: MediaItem) -> Result<(), Self::Error> {
+        self.remember(&item);
+
+        let mut first_error = None;
+        for &mut (sink_id, ref mut sink) in self.sinks.iter_mut() {
+            if let Err(source) = sink.send_media(item.clone()) {
+                first_error.get_or_insert(PipelineError::SinkFailed { sink_id, source });
+            }
+        }
+
+        first_error.map_or(Ok(()), Err)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let mut first_error = None;
+        for &mut (sink_id, ref mut sink) in self.sinks.iter_mut() {
+            if let Err(source) = sink.flush() {
+                first_error.get_or_insert(PipelineError::SinkFailed { sink_id, source });
+            }
+        }
+
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use sessions::StreamMetadata;
+    use std::cell::RefCell;
+    use std::fmt;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use time::RtmpTimestamp;
+
+    #[derive(Debug)]
+    struct TestError;
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "test error")
+        }
+    }
+
+    impl StdError for TestError {}
+
+    struct SharedSink(Rc<RefCell<Vec<MediaItem>>>);
+
+    impl MediaSink for SharedSink {
+        type Error = TestError;
+
+        fn send_media(&mut self, item: MediaItem) -> Result<(), Self::Error> {
+            self.0.borrow_mut().push(item);
+            Ok(())
+        }
+    }
+
+    struct FailingSink;
+
+    impl MediaSink for FailingSink {
+        type Error = TestError;
+
+        fn send_media(&mut self, _item: MediaItem) -> Result<(), Self::Error> {
+            Err(TestError)
+        }
+    }
+
+    fn video(data: Vec<u8>, timestamp: u32) -> MediaItem {
+        MediaItem::Video {
+            data: Bytes::from(data),
+            timestamp: RtmpTimestamp::new(timestamp),
+        }
+    }
+
+    #[test]
+    fn items_are_sent_to_every_sink() {
+        let first = Rc::new(RefCell::new(Vec::new()));
+        let second = Rc::new(RefCell::new(Vec::new()));
+        let mut tee = MediaTee::new();
+        tee.add_sink(SharedSink(first.clone())).unwrap();
+        tee.add_sink(SharedSink(second.clone())).unwrap();
+
+        tee.send_media(video(vec![0x17, 1], 0)).unwrap();
+
+        assert_eq!(*first.borrow(), vec![video(vec![0x17, 1], 0)]);
+        assert_eq!(*second.borrow(), vec![video(vec![0x17, 1], 0)]);
+    }
+
+    #[test]
+    fn late_sinks_receive_metadata_and_sequence_headers() {
+        let metadata = MediaItem::Metadata(Arc::new(StreamMetadata::new()));
+        let mut tee = MediaTee::new();
+        tee.send_media(metadata.clone()).unwrap();
+        tee.send_media(video(vec![0x17, 0], 0)).unwrap();
+        tee.send_media(video(vec![0x17, 1], 0)).unwrap();
+
+        let items = Rc::new(RefCell::new(Vec::new()));
+        tee.add_sink(SharedSink(items.clone())).unwrap();
+
+        assert_eq!(*items.borrow(), vec![metadata, video(vec![0x17, 0], 0)]);
+    }
+
+    #[test]
+    fn end_of_stream_clears_cached_items() {
+        let mut tee = MediaTee::new();
+        tee.send_media(video(vec![0x17, 0], 0)).unwrap();
+        tee.send_media(MediaItem::EndOfStream).unwrap();
+
+        let items = Rc::new(RefCell::new(Vec::new()));
+        tee.add_sink(SharedSink(items.clone())).unwrap();
+
+        assert!(items.borrow().is_empty());
+    }
+
+    #[test]
+    fn failing_sink_does_not_block_other_sinks() {
+        let items = Rc::new(RefCell::new(Vec::new()));
+        let mut tee = MediaTee::new();
+        let failing_id = tee.add_sink(FailingSink).unwrap();
+        tee.add_sink(SharedSink(items.clone())).unwrap();
+
+        match tee.send_media(video(vec![0x17, 1], 0)) {
+            Err(PipelineError::SinkFailed { sink_id, .. }) => assert_eq!(sink_id, failing_id),
+            x => panic!("Unexpected result: {:?}", x),
+        }
+
+        assert_eq!(items.borrow().len(), 1);
+    }
+}
@@ -0,0 +1,356 @@
+use bytes::Bytes;
+use pipeline::{MediaItem, MediaSource};
+#[cfg(feature = "client-session")]
+use sessions::ClientSessionEvent;
+use sessions::StreamMetadata;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::{RtmpTimestamp, TimestampRebaser};
+
+/// Configuration options for a `PlaybackBuffer`
+#[derive(Debug, Clone)]
+pub struct PlaybackBufferConfig {
+    /// How long media is held before it's released.  Larger values absorb more jitter at the
+    /// cost of a larger delay behind the publisher.
+    pub target_latency: Duration,
+
+    /// How far behind the publisher playback can fall before the buffer skips ahead back to the
+    /// target latency
+    pub max_latency: Duration,
+}
+
+impl PlaybackBufferConfig {
+    /// Creates a configuration with a 500 millisecond target latency and a 3 second maximum
+    pub fn new() -> PlaybackBufferConfig {
+        PlaybackBufferConfig {
+            target_latency: Duration::from_millis(500),
+            max_latency: Duration::from_secs(3),
+        }
+    }
+}
+
+impl Default for PlaybackBufferConfig {
+    fn default() -> Self {
+        PlaybackBufferConfig::new()
+    }
+}
+
+/// Media released by a `PlaybackBuffer`, in the order it should be presented
+#[derive(PartialEq, Debug, Clone)]
+pub enum BufferedMedia {
+    Metadata(Arc<StreamMetadata>),
+    Video {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+    Audio {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+}
+
+/// Holds received audio and video until it's time for it to be presented.
+///
+/// The first frame is released once the target latency has passed, and every frame after it is
+/// released when the same amount of time has passed as between its timestamp and the first
+/// frame's.  Timestamps are rebased first, so jumps in the publisher's timestamps don't cause
+/// the buffer to stall.
+///
+/// If the buffer runs dry and media arrives after it was due, the buffer rebuffers by holding
+/// that media for the target latency again.  If media piles up past the maximum latency
+/// (such as after the connection stalls and then catches up), the oldest media is released
+/// immediately until playback is back to the target latency.
+///
+/// The buffer does no timing itself.  The application passes in the current time whenever
+/// media is added or released, and uses `next_release_at()` to know when to check again.
+///
+/// # Examples
+/// ```
+/// # extern crate bytes;
+/// # extern crate rml_rtmp;
+/// use bytes::Bytes;
+/// use rml_rtmp::playback::{PlaybackBuffer, PlaybackBufferConfig};
+/// use rml_rtmp::time::RtmpTimestamp;
+/// use std::time::{Duration, Instant};
+///
+/// # fn main() {
+/// let mut config = PlaybackBufferConfig::new();
+/// config.target_latency = Duration::from_millis(200);
+///
+/// let start = Instant::now();
+/// let mut buffer = PlaybackBuffer::new(config);
+/// buffer.push_video(Bytes::from(vec![0x17, 1]), RtmpTimestamp::new(1000), start);
+/// buffer.push_video(Bytes::from(vec![0x27, 1]), RtmpTimestamp::new(1033), start);
+///
+/// assert!(buffer.pop_ready(start).is_empty());
+/// assert_eq!(buffer.pop_ready(start + Duration::from_millis(200)).len(), 1);
+/// assert_eq!(buffer.next_release_at(), Some(start + Duration::from_millis(233)));
+/// # }
+/// ```
+pub struct PlaybackBuffer {
+    config: PlaybackBufferConfig,
+    queue: VecDeque<(RtmpTimestamp, BufferedMedia)>,
+    rebaser: TimestampRebaser,
+    last_timestamp: RtmpTimestamp,
+    clock: Option<(Instant, RtmpTimestamp)>,
+    rebuffer_count: u32,
+}
+
+impl PlaybackBuffer {
+    /// Creates an empty buffer
+    pub fn new(config: PlaybackBufferConfig) -> PlaybackBuffer {
+        PlaybackBuffer {
+            config,
+            queue: VecDeque::new(),
+            rebaser: TimestampRebaser::new(),
+            last_timestamp: RtmpTimestamp::new(0),
+            clock: None,
+            rebuffer_count: 0,
+        }
+    }
+
+    /// Buffers the media carried by a client session event.  Events that don't carry media are
+    /// returned so the application can handle them.
+    #[cfg(feature = "client-session")]
+    pub fn push_event(
+        &mut self,
+        event: ClientSessionEvent,
+        now: Instant,
+    ) -> Option<ClientSessionEvent> {
+        match event {
+            ClientSessionEvent::StreamMetadataReceived { metadata } => self.push_metadata(metadata),
+
+            ClientSessionEvent::VideoDataReceived { data, timestamp } => {
+                self.push_video(data, timestamp, now)
+            }
+
+            ClientSessionEvent::AudioDataReceived { data, timestamp } => {
+                self.push_audio(data, timestamp, now)
+            }
+
+            event => return Some(event),
+        }
+
+        None
+    }
+
+    /// Buffers metadata, which is released along with the media received before it
+    pub fn push_metadata(&mut self, metadata: Arc<StreamMetadata>) {
+        let timestamp = self.last_timestamp;
+        self.queue
+            .push_back((timestamp, BufferedMedia::Metadata(metadata)));
+    }
+
+    /// Buffers a video frame
+    pub fn push_video(&mut self, data: Bytes, timestamp: RtmpTimestamp, now: Instant) {
+        let timestamp = self.rebase(timestamp, now);
+        self.queue
+            .push_back((timestamp, BufferedMedia::Video { data, timestamp }));
+    }
+
+    /// Buffers an audio frame
+    pub fn push_audio(&mut self, data: Bytes, timestamp: RtmpTimestamp, now: Instant) {
+        let timestamp = self.rebase(timestamp, now);
+        self.queue
+            .push_back((timestamp, BufferedMedia::Audio { data, timestamp }));
+    }
+
+    /// Removes and returns all media that is due to be presented
+    pub fn pop_ready(&mut self, now: Instant) -> Vec<BufferedMedia> {
+        let mut ready = Vec::new();
+        while let Some(release_at) = self.next_release_at() {
+            if release_at > now {
+                break;
+            }
+
+            if let Some((_, media)) = self.queue.pop_front() {
+                ready.push(media);
+            }
+        }
+
+        ready
+    }
+
+    /// When the oldest buffered media is due to be presented, if anything is buffered
+    pub fn next_release_at(&self) -> Option<Instant> {
+        self.queue
+            .front()
+            .and_then(|&(timestamp, _)| self.release_time(timestamp))
+    }
+
+    /// The number of items that are buffered
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns if nothing is buffered
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// The span of timestamps that are buffered
+    pub fn buffered_duration(&self) -> Duration {
+        match (self.queue.front(), self.queue.back()) {
+            (Some(&(first, _)), Some(&(last, _))) => last.saturating_duration_since(first),
+            _ => Duration::from_millis(0),
+        }
+    }
+
+    /// The number of times the buffer ran dry and had to rebuffer
+    pub fn rebuffer_count(&self) -> u32 {
+        self.rebuffer_count
+    }
+
+    /// Discards everything that's buffered, so the next media received starts playback over
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.rebaser.reset();
+        self.last_timestamp = RtmpTimestamp::new(0);
+        self.clock = None;
+    }
+
+    fn rebase(&mut self, timestamp: RtmpTimestamp, now: Instant) -> RtmpTimestamp {
+        let timestamp = self.rebaser.rebase(timestamp);
+        self.last_timestamp = timestamp;
+
+        let restart_clock = match self.release_time(timestamp) {
+            None => true,
+            Some(release_at) if self.queue.is_empty() && release_at < now => {
+                self.rebuffer_count += 1;
+                true
+            }
+
+            Some(release_at) => release_at > now + self.config.max_latency,
+        };
+
+        if restart_clock {
+            self.clock = Some((now + self.config.target_latency, timestamp));
+        }
+
+        timestamp
+    }
+
+    fn release_time(&self, timestamp: RtmpTimestamp) -> Option<Instant> {
+        let (clock_time, clock_timestamp) = self.clock?;
+        match timestamp.checked_duration_since(clock_timestamp) {
+            Some(offset) => Some(clock_time + offset),
+            None => {
+                // Media from before the clock was moved forward is already overdue
+                let offset = clock_timestamp.saturating_duration_since(timestamp);
+                Some(clock_time.checked_sub(offset).unwrap_or(clock_time))
+            }
+        }
+    }
+}
+
+impl MediaSource for PlaybackBuffer {
+    type Error = Infallible;
+
+    /// Releases the media that is due, the same as `pop_ready()`.  The buffer doesn't know when
+    /// a stream ends, so it never returns `MediaItem::EndOfStream`.
+    fn poll_media(&mut self, now: Instant) -> Result<Vec<MediaItem>, Self::Error> {
+        Ok(self
+            .pop_ready(now)
+            .into_iter()
+            .map(MediaItem::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PlaybackBufferConfig {
+        PlaybackBufferConfig {
+            target_latency: Duration::from_millis(500),
+            max_latency: Duration::from_millis(1000),
+        }
+    }
+
+    fn at(start: Instant, milliseconds: u64) -> Instant {
+        start + Duration::from_millis(milliseconds)
+    }
+
+    fn frame() -> Bytes {
+        Bytes::from(vec![0x27, 1])
+    }
+
+    #[test]
+    fn media_is_released_at_timestamp_pace_after_target_latency() {
+        let start = Instant::now();
+        let mut buffer = PlaybackBuffer::new(config());
+        buffer.push_video(frame(), RtmpTimestamp::new(5000), start);
+        buffer.push_audio(frame(), RtmpTimestamp::new(5100), start);
+
+        assert!(buffer.pop_ready(at(start, 499)).is_empty());
+        assert_eq!(buffer.pop_ready(at(start, 500)).len(), 1);
+        assert!(buffer.pop_ready(at(start, 599)).is_empty());
+        assert_eq!(
+            buffer.pop_ready(at(start, 600)),
+            vec![BufferedMedia::Audio {
+                data: frame(),
+                timestamp: RtmpTimestamp::new(100),
+            }]
+        );
+    }
+
+    #[test]
+    fn late_media_after_running_dry_is_rebuffered() {
+        let start = Instant::now();
+        let mut buffer = PlaybackBuffer::new(config());
+        buffer.push_video(frame(), RtmpTimestamp::new(0), start);
+        buffer.pop_ready(at(start, 500));
+
+        buffer.push_video(frame(), RtmpTimestamp::new(100), at(start, 2000));
+
+        assert!(buffer.pop_ready(at(start, 2000)).is_empty());
+        assert_eq!(buffer.pop_ready(at(start, 2500)).len(), 1);
+        assert_eq!(buffer.rebuffer_count(), 1);
+    }
+
+    #[test]
+    fn media_past_max_latency_is_released_immediately() {
+        let start = Instant::now();
+        let mut buffer = PlaybackBuffer::new(config());
+        for timestamp in &[0, 1000, 2000, 3000] {
+            buffer.push_video(frame(), RtmpTimestamp::new(*timestamp), start);
+        }
+
+        assert_eq!(buffer.pop_ready(start).len(), 3);
+        assert_eq!(buffer.next_release_at(), Some(at(start, 500)));
+    }
+
+    #[test]
+    fn metadata_is_released_with_preceding_media() {
+        let start = Instant::now();
+        let mut buffer = PlaybackBuffer::new(config());
+        buffer.push_video(frame(), RtmpTimestamp::new(0), start);
+        buffer.push_metadata(Arc::new(StreamMetadata::new()));
+        buffer.push_video(frame(), RtmpTimestamp::new(100), start);
+
+        let released = buffer.pop_ready(at(start, 500));
+
+        assert_eq!(released.len(), 2);
+        assert_eq!(
+            released[1],
+            BufferedMedia::Metadata(Arc::new(StreamMetadata::new()))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "client-session")]
+    fn events_without_media_are_returned() {
+        let mut buffer = PlaybackBuffer::new(config());
+        let event = ClientSessionEvent::PlaybackRequestAccepted;
+
+        match buffer.push_event(event, Instant::now()) {
+            Some(ClientSessionEvent::PlaybackRequestAccepted) => (),
+            x => panic!("Expected event to be returned, instead got {:?}", x),
+        }
+
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,14 @@
+/*!
+This module contains the `PlaybackBuffer`, which paces the media received by a `ClientSession`
+so it can be presented at the rate it was published.
+
+Media rarely arrives at an even pace.  Network jitter, TCP retransmits, and servers sending a
+cached group of pictures to new players all cause frames to arrive in bursts, and players that
+render frames as soon as they arrive stutter.  The buffer holds media for a target latency and
+releases each frame when its timestamp comes due, so players only need to decode what's
+returned.
+*/
+
+mod buffer;
+
+pub use self::buffer::{BufferedMedia, PlaybackBuffer, PlaybackBufferConfig};
@@ -0,0 +1,27 @@
+//! The default source of the random data used in handshakes and RTMPT client ids
+
+/// Fills the buffer with random bytes from the operating system seeded thread local generator
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub fn fill_with_random_data(buffer: &mut [u8]) {
+    use rand::Rng;
+
+    rand::thread_rng().fill(buffer);
+}
+
+/// The source handshakes use until the application supplies one
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub const DEFAULT_SOURCE: Option<fn(&mut [u8])> = Some(fill_with_random_data);
+
+/// wasm32-unknown-unknown has no operating system to ask for random data, and anything this
+/// crate could generate on its own would be predictable.  There's no default source there, so
+/// applications have to supply one backed by the runtime, such as `crypto.getRandomValues()`.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub const DEFAULT_SOURCE: Option<fn(&mut [u8])> = None;
+
+/// Returns a random number from the default source
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub fn random_u64() -> u64 {
+    let mut bytes = [0_u8; 8];
+    fill_with_random_data(&mut bytes);
+    u64::from_be_bytes(bytes)
+}
@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+/// Doubles the delay before each reconnection attempt, up to a maximum
+pub struct ReconnectBackoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    failed_attempts: u32,
+}
+
+impl ReconnectBackoff {
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> ReconnectBackoff {
+        ReconnectBackoff {
+            initial_delay,
+            max_delay,
+            failed_attempts: 0,
+        }
+    }
+
+    /// Records a failed attempt, returning how long to wait before the next one
+    pub fn next_delay(&mut self) -> Duration {
+        let multiplier = 1u32 << self.failed_attempts.min(16);
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+
+        self.initial_delay
+            .checked_mul(multiplier)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+
+    /// Resets the delay once a connection has succeeded
+    pub fn reset(&mut self) {
+        self.failed_attempts = 0;
+    }
+}
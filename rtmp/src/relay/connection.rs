@@ -0,0 +1,127 @@
+use super::errors::RelayError;
+use handshake::{Handshake, HandshakeProcessResult, PeerType};
+use sessions::{
+    ClientSession, ClientSessionConfig, ClientSessionEvent, ClientSessionResult, StatusLevel,
+};
+
+/// Everything produced by a relay connection while handling input
+pub struct ConnectionOutput {
+    pub outbound_bytes: Vec<u8>,
+    pub events: Vec<ClientSessionEvent>,
+
+    /// If the handshake completed and the session was created while handling the input
+    pub became_ready: bool,
+}
+
+/// A connection to a remote server, which performs the handshake before handing bytes to a
+/// client session
+pub struct RelayConnection {
+    handshake: Handshake,
+    session: Option<ClientSession>,
+    config: ClientSessionConfig,
+}
+
+impl RelayConnection {
+    /// Starts the handshake, returning the bytes to send to the server
+    pub fn start(config: ClientSessionConfig) -> Result<(RelayConnection, Vec<u8>), RelayError> {
+        let mut handshake = Handshake::new(PeerType::Client);
+        let bytes = handshake.generate_outbound_p0_and_p1()?;
+        let connection = RelayConnection {
+            handshake,
+            session: None,
+            config,
+        };
+
+        Ok((connection, bytes))
+    }
+
+    pub fn handle_input(&mut self, bytes: &[u8]) -> Result<ConnectionOutput, RelayError> {
+        let mut output = ConnectionOutput {
+            outbound_bytes: Vec::new(),
+            events: Vec::new(),
+            became_ready: false,
+        };
+
+        if let Some(ref mut session) = self.session {
+            let results = session.handle_input(bytes)?;
+            add_results(results, &mut output);
+            return Ok(output);
+        }
+
+        let remaining_bytes = match self.handshake.process_bytes(bytes)? {
+            HandshakeProcessResult::InProgress { response_bytes } => {
+                output.outbound_bytes = response_bytes;
+                return Ok(output);
+            }
+
+            HandshakeProcessResult::Completed {
+                response_bytes,
+                completion,
+            } => {
+                output.outbound_bytes = response_bytes;
+                completion.remaining_bytes
+            }
+        };
+
+        let (mut session, results) = ClientSession::new(self.config.clone())?;
+        add_results(results, &mut output);
+        if !remaining_bytes.is_empty() {
+            let results = session.handle_input(&remaining_bytes)?;
+            add_results(results, &mut output);
+        }
+
+        self.session = Some(session);
+        output.became_ready = true;
+        Ok(output)
+    }
+
+    /// Gets the session, once the handshake has completed
+    pub fn session(&mut self) -> Option<&mut ClientSession> {
+        self.session.as_mut()
+    }
+}
+
+/// Adds the bytes of outbound packets and raised events to the output
+fn add_results(results: Vec<ClientSessionResult>, output: &mut ConnectionOutput) {
+    for result in results {
+        match result {
+            ClientSessionResult::OutboundResponse(packet) => {
+                output.outbound_bytes.extend_from_slice(&packet.bytes)
+            }
+
+            ClientSessionResult::RaisedEvent(event) => output.events.push(event),
+            ClientSessionResult::UnhandleableMessageReceived(_) => (),
+        }
+    }
+}
+
+/// Returns the bytes of every outbound packet in the results
+pub fn outbound_bytes(results: Vec<ClientSessionResult>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for result in results {
+        if let ClientSessionResult::OutboundResponse(packet) = result {
+            bytes.extend_from_slice(&packet.bytes);
+        }
+    }
+
+    bytes
+}
+
+/// Turns events that mean the server has refused a request into an error
+pub fn check_for_failure(event: &ClientSessionEvent) -> Result<(), RelayError> {
+    match *event {
+        ClientSessionEvent::ConnectionRequestRejected { ref description } => {
+            Err(RelayError::ConnectionRequestRejected {
+                description: description.clone(),
+            })
+        }
+
+        ClientSessionEvent::UnhandleableOnStatusCode { ref code }
+            if code.level() == StatusLevel::Error =>
+        {
+            Err(RelayError::RequestFailed { code: code.clone() })
+        }
+
+        _ => Ok(()),
+    }
+}
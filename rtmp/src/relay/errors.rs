@@ -0,0 +1,39 @@
+use handshake::HandshakeError;
+use hub::StreamHubError;
+use sessions::{ClientSessionError, StatusCode};
+use thiserror::Error;
+
+/// Errors that can occur while relaying a stream to or from a remote server.  Every error
+/// means the connection to the remote server can no longer be used, and it should be closed.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RelayError {
+    /// The handshake with the remote server failed
+    #[error("Handshake with the remote server failed: {0}")]
+    Handshake(#[from] HandshakeError),
+
+    /// The client session could not process the remote server's data or create a request
+    #[error("Client session error: {0}")]
+    Session(#[from] ClientSessionError),
+
+    /// The relayed stream could not be joined in the stream hub
+    #[error("Stream hub error: {0}")]
+    Hub(#[from] StreamHubError),
+
+    /// The remote server rejected the request to connect to the application
+    #[error("Remote server rejected the connection request: {description}")]
+    ConnectionRequestRejected { description: String },
+
+    /// The remote server responded to a play or publish request with a status code that
+    /// signals it has failed, such as `NetStream.Play.StreamNotFound`
+    #[error("Remote server responded with status code {code}")]
+    RequestFailed { code: StatusCode },
+
+    /// Bytes were received before the relay was told a connection had been opened
+    #[error("The relay is not connected")]
+    NotConnected,
+
+    /// The push relay does not have a destination with the specified id
+    #[error("No push destination exists with an id of {destination_id}")]
+    UnknownDestination { destination_id: usize },
+}
@@ -0,0 +1,27 @@
+/*!
+This module contains relays, which use client sessions to move streams between this server and
+remote RTMP servers.
+
+A `PullRelay` plays a stream from a remote server and publishes it into a local `StreamHub`,
+which is how edge servers serve streams published to an origin.  A `PushRelay` does the
+opposite, publishing a stream from the hub to any number of remote servers.
+
+Like the rest of this crate, relays perform no networking themselves.  The application opens
+the connections to remote servers and passes bytes between them and the relay, and is told
+how long to wait before reconnecting whenever a connection is lost.
+*/
+
+mod backoff;
+mod connection;
+mod errors;
+mod pull;
+mod push;
+
+#[cfg(all(test, feature = "server-session"))]
+mod test_server;
+
+pub use self::errors::RelayError;
+pub use self::pull::{PullRelay, PullRelayConfig, PullRelayResult, PullRelayState};
+pub use self::push::{
+    PushDestinationConfig, PushDestinationStatus, PushRelay, PushRelayConfig, PushRelayResult,
+};
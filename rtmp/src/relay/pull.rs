@@ -0,0 +1,346 @@
+use super::backoff::ReconnectBackoff;
+use super::connection::{check_for_failure, outbound_bytes, RelayConnection};
+use super::errors::RelayError;
+use hub::{StreamHub, StreamHubResult};
+use sessions::{ClientSession, ClientSessionConfig, ClientSessionEvent};
+use std::time::Duration;
+use time::TimestampRebaser;
+
+/// Configuration options for pulling a stream from a remote server
+#[derive(Clone)]
+pub struct PullRelayConfig {
+    /// The application on the remote server to connect to
+    pub remote_app_name: String,
+
+    /// The stream key to play from the remote server
+    pub remote_stream_key: String,
+
+    /// The application the stream is published on in the local hub
+    pub local_app_name: String,
+
+    /// The stream key the stream is published on in the local hub
+    pub local_stream_key: String,
+
+    /// The configuration of the client session used to play the remote stream
+    pub session_config: ClientSessionConfig,
+
+    /// How long to wait before the first reconnection attempt
+    pub initial_reconnect_delay: Duration,
+
+    /// The longest to wait between reconnection attempts
+    pub max_reconnect_delay: Duration,
+}
+
+impl PullRelayConfig {
+    /// Creates a configuration that republishes the remote stream under the same application
+    /// name and stream key locally
+    pub fn new(app_name: String, stream_key: String) -> PullRelayConfig {
+        PullRelayConfig {
+            local_app_name: app_name.clone(),
+            local_stream_key: stream_key.clone(),
+            remote_app_name: app_name,
+            remote_stream_key: stream_key,
+            session_config: ClientSessionConfig::new(),
+            initial_reconnect_delay: Duration::from_secs(1),
+            max_reconnect_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The stages a pull relay goes through for each connection to the remote server
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum PullRelayState {
+    /// There is no connection to the remote server
+    Disconnected,
+
+    /// The handshake is being performed with the remote server
+    Handshaking,
+
+    /// A connection to the remote application has been requested
+    Connecting,
+
+    /// Playback of the remote stream has been requested
+    RequestingPlayback,
+
+    /// The remote stream is being played and published into the hub
+    Pulling,
+}
+
+/// Something the application needs to act on for a pull relay
+#[derive(PartialEq, Debug)]
+pub enum PullRelayResult {
+    /// Bytes that need to be sent to the remote server
+    OutboundBytes(Vec<u8>),
+
+    /// Media the hub has routed to a local subscriber
+    HubResult(StreamHubResult),
+
+    /// The connection has been lost, and a new one should be opened after the delay
+    ReconnectAfter(Duration),
+}
+
+/// Plays a stream from a remote server and publishes it into a `StreamHub`, so local players
+/// can watch it as if it were published locally.  This allows edge servers to serve streams
+/// that are published to an origin server.
+///
+/// The relay does no I/O itself.  The application opens a connection to the remote server and
+/// calls `connection_opened()`, passes everything received on it to `handle_input()`, and sends
+/// every `OutboundBytes` result to the server.  Once the connection closes, or any error is
+/// returned, the application closes the connection and calls `connection_closed()`, which says
+/// how long to wait before opening a new connection.  The delay doubles for each connection
+/// that fails before playback starts.
+///
+/// The relay publishes into the hub with the connection id passed into `new()`, which must
+/// not be used by any other connection.  Local subscribers stay joined while the relay
+/// reconnects, and start receiving media again once playback resumes.  The remote stream's
+/// timestamps are rebased so the local stream's timeline continues across reconnections.
+pub struct PullRelay {
+    config: PullRelayConfig,
+    connection_id: usize,
+    state: PullRelayState,
+    connection: Option<RelayConnection>,
+    backoff: ReconnectBackoff,
+    rebaser: TimestampRebaser,
+}
+
+impl PullRelay {
+    /// Creates a relay that publishes into the hub with the specified connection id
+    pub fn new(config: PullRelayConfig, connection_id: usize) -> PullRelay {
+        let backoff =
+            ReconnectBackoff::new(config.initial_reconnect_delay, config.max_reconnect_delay);
+
+        PullRelay {
+            config,
+            connection_id,
+            state: PullRelayState::Disconnected,
+            connection: None,
+            backoff,
+            rebaser: TimestampRebaser::new(),
+        }
+    }
+
+    /// The current stage of the relay
+    pub fn state(&self) -> PullRelayState {
+        self.state
+    }
+
+    /// The id the relay publishes into the hub with
+    pub fn connection_id(&self) -> usize {
+        self.connection_id
+    }
+
+    /// Starts the handshake on a newly opened connection to the remote server, returning the
+    /// bytes to send to it
+    pub fn connection_opened(&mut self) -> Result<Vec<u8>, RelayError> {
+        let (connection, bytes) = RelayConnection::start(self.config.session_config.clone())?;
+        self.connection = Some(connection);
+        self.state = PullRelayState::Handshaking;
+        Ok(bytes)
+    }
+
+    /// Handles bytes received from the remote server, publishing any media into the hub
+    pub fn handle_input(
+        &mut self,
+        bytes: &[u8],
+        hub: &mut StreamHub,
+    ) -> Result<Vec<PullRelayResult>, RelayError> {
+        let mut output = match self.connection {
+            Some(ref mut connection) => connection.handle_input(bytes)?,
+            None => return Err(RelayError::NotConnected),
+        };
+
+        if output.became_ready {
+            self.state = PullRelayState::Connecting;
+            let app_name = self.config.remote_app_name.clone();
+            let result = self.session()?.request_connection(app_name)?;
+            output.outbound_bytes.extend(outbound_bytes(vec![result]));
+        }
+
+        let mut results = Vec::new();
+        if !output.outbound_bytes.is_empty() {
+            results.push(PullRelayResult::OutboundBytes(output.outbound_bytes));
+        }
+
+        for event in output.events {
+            check_for_failure(&event)?;
+            self.handle_event(event, hub, &mut results)?;
+        }
+
+        Ok(results)
+    }
+
+    /// Handles the connection to the remote server closing.  If the relay was publishing into
+    /// the hub it leaves it, and the returned results end with how long to wait before
+    /// reconnecting.
+    pub fn connection_closed(&mut self, hub: &mut StreamHub) -> Vec<PullRelayResult> {
+        let mut results = Vec::new();
+        if self.state == PullRelayState::Pulling {
+            results.extend(
+                hub.leave(self.connection_id)
+                    .into_iter()
+                    .map(PullRelayResult::HubResult),
+            );
+        }
+
+        self.connection = None;
+        self.state = PullRelayState::Disconnected;
+        self.rebaser.mark_discontinuity();
+        results.push(PullRelayResult::ReconnectAfter(self.backoff.next_delay()));
+        results
+    }
+
+    fn handle_event(
+        &mut self,
+        event: ClientSessionEvent,
+        hub: &mut StreamHub,
+        results: &mut Vec<PullRelayResult>,
+    ) -> Result<(), RelayError> {
+        let hub_results = match event {
+            ClientSessionEvent::ConnectionRequestAccepted => {
+                self.state = PullRelayState::RequestingPlayback;
+                let stream_key = self.config.remote_stream_key.clone();
+                let result = self.session()?.request_playback(stream_key)?;
+                results.push(PullRelayResult::OutboundBytes(outbound_bytes(vec![result])));
+                return Ok(());
+            }
+
+            ClientSessionEvent::PlaybackRequestAccepted => {
+                hub.join_as_publisher(
+                    self.connection_id,
+                    &self.config.local_app_name,
+                    &self.config.local_stream_key,
+                )?;
+
+                self.state = PullRelayState::Pulling;
+                self.backoff.reset();
+                return Ok(());
+            }
+
+            ClientSessionEvent::StreamMetadataReceived { metadata } => {
+                hub.publish_metadata(self.connection_id, metadata)?
+            }
+
+            ClientSessionEvent::VideoDataReceived { data, timestamp } => {
+                let timestamp = self.rebaser.rebase(timestamp);
+                hub.publish_video_data(self.connection_id, data, timestamp)?
+            }
+
+            ClientSessionEvent::AudioDataReceived { data, timestamp } => {
+                let timestamp = self.rebaser.rebase(timestamp);
+                hub.publish_audio_data(self.connection_id, data, timestamp)?
+            }
+
+            _ => return Ok(()),
+        };
+
+        results.extend(hub_results.into_iter().map(PullRelayResult::HubResult));
+        Ok(())
+    }
+
+    fn session(&mut self) -> Result<&mut ClientSession, RelayError> {
+        self.connection
+            .as_mut()
+            .and_then(|connection| connection.session())
+            .ok_or(RelayError::NotConnected)
+    }
+}
+
+#[cfg(all(test, feature = "server-session"))]
+mod tests {
+    use super::super::test_server::{TestServer, PLAYED_VIDEO};
+    use super::*;
+    use hub::SubscriberDropPolicy;
+
+    fn config() -> PullRelayConfig {
+        let mut config = PullRelayConfig::new("live".to_string(), "remote".to_string());
+        config.local_stream_key = "local".to_string();
+        config
+    }
+
+    /// Passes bytes back and forth between the relay and server until neither has more to send
+    fn run(
+        relay: &mut PullRelay,
+        server: &mut TestServer,
+        hub: &mut StreamHub,
+        bytes: Vec<u8>,
+    ) -> Vec<StreamHubResult> {
+        let mut hub_results = Vec::new();
+        let mut to_server = bytes;
+        while !to_server.is_empty() {
+            let to_relay = server.handle_input(&to_server);
+            to_server = Vec::new();
+            for result in relay.handle_input(&to_relay, hub).unwrap() {
+                match result {
+                    PullRelayResult::OutboundBytes(bytes) => to_server.extend(bytes),
+                    PullRelayResult::HubResult(result) => hub_results.push(result),
+                    x => panic!("Unexpected result: {:?}", x),
+                }
+            }
+        }
+
+        hub_results
+    }
+
+    #[test]
+    fn remote_stream_is_published_to_local_subscribers() {
+        let mut hub = StreamHub::new();
+        hub.join_as_subscriber(5, "live", "local", 1, SubscriberDropPolicy::DeliverAll)
+            .unwrap();
+
+        let mut relay = PullRelay::new(config(), 100);
+        let mut server = TestServer::new();
+        let bytes = relay.connection_opened().unwrap();
+        let results = run(&mut relay, &mut server, &mut hub, bytes);
+
+        assert_eq!(relay.state(), PullRelayState::Pulling);
+        assert!(hub.has_publisher("live", "local"));
+        match results[..] {
+            [StreamHubResult::SendVideoData {
+                subscriber_id: 5,
+                ref data,
+                ..
+            }] => assert_eq!(&data[..], &PLAYED_VIDEO),
+            ref x => panic!("Unexpected hub results: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn reconnect_delay_doubles_until_playback_succeeds() {
+        let mut hub = StreamHub::new();
+        let mut relay = PullRelay::new(config(), 100);
+
+        let mut delays = Vec::new();
+        for _ in 0..3 {
+            relay.connection_opened().unwrap();
+            delays.push(relay.connection_closed(&mut hub));
+        }
+
+        let bytes = relay.connection_opened().unwrap();
+        run(&mut relay, &mut TestServer::new(), &mut hub, bytes);
+        let results = relay.connection_closed(&mut hub);
+
+        let expected_delays = vec![
+            vec![PullRelayResult::ReconnectAfter(Duration::from_secs(1))],
+            vec![PullRelayResult::ReconnectAfter(Duration::from_secs(2))],
+            vec![PullRelayResult::ReconnectAfter(Duration::from_secs(4))],
+        ];
+
+        assert_eq!(delays, expected_delays);
+        assert_eq!(
+            results,
+            vec![PullRelayResult::ReconnectAfter(Duration::from_secs(1))]
+        );
+
+        assert!(!hub.has_publisher("live", "local"));
+    }
+
+    #[test]
+    fn input_before_connection_opened_is_an_error() {
+        let mut relay = PullRelay::new(config(), 100);
+
+        match relay.handle_input(&[1, 2, 3], &mut StreamHub::new()) {
+            Err(RelayError::NotConnected) => (),
+            x => panic!("Expected not connected error, instead got {:?}", x),
+        }
+    }
+}
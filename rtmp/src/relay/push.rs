@@ -0,0 +1,589 @@
+use super::backoff::ReconnectBackoff;
+use super::connection::{check_for_failure, outbound_bytes, RelayConnection};
+use super::errors::RelayError;
+use hub::media::{is_video_keyframe, is_video_sequence_header};
+use hub::SubscriberDropPolicy;
+use hub::{CachedMedia, GopCache, StreamHub, StreamHubError, StreamHubResult};
+use sessions::{
+    ClientSession, ClientSessionConfig, ClientSessionEvent, ClientSessionResult, PublishRequestType,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Configuration options for pushing a locally published stream to remote servers
+#[derive(Clone)]
+pub struct PushRelayConfig {
+    /// The application the stream is published on in the local hub
+    pub app_name: String,
+
+    /// The stream key the stream is published on in the local hub
+    pub stream_key: String,
+
+    /// How long to wait before the first reconnection attempt to a destination
+    pub initial_reconnect_delay: Duration,
+
+    /// The longest to wait between reconnection attempts to a destination
+    pub max_reconnect_delay: Duration,
+}
+
+impl PushRelayConfig {
+    /// Creates a configuration for pushing the specified local stream
+    pub fn new(app_name: String, stream_key: String) -> PushRelayConfig {
+        PushRelayConfig {
+            app_name,
+            stream_key,
+            initial_reconnect_delay: Duration::from_secs(1),
+            max_reconnect_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A remote server the stream is pushed to
+#[derive(Clone)]
+pub struct PushDestinationConfig {
+    /// The application on the remote server to connect to
+    pub app_name: String,
+
+    /// The stream key to publish on the remote server
+    pub stream_key: String,
+
+    /// The configuration of the client session used to publish to the remote server
+    pub session_config: ClientSessionConfig,
+}
+
+impl PushDestinationConfig {
+    /// Creates a destination that publishes on the specified application and stream key
+    pub fn new(app_name: String, stream_key: String) -> PushDestinationConfig {
+        PushDestinationConfig {
+            app_name,
+            stream_key,
+            session_config: ClientSessionConfig::new(),
+        }
+    }
+}
+
+/// The stages each destination of a push relay goes through for each connection
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum PushDestinationStatus {
+    /// There is no connection to the remote server
+    Disconnected,
+
+    /// The handshake is being performed with the remote server
+    Handshaking,
+
+    /// A connection to the remote application has been requested
+    Connecting,
+
+    /// Publishing on the remote stream key has been requested
+    RequestingPublish,
+
+    /// The stream is being pushed to the remote server
+    Pushing,
+}
+
+/// Something the application needs to act on for a push relay
+#[derive(Debug)]
+pub enum PushRelayResult {
+    /// Bytes that need to be sent to the destination's remote server
+    OutboundBytes {
+        destination_id: usize,
+        bytes: Vec<u8>,
+    },
+
+    /// A destination has moved to a new stage
+    StatusChanged {
+        destination_id: usize,
+        status: PushDestinationStatus,
+    },
+
+    /// The destination's connection has been lost, and a new one should be opened after the
+    /// delay
+    ReconnectAfter {
+        destination_id: usize,
+        delay: Duration,
+    },
+
+    /// The destination's session failed while pushing media.  Its connection should be closed
+    /// and `connection_closed()` called, while the other destinations carry on.
+    DestinationFailed {
+        destination_id: usize,
+        error: RelayError,
+    },
+}
+
+struct Destination {
+    config: PushDestinationConfig,
+    status: PushDestinationStatus,
+    connection: Option<RelayConnection>,
+    backoff: ReconnectBackoff,
+    has_sent_keyframe: bool,
+}
+
+/// Pushes a stream published in a local `StreamHub` to any number of remote servers, such as
+/// restreaming to several streaming services at once.
+///
+/// The relay joins the hub as a subscriber with the connection id passed into `new()`, and
+/// every hub result for that id must be passed to `handle_hub_result()`.  Each destination has
+/// its own connection, which the application opens and passes bytes to and from the same way
+/// as with a `PullRelay`.  A failing destination is reconnected with its own backoff without
+/// affecting the others.
+///
+/// Destinations that start publishing part way through the stream are sent its metadata and
+/// sequence headers, and start receiving video at the next keyframe.
+pub struct PushRelay {
+    config: PushRelayConfig,
+    connection_id: usize,
+    destinations: HashMap<usize, Destination>,
+    next_destination_id: usize,
+    cache: GopCache,
+}
+
+impl PushRelay {
+    /// Creates a relay that subscribes to the hub with the specified connection id
+    pub fn new(config: PushRelayConfig, connection_id: usize) -> PushRelay {
+        PushRelay {
+            config,
+            connection_id,
+            destinations: HashMap::new(),
+            next_destination_id: 0,
+            cache: GopCache::new(0),
+        }
+    }
+
+    /// The id the relay subscribes to the hub with
+    pub fn connection_id(&self) -> usize {
+        self.connection_id
+    }
+
+    /// Adds a remote server to push the stream to, returning the id of the destination.  The
+    /// application should then open a connection to it.
+    pub fn add_destination(&mut self, config: PushDestinationConfig) -> usize {
+        let destination = Destination {
+            config,
+            status: PushDestinationStatus::Disconnected,
+            connection: None,
+            backoff: ReconnectBackoff::new(
+                self.config.initial_reconnect_delay,
+                self.config.max_reconnect_delay,
+            ),
+            has_sent_keyframe: false,
+        };
+
+        let destination_id = self.next_destination_id;
+        self.next_destination_id += 1;
+        self.destinations.insert(destination_id, destination);
+        destination_id
+    }
+
+    /// Stops pushing to a destination.  The application should close its connection.
+    pub fn remove_destination(&mut self, destination_id: usize) {
+        self.destinations.remove(&destination_id);
+    }
+
+    /// The current stage of a destination
+    pub fn destination_status(&self, destination_id: usize) -> Option<PushDestinationStatus> {
+        self.destinations.get(&destination_id).map(|x| x.status)
+    }
+
+    /// Subscribes the relay to the local stream in the hub
+    pub fn join_hub(
+        &mut self,
+        hub: &mut StreamHub,
+    ) -> Result<Vec<PushRelayResult>, StreamHubError> {
+        let hub_results = hub.join_as_subscriber(
+            self.connection_id,
+            &self.config.app_name,
+            &self.config.stream_key,
+            0,
+            SubscriberDropPolicy::DeliverAll,
+        )?;
+
+        let mut results = Vec::new();
+        for hub_result in hub_results {
+            results.extend(self.handle_hub_result(hub_result));
+        }
+
+        Ok(results)
+    }
+
+    /// Starts the handshake on a newly opened connection to a destination, returning the bytes
+    /// to send to it
+    pub fn connection_opened(
+        &mut self,
+        destination_id: usize,
+    ) -> Result<Vec<PushRelayResult>, RelayError> {
+        let destination = match self.destinations.get_mut(&destination_id) {
+            Some(destination) => destination,
+            None => return Err(RelayError::UnknownDestination { destination_id }),
+        };
+
+        let config = destination.config.session_config.clone();
+        let (connection, bytes) = RelayConnection::start(config)?;
+        destination.connection = Some(connection);
+        destination.has_sent_keyframe = false;
+
+        let results = vec![
+            set_status(
+                destination_id,
+                destination,
+                PushDestinationStatus::Handshaking,
+            ),
+            PushRelayResult::OutboundBytes {
+                destination_id,
+                bytes,
+            },
+        ];
+
+        Ok(results)
+    }
+
+    /// Handles bytes received from a destination's remote server
+    pub fn handle_input(
+        &mut self,
+        destination_id: usize,
+        bytes: &[u8],
+    ) -> Result<Vec<PushRelayResult>, RelayError> {
+        let cache = &self.cache;
+        let destination = match self.destinations.get_mut(&destination_id) {
+            Some(destination) => destination,
+            None => return Err(RelayError::UnknownDestination { destination_id }),
+        };
+
+        let mut output = match destination.connection {
+            Some(ref mut connection) => connection.handle_input(bytes)?,
+            None => return Err(RelayError::NotConnected),
+        };
+
+        let mut results = Vec::new();
+        if output.became_ready {
+            results.push(set_status(
+                destination_id,
+                destination,
+                PushDestinationStatus::Connecting,
+            ));
+
+            let app_name = destination.config.app_name.clone();
+            let result = destination.session()?.request_connection(app_name)?;
+            output.outbound_bytes.extend(outbound_bytes(vec![result]));
+        }
+
+        for event in output.events {
+            check_for_failure(&event)?;
+            match event {
+                ClientSessionEvent::ConnectionRequestAccepted => {
+                    results.push(set_status(
+                        destination_id,
+                        destination,
+                        PushDestinationStatus::RequestingPublish,
+                    ));
+
+                    let stream_key = destination.config.stream_key.clone();
+                    let result = destination
+                        .session()?
+                        .request_publishing(stream_key, PublishRequestType::Live)?;
+
+                    output.outbound_bytes.extend(outbound_bytes(vec![result]));
+                }
+
+                ClientSessionEvent::PublishRequestAccepted => {
+                    results.push(set_status(
+                        destination_id,
+                        destination,
+                        PushDestinationStatus::Pushing,
+                    ));
+
+                    destination.backoff.reset();
+                    for item in cache.replay() {
+                        let result = destination.send(item)?;
+                        output
+                            .outbound_bytes
+                            .extend(outbound_bytes(result.into_iter().collect()));
+                    }
+                }
+
+                _ => (),
+            }
+        }
+
+        if !output.outbound_bytes.is_empty() {
+            results.push(PushRelayResult::OutboundBytes {
+                destination_id,
+                bytes: output.outbound_bytes,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Handles the connection to a destination closing, returning how long to wait before
+    /// reconnecting
+    pub fn connection_closed(&mut self, destination_id: usize) -> Vec<PushRelayResult> {
+        let destination = match self.destinations.get_mut(&destination_id) {
+            Some(destination) => destination,
+            None => return Vec::new(),
+        };
+
+        destination.connection = None;
+        vec![
+            set_status(
+                destination_id,
+                destination,
+                PushDestinationStatus::Disconnected,
+            ),
+            PushRelayResult::ReconnectAfter {
+                destination_id,
+                delay: destination.backoff.next_delay(),
+            },
+        ]
+    }
+
+    /// Pushes media the hub routed to the relay to every destination that is publishing.
+    /// Results for other subscribers are ignored.
+    pub fn handle_hub_result(&mut self, result: StreamHubResult) -> Vec<PushRelayResult> {
+        let item = match result {
+            StreamHubResult::SendMetadata {
+                subscriber_id,
+                metadata,
+                ..
+            } if subscriber_id == self.connection_id => {
+                self.cache.set_metadata(metadata.clone());
+                CachedMedia::Metadata(metadata)
+            }
+
+            StreamHubResult::SendVideoData {
+                subscriber_id,
+                data,
+                timestamp,
+                ..
+            } if subscriber_id == self.connection_id => {
+                self.cache.add_video(data.clone(), timestamp);
+                CachedMedia::Video { data, timestamp }
+            }
+
+            StreamHubResult::SendAudioData {
+                subscriber_id,
+                data,
+                timestamp,
+                ..
+            } if subscriber_id == self.connection_id => {
+                self.cache.add_audio(data.clone(), timestamp);
+                CachedMedia::Audio { data, timestamp }
+            }
+
+            StreamHubResult::PublishingFinished { subscriber_id, .. }
+                if subscriber_id == self.connection_id =>
+            {
+                self.cache.clear();
+                for destination in self.destinations.values_mut() {
+                    destination.has_sent_keyframe = false;
+                }
+
+                return Vec::new();
+            }
+
+            _ => return Vec::new(),
+        };
+
+        let mut results = Vec::new();
+        for (destination_id, destination) in self.destinations.iter_mut() {
+            if destination.status != PushDestinationStatus::Pushing {
+                continue;
+            }
+
+            match destination.send(item.clone()) {
+                Ok(Some(result)) => results.push(PushRelayResult::OutboundBytes {
+                    destination_id: *destination_id,
+                    bytes: outbound_bytes(vec![result]),
+                }),
+
+                Ok(None) => (),
+
+                Err(error) => results.push(PushRelayResult::DestinationFailed {
+                    destination_id: *destination_id,
+                    error,
+                }),
+            }
+        }
+
+        results
+    }
+}
+
+impl Destination {
+    fn session(&mut self) -> Result<&mut ClientSession, RelayError> {
+        self.connection
+            .as_mut()
+            .and_then(|connection| connection.session())
+            .ok_or(RelayError::NotConnected)
+    }
+
+    /// Publishes the item to the remote server.  Sequence headers are always sent, but video
+    /// frames are skipped until a keyframe has been sent, returning `None`.
+    fn send(&mut self, item: CachedMedia) -> Result<Option<ClientSessionResult>, RelayError> {
+        let mut can_be_dropped = true;
+        if let CachedMedia::Video { ref data, .. } = item {
+            if is_video_keyframe(data) {
+                self.has_sent_keyframe = true;
+                can_be_dropped = false;
+            } else if is_video_sequence_header(data) {
+                can_be_dropped = false;
+            } else if !self.has_sent_keyframe {
+                return Ok(None);
+            }
+        }
+
+        let session = self.session()?;
+        let result = match item {
+            CachedMedia::Metadata(metadata) => session.publish_metadata(&metadata)?,
+            CachedMedia::Video { data, timestamp } => {
+                session.publish_video_data(data, timestamp, can_be_dropped)?
+            }
+
+            CachedMedia::Audio { data, timestamp } => {
+                session.publish_audio_data(data, timestamp, can_be_dropped)?
+            }
+        };
+
+        Ok(Some(result))
+    }
+}
+
+fn set_status(
+    destination_id: usize,
+    destination: &mut Destination,
+    status: PushDestinationStatus,
+) -> PushRelayResult {
+    destination.status = status;
+    PushRelayResult::StatusChanged {
+        destination_id,
+        status,
+    }
+}
+
+#[cfg(all(test, feature = "server-session"))]
+mod tests {
+    use super::super::test_server::TestServer;
+    use super::*;
+    use bytes::Bytes;
+    use time::RtmpTimestamp;
+
+    const SEQUENCE_HEADER: [u8; 5] = [0x17, 0, 0, 0, 0];
+    const KEYFRAME: [u8; 5] = [0x17, 1, 0, 0, 0];
+    const INTERFRAME: [u8; 5] = [0x27, 1, 0, 0, 0];
+
+    /// Passes bytes back and forth between the relay and a destination's server until neither
+    /// has more to send
+    fn run(relay: &mut PushRelay, server: &mut TestServer, results: Vec<PushRelayResult>) {
+        let mut results = results;
+        loop {
+            let mut to_server = Vec::new();
+            let mut destination = None;
+            for result in results {
+                if let PushRelayResult::OutboundBytes {
+                    destination_id,
+                    bytes,
+                } = result
+                {
+                    destination = Some(destination_id);
+                    to_server.extend(bytes);
+                }
+            }
+
+            let destination_id = match destination {
+                Some(destination_id) => destination_id,
+                None => return,
+            };
+
+            let to_relay = server.handle_input(&to_server);
+            results = relay.handle_input(destination_id, &to_relay).unwrap();
+        }
+    }
+
+    fn publish(hub: &mut StreamHub, relay: &mut PushRelay, data: &[u8]) -> Vec<PushRelayResult> {
+        let data = Bytes::copy_from_slice(data);
+        let mut results = Vec::new();
+        for result in hub
+            .publish_video_data(1, data, RtmpTimestamp::new(0))
+            .unwrap()
+        {
+            results.extend(relay.handle_hub_result(result));
+        }
+
+        results
+    }
+
+    fn joined_relay(hub: &mut StreamHub) -> PushRelay {
+        let config = PushRelayConfig::new("live".to_string(), "key".to_string());
+        let mut relay = PushRelay::new(config, 100);
+        relay.join_hub(hub).unwrap();
+        relay
+    }
+
+    fn connect_destination(relay: &mut PushRelay, server: &mut TestServer) -> usize {
+        let destination = PushDestinationConfig::new("app".to_string(), "remote".to_string());
+        let destination_id = relay.add_destination(destination);
+        let results = relay.connection_opened(destination_id).unwrap();
+        run(relay, server, results);
+        destination_id
+    }
+
+    #[test]
+    fn destination_receives_sequence_header_and_starts_at_keyframe() {
+        let mut hub = StreamHub::new();
+        hub.join_as_publisher(1, "live", "key").unwrap();
+        let mut relay = joined_relay(&mut hub);
+        publish(&mut hub, &mut relay, &SEQUENCE_HEADER);
+
+        let mut server = TestServer::new();
+        let destination_id = connect_destination(&mut relay, &mut server);
+
+        let results = publish(&mut hub, &mut relay, &INTERFRAME);
+        run(&mut relay, &mut server, results);
+        let results = publish(&mut hub, &mut relay, &KEYFRAME);
+        run(&mut relay, &mut server, results);
+
+        assert_eq!(
+            relay.destination_status(destination_id),
+            Some(PushDestinationStatus::Pushing)
+        );
+
+        assert_eq!(
+            server.published_video,
+            vec![&SEQUENCE_HEADER[..], &KEYFRAME[..]]
+        );
+    }
+
+    #[test]
+    fn lost_destination_does_not_affect_others() {
+        let mut hub = StreamHub::new();
+        hub.join_as_publisher(1, "live", "key").unwrap();
+        let mut relay = joined_relay(&mut hub);
+        let mut first_server = TestServer::new();
+        let mut second_server = TestServer::new();
+        let first_id = connect_destination(&mut relay, &mut first_server);
+        let second_id = connect_destination(&mut relay, &mut second_server);
+
+        let results = relay.connection_closed(first_id);
+        let media_results = publish(&mut hub, &mut relay, &KEYFRAME);
+        run(&mut relay, &mut second_server, media_results);
+
+        match results[..] {
+            [PushRelayResult::StatusChanged {
+                status: PushDestinationStatus::Disconnected,
+                ..
+            }, PushRelayResult::ReconnectAfter { delay, .. }] => {
+                assert_eq!(delay, Duration::from_secs(1))
+            }
+
+            ref x => panic!("Unexpected results: {:?}", x),
+        }
+
+        assert_eq!(
+            relay.destination_status(second_id),
+            Some(PushDestinationStatus::Pushing)
+        );
+
+        assert!(first_server.published_video.is_empty());
+        assert_eq!(second_server.published_video, vec![&KEYFRAME[..]]);
+    }
+}
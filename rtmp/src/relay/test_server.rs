@@ -0,0 +1,93 @@
+use bytes::Bytes;
+use handshake::{Handshake, HandshakeProcessResult, PeerType};
+use sessions::{ServerSession, ServerSessionConfig, ServerSessionEvent, ServerSessionResult};
+use time::RtmpTimestamp;
+
+/// The video the test server sends to players once their request is accepted
+pub const PLAYED_VIDEO: [u8; 5] = [0x17, 1, 0, 0, 0];
+
+/// A remote server that accepts every request, sends one video frame to players, and records
+/// the video published to it
+pub struct TestServer {
+    handshake: Handshake,
+    session: Option<ServerSession>,
+    pub published_video: Vec<Bytes>,
+}
+
+impl TestServer {
+    pub fn new() -> TestServer {
+        TestServer {
+            handshake: Handshake::new(PeerType::Server),
+            session: None,
+            published_video: Vec::new(),
+        }
+    }
+
+    /// Handles bytes from the client, returning the bytes to send back
+    pub fn handle_input(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut outbound = Vec::new();
+        let mut results = match self.session {
+            Some(ref mut session) => session.handle_input(bytes).unwrap(),
+            None => match self.handshake.process_bytes(bytes).unwrap() {
+                HandshakeProcessResult::InProgress { response_bytes } => return response_bytes,
+                HandshakeProcessResult::Completed {
+                    response_bytes,
+                    completion,
+                } => {
+                    outbound.extend(response_bytes);
+                    let (mut session, mut results) =
+                        ServerSession::new(ServerSessionConfig::new()).unwrap();
+
+                    results.extend(session.handle_input(&completion.remaining_bytes).unwrap());
+                    self.session = Some(session);
+                    results
+                }
+            },
+        };
+
+        let session = self.session.as_mut().unwrap();
+        while !results.is_empty() {
+            let mut responses = Vec::new();
+            for result in results.drain(..) {
+                match result {
+                    ServerSessionResult::OutboundResponse(packet) => {
+                        outbound.extend_from_slice(&packet.bytes)
+                    }
+
+                    ServerSessionResult::RaisedEvent(ServerSessionEvent::ConnectionRequested {
+                        request_id,
+                        ..
+                    })
+                    | ServerSessionResult::RaisedEvent(
+                        ServerSessionEvent::PublishStreamRequested { request_id, .. },
+                    ) => responses.extend(session.accept_request(request_id).unwrap()),
+
+                    ServerSessionResult::RaisedEvent(ServerSessionEvent::PlayStreamRequested {
+                        request_id,
+                        stream_id,
+                        ..
+                    }) => {
+                        responses.extend(session.accept_request(request_id).unwrap());
+                        let data = Bytes::from(PLAYED_VIDEO.to_vec());
+                        let packet = session
+                            .send_video_data(stream_id, data, RtmpTimestamp::new(40), false)
+                            .unwrap();
+
+                        responses.push(ServerSessionResult::OutboundResponse(packet));
+                    }
+
+                    ServerSessionResult::RaisedEvent(ServerSessionEvent::VideoDataReceived {
+                        data,
+                        ..
+                    }) => self.published_video.push(data),
+
+                    _ => (),
+                }
+            }
+
+            results = responses;
+        }
+
+        outbound
+    }
+}
@@ -0,0 +1,175 @@
+use super::errors::RtmptError;
+use super::MIN_POLLING_DELAY;
+use bytes::Bytes;
+
+/// The client side of an RTMPT tunnel.
+///
+/// This creates the paths for each request the client needs to make and extracts the RTMP
+/// bytes from the server's responses.  Bytes produced by the client's `Handshake` and
+/// `ClientSession` are sent as the body of send requests, while idle requests are made every
+/// `polling_delay()` units (of roughly 100ms each by Flash's implementation) when there is
+/// nothing to send.
+///
+/// # Examples
+/// ```
+/// use rml_rtmp::rtmpt::RtmptClientTunnel;
+///
+/// let mut tunnel = RtmptClientTunnel::new();
+/// assert_eq!(RtmptClientTunnel::open_path(), "/open/1");
+///
+/// tunnel.handle_open_response(b"3f2a\n").unwrap();
+/// assert_eq!(tunnel.send_path().unwrap(), "/send/3f2a/1");
+///
+/// let received = tunnel.handle_response(&[1, 5, 6]).unwrap();
+/// assert_eq!(&received[..], &[5, 6]);
+/// assert_eq!(tunnel.polling_delay(), 1);
+/// ```
+pub struct RtmptClientTunnel {
+    client_id: Option<String>,
+    sequence: u64,
+    polling_delay: u8,
+}
+
+impl RtmptClientTunnel {
+    /// Creates a tunnel that has not been opened yet
+    pub fn new() -> RtmptClientTunnel {
+        RtmptClientTunnel {
+            client_id: None,
+            sequence: 0,
+            polling_delay: MIN_POLLING_DELAY,
+        }
+    }
+
+    /// The path of the request that opens the tunnel
+    pub fn open_path() -> &'static str {
+        "/open/1"
+    }
+
+    /// Reads the client id out of the response to the open request
+    pub fn handle_open_response(&mut self, body: &[u8]) -> Result<(), RtmptError> {
+        let client_id = String::from_utf8_lossy(body).trim().to_string();
+        if client_id.is_empty() {
+            return Err(RtmptError::MissingClientId);
+        }
+
+        self.client_id = Some(client_id);
+        self.sequence = 0;
+        Ok(())
+    }
+
+    /// The client id the server assigned to the tunnel, if it has been opened
+    pub fn client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+
+    /// The path of the next request that sends RTMP bytes to the server
+    pub fn send_path(&mut self) -> Result<String, RtmptError> {
+        self.next_path("send")
+    }
+
+    /// The path of the next request that polls the server for RTMP bytes
+    pub fn idle_path(&mut self) -> Result<String, RtmptError> {
+        self.next_path("idle")
+    }
+
+    /// The path of the request that closes the tunnel
+    pub fn close_path(&mut self) -> Result<String, RtmptError> {
+        self.next_path("close")
+    }
+
+    /// Reads the response to a send or idle request, returning the RTMP bytes the server sent
+    pub fn handle_response(&mut self, body: &[u8]) -> Result<Bytes, RtmptError> {
+        match body.split_first() {
+            Some((&polling_delay, data)) => {
+                self.polling_delay = polling_delay;
+                Ok(Bytes::copy_from_slice(data))
+            }
+
+            None => Err(RtmptError::MissingPollingDelay),
+        }
+    }
+
+    /// How long the server has asked the client to wait before polling again
+    pub fn polling_delay(&self) -> u8 {
+        self.polling_delay
+    }
+
+    fn next_path(&mut self, command: &str) -> Result<String, RtmptError> {
+        let client_id = match self.client_id {
+            Some(ref client_id) => client_id,
+            None => return Err(RtmptError::TunnelNotOpen),
+        };
+
+        self.sequence += 1;
+        Ok(format!("/{}/{}/{}", command, client_id, self.sequence))
+    }
+}
+
+impl Default for RtmptClientTunnel {
+    fn default() -> Self {
+        RtmptClientTunnel::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtmpt::{RtmptRequest, RtmptServerTunnels};
+
+    #[test]
+    fn requests_fail_before_tunnel_is_opened() {
+        let mut tunnel = RtmptClientTunnel::new();
+
+        match tunnel.idle_path() {
+            Err(RtmptError::TunnelNotOpen) => (),
+            x => panic!("Expected tunnel not open error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn sequence_increments_for_each_request() {
+        let mut tunnel = RtmptClientTunnel::new();
+        tunnel.handle_open_response(b"abc\n").unwrap();
+
+        assert_eq!(tunnel.send_path().unwrap(), "/send/abc/1");
+        assert_eq!(tunnel.idle_path().unwrap(), "/idle/abc/2");
+        assert_eq!(tunnel.close_path().unwrap(), "/close/abc/3");
+    }
+
+    #[test]
+    fn empty_response_is_missing_polling_delay() {
+        let mut tunnel = RtmptClientTunnel::new();
+
+        match tunnel.handle_response(&[]) {
+            Err(RtmptError::MissingPollingDelay) => (),
+            x => panic!("Expected missing polling delay error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn client_can_exchange_bytes_with_server_tunnels() {
+        let mut server = RtmptServerTunnels::new();
+        let mut client = RtmptClientTunnel::new();
+
+        let request = server
+            .handle_request(RtmptClientTunnel::open_path(), &[])
+            .unwrap();
+
+        let response = server.create_response(&request);
+        client.handle_open_response(&response.body).unwrap();
+
+        let path = client.send_path().unwrap();
+        let request = server.handle_request(&path, &[3, 4]).unwrap();
+        let client_id = client.client_id().unwrap().to_string();
+        match request {
+            RtmptRequest::Send { ref data, .. } => assert_eq!(&data[..], &[3, 4]),
+            ref x => panic!("Expected send request, instead got {:?}", x),
+        }
+
+        server.queue_outbound(&client_id, &[7]).unwrap();
+        let response = server.create_response(&request);
+        let received = client.handle_response(&response.body).unwrap();
+
+        assert_eq!(&received[..], &[7]);
+    }
+}
@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+/// Errors that can occur while handling RTMPT requests or responses
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RtmptError {
+    /// The request path isn't one of the RTMPT endpoints, or is missing the client id or
+    /// sequence number
+    #[error("Invalid RTMPT request path: {path}")]
+    InvalidPath { path: String },
+
+    /// The request is for a tunnel that doesn't exist, either because it was never opened or
+    /// because it has been closed
+    #[error("No RTMPT tunnel exists with a client id of {client_id}")]
+    UnknownClientId { client_id: String },
+
+    /// The server responded to an open request without a client id
+    #[error("The open response did not contain a client id")]
+    MissingClientId,
+
+    /// A request was made that requires the tunnel to have been opened first
+    #[error("The tunnel has not been opened")]
+    TunnelNotOpen,
+
+    /// A response to a send or idle request was empty, so it didn't contain a polling delay
+    #[error("The response did not contain a polling delay")]
+    MissingPollingDelay,
+}
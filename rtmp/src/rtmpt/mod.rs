@@ -0,0 +1,43 @@
+/*!
+This module implements the framing of RTMPT, which tunnels an RTMP connection through HTTP
+requests so clients behind firewalls that only allow web traffic can still connect.
+
+The client makes `POST` requests to a small set of endpoints:
+
+* `/open/1` creates a tunnel, and the response contains the client id used in all other requests
+* `/send/<client id>/<sequence>` carries RTMP bytes from the client in the request body
+* `/idle/<client id>/<sequence>` polls for bytes the server has waiting for the client
+* `/close/<client id>/<sequence>` closes the tunnel
+
+Responses to send and idle requests start with a single byte telling the client how long to
+wait before polling again, followed by any RTMP bytes waiting for the client.  The server grows
+this delay while there is nothing to send so idle clients don't poll constantly.
+
+Like the rest of this crate, no networking is performed here.  `RtmptServerTunnels` turns the
+paths and bodies of requests received by an HTTP server into the RTMP bytes to pass to a
+`Handshake` and `ServerSession`, and `RtmptClientTunnel` does the same for the requests made by
+an HTTP client.  Everything the tunnel carries is the same byte stream a TCP connection would,
+handshake included.
+
+`RtmptServerTunnels` isn't available on `wasm32-unknown-unknown`, as that target has no clock
+to time out idle tunnels with and no random source for client ids.
+*/
+
+mod client;
+mod errors;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+mod server;
+
+pub use self::client::RtmptClientTunnel;
+pub use self::errors::RtmptError;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use self::server::{RtmptRequest, RtmptResponse, RtmptServerTunnels};
+
+/// The content type of all RTMPT request and response bodies
+pub const CONTENT_TYPE: &str = "application/x-fcs";
+
+/// The polling delay sent to clients right after data has been exchanged
+pub const MIN_POLLING_DELAY: u8 = 0x01;
+
+/// The largest polling delay sent to clients which have been idle for a while
+pub const MAX_POLLING_DELAY: u8 = 0x21;
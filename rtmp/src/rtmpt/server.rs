@@ -0,0 +1,328 @@
+use super::errors::RtmptError;
+use super::{MAX_POLLING_DELAY, MIN_POLLING_DELAY};
+use bytes::{BufMut, Bytes, BytesMut};
+use random::random_u64;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A request made by an RTMPT client
+#[derive(PartialEq, Debug, Clone)]
+pub enum RtmptRequest {
+    /// Some clients probe with a request to `/fcs/ident2` before opening a tunnel.  This is
+    /// answered with a 404, which tells the client to carry on.
+    Ident,
+
+    /// A new tunnel has been opened for the client
+    Open { client_id: String },
+
+    /// The client is polling for data
+    Idle { client_id: String },
+
+    /// The client sent RTMP bytes, which should be passed to the tunnel's handshake or session
+    Send { client_id: String, data: Bytes },
+
+    /// The client has closed the tunnel
+    Close { client_id: String },
+}
+
+/// The response to send back for an RTMPT request, which should have a content type of
+/// `rtmpt::CONTENT_TYPE`
+#[derive(PartialEq, Debug, Clone)]
+pub struct RtmptResponse {
+    pub status_code: u16,
+    pub body: Bytes,
+}
+
+struct Tunnel {
+    outbound: BytesMut,
+    polling_delay: u8,
+    last_request_at: Instant,
+}
+
+/// Tracks the RTMPT tunnels that have been opened with a server.
+///
+/// Each request received on an RTMPT endpoint is passed into `handle_request()`, which works
+/// out what the client is asking for.  Any RTMP bytes the client sent are then processed by
+/// the tunnel's handshake or session as normal, and anything they produce is queued with
+/// `queue_outbound()`.  Finally `create_response()` creates the response to the request,
+/// including everything queued for the client so far.
+///
+/// # Examples
+/// ```
+/// use rml_rtmp::rtmpt::{RtmptRequest, RtmptServerTunnels};
+///
+/// let mut tunnels = RtmptServerTunnels::new();
+/// let request = tunnels.handle_request("/open/1", &[]).unwrap();
+/// let client_id = match request {
+///     RtmptRequest::Open { ref client_id } => client_id.clone(),
+///     x => panic!("Unexpected request: {:?}", x),
+/// };
+///
+/// let response = tunnels.create_response(&request);
+/// assert_eq!(response.body, format!("{}\n", client_id));
+///
+/// let path = format!("/send/{}/1", client_id);
+/// let request = tunnels.handle_request(&path, &[3]).unwrap();
+///
+/// // The handshake would normally respond to the bytes the client sent
+/// tunnels.queue_outbound(&client_id, &[3]).unwrap();
+///
+/// let response = tunnels.create_response(&request);
+/// assert_eq!(&response.body[..], &[1, 3]);
+/// ```
+pub struct RtmptServerTunnels {
+    tunnels: HashMap<String, Tunnel>,
+}
+
+impl RtmptServerTunnels {
+    /// Creates a new set of tunnels
+    pub fn new() -> RtmptServerTunnels {
+        RtmptServerTunnels {
+            tunnels: HashMap::new(),
+        }
+    }
+
+    /// Works out what a request received on an RTMPT endpoint is for, opening or closing
+    /// tunnels as requested
+    pub fn handle_request(&mut self, path: &str, body: &[u8]) -> Result<RtmptRequest, RtmptError> {
+        let mut segments = path.trim_start_matches('/').split('/');
+        let command = segments.next().unwrap_or("");
+        let client_id = segments.next().unwrap_or("").to_string();
+        let has_sequence = segments.next().is_some_and(|x| x.parse::<u64>().is_ok());
+
+        let request = match command {
+            "fcs" if client_id == "ident2" => return Ok(RtmptRequest::Ident),
+            "open" => {
+                let client_id = self.open_tunnel();
+                return Ok(RtmptRequest::Open { client_id });
+            }
+
+            "idle" if has_sequence => RtmptRequest::Idle { client_id },
+            "send" if has_sequence => RtmptRequest::Send {
+                client_id,
+                data: Bytes::copy_from_slice(body),
+            },
+
+            "close" if has_sequence => RtmptRequest::Close { client_id },
+            _ => {
+                return Err(RtmptError::InvalidPath {
+                    path: path.to_string(),
+                })
+            }
+        };
+
+        let client_id = match request {
+            RtmptRequest::Idle { ref client_id }
+            | RtmptRequest::Send { ref client_id, .. }
+            | RtmptRequest::Close { ref client_id } => client_id,
+            _ => unreachable!(),
+        };
+
+        match self.tunnels.get_mut(client_id) {
+            Some(tunnel) => tunnel.last_request_at = Instant::now(),
+            None => {
+                return Err(RtmptError::UnknownClientId {
+                    client_id: client_id.clone(),
+                })
+            }
+        }
+
+        if let RtmptRequest::Close { ref client_id } = request {
+            self.tunnels.remove(client_id);
+        }
+
+        Ok(request)
+    }
+
+    /// Queues RTMP bytes to be sent to the client in the response to its next request
+    pub fn queue_outbound(&mut self, client_id: &str, bytes: &[u8]) -> Result<(), RtmptError> {
+        match self.tunnels.get_mut(client_id) {
+            Some(tunnel) => {
+                tunnel.outbound.extend_from_slice(bytes);
+                Ok(())
+            }
+
+            None => Err(RtmptError::UnknownClientId {
+                client_id: client_id.to_string(),
+            }),
+        }
+    }
+
+    /// Creates the response to a request, taking any bytes queued for the client
+    pub fn create_response(&mut self, request: &RtmptRequest) -> RtmptResponse {
+        let (client_id, sent_data) = match *request {
+            RtmptRequest::Ident => return response(404, Bytes::new()),
+            RtmptRequest::Open { ref client_id } => {
+                return response(200, Bytes::from(format!("{}\n", client_id)));
+            }
+
+            RtmptRequest::Close { .. } => return response(200, Bytes::from_static(&[0])),
+            RtmptRequest::Idle { ref client_id } => (client_id, false),
+            RtmptRequest::Send {
+                ref client_id,
+                ref data,
+            } => (client_id, !data.is_empty()),
+        };
+
+        let tunnel = match self.tunnels.get_mut(client_id) {
+            Some(tunnel) => tunnel,
+            None => return response(404, Bytes::new()),
+        };
+
+        // Clients poll quickly while data is flowing, and back off more the longer it isn't
+        let outbound = tunnel.outbound.split();
+        tunnel.polling_delay = if sent_data || !outbound.is_empty() {
+            MIN_POLLING_DELAY
+        } else {
+            tunnel
+                .polling_delay
+                .saturating_mul(2)
+                .min(MAX_POLLING_DELAY)
+        };
+
+        let mut body = BytesMut::with_capacity(outbound.len() + 1);
+        body.put_u8(tunnel.polling_delay);
+        body.put_slice(&outbound);
+        response(200, body.freeze())
+    }
+
+    /// Closes every tunnel that hasn't received a request within the specified duration,
+    /// returning their client ids.  Clients that disappear without closing their tunnel
+    /// would otherwise be kept forever.
+    pub fn close_inactive_tunnels(&mut self, max_inactivity: Duration) -> Vec<String> {
+        let now = Instant::now();
+        let inactive_ids = self
+            .tunnels
+            .iter()
+            .filter(|&(_, tunnel)| now.duration_since(tunnel.last_request_at) > max_inactivity)
+            .map(|(client_id, _)| client_id.clone())
+            .collect::<Vec<_>>();
+
+        for client_id in &inactive_ids {
+            self.tunnels.remove(client_id);
+        }
+
+        inactive_ids
+    }
+
+    /// The number of tunnels currently open
+    pub fn tunnel_count(&self) -> usize {
+        self.tunnels.len()
+    }
+
+    fn open_tunnel(&mut self) -> String {
+        // Client ids are random, so one client can't guess another's id and hijack its tunnel
+        let mut client_id = format!("{:016x}", random_u64());
+        while self.tunnels.contains_key(&client_id) {
+            client_id = format!("{:016x}", random_u64());
+        }
+
+        let tunnel = Tunnel {
+            outbound: BytesMut::new(),
+            polling_delay: MIN_POLLING_DELAY,
+            last_request_at: Instant::now(),
+        };
+
+        self.tunnels.insert(client_id.clone(), tunnel);
+        client_id
+    }
+}
+
+impl Default for RtmptServerTunnels {
+    fn default() -> Self {
+        RtmptServerTunnels::new()
+    }
+}
+
+fn response(status_code: u16, body: Bytes) -> RtmptResponse {
+    RtmptResponse { status_code, body }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(tunnels: &mut RtmptServerTunnels) -> String {
+        match tunnels.handle_request("/open/1", &[]).unwrap() {
+            RtmptRequest::Open { client_id } => client_id,
+            x => panic!("Expected open request, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn ident_request_is_answered_with_not_found() {
+        let mut tunnels = RtmptServerTunnels::new();
+
+        let request = tunnels.handle_request("/fcs/ident2", &[]).unwrap();
+        let response = tunnels.create_response(&request);
+
+        assert_eq!(request, RtmptRequest::Ident);
+        assert_eq!(response.status_code, 404);
+    }
+
+    #[test]
+    fn polling_delay_grows_while_idle_and_resets_when_data_is_sent() {
+        let mut tunnels = RtmptServerTunnels::new();
+        let client_id = open(&mut tunnels);
+        let idle_path = format!("/idle/{}/1", client_id);
+
+        let mut delays = Vec::new();
+        for _ in 0..7 {
+            let request = tunnels.handle_request(&idle_path, &[]).unwrap();
+            delays.push(tunnels.create_response(&request).body[0]);
+        }
+
+        tunnels.queue_outbound(&client_id, &[9, 8]).unwrap();
+        let request = tunnels.handle_request(&idle_path, &[]).unwrap();
+        let response = tunnels.create_response(&request);
+
+        assert_eq!(delays, vec![2, 4, 8, 16, 32, 0x21, 0x21]);
+        assert_eq!(&response.body[..], &[1, 9, 8]);
+    }
+
+    #[test]
+    fn send_request_carries_client_bytes() {
+        let mut tunnels = RtmptServerTunnels::new();
+        let client_id = open(&mut tunnels);
+
+        let path = format!("/send/{}/2", client_id);
+        let request = tunnels.handle_request(&path, &[1, 2, 3]).unwrap();
+
+        let expected = RtmptRequest::Send {
+            client_id,
+            data: Bytes::from(vec![1, 2, 3]),
+        };
+
+        assert_eq!(request, expected);
+    }
+
+    #[test]
+    fn closed_tunnel_can_no_longer_be_used() {
+        let mut tunnels = RtmptServerTunnels::new();
+        let client_id = open(&mut tunnels);
+
+        let request = tunnels
+            .handle_request(&format!("/close/{}/3", client_id), &[])
+            .unwrap();
+
+        let response = tunnels.create_response(&request);
+        assert_eq!(&response.body[..], &[0]);
+        assert_eq!(tunnels.tunnel_count(), 0);
+
+        match tunnels.handle_request(&format!("/idle/{}/4", client_id), &[]) {
+            Err(RtmptError::UnknownClientId { .. }) => (),
+            x => panic!("Expected unknown client id error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn paths_without_sequence_numbers_are_invalid() {
+        let mut tunnels = RtmptServerTunnels::new();
+        let client_id = open(&mut tunnels);
+
+        match tunnels.handle_request(&format!("/idle/{}", client_id), &[]) {
+            Err(RtmptError::InvalidPath { .. }) => (),
+            x => panic!("Expected invalid path error, instead got {:?}", x),
+        }
+    }
+}
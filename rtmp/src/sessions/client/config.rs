@@ -1,11 +1,43 @@
-/// Configuration options that govern how a RTMP client session should operate
+use sessions::config_errors::{
+    check_chunk_size, check_max_outstanding_requests, check_window_ack_size, SessionConfigError,
+};
+use sessions::{system_clock, SessionClock};
+
+/// Configuration options that govern how a RTMP client session should operate.
+///
+/// Configs can be created with `ClientSessionConfig::builder()`, which checks the values before
+/// handing the config over.  New options may be added in any release, so the config can't be
+/// created with a struct literal outside of this crate.
 #[derive(Clone)]
+#[non_exhaustive]
 pub struct ClientSessionConfig {
     pub flash_version: String,
     pub playback_buffer_length_ms: u32,
     pub window_ack_size: u32,
     pub chunk_size: u32,
     pub tc_url: Option<String>,
+
+    /// The most requests that can be waiting on a response from the server at once.  Requests
+    /// beyond this are refused with an error.
+    pub max_outstanding_transactions: usize,
+
+    /// Where the session reads the time from when timestamping outbound messages.  This
+    /// defaults to the system clock, which isn't available on `wasm32-unknown-unknown`, so
+    /// applications running there should supply their own.
+    pub clock: SessionClock,
+
+    /// Records every byte the session receives and sends into a transcript, which can be
+    /// retrieved with `ClientSession::take_transcript()`.  This is off by default, as the
+    /// transcript holds on to every byte of the connection.
+    pub record_transcript: bool,
+
+    /// The most bytes the session may hold on to between reads, counting input that hasn't
+    /// been deserialized yet, the message being reassembled from multiple chunks, the chunk
+    /// headers remembered for each chunk stream, and the serializer's outbound buffer.  Input
+    /// that would take the session past its budget fails with a `MemoryBudgetExceeded` error
+    /// before it's buffered.  This defaults to `None`, which doesn't limit the session.
+    /// Transcripts aren't counted against the budget.
+    pub memory_budget: Option<usize>,
 }
 
 impl ClientSessionConfig {
@@ -17,6 +49,92 @@ impl ClientSessionConfig {
             window_ack_size: 2_500_000,
             chunk_size: 4096,
             tc_url: None,
+            max_outstanding_transactions: 64,
+            clock: system_clock,
+            record_transcript: false,
+            memory_budget: None,
         }
     }
+
+    /// Creates a builder that starts from the default values
+    pub fn builder() -> ClientSessionConfigBuilder {
+        ClientSessionConfigBuilder {
+            config: ClientSessionConfig::new(),
+        }
+    }
+}
+
+impl Default for ClientSessionConfig {
+    fn default() -> Self {
+        ClientSessionConfig::new()
+    }
+}
+
+/// Builds a `ClientSessionConfig`, checking that its values are ones a session can work with
+#[derive(Clone)]
+pub struct ClientSessionConfigBuilder {
+    config: ClientSessionConfig,
+}
+
+impl ClientSessionConfigBuilder {
+    pub fn flash_version(mut self, flash_version: String) -> Self {
+        self.config.flash_version = flash_version;
+        self
+    }
+
+    /// Must be above zero
+    pub fn playback_buffer_length_ms(mut self, playback_buffer_length_ms: u32) -> Self {
+        self.config.playback_buffer_length_ms = playback_buffer_length_ms;
+        self
+    }
+
+    /// Must be above zero
+    pub fn window_ack_size(mut self, window_ack_size: u32) -> Self {
+        self.config.window_ack_size = window_ack_size;
+        self
+    }
+
+    /// Must be between 1 and 2147483647 bytes
+    pub fn chunk_size(mut self, chunk_size: u32) -> Self {
+        self.config.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn tc_url(mut self, tc_url: Option<String>) -> Self {
+        self.config.tc_url = tc_url;
+        self
+    }
+
+    /// Must be above zero
+    pub fn max_outstanding_transactions(mut self, max_outstanding_transactions: usize) -> Self {
+        self.config.max_outstanding_transactions = max_outstanding_transactions;
+        self
+    }
+
+    pub fn clock(mut self, clock: SessionClock) -> Self {
+        self.config.clock = clock;
+        self
+    }
+
+    pub fn record_transcript(mut self, record_transcript: bool) -> Self {
+        self.config.record_transcript = record_transcript;
+        self
+    }
+
+    pub fn memory_budget(mut self, memory_budget: Option<usize>) -> Self {
+        self.config.memory_budget = memory_budget;
+        self
+    }
+
+    /// Returns the config, or the first value that was found to be invalid
+    pub fn build(self) -> Result<ClientSessionConfig, SessionConfigError> {
+        check_chunk_size(self.config.chunk_size)?;
+        check_window_ack_size(self.config.window_ack_size)?;
+        check_max_outstanding_requests(self.config.max_outstanding_transactions)?;
+        if self.config.playback_buffer_length_ms == 0 {
+            return Err(SessionConfigError::InvalidPlaybackBufferLength);
+        }
+
+        Ok(self.config)
+    }
 }
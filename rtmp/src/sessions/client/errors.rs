@@ -7,6 +7,7 @@ use thiserror::Error;
 /// Error state when a client session encounters an error
 /// Represents the type of error that occurred
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum ClientSessionError {
     /// Encountered when an error occurs while deserializing the incoming byte data
     #[error("An error occurred deserializing incoming data: {0}")]
@@ -64,52 +65,14 @@ pub enum ClientSessionError {
     /// should have a `code` property that says the type of operation the status is for.
     #[error("The server sent an onStatus message with invalid arguments")]
     InvalidOnStatusArguments,
-}
-
-// impl fmt::Display for ClientSessionError {
-//     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//         fmt::Display::fmt(&self.kind, f)
-//     }
-// }
-
-// impl Fail for ClientSessionError {
-//     fn cause(&self) -> Option<&dyn Fail> {
-//         self.kind.cause()
-//     }
-
-//     fn backtrace(&self) -> Option<&Backtrace> {
-//         self.kind.backtrace()
-//     }
-// }
 
-// impl From<ChunkSerializationError> for ClientSessionError {
-//     fn from(kind: ChunkSerializationError) -> Self {
-//         ClientSessionError {
-//             kind: ClientSessionErrorKind::ChunkSerializationError(kind),
-//         }
-//     }
-// }
+    /// A request was made while the maximum number of requests were already waiting on a
+    /// response from the server
+    #[error("The request could not be sent because {limit} requests are already outstanding")]
+    TooManyOutstandingTransactions { limit: usize },
 
-// impl From<ChunkDeserializationError> for ClientSessionError {
-//     fn from(kind: ChunkDeserializationError) -> Self {
-//         ClientSessionError {
-//             kind: ClientSessionErrorKind::ChunkDeserializationError(kind),
-//         }
-//     }
-// }
-
-// impl From<MessageSerializationError> for ClientSessionError {
-//     fn from(kind: MessageSerializationError) -> Self {
-//         ClientSessionError {
-//             kind: ClientSessionErrorKind::MessageSerializationError(kind),
-//         }
-//     }
-// }
-
-// impl From<MessageDeserializationError> for ClientSessionError {
-//     fn from(kind: MessageDeserializationError) -> Self {
-//         ClientSessionError {
-//             kind: ClientSessionErrorKind::MessageDeserializationError(kind),
-//         }
-//     }
-// }
+    /// The peer sent input that would have made the session hold on to more bytes than its
+    /// configured memory budget allows
+    #[error("Handling the input requires {required} bytes, which is over the session's memory budget of {budget} bytes")]
+    MemoryBudgetExceeded { budget: usize, required: usize },
+}
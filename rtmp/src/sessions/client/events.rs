@@ -1,6 +1,8 @@
 use bytes::Bytes;
+use messages::LazyAmf0Arguments;
 use rml_amf0::Amf0Value;
-use sessions::StreamMetadata;
+use sessions::{ClientState, StatusCode, StreamMetadata};
+use std::sync::Arc;
 use time::RtmpTimestamp;
 
 /// Events that can be raised by the client session so that custom business logic can be written
@@ -19,8 +21,9 @@ pub enum ClientSessionEvent {
     /// The server has accepted our request to publish video
     PublishRequestAccepted,
 
-    /// The server has sent over new metadata for the stream
-    StreamMetadataReceived { metadata: StreamMetadata },
+    /// The server has sent over new metadata for the stream.  The metadata is shared so it can
+    /// be relayed on without copying it.
+    StreamMetadataReceived { metadata: Arc<StreamMetadata> },
 
     /// The server has sent over video data for the stream
     VideoDataReceived {
@@ -38,8 +41,10 @@ pub enum ClientSessionEvent {
     UnhandleableAmf0Command {
         command_name: String,
         transaction_id: f64,
-        command_object: Amf0Value,
-        additional_values: Vec<Amf0Value>,
+
+        /// The command object and additional values, which are only decoded if the
+        /// application asks for them
+        arguments: LazyAmf0Arguments,
     },
 
     /// The server sent us a result to a transaction that we don't know about
@@ -50,12 +55,20 @@ pub enum ClientSessionEvent {
     },
 
     /// The server sent an `onStatus` message with a `code` property that we don't know
-    /// how to handle.
-    UnhandleableOnStatusCode { code: String },
+    /// how to handle.  Its `level()` tells if it's the server reporting a failure, such as
+    /// playback of a stream that can't be found.
+    UnhandleableOnStatusCode { code: StatusCode },
 
     /// The client has sent an acknowledgement that they have received the specified number of bytes
     AcknowledgementReceived { bytes_received: u32 },
 
     /// The client has responded to a ping request
     PingResponseReceived { timestamp: RtmpTimestamp },
+
+    /// The session moved from one state to another.  This is raised before any other results
+    /// of whatever caused the change.
+    StateChanged {
+        previous_state: ClientState,
+        new_state: ClientState,
+    },
 }
@@ -7,9 +7,10 @@ mod result;
 mod state;
 
 #[cfg(test)]
+#[allow(clippy::len_zero)]
 mod tests;
 
-pub use self::config::ClientSessionConfig;
+pub use self::config::{ClientSessionConfig, ClientSessionConfigBuilder};
 pub use self::errors::ClientSessionError;
 pub use self::events::ClientSessionEvent;
 pub use self::publish_request_type::PublishRequestType;
@@ -17,15 +18,22 @@ pub use self::result::ClientSessionResult;
 pub use self::state::ClientState;
 
 use self::outstanding_transaction::{OutstandingTransaction, TransactionPurpose};
+use super::request_slab::RequestSlab;
 use bytes::Bytes;
-use chunk_io::{ChunkDeserializer, ChunkSerializer, Packet};
-use messages::{RtmpMessage, UserControlEventType};
-use rml_amf0::Amf0Value;
-use sessions::StreamMetadata;
-use std::collections::HashMap;
+use chunk_io::{ChunkDeserializationError, ChunkDeserializer, Packet};
+use instrument::SessionSpan;
+use messages::UserControlEventType;
+use messages::{LazyAmf0Command, MessagePayload, MessageSerializationError, RtmpMessage};
+use rml_amf0;
+use rml_amf0::{take_optional_field, Amf0Object, Amf0Value, ObjectProperties};
+use sessions::handler::{HandlerSink, ResultSink};
+use sessions::status_object::StatusObject;
+use sessions::{hint_flush_at_end, ConnectProperties, SessionHandler, StatusCode, StreamMetadata};
 use std::mem;
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::Duration;
 use time::RtmpTimestamp;
+use transcript::{TranscribingSerializer, Transcript};
 
 type ClientResult = Result<Vec<ClientSessionResult>, ClientSessionError>;
 
@@ -42,26 +50,27 @@ type ClientResult = Result<Vec<ClientSessionResult>, ClientSessionError>;
 /// is is required that:
 ///
 /// * All bytes **after** the handshake has been completed are passed into the `ClientSession` in
-/// the order they were received
+///   the order they were received
 /// * All responses generated by the session are sent to the server **in order**
 /// * No extraneous bytes are passed into the session, and only bytes generated by the session are
-/// sent to the server.
+///   sent to the server.
 ///
 /// Any violation of these points have a high probability of causing RTMP chunk parsing errors
 /// by either the `ClientSession` or the peer.
 pub struct ClientSession {
-    start_time: SystemTime,
-    serializer: ChunkSerializer,
+    start_time: u64,
+    serializer: TranscribingSerializer,
     deserializer: ChunkDeserializer,
     config: ClientSessionConfig,
-    next_transaction_id: u32,
-    outstanding_transactions: HashMap<u32, OutstandingTransaction>,
+    outstanding_transactions: RequestSlab<OutstandingTransaction>,
     current_state: ClientState,
     connected_app_name: Option<String>,
     active_stream_id: Option<u32>,
     peer_window_ack_size: Option<u32>,
     bytes_received: u64,
     bytes_received_since_last_ack: u32,
+    results_buffer: Vec<ClientSessionResult>,
+    span: SessionSpan,
 }
 
 impl ClientSession {
@@ -73,18 +82,23 @@ impl ClientSession {
         config: ClientSessionConfig,
     ) -> Result<(ClientSession, Vec<ClientSessionResult>), ClientSessionError> {
         let mut session = ClientSession {
-            start_time: SystemTime::now(),
-            serializer: ChunkSerializer::new(),
+            start_time: (config.clock)(),
+            serializer: TranscribingSerializer::new(if config.record_transcript {
+                Some(config.clock)
+            } else {
+                None
+            }),
             deserializer: ChunkDeserializer::new(),
-            next_transaction_id: 1,
-            outstanding_transactions: HashMap::new(),
+            outstanding_transactions: RequestSlab::new(1, config.max_outstanding_transactions),
             current_state: ClientState::Disconnected,
             active_stream_id: None,
             connected_app_name: None,
             peer_window_ack_size: None,
             bytes_received: 0,
             bytes_received_since_last_ack: 0,
+            results_buffer: Vec::new(),
             config,
+            span: SessionSpan::new("client"),
         };
 
         let mut results = Vec::with_capacity(1);
@@ -100,6 +114,121 @@ impl ClientSession {
     /// events are returned.
     pub fn handle_input(&mut self, bytes: &[u8]) -> ClientResult {
         let mut results = Vec::new();
+        self.handle_input_into(bytes, &mut results)?;
+        Ok(results)
+    }
+
+    /// Works like `handle_input()`, but pushes the responses and events onto the end of
+    /// `results` instead of returning them.  Reusing the same vector for every read lets the
+    /// network loop process input without a heap allocation each time.
+    ///
+    /// If an error occurs, results for messages processed before the error remain in `results`.
+    pub fn handle_input_into(
+        &mut self,
+        bytes: &[u8],
+        results: &mut Vec<ClientSessionResult>,
+    ) -> Result<(), ClientSessionError> {
+        let _span = self.span.enter();
+        self.serializer.record_inbound(bytes);
+        let first_result = results.len();
+        let result = self.process_input(bytes, results);
+        hint_flush_at_end(
+            results[first_result..]
+                .iter_mut()
+                .filter_map(ClientSessionResult::packet_mut),
+        );
+
+        if let Err(ref error) = result {
+            trace_event!(warn, "Failed to handle input: {}", error);
+        }
+
+        result
+    }
+
+    /// Works like `handle_input()`, but returns an iterator over the responses and events
+    /// instead of a vector.  The results are held in a buffer the session reuses for every
+    /// call, so callers that consume the results right away don't need an allocation each time.
+    ///
+    /// If an error occurs, the results for messages processed before the error are returned
+    /// first and the error is returned last, so outbound packets that were already produced
+    /// aren't lost.  Results that aren't iterated over are dropped along with the iterator, so
+    /// it should always be run to completion.
+    pub fn handle_input_iter<'a>(
+        &'a mut self,
+        bytes: &[u8],
+    ) -> impl Iterator<Item = Result<ClientSessionResult, ClientSessionError>> + 'a {
+        let mut results = mem::take(&mut self.results_buffer);
+        results.clear();
+
+        let outcome = self.handle_input_into(bytes, &mut results);
+        self.results_buffer = results;
+
+        self.results_buffer
+            .drain(..)
+            .map(Ok)
+            .chain(outcome.err().map(Err))
+    }
+
+    /// Works like `handle_input()`, but passes the responses and events to `handler` as they
+    /// are produced instead of returning them.
+    ///
+    /// If an error occurs, the handler has already been given the results for messages
+    /// processed before the error.
+    pub fn handle_input_with<H>(
+        &mut self,
+        bytes: &[u8],
+        handler: &mut H,
+    ) -> Result<(), ClientSessionError>
+    where
+        H: SessionHandler<Event = ClientSessionEvent>,
+    {
+        let _span = self.span.enter();
+        self.serializer.record_inbound(bytes);
+        let mut sink = HandlerSink::new(handler);
+        let result = self.process_input(bytes, &mut sink);
+        sink.finish();
+
+        if let Err(ref error) = result {
+            trace_event!(warn, "Failed to handle input: {}", error);
+        }
+
+        result
+    }
+
+    /// What has been recorded since the session was created or the transcript was last
+    /// taken.  `None` is returned if the session wasn't configured to record a transcript.
+    pub fn transcript(&self) -> Option<&Transcript> {
+        self.serializer.transcript()
+    }
+
+    /// Returns what has been recorded so far, and keeps recording into a new transcript.
+    /// `None` is returned if the session wasn't configured to record a transcript.
+    pub fn take_transcript(&mut self) -> Option<Transcript> {
+        self.serializer.take_transcript()
+    }
+
+    /// Where the session is in connecting to the server and playing or publishing a stream.
+    /// `StateChanged` events are raised whenever this changes.
+    pub fn current_state(&self) -> ClientState {
+        self.current_state
+    }
+
+    /// Identifies the session in the `log` records and `tracing` spans it emits, so they can be
+    /// matched up with the application's own records for the connection
+    pub fn session_id(&self) -> u64 {
+        self.span.id()
+    }
+
+    /// The number of bytes the session is holding on to, as counted against its memory budget
+    pub fn memory_usage(&self) -> usize {
+        self.deserializer.memory_usage() + self.serializer.memory_usage()
+    }
+
+    fn process_input<S: ResultSink<ClientSessionResult>>(
+        &mut self,
+        bytes: &[u8],
+        results: &mut S,
+    ) -> Result<(), ClientSessionError> {
         self.bytes_received += bytes.len() as u64;
 
         if let Some(peer_ack_size) = self.peer_window_ack_size {
@@ -116,44 +245,57 @@ impl ClientSession {
             }
         }
 
+        // Whatever the serializer is holding on to comes out of the budget first, and the
+        // deserializer gets the rest
+        let memory_limit = self
+            .config
+            .memory_budget
+            .map(|budget| budget.saturating_sub(self.serializer.memory_usage()));
+        self.deserializer.set_memory_limit(memory_limit);
+
         let mut bytes_to_process = bytes;
         loop {
-            match self.deserializer.get_next_message(bytes_to_process)? {
+            match self.read_next_message(bytes_to_process)? {
                 None => break, // no more messages
                 Some(payload) => {
+                    bytes_to_process = &[];
+
+                    if let Some(command) = LazyAmf0Command::from_payload(&payload)? {
+                        results.extend(self.handle_amf0_command(command)?);
+                        continue;
+                    }
+
                     let message = payload.to_rtmp_message()?;
-                    let mut message_results = match message {
+                    let message_results = match message {
                         RtmpMessage::Acknowledgement { sequence_number } => {
                             self.handle_acknowledgement(sequence_number)?
                         }
 
-                        RtmpMessage::Amf0Command {
-                            command_name,
-                            transaction_id,
-                            command_object,
-                            additional_arguments,
-                        } => self.handle_amf0_command(
-                            command_name,
-                            transaction_id,
-                            command_object,
-                            additional_arguments,
-                        )?,
-
                         RtmpMessage::Amf0Data { values } => {
                             self.handle_amf0_data(values, payload.message_stream_id)?
                         }
 
-                        RtmpMessage::AudioData { data } => self.handle_audio_data(
-                            payload.message_stream_id,
-                            data,
-                            payload.timestamp,
-                        )?,
+                        // Media makes up most messages, so its events are pushed directly
+                        // instead of going through a vector of their own
+                        RtmpMessage::AudioData { data } => {
+                            results.extend(self.handle_audio_data(
+                                payload.message_stream_id,
+                                data,
+                                payload.timestamp,
+                            )?);
 
-                        RtmpMessage::VideoData { data } => self.handle_video_data(
-                            payload.message_stream_id,
-                            data,
-                            payload.timestamp,
-                        )?,
+                            continue;
+                        }
+
+                        RtmpMessage::VideoData { data } => {
+                            results.extend(self.handle_video_data(
+                                payload.message_stream_id,
+                                data,
+                                payload.timestamp,
+                            )?);
+
+                            continue;
+                        }
 
                         RtmpMessage::UserControl {
                             event_type,
@@ -173,16 +315,23 @@ impl ClientSession {
 
                         RtmpMessage::SetChunkSize { size } => self.handle_set_chunk_size(size)?,
 
-                        _ => vec![ClientSessionResult::UnhandleableMessageReceived(payload)],
+                        _ => {
+                            trace_event!(
+                                debug,
+                                "Received message of type {} that can't be handled",
+                                payload.type_id
+                            );
+
+                            vec![ClientSessionResult::UnhandleableMessageReceived(payload)]
+                        }
                     };
 
-                    results.append(&mut message_results);
-                    bytes_to_process = &[];
+                    results.extend(message_results);
                 }
             }
         }
 
-        Ok(results)
+        Ok(())
     }
 
     /// Forms an RTMP message requesting a connection to the specified application on the server.
@@ -198,37 +347,39 @@ impl ClientSession {
             }
         }
 
-        let transaction_id = self.get_next_transaction_id();
+        let _span = self.span.enter();
+        self.span.record_app_name(&app_name);
+        trace_event!(info, "Requesting connection to app {}", app_name);
+
         let transaction = OutstandingTransaction::ConnectionRequested {
             app_name: app_name.clone(),
         };
-        self.outstanding_transactions
-            .insert(transaction_id, transaction);
-
-        let mut properties = HashMap::new();
-        properties.insert("app".to_string(), Amf0Value::Utf8String(app_name));
-        properties.insert(
-            "flashVer".to_string(),
-            Amf0Value::Utf8String(self.config.flash_version.clone()),
-        );
-        properties.insert("objectEncoding".to_string(), Amf0Value::Number(0.0));
+        let transaction_id = self.add_outstanding_transaction(transaction)?;
 
-        // Some implementations require a tcUrl to be sent up with the connection request
-        match &self.config.tc_url {
-            Some(tc_url) => {
-                properties.insert("tcUrl".to_string(), Amf0Value::Utf8String(tc_url.clone()));
-            }
-            None => (),
-        };
+        let mut properties = ConnectProperties::new(app_name);
+        properties.flash_ver = Some(self.config.flash_version.clone());
+        properties.object_encoding = Some(0.0);
 
-        let message = RtmpMessage::Amf0Command {
-            command_name: "connect".to_string(),
-            command_object: Amf0Value::Object(properties),
-            additional_arguments: vec![],
-            transaction_id: transaction_id as f64,
+        // Some implementations require a tcUrl to be sent up with the connection request
+        properties.tc_url = self.config.tc_url.clone();
+
+        let command = vec![
+            Amf0Value::from("connect"),
+            Amf0Value::Number(transaction_id as f64),
+        ];
+
+        let mut data = rml_amf0::serialize(&command).map_err(MessageSerializationError::from)?;
+        properties
+            .serialize_amf0(&mut data)
+            .map_err(MessageSerializationError::from)?;
+
+        let payload = MessagePayload {
+            timestamp: self.get_epoch(),
+            type_id: 20,
+            message_stream_id: 0,
+            data: Bytes::from(data),
         };
 
-        let payload = message.into_message_payload(self.get_epoch(), 0)?;
         let packet = self.serializer.serialize(&payload, false, false)?;
 
         Ok(ClientSessionResult::OutboundResponse(packet))
@@ -245,18 +396,19 @@ impl ClientSession {
             ClientState::Connected => (),
             _ => {
                 return Err(ClientSessionError::SessionInInvalidState {
-                    current_state: self.current_state.clone(),
+                    current_state: self.current_state,
                 });
             }
         }
 
-        let transaction_id = self.get_next_transaction_id();
+        let _span = self.span.enter();
+        self.span.record_stream_key(&stream_key);
+        trace_event!(info, "Requesting playback");
+
         let transaction = OutstandingTransaction::CreateStream {
             purpose: TransactionPurpose::PlayRequest { stream_key },
         };
-
-        self.outstanding_transactions
-            .insert(transaction_id, transaction);
+        let transaction_id = self.add_outstanding_transaction(transaction)?;
 
         let message = RtmpMessage::Amf0Command {
             command_name: "createStream".to_string(),
@@ -282,21 +434,22 @@ impl ClientSession {
             ClientState::Connected => (),
             _ => {
                 return Err(ClientSessionError::SessionInInvalidState {
-                    current_state: self.current_state.clone(),
+                    current_state: self.current_state,
                 });
             }
         }
 
-        let transaction_id = self.get_next_transaction_id();
+        let _span = self.span.enter();
+        self.span.record_stream_key(&stream_key);
+        trace_event!(info, "Requesting to publish");
+
         let transaction = OutstandingTransaction::CreateStream {
             purpose: TransactionPurpose::PublishRequest {
                 stream_key,
                 request_type: publish_type,
             },
         };
-
-        self.outstanding_transactions
-            .insert(transaction_id, transaction);
+        let transaction_id = self.add_outstanding_transaction(transaction)?;
 
         let message = RtmpMessage::Amf0Command {
             command_name: "createStream".to_string(),
@@ -316,14 +469,15 @@ impl ClientSession {
     pub fn stop_playback(&mut self) -> ClientResult {
         // Validate we are in a state to do this
         match self.current_state {
-            ClientState::Playing { .. } => (),
-            ClientState::PlayRequested { .. } => (),
+            ClientState::Playing => (),
+            ClientState::PlayRequested => (),
             _ => return Ok(Vec::new()), // Nothing to stop since we aren't performing playback
         }
 
-        self.current_state = ClientState::Connected;
-        match mem::replace(&mut self.active_stream_id, None) {
-            None => Ok(Vec::new()), // Should never happen since we should always have a valid stream id
+        let _span = self.span.enter();
+        let state_change = self.set_state(ClientState::Connected);
+        match self.active_stream_id.take() {
+            None => Ok(vec![state_change]), // Should never happen since we should always have a valid stream id
             Some(stream_id) => {
                 let message = RtmpMessage::Amf0Command {
                     command_name: "deleteStream".to_string(),
@@ -334,7 +488,10 @@ impl ClientSession {
 
                 let payload = message.into_message_payload(self.get_epoch(), stream_id)?;
                 let packet = self.serializer.serialize(&payload, false, false)?;
-                Ok(vec![ClientSessionResult::OutboundResponse(packet)])
+                Ok(vec![
+                    state_change,
+                    ClientSessionResult::OutboundResponse(packet),
+                ])
             }
         }
     }
@@ -344,14 +501,15 @@ impl ClientSession {
     pub fn stop_publishing(&mut self) -> ClientResult {
         // Validate we are in a state to do this
         match self.current_state {
-            ClientState::Publishing { .. } => (),
-            ClientState::PublishRequested { .. } => (),
+            ClientState::Publishing => (),
+            ClientState::PublishRequested => (),
             _ => return Ok(Vec::new()), // Nothing to stop since we aren't performing playback
         }
 
-        self.current_state = ClientState::Connected;
-        match mem::replace(&mut self.active_stream_id, None) {
-            None => Ok(Vec::new()), // Should never happen since we should always have a valid stream id
+        let _span = self.span.enter();
+        let state_change = self.set_state(ClientState::Connected);
+        match self.active_stream_id.take() {
+            None => Ok(vec![state_change]), // Should never happen since we should always have a valid stream id
             Some(stream_id) => {
                 let message = RtmpMessage::Amf0Command {
                     command_name: "deleteStream".to_string(),
@@ -362,7 +520,10 @@ impl ClientSession {
 
                 let payload = message.into_message_payload(self.get_epoch(), stream_id)?;
                 let packet = self.serializer.serialize(&payload, false, false)?;
-                Ok(vec![ClientSessionResult::OutboundResponse(packet)])
+                Ok(vec![
+                    state_change,
+                    ClientSessionResult::OutboundResponse(packet),
+                ])
             }
         }
     }
@@ -374,7 +535,7 @@ impl ClientSession {
             event_type: UserControlEventType::PingRequest,
             buffer_length: None,
             stream_id: None,
-            timestamp: Some(current_epoch.clone()),
+            timestamp: Some(current_epoch),
         };
 
         let payload = message.into_message_payload(self.get_epoch(), 0)?;
@@ -392,7 +553,7 @@ impl ClientSession {
             ClientState::Publishing => (),
             _ => {
                 return Err(ClientSessionError::SessionInInvalidState {
-                    current_state: self.current_state.clone(),
+                    current_state: self.current_state,
                 });
             }
         }
@@ -404,7 +565,7 @@ impl ClientSession {
             }
         };
 
-        let mut properties = HashMap::new();
+        let mut properties = ObjectProperties::new();
         if let Some(x) = metadata.video_width {
             properties.insert("width".to_string(), Amf0Value::Number(x as f64));
         }
@@ -474,7 +635,7 @@ impl ClientSession {
             ClientState::Publishing => (),
             _ => {
                 return Err(ClientSessionError::SessionInInvalidState {
-                    current_state: self.current_state.clone(),
+                    current_state: self.current_state,
                 });
             }
         }
@@ -503,7 +664,7 @@ impl ClientSession {
             ClientState::Publishing => (),
             _ => {
                 return Err(ClientSessionError::SessionInInvalidState {
-                    current_state: self.current_state.clone(),
+                    current_state: self.current_state,
                 });
             }
         }
@@ -526,28 +687,28 @@ impl ClientSession {
         stream_id: u32,
         data: Bytes,
         timestamp: RtmpTimestamp,
-    ) -> ClientResult {
+    ) -> Result<Option<ClientSessionResult>, ClientSessionError> {
         // PlayRequested state is allowed because some servers send video data prior to the
         // `NetStream.Play.Start` command.
         match self.current_state {
-            ClientState::PlayRequested { .. } => (),
-            ClientState::Playing { .. } => (),
+            ClientState::PlayRequested => (),
+            ClientState::Playing => (),
             _ => {
                 return Err(ClientSessionError::SessionInInvalidState {
-                    current_state: self.current_state.clone(),
+                    current_state: self.current_state,
                 });
             }
         }
 
         // Validate we are active on the stream this message came from
         match self.active_stream_id {
-            None => return Ok(Vec::new()), // not active on any stream
-            Some(active_stream_id) if active_stream_id != stream_id => return Ok(Vec::new()), // not active on this stream
+            None => return Ok(None), // not active on any stream
+            Some(active_stream_id) if active_stream_id != stream_id => return Ok(None), // not active on this stream
             Some(_) => (),
         }
 
         let event = ClientSessionEvent::VideoDataReceived { data, timestamp };
-        Ok(vec![ClientSessionResult::RaisedEvent(event)])
+        Ok(Some(ClientSessionResult::RaisedEvent(event)))
     }
 
     fn handle_audio_data(
@@ -555,32 +716,32 @@ impl ClientSession {
         stream_id: u32,
         data: Bytes,
         timestamp: RtmpTimestamp,
-    ) -> ClientResult {
+    ) -> Result<Option<ClientSessionResult>, ClientSessionError> {
         // PlayRequested state is allowed because some servers send audio data prior to the
         // `NetStream.Play.Start` command.
         match self.current_state {
-            ClientState::PlayRequested { .. } => (),
-            ClientState::Playing { .. } => (),
+            ClientState::PlayRequested => (),
+            ClientState::Playing => (),
             _ => {
                 return Err(ClientSessionError::SessionInInvalidState {
-                    current_state: self.current_state.clone(),
+                    current_state: self.current_state,
                 });
             }
         }
 
         // Validate we are active on the stream this message came from
         match self.active_stream_id {
-            None => return Ok(Vec::new()), // not active on any stream
-            Some(active_stream_id) if active_stream_id != stream_id => return Ok(Vec::new()), // not active on this stream
+            None => return Ok(None), // not active on any stream
+            Some(active_stream_id) if active_stream_id != stream_id => return Ok(None), // not active on this stream
             Some(_) => (),
         }
 
         let event = ClientSessionEvent::AudioDataReceived { data, timestamp };
-        Ok(vec![ClientSessionResult::RaisedEvent(event)])
+        Ok(Some(ClientSessionResult::RaisedEvent(event)))
     }
 
     fn handle_amf0_data(&mut self, mut data: Vec<Amf0Value>, stream_id: u32) -> ClientResult {
-        if data.len() == 0 {
+        if data.is_empty() {
             // No data so just do nothing
             return Ok(Vec::new());
         }
@@ -602,32 +763,49 @@ impl ClientSession {
         }
     }
 
-    fn handle_amf0_command(
-        &mut self,
-        name: String,
-        transaction_id: f64,
-        command_object: Amf0Value,
-        additional_args: Vec<Amf0Value>,
-    ) -> ClientResult {
+    fn handle_amf0_command(&mut self, command: LazyAmf0Command) -> ClientResult {
+        let LazyAmf0Command {
+            command_name: name,
+            transaction_id,
+            arguments,
+        } = command;
+
+        trace_event!(
+            debug,
+            "Handling {} command (transaction {})",
+            name,
+            transaction_id
+        );
+
         match name.as_str() {
-            "_result" => self.handle_amf0_command_success_result(
-                transaction_id,
-                command_object,
-                additional_args,
-            ),
-            "_error" => self.handle_amf0_command_failed_result(
-                transaction_id,
-                command_object,
-                additional_args,
-            ),
-            "onStatus" => self.handle_on_status_command(additional_args),
+            "_result" => {
+                let (command_object, additional_args) = arguments.decode()?;
+                self.handle_amf0_command_success_result(
+                    transaction_id,
+                    command_object,
+                    additional_args,
+                )
+            }
+
+            "_error" => {
+                let (command_object, additional_args) = arguments.decode()?;
+                self.handle_amf0_command_failed_result(
+                    transaction_id,
+                    command_object,
+                    additional_args,
+                )
+            }
+
+            "onStatus" => {
+                let (_, additional_args) = arguments.decode()?;
+                self.handle_on_status_command(additional_args)
+            }
 
             _ => {
                 let event = ClientSessionEvent::UnhandleableAmf0Command {
                     command_name: name,
-                    additional_values: additional_args,
-                    command_object,
                     transaction_id,
+                    arguments,
                 };
 
                 Ok(vec![ClientSessionResult::RaisedEvent(event)])
@@ -641,45 +819,50 @@ impl ClientSession {
         command_object: Amf0Value,
         mut additional_args: Vec<Amf0Value>,
     ) -> ClientResult {
-        let outstanding_transaction = match self
-            .outstanding_transactions
-            .remove(&(transaction_id as u32))
-        {
-            Some(transaction) => transaction,
-            None => {
-                let event = ClientSessionEvent::UnknownTransactionResultReceived {
-                    additional_values: additional_args,
-                    command_object,
-                    transaction_id,
-                };
+        let outstanding_transaction =
+            match self.outstanding_transactions.remove(transaction_id as u32) {
+                Some(transaction) => transaction,
+                None => {
+                    trace_event!(
+                        warn,
+                        "Received result for unknown transaction {}",
+                        transaction_id
+                    );
+
+                    let event = ClientSessionEvent::UnknownTransactionResultReceived {
+                        additional_values: additional_args,
+                        command_object,
+                        transaction_id,
+                    };
 
-                return Ok(vec![ClientSessionResult::RaisedEvent(event)]);
-            }
-        };
+                    return Ok(vec![ClientSessionResult::RaisedEvent(event)]);
+                }
+            };
 
         match outstanding_transaction {
             OutstandingTransaction::ConnectionRequested { app_name: _ } => {
-                let description = if additional_args.len() > 0 {
-                    if let Amf0Value::Object(mut properties) = additional_args.remove(0) {
-                        if let Some(Amf0Value::Utf8String(value)) = properties.remove("description")
-                        {
-                            value
-                        } else {
-                            "".to_string()
+                let description = if !additional_args.is_empty() {
+                    match additional_args.remove(0) {
+                        Amf0Value::Object(mut properties) => {
+                            match take_optional_field(&mut properties, "description") {
+                                Ok(Some(value)) => value,
+                                _ => "".to_string(),
+                            }
                         }
-                    } else {
-                        "".to_string()
+
+                        _ => "".to_string(),
                     }
                 } else {
                     "".to_string()
                 };
 
+                trace_event!(info, "Connection request rejected: {}", description);
                 let event = ClientSessionEvent::ConnectionRequestRejected { description };
                 Ok(vec![ClientSessionResult::RaisedEvent(event)])
             }
 
             OutstandingTransaction::CreateStream { purpose: _ } => {
-                return Err(ClientSessionError::CreateStreamFailed);
+                Err(ClientSessionError::CreateStreamFailed)
             }
         }
     }
@@ -690,25 +873,23 @@ impl ClientSession {
         command_object: Amf0Value,
         additional_args: Vec<Amf0Value>,
     ) -> ClientResult {
-        let outstanding_transaction = match self
-            .outstanding_transactions
-            .remove(&(transaction_id as u32))
-        {
-            Some(transaction) => transaction,
-            None => {
-                let event = ClientSessionEvent::UnknownTransactionResultReceived {
-                    additional_values: additional_args,
-                    command_object,
-                    transaction_id,
-                };
+        let outstanding_transaction =
+            match self.outstanding_transactions.remove(transaction_id as u32) {
+                Some(transaction) => transaction,
+                None => {
+                    let event = ClientSessionEvent::UnknownTransactionResultReceived {
+                        additional_values: additional_args,
+                        command_object,
+                        transaction_id,
+                    };
 
-                return Ok(vec![ClientSessionResult::RaisedEvent(event)]);
-            }
-        };
+                    return Ok(vec![ClientSessionResult::RaisedEvent(event)]);
+                }
+            };
 
         match outstanding_transaction {
             OutstandingTransaction::ConnectionRequested { app_name } => {
-                self.current_state = ClientState::Connected;
+                let state_change = self.set_state(ClientState::Connected);
                 self.connected_app_name = Some(app_name);
 
                 let message = RtmpMessage::WindowAcknowledgement {
@@ -718,13 +899,14 @@ impl ClientSession {
                 let packet = self.serializer.serialize(&payload, false, false)?;
                 let event = ClientSessionEvent::ConnectionRequestAccepted;
                 Ok(vec![
+                    state_change,
                     ClientSessionResult::OutboundResponse(packet),
                     ClientSessionResult::RaisedEvent(event),
                 ])
             }
 
             OutstandingTransaction::CreateStream { purpose } => {
-                if additional_args.len() == 0 {
+                if additional_args.is_empty() {
                     return Err(ClientSessionError::CreateStreamResponseHadNoStreamNumber);
                 }
 
@@ -739,7 +921,7 @@ impl ClientSession {
 
                 match purpose {
                     TransactionPurpose::PlayRequest { stream_key } => {
-                        self.current_state = ClientState::PlayRequested;
+                        let state_change = self.set_state(ClientState::PlayRequested);
 
                         let buffer_message = RtmpMessage::UserControl {
                             event_type: UserControlEventType::SetBufferLength,
@@ -765,6 +947,7 @@ impl ClientSession {
                         let play_packet = self.serializer.serialize(&play_payload, false, false)?;
 
                         Ok(vec![
+                            state_change,
                             ClientSessionResult::OutboundResponse(buffer_packet),
                             ClientSessionResult::OutboundResponse(play_packet),
                         ])
@@ -774,7 +957,7 @@ impl ClientSession {
                         stream_key,
                         request_type,
                     } => {
-                        self.current_state = ClientState::PublishRequested;
+                        let state_change = self.set_state(ClientState::PublishRequested);
 
                         let publish_type_string = match request_type {
                             PublishRequestType::Live => "live".to_string(),
@@ -796,7 +979,10 @@ impl ClientSession {
                             publish_message.into_message_payload(self.get_epoch(), stream_id)?;
                         let publish_packet =
                             self.serializer.serialize(&publish_payload, false, false)?;
-                        Ok(vec![ClientSessionResult::OutboundResponse(publish_packet)])
+                        Ok(vec![
+                            state_change,
+                            ClientSessionResult::OutboundResponse(publish_packet),
+                        ])
                     }
                 }
             }
@@ -804,33 +990,21 @@ impl ClientSession {
     }
 
     fn handle_on_status_command(&mut self, mut arguments: Vec<Amf0Value>) -> ClientResult {
-        if arguments.len() < 1 {
+        if arguments.is_empty() {
             return Err(ClientSessionError::InvalidOnStatusArguments);
         }
 
-        let mut properties = match arguments.remove(0) {
-            Amf0Value::Object(properties) => properties,
-            _ => {
-                return Err(ClientSessionError::InvalidOnStatusArguments);
-            }
-        };
-
-        let code = match properties.remove("code") {
-            Some(Amf0Value::Utf8String(code)) => code,
-
-            _ => {
-                return Err(ClientSessionError::InvalidOnStatusArguments);
-            }
+        let status = match StatusObject::from_amf0_value(arguments.remove(0)) {
+            Ok(status) => status,
+            Err(_) => return Err(ClientSessionError::InvalidOnStatusArguments),
         };
 
-        match code.as_ref() {
-            "NetStream.Play.Start" => self.handle_play_start(),
-            "NetStream.Publish.Start" => self.handle_publish_start(),
+        match status.code {
+            StatusCode::PlayStart => self.handle_play_start(),
+            StatusCode::PublishStart => self.handle_publish_start(),
 
-            x => {
-                let event = ClientSessionEvent::UnhandleableOnStatusCode {
-                    code: x.to_string(),
-                };
+            code => {
+                let event = ClientSessionEvent::UnhandleableOnStatusCode { code };
                 Ok(vec![ClientSessionResult::RaisedEvent(event)])
             }
         }
@@ -841,15 +1015,14 @@ impl ClientSession {
             ClientState::PlayRequested => (),
             _ => {
                 return Err(ClientSessionError::SessionInInvalidState {
-                    current_state: self.current_state.clone(),
+                    current_state: self.current_state,
                 });
             }
         };
 
-        self.current_state = ClientState::Playing;
-
+        let state_change = self.set_state(ClientState::Playing);
         let event = ClientSessionEvent::PlaybackRequestAccepted;
-        Ok(vec![ClientSessionResult::RaisedEvent(event)])
+        Ok(vec![state_change, ClientSessionResult::RaisedEvent(event)])
     }
 
     fn handle_publish_start(&mut self) -> ClientResult {
@@ -857,18 +1030,18 @@ impl ClientSession {
             ClientState::PublishRequested => (),
             _ => {
                 return Err(ClientSessionError::SessionInInvalidState {
-                    current_state: self.current_state.clone(),
+                    current_state: self.current_state,
                 });
             }
         };
 
-        self.current_state = ClientState::Publishing;
+        let state_change = self.set_state(ClientState::Publishing);
         let event = ClientSessionEvent::PublishRequestAccepted;
-        Ok(vec![ClientSessionResult::RaisedEvent(event)])
+        Ok(vec![state_change, ClientSessionResult::RaisedEvent(event)])
     }
 
     fn handle_amf0_data_on_meta_data(&mut self, mut data: Vec<Amf0Value>) -> ClientResult {
-        if data.len() < 1 {
+        if data.is_empty() {
             // No data so ignore it
             return Ok(Vec::new());
         }
@@ -881,7 +1054,9 @@ impl ClientSession {
         let mut metadata = StreamMetadata::new();
         metadata.apply_metadata_values(properties);
 
-        let event = ClientSessionEvent::StreamMetadataReceived { metadata };
+        let event = ClientSessionEvent::StreamMetadataReceived {
+            metadata: Arc::new(metadata),
+        };
         Ok(vec![ClientSessionResult::RaisedEvent(event)])
     }
 
@@ -935,24 +1110,57 @@ impl ClientSession {
         Ok(Vec::new())
     }
 
-    fn get_epoch(&self) -> RtmpTimestamp {
-        match self.start_time.elapsed() {
-            Ok(duration) => {
-                let milliseconds =
-                    (duration.as_secs() * 1000) + (duration.subsec_nanos() as u64 / 1_000_000);
-
-                // Casting to u32 should auto-wrap the value as expected.  If not a stream will probably
-                // break after 49 days but testing shows it should wrap
-                RtmpTimestamp::new(milliseconds as u32)
+    /// Moves the session to the new state, returning the event announcing the change
+    fn set_state(&mut self, state: ClientState) -> ClientSessionResult {
+        trace_event!(
+            info,
+            "State changed from {:?} to {:?}",
+            self.current_state,
+            state
+        );
+
+        let previous_state = mem::replace(&mut self.current_state, state);
+        ClientSessionResult::RaisedEvent(ClientSessionEvent::StateChanged {
+            previous_state,
+            new_state: state,
+        })
+    }
+
+    /// Reads the next message out of the deserializer, reporting input that goes over the
+    /// deserializer's share of the memory budget as going over the session's budget
+    fn read_next_message(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<Option<MessagePayload>, ClientSessionError> {
+        match self.deserializer.get_next_message(bytes) {
+            Err(ChunkDeserializationError::MemoryLimitExceeded { limit, required }) => {
+                Err(ClientSessionError::MemoryBudgetExceeded {
+                    budget: self.config.memory_budget.unwrap_or(limit),
+                    required: required + self.serializer.memory_usage(),
+                })
             }
 
-            Err(_) => RtmpTimestamp::new(0), // Time went backwards, so just consider time as at epoch
+            result => Ok(result?),
         }
     }
 
-    fn get_next_transaction_id(&mut self) -> u32 {
-        let transaction_id = self.next_transaction_id;
-        self.next_transaction_id += 1;
-        transaction_id
+    fn get_epoch(&self) -> RtmpTimestamp {
+        // If time went backwards just consider time as at epoch
+        let milliseconds = (self.config.clock)().saturating_sub(self.start_time);
+
+        // Timestamps wrap back around to zero after 49 days
+        RtmpTimestamp::from_duration(Duration::from_millis(milliseconds))
+    }
+
+    fn add_outstanding_transaction(
+        &mut self,
+        transaction: OutstandingTransaction,
+    ) -> Result<u32, ClientSessionError> {
+        match self.outstanding_transactions.insert(transaction) {
+            Some(transaction_id) => Ok(transaction_id),
+            None => Err(ClientSessionError::TooManyOutstandingTransactions {
+                limit: self.config.max_outstanding_transactions,
+            }),
+        }
     }
 }
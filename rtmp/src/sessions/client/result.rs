@@ -1,6 +1,7 @@
 use chunk_io::Packet;
 use messages::MessagePayload;
 use sessions::client::ClientSessionEvent;
+use sessions::handler::{IntoSessionOutput, SessionOutput};
 
 /// A single result that is returned when the client session performs an action
 /// or receives messages from the server.
@@ -19,3 +20,27 @@ pub enum ClientSessionResult {
     /// allows the consumer application to do something with it if it wants to (special logging)
     UnhandleableMessageReceived(MessagePayload),
 }
+
+impl ClientSessionResult {
+    /// The packet to send, if this result is one
+    pub(crate) fn packet_mut(&mut self) -> Option<&mut Packet> {
+        match *self {
+            ClientSessionResult::OutboundResponse(ref mut packet) => Some(packet),
+            _ => None,
+        }
+    }
+}
+
+impl IntoSessionOutput for ClientSessionResult {
+    type Event = ClientSessionEvent;
+
+    fn into_output(self) -> SessionOutput<ClientSessionEvent> {
+        match self {
+            ClientSessionResult::OutboundResponse(packet) => SessionOutput::Packet(packet),
+            ClientSessionResult::RaisedEvent(event) => SessionOutput::Event(event),
+            ClientSessionResult::UnhandleableMessageReceived(payload) => {
+                SessionOutput::UnhandleableMessage(payload)
+            }
+        }
+    }
+}
@@ -1,4 +1,10 @@
-#[derive(Clone, Debug)]
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Where a client session is in the process of connecting to a server and playing or
+/// publishing a stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ClientState {
     /// Client has not connected to an application on the server yet,
     Disconnected,
@@ -1,11 +1,16 @@
 use super::*;
 use bytes::Bytes;
 use bytes::BytesMut;
-use chunk_io::{ChunkDeserializer, ChunkSerializer, Packet};
-use messages::{MessagePayload, RtmpMessage, UserControlEventType};
+use chunk_io::{ChunkDeserializer, ChunkSerializer};
+use fixtures::{
+    connect_error_response, connect_success_response, create_stream_success_response,
+    play_start_response, publish_start_response, split_client_results,
+};
+use messages::{RtmpMessage, UserControlEventType};
 use rand;
-use rml_amf0::Amf0Value;
-use std::collections::HashMap;
+use rml_amf0::{Amf0Value, ObjectProperties};
+use sessions::{SessionConfigError, StatusLevel};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[test]
 fn new_session_creates_set_chunk_size_message() {
@@ -23,6 +28,19 @@ fn new_session_creates_set_chunk_size_message() {
     );
 }
 
+#[test]
+fn config_builder_rejects_zero_length_playback_buffer() {
+    let result = ClientSessionConfig::builder()
+        .playback_buffer_length_ms(0)
+        .build();
+
+    match result {
+        Err(SessionConfigError::InvalidPlaybackBufferLength) => (),
+        Err(x) => panic!("Expected invalid buffer length error, instead got {:?}", x),
+        Ok(_) => panic!("Expected invalid buffer length error, instead got a config"),
+    }
+}
+
 #[test]
 fn can_send_connect_request() {
     let app_name = "test".to_string();
@@ -32,7 +50,7 @@ fn can_send_connect_request() {
     consume_results(&mut deserializer, initial_results);
 
     let results = session.request_connection(app_name.clone()).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, vec![results]);
+    let (mut responses, _) = split_client_results(&mut deserializer, vec![results]);
 
     assert_eq!(responses.len(), 1, "Expected 1 response");
     match responses.remove(0) {
@@ -100,7 +118,7 @@ fn can_send_connect_request_with_tc_url() {
     consume_results(&mut deserializer, initial_results);
 
     let results = session.request_connection(app_name.clone()).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, vec![results]);
+    let (mut responses, _) = split_client_results(&mut deserializer, vec![results]);
 
     assert_eq!(responses.len(), 1, "Expected 1 response");
     match responses.remove(0) {
@@ -173,11 +191,18 @@ fn can_process_connect_success_response() {
     let results = session.request_connection(app_name.clone()).unwrap();
     consume_results(&mut deserializer, vec![results]);
 
-    let response = get_connect_success_response(&mut serializer);
+    let response = connect_success_response(&mut serializer);
     let results = session.handle_input(&response.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
 
-    assert_eq!(events.len(), 1, "Expected one event returned");
+    assert_eq!(events.len(), 2, "Expected two events returned");
+    assert_eq!(
+        events.remove(0),
+        ClientSessionEvent::StateChanged {
+            previous_state: ClientState::Disconnected,
+            new_state: ClientState::Connected,
+        }
+    );
     match events.remove(0) {
         ClientSessionEvent::ConnectionRequestAccepted => (),
         x => panic!(
@@ -199,9 +224,9 @@ fn event_raised_when_connect_request_rejected() {
     let results = session.request_connection(app_name.clone()).unwrap();
     consume_results(&mut deserializer, vec![results]);
 
-    let response = get_connect_error_response(&mut serializer);
+    let response = connect_error_response(&mut serializer);
     let results = session.handle_input(&response.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Expected one event returned");
     match events.remove(0) {
@@ -228,7 +253,7 @@ fn error_thrown_when_connect_request_made_after_successful_connection() {
     let results = session.request_connection(app_name.clone()).unwrap();
     consume_results(&mut deserializer, vec![results]);
 
-    let response = get_connect_success_response(&mut serializer);
+    let response = connect_success_response(&mut serializer);
     let results = session.handle_input(&response.bytes[..]).unwrap();
     consume_results(&mut deserializer, results);
 
@@ -254,9 +279,9 @@ fn successful_connect_request_sends_window_ack_size() {
     let results = session.request_connection(app_name.clone()).unwrap();
     consume_results(&mut deserializer, vec![results]);
 
-    let response = get_connect_success_response(&mut serializer);
+    let response = connect_success_response(&mut serializer);
     let results = session.handle_input(&response.bytes[..]).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
 
     assert_eq!(
         responses.len(),
@@ -291,7 +316,7 @@ fn successful_play_request_workflow() {
     );
 
     let result = session.request_playback(stream_key.clone()).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, vec![result]);
+    let (mut responses, _) = split_client_results(&mut deserializer, vec![result]);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     let transaction_id = match responses.remove(0) {
@@ -318,12 +343,13 @@ fn successful_play_request_workflow() {
         x => panic!("Unexpected response seen: {:?}", x),
     };
 
-    let (created_stream_id, create_stream_response) =
-        get_create_stream_success_response(transaction_id, &mut serializer);
+    let created_stream_id = rand::random::<u32>();
+    let create_stream_response =
+        create_stream_success_response(&mut serializer, transaction_id, created_stream_id);
     let results = session
         .handle_input(&create_stream_response.bytes[..])
         .unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
 
     assert_eq!(responses.len(), 2, "Expected one response returned");
     match responses.remove(0) {
@@ -393,11 +419,19 @@ fn successful_play_request_workflow() {
         x => panic!("Expected play message, instead received: {:?}", x),
     };
 
-    let play_response = get_play_success_response(&mut serializer, created_stream_id);
+    let play_response = play_start_response(&mut serializer, created_stream_id);
     let results = session.handle_input(&play_response.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
+
+    assert_eq!(events.len(), 2, "Expected two events returned");
+    assert_eq!(
+        events.remove(0),
+        ClientSessionEvent::StateChanged {
+            previous_state: ClientState::PlayRequested,
+            new_state: ClientState::Playing,
+        }
+    );
 
-    assert_eq!(events.len(), 1, "Expected one event returned");
     match events.remove(0) {
         ClientSessionEvent::PlaybackRequestAccepted => (),
         x => panic!(
@@ -424,7 +458,7 @@ fn active_play_session_raises_events_when_stream_metadata_changes() {
     let stream_id =
         perform_successful_play_request(config, &mut session, &mut serializer, &mut deserializer);
 
-    let mut properties = HashMap::new();
+    let mut properties = ObjectProperties::new();
     properties.insert("width".to_string(), Amf0Value::Number(1920_f64));
     properties.insert("height".to_string(), Amf0Value::Number(1080_f64));
     properties.insert(
@@ -459,7 +493,7 @@ fn active_play_session_raises_events_when_stream_metadata_changes() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events received");
     match events.remove(0) {
@@ -546,7 +580,7 @@ fn active_play_session_raises_events_when_video_data_received() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events received");
     match events.remove(0) {
@@ -562,6 +596,63 @@ fn active_play_session_raises_events_when_video_data_received() {
     }
 }
 
+#[test]
+fn handle_input_into_pushes_events_for_every_message_onto_existing_results() {
+    let config = ClientSessionConfig::new();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, initial_results) = ClientSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, initial_results);
+
+    perform_successful_connect(
+        "test".to_string(),
+        &mut session,
+        &mut serializer,
+        &mut deserializer,
+    );
+    let stream_id =
+        perform_successful_play_request(config, &mut session, &mut serializer, &mut deserializer);
+
+    let mut input = Vec::new();
+    for timestamp in [10, 20].iter() {
+        let message = RtmpMessage::VideoData {
+            data: Bytes::from(vec![1, 2, 3]),
+        };
+        let payload = message
+            .into_message_payload(RtmpTimestamp::new(*timestamp), stream_id)
+            .unwrap();
+        let packet = serializer.serialize(&payload, false, false).unwrap();
+        input.extend_from_slice(&packet.bytes[..]);
+    }
+
+    let mut results = Vec::with_capacity(4);
+    let allocation = results.as_ptr();
+    session.handle_input_into(&input, &mut results).unwrap();
+
+    assert_eq!(
+        results.as_ptr(),
+        allocation,
+        "Results were not pushed in place"
+    );
+    let (_, events) = split_client_results(&mut deserializer, results);
+    let timestamps = events
+        .into_iter()
+        .map(|event| match event {
+            ClientSessionEvent::VideoDataReceived { timestamp, .. } => timestamp,
+            x => panic!(
+                "Expected video data received event, instead received: {:?}",
+                x
+            ),
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        timestamps,
+        vec![RtmpTimestamp::new(10), RtmpTimestamp::new(20)],
+        "Unexpected timestamps"
+    );
+}
+
 #[test]
 fn active_play_session_raises_events_when_audio_data_received() {
     let config = ClientSessionConfig::new();
@@ -588,7 +679,7 @@ fn active_play_session_raises_events_when_audio_data_received() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events received");
     match events.remove(0) {
@@ -622,7 +713,7 @@ fn can_receive_audio_data_prior_to_play_request_being_accepted() {
     );
 
     let result = session.request_playback(stream_key.clone()).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, vec![result]);
+    let (mut responses, _) = split_client_results(&mut deserializer, vec![result]);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     let transaction_id = match responses.remove(0) {
@@ -649,12 +740,13 @@ fn can_receive_audio_data_prior_to_play_request_being_accepted() {
         x => panic!("Unexpected response seen: {:?}", x),
     };
 
-    let (created_stream_id, create_stream_response) =
-        get_create_stream_success_response(transaction_id, &mut serializer);
+    let created_stream_id = rand::random::<u32>();
+    let create_stream_response =
+        create_stream_success_response(&mut serializer, transaction_id, created_stream_id);
     let results = session
         .handle_input(&create_stream_response.bytes[..])
         .unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
 
     assert_eq!(responses.len(), 2, "Expected one response returned");
     match responses.remove(0) {
@@ -733,7 +825,7 @@ fn can_receive_audio_data_prior_to_play_request_being_accepted() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events received");
     match events.remove(0) {
@@ -767,7 +859,7 @@ fn can_receive_video_data_prior_to_play_request_being_accepted() {
     );
 
     let result = session.request_playback(stream_key.clone()).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, vec![result]);
+    let (mut responses, _) = split_client_results(&mut deserializer, vec![result]);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     let transaction_id = match responses.remove(0) {
@@ -794,12 +886,13 @@ fn can_receive_video_data_prior_to_play_request_being_accepted() {
         x => panic!("Unexpected response seen: {:?}", x),
     };
 
-    let (created_stream_id, create_stream_response) =
-        get_create_stream_success_response(transaction_id, &mut serializer);
+    let created_stream_id = rand::random::<u32>();
+    let create_stream_response =
+        create_stream_success_response(&mut serializer, transaction_id, created_stream_id);
     let results = session
         .handle_input(&create_stream_response.bytes[..])
         .unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
 
     assert_eq!(responses.len(), 2, "Expected one response returned");
     match responses.remove(0) {
@@ -878,7 +971,7 @@ fn can_receive_video_data_prior_to_play_request_being_accepted() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events received");
     match events.remove(0) {
@@ -894,6 +987,56 @@ fn can_receive_video_data_prior_to_play_request_being_accepted() {
     }
 }
 
+#[test]
+fn on_status_codes_the_session_does_not_act_on_are_raised_as_typed_codes() {
+    let config = ClientSessionConfig::new();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, initial_results) = ClientSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, initial_results);
+
+    perform_successful_connect(
+        "test".to_string(),
+        &mut session,
+        &mut serializer,
+        &mut deserializer,
+    );
+
+    let mut status_properties = ObjectProperties::new();
+    status_properties.insert("level".to_string(), Amf0Value::from("error"));
+    status_properties.insert(
+        "code".to_string(),
+        Amf0Value::from("NetStream.Play.StreamNotFound"),
+    );
+
+    let message = RtmpMessage::Amf0Command {
+        command_name: "onStatus".to_string(),
+        transaction_id: 0.0,
+        command_object: Amf0Value::Null,
+        additional_arguments: vec![Amf0Value::Object(status_properties)],
+    };
+
+    let payload = message
+        .into_message_payload(RtmpTimestamp::new(0), 1)
+        .unwrap();
+    let packet = serializer.serialize(&payload, false, false).unwrap();
+    let results = session.handle_input(&packet.bytes[..]).unwrap();
+    let (_, mut events) = split_client_results(&mut deserializer, results);
+
+    assert_eq!(events.len(), 1, "Unexpected number of events");
+    match events.remove(0) {
+        ClientSessionEvent::UnhandleableOnStatusCode { code } => {
+            assert_eq!(code, StatusCode::PlayStreamNotFound);
+            assert_eq!(code.level(), StatusLevel::Error);
+        }
+
+        x => panic!(
+            "Expected unhandleable status code, instead received: {:?}",
+            x
+        ),
+    }
+}
+
 #[test]
 fn can_stop_playback() {
     let config = ClientSessionConfig::new();
@@ -912,7 +1055,7 @@ fn can_stop_playback() {
         perform_successful_play_request(config, &mut session, &mut serializer, &mut deserializer);
 
     let results = session.stop_playback().unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
@@ -975,7 +1118,7 @@ fn automatically_responds_to_ping_requests() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
 
     assert_eq!(
         responses.len(),
@@ -1031,7 +1174,7 @@ fn event_raised_when_ping_response_received() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "One event expected");
     match events.remove(0) {
@@ -1091,6 +1234,23 @@ fn can_send_ping_request() {
     }
 }
 
+#[test]
+fn outbound_timestamps_are_read_from_configured_clock() {
+    static NOW: AtomicU64 = AtomicU64::new(10_000);
+    fn test_clock() -> u64 {
+        NOW.load(Ordering::SeqCst)
+    }
+
+    let mut config = ClientSessionConfig::new();
+    config.clock = test_clock;
+    let (mut session, _) = ClientSession::new(config).unwrap();
+
+    NOW.store(10_250, Ordering::SeqCst);
+    let (_, sent_timestamp) = session.send_ping_request().unwrap();
+
+    assert_eq!(sent_timestamp, RtmpTimestamp::new(250));
+}
+
 #[test]
 fn sends_ack_after_receiving_window_ack_bytes() {
     let config = ClientSessionConfig::new();
@@ -1128,7 +1288,7 @@ fn sends_ack_after_receiving_window_ack_bytes() {
         .unwrap();
     let video_packet = serializer.serialize(&video_payload, false, false).unwrap();
     let results = session.handle_input(&video_packet.bytes[..]).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
@@ -1146,7 +1306,7 @@ fn sends_ack_after_receiving_window_ack_bytes() {
         .unwrap();
     let video_packet = serializer.serialize(&video_payload, false, false).unwrap();
     let results = session.handle_input(&video_packet.bytes[..]).unwrap();
-    let (responses, _) = split_results(&mut deserializer, results);
+    let (responses, _) = split_client_results(&mut deserializer, results);
     assert_eq!(responses.len(), 0, "Expected no responses");
 
     let mut bytes = BytesMut::new();
@@ -1159,7 +1319,7 @@ fn sends_ack_after_receiving_window_ack_bytes() {
         .unwrap();
     let video_packet = serializer.serialize(&video_payload, false, false).unwrap();
     let results = session.handle_input(&video_packet.bytes[..]).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
         (_, RtmpMessage::Acknowledgement { sequence_number: _ }) => (), // No good way to predict sequence number
@@ -1190,7 +1350,7 @@ fn event_raised_when_server_sends_an_acknowledgement() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events");
     match events.remove(0) {
@@ -1227,7 +1387,7 @@ fn successful_publish_request_workflow() {
     let result = session
         .request_publishing(stream_key.clone(), PublishRequestType::Live)
         .unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, vec![result]);
+    let (mut responses, _) = split_client_results(&mut deserializer, vec![result]);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     let transaction_id = match responses.remove(0) {
@@ -1254,12 +1414,13 @@ fn successful_publish_request_workflow() {
         x => panic!("Unexpected response seen: {:?}", x),
     };
 
-    let (created_stream_id, create_stream_response) =
-        get_create_stream_success_response(transaction_id, &mut serializer);
+    let created_stream_id = rand::random::<u32>();
+    let create_stream_response =
+        create_stream_success_response(&mut serializer, transaction_id, created_stream_id);
     let results = session
         .handle_input(&create_stream_response.bytes[..])
         .unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
@@ -1300,11 +1461,19 @@ fn successful_publish_request_workflow() {
         x => panic!("Expected amf0 command, received: {:?}", x),
     };
 
-    let publish_response = get_publish_success_response(&mut serializer, created_stream_id);
+    let publish_response = publish_start_response(&mut serializer, created_stream_id);
     let results = session.handle_input(&publish_response.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_client_results(&mut deserializer, results);
+
+    assert_eq!(events.len(), 2, "Expected two events returned");
+    assert_eq!(
+        events.remove(0),
+        ClientSessionEvent::StateChanged {
+            previous_state: ClientState::PublishRequested,
+            new_state: ClientState::Publishing,
+        }
+    );
 
-    assert_eq!(events.len(), 1, "Unexpected number of events");
     match events.remove(0) {
         ClientSessionEvent::PublishRequestAccepted => (),
         x => panic!(
@@ -1345,7 +1514,7 @@ fn publisher_can_send_metadata() {
     metadata.encoder = Some("encoder".to_string());
 
     let result = session.publish_metadata(&metadata).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, vec![result]);
+    let (mut responses, _) = split_client_results(&mut deserializer, vec![result]);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
@@ -1459,7 +1628,7 @@ fn publisher_can_send_video_data() {
     let result = session
         .publish_video_data(data.clone(), RtmpTimestamp::new(1234), false)
         .unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, vec![result]);
+    let (mut responses, _) = split_client_results(&mut deserializer, vec![result]);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
@@ -1501,7 +1670,7 @@ fn publisher_can_send_audio_data() {
     let result = session
         .publish_audio_data(data.clone(), RtmpTimestamp::new(1234), false)
         .unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, vec![result]);
+    let (mut responses, _) = split_client_results(&mut deserializer, vec![result]);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
@@ -1540,7 +1709,7 @@ fn can_stop_publishing() {
         perform_successful_publish_request(&mut session, &mut serializer, &mut deserializer);
 
     let results = session.stop_publishing().unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_client_results(&mut deserializer, results);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
@@ -1576,196 +1745,70 @@ fn can_stop_publishing() {
     }
 }
 
-fn split_results(
-    deserializer: &mut ChunkDeserializer,
-    mut results: Vec<ClientSessionResult>,
-) -> (Vec<(MessagePayload, RtmpMessage)>, Vec<ClientSessionEvent>) {
-    let mut responses = Vec::new();
-    let mut events = Vec::new();
-
-    for result in results.drain(..) {
-        match result {
-            ClientSessionResult::OutboundResponse(packet) => {
-                let payload = deserializer
-                    .get_next_message(&packet.bytes[..])
-                    .unwrap()
-                    .unwrap();
-                let message = payload.to_rtmp_message().unwrap();
-                match message {
-                    RtmpMessage::SetChunkSize { size } => {
-                        deserializer.set_max_chunk_size(size as usize).unwrap()
-                    }
-                    _ => (),
-                }
-
-                println!("response received: {:?}", message);
-                responses.push((payload, message));
-            }
-
-            ClientSessionResult::RaisedEvent(event) => {
-                println!("event received: {:?}", event);
-                events.push(event);
-            }
-
-            ClientSessionResult::UnhandleableMessageReceived(payload) => {
-                println!("unhandleable message: {:?}", payload);
-            }
-        }
-    }
-
-    (responses, events)
-}
-
 fn consume_results(deserializer: &mut ChunkDeserializer, results: Vec<ClientSessionResult>) {
     // Needed to keep the deserializer up to date
-    split_results(deserializer, results);
+    split_client_results(deserializer, results);
 }
 
-fn get_connect_success_response(serializer: &mut ChunkSerializer) -> Packet {
-    let mut command_properties = HashMap::new();
-    command_properties.insert(
-        "fmsVer".to_string(),
-        Amf0Value::Utf8String("fms".to_string()),
-    );
-    command_properties.insert("capabilities".to_string(), Amf0Value::Number(31.0));
-
-    let mut additional_properties = HashMap::new();
-    additional_properties.insert(
-        "level".to_string(),
-        Amf0Value::Utf8String("status".to_string()),
-    );
-    additional_properties.insert(
-        "code".to_string(),
-        Amf0Value::Utf8String("NetConnection.Connect.Success".to_string()),
-    );
-    additional_properties.insert(
-        "description".to_string(),
-        Amf0Value::Utf8String("hi".to_string()),
-    );
-    additional_properties.insert("objectEncoding".to_string(), Amf0Value::Number(0.0));
-
-    let message = RtmpMessage::Amf0Command {
-        command_name: "_result".to_string(),
-        transaction_id: 1.0,
-        command_object: Amf0Value::Object(command_properties),
-        additional_arguments: vec![Amf0Value::Object(additional_properties)],
-    };
-
-    let payload = message
-        .into_message_payload(RtmpTimestamp::new(0), 0)
-        .unwrap();
-    serializer.serialize(&payload, false, false).unwrap()
-}
-
-fn get_connect_error_response(serializer: &mut ChunkSerializer) -> Packet {
-    let mut command_properties = HashMap::new();
-    command_properties.insert(
-        "fmsVer".to_string(),
-        Amf0Value::Utf8String("fms".to_string()),
-    );
-    command_properties.insert("capabilities".to_string(), Amf0Value::Number(31.0));
+#[test]
+fn requests_beyond_outstanding_limit_are_refused() {
+    let mut config = ClientSessionConfig::new();
+    config.max_outstanding_transactions = 1;
 
-    let mut additional_properties = HashMap::new();
-    additional_properties.insert(
-        "level".to_string(),
-        Amf0Value::Utf8String("error".to_string()),
-    );
-    additional_properties.insert(
-        "code".to_string(),
-        Amf0Value::Utf8String("NetConnection.Connect.Failed".to_string()),
-    );
-    additional_properties.insert(
-        "description".to_string(),
-        Amf0Value::Utf8String("hi".to_string()),
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, initial_results) = ClientSession::new(config).unwrap();
+    consume_results(&mut deserializer, initial_results);
+    perform_successful_connect(
+        "test".to_string(),
+        &mut session,
+        &mut serializer,
+        &mut deserializer,
     );
-    additional_properties.insert("objectEncoding".to_string(), Amf0Value::Number(0.0));
-
-    let message = RtmpMessage::Amf0Command {
-        command_name: "_error".to_string(),
-        transaction_id: 1.0,
-        command_object: Amf0Value::Object(command_properties),
-        additional_arguments: vec![Amf0Value::Object(additional_properties)],
-    };
-
-    let payload = message
-        .into_message_payload(RtmpTimestamp::new(0), 0)
-        .unwrap();
-    serializer.serialize(&payload, false, false).unwrap()
-}
-
-fn get_create_stream_success_response(
-    transaction_id: f64,
-    serializer: &mut ChunkSerializer,
-) -> (u32, Packet) {
-    let stream_id = rand::random::<u32>();
-    let message = RtmpMessage::Amf0Command {
-        command_name: "_result".to_string(),
-        command_object: Amf0Value::Null,
-        additional_arguments: vec![Amf0Value::Number(stream_id as f64)],
-        transaction_id,
-    };
 
-    let payload = message
-        .into_message_payload(RtmpTimestamp::new(0), 0)
-        .unwrap();
-    let packet = serializer.serialize(&payload, false, false).unwrap();
-    (stream_id, packet)
+    session.request_playback("key1".to_string()).unwrap();
+    match session.request_playback("key2".to_string()) {
+        Err(ClientSessionError::TooManyOutstandingTransactions { limit: 1 }) => (),
+        x => panic!(
+            "Expected too many outstanding transactions error, instead got: {:?}",
+            x
+        ),
+    }
 }
 
-fn get_play_success_response(serializer: &mut ChunkSerializer, stream_id: u32) -> Packet {
-    let mut additional_properties = HashMap::new();
-    additional_properties.insert(
-        "level".to_string(),
-        Amf0Value::Utf8String("status".to_string()),
-    );
-    additional_properties.insert(
-        "code".to_string(),
-        Amf0Value::Utf8String("NetStream.Play.Start".to_string()),
-    );
-    additional_properties.insert(
-        "description".to_string(),
-        Amf0Value::Utf8String("hi".to_string()),
-    );
-
-    let message = RtmpMessage::Amf0Command {
-        command_name: "onStatus".to_string(),
-        transaction_id: 0.0,
-        command_object: Amf0Value::Null,
-        additional_arguments: vec![Amf0Value::Object(additional_properties)],
-    };
-
-    let payload = message
-        .into_message_payload(RtmpTimestamp::new(0), stream_id)
-        .unwrap();
-    serializer.serialize(&payload, false, false).unwrap()
-}
+#[test]
+fn input_over_memory_budget_is_refused() {
+    let mut config = ClientSessionConfig::new();
+    config.memory_budget = Some(16 * 1024);
 
-fn get_publish_success_response(serializer: &mut ChunkSerializer, stream_id: u32) -> Packet {
-    let mut additional_properties = HashMap::new();
-    additional_properties.insert(
-        "level".to_string(),
-        Amf0Value::Utf8String("status".to_string()),
-    );
-    additional_properties.insert(
-        "code".to_string(),
-        Amf0Value::Utf8String("NetStream.Publish.Start".to_string()),
-    );
-    additional_properties.insert(
-        "description".to_string(),
-        Amf0Value::Utf8String("hi".to_string()),
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, initial_results) = ClientSession::new(config).unwrap();
+    consume_results(&mut deserializer, initial_results);
+    perform_successful_connect(
+        "test".to_string(),
+        &mut session,
+        &mut serializer,
+        &mut deserializer,
     );
 
-    let message = RtmpMessage::Amf0Command {
-        command_name: "onStatus".to_string(),
-        transaction_id: 0.0,
-        command_object: Amf0Value::Null,
-        additional_arguments: vec![Amf0Value::Object(additional_properties)],
+    let video_message = RtmpMessage::VideoData {
+        data: Bytes::from(vec![1_u8; 100_000]),
     };
-
-    let payload = message
-        .into_message_payload(RtmpTimestamp::new(0), stream_id)
+    let video_payload = video_message
+        .into_message_payload(RtmpTimestamp::new(0), 1)
         .unwrap();
-    serializer.serialize(&payload, false, false).unwrap()
+    let video_packet = serializer.serialize(&video_payload, false, false).unwrap();
+    match session.handle_input(&video_packet.bytes[..1024]) {
+        Err(ClientSessionError::MemoryBudgetExceeded {
+            budget: 16384,
+            required,
+        }) => assert!(required > 100_000, "Required bytes of {} too low", required),
+        x => panic!(
+            "Expected memory budget exceeded error, instead got: {:?}",
+            x
+        ),
+    }
 }
 
 fn perform_successful_connect(
@@ -1777,11 +1820,18 @@ fn perform_successful_connect(
     let results = session.request_connection(app_name).unwrap();
     consume_results(deserializer, vec![results]);
 
-    let response = get_connect_success_response(serializer);
+    let response = connect_success_response(serializer);
     let results = session.handle_input(&response.bytes[..]).unwrap();
-    let (_, mut events) = split_results(deserializer, results);
+    let (_, mut events) = split_client_results(deserializer, results);
 
-    assert_eq!(events.len(), 1, "Expected one event returned");
+    assert_eq!(events.len(), 2, "Expected two events returned");
+    assert_eq!(
+        events.remove(0),
+        ClientSessionEvent::StateChanged {
+            previous_state: ClientState::Disconnected,
+            new_state: ClientState::Connected,
+        }
+    );
     match events.remove(0) {
         ClientSessionEvent::ConnectionRequestAccepted => (),
         x => panic!(
@@ -1799,7 +1849,7 @@ fn perform_successful_play_request(
 ) -> u32 {
     let stream_key = "abcd".to_string();
     let result = session.request_playback(stream_key.clone()).unwrap();
-    let (mut responses, _) = split_results(deserializer, vec![result]);
+    let (mut responses, _) = split_client_results(deserializer, vec![result]);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     let transaction_id = match responses.remove(0) {
@@ -1826,12 +1876,13 @@ fn perform_successful_play_request(
         x => panic!("Unexpected response seen: {:?}", x),
     };
 
-    let (created_stream_id, create_stream_response) =
-        get_create_stream_success_response(transaction_id, serializer);
+    let created_stream_id = rand::random::<u32>();
+    let create_stream_response =
+        create_stream_success_response(serializer, transaction_id, created_stream_id);
     let results = session
         .handle_input(&create_stream_response.bytes[..])
         .unwrap();
-    let (mut responses, _) = split_results(deserializer, results);
+    let (mut responses, _) = split_client_results(deserializer, results);
 
     assert_eq!(responses.len(), 2, "Expected one response returned");
     match responses.remove(0) {
@@ -1901,11 +1952,19 @@ fn perform_successful_play_request(
         x => panic!("Expected play message, instead received: {:?}", x),
     };
 
-    let play_response = get_play_success_response(serializer, created_stream_id);
+    let play_response = play_start_response(serializer, created_stream_id);
     let results = session.handle_input(&play_response.bytes[..]).unwrap();
-    let (_, mut events) = split_results(deserializer, results);
+    let (_, mut events) = split_client_results(deserializer, results);
+
+    assert_eq!(events.len(), 2, "Expected two events returned");
+    assert_eq!(
+        events.remove(0),
+        ClientSessionEvent::StateChanged {
+            previous_state: ClientState::PlayRequested,
+            new_state: ClientState::Playing,
+        }
+    );
 
-    assert_eq!(events.len(), 1, "Expected one event returned");
     match events.remove(0) {
         ClientSessionEvent::PlaybackRequestAccepted => (),
         x => panic!(
@@ -1926,7 +1985,7 @@ fn perform_successful_publish_request(
     let result = session
         .request_publishing(stream_key.clone(), PublishRequestType::Live)
         .unwrap();
-    let (mut responses, _) = split_results(deserializer, vec![result]);
+    let (mut responses, _) = split_client_results(deserializer, vec![result]);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     let transaction_id = match responses.remove(0) {
@@ -1953,12 +2012,13 @@ fn perform_successful_publish_request(
         x => panic!("Unexpected response seen: {:?}", x),
     };
 
-    let (created_stream_id, create_stream_response) =
-        get_create_stream_success_response(transaction_id, serializer);
+    let created_stream_id = rand::random::<u32>();
+    let create_stream_response =
+        create_stream_success_response(serializer, transaction_id, created_stream_id);
     let results = session
         .handle_input(&create_stream_response.bytes[..])
         .unwrap();
-    let (mut responses, _) = split_results(deserializer, results);
+    let (mut responses, _) = split_client_results(deserializer, results);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
@@ -1999,11 +2059,19 @@ fn perform_successful_publish_request(
         x => panic!("Expected amf0 command, received: {:?}", x),
     };
 
-    let publish_response = get_publish_success_response(serializer, created_stream_id);
+    let publish_response = publish_start_response(serializer, created_stream_id);
     let results = session.handle_input(&publish_response.bytes[..]).unwrap();
-    let (_, mut events) = split_results(deserializer, results);
+    let (_, mut events) = split_client_results(deserializer, results);
+
+    assert_eq!(events.len(), 2, "Expected two events returned");
+    assert_eq!(
+        events.remove(0),
+        ClientSessionEvent::StateChanged {
+            previous_state: ClientState::PublishRequested,
+            new_state: ClientState::Publishing,
+        }
+    );
 
-    assert_eq!(events.len(), 1, "Unexpected number of events");
     match events.remove(0) {
         ClientSessionEvent::PublishRequestAccepted => (),
         x => panic!(
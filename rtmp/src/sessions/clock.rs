@@ -0,0 +1,24 @@
+/// A function returning the current time in milliseconds, which sessions use to timestamp the
+/// messages they send.  Only the time that passes between calls matters, so it can be measured
+/// from any starting point.
+pub type SessionClock = fn() -> u64;
+
+/// Returns the number of milliseconds since the unix epoch, according to the system clock.  If
+/// the system clock is set before the epoch zero is returned.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub fn system_clock() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as u64,
+        Err(_) => 0,
+    }
+}
+
+/// wasm32-unknown-unknown has no system clock (reading `SystemTime` panics), so this always
+/// returns zero.  Applications running there should supply a clock backed by the runtime, such
+/// as `Date.now()` or `performance.now()`.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub fn system_clock() -> u64 {
+    0
+}
@@ -0,0 +1,54 @@
+use thiserror::Error;
+
+/// The largest chunk size that fits in the 31 bits a `SetChunkSize` message has for it
+const MAX_CHUNK_SIZE: u32 = 0x7FFF_FFFF;
+
+/// Errors raised when a session config builder is given values a session can't work with
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum SessionConfigError {
+    /// Chunk sizes must be at least 1 byte, and fit in the 31 bits `SetChunkSize` has for them
+    #[error("Chunk size of {chunk_size} is invalid.  Chunk size must be between 1 and 2147483647")]
+    InvalidChunkSize { chunk_size: u32 },
+
+    /// A window acknowledgement size of zero would have the peer acknowledge every read
+    #[error("Window acknowledgement size must be above zero")]
+    InvalidWindowAckSize,
+
+    /// A peer bandwidth of zero would not let the peer send anything
+    #[error("Peer bandwidth must be above zero")]
+    InvalidPeerBandwidth,
+
+    /// A playback buffer of zero milliseconds would have the server start playback without
+    /// anything buffered
+    #[error("Playback buffer length must be above zero milliseconds")]
+    InvalidPlaybackBufferLength,
+
+    /// At least one request has to be able to wait on a response
+    #[error("The maximum number of outstanding requests must be above zero")]
+    InvalidMaxOutstandingRequests,
+}
+
+pub(crate) fn check_chunk_size(chunk_size: u32) -> Result<(), SessionConfigError> {
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(SessionConfigError::InvalidChunkSize { chunk_size });
+    }
+
+    Ok(())
+}
+
+pub(crate) fn check_window_ack_size(window_ack_size: u32) -> Result<(), SessionConfigError> {
+    if window_ack_size == 0 {
+        return Err(SessionConfigError::InvalidWindowAckSize);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn check_max_outstanding_requests(limit: usize) -> Result<(), SessionConfigError> {
+    if limit == 0 {
+        return Err(SessionConfigError::InvalidMaxOutstandingRequests);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use alloc::vec::Vec;
+use rml_amf0::{take_field, take_optional_field, Amf0Object, Amf0ObjectError, Amf0Value};
+use rml_amf0::{Amf0ObjectWriter, Amf0SerializationError, ObjectProperties};
+
+/// The command object sent with a `connect` command.
+///
+/// Only the application name is required.  All other properties are optional and left out of
+/// the command object when they are not set.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ConnectProperties {
+    /// Name of the application the client wants to connect to
+    pub app: String,
+
+    /// Version of the client software (e.g. `LNX 9,0,124,2`)
+    pub flash_ver: Option<String>,
+
+    /// Url of the server the client is connecting to
+    pub tc_url: Option<String>,
+
+    /// Url of the swf file that initiated the connection
+    pub swf_url: Option<String>,
+
+    /// Url of the page the connection was made from
+    pub page_url: Option<String>,
+
+    /// The AMF encoding the client wants to use.  0 for AMF0, 3 for AMF3.
+    pub object_encoding: Option<f64>,
+}
+
+impl ConnectProperties {
+    /// Creates properties for connecting to the specified application, with no optional
+    /// properties set
+    pub fn new(app: String) -> ConnectProperties {
+        ConnectProperties {
+            app,
+            flash_ver: None,
+            tc_url: None,
+            swf_url: None,
+            page_url: None,
+            object_encoding: None,
+        }
+    }
+
+    /// Appends the properties to the bytes as an AMF0 object, without building an
+    /// `Amf0Value` for them first
+    pub fn serialize_amf0(&self, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+        let mut writer = Amf0ObjectWriter::new(bytes);
+        writer.string_property("app", &self.app)?;
+        for &(name, value) in self.optional_strings().iter() {
+            if let Some(ref value) = *value {
+                writer.string_property(name, value)?;
+            }
+        }
+
+        if let Some(encoding) = self.object_encoding {
+            writer.number_property("objectEncoding", encoding)?;
+        }
+
+        writer.finish();
+        Ok(())
+    }
+
+    fn optional_strings(&self) -> [(&'static str, &Option<String>); 4] {
+        [
+            ("flashVer", &self.flash_ver),
+            ("tcUrl", &self.tc_url),
+            ("swfUrl", &self.swf_url),
+            ("pageUrl", &self.page_url),
+        ]
+    }
+}
+
+impl Amf0Object for ConnectProperties {
+    fn to_amf0_properties(&self) -> ObjectProperties {
+        let mut properties = ObjectProperties::new();
+        properties.insert("app".to_string(), Amf0Value::from(self.app.as_str()));
+        for &(name, value) in self.optional_strings().iter() {
+            if let Some(ref value) = *value {
+                properties.insert(name.to_string(), Amf0Value::from(value.as_str()));
+            }
+        }
+
+        if let Some(encoding) = self.object_encoding {
+            properties.insert("objectEncoding".to_string(), Amf0Value::Number(encoding));
+        }
+
+        properties
+    }
+
+    fn from_amf0_properties(mut properties: ObjectProperties) -> Result<Self, Amf0ObjectError> {
+        // Clients disagree on what they send along with the application name, so optional
+        // properties of an unexpected type are treated as missing instead of failing the
+        // connection.
+        Ok(ConnectProperties {
+            app: take_field(&mut properties, "app")?,
+            flash_ver: take_optional_field(&mut properties, "flashVer").unwrap_or(None),
+            tc_url: take_optional_field(&mut properties, "tcUrl").unwrap_or(None),
+            swf_url: take_optional_field(&mut properties, "swfUrl").unwrap_or(None),
+            page_url: take_optional_field(&mut properties, "pageUrl").unwrap_or(None),
+            object_encoding: take_optional_field(&mut properties, "objectEncoding").unwrap_or(None),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rml_amf0;
+
+    #[test]
+    fn connect_properties_round_trip_through_amf0_value() {
+        let mut connect = ConnectProperties::new("live".to_string());
+        connect.flash_ver = Some("LNX 9,0,124,2".to_string());
+        connect.tc_url = Some("rtmp://localhost/live".to_string());
+        connect.object_encoding = Some(0.0);
+
+        let value = connect.to_amf0_value();
+
+        assert_eq!(ConnectProperties::from_amf0_value(value).unwrap(), connect);
+    }
+
+    #[test]
+    fn direct_serialization_matches_serialized_amf0_value() {
+        let mut connect = ConnectProperties::new("live".to_string());
+        connect.flash_ver = Some("LNX 9,0,124,2".to_string());
+        connect.page_url = Some("http://localhost/player".to_string());
+        connect.object_encoding = Some(0.0);
+
+        let mut bytes = Vec::new();
+        connect.serialize_amf0(&mut bytes).unwrap();
+
+        let values = rml_amf0::deserialize_slice(&bytes).unwrap();
+        assert_eq!(values, vec![connect.to_amf0_value()]);
+    }
+
+    #[test]
+    fn unset_properties_are_left_out() {
+        let properties = ConnectProperties::new("live".to_string()).to_amf0_properties();
+
+        assert_eq!(properties.len(), 1);
+        assert_eq!(properties.get("app"), Some(&Amf0Value::from("live")));
+    }
+
+    #[test]
+    fn connect_properties_without_app_are_rejected() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("flashVer".to_string(), Amf0Value::from("LNX 9,0,124,2"));
+
+        match ConnectProperties::from_amf0_properties(properties) {
+            Err(Amf0ObjectError::MissingField { ref field }) if field == "app" => (),
+            x => panic!("Expected missing app error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn mistyped_optional_properties_are_ignored() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("app".to_string(), Amf0Value::from("live"));
+        properties.insert("objectEncoding".to_string(), Amf0Value::from("zero"));
+
+        let connect = ConnectProperties::from_amf0_properties(properties).unwrap();
+
+        assert_eq!(connect.object_encoding, None);
+    }
+}
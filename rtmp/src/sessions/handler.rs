@@ -0,0 +1,114 @@
+use chunk_io::Packet;
+use messages::MessagePayload;
+
+/// Receives what a session produces while it handles input, as an alternative to having the
+/// session collect its results into a vector.
+///
+/// The session calls the handler as each message is processed, in the same order the results
+/// would have been returned in.  This fits runtimes that are already built around callbacks, and
+/// lets the application act on media as soon as it has been read.  The same flush hints are
+/// given as with the vector based methods, so only the last packet produced by a call is
+/// hinted to be flushed.
+///
+/// `Event` is `ServerSessionEvent` for handlers of a `ServerSession` and `ClientSessionEvent`
+/// for handlers of a `ClientSession`.
+pub trait SessionHandler {
+    type Event;
+
+    /// Called with each event the session raises
+    fn on_event(&mut self, event: Self::Event);
+
+    /// Called with each packet that is slated to be sent to the peer.  Packets have the same
+    /// ordering requirements as the `OutboundResponse` results they replace.
+    fn on_outbound_packet(&mut self, packet: Packet);
+
+    /// Called with messages the session received but could not handle.  These are ignored
+    /// unless the handler overrides this.
+    fn on_unhandleable_message(&mut self, payload: MessagePayload) {
+        let _ = payload;
+    }
+}
+
+/// What a single session result is made up of, so results can be passed to a handler
+pub(crate) enum SessionOutput<E> {
+    Packet(Packet),
+    Event(E),
+    UnhandleableMessage(MessagePayload),
+}
+
+/// Session results that can be taken apart to be passed to a `SessionHandler`
+pub(crate) trait IntoSessionOutput {
+    type Event;
+
+    fn into_output(self) -> SessionOutput<Self::Event>;
+}
+
+/// Somewhere a session puts its results while it processes input
+pub(crate) trait ResultSink<R> {
+    fn push(&mut self, result: R);
+
+    fn extend<I: IntoIterator<Item = R>>(&mut self, results: I) {
+        for result in results {
+            self.push(result);
+        }
+    }
+}
+
+impl<R> ResultSink<R> for Vec<R> {
+    fn push(&mut self, result: R) {
+        Vec::push(self, result);
+    }
+
+    fn extend<I: IntoIterator<Item = R>>(&mut self, results: I) {
+        Extend::extend(self, results);
+    }
+}
+
+/// Passes results on to a handler as they are produced.  The latest packet is held back until
+/// the next result comes in, since only then is it known whether it was the last packet of the
+/// call and should be hinted to flush.
+pub(crate) struct HandlerSink<'a, H: 'a> {
+    handler: &'a mut H,
+    pending_packet: Option<Packet>,
+}
+
+impl<'a, H: SessionHandler> HandlerSink<'a, H> {
+    pub fn new(handler: &'a mut H) -> HandlerSink<'a, H> {
+        HandlerSink {
+            handler,
+            pending_packet: None,
+        }
+    }
+
+    /// Passes on the packet that was held back, as the last packet of the call
+    pub fn finish(mut self) {
+        if let Some(mut packet) = self.pending_packet.take() {
+            packet.flush_hint = true;
+            self.handler.on_outbound_packet(packet);
+        }
+    }
+
+    fn send_pending_packet(&mut self) {
+        if let Some(mut packet) = self.pending_packet.take() {
+            packet.flush_hint = false;
+            self.handler.on_outbound_packet(packet);
+        }
+    }
+}
+
+impl<'a, H, R> ResultSink<R> for HandlerSink<'a, H>
+where
+    H: SessionHandler,
+    R: IntoSessionOutput<Event = H::Event>,
+{
+    fn push(&mut self, result: R) {
+        self.send_pending_packet();
+        match result.into_output() {
+            SessionOutput::Packet(packet) => self.pending_packet = Some(packet),
+            SessionOutput::Event(event) => self.handler.on_event(event),
+            SessionOutput::UnhandleableMessage(payload) => {
+                self.handler.on_unhandleable_message(payload)
+            }
+        }
+    }
+}
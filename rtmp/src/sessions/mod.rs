@@ -10,31 +10,138 @@ A single session represents a single peer in an RTMP connection, so if multiple
 being managed (in any direction) each connection should have its own, distinct, session instance.
 
 It is also expected that a session has been created *after* handshaking has been completed.
+
+Input can be handled with `handle_input()`, which returns a vector of results, with
+`handle_input_iter()`, which returns an iterator over a results buffer the session reuses, or
+with `handle_input_with()`, which passes each result to a `SessionHandler` as it is produced.
+The handler form suits applications built around callbacks, and neither it nor the iterator
+form allocates a vector for every read.
+
+# Thread Safety
+
+Sessions, their configs, and the results, events, and errors they return are all `Send`, so a
+session can be created on one thread and moved to whichever worker is servicing its connection,
+and its results can be handed off to other threads.  This is checked at compile time, so it
+won't silently regress.
+
+Sessions are also `Sync`, but since every operation on a session takes `&mut self` sharing one
+between threads requires a lock anyway.  A session is only ever meant to be driven by one
+thread at a time, in the order bytes are received from its peer.
 */
 
+#[cfg(feature = "client-session")]
 mod client;
+mod clock;
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+mod config_errors;
+mod connect_properties;
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+pub(crate) mod handler;
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+mod request_slab;
+#[cfg(feature = "server-session")]
 mod server;
+mod status_code;
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+mod status_object;
+
+#[cfg(feature = "client-session")]
+pub use self::client::{
+    ClientSession, ClientSessionConfig, ClientSessionConfigBuilder, ClientSessionError,
+    ClientSessionEvent, ClientSessionResult, ClientState, PublishRequestType,
+};
+
+pub use self::clock::{system_clock, SessionClock};
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+pub use self::config_errors::SessionConfigError;
+pub use self::connect_properties::ConnectProperties;
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+pub use self::handler::SessionHandler;
+
+#[cfg(feature = "server-session")]
+pub use self::server::{
+    OutstandingRequestInfo, OutstandingRequestType, PlayStartValue, PublishMode, ServerSession,
+    ServerSessionConfig, ServerSessionConfigBuilder, ServerSessionError, ServerSessionEvent,
+    ServerSessionResult, ServerState, StreamState,
+};
 
-pub use self::client::ClientSession;
-pub use self::client::ClientSessionConfig;
-pub use self::client::ClientSessionError;
-pub use self::client::ClientSessionEvent;
-pub use self::client::ClientSessionResult;
-pub use self::client::ClientState;
-pub use self::client::PublishRequestType;
-
-pub use self::server::PublishMode;
-pub use self::server::ServerSession;
-pub use self::server::ServerSessionConfig;
-pub use self::server::ServerSessionError;
-pub use self::server::ServerSessionEvent;
-pub use self::server::ServerSessionResult;
-
-use rml_amf0::Amf0Value;
+pub use self::status_code::{StatusCode, StatusLevel};
+
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+use chunk_io::Packet;
+use chunk_io::{ChunkDeserializationError, ChunkSerializationError};
+use handshake::HandshakeError;
+use messages::{MessageDeserializationError, MessageSerializationError, RtmpMessage};
+use rml_amf0::{Amf0Value, ObjectProperties};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+
+// Fails to compile if anything added to a session, its config, or what it returns stops it
+// from being moved to another thread.
+const _: fn() = || {
+    #[cfg(any(feature = "client-session", feature = "server-session"))]
+    fn assert_send<T: Send>() {}
+    fn assert_send_and_sync<T: Send + Sync>() {}
+
+    #[cfg(feature = "client-session")]
+    {
+        assert_send::<ClientSession>();
+        assert_send::<ClientSessionResult>();
+        assert_send::<ClientSessionError>();
+        assert_send_and_sync::<ClientSessionConfig>();
+    }
+
+    #[cfg(feature = "server-session")]
+    {
+        assert_send::<ServerSession>();
+        assert_send::<ServerSessionResult>();
+        assert_send::<ServerSessionError>();
+        assert_send_and_sync::<ServerSessionConfig>();
+    }
 
-/// Contains the metadata information a stream may advertise on publishing
+    assert_send_and_sync::<StreamMetadata>();
+};
+
+// Errors need to be usable as the source of other errors, and boxed into a
+// `Box<dyn Error + Send + Sync>` or anything built on one (such as `anyhow::Error`).
+const _: fn() = || {
+    fn assert_error<T: std::error::Error + Send + Sync + 'static>() {}
+
+    assert_error::<HandshakeError>();
+    assert_error::<ChunkDeserializationError>();
+    assert_error::<ChunkSerializationError>();
+    assert_error::<MessageDeserializationError>();
+    assert_error::<MessageSerializationError>();
+
+    #[cfg(any(feature = "client-session", feature = "server-session"))]
+    assert_error::<SessionConfigError>();
+
+    #[cfg(feature = "client-session")]
+    assert_error::<ClientSessionError>();
+
+    #[cfg(feature = "server-session")]
+    assert_error::<ServerSessionError>();
+};
+
+/// Marks the last of the packets a session produced in one call as the point to flush, since
+/// the peer needs all of them (such as a whole sequence of status messages) before it can act.
+/// Packets before it are left to be batched with it.
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+fn hint_flush_at_end<'a, I: Iterator<Item = &'a mut Packet>>(packets: I) {
+    let mut packets = packets.peekable();
+    while let Some(packet) = packets.next() {
+        packet.flush_hint = packets.peek().is_none();
+    }
+}
+
+/// Contains the metadata information a stream may advertise on publishing.
+///
+/// Sessions raise metadata as an `Arc<StreamMetadata>` so it can be fanned out to any number of
+/// subscribers without copying it.
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StreamMetadata {
     pub video_width: Option<u32>,
     pub video_height: Option<u32>,
@@ -47,6 +154,12 @@ pub struct StreamMetadata {
     pub audio_channels: Option<u32>,
     pub audio_is_stereo: Option<bool>,
     pub encoder: Option<String>,
+
+    /// Properties that aren't one of the fields above, such as version strings and custom keys
+    /// some encoders add.  They're kept so they make it through to players when the metadata
+    /// is relayed.
+    #[cfg_attr(feature = "serde", serde(default, with = "extras_serde"))]
+    pub extras: HashMap<String, Amf0Value>,
 }
 
 impl StreamMetadata {
@@ -64,72 +177,324 @@ impl StreamMetadata {
             audio_channels: None,
             audio_is_stereo: None,
             encoder: None,
+            extras: HashMap::new(),
         }
     }
 
     /// Iterates through the passed in hashmap and uses their values to set the metadata
     /// properties. The keys are based on standard metadata property names seen from existing
-    /// RTMP encoders.
-    pub fn apply_metadata_values(&mut self, mut properties: HashMap<String, Amf0Value>) {
-        for (key, value) in properties.drain() {
+    /// RTMP encoders, and any other properties are added to the extras.
+    pub fn apply_metadata_values(&mut self, properties: ObjectProperties) {
+        for (key, value) in properties {
             match key.as_ref() {
-                "width" => match value.get_number() {
-                    Some(x) => self.video_width = Some(x as u32),
-                    None => (),
-                },
-
-                "height" => match value.get_number() {
-                    Some(x) => self.video_height = Some(x as u32),
-                    None => (),
-                },
-
-                "videocodecid" => match value.get_string() {
-                    Some(x) => self.video_codec = Some(x),
-                    None => (),
-                },
-
-                "videodatarate" => match value.get_number() {
-                    Some(x) => self.video_bitrate_kbps = Some(x as u32),
-                    None => (),
-                },
-
-                "framerate" => match value.get_number() {
-                    Some(x) => self.video_frame_rate = Some(x as f32),
-                    None => (),
-                },
-
-                "audiocodecid" => match value.get_string() {
-                    Some(x) => self.audio_codec = Some(x),
-                    None => (),
-                },
-
-                "audiodatarate" => match value.get_number() {
-                    Some(x) => self.audio_bitrate_kbps = Some(x as u32),
-                    None => (),
-                },
-
-                "audiosamplerate" => match value.get_number() {
-                    Some(x) => self.audio_sample_rate = Some(x as u32),
-                    None => (),
-                },
-
-                "audiochannels" => match value.get_number() {
-                    Some(x) => self.audio_channels = Some(x as u32),
-                    None => (),
-                },
-
-                "stereo" => match value.get_boolean() {
-                    Some(x) => self.audio_is_stereo = Some(x),
-                    None => (),
-                },
-
-                "encoder" => match value.get_string() {
-                    Some(x) => self.encoder = Some(x),
-                    None => (),
-                },
-
-                _ => (),
+                "width" => {
+                    if let Some(x) = value.get_number() {
+                        self.video_width = Some(x as u32)
+                    }
+                }
+
+                "height" => {
+                    if let Some(x) = value.get_number() {
+                        self.video_height = Some(x as u32)
+                    }
+                }
+
+                "videocodecid" => {
+                    if let Some(x) = value.get_string() {
+                        self.video_codec = Some(x)
+                    }
+                }
+
+                "videodatarate" => {
+                    if let Some(x) = value.get_number() {
+                        self.video_bitrate_kbps = Some(x as u32)
+                    }
+                }
+
+                "framerate" => {
+                    if let Some(x) = value.get_number() {
+                        self.video_frame_rate = Some(x as f32)
+                    }
+                }
+
+                "audiocodecid" => {
+                    if let Some(x) = value.get_string() {
+                        self.audio_codec = Some(x)
+                    }
+                }
+
+                "audiodatarate" => {
+                    if let Some(x) = value.get_number() {
+                        self.audio_bitrate_kbps = Some(x as u32)
+                    }
+                }
+
+                "audiosamplerate" => {
+                    if let Some(x) = value.get_number() {
+                        self.audio_sample_rate = Some(x as u32)
+                    }
+                }
+
+                "audiochannels" => {
+                    if let Some(x) = value.get_number() {
+                        self.audio_channels = Some(x as u32)
+                    }
+                }
+
+                "stereo" => {
+                    if let Some(x) = value.get_boolean() {
+                        self.audio_is_stereo = Some(x)
+                    }
+                }
+
+                "encoder" => {
+                    if let Some(x) = value.get_string() {
+                        self.encoder = Some(x)
+                    }
+                }
+
+                _ => {
+                    self.extras.insert(key, value);
+                }
             }
         }
     }
+
+    /// Applies the metadata values to metadata that may be shared, such as metadata that has
+    /// already been handed out to subscribers.  The metadata is only copied if something else
+    /// still holds a reference to it, so holders of the old metadata never see the change.
+    pub fn apply_shared_metadata_values(
+        metadata: &mut Arc<StreamMetadata>,
+        properties: ObjectProperties,
+    ) {
+        Arc::make_mut(metadata).apply_metadata_values(properties);
+    }
+
+    /// Creates the `onMetaData` data message players are sent the metadata in, using the same
+    /// property names `apply_metadata_values()` reads
+    pub(crate) fn to_metadata_message(&self) -> RtmpMessage {
+        let mut properties = ObjectProperties::with_capacity(11 + self.extras.len());
+
+        self.video_width
+            .map(|x| properties.insert("width".to_string(), Amf0Value::Number(x as f64)));
+
+        self.video_height
+            .map(|x| properties.insert("height".to_string(), Amf0Value::Number(x as f64)));
+
+        self.video_codec.as_ref().map(|x| {
+            properties.insert("videocodecid".to_string(), Amf0Value::Utf8String(x.clone()))
+        });
+
+        self.video_bitrate_kbps
+            .map(|x| properties.insert("videodatarate".to_string(), Amf0Value::Number(x as f64)));
+
+        self.video_frame_rate
+            .map(|x| properties.insert("framerate".to_string(), Amf0Value::Number(x as f64)));
+
+        self.audio_codec.as_ref().map(|x| {
+            properties.insert("audiocodecid".to_string(), Amf0Value::Utf8String(x.clone()))
+        });
+
+        self.audio_bitrate_kbps
+            .map(|x| properties.insert("audiodatarate".to_string(), Amf0Value::Number(x as f64)));
+
+        self.audio_sample_rate
+            .map(|x| properties.insert("audiosamplerate".to_string(), Amf0Value::Number(x as f64)));
+
+        self.audio_channels
+            .map(|x| properties.insert("audiochannels".to_string(), Amf0Value::Number(x as f64)));
+
+        self.audio_is_stereo
+            .map(|x| properties.insert("stereo".to_string(), Amf0Value::Boolean(x)));
+
+        self.encoder
+            .as_ref()
+            .map(|x| properties.insert("encoder".to_string(), Amf0Value::Utf8String(x.clone())));
+
+        // The fields above take priority over extras that were given the same name
+        for (name, value) in &self.extras {
+            if !properties.contains_key(name) {
+                properties.insert(name.clone(), value.clone());
+            }
+        }
+
+        RtmpMessage::Amf0Data {
+            values: vec![
+                Amf0Value::Utf8String("onMetaData".to_string()),
+                Amf0Value::Object(properties),
+            ],
+        }
+    }
+}
+
+impl Default for StreamMetadata {
+    fn default() -> Self {
+        StreamMetadata::new()
+    }
+}
+
+/// Serializes metadata extras as the JSON values they convert to, since Amf0 values don't
+/// implement serde's traits.  Values without a JSON equivalent, such as dates, are deserialized
+/// back as the closest JSON value.
+#[cfg(feature = "serde")]
+mod extras_serde {
+    use rml_amf0::Amf0Value;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde_json::Value;
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer>(
+        extras: &HashMap<String, Amf0Value>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(extras.iter().map(|(name, value)| (name, value.to_json())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, Amf0Value>, D::Error> {
+        let extras = HashMap::<String, Value>::deserialize(deserializer)?;
+        Ok(extras
+            .into_iter()
+            .map(|(name, value)| (name, Amf0Value::from_json(value)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rml_amf0::Amf0Value;
+    #[cfg(all(feature = "client-session", feature = "server-session"))]
+    use std::sync::mpsc;
+    #[cfg(all(feature = "client-session", feature = "server-session"))]
+    use std::thread;
+
+    fn width_properties(width: f64) -> ObjectProperties {
+        let mut properties = ObjectProperties::new();
+        properties.insert("width".to_string(), Amf0Value::Number(width));
+        properties
+    }
+
+    #[test]
+    fn shared_metadata_is_copied_on_write() {
+        let mut metadata = Arc::new(StreamMetadata::new());
+        let subscriber_copy = metadata.clone();
+
+        StreamMetadata::apply_shared_metadata_values(&mut metadata, width_properties(1920.0));
+
+        assert_eq!(metadata.video_width, Some(1920));
+        assert_eq!(subscriber_copy.video_width, None);
+    }
+
+    #[test]
+    fn unshared_metadata_is_updated_in_place() {
+        let mut metadata = Arc::new(StreamMetadata::new());
+        let original = Arc::as_ptr(&metadata);
+
+        StreamMetadata::apply_shared_metadata_values(&mut metadata, width_properties(1280.0));
+
+        assert_eq!(metadata.video_width, Some(1280));
+        assert_eq!(Arc::as_ptr(&metadata), original);
+    }
+
+    #[test]
+    fn unknown_metadata_properties_are_kept_as_extras() {
+        let mut properties = width_properties(1920.0);
+        properties.insert("encoder".to_string(), Amf0Value::from("obs-output module"));
+        properties.insert("obs_version".to_string(), Amf0Value::from("30.1.2"));
+
+        let mut metadata = StreamMetadata::new();
+        metadata.apply_metadata_values(properties);
+
+        assert_eq!(metadata.encoder, Some("obs-output module".to_string()));
+        assert_eq!(
+            metadata.extras.len(),
+            1,
+            "Only unknown properties should be extras"
+        );
+        assert_eq!(
+            metadata.extras.get("obs_version"),
+            Some(&Amf0Value::from("30.1.2"))
+        );
+
+        match metadata.to_metadata_message() {
+            RtmpMessage::Amf0Data { values } => match values[1] {
+                Amf0Value::Object(ref properties) => {
+                    assert_eq!(properties.len(), 3, "Unexpected number of properties");
+                    assert_eq!(
+                        properties.get("obs_version"),
+                        Some(&Amf0Value::from("30.1.2"))
+                    );
+                }
+
+                ref x => panic!("Expected metadata object, instead got {:?}", x),
+            },
+
+            x => panic!("Expected Amf0 data, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn stream_metadata_round_trips_through_json() {
+        let mut metadata = StreamMetadata::new();
+        metadata.video_width = Some(1920);
+        metadata.video_codec = Some("avc1".to_string());
+        metadata.audio_is_stereo = Some(true);
+        metadata
+            .extras
+            .insert("obs_version".to_string(), Amf0Value::from("30.1.2"));
+
+        let json = ::serde_json::to_string(&metadata).unwrap();
+        let deserialized: StreamMetadata = ::serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized, metadata);
+    }
+
+    #[test]
+    #[cfg(all(feature = "client-session", feature = "server-session"))]
+    fn sessions_can_be_moved_between_threads() {
+        let (sender, receiver) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            let (server, results) = ServerSession::new(ServerSessionConfig::new()).unwrap();
+            let (client, _) = ClientSession::new(ClientSessionConfig::new()).unwrap();
+            sender.send((server, client, results)).unwrap();
+        });
+
+        worker.join().unwrap();
+        let (mut server, mut client, server_results) = receiver.recv().unwrap();
+
+        // The sessions keep working on the thread they were moved to
+        let mut client_results = Vec::new();
+        for result in server_results {
+            if let ServerSessionResult::OutboundResponse(packet) = result {
+                client_results.extend(client.handle_input(&packet.bytes).unwrap());
+            }
+        }
+
+        let request = client.request_connection("live".to_string()).unwrap();
+        let packet = match request {
+            ClientSessionResult::OutboundResponse(packet) => packet,
+            x => panic!("Expected an outbound response, instead got {:?}", x),
+        };
+
+        let events = server
+            .handle_input(&packet.bytes)
+            .unwrap()
+            .into_iter()
+            .filter_map(|result| match result {
+                ServerSessionResult::RaisedEvent(event) => Some(event),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        match events.first() {
+            Some(ServerSessionEvent::ConnectionRequested { app_name, .. }) => {
+                assert_eq!(app_name, "live");
+            }
+
+            x => panic!("Expected a connection request, instead got {:?}", x),
+        }
+    }
 }
@@ -0,0 +1,136 @@
+/// Fixed capacity storage for requests that are waiting on a response, keyed by the ids handed
+/// out for them.
+///
+/// Ids increase by one with every insert (wrapping back to the first id), and each id is stored
+/// in the slot at its position modulo the capacity, so inserts, lookups, and removals are all
+/// O(1) without hashing.  An id's slot also records the id itself, so ids from requests that
+/// have already completed are never mistaken for the request now using their slot.  If the next
+/// id's slot is still taken by a request that hasn't completed, the ids for taken slots are
+/// skipped.
+pub(crate) struct RequestSlab<T> {
+    slots: Vec<Option<(u32, T)>>,
+    first_id: u32,
+    next_id: u32,
+    len: usize,
+}
+
+impl<T> RequestSlab<T> {
+    /// Creates an empty slab whose ids start at `first_id`.  Capacities of zero are treated as
+    /// a capacity of one.
+    pub fn new(first_id: u32, capacity: usize) -> RequestSlab<T> {
+        let mut slots = Vec::with_capacity(capacity.max(1));
+        slots.resize_with(capacity.max(1), || None);
+
+        RequestSlab {
+            slots,
+            first_id,
+            next_id: first_id,
+            len: 0,
+        }
+    }
+
+    /// Stores the value, returning its id.  `None` is returned if the slab is full.
+    pub fn insert(&mut self, value: T) -> Option<u32> {
+        if self.len == self.slots.len() {
+            return None;
+        }
+
+        loop {
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1).max(self.first_id);
+
+            let index = self.index(id);
+            if self.slots[index].is_none() {
+                self.slots[index] = Some((id, value));
+                self.len += 1;
+                return Some(id);
+            }
+        }
+    }
+
+    /// Removes and returns the value stored under the id
+    pub fn remove(&mut self, id: u32) -> Option<T> {
+        let index = self.index(id);
+        match self.slots[index] {
+            Some((stored_id, _)) if stored_id == id => (),
+            _ => return None,
+        }
+
+        self.len -= 1;
+        self.slots[index].take().map(|(_, value)| value)
+    }
+
+    /// Iterates over the stored values and their ids, in no particular order
+    #[cfg(feature = "server-session")]
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.as_ref().map(|&(id, ref value)| (id, value)))
+    }
+
+    fn index(&self, id: u32) -> usize {
+        id as usize % self.slots.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_sequential_from_first_id() {
+        let mut slab = RequestSlab::new(1, 4);
+
+        assert_eq!(slab.insert("a"), Some(1));
+        assert_eq!(slab.insert("b"), Some(2));
+        assert_eq!(slab.remove(1), Some("a"));
+        assert_eq!(slab.insert("c"), Some(3));
+    }
+
+    #[test]
+    fn insert_fails_when_full() {
+        let mut slab = RequestSlab::new(0, 2);
+        slab.insert("a").unwrap();
+        slab.insert("b").unwrap();
+
+        assert_eq!(slab.insert("c"), None);
+    }
+
+    #[test]
+    fn stale_ids_do_not_match_reused_slots() {
+        let mut slab = RequestSlab::new(0, 2);
+        slab.insert("a").unwrap();
+        slab.remove(0).unwrap();
+        slab.insert("b").unwrap();
+
+        // Id 2 shares a slot with id 0
+        assert_eq!(slab.insert("c"), Some(2));
+        assert_eq!(slab.remove(0), None);
+        assert_eq!(slab.remove(2), Some("c"));
+    }
+
+    #[test]
+    fn taken_slots_are_skipped() {
+        let mut slab = RequestSlab::new(0, 2);
+        slab.insert("a").unwrap();
+        slab.insert("b").unwrap();
+        slab.remove(1).unwrap();
+
+        // Id 2 would use the slot id 0 still holds
+        assert_eq!(slab.insert("c"), Some(3));
+    }
+
+    #[test]
+    #[cfg(feature = "server-session")]
+    fn iter_returns_only_stored_values() {
+        let mut slab = RequestSlab::new(0, 4);
+        slab.insert("a").unwrap();
+        slab.insert("b").unwrap();
+        slab.insert("c").unwrap();
+        slab.remove(1).unwrap();
+
+        let mut values: Vec<_> = slab.iter().collect();
+        values.sort();
+        assert_eq!(values, vec![(0, &"a"), (2, &"c")]);
+    }
+}
@@ -1,16 +1,22 @@
 use super::PublishMode;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+/// What a stream the client created on a server session is being used for
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum StreamState {
+    /// The stream has been created, but isn't being published to or played from
     Created,
 
+    /// The client is publishing to the stream key
     Publishing {
         stream_key: String,
         mode: PublishMode,
     },
 
-    Playing {
-        stream_key: String,
-    },
+    /// The client is playing the stream key
+    Playing { stream_key: String },
 }
 
 pub struct ActiveStream {
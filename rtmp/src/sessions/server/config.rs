@@ -1,10 +1,36 @@
-/// The configuration options that govern how a RTMP server session should operate
+use sessions::config_errors::{
+    check_chunk_size, check_max_outstanding_requests, check_window_ack_size, SessionConfigError,
+};
+
+/// The configuration options that govern how a RTMP server session should operate.
+///
+/// Configs can be created with `ServerSessionConfig::builder()`, which checks the values before
+/// handing the config over.  New options may be added in any release, so the config can't be
+/// created with a struct literal outside of this crate.
 #[derive(Clone)]
+#[non_exhaustive]
 pub struct ServerSessionConfig {
     pub fms_version: String,
     pub chunk_size: u32,
     pub peer_bandwidth: u32,
     pub window_ack_size: u32,
+
+    /// The most connection, publish, and play requests that can be waiting to be accepted or
+    /// rejected at once.  Requests beyond this are refused with an error.
+    pub max_outstanding_requests: usize,
+
+    /// Records every byte the session receives and sends into a transcript, which can be
+    /// retrieved with `ServerSession::take_transcript()`.  This is off by default, as the
+    /// transcript holds on to every byte of the connection.
+    pub record_transcript: bool,
+
+    /// The most bytes the session may hold on to between reads, counting input that hasn't
+    /// been deserialized yet, the message being reassembled from multiple chunks, the chunk
+    /// headers remembered for each chunk stream, and the serializer's outbound buffer.  Input
+    /// that would take the session past its budget fails with a `MemoryBudgetExceeded` error
+    /// before it's buffered.  This defaults to `None`, which doesn't limit the session.
+    /// Transcripts aren't counted against the budget.
+    pub memory_budget: Option<usize>,
 }
 
 impl ServerSessionConfig {
@@ -15,6 +41,81 @@ impl ServerSessionConfig {
             peer_bandwidth: 2_500_000,
             window_ack_size: 1_073_741_824,
             chunk_size: 4096,
+            max_outstanding_requests: 64,
+            record_transcript: false,
+            memory_budget: None,
+        }
+    }
+
+    /// Creates a builder that starts from the default values
+    pub fn builder() -> ServerSessionConfigBuilder {
+        ServerSessionConfigBuilder {
+            config: ServerSessionConfig::new(),
+        }
+    }
+}
+
+impl Default for ServerSessionConfig {
+    fn default() -> Self {
+        ServerSessionConfig::new()
+    }
+}
+
+/// Builds a `ServerSessionConfig`, checking that its values are ones a session can work with
+#[derive(Clone)]
+pub struct ServerSessionConfigBuilder {
+    config: ServerSessionConfig,
+}
+
+impl ServerSessionConfigBuilder {
+    pub fn fms_version(mut self, fms_version: String) -> Self {
+        self.config.fms_version = fms_version;
+        self
+    }
+
+    /// Must be between 1 and 2147483647 bytes
+    pub fn chunk_size(mut self, chunk_size: u32) -> Self {
+        self.config.chunk_size = chunk_size;
+        self
+    }
+
+    /// Must be above zero
+    pub fn peer_bandwidth(mut self, peer_bandwidth: u32) -> Self {
+        self.config.peer_bandwidth = peer_bandwidth;
+        self
+    }
+
+    /// Must be above zero
+    pub fn window_ack_size(mut self, window_ack_size: u32) -> Self {
+        self.config.window_ack_size = window_ack_size;
+        self
+    }
+
+    /// Must be above zero
+    pub fn max_outstanding_requests(mut self, max_outstanding_requests: usize) -> Self {
+        self.config.max_outstanding_requests = max_outstanding_requests;
+        self
+    }
+
+    pub fn record_transcript(mut self, record_transcript: bool) -> Self {
+        self.config.record_transcript = record_transcript;
+        self
+    }
+
+    pub fn memory_budget(mut self, memory_budget: Option<usize>) -> Self {
+        self.config.memory_budget = memory_budget;
+        self
+    }
+
+    /// Returns the config, or the first value that was found to be invalid
+    pub fn build(self) -> Result<ServerSessionConfig, SessionConfigError> {
+        check_chunk_size(self.config.chunk_size)?;
+        check_window_ack_size(self.config.window_ack_size)?;
+        check_max_outstanding_requests(self.config.max_outstanding_requests)?;
+        if self.config.peer_bandwidth == 0 {
+            return Err(SessionConfigError::InvalidPeerBandwidth);
         }
+
+        Ok(self.config)
     }
 }
@@ -6,6 +6,7 @@ use thiserror::Error;
 /// Error state when a server session encounters an error
 /// Represents the type of error that occurred
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum ServerSessionError {
     /// Encountered when an error occurs while deserializing the incoming byte data
     #[error("An error occurred deserializing incoming data: {0}")]
@@ -42,7 +43,17 @@ pub enum ServerSessionError {
     #[error("The request id specified could not be matched to an outstanding request")]
     InvalidRequestId,
 
+    /// A request was made while the maximum number of requests were already waiting to be
+    /// accepted or rejected
+    #[error("The request could not be raised because {limit} requests are already outstanding")]
+    TooManyOutstandingRequests { limit: usize },
+
     /// An action was attempted to be performed on a inactive stream
     #[error("The '{action}' action was attempted on non-existant stream id {stream_id}")]
     ActionAttemptedOnInactiveStream { action: String, stream_id: u32 },
+
+    /// The peer sent input that would have made the session hold on to more bytes than its
+    /// configured memory budget allows
+    #[error("Handling the input requires {required} bytes, which is over the session's memory budget of {budget} bytes")]
+    MemoryBudgetExceeded { budget: usize, required: usize },
 }
@@ -1,11 +1,15 @@
-use super::PublishMode;
+use super::{PublishMode, ServerState};
 use bytes::Bytes;
-use rml_amf0::Amf0Value;
+use messages::LazyAmf0Arguments;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use sessions::StreamMetadata;
+use std::sync::Arc;
 use time::RtmpTimestamp;
 
 /// Represents where RTMP playback should start from
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PlayStartValue {
     /// If a live stream exists for the specified stream keyplay it, if not
     /// play the recorded stream with a matching name
@@ -48,11 +52,12 @@ pub enum ServerSessionEvent {
         stream_key: String,
     },
 
-    /// The client is changing metadata properties of the stream being published
+    /// The client is changing metadata properties of the stream being published.  The metadata
+    /// is shared so it can be passed on to every subscriber without copying it.
     StreamMetadataChanged {
         app_name: String,
         stream_key: String,
-        metadata: StreamMetadata,
+        metadata: Arc<StreamMetadata>,
     },
 
     /// Audio data was received from the client
@@ -75,8 +80,10 @@ pub enum ServerSessionEvent {
     UnhandleableAmf0Command {
         command_name: String,
         transaction_id: f64,
-        command_object: Amf0Value,
-        additional_values: Vec<Amf0Value>,
+
+        /// The command object and additional values, which are only decoded if the
+        /// application asks for them
+        arguments: LazyAmf0Arguments,
     },
 
     /// The client is requesting playback of the specified stream
@@ -101,4 +108,15 @@ pub enum ServerSessionEvent {
 
     /// The client has responded to a ping request
     PingResponseReceived { timestamp: RtmpTimestamp },
+
+    /// The session moved from one state to another.  This is raised before any other results
+    /// of whatever caused the change.
+    StateChanged {
+        previous_state: ServerState,
+        new_state: ServerState,
+    },
+
+    /// Raised last by `begin_shutdown()`.  Once the packets before it have been sent, the
+    /// client has been told everything it needs to and the connection can be closed.
+    ShutdownFinished,
 }
@@ -5,28 +5,47 @@ mod events;
 mod outstanding_requests;
 mod publish_mode;
 mod result;
-mod session_state;
+mod state;
 
 #[cfg(test)]
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::clone_on_copy,
+    clippy::let_and_return,
+    clippy::needless_return
+)]
 mod tests;
 
-use self::active_stream::{ActiveStream, StreamState};
-use self::outstanding_requests::OutstandingRequest;
-use self::session_state::SessionState;
+use self::active_stream::ActiveStream;
+use self::outstanding_requests::{OutstandingRequest, PendingRequest, RequestFailure};
+use super::request_slab::RequestSlab;
 use bytes::Bytes;
-use chunk_io::{ChunkDeserializer, ChunkSerializer, Packet};
-use messages::{PeerBandwidthLimitType, RtmpMessage, UserControlEventType};
-use rml_amf0::Amf0Value;
-use sessions::StreamMetadata;
+use chunk_io::{ChunkDeserializationError, ChunkDeserializer, Packet, PreparedPacket};
+use instrument::SessionSpan;
+use messages::{
+    LazyAmf0Command, MessagePayload, PeerBandwidthLimitType, RtmpMessage, UserControlEventType,
+};
+use rml_amf0::{Amf0Object, Amf0Value, ObjectProperties};
+use sessions::handler::{HandlerSink, ResultSink};
+use sessions::status_object::StatusObject;
+use sessions::{
+    hint_flush_at_end, system_clock, ConnectProperties, SessionHandler, StatusCode, StreamMetadata,
+};
 use std::collections::HashMap;
-use std::time::SystemTime;
+use std::mem;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 use time::RtmpTimestamp;
+use transcript::{TranscribingSerializer, Transcript};
 
-pub use self::config::ServerSessionConfig;
+pub use self::active_stream::StreamState;
+pub use self::config::{ServerSessionConfig, ServerSessionConfigBuilder};
 pub use self::errors::ServerSessionError;
 pub use self::events::{PlayStartValue, ServerSessionEvent};
+pub use self::outstanding_requests::{OutstandingRequestInfo, OutstandingRequestType};
 pub use self::publish_mode::PublishMode;
 pub use self::result::ServerSessionResult;
+pub use self::state::ServerState;
 
 /// A session that represents the server side of a single RTMP connection.
 ///
@@ -47,12 +66,12 @@ pub use self::result::ServerSessionResult;
 /// instance itself.
 pub struct ServerSession {
     start_time: SystemTime,
-    serializer: ChunkSerializer,
+    serializer: TranscribingSerializer,
     deserializer: ChunkDeserializer,
     connected_app_name: Option<String>,
-    outstanding_requests: HashMap<u32, OutstandingRequest>,
-    next_request_number: u32,
-    current_state: SessionState,
+    outstanding_requests: RequestSlab<PendingRequest>,
+    max_outstanding_requests: usize,
+    current_state: ServerState,
     fms_version: String,
     object_encoding: f64,
     active_streams: HashMap<u32, ActiveStream>,
@@ -60,6 +79,9 @@ pub struct ServerSession {
     peer_window_ack_size: Option<u32>,
     bytes_received: u64,
     bytes_received_since_last_ack: u32,
+    memory_budget: Option<usize>,
+    results_buffer: Vec<ServerSessionResult>,
+    span: SessionSpan,
 }
 
 impl ServerSession {
@@ -73,12 +95,16 @@ impl ServerSession {
     ) -> Result<(ServerSession, Vec<ServerSessionResult>), ServerSessionError> {
         let mut session = ServerSession {
             start_time: SystemTime::now(),
-            serializer: ChunkSerializer::new(),
+            serializer: TranscribingSerializer::new(if config.record_transcript {
+                Some(system_clock)
+            } else {
+                None
+            }),
             deserializer: ChunkDeserializer::new(),
             connected_app_name: None,
-            outstanding_requests: HashMap::new(),
-            next_request_number: 0,
-            current_state: SessionState::Started,
+            outstanding_requests: RequestSlab::new(0, config.max_outstanding_requests),
+            max_outstanding_requests: config.max_outstanding_requests,
+            current_state: ServerState::Started,
             fms_version: config.fms_version,
             object_encoding: 0.0,
             active_streams: HashMap::new(),
@@ -86,8 +112,14 @@ impl ServerSession {
             peer_window_ack_size: None,
             bytes_received: 0,
             bytes_received_since_last_ack: 0,
+            memory_budget: config.memory_budget,
+            results_buffer: Vec::new(),
+            span: SessionSpan::new("server"),
         };
 
+        let _span = session.span.enter();
+        trace_event!(debug, "Server session created");
+
         let mut results = Vec::with_capacity(4);
 
         let chunk_size_packet = session
@@ -135,6 +167,11 @@ impl ServerSession {
             .serializer
             .serialize(&bw_done_payload, true, false)?;
         results.push(ServerSessionResult::OutboundResponse(bw_done_packet));
+        hint_flush_at_end(
+            results
+                .iter_mut()
+                .filter_map(ServerSessionResult::packet_mut),
+        );
 
         Ok((session, results))
     }
@@ -146,6 +183,214 @@ impl ServerSession {
         bytes: &[u8],
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
         let mut results = Vec::new();
+        self.handle_input_into(bytes, &mut results)?;
+        Ok(results)
+    }
+
+    /// Works like `handle_input()`, but pushes the responses and events onto the end of
+    /// `results` instead of returning them.  Reusing the same vector for every read lets the
+    /// network loop process input without a heap allocation each time.
+    ///
+    /// If an error occurs, results for messages processed before the error remain in `results`.
+    pub fn handle_input_into(
+        &mut self,
+        bytes: &[u8],
+        results: &mut Vec<ServerSessionResult>,
+    ) -> Result<(), ServerSessionError> {
+        let _span = self.span.enter();
+        self.serializer.record_inbound(bytes);
+        let first_result = results.len();
+        let result = self.process_input(bytes, results);
+        hint_flush_at_end(
+            results[first_result..]
+                .iter_mut()
+                .filter_map(ServerSessionResult::packet_mut),
+        );
+
+        if let Err(ref error) = result {
+            trace_event!(warn, "Failed to handle input: {}", error);
+        }
+
+        result
+    }
+
+    /// Works like `handle_input()`, but returns an iterator over the responses and events
+    /// instead of a vector.  The results are held in a buffer the session reuses for every
+    /// call, so callers that consume the results right away don't need an allocation each time.
+    ///
+    /// If an error occurs, the results for messages processed before the error are returned
+    /// first and the error is returned last, so outbound packets that were already produced
+    /// aren't lost.  Results that aren't iterated over are dropped along with the iterator, so
+    /// it should always be run to completion.
+    pub fn handle_input_iter<'a>(
+        &'a mut self,
+        bytes: &[u8],
+    ) -> impl Iterator<Item = Result<ServerSessionResult, ServerSessionError>> + 'a {
+        let mut results = mem::take(&mut self.results_buffer);
+        results.clear();
+
+        let outcome = self.handle_input_into(bytes, &mut results);
+        self.results_buffer = results;
+
+        self.results_buffer
+            .drain(..)
+            .map(Ok)
+            .chain(outcome.err().map(Err))
+    }
+
+    /// Works like `handle_input()`, but passes the responses and events to `handler` as they
+    /// are produced instead of returning them.
+    ///
+    /// If an error occurs, the handler has already been given the results for messages
+    /// processed before the error.
+    pub fn handle_input_with<H>(
+        &mut self,
+        bytes: &[u8],
+        handler: &mut H,
+    ) -> Result<(), ServerSessionError>
+    where
+        H: SessionHandler<Event = ServerSessionEvent>,
+    {
+        let _span = self.span.enter();
+        self.serializer.record_inbound(bytes);
+        let mut sink = HandlerSink::new(handler);
+        let result = self.process_input(bytes, &mut sink);
+        sink.finish();
+
+        if let Err(ref error) = result {
+            trace_event!(warn, "Failed to handle input: {}", error);
+        }
+
+        result
+    }
+
+    /// What has been recorded since the session was created or the transcript was last
+    /// taken.  `None` is returned if the session wasn't configured to record a transcript.
+    pub fn transcript(&self) -> Option<&Transcript> {
+        self.serializer.transcript()
+    }
+
+    /// Returns what has been recorded so far, and keeps recording into a new transcript.
+    /// `None` is returned if the session wasn't configured to record a transcript.
+    pub fn take_transcript(&mut self) -> Option<Transcript> {
+        self.serializer.take_transcript()
+    }
+
+    /// Where the session is in accepting the client's connection.  `StateChanged` events are
+    /// raised whenever this changes.
+    pub fn current_state(&self) -> ServerState {
+        self.current_state
+    }
+
+    /// What the client is using a stream it created for, or `None` if the client has no
+    /// stream with that id
+    pub fn stream_state(&self, stream_id: u32) -> Option<&StreamState> {
+        self.active_streams
+            .get(&stream_id)
+            .map(|stream| &stream.current_state)
+    }
+
+    /// Identifies the session in the `log` records and `tracing` spans it emits, so they can be
+    /// matched up with the application's own records for the connection
+    pub fn session_id(&self) -> u64 {
+        self.span.id()
+    }
+
+    /// The number of bytes the session is holding on to, as counted against its memory budget
+    pub fn memory_usage(&self) -> usize {
+        self.deserializer.memory_usage() + self.serializer.memory_usage()
+    }
+
+    /// Tells the server session that it should accept an outstanding request
+    pub fn accept_request(
+        &mut self,
+        request_id: u32,
+    ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
+        let _span = self.span.enter();
+        let mut result = self.process_accepted_request(request_id);
+        match result {
+            Ok(ref mut results) => hint_flush_at_end(
+                results
+                    .iter_mut()
+                    .filter_map(ServerSessionResult::packet_mut),
+            ),
+
+            Err(ref error) => {
+                trace_event!(warn, "Failed to accept request {}: {}", request_id, error);
+            }
+        }
+
+        result
+    }
+
+    /// Tells the server session that it should reject an outstanding request.  The client is
+    /// sent an error status with the description, so it knows why its request was refused.
+    pub fn reject_request(
+        &mut self,
+        request_id: u32,
+        description: &str,
+    ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
+        self.fail_request(request_id, RequestFailure::Rejected, description)
+    }
+
+    /// Tells the server session to give up on an outstanding request without deciding on it,
+    /// such as when the service that would decide has timed out.  The client is sent an error
+    /// status saying its request failed, instead of one saying it was refused, since making the
+    /// same request again later may succeed.
+    pub fn cancel_request(
+        &mut self,
+        request_id: u32,
+    ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
+        self.fail_request(
+            request_id,
+            RequestFailure::Cancelled,
+            "The request was cancelled",
+        )
+    }
+
+    /// The requests that have been raised as events but not accepted, rejected, or cancelled
+    /// yet, in no particular order
+    pub fn outstanding_requests(&self) -> Vec<OutstandingRequestInfo> {
+        self.outstanding_requests
+            .iter()
+            .map(|(request_id, pending)| pending.info(request_id))
+            .collect()
+    }
+
+    fn fail_request(
+        &mut self,
+        request_id: u32,
+        failure: RequestFailure,
+        description: &str,
+    ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
+        let _span = self.span.enter();
+        let mut result = self.process_failed_request(request_id, failure, description);
+        match result {
+            Ok(ref mut results) => hint_flush_at_end(
+                results
+                    .iter_mut()
+                    .filter_map(ServerSessionResult::packet_mut),
+            ),
+
+            Err(ref error) => {
+                trace_event!(
+                    warn,
+                    "Request {} could not be {}: {}",
+                    request_id,
+                    failure.as_str(),
+                    error
+                );
+            }
+        }
+
+        result
+    }
+
+    fn process_input<S: ResultSink<ServerSessionResult>>(
+        &mut self,
+        bytes: &[u8],
+        results: &mut S,
+    ) -> Result<(), ServerSessionError> {
         self.bytes_received += bytes.len() as u64;
 
         if let Some(peer_ack_size) = self.peer_window_ack_size {
@@ -162,43 +407,54 @@ impl ServerSession {
             }
         }
 
+        // Whatever the serializer is holding on to comes out of the budget first, and the
+        // deserializer gets the rest
+        let memory_limit = self
+            .memory_budget
+            .map(|budget| budget.saturating_sub(self.serializer.memory_usage()));
+        self.deserializer.set_memory_limit(memory_limit);
+
         let mut bytes_to_process = bytes;
 
         loop {
-            match self.deserializer.get_next_message(bytes_to_process)? {
+            match self.read_next_message(bytes_to_process)? {
                 None => break,
                 Some(payload) => {
+                    bytes_to_process = &[];
+
+                    // Commands are decoded lazily, as some are handled without their arguments
+                    if let Some(command) = LazyAmf0Command::from_payload(&payload)? {
+                        let command_results =
+                            self.handle_amf0_command(payload.message_stream_id, command)?;
+
+                        results.extend(command_results);
+                        continue;
+                    }
+
                     let message = payload.to_rtmp_message()?;
 
-                    let mut message_results = match message {
+                    let message_results = match message {
                         RtmpMessage::Abort { stream_id } => self.handle_abort_message(stream_id)?,
 
                         RtmpMessage::Acknowledgement { sequence_number } => {
                             self.handle_acknowledgement_message(sequence_number)?
                         }
 
-                        RtmpMessage::Amf0Command {
-                            command_name,
-                            transaction_id,
-                            command_object,
-                            additional_arguments,
-                        } => self.handle_amf0_command(
-                            payload.message_stream_id,
-                            command_name,
-                            transaction_id,
-                            command_object,
-                            additional_arguments,
-                        )?,
-
                         RtmpMessage::Amf0Data { values } => {
                             self.handle_amf0_data(values, payload.message_stream_id)?
                         }
 
-                        RtmpMessage::AudioData { data } => self.handle_audio_data(
-                            data,
-                            payload.message_stream_id,
-                            payload.timestamp,
-                        )?,
+                        // Media makes up most messages, so its events are pushed directly
+                        // instead of going through a vector of their own
+                        RtmpMessage::AudioData { data } => {
+                            results.extend(self.handle_audio_data(
+                                data,
+                                payload.message_stream_id,
+                                payload.timestamp,
+                            ));
+
+                            continue;
+                        }
 
                         RtmpMessage::SetChunkSize { size } => self.handle_set_chunk_size(size)?,
 
@@ -218,35 +474,45 @@ impl ServerSession {
                             timestamp,
                         )?,
 
-                        RtmpMessage::VideoData { data } => self.handle_video_data(
-                            data,
-                            payload.message_stream_id,
-                            payload.timestamp,
-                        )?,
+                        RtmpMessage::VideoData { data } => {
+                            results.extend(self.handle_video_data(
+                                data,
+                                payload.message_stream_id,
+                                payload.timestamp,
+                            ));
+
+                            continue;
+                        }
 
                         RtmpMessage::WindowAcknowledgement { size } => {
                             self.handle_window_acknowledgement(size)?
                         }
 
-                        _ => vec![ServerSessionResult::UnhandleableMessageReceived(payload)],
+                        _ => {
+                            trace_event!(
+                                debug,
+                                "Received message of type {} that can't be handled",
+                                payload.type_id
+                            );
+
+                            vec![ServerSessionResult::UnhandleableMessageReceived(payload)]
+                        }
                     };
 
-                    results.append(&mut message_results);
-                    bytes_to_process = &[];
+                    results.extend(message_results);
                 }
             }
         }
 
-        Ok(results)
+        Ok(())
     }
 
-    /// Tells the server session that it should accept an outstanding request
-    pub fn accept_request(
+    fn process_accepted_request(
         &mut self,
         request_id: u32,
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
-        let request = match self.outstanding_requests.remove(&request_id) {
-            Some(x) => x,
+        let request = match self.outstanding_requests.remove(request_id) {
+            Some(x) => x.request,
             None => return Err(ServerSessionError::InvalidRequestId),
         };
 
@@ -269,66 +535,64 @@ impl ServerSession {
         }
     }
 
-    /// Prepares metadata information to be sent to the client
-    pub fn send_metadata(
+    fn process_failed_request(
         &mut self,
-        stream_id: u32,
-        metadata: &StreamMetadata,
-    ) -> Result<Packet, ServerSessionError> {
-        let mut properties = HashMap::with_capacity(11);
-
-        metadata
-            .video_width
-            .map(|x| properties.insert("width".to_string(), Amf0Value::Number(x as f64)));
-
-        metadata
-            .video_height
-            .map(|x| properties.insert("height".to_string(), Amf0Value::Number(x as f64)));
-
-        metadata.video_codec.as_ref().map(|x| {
-            properties.insert("videocodecid".to_string(), Amf0Value::Utf8String(x.clone()))
-        });
-
-        metadata
-            .video_bitrate_kbps
-            .map(|x| properties.insert("videodatarate".to_string(), Amf0Value::Number(x as f64)));
-
-        metadata
-            .video_frame_rate
-            .map(|x| properties.insert("framerate".to_string(), Amf0Value::Number(x as f64)));
-
-        metadata.audio_codec.as_ref().map(|x| {
-            properties.insert("audiocodecid".to_string(), Amf0Value::Utf8String(x.clone()))
-        });
-
-        metadata
-            .audio_bitrate_kbps
-            .map(|x| properties.insert("audiodatarate".to_string(), Amf0Value::Number(x as f64)));
-
-        metadata
-            .audio_sample_rate
-            .map(|x| properties.insert("audiosamplerate".to_string(), Amf0Value::Number(x as f64)));
-
-        metadata
-            .audio_channels
-            .map(|x| properties.insert("audiochannels".to_string(), Amf0Value::Number(x as f64)));
+        request_id: u32,
+        failure: RequestFailure,
+        description: &str,
+    ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
+        let request = match self.outstanding_requests.remove(request_id) {
+            Some(x) => x.request,
+            None => return Err(ServerSessionError::InvalidRequestId),
+        };
 
-        metadata
-            .audio_is_stereo
-            .map(|x| properties.insert("stereo".to_string(), Amf0Value::Boolean(x)));
+        let code = failure.status_code(&request);
+        let packet = match request {
+            OutstandingRequest::ConnectionRequest {
+                app_name,
+                transaction_id,
+            } => {
+                trace_event!(info, "Connection {} on app {}", failure.as_str(), app_name);
+                let status_object = create_status_object("error", code, description);
+                self.create_error_response(
+                    transaction_id,
+                    Amf0Value::Null,
+                    vec![Amf0Value::Object(status_object)],
+                    0,
+                )?
+            }
 
-        metadata
-            .encoder
-            .as_ref()
-            .map(|x| properties.insert("encoder".to_string(), Amf0Value::Utf8String(x.clone())));
+            OutstandingRequest::PublishRequested { stream_id, .. } => {
+                trace_event!(
+                    info,
+                    "Publishing {} on stream {}",
+                    failure.as_str(),
+                    stream_id
+                );
+                self.create_error_status_packet(code, description, stream_id)?
+            }
 
-        let message = RtmpMessage::Amf0Data {
-            values: vec![
-                Amf0Value::Utf8String("onMetaData".to_string()),
-                Amf0Value::Object(properties),
-            ],
+            OutstandingRequest::PlayRequested { stream_id, .. } => {
+                trace_event!(
+                    info,
+                    "Playback {} on stream {}",
+                    failure.as_str(),
+                    stream_id
+                );
+                self.create_error_status_packet(code, description, stream_id)?
+            }
         };
 
+        Ok(vec![ServerSessionResult::OutboundResponse(packet)])
+    }
+
+    /// Prepares metadata information to be sent to the client
+    pub fn send_metadata(
+        &mut self,
+        stream_id: u32,
+        metadata: &StreamMetadata,
+    ) -> Result<Packet, ServerSessionError> {
+        let message = metadata.to_metadata_message();
         let payload = message.into_message_payload(self.get_epoch(), stream_id)?;
         let packet = self.serializer.serialize(&payload, false, false)?;
         Ok(packet)
@@ -362,21 +626,169 @@ impl ServerSession {
         Ok(packet)
     }
 
+    /// Prepares a packet that was serialized ahead of time, such as the metadata and sequence
+    /// headers a `StreamHub` prepares once for every player of a stream, to be sent to the
+    /// client.  The packet has to have been prepared for the session's outbound chunk size.
+    pub fn send_prepared_packet(
+        &mut self,
+        prepared: &PreparedPacket,
+    ) -> Result<Packet, ServerSessionError> {
+        let packet = self.serializer.serialize_prepared(prepared)?;
+        Ok(packet)
+    }
+
+    /// The max chunk size packets sent to the client are split into
+    pub fn outbound_chunk_size(&self) -> u32 {
+        self.serializer.get_max_chunk_size()
+    }
+
     /// Sends a ping request to the client
     pub fn send_ping_request(&mut self) -> Result<(Packet, RtmpTimestamp), ServerSessionError> {
         let epoch = self.get_epoch();
         let message = RtmpMessage::UserControl {
             event_type: UserControlEventType::PingRequest,
-            timestamp: Some(epoch.clone()),
+            timestamp: Some(epoch),
             buffer_length: None,
             stream_id: None,
         };
 
-        let payload = message.into_message_payload(epoch.clone(), 0)?;
+        let payload = message.into_message_payload(epoch, 0)?;
         let packet = self.serializer.serialize(&payload, false, false)?;
         Ok((packet, epoch))
     }
 
+    /// The name of the application the client is connected to, once its connection request has
+    /// been accepted
+    pub fn connected_app_name(&self) -> Option<&str> {
+        self.connected_app_name.as_deref()
+    }
+
+    /// The total number of bytes that have been passed into the session
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Starts closing the connection, so servers can drain their connections cleanly when they
+    /// restart.
+    ///
+    /// Clients playing a stream are told publishing on it has ended, followed by a `StreamEof`,
+    /// and the client is then sent a `NetConnection.Connect.Closed` status.  Every stream that
+    /// was being published or played raises its finished event, and requests that are still
+    /// outstanding are dropped, since the closed status answers them.  The last result is a
+    /// `ShutdownFinished` event, after which the connection can be closed once the packets
+    /// before it have been sent.
+    ///
+    /// The session refuses any requests the client makes afterwards.  Calling this again only
+    /// returns the `ShutdownFinished` event.
+    pub fn begin_shutdown(&mut self) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
+        let _span = self.span.enter();
+        let mut results = Vec::new();
+        if self.current_state != ServerState::Closed {
+            trace_event!(info, "Shutting down");
+            self.shut_down_streams(&mut results)?;
+            self.outstanding_requests = RequestSlab::new(0, self.max_outstanding_requests);
+
+            let status_object =
+                create_status_object("status", StatusCode::ConnectClosed, "Server shutting down");
+            let message = RtmpMessage::Amf0Command {
+                command_name: "onStatus".to_string(),
+                transaction_id: 0.0,
+                command_object: Amf0Value::Null,
+                additional_arguments: vec![Amf0Value::Object(status_object)],
+            };
+
+            let payload = message.into_message_payload(self.get_epoch(), 0)?;
+            let packet = self.serializer.serialize(&payload, false, false)?;
+            results.push(ServerSessionResult::OutboundResponse(packet));
+            results.push(self.set_state(ServerState::Closed));
+        }
+
+        results.push(ServerSessionResult::RaisedEvent(
+            ServerSessionEvent::ShutdownFinished,
+        ));
+
+        hint_flush_at_end(
+            results
+                .iter_mut()
+                .filter_map(ServerSessionResult::packet_mut),
+        );
+        Ok(results)
+    }
+
+    /// Ends every stream that is being published or played, in stream id order
+    fn shut_down_streams(
+        &mut self,
+        results: &mut Vec<ServerSessionResult>,
+    ) -> Result<(), ServerSessionError> {
+        let app_name = self.connected_app_name.clone().unwrap_or_default();
+        let mut stream_ids: Vec<u32> = self.active_streams.keys().cloned().collect();
+        stream_ids.sort_unstable();
+
+        for stream_id in stream_ids {
+            let state = match self.active_streams.get_mut(&stream_id) {
+                Some(stream) => mem::replace(&mut stream.current_state, StreamState::Created),
+                None => continue,
+            };
+
+            match state {
+                StreamState::Publishing { stream_key, .. } => {
+                    results.push(ServerSessionResult::RaisedEvent(
+                        ServerSessionEvent::PublishStreamFinished {
+                            app_name: app_name.clone(),
+                            stream_key,
+                        },
+                    ));
+                }
+
+                StreamState::Playing { stream_key } => {
+                    let status_object = create_status_object(
+                        "status",
+                        StatusCode::PlayUnpublishNotify,
+                        "Server shutting down",
+                    );
+                    let unpublish_message = RtmpMessage::Amf0Command {
+                        command_name: "onStatus".to_string(),
+                        transaction_id: 0.0,
+                        command_object: Amf0Value::Null,
+                        additional_arguments: vec![Amf0Value::Object(status_object)],
+                    };
+
+                    let eof_message = RtmpMessage::UserControl {
+                        event_type: UserControlEventType::StreamEof,
+                        stream_id: Some(stream_id),
+                        buffer_length: None,
+                        timestamp: None,
+                    };
+
+                    let unpublish_payload =
+                        unpublish_message.into_message_payload(self.get_epoch(), stream_id)?;
+                    let unpublish_packet =
+                        self.serializer
+                            .serialize(&unpublish_payload, false, false)?;
+
+                    let eof_payload =
+                        eof_message.into_message_payload(self.get_epoch(), stream_id)?;
+                    let eof_packet = self.serializer.serialize(&eof_payload, false, false)?;
+
+                    results.push(ServerSessionResult::OutboundResponse(unpublish_packet));
+                    results.push(ServerSessionResult::OutboundResponse(eof_packet));
+                    results.push(ServerSessionResult::RaisedEvent(
+                        ServerSessionEvent::PlayStreamFinished {
+                            app_name: app_name.clone(),
+                            stream_key,
+                        },
+                    ));
+                }
+
+                StreamState::Created => (),
+            }
+
+            trace_event!(info, "Stream {} ended for shutdown", stream_id);
+        }
+
+        Ok(())
+    }
+
     fn handle_abort_message(
         &self,
         _stream_id: u32,
@@ -397,27 +809,61 @@ impl ServerSession {
     fn handle_amf0_command(
         &mut self,
         stream_id: u32,
-        name: String,
-        transaction_id: f64,
-        command_object: Amf0Value,
-        additional_args: Vec<Amf0Value>,
+        command: LazyAmf0Command,
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
+        let LazyAmf0Command {
+            command_name: name,
+            transaction_id,
+            arguments,
+        } = command;
+
+        trace_event!(
+            debug,
+            "Handling {} command on stream {} (transaction {})",
+            name,
+            stream_id,
+            transaction_id
+        );
+
+        // Arguments are only decoded by the commands that use them.  createStream is answered
+        // from the transaction id alone, so it's accepted even if its arguments are malformed.
         let results = match name.as_str() {
-            "connect" => self.handle_command_connect(transaction_id, command_object)?,
-            "closeStream" => self.handle_command_close_stream(additional_args)?,
+            "connect" => {
+                let (command_object, _) = arguments.decode()?;
+                self.handle_command_connect(transaction_id, command_object)?
+            }
+
             "createStream" => self.handle_command_create_stream(transaction_id)?,
-            "deleteStream" => self.handle_command_delete_stream(additional_args)?,
-            "play" => self.handle_command_play(stream_id, transaction_id, additional_args)?,
-            "publish" => self.handle_command_publish(stream_id, transaction_id, additional_args)?,
-
-            _ => vec![ServerSessionResult::RaisedEvent(
-                ServerSessionEvent::UnhandleableAmf0Command {
-                    command_name: name,
-                    additional_values: additional_args,
-                    transaction_id,
-                    command_object,
-                },
-            )],
+            "closeStream" => {
+                let (_, additional_args) = arguments.decode()?;
+                self.handle_command_close_stream(additional_args)?
+            }
+
+            "deleteStream" => {
+                let (_, additional_args) = arguments.decode()?;
+                self.handle_command_delete_stream(additional_args)?
+            }
+
+            "play" => {
+                let (_, additional_args) = arguments.decode()?;
+                self.handle_command_play(stream_id, transaction_id, additional_args)?
+            }
+
+            "publish" => {
+                let (_, additional_args) = arguments.decode()?;
+                self.handle_command_publish(stream_id, transaction_id, additional_args)?
+            }
+
+            _ => {
+                trace_event!(debug, "Received {} command that can't be handled", name);
+                vec![ServerSessionResult::RaisedEvent(
+                    ServerSessionEvent::UnhandleableAmf0Command {
+                        command_name: name,
+                        transaction_id,
+                        arguments,
+                    },
+                )]
+            }
         };
 
         Ok(results)
@@ -428,44 +874,41 @@ impl ServerSession {
         transaction_id: f64,
         command_object: Amf0Value,
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
-        let mut properties = match command_object {
-            Amf0Value::Object(properties) => properties,
-            _ => return Err(ServerSessionError::NoAppNameForConnectionRequest),
+        let properties = match ConnectProperties::from_amf0_value(command_object) {
+            Ok(properties) => properties,
+            Err(_) => return Err(ServerSessionError::NoAppNameForConnectionRequest),
         };
 
-        let app_name = match properties.remove("app") {
-            Some(value) => match value {
-                Amf0Value::Utf8String(mut app) => {
-                    if app.ends_with("/") {
-                        app.pop();
-                    }
+        let mut app_name = properties.app;
+        if app_name.ends_with('/') {
+            app_name.pop();
+        }
 
-                    app
-                }
-                _ => return Err(ServerSessionError::NoAppNameForConnectionRequest),
-            },
-            None => return Err(ServerSessionError::NoAppNameForConnectionRequest),
-        };
+        if self.current_state == ServerState::Closed {
+            let packet = self.create_error_packet(
+                StatusCode::ConnectAppShutdown,
+                "Server shutting down",
+                transaction_id,
+                0,
+            )?;
 
-        self.object_encoding = match properties.remove("objectEncoding") {
-            Some(value) => match value {
-                Amf0Value::Number(number) => number,
-                _ => 0.0,
-            },
-            None => 0.0,
-        };
+            return Ok(vec![ServerSessionResult::OutboundResponse(packet)]);
+        }
+
+        self.span.record_app_name(&app_name);
+        trace_event!(info, "Connection requested on app {}", app_name);
+
+        self.object_encoding = properties.object_encoding.unwrap_or(0.0);
 
         let request = OutstandingRequest::ConnectionRequest {
             app_name: app_name.clone(),
             transaction_id,
         };
 
-        let request_number = self.next_request_number;
-        self.next_request_number = self.next_request_number + 1;
-        self.outstanding_requests.insert(request_number, request);
+        let request_number = self.add_outstanding_request(request)?;
 
         let event = ServerSessionEvent::ConnectionRequested {
-            app_name: app_name,
+            app_name,
             request_id: request_number,
         };
 
@@ -476,7 +919,7 @@ impl ServerSession {
         &mut self,
         mut arguments: Vec<Amf0Value>,
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
-        if self.current_state != SessionState::Connected {
+        if self.current_state != ServerState::Connected {
             return Ok(Vec::new());
         }
 
@@ -486,7 +929,7 @@ impl ServerSession {
         };
 
         // First argument should be the stream id to close
-        if arguments.len() == 0 {
+        if arguments.is_empty() {
             return Ok(Vec::new());
         }
 
@@ -530,6 +973,7 @@ impl ServerSession {
         // As afar as we are concerned, a created and closed stream are equivalent.  Both allow
         // reusing the stream
         stream.current_state = StreamState::Created;
+        trace_event!(info, "Stream {} closed", stream_id);
 
         Ok(results)
     }
@@ -539,7 +983,7 @@ impl ServerSession {
         transaction_id: f64,
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
         let new_stream_id = self.next_stream_id;
-        self.next_stream_id = self.next_stream_id + 1;
+        self.next_stream_id += 1;
 
         let new_stream = ActiveStream {
             current_state: StreamState::Created,
@@ -562,7 +1006,7 @@ impl ServerSession {
         mut arguments: Vec<Amf0Value>,
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
         // Not sure if I need to send a response
-        if self.current_state != SessionState::Connected {
+        if self.current_state != ServerState::Connected {
             return Ok(Vec::new());
         }
 
@@ -571,7 +1015,7 @@ impl ServerSession {
             None => return Ok(Vec::new()),
         };
 
-        if arguments.len() == 0 {
+        if arguments.is_empty() {
             return Ok(Vec::new());
         }
 
@@ -586,6 +1030,8 @@ impl ServerSession {
             None => return Ok(Vec::new()),
         };
 
+        trace_event!(info, "Stream {} deleted", stream_id);
+
         let result = match stream.current_state {
             StreamState::Publishing {
                 ref stream_key,
@@ -621,7 +1067,7 @@ impl ServerSession {
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
         if arguments.len() < 2 {
             let packet = self.create_error_packet(
-                "NetStream.Publish.Start",
+                StatusCode::PublishStart,
                 "Invalid publish arguments",
                 transaction_id,
                 stream_id,
@@ -629,9 +1075,9 @@ impl ServerSession {
             return Ok(vec![ServerSessionResult::OutboundResponse(packet)]);
         }
 
-        if self.current_state != SessionState::Connected {
+        if self.current_state != ServerState::Connected {
             let packet = self.create_error_packet(
-                "NetStream.Publish.Start",
+                StatusCode::PublishStart,
                 "Can't publish before connecting",
                 transaction_id,
                 stream_id,
@@ -643,7 +1089,7 @@ impl ServerSession {
             Some(ref name) => name.clone(),
             None => {
                 let packet = self.create_error_packet(
-                    "NetStream.Publish.Start",
+                    StatusCode::PublishStart,
                     "Can't publish before connecting",
                     transaction_id,
                     stream_id,
@@ -656,7 +1102,7 @@ impl ServerSession {
             Amf0Value::Utf8String(stream_key) => stream_key,
             _ => {
                 let packet = self.create_error_packet(
-                    "NetStream.Publish.Start",
+                    StatusCode::PublishStart,
                     "Invalid publish arguments",
                     transaction_id,
                     stream_id,
@@ -671,9 +1117,16 @@ impl ServerSession {
                 "append" => PublishMode::Append,
                 "record" => PublishMode::Record,
                 _ => {
+                    trace_event!(
+                        info,
+                        "Rejected publish on stream {} with invalid mode {}",
+                        stream_id,
+                        raw_mode
+                    );
+
                     let error_properties = create_status_object(
                         "error",
-                        "NetStream.Publish.Start",
+                        StatusCode::PublishStart,
                         "Invalid publish mode given",
                     );
                     let packet = self.create_error_response(
@@ -689,7 +1142,7 @@ impl ServerSession {
 
             _ => {
                 let packet = self.create_error_packet(
-                    "NetStream.Publish.Start",
+                    StatusCode::PublishStart,
                     "Invalid publish arguments",
                     transaction_id,
                     stream_id,
@@ -698,15 +1151,21 @@ impl ServerSession {
             }
         };
 
+        self.span.record_stream_key(&stream_key);
+        trace_event!(
+            info,
+            "Publishing requested on stream {} ({:?})",
+            stream_id,
+            mode
+        );
+
         let request = OutstandingRequest::PublishRequested {
             stream_key: stream_key.clone(),
             mode: mode.clone(),
             stream_id,
         };
 
-        let request_number = self.next_request_number;
-        self.next_request_number = self.next_request_number + 1;
-        self.outstanding_requests.insert(request_number, request);
+        let request_number = self.add_outstanding_request(request)?;
 
         let event = ServerSessionEvent::PublishStreamRequested {
             request_id: request_number,
@@ -724,9 +1183,9 @@ impl ServerSession {
         transaction_id: f64,
         mut arguments: Vec<Amf0Value>,
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
-        if arguments.len() < 1 {
+        if arguments.is_empty() {
             let packet = self.create_error_packet(
-                "NetStream.Play.Start",
+                StatusCode::PlayStart,
                 "Invalid play arguments",
                 transaction_id,
                 stream_id,
@@ -734,9 +1193,9 @@ impl ServerSession {
             return Ok(vec![ServerSessionResult::OutboundResponse(packet)]);
         }
 
-        if self.current_state != SessionState::Connected {
+        if self.current_state != ServerState::Connected {
             let packet = self.create_error_packet(
-                "NetStream.Play.Start",
+                StatusCode::PlayStart,
                 "Can't play before connecting",
                 transaction_id,
                 stream_id,
@@ -748,7 +1207,7 @@ impl ServerSession {
             Some(ref name) => name.clone(),
             None => {
                 let packet = self.create_error_packet(
-                    "NetStream.Play.Start",
+                    StatusCode::PlayStart,
                     "Can't play before connecting",
                     transaction_id,
                     stream_id,
@@ -761,7 +1220,7 @@ impl ServerSession {
             Amf0Value::Utf8String(stream_key) => stream_key,
             _ => {
                 let packet = self.create_error_packet(
-                    "NetStream.Play.Start",
+                    StatusCode::PlayStart,
                     "Invalid play arguments",
                     transaction_id,
                     stream_id,
@@ -770,7 +1229,7 @@ impl ServerSession {
             }
         };
 
-        let start_at = if arguments.len() >= 1 {
+        let start_at = if !arguments.is_empty() {
             match arguments.remove(0) {
                 Amf0Value::Number(x) => {
                     if x == -2.0 {
@@ -790,7 +1249,7 @@ impl ServerSession {
             PlayStartValue::LiveOrRecorded
         };
 
-        let duration = if arguments.len() >= 1 {
+        let duration = if !arguments.is_empty() {
             match arguments.remove(0) {
                 Amf0Value::Number(x) => {
                     if x >= 0.0 {
@@ -806,7 +1265,7 @@ impl ServerSession {
             None
         };
 
-        let reset = if arguments.len() >= 1 {
+        let reset = if !arguments.is_empty() {
             match arguments.remove(0) {
                 Amf0Value::Boolean(x) => x,
                 _ => false,
@@ -815,14 +1274,15 @@ impl ServerSession {
             false
         };
 
+        self.span.record_stream_key(&stream_key);
+        trace_event!(info, "Playback requested on stream {}", stream_id);
+
         let request = OutstandingRequest::PlayRequested {
             stream_key: stream_key.clone(),
             stream_id,
         };
 
-        let request_number = self.next_request_number;
-        self.next_request_number = self.next_request_number + 1;
-        self.outstanding_requests.insert(request_number, request);
+        let request_number = self.add_outstanding_request(request)?;
 
         let event = ServerSessionEvent::PlayStreamRequested {
             request_id: request_number,
@@ -842,7 +1302,7 @@ impl ServerSession {
         mut data: Vec<Amf0Value>,
         stream_id: u32,
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
-        if data.len() == 0 {
+        if data.is_empty() {
             // No data so just do nothing
             return Ok(Vec::new());
         }
@@ -881,7 +1341,7 @@ impl ServerSession {
         };
 
         let publish_stream_key = match self.active_streams.get(&stream_id) {
-            Some(ref stream) => {
+            Some(stream) => {
                 match stream.current_state {
                     StreamState::Publishing {
                         ref stream_key,
@@ -897,15 +1357,14 @@ impl ServerSession {
         let mut metadata = StreamMetadata::new();
         let object = data.remove(1);
         let properties_option = object.get_object_properties();
-        match properties_option {
-            Some(properties) => metadata.apply_metadata_values(properties),
-            _ => (),
+        if let Some(properties) = properties_option {
+            metadata.apply_metadata_values(properties)
         }
 
         let event = ServerSessionEvent::StreamMetadataChanged {
             stream_key: publish_stream_key.clone(),
             app_name,
-            metadata,
+            metadata: Arc::new(metadata),
         };
 
         Ok(vec![ServerSessionResult::RaisedEvent(event)])
@@ -916,29 +1375,29 @@ impl ServerSession {
         data: Bytes,
         stream_id: u32,
         timestamp: RtmpTimestamp,
-    ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
-        if self.current_state != SessionState::Connected {
+    ) -> Option<ServerSessionResult> {
+        if self.current_state != ServerState::Connected {
             // Audio data sent before connected, just ignore it.
-            return Ok(Vec::new());
+            return None;
         }
 
         let app_name = match self.connected_app_name {
             Some(ref x) => x.clone(),
-            None => return Ok(Vec::new()), // No app name so we aren't in a valid connection state.
+            None => return None, // No app name so we aren't in a valid connection state.
         };
 
         let publish_stream_key = match self.active_streams.get(&stream_id) {
-            Some(ref stream) => {
+            Some(stream) => {
                 match stream.current_state {
                     StreamState::Publishing {
                         ref stream_key,
                         mode: _,
                     } => stream_key.clone(),
-                    _ => return Ok(Vec::new()), // Not a publishing stream so ignore it
+                    _ => return None, // Not a publishing stream so ignore it
                 }
             }
 
-            None => return Ok(Vec::new()), // Audio sent over an invalid stream, ignore it
+            None => return None, // Audio sent over an invalid stream, ignore it
         };
 
         let event = ServerSessionEvent::AudioDataReceived {
@@ -948,7 +1407,7 @@ impl ServerSession {
             data,
         };
 
-        Ok(vec![ServerSessionResult::RaisedEvent(event)])
+        Some(ServerSessionResult::RaisedEvent(event))
     }
 
     fn handle_set_chunk_size(
@@ -1003,29 +1462,29 @@ impl ServerSession {
         data: Bytes,
         stream_id: u32,
         timestamp: RtmpTimestamp,
-    ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
-        if self.current_state != SessionState::Connected {
+    ) -> Option<ServerSessionResult> {
+        if self.current_state != ServerState::Connected {
             // Video data sent before connected, just ignore it.
-            return Ok(Vec::new());
+            return None;
         }
 
         let app_name = match self.connected_app_name {
             Some(ref x) => x.clone(),
-            None => return Ok(Vec::new()), // No app name so we aren't in a valid connection state.
+            None => return None, // No app name so we aren't in a valid connection state.
         };
 
         let publish_stream_key = match self.active_streams.get(&stream_id) {
-            Some(ref stream) => {
+            Some(stream) => {
                 match stream.current_state {
                     StreamState::Publishing {
                         ref stream_key,
                         mode: _,
                     } => stream_key.clone(),
-                    _ => return Ok(Vec::new()), // Not a publishing stream so ignore it
+                    _ => return None, // Not a publishing stream so ignore it
                 }
             }
 
-            None => return Ok(Vec::new()), // Video sent over an invalid stream, ignore it
+            None => return None, // Video sent over an invalid stream, ignore it
         };
 
         let event = ServerSessionEvent::VideoDataReceived {
@@ -1035,7 +1494,7 @@ impl ServerSession {
             data,
         };
 
-        Ok(vec![ServerSessionResult::RaisedEvent(event)])
+        Some(ServerSessionResult::RaisedEvent(event))
     }
 
     fn handle_window_acknowledgement(
@@ -1052,9 +1511,10 @@ impl ServerSession {
         transaction_id: f64,
     ) -> Result<Vec<ServerSessionResult>, ServerSessionError> {
         self.connected_app_name = Some(app_name.clone());
-        self.current_state = SessionState::Connected;
+        let state_change = self.set_state(ServerState::Connected);
+        trace_event!(info, "Connection accepted on app {}", app_name);
 
-        let mut command_object_properties = HashMap::new();
+        let mut command_object_properties = ObjectProperties::new();
         command_object_properties.insert(
             "fmsVer".to_string(),
             Amf0Value::Utf8String(self.fms_version.clone()),
@@ -1062,11 +1522,8 @@ impl ServerSession {
         command_object_properties.insert("capabilities".to_string(), Amf0Value::Number(31.0));
 
         let description = "Successfully connected on app: ".to_string() + &app_name;
-        let mut additional_properties = create_status_object(
-            "status",
-            "NetConnection.Connect.Success",
-            description.as_ref(),
-        );
+        let mut additional_properties =
+            create_status_object("status", StatusCode::ConnectSuccess, description.as_ref());
         additional_properties.insert(
             "objectEncoding".to_string(),
             Amf0Value::Number(self.object_encoding),
@@ -1074,7 +1531,7 @@ impl ServerSession {
 
         let message = RtmpMessage::Amf0Command {
             command_name: "_result".to_string(),
-            transaction_id: transaction_id,
+            transaction_id,
             command_object: Amf0Value::Object(command_object_properties),
             additional_arguments: vec![Amf0Value::Object(additional_properties)],
         };
@@ -1082,7 +1539,26 @@ impl ServerSession {
         let payload = message.into_message_payload(self.get_epoch(), 0)?;
         let packet = self.serializer.serialize(&payload, false, false)?;
 
-        Ok(vec![ServerSessionResult::OutboundResponse(packet)])
+        Ok(vec![
+            state_change,
+            ServerSessionResult::OutboundResponse(packet),
+        ])
+    }
+
+    /// Moves the session to the new state, returning the event announcing the change
+    fn set_state(&mut self, state: ServerState) -> ServerSessionResult {
+        trace_event!(
+            info,
+            "State changed from {:?} to {:?}",
+            self.current_state,
+            state
+        );
+
+        let previous_state = mem::replace(&mut self.current_state, state);
+        ServerSessionResult::RaisedEvent(ServerSessionEvent::StateChanged {
+            previous_state,
+            new_state: state,
+        })
     }
 
     fn accept_publish_request(
@@ -1107,6 +1583,7 @@ impl ServerSession {
             }
         };
 
+        trace_event!(info, "Publishing started on stream {}", stream_id);
         let description = format!(
             "Successfully started publishing on stream key {}",
             stream_key
@@ -1126,7 +1603,7 @@ impl ServerSession {
             .serialize(&stream_begin_payload, false, false)?;
 
         let status_object =
-            create_status_object("status", "NetStream.Publish.Start", description.as_ref());
+            create_status_object("status", StatusCode::PublishStart, description.as_ref());
         let publish_start_message = RtmpMessage::Amf0Command {
             command_name: "onStatus".to_string(),
             transaction_id: 0.0,
@@ -1166,8 +1643,9 @@ impl ServerSession {
             }
         }
 
+        trace_event!(info, "Playback started on stream {}", stream_id);
         let reset_status_object =
-            create_status_object("status", "NetStream.Play.Reset", "Reset stream");
+            create_status_object("status", StatusCode::PlayReset, "Reset stream");
         let reset_message = RtmpMessage::Amf0Command {
             command_name: "onStatus".to_string(),
             transaction_id: 0.0,
@@ -1184,7 +1662,7 @@ impl ServerSession {
 
         let description = format!("Successfully started playback on stream key {}", stream_key);
         let start_status_object =
-            create_status_object("status", "NetStream.Play.Start", description.as_ref());
+            create_status_object("status", StatusCode::PlayStart, description.as_ref());
         let start_message = RtmpMessage::Amf0Command {
             command_name: "onStatus".to_string(),
             transaction_id: 0.0,
@@ -1200,10 +1678,10 @@ impl ServerSession {
             ],
         };
 
-        let mut data_start_properties = HashMap::new();
+        let mut data_start_properties = ObjectProperties::new();
         data_start_properties.insert(
             "code".to_string(),
-            Amf0Value::Utf8String("NetStream.Data.Start".to_string()),
+            Amf0Value::Utf8String(StatusCode::DataStart.to_string()),
         );
 
         let data2_message = RtmpMessage::Amf0Data {
@@ -1278,28 +1756,65 @@ impl ServerSession {
         Ok(packet)
     }
 
-    fn get_epoch(&self) -> RtmpTimestamp {
-        match self.start_time.elapsed() {
-            Ok(duration) => {
-                let milliseconds =
-                    (duration.as_secs() * 1000) + (duration.subsec_nanos() as u64 / 1_000_000);
+    fn add_outstanding_request(
+        &mut self,
+        request: OutstandingRequest,
+    ) -> Result<u32, ServerSessionError> {
+        let pending = PendingRequest {
+            request,
+            received_at: Instant::now(),
+        };
+
+        match self.outstanding_requests.insert(pending) {
+            Some(request_id) => Ok(request_id),
+            None => Err(ServerSessionError::TooManyOutstandingRequests {
+                limit: self.max_outstanding_requests,
+            }),
+        }
+    }
 
-                // Casting to u32 should auto-wrap the value as expected.  If not a stream will probably
-                // break after 49 days but testing shows it should wrap
-                RtmpTimestamp::new(milliseconds as u32)
+    /// Reads the next message out of the deserializer, reporting input that goes over the
+    /// deserializer's share of the memory budget as going over the session's budget
+    fn read_next_message(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<Option<MessagePayload>, ServerSessionError> {
+        match self.deserializer.get_next_message(bytes) {
+            Err(ChunkDeserializationError::MemoryLimitExceeded { limit, required }) => {
+                Err(ServerSessionError::MemoryBudgetExceeded {
+                    budget: self.memory_budget.unwrap_or(limit),
+                    required: required + self.serializer.memory_usage(),
+                })
             }
 
+            result => Ok(result?),
+        }
+    }
+
+    fn get_epoch(&self) -> RtmpTimestamp {
+        match self.start_time.elapsed() {
+            // Timestamps wrap back around to zero after 49 days
+            Ok(duration) => RtmpTimestamp::from_duration(duration),
+
             Err(_) => RtmpTimestamp::new(0), // Time went backwards, so just consider time as at epoch
         }
     }
 
     fn create_error_packet(
         &mut self,
-        code: &str,
+        code: StatusCode,
         description: &str,
         transaction_id: f64,
         stream_id: u32,
     ) -> Result<Packet, ServerSessionError> {
+        trace_event!(
+            info,
+            "Rejected command on stream {} with {}: {}",
+            stream_id,
+            code,
+            description
+        );
+
         let status_object = create_status_object("_error", code, description);
         let packet = self.create_error_response(
             transaction_id,
@@ -1309,18 +1824,27 @@ impl ServerSession {
         )?;
         Ok(packet)
     }
+
+    fn create_error_status_packet(
+        &mut self,
+        code: StatusCode,
+        description: &str,
+        stream_id: u32,
+    ) -> Result<Packet, ServerSessionError> {
+        let status_object = create_status_object("error", code, description);
+        let message = RtmpMessage::Amf0Command {
+            command_name: "onStatus".to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![Amf0Value::Object(status_object)],
+        };
+
+        let payload = message.into_message_payload(self.get_epoch(), stream_id)?;
+        let packet = self.serializer.serialize(&payload, false, false)?;
+        Ok(packet)
+    }
 }
 
-fn create_status_object(level: &str, code: &str, description: &str) -> HashMap<String, Amf0Value> {
-    let mut properties = HashMap::new();
-    properties.insert(
-        "level".to_string(),
-        Amf0Value::Utf8String(level.to_string()),
-    );
-    properties.insert("code".to_string(), Amf0Value::Utf8String(code.to_string()));
-    properties.insert(
-        "description".to_string(),
-        Amf0Value::Utf8String(description.to_string()),
-    );
-    properties
+fn create_status_object(level: &str, code: StatusCode, description: &str) -> ObjectProperties {
+    StatusObject::new(level, code, description).to_amf0_properties()
 }
@@ -1,4 +1,6 @@
 use super::PublishMode;
+use sessions::StatusCode;
+use std::time::{Duration, Instant};
 
 pub enum OutstandingRequest {
     ConnectionRequest {
@@ -17,3 +19,99 @@ pub enum OutstandingRequest {
         stream_id: u32,
     },
 }
+
+/// A request along with when the session received it
+pub struct PendingRequest {
+    pub request: OutstandingRequest,
+    pub received_at: Instant,
+}
+
+impl PendingRequest {
+    pub fn info(&self, request_id: u32) -> OutstandingRequestInfo {
+        let (request_type, stream_id) = match self.request {
+            OutstandingRequest::ConnectionRequest { .. } => (OutstandingRequestType::Connect, None),
+            OutstandingRequest::PublishRequested { stream_id, .. } => {
+                (OutstandingRequestType::Publish, Some(stream_id))
+            }
+
+            OutstandingRequest::PlayRequested { stream_id, .. } => {
+                (OutstandingRequestType::Play, Some(stream_id))
+            }
+        };
+
+        OutstandingRequestInfo {
+            request_id,
+            request_type,
+            age: self.received_at.elapsed(),
+            stream_id,
+        }
+    }
+}
+
+/// What a client is waiting on the application to accept or reject
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutstandingRequestType {
+    Connect,
+    Publish,
+    Play,
+}
+
+/// Details about a request the application has not accepted or rejected yet, as returned by
+/// `ServerSession::outstanding_requests()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutstandingRequestInfo {
+    /// The id given to the request when its event was raised
+    pub request_id: u32,
+
+    pub request_type: OutstandingRequestType,
+
+    /// How long ago the session received the request
+    pub age: Duration,
+
+    /// The stream the request was made on.  Connection requests aren't made on a stream, so
+    /// this is `None` for them.
+    pub stream_id: Option<u32>,
+}
+
+/// Why a request is being answered with an error
+#[derive(Clone, Copy)]
+pub enum RequestFailure {
+    /// The application decided not to allow the request
+    Rejected,
+
+    /// The application gave up on deciding, such as when a service it checks with timed out
+    Cancelled,
+}
+
+impl RequestFailure {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            RequestFailure::Rejected => "rejected",
+            RequestFailure::Cancelled => "cancelled",
+        }
+    }
+
+    /// The code the client is sent.  Cancelled requests get codes that say the request failed,
+    /// rather than that it was refused, since trying again later could succeed.
+    pub fn status_code(&self, request: &OutstandingRequest) -> StatusCode {
+        match (*self, request) {
+            (RequestFailure::Rejected, &OutstandingRequest::ConnectionRequest { .. }) => {
+                StatusCode::ConnectRejected
+            }
+
+            (RequestFailure::Rejected, &OutstandingRequest::PublishRequested { .. }) => {
+                StatusCode::PublishBadName
+            }
+
+            (RequestFailure::Cancelled, &OutstandingRequest::ConnectionRequest { .. }) => {
+                StatusCode::ConnectFailed
+            }
+
+            (RequestFailure::Cancelled, &OutstandingRequest::PublishRequested { .. }) => {
+                StatusCode::StreamFailed
+            }
+
+            (_, &OutstandingRequest::PlayRequested { .. }) => StatusCode::PlayFailed,
+        }
+    }
+}
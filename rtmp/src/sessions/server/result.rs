@@ -1,6 +1,7 @@
 use super::events::ServerSessionEvent;
 use chunk_io::Packet;
 use messages::MessagePayload;
+use sessions::handler::{IntoSessionOutput, SessionOutput};
 
 /// A single result that is returned when a server session processes some bytes
 #[derive(PartialEq, Debug)]
@@ -18,3 +19,27 @@ pub enum ServerSessionResult {
     /// allows the consumer application to do something with it if it wants to (special logging)
     UnhandleableMessageReceived(MessagePayload),
 }
+
+impl ServerSessionResult {
+    /// The packet to send, if this result is one
+    pub(crate) fn packet_mut(&mut self) -> Option<&mut Packet> {
+        match *self {
+            ServerSessionResult::OutboundResponse(ref mut packet) => Some(packet),
+            _ => None,
+        }
+    }
+}
+
+impl IntoSessionOutput for ServerSessionResult {
+    type Event = ServerSessionEvent;
+
+    fn into_output(self) -> SessionOutput<ServerSessionEvent> {
+        match self {
+            ServerSessionResult::OutboundResponse(packet) => SessionOutput::Packet(packet),
+            ServerSessionResult::RaisedEvent(event) => SessionOutput::Event(event),
+            ServerSessionResult::UnhandleableMessageReceived(payload) => {
+                SessionOutput::UnhandleableMessage(payload)
+            }
+        }
+    }
+}
@@ -1,5 +0,0 @@
-#[derive(Eq, PartialEq)]
-pub enum SessionState {
-    Started,
-    Connected,
-}
@@ -0,0 +1,18 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Where a server session is in the process of accepting a client's connection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ServerState {
+    /// The client has not had a connection request accepted yet
+    Started,
+
+    /// A connection request was accepted, so the client can create streams to publish and play
+    /// on
+    Connected,
+
+    /// The session was shut down with `begin_shutdown()`, so the client can't make any more
+    /// requests
+    Closed,
+}
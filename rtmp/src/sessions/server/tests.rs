@@ -1,9 +1,10 @@
 use super::*;
 use bytes::BytesMut;
-use chunk_io::ChunkDeserializer;
+use chunk_io::{ChunkDeserializer, ChunkSerializer};
+use fixtures::split_server_results;
 use messages::{MessagePayload, PeerBandwidthLimitType, RtmpMessage, UserControlEventType};
-use rml_amf0::Amf0Value;
-use std::collections::HashMap;
+use rml_amf0::{Amf0DeserializationError, Amf0Value, ObjectProperties};
+use sessions::SessionConfigError;
 
 const DEFAULT_CHUNK_SIZE: u32 = 1111;
 const DEFAULT_PEER_BANDWIDTH: u32 = 2222;
@@ -15,7 +16,7 @@ fn new_config_creates_initial_responses() {
     let mut deserializer = ChunkDeserializer::new();
     let (_, results) = ServerSession::new(config).unwrap();
 
-    let (responses, _) = split_results(&mut deserializer, results);
+    let (responses, _) = split_server_results(&mut deserializer, results);
 
     assert_vec_contains!(
         responses,
@@ -81,7 +82,7 @@ fn can_accept_connection_request() {
         "Unexpected number of responses when handling connect request message"
     );
 
-    let (_, events) = split_results(&mut deserializer, connect_results);
+    let (_, events) = split_server_results(&mut deserializer, connect_results);
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     let request_id = match events[0] {
         ServerSessionEvent::ConnectionRequested {
@@ -91,14 +92,24 @@ fn can_accept_connection_request() {
         _ => panic!("First event was not as expected: {:?}", events[0]),
     };
 
+    assert_eq!(session.current_state(), ServerState::Started);
     let accept_results = session.accept_request(request_id).unwrap();
     assert_eq!(
         accept_results.len(),
-        1,
+        2,
         "Unexpected number of results returned"
     );
 
-    let (responses, _) = split_results(&mut deserializer, accept_results);
+    let (responses, events) = split_server_results(&mut deserializer, accept_results);
+    assert_eq!(
+        events,
+        vec![ServerSessionEvent::StateChanged {
+            previous_state: ServerState::Started,
+            new_state: ServerState::Connected,
+        }]
+    );
+    assert_eq!(session.current_state(), ServerState::Connected);
+
     match responses[0] {
         (
             _,
@@ -177,7 +188,7 @@ fn connect_request_strips_trailing_slash() {
         "Unexpected number of responses when handling connect request message"
     );
 
-    let (_, events) = split_results(&mut deserializer, connect_results);
+    let (_, events) = split_server_results(&mut deserializer, connect_results);
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     match events[0] {
         ServerSessionEvent::ConnectionRequested {
@@ -188,6 +199,292 @@ fn connect_request_strips_trailing_slash() {
     };
 }
 
+#[test]
+fn can_reject_connection_request() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, initial_results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, initial_results);
+
+    let connect_payload = create_connect_message("some_app".to_string(), 15, 0, 0.0);
+    let connect_packet = serializer.serialize(&connect_payload, true, false).unwrap();
+    let connect_results = session.handle_input(&connect_packet.bytes[..]).unwrap();
+    let (_, events) = split_server_results(&mut deserializer, connect_results);
+    let request_id = match events[0] {
+        ServerSessionEvent::ConnectionRequested { request_id, .. } => request_id,
+        _ => panic!("First event was not as expected: {:?}", events[0]),
+    };
+
+    let reject_results = session.reject_request(request_id, "Not allowed").unwrap();
+    let (responses, _) = split_server_results(&mut deserializer, reject_results);
+    assert_eq!(responses.len(), 1, "Unexpected number of responses");
+    match responses[0] {
+        (
+            _,
+            RtmpMessage::Amf0Command {
+                ref command_name,
+                transaction_id,
+                ref additional_arguments,
+                ..
+            },
+        ) if command_name == "_error" && transaction_id == 1.0 => match additional_arguments[0] {
+            Amf0Value::Object(ref properties) => {
+                assert_eq!(
+                    properties.get("code"),
+                    Some(&Amf0Value::Utf8String(
+                        "NetConnection.Connect.Rejected".to_string()
+                    )),
+                    "Unexpected code value"
+                );
+                assert_eq!(
+                    properties.get("description"),
+                    Some(&Amf0Value::Utf8String("Not allowed".to_string())),
+                    "Unexpected description value"
+                );
+            }
+
+            _ => panic!(
+                "Additional arguments was not an Amf0 object: {:?}",
+                additional_arguments[0]
+            ),
+        },
+
+        _ => panic!("Unexpected first response message: {:?}", responses[0]),
+    }
+
+    match session.accept_request(request_id) {
+        Err(ServerSessionError::InvalidRequestId) => (),
+        x => panic!("Expected rejected request to be removed, got {:?}", x),
+    }
+}
+
+#[test]
+fn can_reject_publish_request() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, results);
+    perform_connection("some_app", &mut session, &mut serializer, &mut deserializer);
+
+    let stream_id = create_active_stream(&mut session, &mut serializer, &mut deserializer);
+    let message = RtmpMessage::Amf0Command {
+        command_name: "publish".to_string(),
+        transaction_id: 5.0,
+        command_object: Amf0Value::Null,
+        additional_arguments: vec![
+            Amf0Value::Utf8String("stream_key".to_string()),
+            Amf0Value::Utf8String("live".to_string()),
+        ],
+    };
+
+    let publish_payload = message
+        .into_message_payload(RtmpTimestamp::new(0), stream_id)
+        .unwrap();
+    let publish_packet = serializer
+        .serialize(&publish_payload, false, false)
+        .unwrap();
+    let publish_results = session.handle_input(&publish_packet.bytes[..]).unwrap();
+    let (_, events) = split_server_results(&mut deserializer, publish_results);
+    let request_id = match events[0] {
+        ServerSessionEvent::PublishStreamRequested { request_id, .. } => request_id,
+        _ => panic!("Unexpected first event found: {:?}", events[0]),
+    };
+
+    let reject_results = session.reject_request(request_id, "Bad token").unwrap();
+    let (responses, _) = split_server_results(&mut deserializer, reject_results);
+    assert_eq!(responses.len(), 1, "Unexpected number of responses");
+    match responses[0] {
+        (
+            ref payload,
+            RtmpMessage::Amf0Command {
+                ref command_name,
+                ref additional_arguments,
+                ..
+            },
+        ) if command_name == "onStatus" => {
+            assert_eq!(payload.message_stream_id, stream_id, "Unexpected stream id");
+            match additional_arguments[0] {
+                Amf0Value::Object(ref properties) => {
+                    assert_eq!(
+                        properties.get("level"),
+                        Some(&Amf0Value::Utf8String("error".to_string())),
+                        "Unexpected level value"
+                    );
+                    assert_eq!(
+                        properties.get("code"),
+                        Some(&Amf0Value::Utf8String(
+                            "NetStream.Publish.BadName".to_string()
+                        )),
+                        "Unexpected code value"
+                    );
+                }
+
+                _ => panic!(
+                    "Additional arguments was not an Amf0 object: {:?}",
+                    additional_arguments[0]
+                ),
+            }
+        }
+
+        _ => panic!("Unexpected first response message: {:?}", responses[0]),
+    }
+}
+
+#[test]
+fn outstanding_connection_request_is_listed_until_cancelled() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, initial_results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, initial_results);
+
+    let connect_payload = create_connect_message("some_app".to_string(), 15, 0, 0.0);
+    let connect_packet = serializer.serialize(&connect_payload, true, false).unwrap();
+    let connect_results = session.handle_input(&connect_packet.bytes[..]).unwrap();
+    let (_, events) = split_server_results(&mut deserializer, connect_results);
+    let request_id = match events[0] {
+        ServerSessionEvent::ConnectionRequested { request_id, .. } => request_id,
+        _ => panic!("First event was not as expected: {:?}", events[0]),
+    };
+
+    let outstanding = session.outstanding_requests();
+    assert_eq!(
+        outstanding.len(),
+        1,
+        "Unexpected number of outstanding requests"
+    );
+    assert_eq!(
+        outstanding[0].request_id, request_id,
+        "Unexpected request id"
+    );
+    assert_eq!(
+        outstanding[0].request_type,
+        OutstandingRequestType::Connect,
+        "Unexpected request type"
+    );
+    assert_eq!(outstanding[0].stream_id, None, "Unexpected stream id");
+
+    let cancel_results = session.cancel_request(request_id).unwrap();
+    let (responses, _) = split_server_results(&mut deserializer, cancel_results);
+    assert_eq!(responses.len(), 1, "Unexpected number of responses");
+    match responses[0] {
+        (
+            _,
+            RtmpMessage::Amf0Command {
+                ref command_name,
+                ref additional_arguments,
+                ..
+            },
+        ) if command_name == "_error" => match additional_arguments[0] {
+            Amf0Value::Object(ref properties) => assert_eq!(
+                properties.get("code"),
+                Some(&Amf0Value::Utf8String(
+                    "NetConnection.Connect.Failed".to_string()
+                )),
+                "Unexpected code value"
+            ),
+
+            _ => panic!(
+                "Additional arguments was not an Amf0 object: {:?}",
+                additional_arguments[0]
+            ),
+        },
+
+        _ => panic!("Unexpected first response message: {:?}", responses[0]),
+    }
+
+    assert!(session.outstanding_requests().is_empty());
+    match session.cancel_request(request_id) {
+        Err(ServerSessionError::InvalidRequestId) => (),
+        x => panic!("Expected cancelled request to be removed, got {:?}", x),
+    }
+}
+
+#[test]
+fn cancelled_publish_request_sends_stream_failed_status() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, results);
+    perform_connection("some_app", &mut session, &mut serializer, &mut deserializer);
+
+    let stream_id = create_active_stream(&mut session, &mut serializer, &mut deserializer);
+    let message = RtmpMessage::Amf0Command {
+        command_name: "publish".to_string(),
+        transaction_id: 5.0,
+        command_object: Amf0Value::Null,
+        additional_arguments: vec![
+            Amf0Value::Utf8String("stream_key".to_string()),
+            Amf0Value::Utf8String("live".to_string()),
+        ],
+    };
+
+    let publish_payload = message
+        .into_message_payload(RtmpTimestamp::new(0), stream_id)
+        .unwrap();
+    let publish_packet = serializer
+        .serialize(&publish_payload, false, false)
+        .unwrap();
+    let publish_results = session.handle_input(&publish_packet.bytes[..]).unwrap();
+    let (_, events) = split_server_results(&mut deserializer, publish_results);
+    let request_id = match events[0] {
+        ServerSessionEvent::PublishStreamRequested { request_id, .. } => request_id,
+        _ => panic!("Unexpected first event found: {:?}", events[0]),
+    };
+
+    let outstanding = session.outstanding_requests();
+    assert_eq!(
+        outstanding.len(),
+        1,
+        "Unexpected number of outstanding requests"
+    );
+    assert_eq!(
+        outstanding[0].request_type,
+        OutstandingRequestType::Publish,
+        "Unexpected request type"
+    );
+    assert_eq!(
+        outstanding[0].stream_id,
+        Some(stream_id),
+        "Unexpected stream id"
+    );
+
+    let cancel_results = session.cancel_request(request_id).unwrap();
+    let (responses, _) = split_server_results(&mut deserializer, cancel_results);
+    assert_eq!(responses.len(), 1, "Unexpected number of responses");
+    match responses[0] {
+        (
+            ref payload,
+            RtmpMessage::Amf0Command {
+                ref command_name,
+                ref additional_arguments,
+                ..
+            },
+        ) if command_name == "onStatus" => {
+            assert_eq!(payload.message_stream_id, stream_id, "Unexpected stream id");
+            match additional_arguments[0] {
+                Amf0Value::Object(ref properties) => assert_eq!(
+                    properties.get("code"),
+                    Some(&Amf0Value::Utf8String("NetStream.Failed".to_string())),
+                    "Unexpected code value"
+                ),
+
+                _ => panic!(
+                    "Additional arguments was not an Amf0 object: {:?}",
+                    additional_arguments[0]
+                ),
+            }
+        }
+
+        _ => panic!("Unexpected first response message: {:?}", responses[0]),
+    }
+
+    assert!(session.outstanding_requests().is_empty());
+}
+
 #[test]
 fn accepted_connection_responds_with_same_object_encoding_value_as_connection_request() {
     let config = get_basic_config();
@@ -205,7 +502,7 @@ fn accepted_connection_responds_with_same_object_encoding_value_as_connection_re
         "Unexpected number of responses when handling connect request message"
     );
 
-    let (_, events) = split_results(&mut deserializer, connect_results);
+    let (_, events) = split_server_results(&mut deserializer, connect_results);
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     let request_id = match events[0] {
         ServerSessionEvent::ConnectionRequested {
@@ -218,11 +515,11 @@ fn accepted_connection_responds_with_same_object_encoding_value_as_connection_re
     let accept_results = session.accept_request(request_id).unwrap();
     assert_eq!(
         accept_results.len(),
-        1,
+        2,
         "Unexpected number of results returned"
     );
 
-    let (responses, _) = split_results(&mut deserializer, accept_results);
+    let (responses, _) = split_server_results(&mut deserializer, accept_results);
     match responses[0] {
         (
             _,
@@ -305,7 +602,7 @@ fn can_create_stream_on_connected_session() {
         .unwrap();
     let packet = serializer.serialize(&payload, true, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (responses, _) = split_results(&mut deserializer, results);
+    let (responses, _) = split_server_results(&mut deserializer, results);
 
     assert_eq!(
         responses.len(),
@@ -365,7 +662,7 @@ fn can_accept_live_publishing_to_requested_stream_key() {
         .serialize(&publish_payload, false, false)
         .unwrap();
     let publish_results = session.handle_input(&publish_packet.bytes[..]).unwrap();
-    let (_, events) = split_results(&mut deserializer, publish_results);
+    let (_, events) = split_server_results(&mut deserializer, publish_results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     let request_id = match events[0] {
@@ -379,8 +676,17 @@ fn can_accept_live_publishing_to_requested_stream_key() {
         _ => panic!("Unexpected first event found: {:?}", events[0]),
     };
 
+    assert_eq!(session.stream_state(stream_id), Some(&StreamState::Created));
     let accept_results = session.accept_request(request_id).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, accept_results);
+    assert_eq!(
+        session.stream_state(stream_id),
+        Some(&StreamState::Publishing {
+            stream_key: "stream_key".to_string(),
+            mode: PublishMode::Live,
+        })
+    );
+
+    let (mut responses, _) = split_server_results(&mut deserializer, accept_results);
     assert_eq!(
         responses.len(),
         2,
@@ -487,7 +793,7 @@ fn can_receive_and_raise_event_for_metadata_from_obs() {
         &mut deserializer,
     );
 
-    let mut properties = HashMap::new();
+    let mut properties = ObjectProperties::new();
     properties.insert("width".to_string(), Amf0Value::Number(1920_f64));
     properties.insert("height".to_string(), Amf0Value::Number(1080_f64));
     properties.insert(
@@ -525,7 +831,7 @@ fn can_receive_and_raise_event_for_metadata_from_obs() {
         .serialize(&metadata_payload, false, false)
         .unwrap();
     let metadata_results = session.handle_input(&metadata_packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, metadata_results);
+    let (_, mut events) = split_server_results(&mut deserializer, metadata_results);
 
     assert_eq!(events.len(), 1, "Unexpected number of metadata events");
 
@@ -626,7 +932,7 @@ fn can_receive_audio_data_on_published_stream() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_server_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
 
@@ -680,7 +986,7 @@ fn can_receive_video_data_on_published_stream() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_server_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
 
@@ -701,6 +1007,203 @@ fn can_receive_video_data_on_published_stream() {
     }
 }
 
+#[test]
+fn create_stream_is_answered_without_decoding_its_arguments() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, results);
+    perform_connection("some_app", &mut session, &mut serializer, &mut deserializer);
+
+    let message = RtmpMessage::Amf0Command {
+        command_name: "createStream".to_string(),
+        transaction_id: 4.0,
+        command_object: Amf0Value::Null,
+        additional_arguments: Vec::new(),
+    };
+
+    // An unknown Amf0 marker makes the arguments undecodable
+    let mut payload = message
+        .into_message_payload(RtmpTimestamp::new(0), 0)
+        .unwrap();
+    let mut data = payload.data.to_vec();
+    data.push(0xff);
+    payload.data = Bytes::from(data);
+
+    let packet = serializer.serialize(&payload, true, false).unwrap();
+    let results = session.handle_input(&packet.bytes[..]).unwrap();
+    let (responses, _) = split_server_results(&mut deserializer, results);
+
+    assert_eq!(
+        responses.len(),
+        1,
+        "Unexpected number of responses returned"
+    );
+    match responses[0] {
+        (
+            _,
+            RtmpMessage::Amf0Command {
+                ref command_name,
+                transaction_id,
+                ..
+            },
+        ) => {
+            assert_eq!(command_name, "_result", "Unexpected command name");
+            assert_eq!(transaction_id, 4.0, "Unexpected transaction id");
+        }
+
+        ref x => panic!("Expected _result command, instead got: {:?}", x),
+    }
+}
+
+#[test]
+fn unhandleable_command_is_raised_without_decoding_its_arguments() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, results);
+    perform_connection("some_app", &mut session, &mut serializer, &mut deserializer);
+
+    let message = RtmpMessage::Amf0Command {
+        command_name: "someCommand".to_string(),
+        transaction_id: 5.0,
+        command_object: Amf0Value::Null,
+        additional_arguments: Vec::new(),
+    };
+
+    // An unknown Amf0 marker makes the arguments undecodable
+    let mut payload = message
+        .into_message_payload(RtmpTimestamp::new(0), 0)
+        .unwrap();
+    let mut data = payload.data.to_vec();
+    data.push(0xff);
+    payload.data = Bytes::from(data);
+
+    let packet = serializer.serialize(&payload, true, false).unwrap();
+    let results = session.handle_input(&packet.bytes[..]).unwrap();
+    let (_, events) = split_server_results(&mut deserializer, results);
+
+    assert_eq!(events.len(), 1, "Unexpected number of events returned");
+    match events[0] {
+        ServerSessionEvent::UnhandleableAmf0Command {
+            ref command_name,
+            transaction_id,
+            ref arguments,
+        } => {
+            assert_eq!(command_name, "someCommand", "Unexpected command name");
+            assert_eq!(transaction_id, 5.0, "Unexpected transaction id");
+            assert!(
+                arguments.clone().decode().is_err(),
+                "Expected arguments to fail decoding"
+            );
+        }
+
+        ref x => panic!("Expected unhandleable command event, instead got: {:?}", x),
+    }
+}
+
+#[test]
+fn handle_input_into_pushes_events_for_every_message_onto_existing_results() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, results);
+    perform_connection("some_app", &mut session, &mut serializer, &mut deserializer);
+    let stream_id = create_active_stream(&mut session, &mut serializer, &mut deserializer);
+    start_publishing(
+        "stream_key",
+        stream_id,
+        &mut session,
+        &mut serializer,
+        &mut deserializer,
+    );
+
+    let mut input = Vec::new();
+    for timestamp in [10, 20].iter() {
+        let message = RtmpMessage::VideoData {
+            data: Bytes::from(vec![1_u8, 2_u8, 3_u8]),
+        };
+        let payload = message
+            .into_message_payload(RtmpTimestamp::new(*timestamp), stream_id)
+            .unwrap();
+        let packet = serializer.serialize(&payload, false, false).unwrap();
+        input.extend_from_slice(&packet.bytes[..]);
+    }
+
+    let mut results = Vec::with_capacity(4);
+    let allocation = results.as_ptr();
+    session.handle_input_into(&input, &mut results).unwrap();
+
+    assert_eq!(
+        results.as_ptr(),
+        allocation,
+        "Results were not pushed in place"
+    );
+    let (_, events) = split_server_results(&mut deserializer, results);
+    let timestamps = events
+        .into_iter()
+        .map(|event| match event {
+            ServerSessionEvent::VideoDataReceived { timestamp, .. } => timestamp,
+            x => panic!("Expected VideoDataReceived event, instead got: {:?}", x),
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        timestamps,
+        vec![RtmpTimestamp::new(10), RtmpTimestamp::new(20)],
+        "Unexpected timestamps"
+    );
+}
+
+#[test]
+fn handle_input_iter_returns_results_for_every_message() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, results);
+    perform_connection("some_app", &mut session, &mut serializer, &mut deserializer);
+    let stream_id = create_active_stream(&mut session, &mut serializer, &mut deserializer);
+    start_publishing(
+        "stream_key",
+        stream_id,
+        &mut session,
+        &mut serializer,
+        &mut deserializer,
+    );
+
+    let mut timestamps = Vec::new();
+    for timestamp in [10, 20].iter() {
+        let message = RtmpMessage::VideoData {
+            data: Bytes::from(vec![1_u8, 2_u8, 3_u8]),
+        };
+        let payload = message
+            .into_message_payload(RtmpTimestamp::new(*timestamp), stream_id)
+            .unwrap();
+        let packet = serializer.serialize(&payload, false, false).unwrap();
+
+        for result in session.handle_input_iter(&packet.bytes[..]) {
+            match result.unwrap() {
+                ServerSessionResult::RaisedEvent(ServerSessionEvent::VideoDataReceived {
+                    timestamp,
+                    ..
+                }) => timestamps.push(timestamp),
+
+                x => panic!("Expected VideoDataReceived event, instead got: {:?}", x),
+            }
+        }
+    }
+
+    assert_eq!(
+        timestamps,
+        vec![RtmpTimestamp::new(10), RtmpTimestamp::new(20)],
+        "Unexpected timestamps"
+    );
+}
+
 #[test]
 fn publish_finished_event_raised_when_delete_stream_invoked_on_publishing_stream() {
     let config = get_basic_config();
@@ -738,7 +1241,7 @@ fn publish_finished_event_raised_when_delete_stream_invoked_on_publishing_stream
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_server_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
 
@@ -795,7 +1298,7 @@ fn publish_finished_event_raised_when_close_stream_invoked_on_publishing_stream(
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_server_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
 
@@ -858,7 +1361,7 @@ fn can_request_publishing_on_closed_stream() {
         .serialize(&publish_payload, false, false)
         .unwrap();
     let publish_results = session.handle_input(&publish_packet.bytes[..]).unwrap();
-    let (_, events) = split_results(&mut deserializer, publish_results);
+    let (_, events) = split_server_results(&mut deserializer, publish_results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     match events[0] {
@@ -906,7 +1409,7 @@ fn can_accept_play_command_with_no_optional_parameters_to_requested_stream_key()
         .unwrap();
     let play_packet = serializer.serialize(&play_payload, false, false).unwrap();
     let play_results = session.handle_input(&play_packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, play_results);
+    let (_, mut events) = split_server_results(&mut deserializer, play_results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     let request_id = match events.remove(0) {
@@ -940,7 +1443,7 @@ fn can_accept_play_command_with_no_optional_parameters_to_requested_stream_key()
     };
 
     let accept_results = session.accept_request(request_id).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, accept_results);
+    let (mut responses, _) = split_server_results(&mut deserializer, accept_results);
     assert_eq!(responses.len(), 5, "Unexpected number of messages received");
 
     match responses.remove(0) {
@@ -1154,7 +1657,7 @@ fn can_accept_play_command_with_all_optional_parameters_to_requested_stream_key(
         .unwrap();
     let play_packet = serializer.serialize(&play_payload, false, false).unwrap();
     let play_results = session.handle_input(&play_packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, play_results);
+    let (_, mut events) = split_server_results(&mut deserializer, play_results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     let request_id = match events.remove(0) {
@@ -1229,7 +1732,7 @@ fn play_finished_event_when_close_stream_invoked() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_server_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
 
@@ -1287,7 +1790,7 @@ fn play_finished_event_when_delete_stream_invoked_on_playing_stream() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_server_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
 
@@ -1344,6 +1847,9 @@ fn can_send_metadata_to_playing_stream() {
         video_frame_rate: Some(107.0),
         video_height: Some(108),
         video_width: Some(109),
+        extras: vec![("obs_version".to_string(), Amf0Value::from("110"))]
+            .into_iter()
+            .collect(),
     };
 
     let packet = session.send_metadata(stream_id, &metadata).unwrap();
@@ -1421,6 +1927,11 @@ fn can_send_metadata_to_playing_stream() {
                         Some(&Amf0Value::Utf8String("104".to_string())),
                         "Unexpected encoder"
                     );
+                    assert_eq!(
+                        properties.get("obs_version"),
+                        Some(&Amf0Value::Utf8String("110".to_string())),
+                        "Unexpected obs_version"
+                    );
                 }
 
                 x => panic!(
@@ -1568,7 +2079,7 @@ fn automatically_responds_to_ping_requests() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_server_results(&mut deserializer, results);
 
     assert_eq!(
         responses.len(),
@@ -1625,7 +2136,7 @@ fn event_raised_when_ping_response_received() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_server_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "One event expected");
     match events.remove(0) {
@@ -1722,7 +2233,7 @@ fn sends_ack_after_receiving_window_ack_bytes() {
         .unwrap();
     let video_packet = serializer.serialize(&video_payload, false, false).unwrap();
     let results = session.handle_input(&video_packet.bytes[..]).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_server_results(&mut deserializer, results);
 
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
@@ -1740,7 +2251,7 @@ fn sends_ack_after_receiving_window_ack_bytes() {
         .unwrap();
     let video_packet = serializer.serialize(&video_payload, false, false).unwrap();
     let results = session.handle_input(&video_packet.bytes[..]).unwrap();
-    let (responses, _) = split_results(&mut deserializer, results);
+    let (responses, _) = split_server_results(&mut deserializer, results);
     assert_eq!(responses.len(), 0, "Expected no responses");
 
     let mut bytes = BytesMut::new();
@@ -1753,7 +2264,7 @@ fn sends_ack_after_receiving_window_ack_bytes() {
         .unwrap();
     let video_packet = serializer.serialize(&video_payload, false, false).unwrap();
     let results = session.handle_input(&video_packet.bytes[..]).unwrap();
-    let (mut responses, _) = split_results(&mut deserializer, results);
+    let (mut responses, _) = split_server_results(&mut deserializer, results);
     assert_eq!(responses.len(), 1, "Unexpected number of responses");
     match responses.remove(0) {
         (_, RtmpMessage::Acknowledgement { sequence_number: _ }) => (), // No good way to predict sequence number
@@ -1785,7 +2296,7 @@ fn event_raised_when_client_sends_an_acknowledgement() {
         .unwrap();
     let packet = serializer.serialize(&payload, false, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(&mut deserializer, results);
+    let (_, mut events) = split_server_results(&mut deserializer, results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events");
     match events.remove(0) {
@@ -1803,56 +2314,399 @@ fn event_raised_when_client_sends_an_acknowledgement() {
     }
 }
 
-fn get_basic_config() -> ServerSessionConfig {
-    ServerSessionConfig {
-        chunk_size: DEFAULT_CHUNK_SIZE,
-        fms_version: "fms_version".to_string(),
-        peer_bandwidth: DEFAULT_PEER_BANDWIDTH,
-        window_ack_size: DEFAULT_WINDOW_ACK_SIZE,
+#[test]
+fn shutdown_ends_playback_and_closes_connection() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, results);
+    perform_connection("some_app", &mut session, &mut serializer, &mut deserializer);
+
+    let stream_id = create_active_stream(&mut session, &mut serializer, &mut deserializer);
+    start_playing(
+        "stream_key",
+        stream_id,
+        &mut session,
+        &mut serializer,
+        &mut deserializer,
+    );
+
+    let results = session.begin_shutdown().unwrap();
+    let (responses, events) = split_server_results(&mut deserializer, results);
+    assert_eq!(responses.len(), 3, "Unexpected number of responses");
+    assert_eq!(
+        get_status_code(&responses[0]),
+        Some("NetStream.Play.UnpublishNotify".to_string()),
+        "Unexpected first response"
+    );
+    assert_eq!(
+        responses[0].0.message_stream_id, stream_id,
+        "Unexpected stream id"
+    );
+
+    match responses[1].1 {
+        RtmpMessage::UserControl {
+            event_type: UserControlEventType::StreamEof,
+            stream_id: Some(eof_stream_id),
+            ..
+        } => assert_eq!(eof_stream_id, stream_id, "Unexpected StreamEof stream id"),
+
+        ref x => panic!("Expected StreamEof, instead received: {:?}", x),
     }
+
+    assert_eq!(
+        get_status_code(&responses[2]),
+        Some("NetConnection.Connect.Closed".to_string()),
+        "Unexpected last response"
+    );
+
+    assert_eq!(
+        events,
+        vec![
+            ServerSessionEvent::PlayStreamFinished {
+                app_name: "some_app".to_string(),
+                stream_key: "stream_key".to_string(),
+            },
+            ServerSessionEvent::StateChanged {
+                previous_state: ServerState::Connected,
+                new_state: ServerState::Closed,
+            },
+            ServerSessionEvent::ShutdownFinished,
+        ]
+    );
+    assert_eq!(session.stream_state(stream_id), Some(&StreamState::Created));
 }
 
-fn split_results(
-    deserializer: &mut ChunkDeserializer,
-    mut results: Vec<ServerSessionResult>,
-) -> (Vec<(MessagePayload, RtmpMessage)>, Vec<ServerSessionEvent>) {
-    let mut responses = Vec::new();
-    let mut events = Vec::new();
-
-    for result in results.drain(..) {
-        match result {
-            ServerSessionResult::OutboundResponse(packet) => {
-                let payload = deserializer
-                    .get_next_message(&packet.bytes[..])
-                    .unwrap()
-                    .unwrap();
-                let message = payload.to_rtmp_message().unwrap();
-                match message {
-                    RtmpMessage::SetChunkSize { size } => {
-                        deserializer.set_max_chunk_size(size as usize).unwrap()
-                    }
-                    _ => (),
-                }
+#[test]
+fn connection_requests_are_refused_after_shutdown() {
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, results);
+    let results = session.begin_shutdown().unwrap();
+    consume_results(&mut deserializer, results);
 
-                println!("response received from server: {:?}", message);
-                responses.push((payload, message));
-            }
+    let connect_payload = create_connect_message("some_app".to_string(), 15, 0, 0.0);
+    let connect_packet = serializer.serialize(&connect_payload, true, false).unwrap();
+    let connect_results = session.handle_input(&connect_packet.bytes[..]).unwrap();
+    let (responses, events) = split_server_results(&mut deserializer, connect_results);
+    assert_eq!(events.len(), 0, "Expected no events");
+    assert_eq!(responses.len(), 1, "Unexpected number of responses");
+    assert_eq!(
+        get_status_code(&responses[0]),
+        Some("NetConnection.Connect.AppShutdown".to_string()),
+        "Unexpected response"
+    );
+
+    let results = session.begin_shutdown().unwrap();
+    assert_eq!(
+        results,
+        vec![ServerSessionResult::RaisedEvent(
+            ServerSessionEvent::ShutdownFinished
+        )]
+    );
+}
+
+#[test]
+fn requests_beyond_outstanding_limit_are_refused() {
+    let mut config = get_basic_config();
+    config.max_outstanding_requests = 1;
+
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config).unwrap();
+    consume_results(&mut deserializer, results);
+
+    let connect_payload = create_connect_message("some_app".to_string(), 15, 0, 0.0);
+    let connect_packet = serializer.serialize(&connect_payload, true, false).unwrap();
+    session.handle_input(&connect_packet.bytes[..]).unwrap();
+
+    let connect_payload = create_connect_message("other_app".to_string(), 15, 0, 0.0);
+    let connect_packet = serializer.serialize(&connect_payload, true, false).unwrap();
+    match session.handle_input(&connect_packet.bytes[..]) {
+        Err(ServerSessionError::TooManyOutstandingRequests { limit: 1 }) => (),
+        x => panic!(
+            "Expected too many outstanding requests error, instead got: {:?}",
+            x
+        ),
+    }
+}
+
+#[test]
+fn handle_input_iter_returns_earlier_results_before_an_error() {
+    let mut config = get_basic_config();
+    config.max_outstanding_requests = 1;
+
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config).unwrap();
+    consume_results(&mut deserializer, results);
+
+    let connect_payload = create_connect_message("some_app".to_string(), 15, 0, 0.0);
+    let mut input = serializer
+        .serialize(&connect_payload, true, false)
+        .unwrap()
+        .bytes
+        .to_vec();
+    let connect_payload = create_connect_message("other_app".to_string(), 15, 0, 0.0);
+    let connect_packet = serializer.serialize(&connect_payload, true, false).unwrap();
+    input.extend_from_slice(&connect_packet.bytes[..]);
+
+    let results: Vec<_> = session.handle_input_iter(&input).collect();
+    match results.first() {
+        Some(Ok(ServerSessionResult::RaisedEvent(ServerSessionEvent::ConnectionRequested {
+            app_name,
+            ..
+        }))) => assert_eq!(app_name, "some_app", "Unexpected app name"),
+        x => panic!("Expected connection requested event, instead got: {:?}", x),
+    }
+
+    match results.last() {
+        Some(Err(ServerSessionError::TooManyOutstandingRequests { limit: 1 })) => (),
+        x => panic!(
+            "Expected too many outstanding requests error, instead got: {:?}",
+            x
+        ),
+    }
+}
+
+#[test]
+fn input_over_memory_budget_is_refused() {
+    let mut config = get_basic_config();
+    config.memory_budget = Some(16 * 1024);
+
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config).unwrap();
+    consume_results(&mut deserializer, results);
+
+    let connect_payload = create_connect_message("some_app".to_string(), 15, 0, 0.0);
+    let connect_packet = serializer.serialize(&connect_payload, true, false).unwrap();
+    session.handle_input(&connect_packet.bytes[..]).unwrap();
+    assert!(
+        session.memory_usage() <= 16 * 1024,
+        "Memory usage over budget"
+    );
+
+    let video_message = RtmpMessage::VideoData {
+        data: Bytes::from(vec![1_u8; 100_000]),
+    };
+    let video_payload = video_message
+        .into_message_payload(RtmpTimestamp::new(0), 1)
+        .unwrap();
+    let video_packet = serializer.serialize(&video_payload, false, false).unwrap();
+    match session.handle_input(&video_packet.bytes[..1024]) {
+        Err(ServerSessionError::MemoryBudgetExceeded {
+            budget: 16384,
+            required,
+        }) => assert!(required > 100_000, "Required bytes of {} too low", required),
+        x => panic!(
+            "Expected memory budget exceeded error, instead got: {:?}",
+            x
+        ),
+    }
+}
 
-            ServerSessionResult::RaisedEvent(event) => {
-                println!("event received from server: {:?}", event);
-                events.push(event);
+#[test]
+fn handler_is_given_results_in_order_with_last_packet_hinted_to_flush() {
+    struct RecordingHandler {
+        received: Vec<(Option<bool>, Option<RtmpTimestamp>)>,
+    }
+
+    impl SessionHandler for RecordingHandler {
+        type Event = ServerSessionEvent;
+
+        fn on_event(&mut self, event: ServerSessionEvent) {
+            match event {
+                ServerSessionEvent::VideoDataReceived { timestamp, .. } => {
+                    self.received.push((None, Some(timestamp)))
+                }
+
+                x => panic!("Expected VideoDataReceived event, instead got: {:?}", x),
             }
+        }
 
-            _ => (),
+        fn on_outbound_packet(&mut self, packet: Packet) {
+            self.received.push((Some(packet.flush_hint), None));
         }
     }
 
-    (responses, events)
+    let config = get_basic_config();
+    let mut deserializer = ChunkDeserializer::new();
+    let mut serializer = ChunkSerializer::new();
+    let (mut session, results) = ServerSession::new(config.clone()).unwrap();
+    consume_results(&mut deserializer, results);
+    perform_connection("some_app", &mut session, &mut serializer, &mut deserializer);
+    let stream_id = create_active_stream(&mut session, &mut serializer, &mut deserializer);
+    start_publishing(
+        "stream_key",
+        stream_id,
+        &mut session,
+        &mut serializer,
+        &mut deserializer,
+    );
+
+    let ping = RtmpMessage::UserControl {
+        event_type: UserControlEventType::PingRequest,
+        timestamp: Some(RtmpTimestamp::new(5230)),
+        stream_id: None,
+        buffer_length: None,
+    };
+
+    let video = RtmpMessage::VideoData {
+        data: Bytes::from(vec![1_u8, 2_u8, 3_u8]),
+    };
+
+    let mut input = Vec::new();
+    for (message, stream) in [(ping.clone(), 0), (video, stream_id), (ping, 0)] {
+        let payload = message
+            .into_message_payload(RtmpTimestamp::new(10), stream)
+            .unwrap();
+        let packet = serializer.serialize(&payload, false, false).unwrap();
+        input.extend_from_slice(&packet.bytes[..]);
+    }
+
+    let mut handler = RecordingHandler {
+        received: Vec::new(),
+    };
+
+    session.handle_input_with(&input, &mut handler).unwrap();
+
+    assert_eq!(
+        handler.received,
+        vec![
+            (Some(false), None),
+            (None, Some(RtmpTimestamp::new(10))),
+            (Some(true), None),
+        ]
+    );
+}
+
+#[test]
+fn only_last_packet_of_a_response_sequence_is_hinted_to_flush() {
+    let config = get_basic_config();
+    let (_, results) = ServerSession::new(config).unwrap();
+
+    let hints = results
+        .into_iter()
+        .filter_map(|result| match result {
+            ServerSessionResult::OutboundResponse(packet) => Some(packet.flush_hint),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(hints, vec![false, false, false, false, true]);
+}
+
+#[test]
+fn errors_from_input_chain_to_their_source() {
+    let (mut session, _) = ServerSession::new(get_basic_config()).unwrap();
+
+    // A type 1 chunk header on a chunk stream that hasn't had a type 0 chunk yet
+    let error = session
+        .handle_input(&[0x43, 0, 0, 0, 0, 0, 1, 20])
+        .unwrap_err();
+
+    let source = std::error::Error::source(&error).expect("Expected the error to have a source");
+    let expected = ChunkDeserializationError::NoPreviousChunkOnStream { csid: 3 };
+
+    assert_eq!(source.to_string(), expected.to_string());
+    assert!(error.to_string().ends_with(&expected.to_string()));
+
+    // A type 0 chunk holding an amf0 command whose only byte is an unknown amf0 marker.  The
+    // message error leaves the amf0 error out of its message, since it's already the source.
+    let (mut session, _) = ServerSession::new(get_basic_config()).unwrap();
+    let error = session
+        .handle_input(&[0x03, 0, 0, 0, 0, 0, 1, 20, 0, 0, 0, 0, 0xff])
+        .unwrap_err();
+
+    let message_error = std::error::Error::source(&error).expect("Expected a message error");
+    let amf0_error = message_error.source().expect("Expected an amf0 error");
+
+    assert!(amf0_error.is::<Amf0DeserializationError>());
+    assert!(!message_error.to_string().contains(&amf0_error.to_string()));
+}
+
+#[test]
+fn config_builder_rejects_values_a_session_cannot_use() {
+    let build_with_chunk_size = |chunk_size| {
+        ServerSessionConfig::builder()
+            .chunk_size(chunk_size)
+            .build()
+            .err()
+    };
+
+    assert_eq!(
+        build_with_chunk_size(0),
+        Some(SessionConfigError::InvalidChunkSize { chunk_size: 0 })
+    );
+    assert_eq!(
+        build_with_chunk_size(0x8000_0000),
+        Some(SessionConfigError::InvalidChunkSize {
+            chunk_size: 0x8000_0000
+        })
+    );
+    assert_eq!(build_with_chunk_size(0x7FFF_FFFF), None);
+
+    assert_eq!(
+        ServerSessionConfig::builder()
+            .window_ack_size(0)
+            .build()
+            .err(),
+        Some(SessionConfigError::InvalidWindowAckSize)
+    );
+    assert_eq!(
+        ServerSessionConfig::builder()
+            .peer_bandwidth(0)
+            .build()
+            .err(),
+        Some(SessionConfigError::InvalidPeerBandwidth)
+    );
+    assert_eq!(
+        ServerSessionConfig::builder()
+            .max_outstanding_requests(0)
+            .build()
+            .err(),
+        Some(SessionConfigError::InvalidMaxOutstandingRequests)
+    );
+}
+
+fn get_basic_config() -> ServerSessionConfig {
+    ServerSessionConfig::builder()
+        .chunk_size(DEFAULT_CHUNK_SIZE)
+        .fms_version("fms_version".to_string())
+        .peer_bandwidth(DEFAULT_PEER_BANDWIDTH)
+        .window_ack_size(DEFAULT_WINDOW_ACK_SIZE)
+        .max_outstanding_requests(64)
+        .build()
+        .unwrap()
 }
 
 fn consume_results(deserializer: &mut ChunkDeserializer, results: Vec<ServerSessionResult>) {
     // Needed to keep the deserializer up to date
-    split_results(deserializer, results);
+    split_server_results(deserializer, results);
+}
+
+/// The code of the status object sent with an `onStatus` or `_error` command, if the message
+/// is one
+fn get_status_code(response: &(MessagePayload, RtmpMessage)) -> Option<String> {
+    match response.1 {
+        RtmpMessage::Amf0Command {
+            ref additional_arguments,
+            ..
+        } => match additional_arguments.first() {
+            Some(Amf0Value::Object(properties)) => match properties.get("code") {
+                Some(Amf0Value::Utf8String(code)) => Some(code.clone()),
+                _ => None,
+            },
+
+            _ => None,
+        },
+
+        _ => None,
+    }
 }
 
 fn create_connect_message(
@@ -1861,7 +2715,7 @@ fn create_connect_message(
     stream_id: u32,
     object_encoding: f64,
 ) -> MessagePayload {
-    let mut properties = HashMap::new();
+    let mut properties = ObjectProperties::new();
     properties.insert("app".to_string(), Amf0Value::Utf8String(app_name));
     properties.insert(
         "objectEncoding".to_string(),
@@ -1895,7 +2749,7 @@ fn perform_connection(
         "Unexpected number of responses when handling connect request message"
     );
 
-    let (_, events) = split_results(deserializer, connect_results);
+    let (_, events) = split_server_results(deserializer, connect_results);
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     let request_id = match events[0] {
         ServerSessionEvent::ConnectionRequested {
@@ -1928,7 +2782,7 @@ fn create_active_stream(
         .unwrap();
     let packet = serializer.serialize(&payload, true, false).unwrap();
     let results = session.handle_input(&packet.bytes[..]).unwrap();
-    let (responses, _) = split_results(deserializer, results);
+    let (responses, _) = split_server_results(deserializer, results);
 
     assert_eq!(
         responses.len(),
@@ -2008,7 +2862,7 @@ fn start_publishing(
         .serialize(&publish_payload, false, false)
         .unwrap();
     let publish_results = session.handle_input(&publish_packet.bytes[..]).unwrap();
-    let (_, events) = split_results(deserializer, publish_results);
+    let (_, events) = split_server_results(deserializer, publish_results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     let request_id = match events[0] {
@@ -2050,7 +2904,7 @@ fn start_playing(
         .unwrap();
     let play_packet = serializer.serialize(&play_payload, false, false).unwrap();
     let play_results = session.handle_input(&play_packet.bytes[..]).unwrap();
-    let (_, mut events) = split_results(deserializer, play_results);
+    let (_, mut events) = split_server_results(deserializer, play_results);
 
     assert_eq!(events.len(), 1, "Unexpected number of events returned");
     let request_id = match events.remove(0) {
@@ -0,0 +1,267 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How severe a status is, as given by the `level` property of a status object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StatusLevel {
+    Status,
+    Warning,
+    Error,
+}
+
+impl StatusLevel {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            StatusLevel::Status => "status",
+            StatusLevel::Warning => "warning",
+            StatusLevel::Error => "error",
+        }
+    }
+}
+
+macro_rules! status_codes {
+    ($($(#[$doc:meta])* $variant:ident => $code:expr, $level:ident, $retryable:expr;)+) => {
+        /// The `code` property of the status objects servers send with `onStatus` commands and
+        /// `_error` responses, which says what happened to a connection or stream.
+        ///
+        /// Codes that aren't one of the standard `NetConnection` and `NetStream` codes are kept
+        /// as `Other`, so status codes can be passed along without losing anything.
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(Serialize, Deserialize),
+            serde(from = "String", into = "String")
+        )]
+        #[non_exhaustive]
+        pub enum StatusCode {
+            $(
+                $(#[$doc])*
+                $variant,
+            )+
+
+            /// A code that isn't one of the standard codes
+            Other(String),
+        }
+
+        impl StatusCode {
+            /// The code as it's sent on the wire, such as `NetStream.Play.Start`
+            pub fn as_str(&self) -> &str {
+                match *self {
+                    $(StatusCode::$variant => $code,)+
+                    StatusCode::Other(ref code) => code,
+                }
+            }
+
+            /// The level the code is normally sent with.  Codes that aren't one of the standard
+            /// codes are treated as errors if they end in a suffix standard error codes use,
+            /// such as `.Failed` or `.Rejected`.
+            pub fn level(&self) -> StatusLevel {
+                match *self {
+                    $(StatusCode::$variant => StatusLevel::$level,)+
+                    StatusCode::Other(ref code) => {
+                        if ERROR_SUFFIXES.iter().any(|suffix| code.ends_with(suffix)) {
+                            StatusLevel::Error
+                        } else {
+                            StatusLevel::Status
+                        }
+                    }
+                }
+            }
+
+            /// If the code is for a failure that making the same request again later could
+            /// get past, such as playing a stream before anyone has started publishing it.
+            /// Failures such as a rejected connection need something about the request to
+            /// change before it's worth trying again.  Codes that aren't one of the standard
+            /// codes are never considered retryable.
+            pub fn is_retryable(&self) -> bool {
+                match *self {
+                    $(StatusCode::$variant => $retryable,)+
+                    StatusCode::Other(_) => false,
+                }
+            }
+        }
+
+        impl<'a> From<&'a str> for StatusCode {
+            fn from(code: &'a str) -> Self {
+                match code {
+                    $($code => StatusCode::$variant,)+
+                    _ => StatusCode::Other(code.to_string()),
+                }
+            }
+        }
+
+        impl From<String> for StatusCode {
+            fn from(code: String) -> Self {
+                match code.as_ref() {
+                    $($code => StatusCode::$variant,)+
+                    _ => StatusCode::Other(code),
+                }
+            }
+        }
+    };
+}
+
+/// Suffixes of the standard codes that are sent with an `error` level
+const ERROR_SUFFIXES: [&str; 5] = [
+    ".Failed",
+    ".BadName",
+    ".StreamNotFound",
+    ".Rejected",
+    ".NoAccess",
+];
+
+status_codes! {
+    /// The connection request was accepted
+    ConnectSuccess => "NetConnection.Connect.Success", Status, false;
+
+    /// The server refused the connection request, such as when authentication fails
+    ConnectRejected => "NetConnection.Connect.Rejected", Error, false;
+
+    /// The connection could not be made
+    ConnectFailed => "NetConnection.Connect.Failed", Error, true;
+
+    /// The connection was closed
+    ConnectClosed => "NetConnection.Connect.Closed", Status, false;
+
+    /// The application being connected to is shutting down
+    ConnectAppShutdown => "NetConnection.Connect.AppShutdown", Error, true;
+
+    /// The application name given in the connection request does not exist on the server
+    ConnectInvalidApp => "NetConnection.Connect.InvalidApp", Error, false;
+
+    /// The server could not invoke a method the client called
+    CallFailed => "NetConnection.Call.Failed", Error, false;
+
+    /// Playback has started
+    PlayStart => "NetStream.Play.Start", Status, false;
+
+    /// Playback has stopped
+    PlayStop => "NetStream.Play.Stop", Status, false;
+
+    /// The playlist has been reset
+    PlayReset => "NetStream.Play.Reset", Status, false;
+
+    /// Playback failed for a reason other than the stream not being found
+    PlayFailed => "NetStream.Play.Failed", Error, true;
+
+    /// The stream requested for playback does not exist, such as when nothing is being
+    /// published to it yet
+    PlayStreamNotFound => "NetStream.Play.StreamNotFound", Error, true;
+
+    /// The client does not have enough bandwidth to play the stream at its normal speed
+    PlayInsufficientBandwidth => "NetStream.Play.InsufficientBW", Warning, false;
+
+    /// Publishing to the stream being played has started
+    PlayPublishNotify => "NetStream.Play.PublishNotify", Status, false;
+
+    /// Publishing to the stream being played has stopped
+    PlayUnpublishNotify => "NetStream.Play.UnpublishNotify", Status, false;
+
+    /// Publishing has started
+    PublishStart => "NetStream.Publish.Start", Status, false;
+
+    /// The stream can't be published to, usually because something is already publishing
+    /// to it
+    PublishBadName => "NetStream.Publish.BadName", Error, true;
+
+    /// The publisher has been idle for too long
+    PublishIdle => "NetStream.Publish.Idle", Status, false;
+
+    /// Publishing has stopped
+    UnpublishSuccess => "NetStream.Unpublish.Success", Status, false;
+
+    /// The stream has been paused
+    PauseNotify => "NetStream.Pause.Notify", Status, false;
+
+    /// The stream has been resumed
+    UnpauseNotify => "NetStream.Unpause.Notify", Status, false;
+
+    /// A seek has completed
+    SeekNotify => "NetStream.Seek.Notify", Status, false;
+
+    /// The stream could not be seeked
+    SeekFailed => "NetStream.Seek.Failed", Error, false;
+
+    /// Recording has started
+    RecordStart => "NetStream.Record.Start", Status, false;
+
+    /// Recording has stopped
+    RecordStop => "NetStream.Record.Stop", Status, false;
+
+    /// The stream can't be recorded, such as when the client doesn't have permission to
+    RecordNoAccess => "NetStream.Record.NoAccess", Error, false;
+
+    /// Recording failed
+    RecordFailed => "NetStream.Record.Failed", Error, true;
+
+    /// Data messages for the stream are about to be sent
+    DataStart => "NetStream.Data.Start", Status, false;
+
+    /// The stream failed for a reason not covered by another code
+    StreamFailed => "NetStream.Failed", Error, true;
+}
+
+impl From<StatusCode> for String {
+    fn from(code: StatusCode) -> Self {
+        match code {
+            StatusCode::Other(code) => code,
+            code => code.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_codes_round_trip_through_strings() {
+        let code = StatusCode::from("NetStream.Play.StreamNotFound");
+
+        assert_eq!(code, StatusCode::PlayStreamNotFound);
+        assert_eq!(code.as_str(), "NetStream.Play.StreamNotFound");
+        assert_eq!(code.level(), StatusLevel::Error);
+        assert!(code.is_retryable());
+    }
+
+    #[test]
+    fn unknown_codes_are_kept_and_categorized_by_suffix() {
+        let failed = StatusCode::from("NetStream.Custom.Failed".to_string());
+        let status = StatusCode::from("NetStream.Custom.Start");
+
+        assert_eq!(
+            failed,
+            StatusCode::Other("NetStream.Custom.Failed".to_string())
+        );
+        assert_eq!(failed.to_string(), "NetStream.Custom.Failed");
+        assert_eq!(failed.level(), StatusLevel::Error);
+        assert_eq!(status.level(), StatusLevel::Status);
+        assert!(!failed.is_retryable());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn codes_are_serialized_as_their_strings() {
+        let codes = vec![
+            StatusCode::PublishBadName,
+            StatusCode::Other("NetStream.Custom.Start".to_string()),
+        ];
+
+        let json = ::serde_json::to_string(&codes).unwrap();
+        assert_eq!(
+            json,
+            r#"["NetStream.Publish.BadName","NetStream.Custom.Start"]"#
+        );
+
+        let deserialized: Vec<StatusCode> = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, codes);
+    }
+}
@@ -0,0 +1,72 @@
+use rml_amf0::ObjectProperties;
+use rml_amf0::{take_field, take_optional_field, Amf0Object, Amf0ObjectError, Amf0Value};
+use sessions::StatusCode;
+
+/// The info object that accompanies `onStatus` commands and `_error` responses
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct StatusObject {
+    pub level: String,
+    pub code: StatusCode,
+    pub description: String,
+}
+
+impl StatusObject {
+    /// Only servers send status objects, clients just read them
+    #[cfg(feature = "server-session")]
+    pub fn new(level: &str, code: StatusCode, description: &str) -> StatusObject {
+        StatusObject {
+            level: level.to_string(),
+            code,
+            description: description.to_string(),
+        }
+    }
+}
+
+impl Amf0Object for StatusObject {
+    fn to_amf0_properties(&self) -> ObjectProperties {
+        let mut properties = ObjectProperties::new();
+        properties.insert("level".to_string(), Amf0Value::from(self.level.as_str()));
+        properties.insert("code".to_string(), Amf0Value::from(self.code.as_str()));
+        properties.insert(
+            "description".to_string(),
+            Amf0Value::from(self.description.as_str()),
+        );
+
+        properties
+    }
+
+    fn from_amf0_properties(mut properties: ObjectProperties) -> Result<Self, Amf0ObjectError> {
+        // Only the code is needed to act on a status, so be lenient with peers that leave out
+        // the level or description.
+        Ok(StatusObject {
+            code: StatusCode::from(take_field::<String>(&mut properties, "code")?),
+            level: take_optional_field(&mut properties, "level")?.unwrap_or_default(),
+            description: take_optional_field(&mut properties, "description")?.unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "server-session")]
+    fn status_object_round_trips_through_amf0_value() {
+        let status = StatusObject::new("status", StatusCode::PlayStart, "Starting");
+        let value = status.to_amf0_value();
+
+        assert_eq!(StatusObject::from_amf0_value(value).unwrap(), status);
+    }
+
+    #[test]
+    fn status_object_without_code_is_rejected() {
+        let mut properties = ObjectProperties::new();
+        properties.insert("level".to_string(), Amf0Value::from("status"));
+
+        match StatusObject::from_amf0_properties(properties) {
+            Err(Amf0ObjectError::MissingField { ref field }) if field == "code" => (),
+            x => panic!("Expected missing code error, instead got {:?}", x),
+        }
+    }
+}
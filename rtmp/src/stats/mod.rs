@@ -0,0 +1,13 @@
+/*!
+This module contains the `StreamStatsTracker`, which measures the health of a live stream from
+the media flowing through it.
+
+Operators need to know more than whether a stream is connected.  Bitrates and frame rates
+that drop, keyframe intervals that are too long for segmenting, audio drifting away from the
+video, and streams that are still connected but have stopped sending media all show up in the
+snapshots the tracker produces.
+*/
+
+mod tracker;
+
+pub use self::tracker::{StreamStatsConfig, StreamStatsSnapshot, StreamStatsTracker};
@@ -0,0 +1,366 @@
+use hub::media::{is_audio_sequence_header, is_video_keyframe, is_video_sequence_header};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use time::RtmpTimestamp;
+
+/// Configuration options for a `StreamStatsTracker`
+#[derive(Debug, Clone)]
+pub struct StreamStatsConfig {
+    /// How far back bitrates and frame rates are averaged over
+    pub window: Duration,
+
+    /// How often `poll_snapshot()` returns a snapshot
+    pub snapshot_interval: Duration,
+
+    /// How long without media before a stream is considered stale
+    pub stale_timeout: Duration,
+}
+
+impl StreamStatsConfig {
+    /// Creates a configuration with a 5 second window, 1 second snapshots, and streams going
+    /// stale after 5 seconds
+    pub fn new() -> StreamStatsConfig {
+        StreamStatsConfig {
+            window: Duration::from_secs(5),
+            snapshot_interval: Duration::from_secs(1),
+            stale_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Default for StreamStatsConfig {
+    fn default() -> Self {
+        StreamStatsConfig::new()
+    }
+}
+
+/// The statistics of a stream at a point in time
+#[derive(PartialEq, Debug, Clone)]
+pub struct StreamStatsSnapshot {
+    /// Video bits received per second over the window
+    pub video_bitrate: u64,
+
+    /// Audio bits received per second over the window
+    pub audio_bitrate: u64,
+
+    /// Video frames per second over the window, based on the frames' timestamps
+    pub video_frame_rate: f32,
+
+    /// The average size of video frames in the window.  Streams sending a black or frozen
+    /// picture usually have frames that are far smaller than normal.
+    pub average_video_frame_size: usize,
+
+    /// The time between the two most recent keyframes, based on their timestamps
+    pub keyframe_interval: Option<Duration>,
+
+    /// How many milliseconds the latest video timestamp is ahead of the latest audio
+    /// timestamp (negative if it's behind)
+    pub audio_video_drift: Option<i64>,
+
+    pub total_video_frames: u64,
+    pub total_audio_frames: u64,
+    pub total_bytes: u64,
+
+    /// How long it has been since any media was received
+    pub since_last_media: Option<Duration>,
+
+    /// How long it has been since the first media was received
+    pub uptime: Option<Duration>,
+
+    /// True if no media has been received within the stale timeout
+    pub is_stale: bool,
+
+    /// True if audio is still being received but video has stopped for longer than the stale
+    /// timeout, which usually means viewers are looking at a frozen picture
+    pub is_video_stalled: bool,
+}
+
+/// Measures the bitrate, frame rate, keyframe interval, and audio/video drift of a single
+/// stream from the media received on it.
+///
+/// The payload of each audio and video message is passed to `record_audio()` and
+/// `record_video()` along with the current time, and `snapshot()` (or `poll_snapshot()`, to get
+/// one at a regular interval) returns the current statistics.  Sequence headers count towards
+/// the total bytes but are otherwise ignored.
+///
+/// # Examples
+/// ```
+/// use rml_rtmp::stats::{StreamStatsConfig, StreamStatsTracker};
+/// use rml_rtmp::time::RtmpTimestamp;
+/// use std::time::{Duration, Instant};
+///
+/// let start = Instant::now();
+/// let mut tracker = StreamStatsTracker::new(StreamStatsConfig::new());
+/// for frame in 0..30 {
+///     let now = start + Duration::from_millis(frame * 100);
+///     let data = if frame % 10 == 0 { [0x17, 1] } else { [0x27, 1] };
+///     tracker.record_video(&data, RtmpTimestamp::new(frame as u32 * 100), now);
+/// }
+///
+/// let snapshot = tracker.snapshot(start + Duration::from_millis(2900));
+/// assert_eq!(snapshot.video_frame_rate.round(), 10.0);
+/// assert_eq!(snapshot.keyframe_interval, Some(Duration::from_secs(1)));
+/// ```
+pub struct StreamStatsTracker {
+    config: StreamStatsConfig,
+    video_window: VecDeque<(Instant, usize, RtmpTimestamp)>,
+    audio_window: VecDeque<(Instant, usize)>,
+    first_media_at: Option<Instant>,
+    last_video: Option<(Instant, RtmpTimestamp)>,
+    last_audio: Option<(Instant, RtmpTimestamp)>,
+    last_keyframe_timestamp: Option<RtmpTimestamp>,
+    keyframe_interval: Option<Duration>,
+    total_video_frames: u64,
+    total_audio_frames: u64,
+    total_bytes: u64,
+    next_snapshot_at: Option<Instant>,
+}
+
+impl StreamStatsTracker {
+    /// Creates a tracker for a stream that hasn't received any media yet
+    pub fn new(config: StreamStatsConfig) -> StreamStatsTracker {
+        StreamStatsTracker {
+            config,
+            video_window: VecDeque::new(),
+            audio_window: VecDeque::new(),
+            first_media_at: None,
+            last_video: None,
+            last_audio: None,
+            last_keyframe_timestamp: None,
+            keyframe_interval: None,
+            total_video_frames: 0,
+            total_audio_frames: 0,
+            total_bytes: 0,
+            next_snapshot_at: None,
+        }
+    }
+
+    /// Records the payload of a video message
+    pub fn record_video(&mut self, data: &[u8], timestamp: RtmpTimestamp, now: Instant) {
+        self.total_bytes += data.len() as u64;
+        if is_video_sequence_header(data) {
+            return;
+        }
+
+        if is_video_keyframe(data) {
+            if let Some(previous) = self.last_keyframe_timestamp {
+                if timestamp > previous {
+                    self.keyframe_interval = timestamp.checked_duration_since(previous);
+                }
+            }
+
+            self.last_keyframe_timestamp = Some(timestamp);
+        }
+
+        self.first_media_at.get_or_insert(now);
+        self.last_video = Some((now, timestamp));
+        self.total_video_frames += 1;
+        self.video_window.push_back((now, data.len(), timestamp));
+        self.prune(now);
+    }
+
+    /// Records the payload of an audio message
+    pub fn record_audio(&mut self, data: &[u8], timestamp: RtmpTimestamp, now: Instant) {
+        self.total_bytes += data.len() as u64;
+        if is_audio_sequence_header(data) {
+            return;
+        }
+
+        self.first_media_at.get_or_insert(now);
+        self.last_audio = Some((now, timestamp));
+        self.total_audio_frames += 1;
+        self.audio_window.push_back((now, data.len()));
+        self.prune(now);
+    }
+
+    /// Returns a snapshot once every snapshot interval, starting one interval after the first
+    /// call
+    pub fn poll_snapshot(&mut self, now: Instant) -> Option<StreamStatsSnapshot> {
+        let interval = self.config.snapshot_interval;
+        match self.next_snapshot_at {
+            Some(next) if now >= next => {
+                // Skip snapshots that were missed rather than returning them all at once
+                let next = next + interval;
+                self.next_snapshot_at = Some(if next > now { next } else { now + interval });
+                Some(self.snapshot(now))
+            }
+
+            Some(_) => None,
+            None => {
+                self.next_snapshot_at = Some(now + interval);
+                None
+            }
+        }
+    }
+
+    /// Calculates the stream's current statistics
+    pub fn snapshot(&mut self, now: Instant) -> StreamStatsSnapshot {
+        self.prune(now);
+
+        // Until a full window has passed, rates are averaged over the time since media started
+        let elapsed = self
+            .first_media_at
+            .map(|start| now.duration_since(start))
+            .unwrap_or_else(|| Duration::from_secs(0));
+
+        let window = if elapsed < self.config.window && elapsed > Duration::from_secs(0) {
+            elapsed
+        } else {
+            self.config.window
+        };
+
+        let video_bytes = self.video_window.iter().map(|x| x.1).sum::<usize>();
+        let audio_bytes = self.audio_window.iter().map(|x| x.1).sum::<usize>();
+
+        let since_last_media = [self.last_video, self.last_audio]
+            .iter()
+            .filter_map(|x| x.map(|(received_at, _)| now.duration_since(received_at)))
+            .min();
+
+        let is_video_stalled = match (self.last_video, self.last_audio) {
+            (Some((video_at, _)), Some((audio_at, _))) => {
+                let timeout = self.config.stale_timeout;
+                now.duration_since(video_at) > timeout && now.duration_since(audio_at) <= timeout
+            }
+
+            _ => false,
+        };
+
+        StreamStatsSnapshot {
+            video_bitrate: bits_per_second(video_bytes, window),
+            audio_bitrate: bits_per_second(audio_bytes, window),
+            video_frame_rate: self.video_frame_rate(),
+            average_video_frame_size: match self.video_window.len() {
+                0 => 0,
+                count => video_bytes / count,
+            },
+            keyframe_interval: self.keyframe_interval,
+            audio_video_drift: match (self.last_video, self.last_audio) {
+                (Some((_, video)), Some((_, audio))) => Some(video.diff(audio) as i64),
+                _ => None,
+            },
+            total_video_frames: self.total_video_frames,
+            total_audio_frames: self.total_audio_frames,
+            total_bytes: self.total_bytes,
+            is_stale: since_last_media.is_none_or(|x| x > self.config.stale_timeout),
+            since_last_media,
+            uptime: self.first_media_at.map(|start| now.duration_since(start)),
+            is_video_stalled,
+        }
+    }
+
+    fn video_frame_rate(&self) -> f32 {
+        let (first, last) = match (self.video_window.front(), self.video_window.back()) {
+            (Some(first), Some(last)) if last.2 > first.2 => (first.2, last.2),
+            _ => return 0.0,
+        };
+
+        let span = last.saturating_duration_since(first).as_secs_f32();
+        (self.video_window.len() - 1) as f32 / span
+    }
+
+    fn prune(&mut self, now: Instant) {
+        let window = self.config.window;
+        while let Some(&(received_at, _, _)) = self.video_window.front() {
+            if now.duration_since(received_at) <= window {
+                break;
+            }
+
+            self.video_window.pop_front();
+        }
+
+        while let Some(&(received_at, _)) = self.audio_window.front() {
+            if now.duration_since(received_at) <= window {
+                break;
+            }
+
+            self.audio_window.pop_front();
+        }
+    }
+}
+
+fn bits_per_second(bytes: usize, window: Duration) -> u64 {
+    let milliseconds = window.as_millis() as u64;
+    if milliseconds == 0 {
+        return 0;
+    }
+
+    bytes as u64 * 8 * 1000 / milliseconds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(start: Instant, milliseconds: u64) -> Instant {
+        start + Duration::from_millis(milliseconds)
+    }
+
+    #[test]
+    fn bitrates_are_averaged_over_window() {
+        let start = Instant::now();
+        let mut config = StreamStatsConfig::new();
+        config.window = Duration::from_secs(2);
+        let mut tracker = StreamStatsTracker::new(config);
+
+        for second in 0..5 {
+            let now = at(start, second * 1000);
+            tracker.record_video(&[0x27; 1000], RtmpTimestamp::new(second as u32 * 1000), now);
+            tracker.record_audio(&[0xaf; 100], RtmpTimestamp::new(second as u32 * 1000), now);
+        }
+
+        let snapshot = tracker.snapshot(at(start, 4000));
+
+        // Frames at 2, 3, and 4 seconds are inside the window
+        assert_eq!(snapshot.video_bitrate, 3 * 1000 * 8 / 2);
+        assert_eq!(snapshot.audio_bitrate, 3 * 100 * 8 / 2);
+        assert_eq!(snapshot.average_video_frame_size, 1000);
+        assert_eq!(snapshot.total_bytes, 5 * 1100);
+    }
+
+    #[test]
+    fn drift_is_difference_between_latest_timestamps() {
+        let start = Instant::now();
+        let mut tracker = StreamStatsTracker::new(StreamStatsConfig::new());
+        tracker.record_video(&[0x17, 1], RtmpTimestamp::new(1000), start);
+        tracker.record_audio(&[0xaf, 1], RtmpTimestamp::new(1250), start);
+
+        assert_eq!(tracker.snapshot(start).audio_video_drift, Some(-250));
+    }
+
+    #[test]
+    fn stream_is_stale_without_media() {
+        let start = Instant::now();
+        let mut tracker = StreamStatsTracker::new(StreamStatsConfig::new());
+        assert!(tracker.snapshot(start).is_stale);
+
+        tracker.record_video(&[0x17, 1], RtmpTimestamp::new(0), start);
+        assert!(!tracker.snapshot(at(start, 5000)).is_stale);
+        assert!(tracker.snapshot(at(start, 5001)).is_stale);
+    }
+
+    #[test]
+    fn video_stopping_while_audio_continues_is_stalled() {
+        let start = Instant::now();
+        let mut tracker = StreamStatsTracker::new(StreamStatsConfig::new());
+        tracker.record_video(&[0x17, 1], RtmpTimestamp::new(0), start);
+        tracker.record_audio(&[0xaf, 1], RtmpTimestamp::new(6000), at(start, 6000));
+
+        let snapshot = tracker.snapshot(at(start, 6000));
+
+        assert!(snapshot.is_video_stalled);
+        assert!(!snapshot.is_stale);
+    }
+
+    #[test]
+    fn snapshots_are_polled_at_interval() {
+        let start = Instant::now();
+        let mut tracker = StreamStatsTracker::new(StreamStatsConfig::new());
+
+        assert!(tracker.poll_snapshot(start).is_none());
+        assert!(tracker.poll_snapshot(at(start, 999)).is_none());
+        assert!(tracker.poll_snapshot(at(start, 1000)).is_some());
+        assert!(tracker.poll_snapshot(at(start, 1500)).is_none());
+        assert!(tracker.poll_snapshot(at(start, 2000)).is_some());
+    }
+}
@@ -0,0 +1,526 @@
+//! RTMP timestamps are 32 byte unsigned integers representing the number of milliseconds from
+//! and unknown epoch.
+//!
+//! Since it's meant to support streams that can go on forever, timestamps have to work with
+//! time values that overflow and underflow a 32 bit integer but still be able to do comparisons.
+//! To support this the `RtmpTimestamp` struct was created to abstract away the calculations
+//! and make it easy to work with RTMP timestamps.
+//!
+//! According to the RTMP spec, times are adjacent if they are within 2<sup>31</sup> - 1 milliseconds
+//! of each other.
+//!
+//! # Examples
+//!
+//! Basic arithmetic and comparison support:
+//!
+//! ```
+//! use rml_rtmp::time::RtmpTimestamp;
+//!
+//! let time1 = RtmpTimestamp::new(10);
+//! let time2 = RtmpTimestamp::new(20);
+//! let time3 = RtmpTimestamp::new(30);
+//! let mut time4 = RtmpTimestamp::new(10);
+//!
+//! assert!(time1 < time2);
+//! assert_eq!(time3, time1 + time2);
+//! assert_eq!(time2, time1 + 10);
+//!
+//! time4.set(30);
+//! assert_eq!(RtmpTimestamp::new(30), time4);
+//! ```
+//!
+//! Value Wrapping support:
+//!
+//! ```
+//! use rml_rtmp::time::RtmpTimestamp;
+//!
+//! let time1 = RtmpTimestamp::new(10000);
+//! let time2 = RtmpTimestamp::new(4000000000);
+//! let time3 = RtmpTimestamp::new(3000000000);
+//!
+//! assert!(time1 > time2);
+//! assert!(time3 < time2);
+//! ```
+//!
+//! The distance between two timestamps is measured the same way, so it stays correct across the
+//! point where timestamps wrap:
+//!
+//! ```
+//! use rml_rtmp::time::RtmpTimestamp;
+//! use std::time::Duration;
+//!
+//! let before_wrap = RtmpTimestamp::new(u32::MAX - 9);
+//! let after_wrap = RtmpTimestamp::new(10);
+//!
+//! assert_eq!(after_wrap.diff(before_wrap), 20);
+//! assert_eq!(before_wrap.diff(after_wrap), -20);
+//! assert_eq!(after_wrap.checked_duration_since(before_wrap), Some(Duration::from_millis(20)));
+//! assert_eq!(before_wrap.checked_duration_since(after_wrap), None);
+//! assert_eq!(before_wrap + Duration::from_millis(20), after_wrap);
+//! ```
+//!
+//! Timestamps convert to and from `u32` milliseconds and `Duration`s, and can be updated in
+//! place:
+//!
+//! ```
+//! use rml_rtmp::time::RtmpTimestamp;
+//! use std::time::Duration;
+//!
+//! let mut time = RtmpTimestamp::from(1000);
+//! time += Duration::from_millis(500);
+//! time -= 250;
+//!
+//! assert_eq!(u32::from(time), 1250);
+//! assert_eq!(Duration::from(time), Duration::from_millis(1250));
+//! ```
+//!
+//! For ease of use, a `RtmpTimestamp` can be directly compared to u32s:
+//!
+//! ```
+//! use rml_rtmp::time::RtmpTimestamp;
+//!
+//! let time = RtmpTimestamp::new(50);
+//!
+//! assert!(time < 60);
+//! assert!(time > 20);
+//! assert!(time == 50);
+//! ```
+//!
+//! Streams that are relayed, recorded, or segmented usually need their timestamps rebased onto
+//! a timeline that starts at zero and never jumps, even when the publisher's encoder restarts.
+//! The `TimestampRebaser` does this so each component doesn't have to.
+
+use core::cmp::{max, min, Ordering};
+use core::num::Wrapping;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::time::Duration;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+mod rebaser;
+
+pub use self::rebaser::TimestampRebaser;
+
+/// The representation of a RTMP timestamp.  With the `serde` feature it's serialized as just
+/// its value.
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct RtmpTimestamp {
+    /// The time (as milliseconds from an unknown epoch) being represented by the timestamp
+    pub value: u32,
+}
+
+impl RtmpTimestamp {
+    /// Creates a new timestamp with the specified time value
+    pub fn new(initial_value: u32) -> Self {
+        RtmpTimestamp {
+            value: initial_value,
+        }
+    }
+
+    /// Creates a timestamp for a duration since the epoch, wrapping back around to zero every
+    /// 2<sup>32</sup> milliseconds
+    pub fn from_duration(duration: Duration) -> Self {
+        // Truncating keeps the milliseconds modulo 2^32, which is how timestamps wrap
+        RtmpTimestamp {
+            value: duration.as_millis() as u32,
+        }
+    }
+
+    /// Sets the timestamp to a new time value
+    pub fn set(&mut self, new_value: u32) {
+        self.value = new_value;
+    }
+
+    /// The number of milliseconds from `earlier` to this timestamp.  This follows the same
+    /// adjacency rule as comparisons, so the result is negative if `earlier` is actually after
+    /// this timestamp, including when the two are on opposite sides of the point where
+    /// timestamps wrap.
+    pub fn diff(self, earlier: RtmpTimestamp) -> i32 {
+        sub_values(self.value, earlier.value) as i32
+    }
+
+    /// The time from `earlier` to this timestamp, or `None` if `earlier` is after it
+    pub fn checked_duration_since(self, earlier: RtmpTimestamp) -> Option<Duration> {
+        match self.diff(earlier) {
+            x if x >= 0 => Some(Duration::from_millis(x as u64)),
+            _ => None,
+        }
+    }
+
+    /// The time from `earlier` to this timestamp, or zero if `earlier` is after it
+    pub fn saturating_duration_since(self, earlier: RtmpTimestamp) -> Duration {
+        self.checked_duration_since(earlier)
+            .unwrap_or(Duration::from_millis(0))
+    }
+}
+
+impl Add for RtmpTimestamp {
+    type Output = RtmpTimestamp;
+
+    fn add(self, other: RtmpTimestamp) -> Self {
+        RtmpTimestamp {
+            value: add_values(self.value, other.value),
+        }
+    }
+}
+
+impl Add<u32> for RtmpTimestamp {
+    type Output = RtmpTimestamp;
+
+    fn add(self, other: u32) -> Self {
+        RtmpTimestamp {
+            value: add_values(self.value, other),
+        }
+    }
+}
+
+impl Sub for RtmpTimestamp {
+    type Output = RtmpTimestamp;
+
+    fn sub(self, other: RtmpTimestamp) -> Self {
+        RtmpTimestamp {
+            value: sub_values(self.value, other.value),
+        }
+    }
+}
+
+impl Sub<u32> for RtmpTimestamp {
+    type Output = RtmpTimestamp;
+
+    fn sub(self, other: u32) -> Self {
+        RtmpTimestamp {
+            value: sub_values(self.value, other),
+        }
+    }
+}
+
+impl Add<Duration> for RtmpTimestamp {
+    type Output = RtmpTimestamp;
+
+    fn add(self, other: Duration) -> Self {
+        self + RtmpTimestamp::from_duration(other)
+    }
+}
+
+impl Sub<Duration> for RtmpTimestamp {
+    type Output = RtmpTimestamp;
+
+    fn sub(self, other: Duration) -> Self {
+        self - RtmpTimestamp::from_duration(other)
+    }
+}
+
+impl AddAssign for RtmpTimestamp {
+    fn add_assign(&mut self, other: RtmpTimestamp) {
+        *self = *self + other;
+    }
+}
+
+impl AddAssign<u32> for RtmpTimestamp {
+    fn add_assign(&mut self, other: u32) {
+        *self = *self + other;
+    }
+}
+
+impl AddAssign<Duration> for RtmpTimestamp {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for RtmpTimestamp {
+    fn sub_assign(&mut self, other: RtmpTimestamp) {
+        *self = *self - other;
+    }
+}
+
+impl SubAssign<u32> for RtmpTimestamp {
+    fn sub_assign(&mut self, other: u32) {
+        *self = *self - other;
+    }
+}
+
+impl SubAssign<Duration> for RtmpTimestamp {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
+    }
+}
+
+impl From<u32> for RtmpTimestamp {
+    fn from(value: u32) -> Self {
+        RtmpTimestamp::new(value)
+    }
+}
+
+impl From<RtmpTimestamp> for u32 {
+    fn from(timestamp: RtmpTimestamp) -> Self {
+        timestamp.value
+    }
+}
+
+/// Wraps back around to zero every 2<sup>32</sup> milliseconds, like `from_duration()`
+impl From<Duration> for RtmpTimestamp {
+    fn from(duration: Duration) -> Self {
+        RtmpTimestamp::from_duration(duration)
+    }
+}
+
+/// The time since the epoch, which is the time since the start of the stream for most streams
+impl From<RtmpTimestamp> for Duration {
+    fn from(timestamp: RtmpTimestamp) -> Self {
+        Duration::from_millis(timestamp.value as u64)
+    }
+}
+
+impl Ord for RtmpTimestamp {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(&self.value, &other.value)
+    }
+}
+
+impl PartialOrd for RtmpTimestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq<u32> for RtmpTimestamp {
+    fn eq(&self, other: &u32) -> bool {
+        self.value == *other
+    }
+}
+
+impl PartialEq<RtmpTimestamp> for u32 {
+    fn eq(&self, other: &RtmpTimestamp) -> bool {
+        self == &other.value
+    }
+}
+
+impl PartialOrd<u32> for RtmpTimestamp {
+    fn partial_cmp(&self, other: &u32) -> Option<Ordering> {
+        Some(compare(&self.value, other))
+    }
+}
+
+impl PartialOrd<RtmpTimestamp> for u32 {
+    fn partial_cmp(&self, other: &RtmpTimestamp) -> Option<Ordering> {
+        Some(compare(self, &other.value))
+    }
+}
+
+fn add_values(value1: u32, value2: u32) -> u32 {
+    (Wrapping(value1) + Wrapping(value2)).0
+}
+
+fn sub_values(value1: u32, value2: u32) -> u32 {
+    (Wrapping(value1) - Wrapping(value2)).0
+}
+
+fn compare(value1: &u32, value2: &u32) -> Ordering {
+    const MAX_ADJACENT_VALUE: u32 = 2147483647; //2u32.pow(31) - 1
+
+    let max_val = max(value1, value2);
+    let min_val = min(value1, value2);
+    let difference = max_val - min_val;
+    match difference <= MAX_ADJACENT_VALUE {
+        true => value1.cmp(value2),
+        false => value2.cmp(value1),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::legacy_numeric_constants)]
+mod tests {
+    use super::RtmpTimestamp;
+    use core::time::Duration;
+
+    #[test]
+    fn two_timestamps_can_be_added_together() {
+        let time1 = RtmpTimestamp::new(50);
+        let time2 = RtmpTimestamp::new(60);
+        let result = time1 + time2;
+
+        assert_eq!(result.value, 110);
+    }
+
+    #[test]
+    fn can_add_number_to_timestamp() {
+        let time = RtmpTimestamp::new(50);
+        let result = time + 60;
+
+        assert_eq!(result.value, 110);
+    }
+
+    #[test]
+    fn can_add_timestamps_that_overflow_u32() {
+        let time1 = RtmpTimestamp::new(u32::max_value());
+        let time2 = RtmpTimestamp::new(60);
+        let result = time1 + time2;
+
+        assert_eq!(result.value, 59);
+    }
+
+    #[test]
+    fn can_add_number_to_timestamp_that_overflows_u32() {
+        let time = RtmpTimestamp::new(u32::max_value());
+        let result = time + 60;
+
+        assert_eq!(result.value, 59);
+    }
+
+    #[test]
+    fn two_timestamps_can_be_subtracted_from_each_other() {
+        let time1 = RtmpTimestamp::new(60);
+        let time2 = RtmpTimestamp::new(50);
+        let result = time1 - time2;
+
+        assert_eq!(result.value, 10);
+    }
+
+    #[test]
+    fn can_subtract_number_from_timestamp() {
+        let time = RtmpTimestamp::new(60);
+        let result = time - 50;
+
+        assert_eq!(result.value, 10);
+    }
+
+    #[test]
+    fn can_subtract_timestamps_that_underflow() {
+        let time1 = RtmpTimestamp::new(0);
+        let time2 = RtmpTimestamp::new(50);
+        let result = time1 - time2;
+
+        assert_eq!(result.value, u32::max_value() - 49);
+    }
+
+    #[test]
+    fn can_subtract_number_from_timestamp_that_underflow_u32() {
+        let time = RtmpTimestamp::new(0);
+        let result = time - 50;
+
+        assert_eq!(result.value, u32::max_value() - 49);
+    }
+
+    #[test]
+    fn can_do_basic_comparisons_of_timestamps() {
+        let time1 = RtmpTimestamp::new(50);
+        let time2 = RtmpTimestamp::new(60);
+
+        assert!(time1 < time2, "time1 was not less than time2");
+        assert!(time2 > time1, "time2 was not greater than time2");
+        assert_eq!(
+            time1,
+            RtmpTimestamp::new(50),
+            "Two timestamps with the same time were not equal"
+        );
+    }
+
+    #[test]
+    fn can_do_comparisons_with_timestamps_that_wrap_around() {
+        let time1 = RtmpTimestamp::new(10000);
+        let time2 = RtmpTimestamp::new(4000000000);
+        let time3 = RtmpTimestamp::new(3000000000);
+
+        assert!(
+            time1 > time2,
+            "10000 was not marked as greater than 4000000000"
+        );
+        assert!(
+            time3 < time2,
+            "4000000000 was not marked greater than 3000000000"
+        );
+    }
+
+    #[test]
+    fn can_compare_timestamps_with_u32() {
+        let time1 = RtmpTimestamp::new(50);
+
+        assert!(time1 < 60, "time1 was not less than 60");
+        assert!(time1 > 20, "time1 was not greater than 20");
+        assert_eq!(time1, 50, "time1 was not equal to 50");
+    }
+
+    #[test]
+    fn can_set_timestamp_value() {
+        let mut time = RtmpTimestamp::new(50);
+        time.set(60);
+
+        assert_eq!(time, 60);
+    }
+
+    #[test]
+    fn diff_is_signed_distance_between_timestamps() {
+        let time1 = RtmpTimestamp::new(50);
+        let time2 = RtmpTimestamp::new(80);
+
+        assert_eq!(time2.diff(time1), 30);
+        assert_eq!(time1.diff(time2), -30);
+    }
+
+    #[test]
+    fn diff_is_continuous_across_wrap_around() {
+        let before_wrap = RtmpTimestamp::new(u32::MAX - 4);
+        let after_wrap = RtmpTimestamp::new(5);
+
+        assert_eq!(after_wrap.diff(before_wrap), 10);
+        assert_eq!(before_wrap.diff(after_wrap), -10);
+    }
+
+    #[test]
+    fn duration_since_earlier_timestamp_across_wrap_around() {
+        let before_wrap = RtmpTimestamp::new(u32::MAX);
+        let after_wrap = RtmpTimestamp::new(99);
+
+        assert_eq!(
+            after_wrap.checked_duration_since(before_wrap),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(before_wrap.checked_duration_since(after_wrap), None);
+        assert_eq!(
+            before_wrap.saturating_duration_since(after_wrap),
+            Duration::from_millis(0)
+        );
+    }
+
+    #[test]
+    fn durations_wrap_when_added_and_subtracted() {
+        let time = RtmpTimestamp::new(u32::MAX - 9);
+
+        assert_eq!(time + Duration::from_millis(20), RtmpTimestamp::new(10));
+        assert_eq!(RtmpTimestamp::new(10) - Duration::from_millis(20), time);
+    }
+
+    #[test]
+    fn can_create_timestamp_from_duration_past_u32() {
+        let duration = Duration::from_millis(u32::MAX as u64 + 51);
+
+        assert_eq!(
+            RtmpTimestamp::from_duration(duration),
+            RtmpTimestamp::new(50)
+        );
+    }
+
+    #[test]
+    fn assignment_operators_wrap_like_their_operators() {
+        let mut time = RtmpTimestamp::new(u32::MAX - 9);
+        time += 20;
+        assert_eq!(time, 10);
+
+        time -= Duration::from_millis(20);
+        assert_eq!(time, u32::MAX - 9);
+
+        time += RtmpTimestamp::new(10);
+        assert_eq!(time, 0);
+    }
+
+    #[test]
+    fn timestamps_convert_to_and_from_durations() {
+        let time = RtmpTimestamp::from(Duration::from_millis(u32::MAX as u64 + 51));
+
+        assert_eq!(time, RtmpTimestamp::new(50));
+        assert_eq!(Duration::from(time), Duration::from_millis(50));
+        assert_eq!(u32::from(time), 50);
+    }
+}
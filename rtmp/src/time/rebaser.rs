@@ -0,0 +1,177 @@
+use super::RtmpTimestamp;
+
+const DEFAULT_MAX_FORWARD_JUMP_MS: u32 = 10_000;
+const DEFAULT_MAX_BACKWARD_JUMP_MS: u32 = 1_000;
+
+/// Maps the timestamps a stream is received with onto an outgoing timeline that starts at zero
+/// and never goes backwards.
+///
+/// Publishers can start their timestamps from any value, and when an encoder reconnects (or a
+/// relay reconnects to its source) the timestamps usually start over from somewhere else.
+/// Passing these through as-is makes players stall or skip, and makes recordings and segments
+/// claim durations that are wildly wrong.
+///
+/// Timestamps are passed through with a fixed offset for as long as they move forward by no
+/// more than the maximum forward jump, or backwards by no more than the maximum backward jump
+/// (which allows for audio and video that are interleaved slightly out of order).  Anything
+/// outside of that is treated as a discontinuity, and the offset is recalculated so the
+/// timeline continues from where it left off.  Wrapping past the 32 bit limit is not a
+/// discontinuity, as `RtmpTimestamp` handles it.
+///
+/// Audio and video of the same stream should go through the same rebaser, so they stay in
+/// sync with each other.  Outgoing timestamps that would go backwards are held at the latest
+/// timestamp instead.
+///
+/// # Examples
+/// ```
+/// use rml_rtmp::time::{RtmpTimestamp, TimestampRebaser};
+///
+/// let mut rebaser = TimestampRebaser::new();
+/// assert_eq!(rebaser.rebase(RtmpTimestamp::new(50_000)), RtmpTimestamp::new(0));
+/// assert_eq!(rebaser.rebase(RtmpTimestamp::new(50_033)), RtmpTimestamp::new(33));
+///
+/// // The encoder restarted, so the timeline continues from the last timestamp
+/// assert_eq!(rebaser.rebase(RtmpTimestamp::new(0)), RtmpTimestamp::new(33));
+/// assert_eq!(rebaser.rebase(RtmpTimestamp::new(33)), RtmpTimestamp::new(66));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimestampRebaser {
+    max_forward_jump: u32,
+    max_backward_jump: u32,
+    offset: Option<u32>,
+    last_input: RtmpTimestamp,
+    last_output: Option<RtmpTimestamp>,
+}
+
+impl TimestampRebaser {
+    /// Creates a rebaser that treats forward jumps of more than 10 seconds, and backward jumps
+    /// of more than 1 second, as discontinuities
+    pub fn new() -> TimestampRebaser {
+        TimestampRebaser {
+            max_forward_jump: DEFAULT_MAX_FORWARD_JUMP_MS,
+            max_backward_jump: DEFAULT_MAX_BACKWARD_JUMP_MS,
+            offset: None,
+            last_input: RtmpTimestamp::new(0),
+            last_output: None,
+        }
+    }
+
+    /// Sets how many milliseconds timestamps can move forward by before it's treated as a
+    /// discontinuity
+    pub fn set_max_forward_jump(&mut self, milliseconds: u32) {
+        self.max_forward_jump = milliseconds;
+    }
+
+    /// Sets how many milliseconds timestamps can move backwards by before it's treated as a
+    /// discontinuity
+    pub fn set_max_backward_jump(&mut self, milliseconds: u32) {
+        self.max_backward_jump = milliseconds;
+    }
+
+    /// Maps a received timestamp onto the outgoing timeline
+    pub fn rebase(&mut self, timestamp: RtmpTimestamp) -> RtmpTimestamp {
+        let offset = match self.offset {
+            Some(offset) if !self.is_discontinuity(timestamp) => offset,
+            _ => {
+                let continue_from = self.last_output.unwrap_or_else(|| RtmpTimestamp::new(0));
+                (continue_from - timestamp).value
+            }
+        };
+
+        let mut output = timestamp + offset;
+        if let Some(last_output) = self.last_output {
+            if output < last_output {
+                output = last_output;
+            }
+        }
+
+        self.offset = Some(offset);
+        self.last_input = timestamp;
+        self.last_output = Some(output);
+        output
+    }
+
+    /// Makes the next timestamp continue the timeline from the latest outgoing timestamp, no
+    /// matter how far it is from the previous one.  This is for when the caller knows the
+    /// source has changed, such as a relay reconnecting.
+    pub fn mark_discontinuity(&mut self) {
+        self.offset = None;
+    }
+
+    /// Starts a new timeline, so the next timestamp is mapped to zero
+    pub fn reset(&mut self) {
+        self.offset = None;
+        self.last_output = None;
+    }
+
+    fn is_discontinuity(&self, timestamp: RtmpTimestamp) -> bool {
+        let jump = timestamp.diff(self.last_input);
+        if jump >= 0 {
+            jump as u32 > self.max_forward_jump
+        } else {
+            jump.unsigned_abs() > self.max_backward_jump
+        }
+    }
+}
+
+impl Default for TimestampRebaser {
+    fn default() -> Self {
+        TimestampRebaser::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn rebase_all(rebaser: &mut TimestampRebaser, timestamps: &[u32]) -> Vec<u32> {
+        timestamps
+            .iter()
+            .map(|x| rebaser.rebase(RtmpTimestamp::new(*x)).value)
+            .collect()
+    }
+
+    #[test]
+    fn timestamps_wrapping_past_u32_are_continuous() {
+        let mut rebaser = TimestampRebaser::new();
+        let outputs = rebase_all(&mut rebaser, &[u32::MAX - 40, u32::MAX - 7, 25]);
+
+        assert_eq!(outputs, vec![0, 33, 66]);
+    }
+
+    #[test]
+    fn large_forward_jump_continues_timeline() {
+        let mut rebaser = TimestampRebaser::new();
+        let outputs = rebase_all(&mut rebaser, &[1000, 1033, 500_000, 500_033]);
+
+        assert_eq!(outputs, vec![0, 33, 33, 66]);
+    }
+
+    #[test]
+    fn slightly_out_of_order_timestamps_do_not_go_backwards() {
+        let mut rebaser = TimestampRebaser::new();
+        let outputs = rebase_all(&mut rebaser, &[1000, 1040, 1020, 1060]);
+
+        assert_eq!(outputs, vec![0, 40, 40, 60]);
+    }
+
+    #[test]
+    fn marked_discontinuity_continues_timeline_from_next_timestamp() {
+        let mut rebaser = TimestampRebaser::new();
+        rebase_all(&mut rebaser, &[1000, 2000]);
+        rebaser.mark_discontinuity();
+        let outputs = rebase_all(&mut rebaser, &[2500, 2533]);
+
+        assert_eq!(outputs, vec![1000, 1033]);
+    }
+
+    #[test]
+    fn reset_starts_timeline_from_zero() {
+        let mut rebaser = TimestampRebaser::new();
+        rebase_all(&mut rebaser, &[1000, 2000]);
+        rebaser.reset();
+
+        assert_eq!(rebase_all(&mut rebaser, &[5000]), vec![0]);
+    }
+}
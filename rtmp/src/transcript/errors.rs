@@ -0,0 +1,23 @@
+use std::io;
+use thiserror::Error;
+
+/// Errors that can occur while reading or writing a transcript
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TranscriptError {
+    /// The input doesn't start with the bytes every transcript starts with
+    #[error("The input is not a session transcript")]
+    NotATranscript,
+
+    /// The transcript was written in a format this version doesn't know how to read
+    #[error("Transcript format version {version} is not supported")]
+    UnsupportedVersion { version: u8 },
+
+    /// An entry's direction byte wasn't inbound or outbound, so the transcript is corrupt
+    #[error("Invalid transcript entry direction of {direction}")]
+    InvalidDirection { direction: u8 },
+
+    /// Failed to read or write the transcript
+    #[error("An IO error occurred with the transcript: {0}")]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,210 @@
+/*!
+This module contains session transcripts, which record every byte a session received and sent
+so a connection can be reproduced offline.
+
+Interoperability bugs usually only show up against one customer's encoder or player, which is
+rarely available to whoever is debugging the problem.  Setting `record_transcript` in a
+`ClientSessionConfig` or `ServerSessionConfig` makes the session record its inbound bytes and
+the packets it produces, along with when each was seen.  The transcript can then be saved with
+`write_to()`, sent along with the bug report, and loaded with `read_from()`.  A
+`TranscriptReplay` feeds the recorded inbound bytes into a fresh session, one read at a time,
+so the problem can be stepped through in a debugger or turned into a test.
+
+Transcripts start after the handshake, since that's when sessions are created, and record the
+packets a session produced even if the application chose to drop some of them.
+
+# Format
+
+A transcript starts with the 4 bytes `RMLT` and a version byte of 1.  Each entry follows as a
+direction byte (0 for inbound, 1 for outbound), the milliseconds since recording started as a
+big endian u64, the number of bytes as a big endian u32, and then the bytes themselves.
+*/
+
+mod errors;
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+mod recorder;
+mod replay;
+
+pub use self::errors::TranscriptError;
+pub use self::replay::{ReplayResult, ReplayableSession, TranscriptReplay};
+
+#[cfg(any(feature = "client-session", feature = "server-session"))]
+pub(crate) use self::recorder::TranscribingSerializer;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::Bytes;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"RMLT";
+const VERSION: u8 = 1;
+
+/// Which way the bytes of a transcript entry were travelling
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum TranscriptDirection {
+    /// Bytes received from the peer and passed into the session
+    Inbound,
+
+    /// A packet the session produced to be sent to the peer
+    Outbound,
+}
+
+/// A single read or packet in a transcript
+#[derive(PartialEq, Debug, Clone)]
+pub struct TranscriptEntry {
+    /// Milliseconds between when recording started and when the bytes were seen
+    pub elapsed_ms: u64,
+    pub direction: TranscriptDirection,
+    pub bytes: Bytes,
+}
+
+/// Every byte a session received and sent, in the order it happened
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    /// Creates a transcript without any entries
+    pub fn new() -> Transcript {
+        Transcript {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Writes the transcript in the format described in the module documentation
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), TranscriptError> {
+        writer.write_all(MAGIC)?;
+        writer.write_u8(VERSION)?;
+        for entry in &self.entries {
+            let direction = match entry.direction {
+                TranscriptDirection::Inbound => 0,
+                TranscriptDirection::Outbound => 1,
+            };
+
+            writer.write_u8(direction)?;
+            writer.write_u64::<BigEndian>(entry.elapsed_ms)?;
+            writer.write_u32::<BigEndian>(entry.bytes.len() as u32)?;
+            writer.write_all(&entry.bytes)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a transcript that was written with `write_to()`
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Transcript, TranscriptError> {
+        let mut magic = [0_u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(TranscriptError::NotATranscript);
+        }
+
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(TranscriptError::UnsupportedVersion { version });
+        }
+
+        let mut transcript = Transcript::new();
+        loop {
+            let direction = match reader.read_u8() {
+                Ok(0) => TranscriptDirection::Inbound,
+                Ok(1) => TranscriptDirection::Outbound,
+                Ok(direction) => return Err(TranscriptError::InvalidDirection { direction }),
+                Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error.into()),
+            };
+
+            let elapsed_ms = reader.read_u64::<BigEndian>()?;
+            let length = reader.read_u32::<BigEndian>()?;
+
+            // Read through `take()` so a corrupt length can't allocate more than the input holds
+            let mut bytes = Vec::new();
+            reader.take(length as u64).read_to_end(&mut bytes)?;
+            if bytes.len() != length as usize {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+
+            transcript.entries.push(TranscriptEntry {
+                elapsed_ms,
+                direction,
+                bytes: Bytes::from(bytes),
+            });
+        }
+
+        Ok(transcript)
+    }
+
+    /// Iterates over the entries the session received from its peer
+    pub fn inbound(&self) -> impl Iterator<Item = &TranscriptEntry> {
+        self.entries
+            .iter()
+            .filter(|x| x.direction == TranscriptDirection::Inbound)
+    }
+
+    /// Iterates over the packets the session produced
+    pub fn outbound(&self) -> impl Iterator<Item = &TranscriptEntry> {
+        self.entries
+            .iter()
+            .filter(|x| x.direction == TranscriptDirection::Outbound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn entry(elapsed_ms: u64, direction: TranscriptDirection, bytes: &[u8]) -> TranscriptEntry {
+        TranscriptEntry {
+            elapsed_ms,
+            direction,
+            bytes: Bytes::copy_from_slice(bytes),
+        }
+    }
+
+    #[test]
+    fn transcript_round_trips_through_bytes() {
+        let mut transcript = Transcript::new();
+        transcript
+            .entries
+            .push(entry(0, TranscriptDirection::Outbound, &[1, 2, 3]));
+        transcript
+            .entries
+            .push(entry(25, TranscriptDirection::Inbound, &[4, 5]));
+        transcript
+            .entries
+            .push(entry(u64::MAX, TranscriptDirection::Inbound, &[]));
+
+        let mut bytes = Vec::new();
+        transcript.write_to(&mut bytes).unwrap();
+        let result = Transcript::read_from(&mut Cursor::new(bytes)).unwrap();
+
+        assert_eq!(result, transcript);
+    }
+
+    #[test]
+    fn input_without_magic_is_rejected() {
+        let mut input = Cursor::new(b"FLV\x01\x05".to_vec());
+
+        match Transcript::read_from(&mut input) {
+            Err(TranscriptError::NotATranscript) => (),
+            x => panic!("Expected not a transcript error, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn truncated_entry_is_rejected() {
+        let mut transcript = Transcript::new();
+        transcript
+            .entries
+            .push(entry(10, TranscriptDirection::Inbound, &[1, 2, 3, 4]));
+
+        let mut bytes = Vec::new();
+        transcript.write_to(&mut bytes).unwrap();
+        bytes.pop();
+
+        match Transcript::read_from(&mut Cursor::new(bytes)) {
+            Err(TranscriptError::Io(_)) => (),
+            x => panic!("Expected io error, instead got {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use super::{Transcript, TranscriptDirection, TranscriptEntry};
+use bytes::Bytes;
+#[cfg(feature = "server-session")]
+use chunk_io::PreparedPacket;
+use chunk_io::{ChunkSerializationError, ChunkSerializer, Packet};
+use messages::MessagePayload;
+use sessions::SessionClock;
+use time::RtmpTimestamp;
+
+/// Wraps a session's `ChunkSerializer` so every packet it produces can be recorded, along with
+/// the inbound bytes the session is given.  Every outbound packet passes through the serializer,
+/// so sessions don't have to remember to record them.
+pub(crate) struct TranscribingSerializer {
+    serializer: ChunkSerializer,
+    recording: Option<Recording>,
+}
+
+struct Recording {
+    clock: SessionClock,
+    started_at: u64,
+    transcript: Transcript,
+}
+
+impl TranscribingSerializer {
+    /// Creates a serializer that only records if a clock to timestamp entries with is given
+    pub fn new(recording_clock: Option<SessionClock>) -> TranscribingSerializer {
+        TranscribingSerializer {
+            serializer: ChunkSerializer::new(),
+            recording: recording_clock.map(|clock| Recording {
+                clock,
+                started_at: clock(),
+                transcript: Transcript::new(),
+            }),
+        }
+    }
+
+    pub fn set_max_chunk_size(
+        &mut self,
+        new_size: u32,
+        time: RtmpTimestamp,
+    ) -> Result<Packet, ChunkSerializationError> {
+        let packet = self.serializer.set_max_chunk_size(new_size, time)?;
+        self.record(TranscriptDirection::Outbound, &packet.bytes);
+        Ok(packet)
+    }
+
+    pub fn serialize(
+        &mut self,
+        message: &MessagePayload,
+        force_uncompressed: bool,
+        can_be_dropped: bool,
+    ) -> Result<Packet, ChunkSerializationError> {
+        let packet = self
+            .serializer
+            .serialize(message, force_uncompressed, can_be_dropped)?;
+
+        self.record(TranscriptDirection::Outbound, &packet.bytes);
+        Ok(packet)
+    }
+
+    #[cfg(feature = "server-session")]
+    pub fn serialize_prepared(
+        &mut self,
+        prepared: &PreparedPacket,
+    ) -> Result<Packet, ChunkSerializationError> {
+        let packet = self.serializer.serialize_prepared(prepared)?;
+        self.record(TranscriptDirection::Outbound, &packet.bytes);
+        Ok(packet)
+    }
+
+    #[cfg(feature = "server-session")]
+    pub fn get_max_chunk_size(&self) -> u32 {
+        self.serializer.get_max_chunk_size()
+    }
+
+    /// Transcripts are left out, as they're opt in and hold on to every byte by design
+    pub fn memory_usage(&self) -> usize {
+        self.serializer.memory_usage()
+    }
+
+    pub fn record_inbound(&mut self, bytes: &[u8]) {
+        if self.recording.is_some() {
+            self.record(TranscriptDirection::Inbound, &Bytes::copy_from_slice(bytes));
+        }
+    }
+
+    pub fn transcript(&self) -> Option<&Transcript> {
+        self.recording.as_ref().map(|x| &x.transcript)
+    }
+
+    /// Returns what has been recorded so far, and starts the next transcript empty
+    pub fn take_transcript(&mut self) -> Option<Transcript> {
+        self.recording
+            .as_mut()
+            .map(|x| std::mem::take(&mut x.transcript))
+    }
+
+    fn record(&mut self, direction: TranscriptDirection, bytes: &Bytes) {
+        if let Some(ref mut recording) = self.recording {
+            // Clocks that go backwards are treated as not having moved
+            let elapsed_ms = (recording.clock)().saturating_sub(recording.started_at);
+            recording.transcript.entries.push(TranscriptEntry {
+                elapsed_ms,
+                direction,
+                bytes: bytes.clone(),
+            });
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use super::{Transcript, TranscriptDirection, TranscriptEntry};
+#[cfg(feature = "client-session")]
+use sessions::{ClientSession, ClientSessionError, ClientSessionResult};
+#[cfg(feature = "server-session")]
+use sessions::{ServerSession, ServerSessionError, ServerSessionResult};
+use std::slice;
+
+/// A session that can have a transcript's inbound bytes replayed into it
+pub trait ReplayableSession {
+    type Result;
+    type Error;
+
+    /// Passes bytes received from the peer into the session
+    fn replay_input(&mut self, bytes: &[u8]) -> Result<Vec<Self::Result>, Self::Error>;
+}
+
+/// What a session did with one of the inbound entries being replayed into it
+pub type ReplayResult<S> =
+    Result<Vec<<S as ReplayableSession>::Result>, <S as ReplayableSession>::Error>;
+
+#[cfg(feature = "client-session")]
+impl ReplayableSession for ClientSession {
+    type Result = ClientSessionResult;
+    type Error = ClientSessionError;
+
+    fn replay_input(&mut self, bytes: &[u8]) -> Result<Vec<Self::Result>, Self::Error> {
+        self.handle_input(bytes)
+    }
+}
+
+#[cfg(feature = "server-session")]
+impl ReplayableSession for ServerSession {
+    type Result = ServerSessionResult;
+    type Error = ServerSessionError;
+
+    fn replay_input(&mut self, bytes: &[u8]) -> Result<Vec<Self::Result>, Self::Error> {
+        self.handle_input(bytes)
+    }
+}
+
+/// Drives a fresh session with the inbound bytes of a transcript, in the same reads they were
+/// originally received in.
+///
+/// The session should be created with the same configuration as the session that was recorded.
+/// Anything the application did in response to the recorded session's events, such as
+/// accepting a request, has to be repeated on the replayed session between calls to
+/// `replay_next()` for it to follow the same path.
+pub struct TranscriptReplay<'a, S> {
+    session: S,
+    entries: slice::Iter<'a, TranscriptEntry>,
+}
+
+impl<'a, S: ReplayableSession> TranscriptReplay<'a, S> {
+    /// Creates a replay that starts from the first entry of the transcript
+    pub fn new(session: S, transcript: &'a Transcript) -> TranscriptReplay<'a, S> {
+        TranscriptReplay {
+            session,
+            entries: transcript.entries.iter(),
+        }
+    }
+
+    /// Passes the next inbound entry's bytes into the session, returning the entry along with
+    /// what the session did with them.  `None` is returned once every inbound entry has been
+    /// replayed.
+    pub fn replay_next(&mut self) -> Option<(&'a TranscriptEntry, ReplayResult<S>)> {
+        let entry = self
+            .entries
+            .by_ref()
+            .find(|x| x.direction == TranscriptDirection::Inbound)?;
+
+        Some((entry, self.session.replay_input(&entry.bytes)))
+    }
+
+    /// Replays every remaining inbound entry, returning the results of all of them.  Replaying
+    /// stops at the first error.
+    pub fn replay_all(&mut self) -> ReplayResult<S> {
+        let mut results = Vec::new();
+        while let Some((_, entry_results)) = self.replay_next() {
+            results.extend(entry_results?);
+        }
+
+        Ok(results)
+    }
+
+    /// The session being replayed into
+    pub fn session(&self) -> &S {
+        &self.session
+    }
+
+    /// The session being replayed into, so the application's reactions to events can be
+    /// repeated on it
+    pub fn session_mut(&mut self) -> &mut S {
+        &mut self.session
+    }
+
+    /// Ends the replay, returning the session
+    pub fn into_session(self) -> S {
+        self.session
+    }
+}
+
+#[cfg(all(test, feature = "client-session", feature = "server-session"))]
+mod tests {
+    use super::*;
+    use sessions::{ClientSessionConfig, ServerSessionConfig, ServerSessionEvent};
+    use std::io::Cursor;
+
+    fn outbound_bytes<T>(results: Vec<T>, packet: fn(T) -> Option<Vec<u8>>) -> Vec<u8> {
+        results.into_iter().filter_map(packet).flatten().collect()
+    }
+
+    fn client_packet(result: ClientSessionResult) -> Option<Vec<u8>> {
+        match result {
+            ClientSessionResult::OutboundResponse(packet) => Some(packet.bytes.to_vec()),
+            _ => None,
+        }
+    }
+
+    fn server_packet(result: ServerSessionResult) -> Option<Vec<u8>> {
+        match result {
+            ServerSessionResult::OutboundResponse(packet) => Some(packet.bytes.to_vec()),
+            _ => None,
+        }
+    }
+
+    fn connection_requests(results: &[ServerSessionResult]) -> Vec<String> {
+        results
+            .iter()
+            .filter_map(|result| match *result {
+                ServerSessionResult::RaisedEvent(ServerSessionEvent::ConnectionRequested {
+                    ref app_name,
+                    ..
+                }) => Some(app_name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Connects a client to a server that records a transcript, returning the transcript
+    fn record_connection_request() -> Transcript {
+        let mut server_config = ServerSessionConfig::new();
+        server_config.record_transcript = true;
+
+        let (mut server, server_results) = ServerSession::new(server_config).unwrap();
+        let (mut client, client_results) = ClientSession::new(ClientSessionConfig::new()).unwrap();
+
+        client
+            .handle_input(&outbound_bytes(server_results, server_packet))
+            .unwrap();
+
+        let connect = client.request_connection("live".to_string()).unwrap();
+        let mut to_server = outbound_bytes(client_results, client_packet);
+        to_server.extend(outbound_bytes(vec![connect], client_packet));
+
+        let results = server.handle_input(&to_server).unwrap();
+        assert_eq!(connection_requests(&results), vec!["live".to_string()]);
+
+        server.take_transcript().unwrap()
+    }
+
+    #[test]
+    fn server_records_inbound_and_outbound_bytes() {
+        let transcript = record_connection_request();
+
+        assert_eq!(transcript.inbound().count(), 1);
+        assert!(transcript.outbound().count() >= 5);
+        assert_eq!(
+            transcript.entries.first().map(|x| x.direction),
+            Some(TranscriptDirection::Outbound)
+        );
+    }
+
+    #[test]
+    fn replaying_transcript_raises_same_events() {
+        let mut saved = Vec::new();
+        record_connection_request().write_to(&mut saved).unwrap();
+        let transcript = Transcript::read_from(&mut Cursor::new(saved)).unwrap();
+
+        let (server, _) = ServerSession::new(ServerSessionConfig::new()).unwrap();
+        let mut replay = TranscriptReplay::new(server, &transcript);
+        let results = replay.replay_all().unwrap();
+
+        assert_eq!(connection_requests(&results), vec!["live".to_string()]);
+        assert!(replay.replay_next().is_none());
+    }
+
+    #[test]
+    fn sessions_do_not_record_by_default() {
+        let (mut server, _) = ServerSession::new(ServerSessionConfig::new()).unwrap();
+        server.handle_input(&[]).unwrap();
+
+        assert!(server.transcript().is_none());
+    }
+}
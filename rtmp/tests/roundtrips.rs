@@ -0,0 +1,276 @@
+//! Property based tests that generate random RTMP messages and chunk streams, and check that
+//! everything that gets serialized deserializes back into what it started as.
+//!
+//! Chunk streams are generated with random max chunk size changes mixed in with the messages,
+//! and the serialized bytes are handed to the deserializer in randomly sized reads, so chunks
+//! split across reads and compressed headers that rely on earlier messages get exercised.
+
+extern crate bytes;
+extern crate proptest;
+extern crate rml_amf0;
+extern crate rml_rtmp;
+
+use bytes::Bytes;
+use proptest::prelude::*;
+use rml_amf0::Amf0Value;
+use rml_rtmp::chunk_io::{ChunkDeserializer, ChunkSerializer};
+use rml_rtmp::messages::{
+    MessagePayload, PeerBandwidthLimitType, RtmpMessage, UserControlEventType,
+};
+use rml_rtmp::time::RtmpTimestamp;
+
+/// Type ids that are deserialized into something other than an `Unknown` message
+const KNOWN_TYPE_IDS: &[u8] = &[1, 2, 3, 4, 5, 6, 8, 9, 15, 17, 18, 20];
+
+#[derive(Debug)]
+enum ChunkStreamItem {
+    Message(MessagePayload),
+    SetMaxChunkSize(u32),
+}
+
+fn data(max_length: usize) -> impl Strategy<Value = Bytes> {
+    prop::collection::vec(any::<u8>(), 0..max_length).prop_map(Bytes::from)
+}
+
+/// NaN never equals itself, so it can't be checked for by comparing values
+fn number() -> impl Strategy<Value = f64> {
+    any::<f64>().prop_filter("NaN does not equal itself", |x| !x.is_nan())
+}
+
+fn amf0_value() -> impl Strategy<Value = Amf0Value> {
+    let leaf = prop_oneof![
+        number().prop_map(Amf0Value::Number),
+        any::<bool>().prop_map(Amf0Value::Boolean),
+        ".{0,20}".prop_map(Amf0Value::Utf8String),
+        Just(Amf0Value::Null),
+        Just(Amf0Value::Undefined),
+    ];
+
+    leaf.prop_recursive(3, 32, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Amf0Value::StrictArray),
+            prop::collection::vec(("[a-zA-Z_]{1,10}", inner), 0..4)
+                .prop_map(|properties| Amf0Value::Object(properties.into_iter().collect())),
+        ]
+    })
+}
+
+fn user_control() -> impl Strategy<Value = RtmpMessage> {
+    let stream_event = prop_oneof![
+        Just(UserControlEventType::StreamBegin),
+        Just(UserControlEventType::StreamEof),
+        Just(UserControlEventType::StreamDry),
+        Just(UserControlEventType::StreamIsRecorded),
+        Just(UserControlEventType::BufferEmpty),
+        Just(UserControlEventType::BufferReady),
+    ];
+
+    let ping_event = prop_oneof![
+        Just(UserControlEventType::PingRequest),
+        Just(UserControlEventType::PingResponse),
+    ];
+
+    // Each event type only carries the fields it has on the wire
+    prop_oneof![
+        (stream_event, any::<u32>()).prop_map(|(event_type, stream_id)| {
+            RtmpMessage::UserControl {
+                event_type,
+                stream_id: Some(stream_id),
+                buffer_length: None,
+                timestamp: None,
+            }
+        }),
+        (any::<u32>(), any::<u32>()).prop_map(|(stream_id, buffer_length)| {
+            RtmpMessage::UserControl {
+                event_type: UserControlEventType::SetBufferLength,
+                stream_id: Some(stream_id),
+                buffer_length: Some(buffer_length),
+                timestamp: None,
+            }
+        }),
+        (ping_event, any::<u32>()).prop_map(|(event_type, timestamp)| {
+            RtmpMessage::UserControl {
+                event_type,
+                stream_id: None,
+                buffer_length: None,
+                timestamp: Some(RtmpTimestamp::new(timestamp)),
+            }
+        }),
+    ]
+}
+
+fn rtmp_message() -> impl Strategy<Value = RtmpMessage> {
+    let limit_type = prop_oneof![
+        Just(PeerBandwidthLimitType::Hard),
+        Just(PeerBandwidthLimitType::Soft),
+        Just(PeerBandwidthLimitType::Dynamic),
+    ];
+
+    prop_oneof![
+        (
+            any::<u8>().prop_filter("known types are not unknown", |x| {
+                !KNOWN_TYPE_IDS.contains(x)
+            }),
+            data(256)
+        )
+            .prop_map(|(type_id, data)| RtmpMessage::Unknown { type_id, data }),
+        any::<u32>().prop_map(|stream_id| RtmpMessage::Abort { stream_id }),
+        any::<u32>().prop_map(|sequence_number| RtmpMessage::Acknowledgement { sequence_number }),
+        (
+            ".{0,20}",
+            number(),
+            amf0_value(),
+            prop::collection::vec(amf0_value(), 0..4)
+        )
+            .prop_map(
+                |(command_name, transaction_id, command_object, additional_arguments)| {
+                    RtmpMessage::Amf0Command {
+                        command_name,
+                        transaction_id,
+                        command_object,
+                        additional_arguments,
+                    }
+                }
+            ),
+        prop::collection::vec(amf0_value(), 0..4)
+            .prop_map(|values| RtmpMessage::Amf0Data { values }),
+        data(256).prop_map(|data| RtmpMessage::AudioData { data }),
+        (0..0x80000000_u32).prop_map(|size| RtmpMessage::SetChunkSize { size }),
+        (any::<u32>(), limit_type)
+            .prop_map(|(size, limit_type)| RtmpMessage::SetPeerBandwidth { size, limit_type }),
+        user_control(),
+        data(256).prop_map(|data| RtmpMessage::VideoData { data }),
+        any::<u32>().prop_map(|size| RtmpMessage::WindowAcknowledgement { size }),
+    ]
+}
+
+/// Timestamps are mostly small so chunk headers get compressed into deltas, but sometimes large
+/// enough to need extended timestamps or to go backwards.
+fn timestamp() -> impl Strategy<Value = RtmpTimestamp> {
+    prop_oneof![
+        3 => 0..10_000_u32,
+        1 => any::<u32>(),
+    ]
+    .prop_map(RtmpTimestamp::new)
+}
+
+/// Payloads share a few stream ids and type ids between them, so messages of different streams
+/// are interleaved on the same chunk stream ids.
+fn message_payload() -> impl Strategy<Value = MessagePayload> {
+    (
+        timestamp(),
+        prop_oneof![3 => 0..6_u32, 1 => any::<u32>()],
+        prop_oneof![
+            Just(8_u8),
+            Just(9_u8),
+            Just(18_u8),
+            Just(20_u8),
+            any::<u8>()
+        ],
+        data(1024),
+    )
+        .prop_map(
+            |(timestamp, message_stream_id, type_id, data)| MessagePayload {
+                timestamp,
+                type_id,
+                message_stream_id,
+                data,
+            },
+        )
+}
+
+fn chunk_stream() -> impl Strategy<Value = Vec<ChunkStreamItem>> {
+    let item = prop_oneof![
+        6 => message_payload().prop_map(ChunkStreamItem::Message),
+        1 => (1..4096_u32).prop_map(ChunkStreamItem::SetMaxChunkSize),
+    ];
+
+    prop::collection::vec(item, 1..24)
+}
+
+proptest! {
+    #[test]
+    fn rtmp_messages_round_trip_through_payloads(
+        message in rtmp_message(),
+        timestamp in any::<u32>(),
+        stream_id in any::<u32>(),
+    ) {
+        let timestamp = RtmpTimestamp::new(timestamp);
+        let payload = MessagePayload::from_rtmp_message(message.clone(), timestamp, stream_id).unwrap();
+
+        prop_assert_eq!(payload.timestamp, timestamp);
+        prop_assert_eq!(payload.message_stream_id, stream_id);
+        prop_assert_eq!(payload.to_rtmp_message().unwrap(), message);
+    }
+
+    #[test]
+    fn rtmp_messages_round_trip_through_chunks(
+        messages in prop::collection::vec((rtmp_message(), timestamp()), 1..8),
+    ) {
+        let mut serializer = ChunkSerializer::new();
+        let mut deserializer = ChunkDeserializer::new();
+
+        for (message, timestamp) in messages {
+            let payload = MessagePayload::from_rtmp_message(message.clone(), timestamp, 1).unwrap();
+            let packet = serializer.serialize(&payload, false, false).unwrap();
+            let result = deserializer.get_next_message(&packet.bytes).unwrap();
+
+            prop_assert_eq!(result.as_ref(), Some(&payload));
+            prop_assert_eq!(result.unwrap().to_rtmp_message().unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn chunk_streams_round_trip_across_random_reads(
+        items in chunk_stream(),
+        read_sizes in prop::collection::vec(1..600_usize, 1..8),
+    ) {
+        let mut serializer = ChunkSerializer::new();
+        let mut bytes = Vec::new();
+        let mut expected = Vec::new();
+        for item in items {
+            match item {
+                ChunkStreamItem::Message(payload) => {
+                    let packet = serializer.serialize(&payload, false, false).unwrap();
+                    bytes.extend_from_slice(&packet.bytes);
+                    expected.push((payload, None));
+                }
+
+                ChunkStreamItem::SetMaxChunkSize(size) => {
+                    let time = RtmpTimestamp::new(0);
+                    let packet = serializer.set_max_chunk_size(size, time).unwrap();
+                    let message = RtmpMessage::SetChunkSize { size };
+                    let payload = MessagePayload::from_rtmp_message(message, time, 0).unwrap();
+
+                    bytes.extend_from_slice(&packet.bytes);
+                    expected.push((payload, Some(size)));
+                }
+            }
+        }
+
+        // The deserializer returns the set chunk size message before it reads any further, which
+        // gives the chance to apply the new size like a session would.
+        let mut deserializer = ChunkDeserializer::new();
+        let mut received = Vec::new();
+        let mut remaining = &bytes[..];
+        let mut read_sizes = read_sizes.iter().cycle();
+        while !remaining.is_empty() {
+            let length = (*read_sizes.next().unwrap()).min(remaining.len());
+            let (read, rest) = remaining.split_at(length);
+            remaining = rest;
+
+            let mut result = deserializer.get_next_message(read).unwrap();
+            while let Some(payload) = result {
+                if let Some(&(_, Some(size))) = expected.get(received.len()) {
+                    deserializer.set_max_chunk_size(size as usize).unwrap();
+                }
+
+                received.push(payload);
+                result = deserializer.get_next_message(&[]).unwrap();
+            }
+        }
+
+        let expected = expected.into_iter().map(|(payload, _)| payload).collect::<Vec<_>>();
+        prop_assert_eq!(received, expected);
+    }
+}
@@ -0,0 +1,474 @@
+use bytes::Bytes;
+use config::SrtConfig;
+use connection::{Connection, ConnectionOutput, ConnectionParameters};
+use errors::SrtError;
+use listener::{
+    handshake_packet, new_socket_id, MAX_TRANSMISSION_UNIT, SRT_VERSION, SUPPORTED_FLAGS,
+};
+use packet::{
+    ControlInfo, ControlPacket, Handshake, HandshakeExtension, HandshakeType, SrtOptions,
+    SrtPacket, HANDSHAKE_MAGIC, MAX_STREAM_ID_LENGTH,
+};
+use rand::{self, Rng};
+use reject::RejectReason;
+use seq::SequenceNumber;
+use std::cmp;
+use std::time::{Duration, Instant};
+
+const HANDSHAKE_RESEND_INTERVAL: Duration = Duration::from_millis(250);
+const UDT_DGRAM_SOCKET_TYPE: u16 = 2;
+
+/// What happened as a result of the caller handling a datagram or being polled
+#[derive(PartialEq, Debug, Clone)]
+pub enum SrtCallerResult {
+    /// A datagram that needs to be sent to the listener
+    OutboundPacket(Bytes),
+
+    /// The handshake completed, so payloads can now be sent
+    Connected,
+
+    /// The listener refused the connection
+    Rejected(RejectReason),
+
+    /// A payload the listener sent, in the order it was sent
+    DataReceived(Bytes),
+
+    /// The listener shut the connection down or stopped responding, or never completed the
+    /// handshake
+    Disconnected,
+}
+
+enum CallerState {
+    Induction,
+    Conclusion { handshake: Bytes },
+    Connected(Box<Connection>),
+    Closed,
+}
+
+/// Connects to an SRT listener, such as to publish a stream to it.
+///
+/// Creating the caller returns the first handshake packet, which is sent to the listener's
+/// address.  Every datagram received back is passed to `handle_datagram()`, and `poll()` is
+/// called at least every 10 milliseconds so handshake packets are resent if they're lost and
+/// the connection is kept alive.  Once `Connected` is raised, payloads (usually 7 MPEG-TS
+/// packets at a time) are sent with `send()`.
+///
+/// # Examples
+/// ```
+/// use rml_srt::{SrtCaller, SrtCallerResult, SrtConfig};
+/// use std::time::Instant;
+///
+/// let mut config = SrtConfig::new();
+/// config.stream_id = Some("#!::r=live/key,m=publish".to_string());
+///
+/// let (caller, results) = SrtCaller::new(config, Instant::now()).unwrap();
+/// match results[0] {
+///     SrtCallerResult::OutboundPacket(ref _induction) => (), // send to the listener
+///     ref x => panic!("Unexpected result: {:?}", x),
+/// }
+///
+/// assert!(!caller.is_connected());
+/// ```
+pub struct SrtCaller {
+    config: SrtConfig,
+    state: CallerState,
+    socket_id: u32,
+    initial_sequence_number: SequenceNumber,
+    started_at: Instant,
+    last_handshake_sent_at: Instant,
+}
+
+impl SrtCaller {
+    /// Creates a caller, returning the induction handshake that starts the connection
+    pub fn new(
+        config: SrtConfig,
+        now: Instant,
+    ) -> Result<(SrtCaller, Vec<SrtCallerResult>), SrtError> {
+        if let Some(ref stream_id) = config.stream_id {
+            if stream_id.len() > MAX_STREAM_ID_LENGTH {
+                return Err(SrtError::StreamIdTooLong {
+                    size: stream_id.len(),
+                    max_size: MAX_STREAM_ID_LENGTH,
+                });
+            }
+        }
+
+        let caller = SrtCaller {
+            config,
+            state: CallerState::Induction,
+            socket_id: new_socket_id(),
+            initial_sequence_number: SequenceNumber::new(rand::thread_rng().gen()),
+            started_at: now,
+            last_handshake_sent_at: now,
+        };
+
+        let results = vec![SrtCallerResult::OutboundPacket(caller.induction())];
+        Ok((caller, results))
+    }
+
+    /// Handles a datagram received from the listener
+    pub fn handle_datagram(
+        &mut self,
+        datagram: &[u8],
+        now: Instant,
+    ) -> Result<Vec<SrtCallerResult>, SrtError> {
+        let packet = SrtPacket::parse(datagram)?;
+        if packet.destination_socket_id() != self.socket_id {
+            return Ok(Vec::new());
+        }
+
+        let handshake = match (&mut self.state, packet) {
+            (
+                &mut CallerState::Induction,
+                SrtPacket::Control(ControlPacket {
+                    info: ControlInfo::Handshake(handshake),
+                    ..
+                }),
+            )
+            | (
+                &mut CallerState::Conclusion { .. },
+                SrtPacket::Control(ControlPacket {
+                    info: ControlInfo::Handshake(handshake),
+                    ..
+                }),
+            ) => handshake,
+
+            (&mut CallerState::Connected(ref mut connection), packet) => {
+                let outputs = connection.handle_packet(packet, now);
+                return Ok(self.convert_outputs(outputs));
+            }
+
+            _ => return Ok(Vec::new()),
+        };
+
+        if let HandshakeType::Rejection(code) = handshake.handshake_type {
+            self.state = CallerState::Closed;
+            return Ok(vec![SrtCallerResult::Rejected(RejectReason::from_code(
+                code,
+            ))]);
+        }
+
+        match (&self.state, handshake.handshake_type) {
+            (&CallerState::Induction, HandshakeType::Induction) => {
+                if handshake.version < 5 || handshake.extension_field != HANDSHAKE_MAGIC {
+                    self.state = CallerState::Closed;
+                    let reason = RejectReason::UnsupportedVersion;
+                    return Ok(vec![SrtCallerResult::Rejected(reason)]);
+                }
+
+                let conclusion = self.conclusion(handshake.syn_cookie);
+                self.state = CallerState::Conclusion {
+                    handshake: conclusion.clone(),
+                };
+
+                self.last_handshake_sent_at = now;
+                Ok(vec![SrtCallerResult::OutboundPacket(conclusion)])
+            }
+
+            (&CallerState::Conclusion { .. }, HandshakeType::Conclusion) => {
+                let mut latency_ms = self.config.latency.as_millis() as u16;
+                if let Some(options) = handshake.options() {
+                    latency_ms = cmp::max(latency_ms, options.sender_latency);
+                    latency_ms = cmp::max(latency_ms, options.receiver_latency);
+                }
+
+                let parameters = ConnectionParameters {
+                    local_socket_id: self.socket_id,
+                    peer_socket_id: handshake.socket_id,
+                    initial_sequence_number: self.initial_sequence_number,
+                    latency: Duration::from_millis(latency_ms as u64),
+                };
+
+                let connection = Connection::new(parameters, &self.config, now);
+                self.state = CallerState::Connected(Box::new(connection));
+                Ok(vec![SrtCallerResult::Connected])
+            }
+
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Sends a payload to the listener
+    pub fn send(&mut self, payload: Bytes, now: Instant) -> Result<SrtCallerResult, SrtError> {
+        match self.state {
+            CallerState::Connected(ref mut connection) => {
+                let data = connection.send(payload, now)?;
+                Ok(SrtCallerResult::OutboundPacket(data))
+            }
+
+            _ => Err(SrtError::NotConnected),
+        }
+    }
+
+    /// Resends handshake packets that went unanswered, and performs the work the connection
+    /// needs done on a timer once it's established
+    pub fn poll(&mut self, now: Instant) -> Vec<SrtCallerResult> {
+        let handshake = match self.state {
+            CallerState::Connected(ref mut connection) => {
+                let outputs = connection.poll(now);
+                return self.convert_outputs(outputs);
+            }
+
+            CallerState::Closed => return Vec::new(),
+            CallerState::Induction => self.induction(),
+            CallerState::Conclusion { ref handshake } => handshake.clone(),
+        };
+
+        if now.duration_since(self.started_at) >= self.config.connect_timeout {
+            self.state = CallerState::Closed;
+            return vec![SrtCallerResult::Disconnected];
+        }
+
+        if now.duration_since(self.last_handshake_sent_at) < HANDSHAKE_RESEND_INTERVAL {
+            return Vec::new();
+        }
+
+        self.last_handshake_sent_at = now;
+        vec![SrtCallerResult::OutboundPacket(handshake)]
+    }
+
+    /// Closes the connection, returning the packet that tells the listener
+    pub fn close(&mut self, now: Instant) -> Vec<SrtCallerResult> {
+        let results = match self.state {
+            CallerState::Connected(ref mut connection) => connection
+                .close(now)
+                .map(SrtCallerResult::OutboundPacket)
+                .into_iter()
+                .collect(),
+
+            _ => Vec::new(),
+        };
+
+        self.state = CallerState::Closed;
+        results
+    }
+
+    /// True once the handshake has completed, until the connection is closed
+    pub fn is_connected(&self) -> bool {
+        matches!(self.state, CallerState::Connected(_))
+    }
+
+    /// How long each side waits for lost packets to be retransmitted, once connected
+    pub fn latency(&self) -> Option<Duration> {
+        match self.state {
+            CallerState::Connected(ref connection) => Some(connection.latency()),
+            _ => None,
+        }
+    }
+
+    fn induction(&self) -> Bytes {
+        let handshake = Handshake {
+            version: 4,
+            encryption_field: 0,
+            extension_field: UDT_DGRAM_SOCKET_TYPE,
+            initial_sequence_number: self.initial_sequence_number,
+            max_transmission_unit: MAX_TRANSMISSION_UNIT,
+            max_flow_window: self.config.max_flow_window,
+            handshake_type: HandshakeType::Induction,
+            socket_id: self.socket_id,
+            syn_cookie: 0,
+            peer_ip_address: [0; 16],
+            extensions: Vec::new(),
+        };
+
+        handshake_packet(handshake, 0)
+    }
+
+    fn conclusion(&self, syn_cookie: u32) -> Bytes {
+        let latency_ms = self.config.latency.as_millis() as u16;
+        let mut extension_field = Handshake::EXTENSION_FLAG_OPTIONS;
+        let mut extensions = vec![HandshakeExtension::Request(SrtOptions {
+            version: SRT_VERSION,
+            flags: SUPPORTED_FLAGS,
+            receiver_latency: latency_ms,
+            sender_latency: latency_ms,
+        })];
+
+        if let Some(ref stream_id) = self.config.stream_id {
+            extension_field |= Handshake::EXTENSION_FLAG_CONFIG;
+            extensions.push(HandshakeExtension::StreamId(stream_id.clone()));
+        }
+
+        let handshake = Handshake {
+            version: 5,
+            encryption_field: 0,
+            extension_field,
+            initial_sequence_number: self.initial_sequence_number,
+            max_transmission_unit: MAX_TRANSMISSION_UNIT,
+            max_flow_window: self.config.max_flow_window,
+            handshake_type: HandshakeType::Conclusion,
+            socket_id: self.socket_id,
+            syn_cookie,
+            peer_ip_address: [0; 16],
+            extensions,
+        };
+
+        handshake_packet(handshake, 0)
+    }
+
+    fn convert_outputs(&mut self, outputs: Vec<ConnectionOutput>) -> Vec<SrtCallerResult> {
+        let mut results = Vec::with_capacity(outputs.len());
+        for output in outputs {
+            results.push(match output {
+                ConnectionOutput::Packet(data) => SrtCallerResult::OutboundPacket(data),
+                ConnectionOutput::Data(data) => SrtCallerResult::DataReceived(data),
+                ConnectionOutput::Closed => {
+                    self.state = CallerState::Closed;
+                    SrtCallerResult::Disconnected
+                }
+            });
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use listener::{SrtListener, SrtListenerResult};
+    use std::net::SocketAddr;
+
+    fn caller_address() -> SocketAddr {
+        "10.0.0.2:5000".parse().unwrap()
+    }
+
+    fn to_listener(
+        listener: &mut SrtListener,
+        results: Vec<SrtCallerResult>,
+        now: Instant,
+    ) -> Vec<SrtListenerResult> {
+        let mut listener_results = Vec::new();
+        for result in results {
+            if let SrtCallerResult::OutboundPacket(data) = result {
+                listener_results.extend(
+                    listener
+                        .handle_datagram(caller_address(), &data, now)
+                        .unwrap(),
+                );
+            }
+        }
+
+        listener_results
+    }
+
+    fn to_caller(
+        caller: &mut SrtCaller,
+        results: Vec<SrtListenerResult>,
+        now: Instant,
+    ) -> Vec<SrtCallerResult> {
+        let mut caller_results = Vec::new();
+        for result in results {
+            if let SrtListenerResult::OutboundPacket { data, .. } = result {
+                caller_results.extend(caller.handle_datagram(&data, now).unwrap());
+            }
+        }
+
+        caller_results
+    }
+
+    fn handshake(
+        stream_id: &str,
+        now: Instant,
+    ) -> (SrtCaller, SrtListener, Vec<SrtListenerResult>) {
+        let mut config = SrtConfig::new();
+        config.stream_id = Some(stream_id.to_string());
+        let (mut caller, results) = SrtCaller::new(config, now).unwrap();
+        let mut listener = SrtListener::new(SrtConfig::new());
+
+        let induction_response = to_listener(&mut listener, results, now);
+        let conclusion = to_caller(&mut caller, induction_response, now);
+        let requested = to_listener(&mut listener, conclusion, now);
+
+        (caller, listener, requested)
+    }
+
+    #[test]
+    fn caller_connects_and_publishes_to_listener() {
+        let now = Instant::now();
+        let (mut caller, mut listener, requested) = handshake("live/key", now);
+
+        assert_eq!(
+            requested,
+            vec![SrtListenerResult::ConnectionRequested {
+                peer: caller_address(),
+                stream_id: Some("live/key".to_string()),
+            }]
+        );
+
+        let response = listener.accept_request(caller_address(), now).unwrap();
+        assert_eq!(
+            to_caller(&mut caller, response, now),
+            vec![SrtCallerResult::Connected]
+        );
+
+        let payload = Bytes::from(vec![0x47; 188]);
+        let packet = caller.send(payload.clone(), now).unwrap();
+
+        assert_eq!(
+            to_listener(&mut listener, vec![packet], now),
+            vec![SrtListenerResult::DataReceived {
+                peer: caller_address(),
+                data: payload,
+            }]
+        );
+
+        assert_eq!(listener.connection_count(), 1);
+    }
+
+    #[test]
+    fn rejected_caller_is_told_why() {
+        let now = Instant::now();
+        let (mut caller, mut listener, _) = handshake("live/key", now);
+
+        let response = listener
+            .reject_request(caller_address(), RejectReason::Unauthorized)
+            .unwrap();
+
+        assert_eq!(
+            to_caller(&mut caller, response, now),
+            vec![SrtCallerResult::Rejected(RejectReason::Unauthorized)]
+        );
+
+        assert!(!caller.is_connected());
+    }
+
+    #[test]
+    fn lost_packet_is_retransmitted_to_listener() {
+        let now = Instant::now();
+        let (mut caller, mut listener, _) = handshake("live/key", now);
+        let response = listener.accept_request(caller_address(), now).unwrap();
+        to_caller(&mut caller, response, now);
+
+        let first = caller.send(Bytes::from(vec![1]), now).unwrap();
+        let _lost = caller.send(Bytes::from(vec![2]), now).unwrap();
+        let third = caller.send(Bytes::from(vec![3]), now).unwrap();
+        to_listener(&mut listener, vec![first], now);
+
+        // The gap causes a NAK, which the caller answers with the lost packet
+        let nak = to_listener(&mut listener, vec![third], now);
+        let retransmitted = to_caller(&mut caller, nak, now);
+        let received = to_listener(&mut listener, retransmitted, now)
+            .into_iter()
+            .filter_map(|result| match result {
+                SrtListenerResult::DataReceived { data, .. } => Some(data[0]),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(received, vec![2, 3]);
+    }
+
+    #[test]
+    fn handshake_is_resent_until_timeout() {
+        let now = Instant::now();
+        let (mut caller, _) = SrtCaller::new(SrtConfig::new(), now).unwrap();
+
+        assert!(caller.poll(now + Duration::from_millis(100)).is_empty());
+        assert_eq!(caller.poll(now + Duration::from_millis(250)).len(), 1);
+        assert_eq!(
+            caller.poll(now + Duration::from_secs(3)),
+            vec![SrtCallerResult::Disconnected]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+/// Configuration options for SRT callers and listeners
+#[derive(Debug, Clone)]
+pub struct SrtConfig {
+    /// How long to wait for a lost packet to be retransmitted before giving up on it and
+    /// delivering the packets after it.  Both sides use the larger of their two latencies.
+    pub latency: Duration,
+
+    /// How long without receiving anything from the peer before the connection is closed
+    pub peer_idle_timeout: Duration,
+
+    /// How long a caller waits for the listener to complete the handshake
+    pub connect_timeout: Duration,
+
+    /// The largest payload a single packet can carry.  The default fits 7 MPEG-TS packets,
+    /// which is what most SRT senders put in each packet.
+    pub max_payload_size: usize,
+
+    /// The most packets that can be in flight at once
+    pub max_flow_window: u32,
+
+    /// The stream id a caller sends to the listener, which tells the listener which stream it
+    /// wants to publish or play
+    pub stream_id: Option<String>,
+}
+
+impl SrtConfig {
+    /// Creates a configuration with the same defaults as libsrt's live mode, a 120 millisecond
+    /// latency and a 5 second idle timeout
+    pub fn new() -> SrtConfig {
+        SrtConfig {
+            latency: Duration::from_millis(120),
+            peer_idle_timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(3),
+            max_payload_size: 1316,
+            max_flow_window: 8192,
+            stream_id: None,
+        }
+    }
+}
+
+impl Default for SrtConfig {
+    fn default() -> Self {
+        SrtConfig::new()
+    }
+}
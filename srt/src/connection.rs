@@ -0,0 +1,671 @@
+use bytes::Bytes;
+use config::SrtConfig;
+use errors::SrtError;
+use packet::{Ack, ControlInfo, ControlPacket, DataPacket, LossRange, PacketPosition, SrtPacket};
+use seq::SequenceNumber;
+use std::cmp;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const ACK_INTERVAL: Duration = Duration::from_millis(10);
+const MIN_NAK_INTERVAL: Duration = Duration::from_millis(20);
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+const INITIAL_RTT: Duration = Duration::from_millis(100);
+const INITIAL_RTT_VARIANCE: Duration = Duration::from_millis(50);
+const MAX_OUTSTANDING_ACKS: usize = 64;
+const MAX_MESSAGE_NUMBER: u32 = 0x03ff_ffff;
+
+/// What happened as a result of the connection handling a packet or being polled
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) enum ConnectionOutput {
+    /// A datagram to send to the peer
+    Packet(Bytes),
+
+    /// A payload received from the peer, in the order it was sent
+    Data(Bytes),
+
+    /// The peer shut the connection down or stopped responding
+    Closed,
+}
+
+/// The identifiers both sides agreed on during the handshake
+pub(crate) struct ConnectionParameters {
+    pub local_socket_id: u32,
+    pub peer_socket_id: u32,
+    pub initial_sequence_number: SequenceNumber,
+    pub latency: Duration,
+}
+
+enum ReceiveSlot {
+    Missing { since: Instant },
+    Received(Bytes),
+    Dropped,
+}
+
+struct SentPacket {
+    packet: DataPacket,
+    sent_at: Instant,
+}
+
+/// An established SRT connection in live mode, which both callers and listeners use once the
+/// handshake is complete.
+///
+/// Payloads are delivered as soon as every packet before them has been received.  When a gap
+/// is detected, a NAK is sent immediately and then periodically until the missing packets are
+/// retransmitted, and once a gap is older than the latency it's given up on so the stream
+/// keeps flowing.
+pub(crate) struct Connection {
+    local_socket_id: u32,
+    peer_socket_id: u32,
+    started_at: Instant,
+    latency: Duration,
+    peer_idle_timeout: Duration,
+    max_payload_size: usize,
+    max_flow_window: usize,
+    last_received_at: Instant,
+    last_sent_at: Instant,
+    is_closed: bool,
+    rtt: Duration,
+    rtt_variance: Duration,
+
+    next_expected: SequenceNumber,
+    receive_window: VecDeque<ReceiveSlot>,
+    last_acknowledged: SequenceNumber,
+    next_ack_number: u32,
+    next_ack_at: Instant,
+    outstanding_acks: VecDeque<(u32, Instant)>,
+    next_nak_at: Instant,
+
+    next_sequence_number: SequenceNumber,
+    next_message_number: u32,
+    send_buffer: VecDeque<SentPacket>,
+}
+
+impl Connection {
+    pub fn new(parameters: ConnectionParameters, config: &SrtConfig, now: Instant) -> Connection {
+        Connection {
+            local_socket_id: parameters.local_socket_id,
+            peer_socket_id: parameters.peer_socket_id,
+            started_at: now,
+            latency: parameters.latency,
+            peer_idle_timeout: config.peer_idle_timeout,
+            max_payload_size: config.max_payload_size,
+            max_flow_window: config.max_flow_window as usize,
+            last_received_at: now,
+            last_sent_at: now,
+            is_closed: false,
+            rtt: INITIAL_RTT,
+            rtt_variance: INITIAL_RTT_VARIANCE,
+
+            next_expected: parameters.initial_sequence_number,
+            receive_window: VecDeque::new(),
+            last_acknowledged: parameters.initial_sequence_number,
+            next_ack_number: 1,
+            next_ack_at: now + ACK_INTERVAL,
+            outstanding_acks: VecDeque::new(),
+            next_nak_at: now + MIN_NAK_INTERVAL,
+
+            next_sequence_number: parameters.initial_sequence_number,
+            next_message_number: 1,
+            send_buffer: VecDeque::new(),
+        }
+    }
+
+    pub fn local_socket_id(&self) -> u32 {
+        self.local_socket_id
+    }
+
+    pub fn peer_socket_id(&self) -> u32 {
+        self.peer_socket_id
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    pub fn handle_packet(&mut self, packet: SrtPacket, now: Instant) -> Vec<ConnectionOutput> {
+        let mut outputs = Vec::new();
+        if self.is_closed {
+            return outputs;
+        }
+
+        self.last_received_at = now;
+        match packet {
+            SrtPacket::Data(packet) => self.handle_data(packet, now, &mut outputs),
+            SrtPacket::Control(packet) => match packet.info {
+                ControlInfo::Ack(ack) => self.handle_ack(ack, now, &mut outputs),
+                ControlInfo::AckAck { ack_number } => self.handle_ack_ack(ack_number, now),
+                ControlInfo::Nak(losses) => self.handle_nak(&losses, now, &mut outputs),
+                ControlInfo::DropRequest { first, last, .. } => {
+                    self.handle_drop_request(first, last, &mut outputs)
+                }
+
+                ControlInfo::Shutdown => {
+                    self.is_closed = true;
+                    outputs.push(ConnectionOutput::Closed);
+                }
+
+                // Handshakes that are resent after the connection is established are handled
+                // by the caller or listener
+                ControlInfo::Handshake(_)
+                | ControlInfo::KeepAlive
+                | ControlInfo::Unknown { .. } => (),
+            },
+        }
+
+        outputs
+    }
+
+    /// Sends acknowledgements, loss reports, and keep alives that are due, gives up on lost
+    /// packets that are too late, and closes the connection if the peer has gone quiet
+    pub fn poll(&mut self, now: Instant) -> Vec<ConnectionOutput> {
+        let mut outputs = Vec::new();
+        if self.is_closed {
+            return outputs;
+        }
+
+        if now.duration_since(self.last_received_at) > self.peer_idle_timeout {
+            self.is_closed = true;
+            outputs.push(ConnectionOutput::Closed);
+            return outputs;
+        }
+
+        for slot in self.receive_window.iter_mut() {
+            match *slot {
+                ReceiveSlot::Missing { since } if now.duration_since(since) >= self.latency => {
+                    *slot = ReceiveSlot::Dropped;
+                }
+                _ => (),
+            }
+        }
+
+        self.deliver_received(&mut outputs);
+
+        if now >= self.next_ack_at {
+            self.next_ack_at = now + ACK_INTERVAL;
+            if self.next_expected != self.last_acknowledged {
+                self.send_ack(now, &mut outputs);
+            }
+        }
+
+        if now >= self.next_nak_at {
+            self.next_nak_at = now + self.nak_interval();
+            let losses = self.loss_ranges();
+            if !losses.is_empty() {
+                self.send_control(ControlInfo::Nak(losses), now, &mut outputs);
+            }
+        }
+
+        // Packets the receiver would have given up on by now aren't worth retransmitting
+        let keep_for = self.latency + self.rtt;
+        while let Some(sent) = self.send_buffer.front() {
+            if now.duration_since(sent.sent_at) <= keep_for {
+                break;
+            }
+
+            self.send_buffer.pop_front();
+        }
+
+        if now.duration_since(self.last_sent_at) >= KEEP_ALIVE_INTERVAL {
+            self.send_control(ControlInfo::KeepAlive, now, &mut outputs);
+        }
+
+        outputs
+    }
+
+    pub fn send(&mut self, payload: Bytes, now: Instant) -> Result<Bytes, SrtError> {
+        if self.is_closed {
+            return Err(SrtError::NotConnected);
+        }
+
+        if payload.len() > self.max_payload_size {
+            return Err(SrtError::PayloadTooLarge {
+                size: payload.len(),
+                max_size: self.max_payload_size,
+            });
+        }
+
+        let packet = DataPacket {
+            sequence_number: self.next_sequence_number,
+            position: PacketPosition::Only,
+            in_order: false,
+            encryption_key: 0,
+            is_retransmitted: false,
+            message_number: self.next_message_number,
+            timestamp: self.timestamp(now),
+            destination_socket_id: self.peer_socket_id,
+            payload,
+        };
+
+        self.next_sequence_number = self.next_sequence_number.next();
+        self.next_message_number = match self.next_message_number {
+            MAX_MESSAGE_NUMBER => 1,
+            x => x + 1,
+        };
+
+        let bytes = SrtPacket::Data(packet.clone()).serialize();
+        self.send_buffer.push_back(SentPacket {
+            packet,
+            sent_at: now,
+        });
+
+        if self.send_buffer.len() > self.max_flow_window {
+            self.send_buffer.pop_front();
+        }
+
+        self.last_sent_at = now;
+        Ok(bytes)
+    }
+
+    /// Closes the connection, returning the shutdown packet to send to the peer
+    pub fn close(&mut self, now: Instant) -> Option<Bytes> {
+        if self.is_closed {
+            return None;
+        }
+
+        self.is_closed = true;
+        let packet = self.control_packet(ControlInfo::Shutdown, now);
+        Some(packet.serialize())
+    }
+
+    fn handle_data(
+        &mut self,
+        packet: DataPacket,
+        now: Instant,
+        outputs: &mut Vec<ConnectionOutput>,
+    ) {
+        let offset = packet.sequence_number.offset_from(self.next_expected);
+        if offset < 0 || offset as usize >= self.max_flow_window {
+            // Already delivered or given up on, or too far ahead to be genuine
+            return;
+        }
+
+        let offset = offset as usize;
+        if offset >= self.receive_window.len() {
+            let first_missing = self.receive_window.len();
+            while self.receive_window.len() < offset {
+                self.receive_window
+                    .push_back(ReceiveSlot::Missing { since: now });
+            }
+
+            self.receive_window
+                .push_back(ReceiveSlot::Received(packet.payload));
+
+            if offset > first_missing {
+                let losses = vec![LossRange {
+                    first: self.next_expected + first_missing as u32,
+                    last: self.next_expected + (offset as u32 - 1),
+                }];
+
+                self.send_control(ControlInfo::Nak(losses), now, outputs);
+            }
+        } else if let ReceiveSlot::Missing { .. } = self.receive_window[offset] {
+            self.receive_window[offset] = ReceiveSlot::Received(packet.payload);
+        }
+
+        self.deliver_received(outputs);
+    }
+
+    fn handle_ack(&mut self, ack: Ack, now: Instant, outputs: &mut Vec<ConnectionOutput>) {
+        while let Some(sent) = self.send_buffer.front() {
+            if sent
+                .packet
+                .sequence_number
+                .offset_from(ack.next_sequence_number)
+                >= 0
+            {
+                break;
+            }
+
+            self.send_buffer.pop_front();
+        }
+
+        if ack.rtt > 0 {
+            self.rtt = Duration::from_micros(ack.rtt as u64);
+            self.rtt_variance = Duration::from_micros(ack.rtt_variance as u64);
+        }
+
+        let ack_ack = ControlInfo::AckAck {
+            ack_number: ack.ack_number,
+        };
+
+        self.send_control(ack_ack, now, outputs);
+    }
+
+    fn handle_ack_ack(&mut self, ack_number: u32, now: Instant) {
+        let sent_at = match self
+            .outstanding_acks
+            .iter()
+            .position(|&(number, _)| number == ack_number)
+        {
+            Some(index) => self.outstanding_acks[index].1,
+            None => return,
+        };
+
+        // Acknowledgements sent before this one will never be acknowledged
+        while let Some((number, _)) = self.outstanding_acks.pop_front() {
+            if number == ack_number {
+                break;
+            }
+        }
+
+        let sample = now.duration_since(sent_at);
+        let difference = sample.abs_diff(self.rtt);
+        self.rtt_variance = (self.rtt_variance * 3 + difference) / 4;
+        self.rtt = (self.rtt * 7 + sample) / 8;
+    }
+
+    fn handle_nak(
+        &mut self,
+        losses: &[LossRange],
+        now: Instant,
+        outputs: &mut Vec<ConnectionOutput>,
+    ) {
+        let first_buffered = match self.send_buffer.front() {
+            Some(sent) => sent.packet.sequence_number,
+            None => return,
+        };
+
+        for range in losses {
+            let first = cmp::max(range.first.offset_from(first_buffered), 0);
+            let last = range.last.offset_from(first_buffered);
+            if last < first {
+                continue;
+            }
+
+            for index in first as usize..=last as usize {
+                let sent = match self.send_buffer.get(index) {
+                    Some(sent) => sent,
+                    None => break,
+                };
+
+                let mut packet = sent.packet.clone();
+                packet.is_retransmitted = true;
+                outputs.push(ConnectionOutput::Packet(
+                    SrtPacket::Data(packet).serialize(),
+                ));
+                self.last_sent_at = now;
+            }
+        }
+    }
+
+    fn handle_drop_request(
+        &mut self,
+        first: SequenceNumber,
+        last: SequenceNumber,
+        outputs: &mut Vec<ConnectionOutput>,
+    ) {
+        let first = cmp::max(first.offset_from(self.next_expected), 0);
+        let last = last.offset_from(self.next_expected);
+        if last < first {
+            return;
+        }
+
+        for index in first as usize..=last as usize {
+            match self.receive_window.get_mut(index) {
+                Some(slot) => {
+                    if let ReceiveSlot::Missing { .. } = *slot {
+                        *slot = ReceiveSlot::Dropped;
+                    }
+                }
+
+                None => break,
+            }
+        }
+
+        self.deliver_received(outputs);
+    }
+
+    fn deliver_received(&mut self, outputs: &mut Vec<ConnectionOutput>) {
+        while let Some(&ReceiveSlot::Received(_)) | Some(&ReceiveSlot::Dropped) =
+            self.receive_window.front()
+        {
+            if let Some(ReceiveSlot::Received(payload)) = self.receive_window.pop_front() {
+                outputs.push(ConnectionOutput::Data(payload));
+            }
+
+            self.next_expected = self.next_expected.next();
+        }
+    }
+
+    fn send_ack(&mut self, now: Instant, outputs: &mut Vec<ConnectionOutput>) {
+        let ack_number = self.next_ack_number;
+        self.next_ack_number = self.next_ack_number.wrapping_add(1);
+        self.last_acknowledged = self.next_expected;
+
+        self.outstanding_acks.push_back((ack_number, now));
+        if self.outstanding_acks.len() > MAX_OUTSTANDING_ACKS {
+            self.outstanding_acks.pop_front();
+        }
+
+        let ack = Ack {
+            ack_number,
+            next_sequence_number: self.next_expected,
+            rtt: self.rtt.as_micros() as u32,
+            rtt_variance: self.rtt_variance.as_micros() as u32,
+            available_buffer: self
+                .max_flow_window
+                .saturating_sub(self.receive_window.len()) as u32,
+        };
+
+        self.send_control(ControlInfo::Ack(ack), now, outputs);
+    }
+
+    fn loss_ranges(&self) -> Vec<LossRange> {
+        let mut ranges: Vec<LossRange> = Vec::new();
+        for (index, slot) in self.receive_window.iter().enumerate() {
+            if let ReceiveSlot::Missing { .. } = *slot {
+                let sequence_number = self.next_expected + index as u32;
+                match ranges.last_mut() {
+                    Some(range) if range.last.next() == sequence_number => {
+                        range.last = sequence_number
+                    }
+
+                    _ => ranges.push(LossRange {
+                        first: sequence_number,
+                        last: sequence_number,
+                    }),
+                }
+            }
+        }
+
+        ranges
+    }
+
+    fn nak_interval(&self) -> Duration {
+        cmp::max(self.rtt + self.rtt_variance * 4, MIN_NAK_INTERVAL)
+    }
+
+    fn send_control(
+        &mut self,
+        info: ControlInfo,
+        now: Instant,
+        outputs: &mut Vec<ConnectionOutput>,
+    ) {
+        let packet = self.control_packet(info, now);
+        outputs.push(ConnectionOutput::Packet(packet.serialize()));
+        self.last_sent_at = now;
+    }
+
+    fn control_packet(&self, info: ControlInfo, now: Instant) -> SrtPacket {
+        SrtPacket::Control(ControlPacket {
+            timestamp: self.timestamp(now),
+            destination_socket_id: self.peer_socket_id,
+            info,
+        })
+    }
+
+    fn timestamp(&self, now: Instant) -> u32 {
+        now.duration_since(self.started_at).as_micros() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(now: Instant) -> Connection {
+        let parameters = ConnectionParameters {
+            local_socket_id: 1,
+            peer_socket_id: 2,
+            initial_sequence_number: SequenceNumber::new(100),
+            latency: Duration::from_millis(120),
+        };
+
+        Connection::new(parameters, &SrtConfig::new(), now)
+    }
+
+    fn data(sequence_number: u32, payload: u8) -> SrtPacket {
+        SrtPacket::Data(DataPacket {
+            sequence_number: SequenceNumber::new(sequence_number),
+            position: PacketPosition::Only,
+            in_order: false,
+            encryption_key: 0,
+            is_retransmitted: false,
+            message_number: 1,
+            timestamp: 0,
+            destination_socket_id: 1,
+            payload: Bytes::from(vec![payload]),
+        })
+    }
+
+    fn received(outputs: &[ConnectionOutput]) -> Vec<u8> {
+        outputs
+            .iter()
+            .filter_map(|output| match *output {
+                ConnectionOutput::Data(ref data) => Some(data[0]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn control(outputs: &[ConnectionOutput]) -> Vec<ControlInfo> {
+        outputs
+            .iter()
+            .filter_map(|output| match *output {
+                ConnectionOutput::Packet(ref bytes) => match SrtPacket::parse(bytes).unwrap() {
+                    SrtPacket::Control(packet) => Some(packet.info),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gap_is_reported_and_filled_by_retransmission() {
+        let now = Instant::now();
+        let mut connection = connection(now);
+
+        assert_eq!(
+            received(&connection.handle_packet(data(100, 0), now)),
+            vec![0]
+        );
+
+        let outputs = connection.handle_packet(data(103, 3), now);
+        assert!(received(&outputs).is_empty());
+        assert_eq!(
+            control(&outputs),
+            vec![ControlInfo::Nak(vec![LossRange {
+                first: SequenceNumber::new(101),
+                last: SequenceNumber::new(102),
+            }])]
+        );
+
+        assert!(received(&connection.handle_packet(data(102, 2), now)).is_empty());
+        assert_eq!(
+            received(&connection.handle_packet(data(101, 1), now)),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn gap_older_than_latency_is_skipped() {
+        let now = Instant::now();
+        let mut connection = connection(now);
+        connection.handle_packet(data(101, 1), now);
+
+        assert!(received(&connection.poll(now + Duration::from_millis(119))).is_empty());
+        assert_eq!(
+            received(&connection.poll(now + Duration::from_millis(120))),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn nak_retransmits_buffered_packets() {
+        let now = Instant::now();
+        let mut connection = connection(now);
+        for payload in 0..3 {
+            connection.send(Bytes::from(vec![payload]), now).unwrap();
+        }
+
+        let nak = SrtPacket::Control(ControlPacket {
+            timestamp: 0,
+            destination_socket_id: 1,
+            info: ControlInfo::Nak(vec![LossRange {
+                first: SequenceNumber::new(101),
+                last: SequenceNumber::new(101),
+            }]),
+        });
+
+        let outputs = connection.handle_packet(nak, now);
+
+        assert_eq!(outputs.len(), 1);
+        match outputs[0] {
+            ConnectionOutput::Packet(ref bytes) => match SrtPacket::parse(bytes).unwrap() {
+                SrtPacket::Data(ref packet) => {
+                    assert_eq!(packet.sequence_number, SequenceNumber::new(101));
+                    assert!(packet.is_retransmitted);
+                }
+                x => panic!("Expected data packet, instead got {:?}", x),
+            },
+            ref x => panic!("Expected packet, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn acknowledged_packets_are_not_retransmitted() {
+        let now = Instant::now();
+        let mut connection = connection(now);
+        connection.send(Bytes::from(vec![0]), now).unwrap();
+
+        let ack = SrtPacket::Control(ControlPacket {
+            timestamp: 0,
+            destination_socket_id: 1,
+            info: ControlInfo::Ack(Ack {
+                ack_number: 1,
+                next_sequence_number: SequenceNumber::new(101),
+                rtt: 0,
+                rtt_variance: 0,
+                available_buffer: 8192,
+            }),
+        });
+
+        assert_eq!(
+            control(&connection.handle_packet(ack, now)),
+            vec![ControlInfo::AckAck { ack_number: 1 }]
+        );
+
+        let nak = SrtPacket::Control(ControlPacket {
+            timestamp: 0,
+            destination_socket_id: 1,
+            info: ControlInfo::Nak(vec![LossRange {
+                first: SequenceNumber::new(100),
+                last: SequenceNumber::new(100),
+            }]),
+        });
+
+        assert!(connection.handle_packet(nak, now).is_empty());
+    }
+
+    #[test]
+    fn connection_closes_when_peer_is_idle() {
+        let now = Instant::now();
+        let mut connection = connection(now);
+
+        let outputs = connection.poll(now + Duration::from_secs(6));
+
+        assert_eq!(outputs, vec![ConnectionOutput::Closed]);
+    }
+}
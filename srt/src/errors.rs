@@ -0,0 +1,41 @@
+use thiserror::Error;
+
+/// Errors that can occur while reading an SRT packet
+#[derive(Debug, Error)]
+pub enum SrtPacketError {
+    /// The datagram is smaller than the packet it claims to contain
+    #[error("The packet is truncated")]
+    Truncated,
+
+    /// A handshake extension claims to be larger than the handshake
+    #[error("Handshake extension {extension_type} has an invalid length")]
+    InvalidExtensionLength { extension_type: u16 },
+
+    /// The stream id in a handshake is not valid UTF-8
+    #[error("The handshake's stream id is not valid UTF-8")]
+    InvalidStreamId,
+}
+
+/// Errors that can occur on an SRT connection
+#[derive(Debug, Error)]
+pub enum SrtError {
+    /// A packet received from the peer could not be read
+    #[error("Received an invalid packet: {0}")]
+    InvalidPacket(#[from] SrtPacketError),
+
+    /// The payload is larger than fits in a single packet
+    #[error("The payload is {size} bytes, which is larger than the maximum of {max_size}")]
+    PayloadTooLarge { size: usize, max_size: usize },
+
+    /// The stream id is larger than SRT allows
+    #[error("The stream id is {size} bytes, which is larger than the maximum of {max_size}")]
+    StreamIdTooLong { size: usize, max_size: usize },
+
+    /// Data can only be sent once the connection has been established
+    #[error("The connection is not established")]
+    NotConnected,
+
+    /// There is no connection request from the specified peer to accept or reject
+    #[error("There is no outstanding connection request from the peer")]
+    NoOutstandingRequest,
+}
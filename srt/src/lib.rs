@@ -0,0 +1,83 @@
+//! This crate implements the live mode of the SRT (Secure Reliable Transport) protocol, which
+//! encoders and contribution links commonly use to send MPEG transport streams over the
+//! internet.  It lets a server accept SRT feeds next to RTMP ones.
+//!
+//! `SrtListener` accepts connections from SRT callers on a UDP socket, and `SrtCaller` connects
+//! to a listener.  Both take care of the handshake, acknowledging received packets,
+//! retransmitting lost ones, and giving up on packets that are too late to be useful.  Like the
+//! rest of this repository they do no networking themselves: the application passes in the
+//! datagrams it receives and sends the ones they return.  Encryption is not supported, and
+//! callers that ask for it are rejected.
+//!
+//! The payloads received over SRT are usually an MPEG transport stream, which the
+//! `ts::TsDemuxer` turns into RTMP audio and video payloads that can be published into a
+//! `StreamHub`.  The caller's stream id (parsed by `SrtStreamId`) says which stream it is
+//! publishing.  For output, `ts::TsStreamSink` muxes a published RTMP stream back into a
+//! transport stream, paced for sending over UDP or with `SrtCaller`.
+//!
+//! # Examples
+//! ```
+//! extern crate rml_rtmp;
+//! extern crate rml_srt;
+//!
+//! use rml_rtmp::hub::StreamHub;
+//! use rml_srt::ts::TsDemuxer;
+//! use rml_srt::{SrtConfig, SrtListener, SrtListenerResult, SrtStreamId};
+//! use std::time::Instant;
+//!
+//! # fn main() {
+//! let mut listener = SrtListener::new(SrtConfig::new());
+//! let mut hub = StreamHub::new();
+//! let mut demuxer = TsDemuxer::new();
+//! let connection_id = 1;
+//!
+//! # let peer = "127.0.0.1:9000".parse().unwrap();
+//! # let datagram = [0_u8; 16];
+//! let results = listener.handle_datagram(peer, &datagram, Instant::now()).unwrap();
+//! for result in results {
+//!     match result {
+//!         SrtListenerResult::ConnectionRequested { peer, stream_id } => {
+//!             let stream_id = SrtStreamId::parse(&stream_id.unwrap_or_default());
+//!             if let Some((app_name, stream_key)) = stream_id.app_and_stream_key() {
+//!                 hub.join_as_publisher(connection_id, &app_name, &stream_key).unwrap();
+//!                 listener.accept_request(peer, Instant::now()).unwrap();
+//!             }
+//!         }
+//!
+//!         SrtListenerResult::DataReceived { data, .. } => {
+//!             for media in demuxer.push(&data) {
+//!                 let _hub_results = media.publish(&mut hub, connection_id).unwrap();
+//!             }
+//!         }
+//!
+//!         _ => (),
+//!     }
+//! }
+//! # }
+//! ```
+
+extern crate bytes;
+extern crate rand;
+extern crate rml_flv;
+extern crate rml_rtmp;
+extern crate thiserror;
+
+mod caller;
+mod config;
+mod connection;
+mod errors;
+mod listener;
+mod reject;
+mod seq;
+mod stream_id;
+
+pub mod packet;
+pub mod ts;
+
+pub use caller::{SrtCaller, SrtCallerResult};
+pub use config::SrtConfig;
+pub use errors::{SrtError, SrtPacketError};
+pub use listener::{SrtListener, SrtListenerResult};
+pub use reject::RejectReason;
+pub use seq::SequenceNumber;
+pub use stream_id::{SrtStreamId, StreamMode};
@@ -0,0 +1,465 @@
+use bytes::Bytes;
+use config::SrtConfig;
+use connection::{Connection, ConnectionOutput, ConnectionParameters};
+use errors::SrtError;
+use packet::{
+    ControlInfo, ControlPacket, Handshake, HandshakeExtension, HandshakeType, SrtOptions,
+    SrtPacket, HANDSHAKE_MAGIC,
+};
+use rand::{self, Rng};
+use reject::RejectReason;
+use std::cmp;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+pub(crate) const SRT_VERSION: u32 = 0x0001_0500;
+pub(crate) const MAX_TRANSMISSION_UNIT: u32 = 1500;
+
+pub(crate) const SUPPORTED_FLAGS: u32 = SrtOptions::TSBPD_SEND
+    | SrtOptions::TSBPD_RECEIVE
+    | SrtOptions::TOO_LATE_PACKET_DROP
+    | SrtOptions::PERIODIC_NAK
+    | SrtOptions::RETRANSMIT_FLAG;
+
+/// What happened as a result of the listener handling a datagram, being polled, or the
+/// application acting on a connection
+#[derive(PartialEq, Debug, Clone)]
+pub enum SrtListenerResult {
+    /// A datagram that needs to be sent to the peer
+    OutboundPacket { peer: SocketAddr, data: Bytes },
+
+    /// A caller completed the handshake and is waiting for the application to call either
+    /// `accept_request()` or `reject_request()`.  The stream id usually identifies the stream
+    /// it wants to publish or play, and can be parsed with `SrtStreamId`.
+    ConnectionRequested {
+        peer: SocketAddr,
+        stream_id: Option<String>,
+    },
+
+    /// A payload the peer sent, in the order it was sent.  Contribution feeds send MPEG-TS
+    /// packets, which a `TsDemuxer` can turn into audio and video for the `StreamHub`.
+    DataReceived { peer: SocketAddr, data: Bytes },
+
+    /// The peer shut the connection down or stopped responding
+    Disconnected { peer: SocketAddr },
+}
+
+enum Peer {
+    Pending {
+        handshake: Handshake,
+        requested_at: Instant,
+    },
+
+    Connected {
+        connection: Box<Connection>,
+        response: Bytes,
+    },
+}
+
+/// Accepts SRT connections from callers on a single UDP socket.
+///
+/// Every datagram the socket receives is passed to `handle_datagram()` along with the address
+/// it came from, and the listener takes care of the handshake with each caller and the live
+/// mode retransmission of lost packets once they're connected.  Once a caller completes the
+/// handshake the application decides whether to accept it, usually based on its stream id.
+///
+/// Like the rest of this repository the listener does no I/O itself.  The application sends
+/// the outbound packets it returns, and calls `poll()` at least every 10 milliseconds so
+/// acknowledgements and loss reports go out on time.
+///
+/// # Examples
+/// ```
+/// use rml_srt::{SrtConfig, SrtListener, SrtListenerResult};
+/// use std::time::Instant;
+///
+/// let mut listener = SrtListener::new(SrtConfig::new());
+/// # let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+/// # socket.set_nonblocking(true).unwrap();
+/// let mut buffer = [0_u8; 1500];
+/// # let mut results = Vec::new();
+/// if let Ok((length, peer)) = socket.recv_from(&mut buffer) {
+///     results = listener.handle_datagram(peer, &buffer[..length], Instant::now()).unwrap();
+/// }
+///
+/// for result in results {
+///     match result {
+///         SrtListenerResult::OutboundPacket { peer, data } => {
+///             socket.send_to(&data, peer).unwrap();
+///         }
+///
+///         SrtListenerResult::ConnectionRequested { peer, stream_id } => {
+///             // Check the stream id, then accept_request() or reject_request()
+///         }
+///
+///         _ => (),
+///     }
+/// }
+/// ```
+pub struct SrtListener {
+    config: SrtConfig,
+    socket_id: u32,
+    cookie_secret: u64,
+    peers: HashMap<SocketAddr, Peer>,
+}
+
+impl SrtListener {
+    /// Creates a listener without any connections
+    pub fn new(config: SrtConfig) -> SrtListener {
+        let mut rng = rand::thread_rng();
+        SrtListener {
+            config,
+            socket_id: new_socket_id(),
+            cookie_secret: rng.gen(),
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Handles a datagram received from the specified address
+    pub fn handle_datagram(
+        &mut self,
+        peer: SocketAddr,
+        datagram: &[u8],
+        now: Instant,
+    ) -> Result<Vec<SrtListenerResult>, SrtError> {
+        let packet = SrtPacket::parse(datagram)?;
+        if let SrtPacket::Control(ControlPacket {
+            info: ControlInfo::Handshake(ref handshake),
+            ..
+        }) = packet
+        {
+            if packet.destination_socket_id() == 0 {
+                return Ok(self.handle_handshake(peer, handshake, now));
+            }
+        }
+
+        let outputs = match self.peers.get_mut(&peer) {
+            Some(Peer::Connected { connection, .. })
+                if connection.local_socket_id() == packet.destination_socket_id() =>
+            {
+                connection.handle_packet(packet, now)
+            }
+
+            _ => return Ok(Vec::new()),
+        };
+
+        Ok(self.convert_outputs(peer, outputs))
+    }
+
+    /// Accepts the connection from a peer that raised a `ConnectionRequested` result
+    pub fn accept_request(
+        &mut self,
+        peer: SocketAddr,
+        now: Instant,
+    ) -> Result<Vec<SrtListenerResult>, SrtError> {
+        let handshake = match self.peers.remove(&peer) {
+            Some(Peer::Pending { handshake, .. }) => handshake,
+            Some(connected) => {
+                self.peers.insert(peer, connected);
+                return Err(SrtError::NoOutstandingRequest);
+            }
+
+            None => return Err(SrtError::NoOutstandingRequest),
+        };
+
+        // Both directions use the largest latency either side asked for
+        let mut latency_ms = self.config.latency.as_millis() as u16;
+        if let Some(options) = handshake.options() {
+            latency_ms = cmp::max(latency_ms, options.sender_latency);
+            latency_ms = cmp::max(latency_ms, options.receiver_latency);
+        }
+
+        let parameters = ConnectionParameters {
+            local_socket_id: new_socket_id(),
+            peer_socket_id: handshake.socket_id,
+            initial_sequence_number: handshake.initial_sequence_number,
+            latency: Duration::from_millis(latency_ms as u64),
+        };
+
+        let response = Handshake {
+            version: 5,
+            encryption_field: 0,
+            extension_field: Handshake::EXTENSION_FLAG_OPTIONS,
+            initial_sequence_number: handshake.initial_sequence_number,
+            max_transmission_unit: cmp::min(handshake.max_transmission_unit, MAX_TRANSMISSION_UNIT),
+            max_flow_window: self.config.max_flow_window,
+            handshake_type: HandshakeType::Conclusion,
+            socket_id: parameters.local_socket_id,
+            syn_cookie: handshake.syn_cookie,
+            peer_ip_address: [0; 16],
+            extensions: vec![HandshakeExtension::Response(SrtOptions {
+                version: SRT_VERSION,
+                flags: SUPPORTED_FLAGS,
+                receiver_latency: latency_ms,
+                sender_latency: latency_ms,
+            })],
+        };
+
+        let response = handshake_packet(response, handshake.socket_id);
+        let connection = Box::new(Connection::new(parameters, &self.config, now));
+        self.peers.insert(
+            peer,
+            Peer::Connected {
+                connection,
+                response: response.clone(),
+            },
+        );
+
+        Ok(vec![SrtListenerResult::OutboundPacket {
+            peer,
+            data: response,
+        }])
+    }
+
+    /// Rejects the connection from a peer that raised a `ConnectionRequested` result
+    pub fn reject_request(
+        &mut self,
+        peer: SocketAddr,
+        reason: RejectReason,
+    ) -> Result<Vec<SrtListenerResult>, SrtError> {
+        let handshake = match self.peers.remove(&peer) {
+            Some(Peer::Pending { handshake, .. }) => handshake,
+            Some(connected) => {
+                self.peers.insert(peer, connected);
+                return Err(SrtError::NoOutstandingRequest);
+            }
+
+            None => return Err(SrtError::NoOutstandingRequest),
+        };
+
+        Ok(vec![self.rejection(peer, &handshake, reason)])
+    }
+
+    /// Sends a payload to a connected peer, such as MPEG-TS packets to a caller that is
+    /// playing a stream
+    pub fn send(
+        &mut self,
+        peer: SocketAddr,
+        payload: Bytes,
+        now: Instant,
+    ) -> Result<SrtListenerResult, SrtError> {
+        match self.peers.get_mut(&peer) {
+            Some(Peer::Connected { connection, .. }) => {
+                let data = connection.send(payload, now)?;
+                Ok(SrtListenerResult::OutboundPacket { peer, data })
+            }
+
+            _ => Err(SrtError::NotConnected),
+        }
+    }
+
+    /// Closes the connection with a peer
+    pub fn close(&mut self, peer: SocketAddr, now: Instant) -> Vec<SrtListenerResult> {
+        match self.peers.remove(&peer) {
+            Some(Peer::Connected { mut connection, .. }) => connection
+                .close(now)
+                .map(|data| SrtListenerResult::OutboundPacket { peer, data })
+                .into_iter()
+                .collect(),
+
+            _ => Vec::new(),
+        }
+    }
+
+    /// Performs the work each connection needs done on a timer, and drops connection requests
+    /// the application never responded to
+    pub fn poll(&mut self, now: Instant) -> Vec<SrtListenerResult> {
+        let connect_timeout = self.config.connect_timeout;
+        self.peers.retain(|_, peer| match *peer {
+            Peer::Pending { requested_at, .. } => {
+                now.duration_since(requested_at) < connect_timeout
+            }
+            Peer::Connected { .. } => true,
+        });
+
+        let mut outputs = Vec::new();
+        for (address, peer) in self.peers.iter_mut() {
+            if let Peer::Connected { connection, .. } = peer {
+                outputs.push((*address, connection.poll(now)));
+            }
+        }
+
+        outputs
+            .into_iter()
+            .flat_map(|(peer, outputs)| self.convert_outputs(peer, outputs))
+            .collect()
+    }
+
+    /// The number of peers that have been accepted and are still connected
+    pub fn connection_count(&self) -> usize {
+        self.peers
+            .values()
+            .filter(|peer| matches!(peer, Peer::Connected { .. }))
+            .count()
+    }
+
+    fn handle_handshake(
+        &mut self,
+        peer: SocketAddr,
+        handshake: &Handshake,
+        now: Instant,
+    ) -> Vec<SrtListenerResult> {
+        let cookie = self.cookie(peer);
+        match handshake.handshake_type {
+            HandshakeType::Induction => {
+                let response = Handshake {
+                    version: 5,
+                    encryption_field: 0,
+                    extension_field: HANDSHAKE_MAGIC,
+                    initial_sequence_number: handshake.initial_sequence_number,
+                    max_transmission_unit: MAX_TRANSMISSION_UNIT,
+                    max_flow_window: self.config.max_flow_window,
+                    handshake_type: HandshakeType::Induction,
+                    socket_id: self.socket_id,
+                    syn_cookie: cookie,
+                    peer_ip_address: [0; 16],
+                    extensions: Vec::new(),
+                };
+
+                vec![SrtListenerResult::OutboundPacket {
+                    peer,
+                    data: handshake_packet(response, handshake.socket_id),
+                }]
+            }
+
+            // Conclusions without the cookie from our induction response could be from a
+            // spoofed address, so they're ignored
+            HandshakeType::Conclusion if handshake.syn_cookie == cookie => {
+                self.handle_conclusion(peer, handshake, now)
+            }
+
+            _ => Vec::new(),
+        }
+    }
+
+    fn handle_conclusion(
+        &mut self,
+        peer: SocketAddr,
+        handshake: &Handshake,
+        now: Instant,
+    ) -> Vec<SrtListenerResult> {
+        let mut results = Vec::new();
+        match self.peers.get(&peer) {
+            Some(Peer::Pending { .. }) => return results,
+            Some(Peer::Connected {
+                connection,
+                response,
+            }) => {
+                if connection.peer_socket_id() == handshake.socket_id {
+                    // Our response was lost, so the caller is still waiting on it
+                    results.push(SrtListenerResult::OutboundPacket {
+                        peer,
+                        data: response.clone(),
+                    });
+
+                    return results;
+                }
+
+                // The caller restarted with a new socket, so the old connection is gone
+                self.peers.remove(&peer);
+                results.push(SrtListenerResult::Disconnected { peer });
+            }
+
+            None => (),
+        }
+
+        if handshake.version < 5 {
+            results.push(self.rejection(peer, handshake, RejectReason::UnsupportedVersion));
+            return results;
+        }
+
+        let wants_encryption = handshake.encryption_field != 0
+            || handshake
+                .options()
+                .is_some_and(|options| options.flags & SrtOptions::CRYPTO != 0);
+
+        if wants_encryption {
+            results.push(self.rejection(peer, handshake, RejectReason::EncryptionNotSupported));
+            return results;
+        }
+
+        self.peers.insert(
+            peer,
+            Peer::Pending {
+                handshake: handshake.clone(),
+                requested_at: now,
+            },
+        );
+
+        results.push(SrtListenerResult::ConnectionRequested {
+            peer,
+            stream_id: handshake.stream_id().map(|x| x.to_string()),
+        });
+
+        results
+    }
+
+    fn rejection(
+        &self,
+        peer: SocketAddr,
+        handshake: &Handshake,
+        reason: RejectReason,
+    ) -> SrtListenerResult {
+        let response = Handshake {
+            version: 5,
+            encryption_field: 0,
+            extension_field: 0,
+            initial_sequence_number: handshake.initial_sequence_number,
+            max_transmission_unit: MAX_TRANSMISSION_UNIT,
+            max_flow_window: self.config.max_flow_window,
+            handshake_type: HandshakeType::Rejection(reason.code()),
+            socket_id: self.socket_id,
+            syn_cookie: handshake.syn_cookie,
+            peer_ip_address: [0; 16],
+            extensions: Vec::new(),
+        };
+
+        SrtListenerResult::OutboundPacket {
+            peer,
+            data: handshake_packet(response, handshake.socket_id),
+        }
+    }
+
+    fn convert_outputs(
+        &mut self,
+        peer: SocketAddr,
+        outputs: Vec<ConnectionOutput>,
+    ) -> Vec<SrtListenerResult> {
+        let mut results = Vec::with_capacity(outputs.len());
+        for output in outputs {
+            results.push(match output {
+                ConnectionOutput::Packet(data) => SrtListenerResult::OutboundPacket { peer, data },
+                ConnectionOutput::Data(data) => SrtListenerResult::DataReceived { peer, data },
+                ConnectionOutput::Closed => {
+                    self.peers.remove(&peer);
+                    SrtListenerResult::Disconnected { peer }
+                }
+            });
+        }
+
+        results
+    }
+
+    fn cookie(&self, peer: SocketAddr) -> u32 {
+        let mut hasher = DefaultHasher::new();
+        self.cookie_secret.hash(&mut hasher);
+        peer.hash(&mut hasher);
+        hasher.finish() as u32
+    }
+}
+
+pub(crate) fn new_socket_id() -> u32 {
+    rand::thread_rng().gen_range(1..0x4000_0000)
+}
+
+pub(crate) fn handshake_packet(handshake: Handshake, destination_socket_id: u32) -> Bytes {
+    let packet = SrtPacket::Control(ControlPacket {
+        timestamp: 0,
+        destination_socket_id,
+        info: ControlInfo::Handshake(handshake),
+    });
+
+    packet.serialize()
+}
@@ -0,0 +1,282 @@
+use super::handshake::Handshake;
+use super::read_u32;
+use bytes::{BufMut, Bytes, BytesMut};
+use errors::SrtPacketError;
+use seq::SequenceNumber;
+
+const HANDSHAKE: u16 = 0x0000;
+const KEEP_ALIVE: u16 = 0x0001;
+const ACK: u16 = 0x0002;
+const NAK: u16 = 0x0003;
+const SHUTDOWN: u16 = 0x0005;
+const ACK_ACK: u16 = 0x0006;
+const DROP_REQUEST: u16 = 0x0007;
+
+const LOSS_RANGE_FLAG: u32 = 0x8000_0000;
+
+/// A packet used to establish, maintain, or close a connection
+#[derive(PartialEq, Debug, Clone)]
+pub struct ControlPacket {
+    /// Microseconds since the sender's side of the connection was established
+    pub timestamp: u32,
+
+    pub destination_socket_id: u32,
+    pub info: ControlInfo,
+}
+
+/// Acknowledges every packet before a sequence number, and reports the receiver's view of
+/// the connection
+#[derive(PartialEq, Debug, Clone)]
+pub struct Ack {
+    /// Increments with each full acknowledgement, and is echoed back in an ACKACK so the
+    /// receiver can measure the round trip time
+    pub ack_number: u32,
+
+    /// The sequence number of the first packet that hasn't been received
+    pub next_sequence_number: SequenceNumber,
+
+    /// The round trip time measured by the receiver, in microseconds
+    pub rtt: u32,
+
+    /// The variance of the round trip time, in microseconds
+    pub rtt_variance: u32,
+
+    /// How many more packets the receiver has room to buffer
+    pub available_buffer: u32,
+}
+
+/// An inclusive range of sequence numbers that were lost
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct LossRange {
+    pub first: SequenceNumber,
+    pub last: SequenceNumber,
+}
+
+/// The contents of a control packet
+#[derive(PartialEq, Debug, Clone)]
+pub enum ControlInfo {
+    Handshake(Handshake),
+
+    /// Sent when nothing else has been sent for a while, so the peer knows the connection is
+    /// still alive
+    KeepAlive,
+
+    Ack(Ack),
+
+    /// Reports packets that were lost, so the sender retransmits them
+    Nak(Vec<LossRange>),
+
+    Shutdown,
+
+    /// Acknowledges an ACK
+    AckAck {
+        ack_number: u32,
+    },
+
+    /// Tells the receiver that the sender dropped a message, so it's no longer waiting on it
+    DropRequest {
+        message_number: u32,
+        first: SequenceNumber,
+        last: SequenceNumber,
+    },
+
+    /// A control packet this crate doesn't handle
+    Unknown {
+        control_type: u16,
+        subtype: u16,
+        type_specific_info: u32,
+        body: Bytes,
+    },
+}
+
+impl ControlInfo {
+    pub(super) fn parse(
+        control_type: u16,
+        subtype: u16,
+        type_specific_info: u32,
+        body: &[u8],
+    ) -> Result<ControlInfo, SrtPacketError> {
+        let info = match control_type {
+            HANDSHAKE => ControlInfo::Handshake(Handshake::parse(body)?),
+            KEEP_ALIVE => ControlInfo::KeepAlive,
+            SHUTDOWN => ControlInfo::Shutdown,
+            ACK_ACK => ControlInfo::AckAck {
+                ack_number: type_specific_info,
+            },
+
+            ACK => {
+                if body.len() < 4 {
+                    return Err(SrtPacketError::Truncated);
+                }
+
+                // Light acknowledgements only carry the sequence number
+                let optional_word = |index: usize| {
+                    if body.len() >= (index + 1) * 4 {
+                        read_u32(body, index * 4)
+                    } else {
+                        0
+                    }
+                };
+
+                ControlInfo::Ack(Ack {
+                    ack_number: type_specific_info,
+                    next_sequence_number: SequenceNumber::new(read_u32(body, 0)),
+                    rtt: optional_word(1),
+                    rtt_variance: optional_word(2),
+                    available_buffer: optional_word(3),
+                })
+            }
+
+            NAK => {
+                let mut ranges = Vec::new();
+                let mut words = body.chunks_exact(4).map(|word| read_u32(word, 0));
+                while let Some(word) = words.next() {
+                    let first = SequenceNumber::new(word);
+                    let last = match word & LOSS_RANGE_FLAG {
+                        0 => first,
+                        _ => SequenceNumber::new(words.next().ok_or(SrtPacketError::Truncated)?),
+                    };
+
+                    ranges.push(LossRange { first, last });
+                }
+
+                ControlInfo::Nak(ranges)
+            }
+
+            DROP_REQUEST => {
+                if body.len() < 8 {
+                    return Err(SrtPacketError::Truncated);
+                }
+
+                ControlInfo::DropRequest {
+                    message_number: type_specific_info,
+                    first: SequenceNumber::new(read_u32(body, 0)),
+                    last: SequenceNumber::new(read_u32(body, 4)),
+                }
+            }
+
+            _ => ControlInfo::Unknown {
+                control_type,
+                subtype,
+                type_specific_info,
+                body: Bytes::copy_from_slice(body),
+            },
+        };
+
+        Ok(info)
+    }
+
+    pub(super) fn control_type(&self) -> (u16, u16) {
+        match *self {
+            ControlInfo::Handshake(_) => (HANDSHAKE, 0),
+            ControlInfo::KeepAlive => (KEEP_ALIVE, 0),
+            ControlInfo::Ack(_) => (ACK, 0),
+            ControlInfo::Nak(_) => (NAK, 0),
+            ControlInfo::Shutdown => (SHUTDOWN, 0),
+            ControlInfo::AckAck { .. } => (ACK_ACK, 0),
+            ControlInfo::DropRequest { .. } => (DROP_REQUEST, 0),
+            ControlInfo::Unknown {
+                control_type,
+                subtype,
+                ..
+            } => (control_type, subtype),
+        }
+    }
+
+    pub(super) fn type_specific_info(&self) -> u32 {
+        match *self {
+            ControlInfo::Ack(ref ack) => ack.ack_number,
+            ControlInfo::AckAck { ack_number } => ack_number,
+            ControlInfo::DropRequest { message_number, .. } => message_number,
+            ControlInfo::Unknown {
+                type_specific_info, ..
+            } => type_specific_info,
+            _ => 0,
+        }
+    }
+
+    pub(super) fn write_body(&self, bytes: &mut BytesMut) {
+        match *self {
+            ControlInfo::Handshake(ref handshake) => handshake.write(bytes),
+            ControlInfo::KeepAlive | ControlInfo::Shutdown | ControlInfo::AckAck { .. } => (),
+
+            ControlInfo::Ack(ref ack) => {
+                bytes.put_u32(ack.next_sequence_number.value());
+                bytes.put_u32(ack.rtt);
+                bytes.put_u32(ack.rtt_variance);
+                bytes.put_u32(ack.available_buffer);
+
+                // Receive rates and link capacity aren't estimated
+                bytes.put_u32(0);
+                bytes.put_u32(0);
+                bytes.put_u32(0);
+            }
+
+            ControlInfo::Nak(ref ranges) => {
+                for range in ranges {
+                    if range.first == range.last {
+                        bytes.put_u32(range.first.value());
+                    } else {
+                        bytes.put_u32(range.first.value() | LOSS_RANGE_FLAG);
+                        bytes.put_u32(range.last.value());
+                    }
+                }
+            }
+
+            ControlInfo::DropRequest { first, last, .. } => {
+                bytes.put_u32(first.value());
+                bytes.put_u32(last.value());
+            }
+
+            ControlInfo::Unknown { ref body, .. } => bytes.put_slice(body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SrtPacket;
+    use super::*;
+
+    fn round_trip(info: ControlInfo) -> ControlInfo {
+        let packet = SrtPacket::Control(ControlPacket {
+            timestamp: 5,
+            destination_socket_id: 7,
+            info,
+        });
+
+        match SrtPacket::parse(&packet.serialize()).unwrap() {
+            SrtPacket::Control(control) => control.info,
+            x => panic!("Expected control packet, instead got {:?}", x),
+        }
+    }
+
+    #[test]
+    fn nak_with_single_losses_and_ranges_round_trips() {
+        let info = ControlInfo::Nak(vec![
+            LossRange {
+                first: SequenceNumber::new(5),
+                last: SequenceNumber::new(5),
+            },
+            LossRange {
+                first: SequenceNumber::new(10),
+                last: SequenceNumber::new(14),
+            },
+        ]);
+
+        assert_eq!(round_trip(info.clone()), info);
+    }
+
+    #[test]
+    fn ack_round_trips() {
+        let info = ControlInfo::Ack(Ack {
+            ack_number: 3,
+            next_sequence_number: SequenceNumber::new(1000),
+            rtt: 20_000,
+            rtt_variance: 5_000,
+            available_buffer: 8192,
+        });
+
+        assert_eq!(round_trip(info.clone()), info);
+    }
+}
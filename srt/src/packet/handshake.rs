@@ -0,0 +1,367 @@
+use super::{read_u16, read_u32};
+use bytes::{BufMut, Bytes, BytesMut};
+use errors::SrtPacketError;
+use seq::SequenceNumber;
+
+/// The value listeners put in the extension field of their induction response, to show that
+/// they support version 5 handshakes
+pub const HANDSHAKE_MAGIC: u16 = 0x4a17;
+
+/// The longest stream id a handshake can carry, in bytes
+pub const MAX_STREAM_ID_LENGTH: usize = 512;
+
+const HANDSHAKE_SIZE: usize = 48;
+
+const HANDSHAKE_REQUEST: u16 = 1;
+const HANDSHAKE_RESPONSE: u16 = 2;
+const STREAM_ID: u16 = 5;
+
+/// The stage of the handshake a handshake packet is for
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum HandshakeType {
+    Induction,
+    Conclusion,
+    Wavehand,
+    Agreement,
+    Done,
+
+    /// The listener refused the connection, for the specified reason
+    Rejection(u32),
+}
+
+impl HandshakeType {
+    fn from_value(value: u32) -> HandshakeType {
+        match value {
+            1 => HandshakeType::Induction,
+            0 => HandshakeType::Wavehand,
+            0xffff_ffff => HandshakeType::Conclusion,
+            0xffff_fffe => HandshakeType::Agreement,
+            0xffff_fffd => HandshakeType::Done,
+            x => HandshakeType::Rejection(x),
+        }
+    }
+
+    fn value(self) -> u32 {
+        match self {
+            HandshakeType::Induction => 1,
+            HandshakeType::Wavehand => 0,
+            HandshakeType::Conclusion => 0xffff_ffff,
+            HandshakeType::Agreement => 0xffff_fffe,
+            HandshakeType::Done => 0xffff_fffd,
+            HandshakeType::Rejection(reason) => reason,
+        }
+    }
+}
+
+/// The SRT specific options each side sends during the conclusion stage of the handshake
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct SrtOptions {
+    /// The SRT version of the sender, such as `0x010500` for 1.5.0
+    pub version: u32,
+
+    pub flags: u32,
+
+    /// How many milliseconds the sender buffers the media it receives
+    pub receiver_latency: u16,
+
+    /// How many milliseconds the sender expects the peer to buffer the media it sends
+    pub sender_latency: u16,
+}
+
+impl SrtOptions {
+    pub const TSBPD_SEND: u32 = 0x01;
+    pub const TSBPD_RECEIVE: u32 = 0x02;
+    pub const CRYPTO: u32 = 0x04;
+    pub const TOO_LATE_PACKET_DROP: u32 = 0x08;
+    pub const PERIODIC_NAK: u32 = 0x10;
+    pub const RETRANSMIT_FLAG: u32 = 0x20;
+    pub const STREAM: u32 = 0x40;
+    pub const PACKET_FILTER: u32 = 0x80;
+}
+
+/// An extension carried after the fields of a conclusion handshake
+#[derive(PartialEq, Debug, Clone)]
+pub enum HandshakeExtension {
+    /// The caller's options
+    Request(SrtOptions),
+
+    /// The listener's options, in response to the caller's
+    Response(SrtOptions),
+
+    /// The stream the caller wants to publish or play, which is how a listener tells different
+    /// streams apart
+    StreamId(String),
+
+    /// An extension this crate doesn't handle, such as encryption key material
+    Unknown { extension_type: u16, data: Bytes },
+}
+
+/// The contents of a handshake control packet
+#[derive(PartialEq, Debug, Clone)]
+pub struct Handshake {
+    pub version: u32,
+    pub encryption_field: u16,
+
+    /// During induction this is `HANDSHAKE_MAGIC` (from the listener) or the socket type (from
+    /// the caller).  During conclusion this has a flag set for each type of extension that's
+    /// included.
+    pub extension_field: u16,
+
+    /// The sequence number of the first data packet the sender will send
+    pub initial_sequence_number: SequenceNumber,
+
+    pub max_transmission_unit: u32,
+
+    /// The most packets the sender can have in flight at once
+    pub max_flow_window: u32,
+
+    pub handshake_type: HandshakeType,
+
+    /// The id the sender uses for its side of the connection, which the peer sets as the
+    /// destination of the packets it sends
+    pub socket_id: u32,
+
+    /// Generated by the listener during induction, and sent back by the caller during
+    /// conclusion so the listener knows the caller's address is genuine
+    pub syn_cookie: u32,
+
+    pub peer_ip_address: [u8; 16],
+    pub extensions: Vec<HandshakeExtension>,
+}
+
+impl Handshake {
+    /// The extension field flag set when the conclusion has a request or response extension
+    pub const EXTENSION_FLAG_OPTIONS: u16 = 0x01;
+
+    /// The extension field flag set when the conclusion has a stream id extension
+    pub const EXTENSION_FLAG_CONFIG: u16 = 0x04;
+
+    /// Returns the SRT options in the handshake's request or response extension
+    pub fn options(&self) -> Option<SrtOptions> {
+        self.extensions
+            .iter()
+            .find_map(|extension| match *extension {
+                HandshakeExtension::Request(options) | HandshakeExtension::Response(options) => {
+                    Some(options)
+                }
+                _ => None,
+            })
+    }
+
+    /// Returns the stream id the handshake carries
+    pub fn stream_id(&self) -> Option<&str> {
+        self.extensions
+            .iter()
+            .find_map(|extension| match *extension {
+                HandshakeExtension::StreamId(ref stream_id) => Some(stream_id.as_str()),
+                _ => None,
+            })
+    }
+
+    pub(super) fn parse(body: &[u8]) -> Result<Handshake, SrtPacketError> {
+        if body.len() < HANDSHAKE_SIZE {
+            return Err(SrtPacketError::Truncated);
+        }
+
+        let mut peer_ip_address = [0_u8; 16];
+        peer_ip_address.copy_from_slice(&body[32..HANDSHAKE_SIZE]);
+
+        let mut extensions = Vec::new();
+        let mut remaining = &body[HANDSHAKE_SIZE..];
+        while remaining.len() >= 4 {
+            let extension_type = read_u16(remaining, 0);
+            let length = read_u16(remaining, 2) as usize * 4;
+            if remaining.len() < 4 + length {
+                return Err(SrtPacketError::InvalidExtensionLength { extension_type });
+            }
+
+            let data = &remaining[4..4 + length];
+            remaining = &remaining[4 + length..];
+            extensions.push(parse_extension(extension_type, data)?);
+        }
+
+        Ok(Handshake {
+            version: read_u32(body, 0),
+            encryption_field: read_u16(body, 4),
+            extension_field: read_u16(body, 6),
+            initial_sequence_number: SequenceNumber::new(read_u32(body, 8)),
+            max_transmission_unit: read_u32(body, 12),
+            max_flow_window: read_u32(body, 16),
+            handshake_type: HandshakeType::from_value(read_u32(body, 20)),
+            socket_id: read_u32(body, 24),
+            syn_cookie: read_u32(body, 28),
+            peer_ip_address,
+            extensions,
+        })
+    }
+
+    pub(super) fn write(&self, bytes: &mut BytesMut) {
+        bytes.reserve(HANDSHAKE_SIZE);
+        bytes.put_u32(self.version);
+        bytes.put_u16(self.encryption_field);
+        bytes.put_u16(self.extension_field);
+        bytes.put_u32(self.initial_sequence_number.value());
+        bytes.put_u32(self.max_transmission_unit);
+        bytes.put_u32(self.max_flow_window);
+        bytes.put_u32(self.handshake_type.value());
+        bytes.put_u32(self.socket_id);
+        bytes.put_u32(self.syn_cookie);
+        bytes.put_slice(&self.peer_ip_address);
+
+        for extension in &self.extensions {
+            match *extension {
+                HandshakeExtension::Request(ref options) => {
+                    write_options(bytes, HANDSHAKE_REQUEST, options)
+                }
+
+                HandshakeExtension::Response(ref options) => {
+                    write_options(bytes, HANDSHAKE_RESPONSE, options)
+                }
+
+                HandshakeExtension::StreamId(ref stream_id) => {
+                    // The stream id is padded to whole words, with the bytes of each word in
+                    // little endian order
+                    let mut padded = stream_id.as_bytes().to_vec();
+                    padded.resize(padded.len().div_ceil(4) * 4, 0);
+
+                    bytes.put_u16(STREAM_ID);
+                    bytes.put_u16((padded.len() / 4) as u16);
+                    for word in padded.chunks_exact(4) {
+                        bytes.put_slice(&[word[3], word[2], word[1], word[0]]);
+                    }
+                }
+
+                HandshakeExtension::Unknown {
+                    extension_type,
+                    ref data,
+                } => {
+                    bytes.put_u16(extension_type);
+                    bytes.put_u16((data.len() / 4) as u16);
+                    bytes.put_slice(data);
+                }
+            }
+        }
+    }
+}
+
+fn parse_extension(extension_type: u16, data: &[u8]) -> Result<HandshakeExtension, SrtPacketError> {
+    let extension = match extension_type {
+        HANDSHAKE_REQUEST | HANDSHAKE_RESPONSE => {
+            if data.len() < 12 {
+                return Err(SrtPacketError::InvalidExtensionLength { extension_type });
+            }
+
+            let options = SrtOptions {
+                version: read_u32(data, 0),
+                flags: read_u32(data, 4),
+                receiver_latency: read_u16(data, 8),
+                sender_latency: read_u16(data, 10),
+            };
+
+            if extension_type == HANDSHAKE_REQUEST {
+                HandshakeExtension::Request(options)
+            } else {
+                HandshakeExtension::Response(options)
+            }
+        }
+
+        STREAM_ID => {
+            let mut stream_id = Vec::with_capacity(data.len());
+            for word in data.chunks_exact(4) {
+                stream_id.extend_from_slice(&[word[3], word[2], word[1], word[0]]);
+            }
+
+            while stream_id.last() == Some(&0) {
+                stream_id.pop();
+            }
+
+            let stream_id =
+                String::from_utf8(stream_id).map_err(|_| SrtPacketError::InvalidStreamId)?;
+            HandshakeExtension::StreamId(stream_id)
+        }
+
+        _ => HandshakeExtension::Unknown {
+            extension_type,
+            data: Bytes::copy_from_slice(data),
+        },
+    };
+
+    Ok(extension)
+}
+
+fn write_options(bytes: &mut BytesMut, extension_type: u16, options: &SrtOptions) {
+    bytes.put_u16(extension_type);
+    bytes.put_u16(3);
+    bytes.put_u32(options.version);
+    bytes.put_u32(options.flags);
+    bytes.put_u16(options.receiver_latency);
+    bytes.put_u16(options.sender_latency);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{ControlInfo, ControlPacket, SrtPacket};
+    use super::*;
+
+    #[test]
+    fn conclusion_with_extensions_round_trips() {
+        let handshake = Handshake {
+            version: 5,
+            encryption_field: 0,
+            extension_field: Handshake::EXTENSION_FLAG_OPTIONS | Handshake::EXTENSION_FLAG_CONFIG,
+            initial_sequence_number: SequenceNumber::new(12345),
+            max_transmission_unit: 1500,
+            max_flow_window: 8192,
+            handshake_type: HandshakeType::Conclusion,
+            socket_id: 0x1122_3344,
+            syn_cookie: 0x5566_7788,
+            peer_ip_address: [0; 16],
+            extensions: vec![
+                HandshakeExtension::Request(SrtOptions {
+                    version: 0x010500,
+                    flags: SrtOptions::TSBPD_SEND | SrtOptions::TSBPD_RECEIVE,
+                    receiver_latency: 120,
+                    sender_latency: 0,
+                }),
+                HandshakeExtension::StreamId("live/key".to_string()),
+            ],
+        };
+
+        let packet = SrtPacket::Control(ControlPacket {
+            timestamp: 0,
+            destination_socket_id: 0,
+            info: ControlInfo::Handshake(handshake.clone()),
+        });
+
+        let bytes = packet.serialize();
+
+        assert_eq!(SrtPacket::parse(&bytes).unwrap(), packet);
+        assert_eq!(handshake.stream_id(), Some("live/key"));
+        assert_eq!(handshake.options().unwrap().receiver_latency, 120);
+    }
+
+    #[test]
+    fn stream_id_words_are_byte_swapped() {
+        let mut bytes = BytesMut::new();
+        let handshake = Handshake {
+            version: 5,
+            encryption_field: 0,
+            extension_field: 0,
+            initial_sequence_number: SequenceNumber::new(0),
+            max_transmission_unit: 1500,
+            max_flow_window: 8192,
+            handshake_type: HandshakeType::Conclusion,
+            socket_id: 0,
+            syn_cookie: 0,
+            peer_ip_address: [0; 16],
+            extensions: vec![HandshakeExtension::StreamId("abcde".to_string())],
+        };
+
+        handshake.write(&mut bytes);
+
+        assert_eq!(
+            &bytes[HANDSHAKE_SIZE..],
+            &[0, 5, 0, 2, b'd', b'c', b'b', b'a', 0, 0, 0, b'e']
+        );
+    }
+}
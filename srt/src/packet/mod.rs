@@ -0,0 +1,207 @@
+/*!
+This module contains the packets that make up the SRT protocol, along with reading them from
+and writing them to UDP datagrams.
+
+Every datagram carries exactly one packet, which is either a data packet carrying part of the
+stream, or a control packet used to establish and maintain the connection.
+*/
+
+mod control;
+mod handshake;
+
+pub use self::control::{Ack, ControlInfo, ControlPacket, LossRange};
+pub use self::handshake::{
+    Handshake, HandshakeExtension, HandshakeType, SrtOptions, HANDSHAKE_MAGIC, MAX_STREAM_ID_LENGTH,
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use errors::SrtPacketError;
+use seq::SequenceNumber;
+
+/// The size of the header at the start of every packet
+pub const HEADER_SIZE: usize = 16;
+
+const CONTROL_FLAG: u32 = 0x8000_0000;
+
+/// Where a data packet's payload falls in the message it's part of.  Live mode sends every
+/// message in a single packet.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum PacketPosition {
+    Middle,
+    Last,
+    First,
+    Only,
+}
+
+/// A packet carrying part of the stream
+#[derive(PartialEq, Debug, Clone)]
+pub struct DataPacket {
+    pub sequence_number: SequenceNumber,
+    pub position: PacketPosition,
+
+    /// If the message must be delivered in order, which isn't used in live mode
+    pub in_order: bool,
+
+    /// Which of the stream's keys the payload is encrypted with, where zero means it's not
+    /// encrypted
+    pub encryption_key: u8,
+
+    /// True if the packet is being sent again after the peer reported it as lost
+    pub is_retransmitted: bool,
+
+    pub message_number: u32,
+
+    /// Microseconds since the sender's side of the connection was established
+    pub timestamp: u32,
+
+    pub destination_socket_id: u32,
+    pub payload: Bytes,
+}
+
+/// A packet that can be sent over an SRT connection
+#[derive(PartialEq, Debug, Clone)]
+pub enum SrtPacket {
+    Data(DataPacket),
+    Control(ControlPacket),
+}
+
+impl SrtPacket {
+    /// Reads the packet contained in a UDP datagram
+    pub fn parse(datagram: &[u8]) -> Result<SrtPacket, SrtPacketError> {
+        if datagram.len() < HEADER_SIZE {
+            return Err(SrtPacketError::Truncated);
+        }
+
+        let first_word = read_u32(datagram, 0);
+        let second_word = read_u32(datagram, 4);
+        let timestamp = read_u32(datagram, 8);
+        let destination_socket_id = read_u32(datagram, 12);
+        let body = &datagram[HEADER_SIZE..];
+
+        if first_word & CONTROL_FLAG != 0 {
+            let control_type = ((first_word >> 16) & 0x7fff) as u16;
+            let subtype = first_word as u16;
+            let info = ControlInfo::parse(control_type, subtype, second_word, body)?;
+            return Ok(SrtPacket::Control(ControlPacket {
+                timestamp,
+                destination_socket_id,
+                info,
+            }));
+        }
+
+        let position = match second_word >> 30 {
+            0b00 => PacketPosition::Middle,
+            0b01 => PacketPosition::Last,
+            0b10 => PacketPosition::First,
+            _ => PacketPosition::Only,
+        };
+
+        Ok(SrtPacket::Data(DataPacket {
+            sequence_number: SequenceNumber::new(first_word),
+            position,
+            in_order: second_word & 0x2000_0000 != 0,
+            encryption_key: ((second_word >> 27) & 0b11) as u8,
+            is_retransmitted: second_word & 0x0400_0000 != 0,
+            message_number: second_word & 0x03ff_ffff,
+            timestamp,
+            destination_socket_id,
+            payload: Bytes::copy_from_slice(body),
+        }))
+    }
+
+    /// Writes the packet into a UDP datagram
+    pub fn serialize(&self) -> Bytes {
+        let mut bytes = BytesMut::with_capacity(HEADER_SIZE);
+        match *self {
+            SrtPacket::Data(ref packet) => {
+                let position: u32 = match packet.position {
+                    PacketPosition::Middle => 0b00,
+                    PacketPosition::Last => 0b01,
+                    PacketPosition::First => 0b10,
+                    PacketPosition::Only => 0b11,
+                };
+
+                let mut second_word = (position << 30)
+                    | ((packet.encryption_key as u32 & 0b11) << 27)
+                    | (packet.message_number & 0x03ff_ffff);
+
+                if packet.in_order {
+                    second_word |= 0x2000_0000;
+                }
+
+                if packet.is_retransmitted {
+                    second_word |= 0x0400_0000;
+                }
+
+                bytes.reserve(packet.payload.len());
+                bytes.put_u32(packet.sequence_number.value());
+                bytes.put_u32(second_word);
+                bytes.put_u32(packet.timestamp);
+                bytes.put_u32(packet.destination_socket_id);
+                bytes.put_slice(&packet.payload);
+            }
+
+            SrtPacket::Control(ref packet) => {
+                let (control_type, subtype) = packet.info.control_type();
+                bytes.put_u32(CONTROL_FLAG | ((control_type as u32) << 16) | subtype as u32);
+                bytes.put_u32(packet.info.type_specific_info());
+                bytes.put_u32(packet.timestamp);
+                bytes.put_u32(packet.destination_socket_id);
+                packet.info.write_body(&mut bytes);
+            }
+        }
+
+        bytes.freeze()
+    }
+
+    /// The socket id of the connection the packet is addressed to
+    pub fn destination_socket_id(&self) -> u32 {
+        match *self {
+            SrtPacket::Data(ref packet) => packet.destination_socket_id,
+            SrtPacket::Control(ref packet) => packet.destination_socket_id,
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0_u8; 4];
+    word.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_be_bytes(word)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_packet_round_trips() {
+        let packet = SrtPacket::Data(DataPacket {
+            sequence_number: SequenceNumber::new(0x1234_5678),
+            position: PacketPosition::Only,
+            in_order: false,
+            encryption_key: 0,
+            is_retransmitted: true,
+            message_number: 99,
+            timestamp: 120_000,
+            destination_socket_id: 0xdead_beef,
+            payload: Bytes::from(vec![0x47, 1, 2, 3]),
+        });
+
+        let bytes = packet.serialize();
+
+        assert_eq!(&bytes[..8], &[0x12, 0x34, 0x56, 0x78, 0xc4, 0, 0, 99]);
+        assert_eq!(SrtPacket::parse(&bytes).unwrap(), packet);
+    }
+
+    #[test]
+    fn datagram_shorter_than_header_is_truncated() {
+        match SrtPacket::parse(&[0x80, 0, 0, 0]) {
+            Err(SrtPacketError::Truncated) => (),
+            x => panic!("Expected truncated error, instead got {:?}", x),
+        }
+    }
+}
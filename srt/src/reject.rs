@@ -0,0 +1,90 @@
+const PEER: u32 = 1002;
+const VERSION: u32 = 1008;
+const UNSECURE: u32 = 1011;
+const TIMEOUT: u32 = 1016;
+const BAD_REQUEST: u32 = 1400;
+const UNAUTHORIZED: u32 = 1401;
+const OVERLOAD: u32 = 1402;
+const FORBIDDEN: u32 = 1403;
+const NOT_FOUND: u32 = 1404;
+const BAD_MODE: u32 = 1405;
+const CONFLICT: u32 = 1409;
+
+/// Why a listener refused a connection, which is sent to the caller in place of the
+/// conclusion handshake
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum RejectReason {
+    /// The application rejected the connection without a more specific reason
+    Peer,
+
+    /// The caller only supports an older version of the handshake
+    UnsupportedVersion,
+
+    /// The caller wants to encrypt the stream, which isn't supported
+    EncryptionNotSupported,
+
+    /// The caller gave up waiting for the listener to respond
+    Timeout,
+
+    /// The stream id could not be understood
+    BadRequest,
+
+    /// The caller did not provide valid credentials
+    Unauthorized,
+
+    /// The listener has too many connections
+    Overload,
+
+    /// The caller is not allowed to access the stream
+    Forbidden,
+
+    /// The stream does not exist
+    NotFound,
+
+    /// The stream can't be accessed with the requested mode
+    BadMode,
+
+    /// The stream is already being published
+    Conflict,
+
+    /// Any other reason code, including ones defined by the application (2000 and up)
+    Other(u32),
+}
+
+impl RejectReason {
+    /// The code sent in the handshake for this reason
+    pub fn code(self) -> u32 {
+        match self {
+            RejectReason::Peer => PEER,
+            RejectReason::UnsupportedVersion => VERSION,
+            RejectReason::EncryptionNotSupported => UNSECURE,
+            RejectReason::Timeout => TIMEOUT,
+            RejectReason::BadRequest => BAD_REQUEST,
+            RejectReason::Unauthorized => UNAUTHORIZED,
+            RejectReason::Overload => OVERLOAD,
+            RejectReason::Forbidden => FORBIDDEN,
+            RejectReason::NotFound => NOT_FOUND,
+            RejectReason::BadMode => BAD_MODE,
+            RejectReason::Conflict => CONFLICT,
+            RejectReason::Other(code) => code,
+        }
+    }
+
+    /// The reason a handshake's code represents
+    pub fn from_code(code: u32) -> RejectReason {
+        match code {
+            PEER => RejectReason::Peer,
+            VERSION => RejectReason::UnsupportedVersion,
+            UNSECURE => RejectReason::EncryptionNotSupported,
+            TIMEOUT => RejectReason::Timeout,
+            BAD_REQUEST => RejectReason::BadRequest,
+            UNAUTHORIZED => RejectReason::Unauthorized,
+            OVERLOAD => RejectReason::Overload,
+            FORBIDDEN => RejectReason::Forbidden,
+            NOT_FOUND => RejectReason::NotFound,
+            BAD_MODE => RejectReason::BadMode,
+            CONFLICT => RejectReason::Conflict,
+            code => RejectReason::Other(code),
+        }
+    }
+}
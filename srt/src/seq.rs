@@ -0,0 +1,65 @@
+use std::fmt;
+use std::ops::Add;
+
+const MAX_VALUE: u32 = 0x7fff_ffff;
+const HALF_RANGE: u32 = 0x4000_0000;
+
+/// The 31 bit sequence number of an SRT data packet, which wraps back to zero after reaching
+/// its maximum value
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
+pub struct SequenceNumber(u32);
+
+impl SequenceNumber {
+    /// Creates a sequence number, discarding the highest bit of the value
+    pub fn new(value: u32) -> SequenceNumber {
+        SequenceNumber(value & MAX_VALUE)
+    }
+
+    pub fn value(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the sequence number directly after this one
+    pub fn next(self) -> SequenceNumber {
+        self + 1
+    }
+
+    /// How many packets this sequence number is after the other one, taking wrapping into
+    /// account.  This is negative if this sequence number comes first.
+    pub fn offset_from(self, other: SequenceNumber) -> i32 {
+        let difference = self.0.wrapping_sub(other.0) & MAX_VALUE;
+        if difference < HALF_RANGE {
+            difference as i32
+        } else {
+            difference as i32 - (MAX_VALUE as i32) - 1
+        }
+    }
+}
+
+impl Add<u32> for SequenceNumber {
+    type Output = SequenceNumber;
+
+    fn add(self, count: u32) -> SequenceNumber {
+        SequenceNumber::new(self.0.wrapping_add(count))
+    }
+}
+
+impl fmt::Display for SequenceNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_number_wraps_at_31_bits() {
+        let number = SequenceNumber::new(MAX_VALUE);
+
+        assert_eq!(number.next(), SequenceNumber::new(0));
+        assert_eq!(SequenceNumber::new(3).offset_from(number), 4);
+        assert_eq!(number.offset_from(SequenceNumber::new(3)), -4);
+    }
+}
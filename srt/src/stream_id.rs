@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+const ACCESS_CONTROL_PREFIX: &str = "#!::";
+
+/// What a caller wants to do with the stream it's connecting to
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum StreamMode {
+    /// Play the stream, which is the default when the stream id doesn't specify a mode
+    Request,
+
+    /// Send the stream to the listener
+    Publish,
+
+    Bidirectional,
+}
+
+/// The stream id a caller sent in its handshake, parsed using SRT's access control syntax.
+///
+/// Stream ids in the form `#!::r=live/key,m=publish,u=user` have their keys parsed into the
+/// matching fields, and any keys this crate doesn't know of are put in `extra`.  Any other
+/// stream id is used as the resource name, so callers that just send `live/key` work as well.
+///
+/// # Examples
+/// ```
+/// use rml_srt::{SrtStreamId, StreamMode};
+///
+/// let stream_id = SrtStreamId::parse("#!::r=live/key,m=publish");
+/// assert_eq!(stream_id.mode, StreamMode::Publish);
+/// assert_eq!(
+///     stream_id.app_and_stream_key(),
+///     Some(("live".to_string(), "key".to_string()))
+/// );
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct SrtStreamId {
+    /// The name of the stream (`r`)
+    pub resource: Option<String>,
+
+    /// The name of the user the caller is authenticating as (`u`)
+    pub user: Option<String>,
+
+    /// The host the caller connected to, for listeners serving multiple hosts (`h`)
+    pub host: Option<String>,
+
+    /// An identifier for the caller's session (`s`)
+    pub session: Option<String>,
+
+    /// The mode the caller is connecting with (`m`)
+    pub mode: StreamMode,
+
+    /// Any other keys, such as ones the application defined itself
+    pub extra: HashMap<String, String>,
+}
+
+impl SrtStreamId {
+    /// Parses a stream id
+    pub fn parse(stream_id: &str) -> SrtStreamId {
+        let mut parsed = SrtStreamId {
+            resource: None,
+            user: None,
+            host: None,
+            session: None,
+            mode: StreamMode::Request,
+            extra: HashMap::new(),
+        };
+
+        if !stream_id.starts_with(ACCESS_CONTROL_PREFIX) {
+            if !stream_id.is_empty() {
+                parsed.resource = Some(stream_id.to_string());
+            }
+
+            return parsed;
+        }
+
+        for pair in stream_id[ACCESS_CONTROL_PREFIX.len()..].split(',') {
+            let mut parts = pair.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = match parts.next() {
+                Some(value) => value.trim().to_string(),
+                None => continue,
+            };
+
+            match key {
+                "r" => parsed.resource = Some(value),
+                "u" => parsed.user = Some(value),
+                "h" => parsed.host = Some(value),
+                "s" => parsed.session = Some(value),
+                "m" => {
+                    parsed.mode = match value.as_str() {
+                        "publish" => StreamMode::Publish,
+                        "bidirectional" => StreamMode::Bidirectional,
+                        _ => StreamMode::Request,
+                    }
+                }
+
+                _ => {
+                    parsed.extra.insert(key.to_string(), value);
+                }
+            }
+        }
+
+        parsed
+    }
+
+    /// Splits the resource name at its last slash into the RTMP application name and stream
+    /// key, so SRT streams can share the same names as streams published over RTMP
+    pub fn app_and_stream_key(&self) -> Option<(String, String)> {
+        let resource = self.resource.as_ref()?.trim_matches('/');
+        let separator = resource.rfind('/')?;
+        let (app_name, stream_key) = (&resource[..separator], &resource[separator + 1..]);
+        if app_name.is_empty() || stream_key.is_empty() {
+            return None;
+        }
+
+        Some((app_name.to_string(), stream_key.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_stream_id_is_resource() {
+        let stream_id = SrtStreamId::parse("live/key");
+
+        assert_eq!(stream_id.resource, Some("live/key".to_string()));
+        assert_eq!(stream_id.mode, StreamMode::Request);
+    }
+
+    #[test]
+    fn access_control_keys_are_parsed() {
+        let stream_id = SrtStreamId::parse("#!::u=admin,r=apps/live/key,m=publish,token=abc");
+
+        assert_eq!(stream_id.user, Some("admin".to_string()));
+        assert_eq!(stream_id.mode, StreamMode::Publish);
+        assert_eq!(stream_id.extra.get("token"), Some(&"abc".to_string()));
+        assert_eq!(
+            stream_id.app_and_stream_key(),
+            Some(("apps/live".to_string(), "key".to_string()))
+        );
+    }
+
+    #[test]
+    fn resource_without_app_has_no_stream_key() {
+        assert_eq!(SrtStreamId::parse("key").app_and_stream_key(), None);
+    }
+}
@@ -0,0 +1,705 @@
+use super::{TsMedia, TS_PACKET_SIZE};
+use bytes::{BufMut, Bytes, BytesMut};
+use rml_rtmp::time::RtmpTimestamp;
+use std::collections::HashMap;
+
+const SYNC_BYTE: u8 = 0x47;
+const PAT_PID: u16 = 0;
+const STREAM_TYPE_AAC: u8 = 0x0f;
+const STREAM_TYPE_H264: u8 = 0x1b;
+
+const NAL_TYPE_IDR: u8 = 5;
+const NAL_TYPE_SPS: u8 = 7;
+const NAL_TYPE_PPS: u8 = 8;
+const NAL_TYPE_ACCESS_UNIT_DELIMITER: u8 = 9;
+
+const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+const AAC_SAMPLES_PER_FRAME: u64 = 1024;
+const PTS_WRAP: u64 = 1 << 33;
+const PTS_TICKS_PER_MILLISECOND: u64 = 90;
+
+#[derive(PartialEq, Eq, Copy, Clone)]
+enum StreamKind {
+    Video,
+    Audio,
+}
+
+struct PesAssembler {
+    kind: StreamKind,
+    data: Vec<u8>,
+    continuity_counter: Option<u8>,
+    is_started: bool,
+}
+
+/// Extracts H.264 video and AAC audio from an MPEG transport stream.
+///
+/// Bytes of the transport stream are passed to `push()` as they're received, in chunks of any
+/// size, and the audio and video completed by them are returned as RTMP payloads.  The first
+/// program in the stream is used, and any streams in it that aren't H.264 or AAC are ignored.
+///
+/// Sequence headers are generated from the SPS and PPS in the video and from the ADTS headers
+/// of the audio, and are returned before the first frame (and again whenever they change).
+/// Video frames before the first SPS and PPS can't be decoded by players, so they're dropped.
+/// Timestamps are the DTS of each frame in milliseconds, with the presentation offset carried
+/// in the composition time of the video payload.
+///
+/// Live feeds can lose packets, so malformed packets and partial PES packets that are missing
+/// data are skipped rather than treated as errors.
+///
+/// # Examples
+/// ```
+/// use rml_srt::ts::TsDemuxer;
+///
+/// let mut demuxer = TsDemuxer::new();
+/// # let received_payload = vec![0x47, 0x1f, 0xff, 0x10];
+/// for media in demuxer.push(&received_payload) {
+///     // Publish it into a StreamHub with `media.publish()`
+/// }
+/// ```
+pub struct TsDemuxer {
+    pending: Vec<u8>,
+    pmt_pid: Option<u16>,
+    streams: HashMap<u16, PesAssembler>,
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+    sent_video_config: Option<Vec<u8>>,
+    sent_audio_config: Option<[u8; 2]>,
+    last_timestamp: Option<u64>,
+}
+
+impl TsDemuxer {
+    /// Creates a demuxer that hasn't seen any of the stream yet
+    pub fn new() -> TsDemuxer {
+        TsDemuxer {
+            pending: Vec::new(),
+            pmt_pid: None,
+            streams: HashMap::new(),
+            sps: None,
+            pps: None,
+            sent_video_config: None,
+            sent_audio_config: None,
+            last_timestamp: None,
+        }
+    }
+
+    /// Demultiplexes the bytes, returning any media they complete
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<TsMedia> {
+        let mut media = Vec::new();
+        self.pending.extend_from_slice(bytes);
+
+        let mut offset = 0;
+        while self.pending.len() - offset >= TS_PACKET_SIZE {
+            if self.pending[offset] != SYNC_BYTE {
+                // Skip ahead to the next sync byte to recover from lost or corrupt data
+                offset += 1;
+                continue;
+            }
+
+            let mut packet = [0_u8; TS_PACKET_SIZE];
+            packet.copy_from_slice(&self.pending[offset..offset + TS_PACKET_SIZE]);
+            offset += TS_PACKET_SIZE;
+            self.handle_packet(&packet, &mut media);
+        }
+
+        self.pending.drain(..offset);
+        media
+    }
+
+    /// Returns the media in PES packets that haven't been completed yet.  Video PES packets
+    /// usually don't state their length, so the last frame is only known to be complete once
+    /// the next one starts, or the stream ends.
+    pub fn flush(&mut self) -> Vec<TsMedia> {
+        let mut media = Vec::new();
+        let pids = self.streams.keys().cloned().collect::<Vec<_>>();
+        for pid in pids {
+            self.finish_pes(pid, &mut media);
+        }
+
+        media
+    }
+
+    fn handle_packet(&mut self, packet: &[u8], media: &mut Vec<TsMedia>) {
+        let is_unit_start = packet[1] & 0x40 != 0;
+        let pid = (((packet[1] & 0x1f) as u16) << 8) | packet[2] as u16;
+        let adaptation_field_control = (packet[3] >> 4) & 0b11;
+        let continuity_counter = packet[3] & 0x0f;
+
+        let mut payload_start = 4;
+        if adaptation_field_control & 0b10 != 0 {
+            payload_start += 1 + packet[4] as usize;
+        }
+
+        if adaptation_field_control & 0b01 == 0 || payload_start >= TS_PACKET_SIZE {
+            return;
+        }
+
+        let payload = &packet[payload_start..];
+        if pid == PAT_PID {
+            if is_unit_start {
+                self.handle_pat(payload);
+            }
+        } else if Some(pid) == self.pmt_pid {
+            if is_unit_start {
+                self.handle_pmt(payload);
+            }
+        } else if self.streams.contains_key(&pid) {
+            self.handle_pes_payload(pid, is_unit_start, continuity_counter, payload, media);
+        }
+    }
+
+    fn handle_pat(&mut self, payload: &[u8]) {
+        let section = match psi_section(payload) {
+            Some(section) => section,
+            None => return,
+        };
+
+        // The program loop starts after the 8 byte header, and is followed by the CRC
+        for program in section[8..].chunks_exact(4) {
+            let program_number = ((program[0] as u16) << 8) | program[1] as u16;
+            if program_number != 0 {
+                self.pmt_pid = Some((((program[2] & 0x1f) as u16) << 8) | program[3] as u16);
+                return;
+            }
+        }
+    }
+
+    fn handle_pmt(&mut self, payload: &[u8]) {
+        let section = match psi_section(payload) {
+            Some(section) if section.len() >= 12 => section,
+            _ => return,
+        };
+
+        let program_info_length =
+            ((((section[10] & 0x0f) as usize) << 8) | section[11] as usize).min(section.len() - 12);
+
+        let mut streams = HashMap::new();
+        let mut remaining = &section[12 + program_info_length..];
+        while remaining.len() >= 5 {
+            let stream_type = remaining[0];
+            let pid = (((remaining[1] & 0x1f) as u16) << 8) | remaining[2] as u16;
+            let info_length = (((remaining[3] & 0x0f) as usize) << 8) | remaining[4] as usize;
+            remaining = &remaining[(5 + info_length).min(remaining.len())..];
+
+            let kind = match stream_type {
+                STREAM_TYPE_H264 => StreamKind::Video,
+                STREAM_TYPE_AAC => StreamKind::Audio,
+                _ => continue,
+            };
+
+            // Only the first stream of each kind is used
+            if streams.values().any(|x: &PesAssembler| x.kind == kind) {
+                continue;
+            }
+
+            // Keep any data already received if the PMT is just being repeated
+            let assembler = self.streams.remove(&pid).unwrap_or(PesAssembler {
+                kind,
+                data: Vec::new(),
+                continuity_counter: None,
+                is_started: false,
+            });
+
+            streams.insert(pid, assembler);
+        }
+
+        self.streams = streams;
+    }
+
+    fn handle_pes_payload(
+        &mut self,
+        pid: u16,
+        is_unit_start: bool,
+        continuity_counter: u8,
+        payload: &[u8],
+        media: &mut Vec<TsMedia>,
+    ) {
+        if is_unit_start {
+            self.finish_pes(pid, media);
+        }
+
+        let assembler = match self.streams.get_mut(&pid) {
+            Some(assembler) => assembler,
+            None => return,
+        };
+
+        let expected_counter = assembler.continuity_counter.map(|x| (x + 1) & 0x0f);
+        assembler.continuity_counter = Some(continuity_counter);
+        if is_unit_start {
+            assembler.is_started = true;
+            assembler.data.clear();
+        } else if expected_counter != Some(continuity_counter) {
+            // A packet was lost, so the PES packet being assembled is incomplete
+            assembler.is_started = false;
+            assembler.data.clear();
+        }
+
+        if !assembler.is_started {
+            return;
+        }
+
+        assembler.data.extend_from_slice(payload);
+
+        // PES packets that state their length can be finished without waiting for the next one
+        let data = &assembler.data;
+        if data.len() >= 6 {
+            let length = ((data[4] as usize) << 8) | data[5] as usize;
+            if length > 0 && data.len() >= 6 + length {
+                self.finish_pes(pid, media);
+            }
+        }
+    }
+
+    fn finish_pes(&mut self, pid: u16, media: &mut Vec<TsMedia>) {
+        let (kind, data) = match self.streams.get_mut(&pid) {
+            Some(assembler) if assembler.is_started => {
+                assembler.is_started = false;
+                (assembler.kind, std::mem::take(&mut assembler.data))
+            }
+
+            _ => return,
+        };
+
+        let (pts, dts, payload) = match parse_pes(&data) {
+            Some(pes) => pes,
+            None => return,
+        };
+
+        let pts = self.unwrap_timestamp(pts);
+        let dts = dts.map(|dts| self.unwrap_timestamp(dts)).unwrap_or(pts);
+        match kind {
+            StreamKind::Video => self.handle_h264(payload, pts, dts, media),
+            StreamKind::Audio => self.handle_adts(payload, pts, media),
+        }
+    }
+
+    fn handle_h264(&mut self, payload: &[u8], pts: u64, dts: u64, media: &mut Vec<TsMedia>) {
+        let mut is_keyframe = false;
+        let mut frame = BytesMut::new();
+        for nal_unit in split_annex_b(payload) {
+            match nal_unit[0] & 0x1f {
+                NAL_TYPE_SPS => self.sps = Some(nal_unit.to_vec()),
+                NAL_TYPE_PPS => self.pps = Some(nal_unit.to_vec()),
+                NAL_TYPE_ACCESS_UNIT_DELIMITER => (),
+                nal_type => {
+                    is_keyframe |= nal_type == NAL_TYPE_IDR;
+                    frame.put_u32(nal_unit.len() as u32);
+                    frame.put_slice(nal_unit);
+                }
+            }
+        }
+
+        let timestamp = to_rtmp_timestamp(dts);
+        if let (Some(sps), Some(pps)) = (&self.sps, &self.pps) {
+            let config = avc_decoder_config(sps, pps);
+            if self.sent_video_config.as_ref() != Some(&config) {
+                let mut data = BytesMut::with_capacity(5 + config.len());
+                data.put_slice(&[0x17, 0, 0, 0, 0]);
+                data.put_slice(&config);
+                media.push(TsMedia::Video {
+                    data: data.freeze(),
+                    timestamp,
+                });
+
+                self.sent_video_config = Some(config);
+            }
+        }
+
+        if frame.is_empty() || self.sent_video_config.is_none() {
+            return;
+        }
+
+        let composition_time = (pts.saturating_sub(dts) / PTS_TICKS_PER_MILLISECOND) as u32;
+        let mut data = BytesMut::with_capacity(5 + frame.len());
+        data.put_u8(if is_keyframe { 0x17 } else { 0x27 });
+        data.put_u8(1);
+        data.put_uint(composition_time as u64 & 0x00ff_ffff, 3);
+        data.put_slice(&frame);
+
+        media.push(TsMedia::Video {
+            data: data.freeze(),
+            timestamp,
+        });
+    }
+
+    fn handle_adts(&mut self, payload: &[u8], pts: u64, media: &mut Vec<TsMedia>) {
+        let mut remaining = payload;
+        let mut frame_index = 0;
+        while remaining.len() >= 7 && remaining[0] == 0xff && remaining[1] & 0xf0 == 0xf0 {
+            let header_length = if remaining[1] & 0x01 != 0 { 7 } else { 9 };
+            let frame_length = (((remaining[3] & 0x03) as usize) << 11)
+                | ((remaining[4] as usize) << 3)
+                | (remaining[5] as usize >> 5);
+
+            if frame_length <= header_length || frame_length > remaining.len() {
+                break;
+            }
+
+            let object_type = (remaining[2] >> 6) + 1;
+            let sample_rate_index = (remaining[2] >> 2) & 0x0f;
+            let channels = ((remaining[2] & 0x01) << 2) | (remaining[3] >> 6);
+            let sample_rate = match AAC_SAMPLE_RATES.get(sample_rate_index as usize) {
+                Some(rate) => *rate as u64,
+                None => break,
+            };
+
+            let offset = frame_index * AAC_SAMPLES_PER_FRAME * 90_000 / sample_rate;
+            let timestamp = to_rtmp_timestamp(pts + offset);
+
+            let config = [
+                (object_type << 3) | (sample_rate_index >> 1),
+                ((sample_rate_index & 0x01) << 7) | (channels << 3),
+            ];
+
+            if self.sent_audio_config != Some(config) {
+                media.push(TsMedia::Audio {
+                    data: Bytes::from(vec![0xaf, 0, config[0], config[1]]),
+                    timestamp,
+                });
+
+                self.sent_audio_config = Some(config);
+            }
+
+            let mut data = BytesMut::with_capacity(2 + frame_length - header_length);
+            data.put_slice(&[0xaf, 1]);
+            data.put_slice(&remaining[header_length..frame_length]);
+            media.push(TsMedia::Audio {
+                data: data.freeze(),
+                timestamp,
+            });
+
+            remaining = &remaining[frame_length..];
+            frame_index += 1;
+        }
+    }
+
+    /// Extends a 33 bit timestamp so it keeps increasing after wrapping
+    fn unwrap_timestamp(&mut self, timestamp: u64) -> u64 {
+        let unwrapped = match self.last_timestamp {
+            None => timestamp,
+            Some(last) => {
+                let candidate = (last & !(PTS_WRAP - 1)) | timestamp;
+                if candidate + PTS_WRAP / 2 < last {
+                    candidate + PTS_WRAP
+                } else if candidate > last + PTS_WRAP / 2 && candidate >= PTS_WRAP {
+                    candidate - PTS_WRAP
+                } else {
+                    candidate
+                }
+            }
+        };
+
+        self.last_timestamp = Some(unwrapped);
+        unwrapped
+    }
+}
+
+impl Default for TsDemuxer {
+    fn default() -> Self {
+        TsDemuxer::new()
+    }
+}
+
+/// Returns the section a PSI payload carries, up to but not including its CRC
+fn psi_section(payload: &[u8]) -> Option<&[u8]> {
+    let pointer = *payload.first()? as usize;
+    let section = payload.get(1 + pointer..)?;
+    if section.len() < 3 {
+        return None;
+    }
+
+    let section_length = (((section[1] & 0x0f) as usize) << 8) | section[2] as usize;
+    if section_length < 9 || section.len() < 3 + section_length {
+        return None;
+    }
+
+    Some(&section[..3 + section_length - 4])
+}
+
+/// Returns the PTS, DTS, and payload of a PES packet
+fn parse_pes(data: &[u8]) -> Option<(u64, Option<u64>, &[u8])> {
+    if data.len() < 9 || data[0..3] != [0, 0, 1] {
+        return None;
+    }
+
+    let flags = data[7] >> 6;
+    let header_length = data[8] as usize;
+    let payload_start = 9 + header_length;
+    if data.len() < payload_start || flags & 0b10 == 0 || header_length < 5 {
+        return None;
+    }
+
+    let pts = parse_pes_timestamp(&data[9..14]);
+    let dts = if flags == 0b11 && header_length >= 10 {
+        Some(parse_pes_timestamp(&data[14..19]))
+    } else {
+        None
+    };
+
+    let length = ((data[4] as usize) << 8) | data[5] as usize;
+    let end = if length > 0 {
+        (6 + length).min(data.len())
+    } else {
+        data.len()
+    };
+
+    Some((pts, dts, &data[payload_start.min(end)..end]))
+}
+
+fn parse_pes_timestamp(bytes: &[u8]) -> u64 {
+    (((bytes[0] >> 1) & 0x07) as u64) << 30
+        | (bytes[1] as u64) << 22
+        | ((bytes[2] >> 1) as u64) << 15
+        | (bytes[3] as u64) << 7
+        | (bytes[4] >> 1) as u64
+}
+
+fn to_rtmp_timestamp(timestamp: u64) -> RtmpTimestamp {
+    RtmpTimestamp::new((timestamp / PTS_TICKS_PER_MILLISECOND) as u32)
+}
+
+/// Splits an Annex B byte stream into its NAL units
+fn split_annex_b(data: &[u8]) -> Vec<&[u8]> {
+    let mut nal_units = Vec::new();
+    let mut start = None;
+    let mut index = 0;
+    while index + 3 <= data.len() {
+        if data[index] == 0 && data[index + 1] == 0 && data[index + 2] == 1 {
+            if let Some(start) = start {
+                nal_units.push(trim_trailing_zeros(&data[start..index]));
+            }
+
+            index += 3;
+            start = Some(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    if let Some(start) = start {
+        nal_units.push(trim_trailing_zeros(&data[start..]));
+    }
+
+    nal_units.retain(|nal_unit| !nal_unit.is_empty());
+    nal_units
+}
+
+fn trim_trailing_zeros(data: &[u8]) -> &[u8] {
+    let end = data.iter().rposition(|x| *x != 0).map_or(0, |x| x + 1);
+    &data[..end]
+}
+
+fn avc_decoder_config(sps: &[u8], pps: &[u8]) -> Vec<u8> {
+    let mut config = Vec::with_capacity(11 + sps.len() + pps.len());
+    config.push(1);
+    config.extend_from_slice(sps.get(1..4).unwrap_or(&[0, 0, 0]));
+    config.push(0xff);
+    config.push(0xe1);
+    config.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+    config.extend_from_slice(sps);
+    config.push(1);
+    config.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+    config.extend_from_slice(pps);
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PMT_PID: u16 = 0x1000;
+    const VIDEO_PID: u16 = 0x100;
+    const AUDIO_PID: u16 = 0x101;
+
+    fn ts_packet(pid: u16, is_unit_start: bool, counter: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![
+            SYNC_BYTE,
+            ((is_unit_start as u8) << 6) | (pid >> 8) as u8,
+            pid as u8,
+            0x10 | (counter & 0x0f),
+        ];
+
+        // Pad with an adaptation field so the payload ends the packet
+        let padding = TS_PACKET_SIZE - 4 - payload.len();
+        if padding > 0 {
+            packet[3] |= 0x20;
+            packet.push(padding as u8 - 1);
+            if padding > 1 {
+                packet.push(0);
+                packet.extend(std::iter::repeat_n(0xff, padding - 2));
+            }
+        }
+
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn psi(table_id: u8, body: &[u8]) -> Vec<u8> {
+        let section_length = 5 + body.len() + 4;
+        let mut payload = vec![
+            0,
+            table_id,
+            0xb0 | (section_length >> 8) as u8,
+            section_length as u8,
+        ];
+        payload.extend_from_slice(&[0, 1, 0xc1, 0, 0]);
+        payload.extend_from_slice(body);
+        payload.extend_from_slice(&[0, 0, 0, 0]);
+        payload
+    }
+
+    fn program_tables() -> Vec<u8> {
+        let pat = psi(0, &[0, 1, 0xe0 | (PMT_PID >> 8) as u8, PMT_PID as u8]);
+        let pmt = psi(
+            2,
+            &[
+                0xe1,
+                0x00,
+                0xf0,
+                0x00,
+                STREAM_TYPE_H264,
+                0xe1,
+                0x00,
+                0xf0,
+                0x00,
+                STREAM_TYPE_AAC,
+                0xe1,
+                0x01,
+                0xf0,
+                0x00,
+            ],
+        );
+
+        let mut bytes = ts_packet(PAT_PID, true, 0, &pat);
+        bytes.extend(ts_packet(PMT_PID, true, 0, &pmt));
+        bytes
+    }
+
+    fn pes_timestamp(marker: u8, timestamp: u64) -> [u8; 5] {
+        [
+            (marker << 4) | (((timestamp >> 30) as u8 & 0x07) << 1) | 1,
+            (timestamp >> 22) as u8,
+            (((timestamp >> 15) as u8) << 1) | 1,
+            (timestamp >> 7) as u8,
+            ((timestamp as u8) << 1) | 1,
+        ]
+    }
+
+    fn pes(stream_id: u8, pts: u64, dts: u64, payload: &[u8], bounded: bool) -> Vec<u8> {
+        let mut header = vec![0x80, 0xc0, 10];
+        header.extend_from_slice(&pes_timestamp(0b0011, pts));
+        header.extend_from_slice(&pes_timestamp(0b0001, dts));
+
+        let length = if bounded {
+            header.len() + payload.len()
+        } else {
+            0
+        };
+        let mut pes = vec![0, 0, 1, stream_id, (length >> 8) as u8, length as u8];
+        pes.extend(header);
+        pes.extend_from_slice(payload);
+        pes
+    }
+
+    #[test]
+    fn h264_access_units_become_rtmp_video() {
+        let mut demuxer = TsDemuxer::new();
+        let access_unit = [
+            0, 0, 0, 1, 0x09, 0xf0, 0, 0, 0, 1, 0x67, 0x64, 0x00, 0x1f, 0xac, 0, 0, 0, 1, 0x68,
+            0xee, 0x3c, 0x80, 0, 0, 1, 0x65, 0x88, 0x84,
+        ];
+
+        let mut bytes = program_tables();
+        bytes.extend(ts_packet(
+            VIDEO_PID,
+            true,
+            0,
+            &pes(0xe0, 93_000, 90_000, &access_unit, false),
+        ));
+        assert!(demuxer.push(&bytes).is_empty());
+
+        let media = demuxer.flush();
+
+        assert_eq!(
+            media[0],
+            TsMedia::Video {
+                data: Bytes::from(vec![
+                    0x17, 0, 0, 0, 0, 1, 0x64, 0x00, 0x1f, 0xff, 0xe1, 0, 5, 0x67, 0x64, 0x00,
+                    0x1f, 0xac, 1, 0, 4, 0x68, 0xee, 0x3c, 0x80,
+                ]),
+                timestamp: RtmpTimestamp::new(1000),
+            }
+        );
+
+        assert_eq!(
+            media[1],
+            TsMedia::Video {
+                data: Bytes::from(vec![0x17, 1, 0, 0, 33, 0, 0, 0, 3, 0x65, 0x88, 0x84]),
+                timestamp: RtmpTimestamp::new(1000),
+            }
+        );
+    }
+
+    #[test]
+    fn adts_frames_become_rtmp_audio() {
+        let mut demuxer = TsDemuxer::new();
+
+        // Two AAC LC frames at 48kHz in stereo
+        let frame = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x3f, 0xfc, 0xaa, 0xbb];
+        let mut payload = frame.to_vec();
+        payload.extend_from_slice(&frame);
+
+        let mut bytes = program_tables();
+        bytes.extend(ts_packet(
+            AUDIO_PID,
+            true,
+            0,
+            &pes(0xc0, 90_000, 90_000, &payload, true),
+        ));
+
+        let media = demuxer.push(&bytes);
+
+        assert_eq!(
+            media,
+            vec![
+                TsMedia::Audio {
+                    data: Bytes::from(vec![0xaf, 0, 0x11, 0x90]),
+                    timestamp: RtmpTimestamp::new(1000),
+                },
+                TsMedia::Audio {
+                    data: Bytes::from(vec![0xaf, 1, 0xaa, 0xbb]),
+                    timestamp: RtmpTimestamp::new(1000),
+                },
+                TsMedia::Audio {
+                    data: Bytes::from(vec![0xaf, 1, 0xaa, 0xbb]),
+                    timestamp: RtmpTimestamp::new(1021),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pes_with_lost_packet_is_discarded() {
+        let mut demuxer = TsDemuxer::new();
+        let frame = [0xff, 0xf1, 0x4c, 0x80, 0x01, 0x3f, 0xfc, 0xaa, 0xbb];
+        let pes = pes(0xc0, 90_000, 90_000, &frame, true);
+
+        let mut bytes = program_tables();
+        bytes.extend(ts_packet(AUDIO_PID, true, 0, &pes[..10]));
+        bytes.extend(ts_packet(AUDIO_PID, false, 2, &pes[10..]));
+
+        assert!(demuxer.push(&bytes).is_empty());
+    }
+
+    #[test]
+    fn timestamps_continue_past_33_bit_wrap() {
+        let mut demuxer = TsDemuxer::new();
+
+        let before = demuxer.unwrap_timestamp(PTS_WRAP - 90);
+        let after = demuxer.unwrap_timestamp(90);
+
+        assert_eq!(after - before, 180);
+    }
+}
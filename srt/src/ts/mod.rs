@@ -0,0 +1,61 @@
+/*!
+This module converts between MPEG transport streams, which is what SRT contribution feeds
+carry, and the audio and video payloads RTMP uses.
+
+The `TsDemuxer` turns H.264 and AAC carried in a transport stream into the same payloads a
+`ServerSession` raises for an RTMP publisher, so streams received over SRT can be published into
+a `StreamHub` alongside RTMP streams.
+
+Going the other way, the `TsMuxer` turns RTMP payloads into a transport stream, and the
+`TsStreamSink` paces its output so a published RTMP stream can be sent to broadcast equipment
+or FFmpeg over UDP or SRT.
+*/
+
+mod demuxer;
+mod muxer;
+mod sink;
+
+pub use self::demuxer::TsDemuxer;
+pub use self::muxer::TsMuxer;
+pub use self::sink::{TsStreamSink, TsStreamSinkConfig};
+
+use bytes::Bytes;
+use rml_rtmp::hub::{StreamHub, StreamHubError, StreamHubResult};
+use rml_rtmp::time::RtmpTimestamp;
+
+/// The size of every transport stream packet
+pub const TS_PACKET_SIZE: usize = 188;
+
+/// Audio or video taken out of a transport stream, in the format of an RTMP audio or video
+/// message payload
+#[derive(PartialEq, Debug, Clone)]
+pub enum TsMedia {
+    Video {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+    Audio {
+        data: Bytes,
+        timestamp: RtmpTimestamp,
+    },
+}
+
+impl TsMedia {
+    /// Publishes the media into a `StreamHub` on behalf of a connection that has joined it as
+    /// a publisher
+    pub fn publish(
+        self,
+        hub: &mut StreamHub,
+        connection_id: usize,
+    ) -> Result<Vec<StreamHubResult>, StreamHubError> {
+        match self {
+            TsMedia::Video { data, timestamp } => {
+                hub.publish_video_data(connection_id, data, timestamp)
+            }
+
+            TsMedia::Audio { data, timestamp } => {
+                hub.publish_audio_data(connection_id, data, timestamp)
+            }
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use super::TS_PACKET_SIZE;
+use bytes::{BufMut, Bytes, BytesMut};
+use rml_flv::codecs::AvcDecoderConfig;
+use rml_flv::{
+    AacPacketType, AudioTagHeader, AvcPacketType, SoundFormat, VideoCodec, VideoTagHeader,
+};
+use rml_rtmp::time::RtmpTimestamp;
+use std::collections::HashMap;
+
+const PMT_PID: u16 = 0x1000;
+const VIDEO_PID: u16 = 0x100;
+const AUDIO_PID: u16 = 0x101;
+
+const STREAM_TYPE_AAC: u8 = 0x0f;
+const STREAM_TYPE_H264: u8 = 0x1b;
+
+const PAT_PID: u16 = 0;
+const VIDEO_STREAM_ID: u8 = 0xe0;
+const AUDIO_STREAM_ID: u8 = 0xc0;
+const PAYLOAD_SIZE: usize = TS_PACKET_SIZE - 4;
+
+/// How far timestamps are placed ahead of the clock reference, giving decoders time to
+/// receive each frame before it's due
+const DECODE_DELAY: u64 = 90_000 / 2;
+
+/// How often the program tables are repeated (in 90kHz ticks) when there are no keyframes
+const TABLE_INTERVAL: u64 = 90_000 / 2;
+
+const ACCESS_UNIT_DELIMITER: [u8; 6] = [0, 0, 0, 1, 0x09, 0xf0];
+
+struct VideoConfig {
+    nal_unit_length_size: usize,
+    parameter_sets: Vec<u8>,
+}
+
+/// Converts the audio and video payloads of an RTMP stream into an MPEG transport stream.
+///
+/// This is the reverse of the `TsDemuxer`: H.264 video is converted to an Annex B byte stream
+/// with the SPS and PPS repeated before every keyframe, and AAC audio is given ADTS headers.
+/// The program tables are sent before every keyframe (and at least twice a second), so
+/// receivers can start decoding from any keyframe.  Other codecs are ignored.
+///
+/// Each call returns the transport stream packets for that payload, which is empty for sequence
+/// headers and anything that comes before them.
+///
+/// # Examples
+/// ```
+/// # extern crate rml_rtmp;
+/// # extern crate rml_srt;
+/// use rml_rtmp::time::RtmpTimestamp;
+/// use rml_srt::ts::{TsMuxer, TS_PACKET_SIZE};
+///
+/// # fn main() {
+/// let mut muxer = TsMuxer::new();
+/// let sequence_header = [0xaf, 0, 0x12, 0x10];
+/// let frame = [0xaf, 1, 0x21, 0x10, 0x04];
+///
+/// assert!(muxer.push_audio(&sequence_header, RtmpTimestamp::new(0)).is_empty());
+///
+/// let packets = muxer.push_audio(&frame, RtmpTimestamp::new(0));
+/// assert_eq!(packets.len() % TS_PACKET_SIZE, 0);
+/// # }
+/// ```
+pub struct TsMuxer {
+    video_config: Option<VideoConfig>,
+    audio_config: Option<[u8; 2]>,
+    continuity_counters: HashMap<u16, u8>,
+    last_tables_at: Option<u64>,
+    table_streams: (bool, bool),
+}
+
+impl TsMuxer {
+    /// Creates a muxer for a stream that hasn't received any sequence headers yet
+    pub fn new() -> TsMuxer {
+        TsMuxer {
+            video_config: None,
+            audio_config: None,
+            continuity_counters: HashMap::new(),
+            last_tables_at: None,
+            table_streams: (false, false),
+        }
+    }
+
+    /// Converts the payload of an RTMP video message into transport stream packets
+    pub fn push_video(&mut self, data: &[u8], timestamp: RtmpTimestamp) -> Bytes {
+        let header = match VideoTagHeader::parse(data) {
+            Some(header) if header.codec == VideoCodec::Avc => header,
+            _ => return Bytes::new(),
+        };
+
+        match header.avc_packet_type {
+            Some(AvcPacketType::SequenceHeader) => {
+                if let Ok(config) = AvcDecoderConfig::parse(&data[5..]) {
+                    let mut parameter_sets = Vec::new();
+                    let sets = config
+                        .sequence_parameter_sets
+                        .iter()
+                        .chain(config.picture_parameter_sets.iter());
+
+                    for set in sets {
+                        parameter_sets.extend_from_slice(&[0, 0, 0, 1]);
+                        parameter_sets.extend_from_slice(set);
+                    }
+
+                    self.video_config = Some(VideoConfig {
+                        nal_unit_length_size: config.nal_unit_length_size as usize,
+                        parameter_sets,
+                    });
+                }
+
+                return Bytes::new();
+            }
+
+            Some(AvcPacketType::Nalu) => (),
+            _ => return Bytes::new(),
+        }
+
+        let is_keyframe = header.is_keyframe();
+        let mut frame = ACCESS_UNIT_DELIMITER.to_vec();
+        match self.video_config {
+            Some(ref config) => {
+                if is_keyframe {
+                    frame.extend_from_slice(&config.parameter_sets);
+                }
+
+                let mut remaining = &data[5..];
+                let length_size = config.nal_unit_length_size;
+                while remaining.len() > length_size {
+                    let length = remaining[..length_size]
+                        .iter()
+                        .fold(0, |length, byte| (length << 8) | *byte as usize);
+
+                    let end = (length_size + length).min(remaining.len());
+                    frame.extend_from_slice(&[0, 0, 0, 1]);
+                    frame.extend_from_slice(&remaining[length_size..end]);
+                    remaining = &remaining[end..];
+                }
+            }
+
+            None => return Bytes::new(),
+        }
+
+        let dts = timestamp.value as u64 * 90 + DECODE_DELAY;
+        let pts = (dts as i64 + header.composition_time as i64 * 90).max(0) as u64;
+        let pcr = dts - DECODE_DELAY;
+
+        let mut output = BytesMut::new();
+        self.write_tables_if_due(pcr, is_keyframe, &mut output);
+
+        let pes = pes_packet(VIDEO_STREAM_ID, pts, Some(dts), &frame);
+        self.write_pes(VIDEO_PID, &pes, Some(pcr), is_keyframe, &mut output);
+        output.freeze()
+    }
+
+    /// Converts the payload of an RTMP audio message into transport stream packets
+    pub fn push_audio(&mut self, data: &[u8], timestamp: RtmpTimestamp) -> Bytes {
+        let header = match AudioTagHeader::parse(data) {
+            Some(header) if header.sound_format == SoundFormat::Aac => header,
+            _ => return Bytes::new(),
+        };
+
+        let config = match header.aac_packet_type {
+            Some(AacPacketType::SequenceHeader) => {
+                if data.len() >= 4 {
+                    self.audio_config = Some([data[2], data[3]]);
+                }
+
+                return Bytes::new();
+            }
+
+            Some(AacPacketType::Raw) => match self.audio_config {
+                Some(config) => config,
+                None => return Bytes::new(),
+            },
+
+            _ => return Bytes::new(),
+        };
+
+        let object_type = config[0] >> 3;
+        let sample_rate_index = ((config[0] & 0x07) << 1) | (config[1] >> 7);
+        let channels = (config[1] >> 3) & 0x0f;
+        let frame_length = data.len() - 2 + 7;
+
+        let mut frame = Vec::with_capacity(frame_length);
+        frame.extend_from_slice(&[
+            0xff,
+            0xf1,
+            (object_type.saturating_sub(1) << 6) | (sample_rate_index << 2) | (channels >> 2),
+            ((channels & 0x03) << 6) | (frame_length >> 11) as u8,
+            (frame_length >> 3) as u8,
+            ((frame_length as u8 & 0x07) << 5) | 0x1f,
+            0xfc,
+        ]);
+
+        frame.extend_from_slice(&data[2..]);
+
+        let pts = timestamp.value as u64 * 90 + DECODE_DELAY;
+        let pcr = pts - DECODE_DELAY;
+
+        // Audio carries the clock reference when there is no video
+        let is_clock_source = self.video_config.is_none();
+        let mut output = BytesMut::new();
+        self.write_tables_if_due(pcr, false, &mut output);
+
+        let pes = pes_packet(AUDIO_STREAM_ID, pts, None, &frame);
+        let pcr = if is_clock_source { Some(pcr) } else { None };
+        self.write_pes(AUDIO_PID, &pes, pcr, is_clock_source, &mut output);
+        output.freeze()
+    }
+
+    fn write_tables_if_due(&mut self, clock: u64, is_keyframe: bool, output: &mut BytesMut) {
+        let streams = (self.video_config.is_some(), self.audio_config.is_some());
+        let is_due = match self.last_tables_at {
+            Some(last) => clock < last || clock - last >= TABLE_INTERVAL,
+            None => true,
+        };
+
+        if !is_due && !is_keyframe && streams == self.table_streams {
+            return;
+        }
+
+        self.last_tables_at = Some(clock);
+        self.table_streams = streams;
+
+        let mut pat = BytesMut::new();
+        pat.put_u16(1);
+        pat.put_u16(0xe000 | PMT_PID);
+        self.write_section(PAT_PID, 0x00, 1, &pat, output);
+
+        let pcr_pid = if streams.0 { VIDEO_PID } else { AUDIO_PID };
+        let mut pmt = BytesMut::new();
+        pmt.put_u16(0xe000 | pcr_pid);
+        pmt.put_u16(0xf000);
+        if streams.0 {
+            pmt.put_u8(STREAM_TYPE_H264);
+            pmt.put_u16(0xe000 | VIDEO_PID);
+            pmt.put_u16(0xf000);
+        }
+
+        if streams.1 {
+            pmt.put_u8(STREAM_TYPE_AAC);
+            pmt.put_u16(0xe000 | AUDIO_PID);
+            pmt.put_u16(0xf000);
+        }
+
+        self.write_section(PMT_PID, 0x02, 1, &pmt, output);
+    }
+
+    fn write_section(
+        &mut self,
+        pid: u16,
+        table_id: u8,
+        table_id_extension: u16,
+        body: &[u8],
+        output: &mut BytesMut,
+    ) {
+        let section_length = 5 + body.len() + 4;
+        let mut section = BytesMut::with_capacity(3 + section_length);
+        section.put_u8(table_id);
+        section.put_u16(0xb000 | section_length as u16);
+        section.put_u16(table_id_extension);
+        section.put_slice(&[0xc1, 0, 0]);
+        section.put_slice(body);
+        let crc = crc32(&section);
+        section.put_u32(crc);
+
+        let mut payload = vec![0];
+        payload.extend_from_slice(&section);
+        payload.resize(PAYLOAD_SIZE, 0xff);
+
+        let counter = self.next_continuity_counter(pid);
+        output.put_slice(&[0x47, 0x40 | (pid >> 8) as u8, pid as u8, 0x10 | counter]);
+        output.put_slice(&payload);
+    }
+
+    fn write_pes(
+        &mut self,
+        pid: u16,
+        pes: &[u8],
+        pcr: Option<u64>,
+        is_random_access: bool,
+        output: &mut BytesMut,
+    ) {
+        let mut remaining = pes;
+        let mut is_first = true;
+        while !remaining.is_empty() {
+            let mut adaptation_field = Vec::new();
+            if is_first && (pcr.is_some() || is_random_access) {
+                let mut flags = 0;
+                if is_random_access {
+                    flags |= 0x40;
+                }
+
+                adaptation_field.push(flags);
+                if let Some(pcr) = pcr {
+                    adaptation_field[0] |= 0x10;
+                    adaptation_field.extend_from_slice(&[
+                        (pcr >> 25) as u8,
+                        (pcr >> 17) as u8,
+                        (pcr >> 9) as u8,
+                        (pcr >> 1) as u8,
+                        ((pcr as u8 & 0x01) << 7) | 0x7e,
+                        0,
+                    ]);
+                }
+            }
+
+            let mut adaptation_length = match adaptation_field.len() {
+                0 => 0,
+                x => x + 1,
+            };
+
+            // The last packet is padded out with stuffing bytes in the adaptation field
+            let space = PAYLOAD_SIZE - adaptation_length;
+            if remaining.len() < space {
+                let stuffing = space - remaining.len();
+                if adaptation_length == 0 && stuffing > 1 {
+                    adaptation_field.push(0);
+                    adaptation_field.resize(stuffing - 1, 0xff);
+                } else {
+                    adaptation_field.resize(adaptation_field.len() + stuffing, 0xff);
+                }
+
+                adaptation_length += stuffing;
+            }
+
+            let counter = self.next_continuity_counter(pid);
+            let mut flags = 0x10 | counter;
+            if adaptation_length > 0 {
+                flags |= 0x20;
+            }
+
+            let unit_start = if is_first { 0x40 } else { 0 };
+            output.put_slice(&[0x47, unit_start | (pid >> 8) as u8, pid as u8, flags]);
+            if adaptation_length > 0 {
+                output.put_u8(adaptation_length as u8 - 1);
+                output.put_slice(&adaptation_field[..adaptation_length - 1]);
+            }
+
+            let length = PAYLOAD_SIZE - adaptation_length;
+            output.put_slice(&remaining[..length]);
+            remaining = &remaining[length..];
+            is_first = false;
+        }
+    }
+
+    fn next_continuity_counter(&mut self, pid: u16) -> u8 {
+        let counter = self.continuity_counters.entry(pid).or_insert(0x0f);
+        *counter = (*counter + 1) & 0x0f;
+        *counter
+    }
+}
+
+impl Default for TsMuxer {
+    fn default() -> Self {
+        TsMuxer::new()
+    }
+}
+
+fn pes_packet(stream_id: u8, pts: u64, dts: Option<u64>, payload: &[u8]) -> Vec<u8> {
+    let header_length = if dts.is_some() { 10 } else { 5 };
+    let packet_length = 3 + header_length + payload.len();
+
+    let mut pes = Vec::with_capacity(6 + packet_length);
+    pes.extend_from_slice(&[0, 0, 1, stream_id]);
+
+    // Video frames can be larger than the length field allows, so it's left unbounded
+    if stream_id == VIDEO_STREAM_ID || packet_length > 0xffff {
+        pes.extend_from_slice(&[0, 0]);
+    } else {
+        pes.extend_from_slice(&(packet_length as u16).to_be_bytes());
+    }
+
+    pes.push(0x80);
+    match dts {
+        Some(dts) => {
+            pes.extend_from_slice(&[0xc0, header_length as u8]);
+            pes.extend_from_slice(&pes_timestamp(0b0011, pts));
+            pes.extend_from_slice(&pes_timestamp(0b0001, dts));
+        }
+
+        None => {
+            pes.extend_from_slice(&[0x80, header_length as u8]);
+            pes.extend_from_slice(&pes_timestamp(0b0010, pts));
+        }
+    }
+
+    pes.extend_from_slice(payload);
+    pes
+}
+
+fn pes_timestamp(prefix: u8, timestamp: u64) -> [u8; 5] {
+    [
+        (prefix << 4) | (((timestamp >> 30) as u8 & 0x07) << 1) | 1,
+        (timestamp >> 22) as u8,
+        ((timestamp >> 14) as u8 & 0xfe) | 1,
+        (timestamp >> 7) as u8,
+        ((timestamp << 1) as u8) | 1,
+    ]
+}
+
+/// The CRC used by MPEG-2 program specific information tables
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+    for byte in data {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{TsDemuxer, TsMedia};
+    use super::*;
+
+    #[test]
+    fn muxed_stream_demuxes_to_same_payloads() {
+        let sequence_header = Bytes::from(vec![
+            0x17, 0, 0, 0, 0, 1, 0x64, 0x00, 0x1f, 0xff, 0xe1, 0, 5, 0x67, 0x64, 0x00, 0x1f, 0xac,
+            1, 0, 4, 0x68, 0xee, 0x3c, 0x80,
+        ]);
+
+        let mut keyframe = vec![0x17, 1, 0, 0, 40, 0, 0, 1, 0x2c, 0x65];
+        keyframe.extend(std::iter::repeat_n(0x88, 299));
+
+        let audio_header = Bytes::from(vec![0xaf, 0, 0x11, 0x90]);
+        let audio_frame = Bytes::from(vec![0xaf, 1, 0x21, 0x10, 0x04]);
+
+        let mut muxer = TsMuxer::new();
+        let mut stream = Vec::new();
+        stream.extend_from_slice(&muxer.push_video(&sequence_header, RtmpTimestamp::new(0)));
+        stream.extend_from_slice(&muxer.push_audio(&audio_header, RtmpTimestamp::new(0)));
+        stream.extend_from_slice(&muxer.push_video(&keyframe, RtmpTimestamp::new(1000)));
+        stream.extend_from_slice(&muxer.push_audio(&audio_frame, RtmpTimestamp::new(1010)));
+
+        assert_eq!(stream.len() % TS_PACKET_SIZE, 0);
+
+        let mut demuxer = TsDemuxer::new();
+        let mut media = demuxer.push(&stream);
+        media.extend(demuxer.flush());
+
+        // Timestamps come back shifted by the decode delay between the clock reference and the
+        // timestamps of each frame
+        assert_eq!(
+            media,
+            vec![
+                TsMedia::Audio {
+                    data: audio_header,
+                    timestamp: RtmpTimestamp::new(1510),
+                },
+                TsMedia::Audio {
+                    data: audio_frame,
+                    timestamp: RtmpTimestamp::new(1510),
+                },
+                TsMedia::Video {
+                    data: sequence_header,
+                    timestamp: RtmpTimestamp::new(1500),
+                },
+                TsMedia::Video {
+                    data: Bytes::from(keyframe),
+                    timestamp: RtmpTimestamp::new(1500),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn crc_matches_mpeg2_reference() {
+        assert_eq!(crc32(b"123456789"), 0x0376_e6e7);
+    }
+}
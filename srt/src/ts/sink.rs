@@ -0,0 +1,164 @@
+use super::{TsMuxer, TS_PACKET_SIZE};
+use bytes::{Bytes, BytesMut};
+use rml_rtmp::playback::{BufferedMedia, PlaybackBuffer, PlaybackBufferConfig};
+use rml_rtmp::time::RtmpTimestamp;
+use std::time::Instant;
+
+/// Configuration options for a `TsStreamSink`
+#[derive(Debug, Clone)]
+pub struct TsStreamSinkConfig {
+    /// How media is buffered before it's sent.  The target latency evens out the jitter of the
+    /// RTMP publisher, so the transport stream is sent at the rate it's played back.
+    pub buffer: PlaybackBufferConfig,
+
+    /// How many transport stream packets are put in each payload.  The default of 7 fills a
+    /// 1316 byte payload, which fits in a single UDP datagram or SRT packet.
+    pub packets_per_payload: usize,
+}
+
+impl TsStreamSinkConfig {
+    /// Creates a configuration with the default playback buffer and 7 packets per payload
+    pub fn new() -> TsStreamSinkConfig {
+        TsStreamSinkConfig {
+            buffer: PlaybackBufferConfig::new(),
+            packets_per_payload: 7,
+        }
+    }
+}
+
+impl Default for TsStreamSinkConfig {
+    fn default() -> Self {
+        TsStreamSinkConfig::new()
+    }
+}
+
+/// Turns an RTMP stream into a paced MPEG transport stream, for sending to broadcast equipment
+/// or FFmpeg over plain UDP or SRT.
+///
+/// Audio and video received from an RTMP publisher (or a `StreamHub` subscription) is held in
+/// a `PlaybackBuffer`, and is only muxed into the transport stream once it's due.  Receivers
+/// of a UDP transport stream expect it to arrive at the rate it's played back, and bursts from
+/// the publisher would otherwise overflow their buffers.
+///
+/// Like the rest of this crate the sink does no networking or timing itself.  The application
+/// calls `poll()` at the time given by `next_send_at()` and sends every payload it returns,
+/// either in its own UDP datagram or with `SrtCaller::send()`.
+///
+/// # Examples
+/// ```
+/// # extern crate bytes;
+/// # extern crate rml_rtmp;
+/// # extern crate rml_srt;
+/// use bytes::Bytes;
+/// use rml_rtmp::time::RtmpTimestamp;
+/// use rml_srt::ts::{TsStreamSink, TsStreamSinkConfig};
+/// use std::time::{Duration, Instant};
+///
+/// # fn main() {
+/// let start = Instant::now();
+/// let mut sink = TsStreamSink::new(TsStreamSinkConfig::new());
+/// sink.push_audio(Bytes::from(vec![0xaf, 0, 0x12, 0x10]), RtmpTimestamp::new(0), start);
+/// sink.push_audio(Bytes::from(vec![0xaf, 1, 0x21, 0x10]), RtmpTimestamp::new(0), start);
+///
+/// assert!(sink.poll(start).is_empty());
+///
+/// let start = sink.next_send_at().unwrap();
+/// for payload in sink.poll(start) {
+///     // socket.send_to(&payload, destination)
+/// #   assert!(payload.len() <= 1316);
+/// }
+/// # }
+/// ```
+pub struct TsStreamSink {
+    buffer: PlaybackBuffer,
+    muxer: TsMuxer,
+    payload_size: usize,
+}
+
+impl TsStreamSink {
+    /// Creates a sink that hasn't received any media yet
+    pub fn new(config: TsStreamSinkConfig) -> TsStreamSink {
+        TsStreamSink {
+            buffer: PlaybackBuffer::new(config.buffer),
+            muxer: TsMuxer::new(),
+            payload_size: config.packets_per_payload.max(1) * TS_PACKET_SIZE,
+        }
+    }
+
+    /// Buffers the payload of an RTMP video message
+    pub fn push_video(&mut self, data: Bytes, timestamp: RtmpTimestamp, now: Instant) {
+        self.buffer.push_video(data, timestamp, now);
+    }
+
+    /// Buffers the payload of an RTMP audio message
+    pub fn push_audio(&mut self, data: Bytes, timestamp: RtmpTimestamp, now: Instant) {
+        self.buffer.push_audio(data, timestamp, now);
+    }
+
+    /// Muxes the media that's due to be sent, and returns the payloads to send
+    pub fn poll(&mut self, now: Instant) -> Vec<Bytes> {
+        let mut stream = BytesMut::new();
+        for media in self.buffer.pop_ready(now) {
+            let packets = match media {
+                BufferedMedia::Video { data, timestamp } => self.muxer.push_video(&data, timestamp),
+                BufferedMedia::Audio { data, timestamp } => self.muxer.push_audio(&data, timestamp),
+                BufferedMedia::Metadata(_) => continue,
+            };
+
+            stream.extend_from_slice(&packets);
+        }
+
+        let mut stream = stream.freeze();
+        let mut payloads = Vec::new();
+        while !stream.is_empty() {
+            let size = self.payload_size.min(stream.len());
+            payloads.push(stream.split_to(size));
+        }
+
+        payloads
+    }
+
+    /// When `poll()` next has payloads to send, if any media is buffered
+    pub fn next_send_at(&self) -> Option<Instant> {
+        self.buffer.next_release_at()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn media_is_sent_when_due_in_whole_packets() {
+        let start = Instant::now();
+        let mut config = TsStreamSinkConfig::new();
+        config.buffer.target_latency = Duration::from_millis(100);
+        config.packets_per_payload = 2;
+
+        let mut sink = TsStreamSink::new(config);
+        let header = Bytes::from(vec![0xaf, 0, 0x12, 0x10]);
+        sink.push_audio(header, RtmpTimestamp::new(0), start);
+        for timestamp in &[0, 21, 42] {
+            let frame = Bytes::from(vec![0xaf, 1, 0x21, 0x10, 0x04]);
+            sink.push_audio(frame, RtmpTimestamp::new(*timestamp), start);
+        }
+
+        assert!(sink.poll(start + Duration::from_millis(99)).is_empty());
+
+        // The first frame is sent with the program tables, taking 3 packets
+        let payloads = sink.poll(start + Duration::from_millis(100));
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0].len(), 2 * TS_PACKET_SIZE);
+        assert_eq!(payloads[1].len(), TS_PACKET_SIZE);
+        assert_eq!(
+            sink.next_send_at(),
+            Some(start + Duration::from_millis(121))
+        );
+
+        let payloads = sink.poll(start + Duration::from_millis(142));
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(payloads[0].len(), 2 * TS_PACKET_SIZE);
+        assert_eq!(sink.next_send_at(), None);
+    }
+}
@@ -9,7 +9,7 @@ fn main() {
     let mut args: Vec<String> = env::args().collect();
     args.drain(0..1); // remove the executable
 
-    if args.len() == 0 || ((args[0] != "client" && args.len() < 2) && args[0] != "server") {
+    if args.is_empty() || ((args[0] != "client" && args.len() < 2) && args[0] != "server") {
         println!("No arguments provided.  One of the following must be provided");
         println!("Act as a server: server");
         println!("Act as a client: client <server host>");
@@ -24,7 +24,7 @@ fn act_as_client(host_address: &str) {
     let mut stream = TcpStream::connect(host_address).unwrap();
     let mut handshake = Handshake::new(PeerType::Client);
     let c0_and_c1 = handshake.generate_outbound_p0_and_p1().unwrap();
-    stream.write(&c0_and_c1).unwrap();
+    stream.write_all(&c0_and_c1).unwrap();
 
     let mut read_buffer = [0_u8; 1024];
 
@@ -38,12 +38,15 @@ fn act_as_client(host_address: &str) {
                 }) => (false, bytes),
                 Ok(HandshakeProcessResult::Completed {
                     response_bytes: bytes,
-                    remaining_bytes: _,
-                }) => (true, bytes),
+                    completion,
+                }) => {
+                    println!("Completion details: {:?}", completion);
+                    (true, bytes)
+                }
             };
 
-        if response_bytes.len() > 0 {
-            stream.write(&response_bytes).unwrap();
+        if !response_bytes.is_empty() {
+            stream.write_all(&response_bytes).unwrap();
         }
 
         if is_finished {
@@ -75,12 +78,15 @@ fn act_as_server() {
                     }) => (false, bytes),
                     Ok(HandshakeProcessResult::Completed {
                         response_bytes: bytes,
-                        remaining_bytes: _,
-                    }) => (true, bytes),
+                        completion,
+                    }) => {
+                        println!("Completion details: {:?}", completion);
+                        (true, bytes)
+                    }
                 };
 
-            if response_bytes.len() > 0 {
-                stream.write(&response_bytes).unwrap();
+            if !response_bytes.is_empty() {
+                stream.write_all(&response_bytes).unwrap();
             }
 
             if is_finished {
@@ -0,0 +1,198 @@
+use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
+use rml_rtmp::sessions::{
+    ClientSession, ClientSessionConfig, ClientSessionError, ClientSessionEvent, ClientSessionResult,
+};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the server to respond while connecting
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An RTMP client connection to the server being tested.  Bytes are read from the socket on a
+/// background thread, so the thread owning the connection can keep to its own schedule.
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Receiver<Vec<u8>>,
+    session: ClientSession,
+}
+
+impl Connection {
+    /// Opens a connection, performs the handshake, and connects to the app
+    pub fn open(address: &str, app: &str) -> Result<Connection, String> {
+        let mut stream = TcpStream::connect(address).map_err(|error| error.to_string())?;
+        stream
+            .set_nodelay(true)
+            .map_err(|error| error.to_string())?;
+
+        let remaining_bytes = perform_handshake(&mut stream)?;
+
+        let mut reader = stream.try_clone().map_err(|error| error.to_string())?;
+        let (sender, incoming) = channel();
+        thread::spawn(move || {
+            let mut buffer = [0_u8; 4096];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(bytes_read) => {
+                        if sender.send(buffer[..bytes_read].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let mut config = ClientSessionConfig::new();
+        config.tc_url = Some(format!("rtmp://{}/{}", address, app));
+
+        let (session, results) = ClientSession::new(config).map_err(session_error)?;
+        let mut connection = Connection {
+            stream,
+            incoming,
+            session,
+        };
+
+        connection.handle_results(results)?;
+        if !remaining_bytes.is_empty() {
+            let results = connection
+                .session
+                .handle_input(&remaining_bytes)
+                .map_err(session_error)?;
+
+            connection.handle_results(results)?;
+        }
+
+        let result = connection
+            .session
+            .request_connection(app.to_string())
+            .map_err(session_error)?;
+
+        connection.handle_results(vec![result])?;
+        connection.wait_for(|event| match event {
+            ClientSessionEvent::ConnectionRequestAccepted => Some(Ok(())),
+            ClientSessionEvent::ConnectionRequestRejected { description } => {
+                Some(Err(format!("Connection rejected: {}", description)))
+            }
+            _ => None,
+        })
+    }
+
+    pub fn session(&mut self) -> &mut ClientSession {
+        &mut self.session
+    }
+
+    /// Sends the packets in the results, returning any events they contain
+    pub fn handle_results(
+        &mut self,
+        results: Vec<ClientSessionResult>,
+    ) -> Result<Vec<ClientSessionEvent>, String> {
+        let mut events = Vec::new();
+        for result in results {
+            match result {
+                ClientSessionResult::OutboundResponse(packet) => self
+                    .stream
+                    .write_all(&packet.bytes)
+                    .map_err(|error| error.to_string())?,
+
+                ClientSessionResult::RaisedEvent(event) => events.push(event),
+                ClientSessionResult::UnhandleableMessageReceived(_) => (),
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Handles everything the server sends until the deadline, returning the raised events
+    pub fn poll(&mut self, deadline: Instant) -> Result<Vec<ClientSessionEvent>, String> {
+        let mut events = Vec::new();
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let bytes = match self.incoming.recv_timeout(timeout) {
+                Ok(bytes) => bytes,
+                Err(RecvTimeoutError::Timeout) => return Ok(events),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("Connection closed by the server".to_string());
+                }
+            };
+
+            let results = self.session.handle_input(&bytes).map_err(session_error)?;
+            events.extend(self.handle_results(results)?);
+        }
+    }
+
+    /// Handles everything the server sends until the check returns a result for one of the
+    /// events it raises
+    pub fn wait_for<F>(mut self, check: F) -> Result<Connection, String>
+    where
+        F: Fn(&ClientSessionEvent) -> Option<Result<(), String>>,
+    {
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        while Instant::now() < deadline {
+            for event in self.poll(Instant::now() + Duration::from_millis(50))? {
+                match check(&event) {
+                    Some(Ok(_)) => return Ok(self),
+                    Some(Err(error)) => return Err(error),
+                    None => (),
+                }
+            }
+        }
+
+        Err("Timed out waiting for the server".to_string())
+    }
+}
+
+fn perform_handshake(stream: &mut TcpStream) -> Result<Vec<u8>, String> {
+    stream
+        .set_read_timeout(Some(CONNECT_TIMEOUT))
+        .map_err(|error| error.to_string())?;
+
+    let mut handshake = Handshake::new(PeerType::Client);
+    let p0_and_p1 = handshake
+        .generate_outbound_p0_and_p1()
+        .map_err(|error| error.to_string())?;
+
+    stream
+        .write_all(&p0_and_p1)
+        .map_err(|error| error.to_string())?;
+
+    let mut buffer = [0_u8; 4096];
+    loop {
+        let bytes_read = stream
+            .read(&mut buffer)
+            .map_err(|error| error.to_string())?;
+
+        if bytes_read == 0 {
+            return Err("Connection closed during the handshake".to_string());
+        }
+
+        match handshake.process_bytes(&buffer[..bytes_read]) {
+            Ok(HandshakeProcessResult::InProgress { response_bytes }) => stream
+                .write_all(&response_bytes)
+                .map_err(|error| error.to_string())?,
+
+            Ok(HandshakeProcessResult::Completed {
+                response_bytes,
+                completion,
+            }) => {
+                stream
+                    .write_all(&response_bytes)
+                    .map_err(|error| error.to_string())?;
+
+                stream
+                    .set_read_timeout(None)
+                    .map_err(|error| error.to_string())?;
+
+                return Ok(completion.remaining_bytes.to_vec());
+            }
+
+            Err(error) => return Err(format!("Handshake failed: {}", error)),
+        }
+    }
+}
+
+fn session_error(error: ClientSessionError) -> String {
+    error.to_string()
+}
@@ -0,0 +1,107 @@
+//! Generates the synthetic H.264 video that publishers send.  Every frame carries a marker with
+//! its sequence number and the time it was sent, so players can measure latency and spot
+//! frames that never arrived.
+
+use bytes::Bytes;
+use std::time::{Duration, Instant};
+
+const MARKER_MAGIC: &[u8; 4] = b"RMLT";
+
+/// The FLV video tag header and NAL unit length that come before the marker
+const FRAME_PREFIX_LENGTH: usize = 9;
+
+/// The NAL unit header, magic, sequence number, and sent time
+const MARKER_LENGTH: usize = 1 + 4 + 4 + 8;
+
+const SPS: &[u8] = &[0x67, 0x42, 0xc0, 0x1e, 0xd9, 0x00, 0xa0, 0x47, 0xfe, 0xc8];
+const PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+
+/// The marker embedded in a received frame
+pub struct FrameMarker {
+    pub sequence: u32,
+    pub sent_at: Duration,
+}
+
+pub struct ContentGenerator {
+    epoch: Instant,
+    frame_size: usize,
+    keyframe_interval: u32,
+    frames_until_keyframe: u32,
+    next_sequence: u32,
+}
+
+impl ContentGenerator {
+    /// Creates a generator whose frames add up to the bitrate.  Sent times are measured from
+    /// the epoch, which must be shared with the players reading the frames.
+    pub fn new(epoch: Instant, bitrate_kbps: u32, fps: u32) -> ContentGenerator {
+        let frame_size = (bitrate_kbps as usize * 1000 / 8 / fps as usize)
+            .max(FRAME_PREFIX_LENGTH + MARKER_LENGTH);
+
+        ContentGenerator {
+            epoch,
+            frame_size,
+            keyframe_interval: fps * 2,
+            frames_until_keyframe: 0,
+            next_sequence: 0,
+        }
+    }
+
+    /// The AVC sequence header that has to be sent before any frames
+    pub fn sequence_header(&self) -> Bytes {
+        let mut data = vec![0x17, 0, 0, 0, 0, 1, SPS[1], SPS[2], SPS[3], 0xff, 0xe1];
+        data.extend_from_slice(&(SPS.len() as u16).to_be_bytes());
+        data.extend_from_slice(SPS);
+        data.push(1);
+        data.extend_from_slice(&(PPS.len() as u16).to_be_bytes());
+        data.extend_from_slice(PPS);
+
+        Bytes::from(data)
+    }
+
+    /// Creates the next frame, returning it along with whether it's a keyframe
+    pub fn next_frame(&mut self) -> (Bytes, bool) {
+        let sequence = self.next_sequence;
+        let is_keyframe = self.frames_until_keyframe == 0;
+        self.next_sequence += 1;
+        self.frames_until_keyframe = if is_keyframe {
+            self.keyframe_interval - 1
+        } else {
+            self.frames_until_keyframe - 1
+        };
+
+        let mut data = Vec::with_capacity(self.frame_size);
+        data.push(if is_keyframe { 0x17 } else { 0x27 });
+        data.extend_from_slice(&[1, 0, 0, 0]);
+
+        let nal_unit_length = (self.frame_size - FRAME_PREFIX_LENGTH) as u32;
+        data.extend_from_slice(&nal_unit_length.to_be_bytes());
+        data.push(if is_keyframe { 0x65 } else { 0x41 });
+        data.extend_from_slice(MARKER_MAGIC);
+        data.extend_from_slice(&sequence.to_be_bytes());
+
+        let sent_at = self.epoch.elapsed().as_micros() as u64;
+        data.extend_from_slice(&sent_at.to_be_bytes());
+        data.resize(self.frame_size, 0xab);
+
+        (Bytes::from(data), is_keyframe)
+    }
+}
+
+/// Reads the marker out of a frame created by a `ContentGenerator`
+pub fn read_marker(data: &[u8]) -> Option<FrameMarker> {
+    let marker = data.get(FRAME_PREFIX_LENGTH..FRAME_PREFIX_LENGTH + MARKER_LENGTH)?;
+    if &marker[1..5] != MARKER_MAGIC {
+        return None;
+    }
+
+    let mut sequence = [0_u8; 4];
+    sequence.copy_from_slice(&marker[5..9]);
+
+    let mut sent_at = [0_u8; 8];
+    sent_at.copy_from_slice(&marker[9..17]);
+
+    Some(FrameMarker {
+        sequence: u32::from_be_bytes(sequence),
+        sent_at: Duration::from_micros(u64::from_be_bytes(sent_at)),
+    })
+}
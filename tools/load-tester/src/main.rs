@@ -0,0 +1,220 @@
+extern crate bytes;
+extern crate rml_rtmp;
+
+mod connection;
+mod content;
+mod options;
+mod report;
+
+use connection::Connection;
+use content::{read_marker, ContentGenerator};
+use options::{Options, USAGE};
+use report::{PlayerReport, PublisherReport};
+use rml_rtmp::sessions::{ClientSessionEvent, PublishRequestType, StreamMetadata};
+use rml_rtmp::time::RtmpTimestamp;
+use std::env;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long players wait after publishers start before requesting playback, so the streams
+/// they play exist
+const PLAYER_START_DELAY: Duration = Duration::from_secs(1);
+
+/// How long players keep reading after publishers stop, so frames still in flight are counted
+const PLAYER_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => Arc::new(options),
+        Err(error) => {
+            println!("{}", error);
+            println!("{}", USAGE);
+            return;
+        }
+    };
+
+    println!(
+        "Starting {} publishers and {} players against {} for {:?}",
+        options.publishers, options.players, options.address, options.duration
+    );
+
+    let epoch = Instant::now();
+    let publishers = (0..options.publishers)
+        .map(|index| {
+            let options = options.clone();
+            thread::spawn(move || run_publisher(&options, index, epoch))
+        })
+        .collect::<Vec<_>>();
+
+    thread::sleep(PLAYER_START_DELAY);
+    let players = (0..options.players)
+        .map(|index| {
+            let options = options.clone();
+            thread::spawn(move || run_player(&options, index, epoch))
+        })
+        .collect::<Vec<_>>();
+
+    let publisher_reports = publishers
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect::<Vec<_>>();
+
+    let player_reports = players
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect::<Vec<_>>();
+
+    report::print_summary(&publisher_reports, &player_reports);
+}
+
+fn run_publisher(options: &Options, index: usize, epoch: Instant) -> PublisherReport {
+    let mut report = PublisherReport::default();
+    let started_at = Instant::now();
+    let mut connection = match start_publishing(options, index) {
+        Ok(connection) => connection,
+        Err(error) => {
+            report.error = Some(error);
+            return report;
+        }
+    };
+
+    report.connect_time = Some(started_at.elapsed());
+    if let Err(error) = publish(options, epoch, &mut connection, &mut report) {
+        report.error = Some(error);
+    }
+
+    report
+}
+
+fn start_publishing(options: &Options, index: usize) -> Result<Connection, String> {
+    let mut connection = Connection::open(&options.address, &options.app)?;
+    let result = connection
+        .session()
+        .request_publishing(options.stream_key_for(index), PublishRequestType::Live)
+        .map_err(|error| error.to_string())?;
+
+    connection.handle_results(vec![result])?;
+    connection.wait_for(|event| match event {
+        ClientSessionEvent::PublishRequestAccepted => Some(Ok(())),
+        _ => None,
+    })
+}
+
+fn publish(
+    options: &Options,
+    epoch: Instant,
+    connection: &mut Connection,
+    report: &mut PublisherReport,
+) -> Result<(), String> {
+    let mut metadata = StreamMetadata::new();
+    metadata.video_codec = Some("avc1".to_string());
+    metadata.video_frame_rate = Some(options.fps as f32);
+    metadata.video_bitrate_kbps = Some(options.bitrate_kbps);
+    metadata.encoder = Some("rtmp-load-tester".to_string());
+
+    let mut content = ContentGenerator::new(epoch, options.bitrate_kbps, options.fps);
+    let mut results = vec![connection
+        .session()
+        .publish_metadata(&metadata)
+        .map_err(|error| error.to_string())?];
+
+    results.push(
+        connection
+            .session()
+            .publish_video_data(content.sequence_header(), RtmpTimestamp::new(0), false)
+            .map_err(|error| error.to_string())?,
+    );
+
+    connection.handle_results(results)?;
+
+    let started_at = Instant::now();
+    let frame_interval = Duration::from_secs(1) / options.fps;
+    let mut next_frame_at = started_at;
+    while next_frame_at - started_at < options.duration {
+        connection.poll(next_frame_at)?;
+
+        let (frame, is_keyframe) = content.next_frame();
+        let timestamp = RtmpTimestamp::new(started_at.elapsed().as_millis() as u32);
+        let frame_length = frame.len() as u64;
+        let result = connection
+            .session()
+            .publish_video_data(frame, timestamp, !is_keyframe)
+            .map_err(|error| error.to_string())?;
+
+        connection.handle_results(vec![result])?;
+        report.frames_sent += 1;
+        report.bytes_sent += frame_length;
+        next_frame_at += frame_interval;
+    }
+
+    Ok(())
+}
+
+fn run_player(options: &Options, index: usize, epoch: Instant) -> PlayerReport {
+    let mut report = PlayerReport::default();
+    let started_at = Instant::now();
+    let mut connection = match start_playback(options, index) {
+        Ok(connection) => connection,
+        Err(error) => {
+            report.error = Some(error);
+            return report;
+        }
+    };
+
+    report.connect_time = Some(started_at.elapsed());
+
+    let stop_at = epoch + options.duration + PLAYER_START_DELAY + PLAYER_GRACE_PERIOD;
+    let mut last_sequence = None;
+    while Instant::now() < stop_at {
+        let events = match connection.poll(Instant::now() + Duration::from_millis(100)) {
+            Ok(events) => events,
+            Err(error) => {
+                report.error = Some(error);
+                break;
+            }
+        };
+
+        for event in events {
+            let data = match event {
+                ClientSessionEvent::VideoDataReceived { data, .. } => data,
+                _ => continue,
+            };
+
+            let marker = match read_marker(&data) {
+                Some(marker) => marker,
+                None => continue,
+            };
+
+            report.frames_received += 1;
+            report
+                .latencies
+                .push(epoch.elapsed().saturating_sub(marker.sent_at));
+
+            if let Some(last_sequence) = last_sequence {
+                if marker.sequence > last_sequence {
+                    report.frames_dropped += (marker.sequence - last_sequence - 1) as u64;
+                }
+            }
+
+            last_sequence = Some(marker.sequence);
+        }
+    }
+
+    report
+}
+
+fn start_playback(options: &Options, index: usize) -> Result<Connection, String> {
+    let mut connection = Connection::open(&options.address, &options.app)?;
+    let result = connection
+        .session()
+        .request_playback(options.stream_key_for(index))
+        .map_err(|error| error.to_string())?;
+
+    connection.handle_results(vec![result])?;
+    connection.wait_for(|event| match event {
+        ClientSessionEvent::PlaybackRequestAccepted => Some(Ok(())),
+        _ => None,
+    })
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+pub const USAGE: &str = "Usage: rtmp-load-tester <host:port> <app> <stream key> [options]
+
+Options:
+  --publishers <count>   Number of synthetic publishers (default 1)
+  --players <count>      Number of players (default 1)
+  --bitrate <kbps>       Video bitrate of each publisher (default 2500)
+  --fps <frames>         Frames per second of each publisher (default 30)
+  --duration <seconds>   How long to publish for (default 30)
+
+Each publisher publishes to <stream key>_<index>, and players are spread evenly across
+the published streams.  If there are no publishers the players all play <stream key>.";
+
+pub struct Options {
+    pub address: String,
+    pub app: String,
+    pub stream_key: String,
+    pub publishers: usize,
+    pub players: usize,
+    pub bitrate_kbps: u32,
+    pub fps: u32,
+    pub duration: Duration,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        if args.len() < 3 {
+            return Err("Expected a server address, app, and stream key".to_string());
+        }
+
+        let mut options = Options {
+            address: args[0].clone(),
+            app: args[1].clone(),
+            stream_key: args[2].clone(),
+            publishers: 1,
+            players: 1,
+            bitrate_kbps: 2500,
+            fps: 30,
+            duration: Duration::from_secs(30),
+        };
+
+        let mut remaining = args[3..].iter();
+        while let Some(name) = remaining.next() {
+            let value = match remaining.next() {
+                Some(value) => value,
+                None => return Err(format!("No value provided for {}", name)),
+            };
+
+            match name.as_str() {
+                "--publishers" => options.publishers = parse_number(name, value)?,
+                "--players" => options.players = parse_number(name, value)?,
+                "--bitrate" => options.bitrate_kbps = parse_number(name, value)?,
+                "--fps" => options.fps = parse_number(name, value)?,
+                "--duration" => options.duration = Duration::from_secs(parse_number(name, value)?),
+                _ => return Err(format!("Unknown option {}", name)),
+            }
+        }
+
+        if options.fps == 0 {
+            return Err("--fps must be greater than zero".to_string());
+        }
+
+        Ok(options)
+    }
+
+    /// The stream key the publisher or player with the specified index uses
+    pub fn stream_key_for(&self, index: usize) -> String {
+        if self.publishers == 0 {
+            self.stream_key.clone()
+        } else {
+            format!("{}_{}", self.stream_key, index % self.publishers)
+        }
+    }
+}
+
+fn parse_number<T: ::std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", name, value))
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// What happened to a single publisher
+#[derive(Default)]
+pub struct PublisherReport {
+    /// How long it took to connect and have publishing accepted, if it was
+    pub connect_time: Option<Duration>,
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+    pub error: Option<String>,
+}
+
+/// What happened to a single player
+#[derive(Default)]
+pub struct PlayerReport {
+    /// How long it took to connect and have playback accepted, if it was
+    pub connect_time: Option<Duration>,
+    pub frames_received: u64,
+
+    /// Frames that were skipped over in the sequence of frames received
+    pub frames_dropped: u64,
+
+    /// How long each frame took to get from the publisher to the player
+    pub latencies: Vec<Duration>,
+    pub error: Option<String>,
+}
+
+pub fn print_summary(publishers: &[PublisherReport], players: &[PlayerReport]) {
+    let publishers_connected = publishers
+        .iter()
+        .filter(|report| report.connect_time.is_some())
+        .count();
+
+    let frames_sent: u64 = publishers.iter().map(|report| report.frames_sent).sum();
+    let bytes_sent: u64 = publishers.iter().map(|report| report.bytes_sent).sum();
+    println!(
+        "Publishers: {}/{} connected ({}), {} frames ({} bytes) sent",
+        publishers_connected,
+        publishers.len(),
+        percentage(publishers_connected as u64, publishers.len() as u64),
+        frames_sent,
+        bytes_sent
+    );
+
+    let players_connected = players
+        .iter()
+        .filter(|report| report.connect_time.is_some())
+        .count();
+
+    let frames_received: u64 = players.iter().map(|report| report.frames_received).sum();
+    let frames_dropped: u64 = players.iter().map(|report| report.frames_dropped).sum();
+    println!(
+        "Players: {}/{} connected ({}), {} frames received, {} dropped ({})",
+        players_connected,
+        players.len(),
+        percentage(players_connected as u64, players.len() as u64),
+        frames_received,
+        frames_dropped,
+        percentage(frames_dropped, frames_received + frames_dropped)
+    );
+
+    let publisher_connect_times = publishers.iter().filter_map(|report| report.connect_time);
+    let connect_times = players
+        .iter()
+        .filter_map(|report| report.connect_time)
+        .chain(publisher_connect_times)
+        .collect();
+
+    print_distribution("Connect time", connect_times);
+
+    let latencies = players
+        .iter()
+        .flat_map(|report| report.latencies.iter().cloned())
+        .collect();
+
+    print_distribution("Frame latency", latencies);
+
+    let mut errors = HashMap::new();
+    let publisher_errors = publishers.iter().filter_map(|report| report.error.as_ref());
+    for error in players
+        .iter()
+        .filter_map(|report| report.error.as_ref())
+        .chain(publisher_errors)
+    {
+        *errors.entry(error.as_str()).or_insert(0) += 1;
+    }
+
+    if !errors.is_empty() {
+        println!("Errors:");
+        let mut errors = errors.into_iter().collect::<Vec<_>>();
+        errors.sort_by_key(|&(_, count)| ::std::cmp::Reverse(count));
+        for (error, count) in errors {
+            println!("  {}x {}", count, error);
+        }
+    }
+}
+
+fn print_distribution(name: &str, mut durations: Vec<Duration>) {
+    if durations.is_empty() {
+        println!("{}: no samples", name);
+        return;
+    }
+
+    durations.sort();
+    let total: Duration = durations.iter().sum();
+    let percentile = |percent: usize| durations[(durations.len() - 1) * percent / 100];
+    println!(
+        "{}: avg {:?}, p50 {:?}, p95 {:?}, p99 {:?}, max {:?}",
+        name,
+        total / durations.len() as u32,
+        percentile(50),
+        percentile(95),
+        percentile(99),
+        durations[durations.len() - 1]
+    );
+}
+
+fn percentage(count: u64, total: u64) -> String {
+    if total == 0 {
+        return "n/a".to_string();
+    }
+
+    format!("{:.1}%", count as f64 * 100.0 / total as f64)
+}
@@ -0,0 +1,105 @@
+use rml_rtmp::time::RtmpTimestamp;
+
+/// The clock rate of the RTP timestamps of video streams
+pub const VIDEO_CLOCK_RATE: u32 = 90_000;
+
+/// The clock rate of the RTP timestamps of Opus audio, regardless of the audio's sample rate
+pub const OPUS_CLOCK_RATE: u32 = 48_000;
+
+/// Maps the millisecond timestamps of an RTMP stream onto an RTP clock.
+///
+/// The first timestamp mapped becomes the clock's initial value, which should be random to
+/// follow RTP's recommendations.  Every timestamp after it is placed relative to the previous
+/// one, so the RTP clock keeps counting when RTMP timestamps wrap back to zero.  RTP
+/// timestamps wrap as well, so they are only meaningful relative to each other.
+///
+/// # Examples
+/// ```
+/// # extern crate rml_rtmp;
+/// # extern crate rml_webrtc;
+/// use rml_rtmp::time::RtmpTimestamp;
+/// use rml_webrtc::{RtpClock, VIDEO_CLOCK_RATE};
+///
+/// # fn main() {
+/// let mut clock = RtpClock::new(VIDEO_CLOCK_RATE, 1000);
+///
+/// assert_eq!(clock.map(RtmpTimestamp::new(u32::MAX)), 1000);
+/// assert_eq!(clock.map(RtmpTimestamp::new(9)), 1000 + 900);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RtpClock {
+    clock_rate: u32,
+    initial_value: u32,
+    last_timestamp: Option<RtmpTimestamp>,
+    elapsed_milliseconds: i64,
+}
+
+impl RtpClock {
+    /// Creates a clock running at the specified rate in hertz, which starts at the initial value
+    pub fn new(clock_rate: u32, initial_value: u32) -> RtpClock {
+        RtpClock {
+            clock_rate,
+            initial_value,
+            last_timestamp: None,
+            elapsed_milliseconds: 0,
+        }
+    }
+
+    /// The rate of the clock, in hertz
+    pub fn clock_rate(&self) -> u32 {
+        self.clock_rate
+    }
+
+    /// Returns the RTP timestamp of an RTMP timestamp
+    pub fn map(&mut self, timestamp: RtmpTimestamp) -> u32 {
+        if let Some(last) = self.last_timestamp {
+            if timestamp >= last {
+                self.elapsed_milliseconds += (timestamp - last).value as i64;
+            } else {
+                self.elapsed_milliseconds -= (last - timestamp).value as i64;
+            }
+        }
+
+        self.last_timestamp = Some(timestamp);
+        self.offset(self.elapsed_milliseconds)
+    }
+
+    /// Returns the RTP timestamp of the presentation time of a video frame, which is the RTMP
+    /// timestamp plus the frame's composition time offset
+    pub fn map_presentation(&mut self, timestamp: RtmpTimestamp, composition_time: i32) -> u32 {
+        self.map(timestamp);
+        self.offset(self.elapsed_milliseconds + composition_time as i64)
+    }
+
+    fn offset(&self, milliseconds: i64) -> u32 {
+        let ticks = milliseconds * self.clock_rate as i64 / 1000;
+        self.initial_value.wrapping_add(ticks as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtp_timestamps_wrap_with_clock() {
+        let mut clock = RtpClock::new(OPUS_CLOCK_RATE, u32::MAX - 47);
+
+        assert_eq!(clock.map(RtmpTimestamp::new(500)), u32::MAX - 47);
+        assert_eq!(clock.map(RtmpTimestamp::new(501)), 0);
+        assert_eq!(clock.map(RtmpTimestamp::new(499)), u32::MAX - 95);
+    }
+
+    #[test]
+    fn presentation_time_includes_composition_offset() {
+        let mut clock = RtpClock::new(VIDEO_CLOCK_RATE, 0);
+        clock.map(RtmpTimestamp::new(1000));
+
+        assert_eq!(
+            clock.map_presentation(RtmpTimestamp::new(1040), 80),
+            120 * 90
+        );
+        assert_eq!(clock.map(RtmpTimestamp::new(1080)), 80 * 90);
+    }
+}
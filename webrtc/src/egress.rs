@@ -0,0 +1,338 @@
+use bytes::Bytes;
+use clock::{RtpClock, OPUS_CLOCK_RATE, VIDEO_CLOCK_RATE};
+use rml_flv::codecs::AvcDecoderConfig;
+use rml_flv::{
+    AacPacketType, AudioTagHeader, AvcPacketType, SoundFormat, VideoCodec, VideoTagHeader,
+};
+use rml_rtmp::time::RtmpTimestamp;
+use sink::{EgressAudioFrame, EgressVideoFrame, OpusTranscoder, WebRtcSink};
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// The number of samples in each 20 millisecond Opus packet
+const OPUS_PACKET_SAMPLES: u32 = OPUS_CLOCK_RATE / 50;
+
+/// How far the audio can drift from the RTMP timestamps before the Opus timestamps are
+/// resynchronized with them
+const MAX_AUDIO_DRIFT: u32 = OPUS_CLOCK_RATE / 10;
+
+struct VideoConfig {
+    nal_unit_length_size: usize,
+    parameter_sets: Vec<u8>,
+}
+
+/// Repackages the audio and video of an RTMP stream and sends it to a `WebRtcSink`.
+///
+/// The payloads of each RTMP audio and video message (as raised by a `ServerSession` or a
+/// `StreamHub` subscription) are passed to `push_video()` and `push_audio()`.  Video is held
+/// back until a keyframe arrives, since viewers can't decode anything before it, and audio is
+/// transcoded to Opus with contiguous timestamps so that jitter in the RTMP timestamps doesn't
+/// cause gaps in playback.
+///
+/// Errors returned by the sink are passed back to the caller, and the frame that caused them
+/// is lost.
+pub struct WebRtcEgress<S: WebRtcSink, T: OpusTranscoder> {
+    sink: S,
+    transcoder: T,
+    video_config: Option<VideoConfig>,
+    video_clock: RtpClock,
+    has_sent_keyframe: bool,
+    audio_clock: RtpClock,
+    is_audio_configured: bool,
+    next_audio_timestamp: Option<u32>,
+}
+
+impl<S: WebRtcSink, T: OpusTranscoder> WebRtcEgress<S, T> {
+    /// Creates an egress that sends to the specified sink, with RTP clocks starting at random
+    /// values
+    pub fn new(sink: S, transcoder: T) -> WebRtcEgress<S, T> {
+        WebRtcEgress::with_clocks(
+            sink,
+            transcoder,
+            RtpClock::new(VIDEO_CLOCK_RATE, rand::random()),
+            RtpClock::new(OPUS_CLOCK_RATE, rand::random()),
+        )
+    }
+
+    /// Creates an egress that uses the specified clocks for its video and audio timestamps
+    pub fn with_clocks(
+        sink: S,
+        transcoder: T,
+        video_clock: RtpClock,
+        audio_clock: RtpClock,
+    ) -> WebRtcEgress<S, T> {
+        WebRtcEgress {
+            sink,
+            transcoder,
+            video_config: None,
+            video_clock,
+            has_sent_keyframe: false,
+            audio_clock,
+            is_audio_configured: false,
+            next_audio_timestamp: None,
+        }
+    }
+
+    /// Repackages the payload of an RTMP video message, and sends it to the sink if it's a
+    /// frame that can be decoded
+    pub fn push_video(&mut self, data: &[u8], timestamp: RtmpTimestamp) -> Result<(), S::Error> {
+        let header = match VideoTagHeader::parse(data) {
+            Some(header) if header.codec == VideoCodec::Avc => header,
+            _ => return Ok(()),
+        };
+
+        match header.avc_packet_type {
+            Some(AvcPacketType::SequenceHeader) => {
+                if let Ok(config) = AvcDecoderConfig::parse(&data[5..]) {
+                    let mut parameter_sets = Vec::new();
+                    let sets = config
+                        .sequence_parameter_sets
+                        .iter()
+                        .chain(config.picture_parameter_sets.iter());
+
+                    for set in sets {
+                        parameter_sets.extend_from_slice(&START_CODE);
+                        parameter_sets.extend_from_slice(set);
+                    }
+
+                    self.video_config = Some(VideoConfig {
+                        nal_unit_length_size: config.nal_unit_length_size as usize,
+                        parameter_sets,
+                    });
+                }
+
+                return Ok(());
+            }
+
+            Some(AvcPacketType::Nalu) => (),
+            _ => return Ok(()),
+        }
+
+        let is_keyframe = header.is_keyframe();
+        let rtp_timestamp = self
+            .video_clock
+            .map_presentation(timestamp, header.composition_time);
+
+        let config = match self.video_config {
+            Some(ref config) => config,
+            None => return Ok(()),
+        };
+
+        if !is_keyframe && !self.has_sent_keyframe {
+            return Ok(());
+        }
+
+        let mut frame = Vec::with_capacity(data.len() + config.parameter_sets.len());
+        if is_keyframe {
+            frame.extend_from_slice(&config.parameter_sets);
+        }
+
+        let mut remaining = &data[5..];
+        let length_size = config.nal_unit_length_size;
+        while remaining.len() > length_size {
+            let length = remaining[..length_size]
+                .iter()
+                .fold(0, |length, byte| (length << 8) | *byte as usize);
+
+            let end = (length_size + length).min(remaining.len());
+            frame.extend_from_slice(&START_CODE);
+            frame.extend_from_slice(&remaining[length_size..end]);
+            remaining = &remaining[end..];
+        }
+
+        self.has_sent_keyframe = true;
+        self.sink.send_video(EgressVideoFrame {
+            data: Bytes::from(frame),
+            rtp_timestamp,
+            is_keyframe,
+        })
+    }
+
+    /// Transcodes the payload of an RTMP audio message, and sends the Opus packets it produces
+    /// to the sink
+    pub fn push_audio(&mut self, data: &[u8], timestamp: RtmpTimestamp) -> Result<(), S::Error> {
+        let header = match AudioTagHeader::parse(data) {
+            Some(header) if header.sound_format == SoundFormat::Aac => header,
+            _ => return Ok(()),
+        };
+
+        match header.aac_packet_type {
+            Some(AacPacketType::SequenceHeader) => {
+                self.transcoder.configure(&data[2..]);
+                self.is_audio_configured = true;
+                return Ok(());
+            }
+
+            Some(AacPacketType::Raw) if self.is_audio_configured => (),
+            _ => return Ok(()),
+        }
+
+        let expected_timestamp = self.audio_clock.map(timestamp);
+        let mut rtp_timestamp = match self.next_audio_timestamp {
+            Some(next) if drift(next, expected_timestamp) <= MAX_AUDIO_DRIFT => next,
+            _ => expected_timestamp,
+        };
+
+        let packets = self.transcoder.transcode(&data[2..]);
+        let mut result = Ok(());
+        for data in packets {
+            let frame = EgressAudioFrame {
+                data,
+                rtp_timestamp,
+            };
+
+            rtp_timestamp = rtp_timestamp.wrapping_add(OPUS_PACKET_SAMPLES);
+            if let Err(error) = self.sink.send_audio(frame) {
+                result = Err(error);
+            }
+        }
+
+        self.next_audio_timestamp = Some(rtp_timestamp);
+        result
+    }
+
+    /// Tells the sink the stream has ended
+    pub fn finish(&mut self) -> Result<(), S::Error> {
+        self.sink.end_of_stream()
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Returns the sink, such as after the stream has finished
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+}
+
+fn drift(first: u32, second: u32) -> u32 {
+    first.wrapping_sub(second).min(second.wrapping_sub(first))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingSink {
+        video: Vec<EgressVideoFrame>,
+        audio: Vec<EgressAudioFrame>,
+    }
+
+    impl WebRtcSink for RecordingSink {
+        type Error = ();
+
+        fn send_video(&mut self, frame: EgressVideoFrame) -> Result<(), ()> {
+            self.video.push(frame);
+            Ok(())
+        }
+
+        fn send_audio(&mut self, frame: EgressAudioFrame) -> Result<(), ()> {
+            self.audio.push(frame);
+            Ok(())
+        }
+
+        fn end_of_stream(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    /// Returns one 20ms packet for every other AAC frame, like an encoder that needs more
+    /// input than each frame provides
+    struct HalvingTranscoder {
+        has_pending_frame: bool,
+    }
+
+    impl OpusTranscoder for HalvingTranscoder {
+        fn configure(&mut self, _audio_specific_config: &[u8]) {}
+
+        fn transcode(&mut self, aac_frame: &[u8]) -> Vec<Bytes> {
+            self.has_pending_frame = !self.has_pending_frame;
+            if !self.has_pending_frame {
+                vec![Bytes::copy_from_slice(aac_frame)]
+            } else {
+                Vec::new()
+            }
+        }
+    }
+
+    fn new_egress() -> WebRtcEgress<RecordingSink, HalvingTranscoder> {
+        WebRtcEgress::with_clocks(
+            RecordingSink::default(),
+            HalvingTranscoder {
+                has_pending_frame: false,
+            },
+            RtpClock::new(VIDEO_CLOCK_RATE, 0),
+            RtpClock::new(OPUS_CLOCK_RATE, 0),
+        )
+    }
+
+    #[test]
+    fn video_before_first_keyframe_is_dropped() {
+        let mut egress = new_egress();
+        let sequence_header = [
+            0x17, 0, 0, 0, 0, 1, 0x42, 0xc0, 0x1e, 0xff, 0xe1, 0, 1, 0x67, 1, 0, 1, 0x68,
+        ];
+
+        egress
+            .push_video(&sequence_header, RtmpTimestamp::new(0))
+            .unwrap();
+        egress
+            .push_video(&[0x27, 1, 0, 0, 0, 0, 0, 0, 1, 0x41], RtmpTimestamp::new(0))
+            .unwrap();
+        egress
+            .push_video(
+                &[0x17, 1, 0, 0, 0, 0, 0, 0, 1, 0x65],
+                RtmpTimestamp::new(33),
+            )
+            .unwrap();
+        egress
+            .push_video(
+                &[0x27, 1, 0, 0, 33, 0, 0, 0, 1, 0x41],
+                RtmpTimestamp::new(66),
+            )
+            .unwrap();
+
+        let video = &egress.sink().video;
+        assert_eq!(video.len(), 2);
+        assert_eq!(
+            &video[0].data[..],
+            &[0, 0, 0, 1, 0x67, 0, 0, 0, 1, 0x68, 0, 0, 0, 1, 0x65]
+        );
+        assert_eq!(video[0].rtp_timestamp, 33 * 90);
+        assert_eq!(&video[1].data[..], &[0, 0, 0, 1, 0x41]);
+        assert_eq!(video[1].rtp_timestamp, 99 * 90);
+        assert!(!video[1].is_keyframe);
+    }
+
+    #[test]
+    fn opus_timestamps_are_contiguous_until_audio_drifts() {
+        let mut egress = new_egress();
+        egress
+            .push_audio(&[0xaf, 0, 0x12, 0x10], RtmpTimestamp::new(0))
+            .unwrap();
+
+        // Two 23ms AAC frames become one 20ms Opus packet, so the packets fall behind the RTMP
+        // timestamps until they've drifted far enough to be resynchronized
+        for index in 0..12 {
+            let timestamp = RtmpTimestamp::new(index * 23);
+            egress
+                .push_audio(&[0xaf, 1, index as u8], timestamp)
+                .unwrap();
+        }
+
+        let timestamps = egress
+            .sink()
+            .audio
+            .iter()
+            .map(|frame| frame.rtp_timestamp)
+            .collect::<Vec<_>>();
+
+        assert_eq!(timestamps, vec![0, 960, 1920, 7728, 8688, 9648]);
+    }
+}
@@ -0,0 +1,101 @@
+//! This crate contains experimental building blocks for sending streams published over RTMP to
+//! WebRTC viewers, most commonly by pushing them to a WHIP (WebRTC-HTTP Ingestion Protocol)
+//! endpoint of a media server or CDN.
+//!
+//! WebRTC needs a full network stack (ICE, DTLS, SRTP and congestion control) that doesn't fit
+//! the sans-IO design of this repository, so this crate doesn't implement it.  Instead it does
+//! the work that's specific to RTMP sources, and leaves the transport to a `WebRtcSink`
+//! implemented on top of whichever WebRTC library the application uses:
+//!
+//! * H.264 video is converted from the length prefixed NAL units RTMP carries into Annex B
+//!   access units, with the SPS and PPS repeated before every keyframe so viewers can start
+//!   decoding from any of them.
+//! * Browsers don't decode AAC, so audio is passed to an `OpusTranscoder` supplied by the
+//!   application, and the Opus packets it produces are sent instead.
+//! * RTMP timestamps are mapped onto the 90kHz RTP clock used for video and the 48kHz clock
+//!   used for Opus by `RtpClock`, which keeps counting through RTMP timestamp wrapping.
+//!
+//! The sink is also responsible for signaling, which for WHIP means POSTing the peer
+//! connection's SDP offer to the endpoint and applying the answer it returns.  Only H.264
+//! video is supported, and since WebRTC has no way to reorder frames, publishers should not use
+//! B-frames.
+//!
+//! # Examples
+//! ```
+//! extern crate bytes;
+//! extern crate rml_rtmp;
+//! extern crate rml_webrtc;
+//!
+//! use bytes::Bytes;
+//! use rml_rtmp::time::RtmpTimestamp;
+//! use rml_webrtc::{EgressAudioFrame, EgressVideoFrame, OpusTranscoder, WebRtcEgress, WebRtcSink};
+//!
+//! struct PeerConnection {
+//!     video_frames: Vec<EgressVideoFrame>,
+//! }
+//!
+//! impl WebRtcSink for PeerConnection {
+//!     type Error = ();
+//!
+//!     fn send_video(&mut self, frame: EgressVideoFrame) -> Result<(), ()> {
+//!         // Packetize and send the frame on the peer connection's video track
+//!         self.video_frames.push(frame);
+//!         Ok(())
+//!     }
+//!
+//!     fn send_audio(&mut self, _frame: EgressAudioFrame) -> Result<(), ()> {
+//!         Ok(())
+//!     }
+//!
+//!     fn end_of_stream(&mut self) -> Result<(), ()> {
+//!         // Send a DELETE to the WHIP session's resource url
+//!         Ok(())
+//!     }
+//! }
+//!
+//! struct SilentTranscoder;
+//!
+//! impl OpusTranscoder for SilentTranscoder {
+//!     fn configure(&mut self, _audio_specific_config: &[u8]) {}
+//!
+//!     fn transcode(&mut self, _aac_frame: &[u8]) -> Vec<Bytes> {
+//!         Vec::new()
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let sink = PeerConnection { video_frames: Vec::new() };
+//! let mut egress = WebRtcEgress::new(sink, SilentTranscoder);
+//!
+//! let sequence_header = [
+//!     0x17, 0, 0, 0, 0, 0x01, 0x42, 0xc0, 0x1e, 0xff, 0xe1, 0x00, 0x04, 0x67, 0x42, 0xc0,
+//!     0x1e, 0x01, 0x00, 0x02, 0x68, 0xeb,
+//! ];
+//!
+//! let keyframe = [0x17, 1, 0, 0, 0, 0, 0, 0, 2, 0x65, 0x88];
+//!
+//! egress.push_video(&sequence_header, RtmpTimestamp::new(0)).unwrap();
+//! egress.push_video(&keyframe, RtmpTimestamp::new(0)).unwrap();
+//!
+//! let frame = &egress.sink().video_frames[0];
+//! assert!(frame.is_keyframe);
+//! assert_eq!(&frame.data[..], &[
+//!     0, 0, 0, 1, 0x67, 0x42, 0xc0, 0x1e,
+//!     0, 0, 0, 1, 0x68, 0xeb,
+//!     0, 0, 0, 1, 0x65, 0x88,
+//! ][..]);
+//! # }
+//! ```
+
+extern crate bytes;
+extern crate rand;
+extern crate rml_flv;
+extern crate rml_rtmp;
+
+mod clock;
+mod egress;
+mod sink;
+
+pub use clock::{RtpClock, OPUS_CLOCK_RATE, VIDEO_CLOCK_RATE};
+pub use egress::WebRtcEgress;
+pub use sink::{EgressAudioFrame, EgressVideoFrame, OpusTranscoder, WebRtcSink};
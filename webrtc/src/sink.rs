@@ -0,0 +1,56 @@
+use bytes::Bytes;
+
+/// An H.264 access unit ready to be packetized into RTP
+#[derive(PartialEq, Debug, Clone)]
+pub struct EgressVideoFrame {
+    /// The frame's NAL units in Annex B format, each preceded by a 4 byte start code
+    pub data: Bytes,
+
+    /// The presentation time of the frame on the 90kHz video clock
+    pub rtp_timestamp: u32,
+
+    /// If the frame is an IDR frame, which is preceded by the stream's SPS and PPS
+    pub is_keyframe: bool,
+}
+
+/// An Opus packet ready to be sent as a single RTP packet
+#[derive(PartialEq, Debug, Clone)]
+pub struct EgressAudioFrame {
+    pub data: Bytes,
+
+    /// The time of the packet's first sample on the 48kHz Opus clock
+    pub rtp_timestamp: u32,
+}
+
+/// Where a `WebRtcEgress` sends the media it repackages.
+///
+/// This is implemented on top of a WebRTC library, by packetizing each frame onto the peer
+/// connection's video and audio tracks.  The implementation also owns signaling, so for WHIP
+/// it creates the peer connection's offer, POSTs it to the endpoint and applies the answer
+/// before media is sent, and sends a DELETE to the session's resource when the stream ends.
+pub trait WebRtcSink {
+    type Error;
+
+    /// Sends a video frame on the video track
+    fn send_video(&mut self, frame: EgressVideoFrame) -> Result<(), Self::Error>;
+
+    /// Sends an Opus packet on the audio track
+    fn send_audio(&mut self, frame: EgressAudioFrame) -> Result<(), Self::Error>;
+
+    /// Called when the RTMP stream has ended, so the WebRTC session can be torn down
+    fn end_of_stream(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Converts AAC audio to Opus, which is the only audio codec every browser can play over
+/// WebRTC.  This is usually implemented by decoding with an AAC library and encoding with
+/// libopus.
+pub trait OpusTranscoder {
+    /// Sets up the decoder with the AudioSpecificConfig from the stream's AAC sequence header.
+    /// This is called again if the publisher sends a new sequence header.
+    fn configure(&mut self, audio_specific_config: &[u8]);
+
+    /// Decodes a raw AAC frame and returns the Opus packets that are ready.  Each packet must
+    /// hold 20 milliseconds of audio, but packets don't have to line up with the AAC frames, so
+    /// any number of packets can be returned for each frame.
+    fn transcode(&mut self, aac_frame: &[u8]) -> Vec<Bytes>;
+}